@@ -1,7 +1,19 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use project_gateway::config::AppConfig;
-use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use project_gateway::{
+    config::AppConfig,
+    memory::expiring::{ExpiringMap, SWEEP_BUDGET},
+    middleware::canary::decision::{decide, RequestAttributes},
+    mirror::tee::tee,
+    upstream::{Upstream, UpstreamPool},
+};
+use std::{
+    convert::Infallible,
+    time::{Duration, Instant},
+};
 use tokio::runtime::Runtime;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
 
 fn config_loading_benchmark(c: &mut Criterion) {
     c.bench_function("config_loading", |b| {
@@ -31,10 +43,102 @@ fn uuid_generation_benchmark(c: &mut Criterion) {
     });
 }
 
+fn routing_decision_benchmark(c: &mut Criterion) {
+    let mut config = AppConfig::load().unwrap().canary_rollout;
+    config.rollout_percentage = 50.0;
+    let attributes = RequestAttributes {
+        trigger_header: Some("canary"),
+        sticky_sample: None,
+        sticky_cookie: None,
+    };
+
+    c.bench_function("routing_decision", |b| {
+        b.iter(|| black_box(decide(black_box(&attributes), &config, black_box(0.42))))
+    });
+}
+
+/// One sweep tick over a map where every entry has expired: the most work a
+/// tick can do, and how long it can hold shard locks.
+fn expiring_map_sweep_benchmark(c: &mut Criterion) {
+    let start = Instant::now();
+    let later = start + Duration::from_secs(2);
+    let expired_map = || {
+        let map = ExpiringMap::new("bench", 100_000);
+        for client in 0..10_000u32 {
+            map.insert_at(client, client, Duration::from_secs(1), start);
+        }
+        map
+    };
+
+    c.bench_function("expiring_map_sweep_tick", |b| {
+        b.iter_batched_ref(
+            expired_map,
+            |map| black_box(map.sweep_at(later, SWEEP_BUDGET)),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+/// Draining a 4 MiB body of 16 KiB chunks as a client would, with and
+/// without the mirror comparison's tee. The digest is computed on its own
+/// task, so only the tee's cost on the client's path is measured.
+fn response_tee_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let chunk = Bytes::from(vec![b'x'; 16 * 1024]);
+    let body = move || {
+        let chunks = std::iter::repeat_n(chunk.clone(), 256).map(Ok::<_, Infallible>);
+        Body::from_stream(futures::stream::iter(chunks))
+    };
+
+    let plain = body.clone();
+    c.bench_function("response_streaming_plain", |b| {
+        b.to_async(&rt).iter(|| async { black_box(to_bytes(plain(), usize::MAX).await.unwrap().len()) })
+    });
+    c.bench_function("response_streaming_tee", |b| {
+        b.to_async(&rt).iter(|| async {
+            let (teed, reader) = tee(body(), 64 * 1024, None);
+            tokio::spawn(reader.finish());
+            black_box(to_bytes(teed, usize::MAX).await.unwrap().len())
+        })
+    });
+}
+
+/// A request to a local legacy upstream through the shared pooled client,
+/// against one through a client built for the request, which pays for a
+/// new connection every time.
+fn legacy_request_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let legacy = rt.block_on(async {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&server).await;
+        server
+    });
+    let url = format!("{}/api/v1/users", legacy.uri());
+    let pool = UpstreamPool::new(&AppConfig::load().unwrap().http_client);
+
+    c.bench_function("legacy_request_shared_client", |b| {
+        b.to_async(&rt).iter(|| async {
+            let _permit = pool.acquire(&url).await;
+            let response = pool.request(Upstream::Legacy, reqwest::Method::GET, &url).send().await.unwrap();
+            black_box(response.bytes().await.unwrap())
+        })
+    });
+    c.bench_function("legacy_request_new_client", |b| {
+        b.to_async(&rt).iter(|| async {
+            let response = reqwest::Client::new().get(&url).send().await.unwrap();
+            black_box(response.bytes().await.unwrap())
+        })
+    });
+}
+
 criterion_group!(
     benches,
     config_loading_benchmark,
     json_serialization_benchmark,
-    uuid_generation_benchmark
+    uuid_generation_benchmark,
+    routing_decision_benchmark,
+    expiring_map_sweep_benchmark,
+    response_tee_benchmark,
+    legacy_request_benchmark
 );
 criterion_main!(benches);
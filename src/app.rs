@@ -0,0 +1,332 @@
+use anyhow::Result;
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    response::Json,
+    routing::{get, post, put},
+    Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::CorsLayer,
+    compression::CompressionLayer,
+    trace::TraceLayer,
+};
+use utoipa::ToSchema;
+
+use crate::{docs, gatekeeper, metrics, middleware, routes, AppState};
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct MirrorTestResponse {
+    pub message: String,
+    pub timestamp: String,
+    pub note: String,
+}
+
+/// Mirror test endpoint
+///
+/// Test endpoint for validating mirror functionality.
+#[utoipa::path(
+    get,
+    path = "/mirror/test",
+    tag = "testing",
+    responses(
+        (status = 200, description = "Mirror test response", body = MirrorTestResponse)
+    )
+)]
+async fn mirror_test_handler() -> Json<MirrorTestResponse> {
+    Json(MirrorTestResponse {
+        message: "Mirror test endpoint".to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        note: "This request should be mirrored if mirror mode is enabled".to_string(),
+    })
+}
+
+/// Gatekeeper status endpoint
+///
+/// Returns the current status of the gatekeeper and rollout system.
+#[utoipa::path(
+    get,
+    path = "/gatekeeper/status",
+    tag = "monitoring",
+    responses(
+        (status = 200, description = "Gatekeeper status", body = gatekeeper::GatekeeperStatus)
+    )
+)]
+async fn gatekeeper_status_handler(State(state): State<AppState>) -> Json<gatekeeper::GatekeeperStatus> {
+    let config = state.config_watcher.get_config().await;
+
+    // Mock gatekeeper status for now
+    Json(gatekeeper::GatekeeperStatus {
+        is_healthy: true,
+        current_rollout_percentage: config.canary_rollout.rollout_percentage,
+        effective_rollout_percentage: state.slow_start.effective_percentage(&config.canary_rollout),
+        slow_start: state.slow_start.status(&config.canary_rollout),
+        error_rate: 0.1,
+        latency_degradation_percent: 0.0,
+        last_check: chrono::Utc::now().timestamp() as u64,
+        rollback_triggered: false,
+        rollback_reason: None,
+        latency: state.performance_monitor.latency_decomposition(),
+        rollout_readiness: gatekeeper::rollout_readiness(&state, &config),
+        coordination: Some(state.coordinator.status().await),
+        maintenance: state.maintenance.active(),
+        scoped_rollbacks: state.scoped_rollbacks.active(),
+        latency_drift: gatekeeper::sustained_drift(&state, &config),
+        route_percentages: gatekeeper::route_percentages(&state, &config),
+    })
+}
+
+pub async fn create_app(state: AppState) -> Result<Router> {
+    // Install the Prometheus recorder before any metric handle is created
+    metrics::install_recorder();
+
+    let config = state.config_watcher.get_config().await;
+
+    let mut app = Router::new()
+        // Health endpoints
+        .route("/health", get(routes::health::health))
+        .route("/api/v1/health", get(routes::health::health_detailed))
+        .route("/readyz", get(routes::health::readiness))
+
+        // User management endpoints
+        .route("/api/v1/users", get(routes::users::list_users))
+        .route("/api/v1/users", post(routes::users::create_user))
+        .route(middleware::versioning::VERSIONS_PATH, get(routes::versions::list_versions))
+
+        // Monitoring endpoints
+        .route("/gatekeeper/status", get(gatekeeper_status_handler))
+        .route("/monitoring/slo", get(routes::monitoring::slo_status))
+        .route("/monitoring/performance", get(routes::monitoring::performance))
+        .route("/monitoring/heatmap", get(routes::monitoring::heatmap))
+        .route("/metrics", get(metrics::metrics_handler))
+
+        // Admin endpoints
+        .route("/admin/config", get(routes::admin::effective_config))
+        .route("/admin/config/reload", post(routes::admin::reload_config))
+        .route("/admin/config/validate", post(routes::admin::validate_config))
+        .route("/admin/config/stage", post(routes::admin::stage_config))
+        .route("/admin/config/promote-staged", post(routes::admin::promote_staged_config))
+        .route("/admin/config/discard-staged", post(routes::admin::discard_staged_config))
+        .route("/admin/contract-report", get(routes::admin::contract_report))
+        .route("/admin/upstreams", get(routes::admin::upstreams))
+        .route("/admin/tls", get(routes::admin::tls_certificates))
+        .route("/admin/routes", get(routes::admin::routes))
+        .route("/admin/routes/match", get(routes::admin::match_route))
+        .route("/admin/mirror/status", get(routes::admin::mirror_status))
+        .route("/admin/mirror/report", post(routes::admin::mirror_report))
+        .route("/admin/notifications/status", get(routes::admin::notification_status))
+        .route("/admin/metrics/selfcheck", get(routes::admin::metrics_selfcheck))
+        .route("/admin/state/export", get(routes::admin::export_state))
+        .route("/admin/state/import", post(routes::admin::import_state))
+        .route("/admin/quotas/:key_id", get(routes::admin::quota_usage))
+        .route(
+            "/admin/rollout",
+            get(routes::admin::rollout_state).put(routes::admin::update_rollout),
+        )
+        .route("/admin/rollout/advance", post(routes::admin::advance_rollout))
+        .route("/admin/rollout/rollback", post(routes::admin::rollback_rollout))
+        .route("/admin/gatekeeper/evaluate", post(routes::admin::evaluate_gatekeeper))
+        .route("/admin/features", get(routes::admin::list_features))
+        .route("/admin/features/:name", put(routes::admin::set_feature))
+        .route(
+            "/admin/read-only",
+            get(routes::admin::read_only_status).post(routes::admin::set_read_only),
+        )
+        .route("/admin/debug/capture", post(routes::admin::start_capture))
+        .route("/admin/debug/capture/results", get(routes::admin::capture_results))
+        .route("/admin/debug/capture/export", get(routes::admin::export_capture))
+        .route("/admin/auth/failures", get(routes::admin::auth_failures))
+        .route("/admin/profiles", get(routes::admin::list_profiles))
+        .route("/admin/profiles/:id", get(routes::admin::get_profile))
+
+        // Testing endpoints
+        .route("/mirror/test", get(mirror_test_handler));
+
+    // Swagger UI and OpenAPI documentation, also under the public base path;
+    // not mounted at all when disabled
+    if config.docs.enabled {
+        app = app.merge(docs::create_swagger_router(&state, &config.server.base_path()));
+    }
+
+    // Add middleware stack
+    app = app.layer(
+        ServiceBuilder::new()
+            .layer(CorsLayer::permissive())
+            .layer(CompressionLayer::new())
+    );
+
+    // Bounds handlers by `server.timeout`, read per request so it follows
+    // reloads; the legacy proxy bounds its call, body included, by
+    // `canary_rollout.legacy_timeout`
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::timeout::timeout_middleware,
+    ));
+
+    // Add canary routing middleware if enabled
+    if config.canary_rollout.enabled {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::canary::canary_routing_middleware,
+        ));
+    }
+
+    // Mirroring is always layered; it checks the config and runtime
+    // overrides per request so it can be switched without a restart
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::mirror::mirror_middleware,
+    ));
+
+    // Routes under maintenance answer 503 before anything is mirrored or
+    // proxied
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::maintenance::maintenance_middleware,
+    ));
+
+    // Writes are turned away in read-only mode ahead of maintenance, so
+    // neither is mirrored nor proxied
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::read_only::read_only_middleware,
+    ));
+
+    // Daily and monthly API key quotas; inside the rate limiter so requests
+    // it turns away don't count
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::quota::quota_middleware,
+    ));
+
+    // Per-client concurrency caps; inside auth so JWT subjects identify clients
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::rate_limit::rate_limit_middleware,
+    ));
+
+    // Authenticate before anything is mirrored or proxied, then check the
+    // route's authorization against the claims
+    if config.middleware.auth.enabled {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::authorization::authorization_middleware,
+        ));
+        app = app.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::auth::auth_middleware,
+        ));
+    }
+
+    // Cross-site checks run ahead of auth so forged requests are never
+    // authenticated, let alone proxied
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::csrf::csrf_middleware,
+    ));
+
+    // Docs and metrics credentials are independent of JWT auth, so they're
+    // asked for whether or not auth is mounted
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::basic_auth::basic_auth_middleware,
+    ));
+
+    // Always layered, like mirroring, so the request_logging feature
+    // override can switch it on without a restart; it passes requests
+    // straight through while off
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::logging::logging_middleware,
+    ));
+
+    // SLOs count what clients got, including auth and rate-limit rejections
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::slo::slo_middleware,
+    ));
+
+    // Sampling decides on what clients got, like the SLOs
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::trace_sampling::trace_sampling_middleware,
+    ));
+
+    // Outside logging so it can reuse the breakdown the access log took
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::timing::server_timing_middleware,
+    ));
+
+    // Per-route debug capture sees what the client sent and received
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::capture::capture_middleware,
+    ));
+
+    // Abandoned requests are noticed outside everything that records them
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::cancellation::cancellation_middleware,
+    ));
+
+    // Oversized bodies are turned away, or cut off, before anything buffers,
+    // mirrors or proxies them. The limit here replaces axum's own, which
+    // would otherwise cap route overrides at 2 MB for the Rust handlers.
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::body_limit::body_limit_middleware,
+    ));
+    app = app.layer(DefaultBodyLimit::disable());
+
+    // Oversized headers are turned away before anything buffers or logs them
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::header_limits::header_limits_middleware,
+    ));
+
+    // Shed load before any other work is done for the request; probes are
+    // let through so the gateway isn't restarted for being busy
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::load_shedding::load_shedding_middleware,
+    ));
+
+    // Blocked addresses are turned away before they take a load-shedding
+    // slot
+    app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::ip_filter::ip_filter_middleware,
+    ));
+
+    // Profiles cover every layer but the byte counting that starts the clock
+    #[cfg(feature = "profiling")]
+    {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::profiling::profiling_middleware,
+        ));
+    }
+
+    // Count request/response bytes outermost so every variant is measured
+    app = app.layer(axum::middleware::from_fn(
+        middleware::recording::recording_middleware,
+    ));
+
+    let versioned = ServiceBuilder::new()
+        // Every request gets its id first, so rejections at any layer carry
+        // it, and its span covers every layer, proxied requests included
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::request_id::request_id_middleware,
+        ))
+        .layer(TraceLayer::new_for_http().make_span_with(middleware::request_id::make_span))
+        // Versions are negotiated ahead of routing, since a version picked
+        // by header or default moves the request onto that version's routes
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::versioning::versioning_middleware,
+        ))
+        .service(app.with_state(state));
+    Ok(Router::new().fallback_service(versioned))
+}
@@ -0,0 +1,128 @@
+//! What the listener serves while the gateway is still starting.
+//!
+//! `main` binds the listener as soon as the state is built, then starts the
+//! background tasks and builds the router. Until the router is handed over
+//! with [`Bootstrap::ready`], every request gets `503` problem+json with
+//! `code: "starting_up"` from a service that touches none of the state.
+//! Those rejections are counted as they happen, so the metrics recorder is
+//! installed here rather than with the router.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    response::Response,
+    Router,
+};
+use serde_json::json;
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tower::ServiceExt;
+use tracing::info;
+
+/// Problem `code` of a request that arrived before the gateway had started.
+pub const STARTING_UP_CODE: &str = "starting_up";
+
+/// How the bootstrap window went.
+#[derive(Debug, Clone, Copy)]
+pub struct BootstrapReport {
+    /// From [`Bootstrap::new`] to the router being handed over.
+    pub duration: Duration,
+    /// Requests answered `starting_up` in that time.
+    pub rejected: u64,
+}
+
+/// Stands in for the router until it is built.
+pub struct Bootstrap {
+    started: Instant,
+    app: OnceLock<Router>,
+    rejected: AtomicU64,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bootstrap {
+    pub fn new() -> Self {
+        crate::metrics::install_recorder();
+        Self {
+            started: Instant::now(),
+            app: OnceLock::new(),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// The service to listen with: the router once it has been handed over,
+    /// `503 starting_up` until then.
+    pub fn service(self: &Arc<Self>) -> Router {
+        let bootstrap = self.clone();
+        Router::new().fallback_service(tower::service_fn(move |request: Request| {
+            let bootstrap = bootstrap.clone();
+            async move { Ok::<_, Infallible>(bootstrap.call(request).await) }
+        }))
+    }
+
+    async fn call(&self, request: Request) -> Response {
+        match self.app.get() {
+            Some(app) => app.clone().oneshot(request).await.unwrap_or_else(|never| match never {}),
+            None => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                crate::metrics::record_bootstrap_rejection();
+                starting_up()
+            }
+        }
+    }
+
+    /// Whether the router has been handed over.
+    pub fn is_ready(&self) -> bool {
+        self.app.get().is_some()
+    }
+
+    /// Hands the listener over to `app`, then records and logs how long
+    /// the window lasted and how many requests it turned away. A rejection
+    /// still in flight at the hand-over may be missing from the report, but
+    /// not from the metric. Only the first call hands over; later ones just
+    /// report.
+    pub fn ready(&self, app: Router) -> BootstrapReport {
+        let handed_over = self.app.set(app).is_ok();
+        let report = BootstrapReport {
+            duration: self.started.elapsed(),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        };
+        if handed_over {
+            crate::metrics::record_bootstrap_duration(report.duration);
+            info!(
+                duration_ms = report.duration.as_millis() as u64,
+                rejected = report.rejected,
+                "Gateway started; requests now reach the router"
+            );
+        }
+        report
+    }
+}
+
+/// 503 problem+json asking the client to retry once the gateway is up.
+fn starting_up() -> Response {
+    let problem = json!({
+        "type": "about:blank",
+        "title": "Service Unavailable",
+        "status": 503,
+        "detail": "The gateway is starting up; retry shortly",
+        "code": STARTING_UP_CODE,
+    });
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("content-type", "application/problem+json")
+        .header(header::RETRY_AFTER, "1")
+        .body(Body::from(problem.to_string()))
+        .unwrap()
+}
@@ -0,0 +1,152 @@
+//! Typed client for services that call the gateway.
+//!
+//! Requests and responses are the [`models`](crate::models) the handlers
+//! serialize, so a field changed on the server changes here too. Build with
+//! `default-features = false, features = ["client"]` to leave the server's
+//! dependencies out.
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::models::{
+    admin::{ReadOnlyRequest, ReadOnlyStatus, RollbackRequest},
+    error::{ErrorBody, ErrorResponse},
+    gatekeeper::GatekeeperStatus,
+    health::{DetailedHealthResponse, HealthResponse, ReadinessResponse},
+    monitoring::SloStatus,
+    rollout::{CoordinationStatus, RolloutUpdate},
+    users::{CreateUserRequest, CreateUserResponse, ListUsersQuery, UserListResponse},
+};
+
+/// Pins operational calls to this gateway; at a 0% rollout unpinned
+/// requests, `/admin` included, are proxied to the legacy gateway.
+const PIN_HEADER: (&str, &str) = ("X-Gateway-Version", "rust");
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("API error {status}: {message}")]
+    Api {
+        status: u16,
+        message: String,
+        /// The gateway's error envelope, when it answered with one.
+        error: Option<ErrorBody>,
+    },
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+pub struct GatewayClient {
+    server: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl GatewayClient {
+    pub fn new(server: &str, token: Option<String>) -> Self {
+        Self {
+            server: server.trim_end_matches('/').to_string(),
+            token,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// A request to the API proper, routed like any client's.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.server, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// A request to one of the gateway's own endpoints.
+    fn pinned(&self, method: Method, path: &str) -> RequestBuilder {
+        self.request(method, path).header(PIN_HEADER.0, PIN_HEADER.1)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        parse(request.send().await?).await
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, method: Method, path: &str, body: &impl Serialize) -> Result<T, ClientError> {
+        self.send(self.pinned(method, path).json(body)).await
+    }
+
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.send(self.pinned(Method::GET, "/health")).await
+    }
+
+    pub async fn detailed_health(&self) -> Result<DetailedHealthResponse, ClientError> {
+        self.send(self.pinned(Method::GET, "/api/v1/health")).await
+    }
+
+    /// Readiness, including while warm-up is still running and the gateway
+    /// answers 503.
+    pub async fn readiness(&self) -> Result<ReadinessResponse, ClientError> {
+        let response = self.pinned(Method::GET, "/readyz").send().await?;
+        if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response.json().await?);
+        }
+        parse(response).await
+    }
+
+    pub async fn list_users(&self, page: usize) -> Result<UserListResponse, ClientError> {
+        let query = ListUsersQuery { page };
+        self.send(self.request(Method::GET, "/api/v1/users").query(&query)).await
+    }
+
+    pub async fn create_user(&self, user: &CreateUserRequest) -> Result<CreateUserResponse, ClientError> {
+        self.send(self.request(Method::POST, "/api/v1/users").json(user)).await
+    }
+
+    pub async fn gatekeeper_status(&self) -> Result<GatekeeperStatus, ClientError> {
+        self.send(self.pinned(Method::GET, "/gatekeeper/status")).await
+    }
+
+    pub async fn slo_status(&self) -> Result<Vec<SloStatus>, ClientError> {
+        self.send(self.pinned(Method::GET, "/monitoring/slo")).await
+    }
+
+    pub async fn rollout_status(&self) -> Result<CoordinationStatus, ClientError> {
+        self.send(self.pinned(Method::GET, "/admin/rollout")).await
+    }
+
+    pub async fn update_rollout(&self, update: &RolloutUpdate) -> Result<CoordinationStatus, ClientError> {
+        self.send_json(Method::PUT, "/admin/rollout", update).await
+    }
+
+    pub async fn advance_rollout(&self) -> Result<CoordinationStatus, ClientError> {
+        self.send(self.pinned(Method::POST, "/admin/rollout/advance")).await
+    }
+
+    pub async fn rollback_rollout(&self, request: &RollbackRequest) -> Result<CoordinationStatus, ClientError> {
+        self.send_json(Method::POST, "/admin/rollout/rollback", request).await
+    }
+
+    pub async fn read_only_status(&self) -> Result<ReadOnlyStatus, ClientError> {
+        self.send(self.pinned(Method::GET, "/admin/read-only")).await
+    }
+
+    pub async fn set_read_only(&self, enabled: bool) -> Result<ReadOnlyStatus, ClientError> {
+        self.send_json(Method::POST, "/admin/read-only", &ReadOnlyRequest { enabled }).await
+    }
+}
+
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let error = serde_json::from_str::<ErrorResponse>(&body).ok().map(|envelope| envelope.error);
+    let message = match &error {
+        Some(error) => error.message.clone(),
+        None if !body.trim().is_empty() => body.trim().to_string(),
+        None => status.canonical_reason().unwrap_or("request failed").to_lowercase(),
+    };
+    Err(ClientError::Api {
+        status: status.as_u16(),
+        message,
+        error,
+    })
+}
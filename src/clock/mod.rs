@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{
+    upstream::{Upstream, UpstreamPool},
+    AppState,
+};
+
+pub use crate::models::health::ClockSkew;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The gateway's wall clock. Schedules (maintenance windows, the mirror
+/// schedule) and token expiry read the time from here, so the skew measured
+/// against a trusted source is the skew they run with.
+pub struct Clock {
+    offset: chrono::Duration,
+    skew: RwLock<Option<ClockSkew>>,
+}
+
+impl Clock {
+    pub fn system() -> Self {
+        Self::offset_by(chrono::Duration::zero())
+    }
+
+    /// A clock running `offset` ahead of the system clock, for simulating
+    /// drift.
+    pub fn offset_by(offset: chrono::Duration) -> Self {
+        Self {
+            offset,
+            skew: RwLock::new(None),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset
+    }
+
+    /// The latest check's result, if one has succeeded.
+    pub fn skew(&self) -> Option<ClockSkew> {
+        self.skew.read().ok().and_then(|skew| skew.clone())
+    }
+
+    /// Compares this clock with the `Date` header `url` answers `HEAD` with.
+    /// The header only has whole seconds, so skew under a second or so
+    /// isn't meaningful.
+    pub async fn check(&self, upstreams: &UpstreamPool, url: &str, max_skew: Duration) -> Result<ClockSkew> {
+        let sent_at = self.now();
+        let started = Instant::now();
+        let response = upstreams
+            .request(Upstream::TimeSource, reqwest::Method::HEAD, url)
+            .timeout(CHECK_TIMEOUT)
+            .send()
+            .await
+            .with_context(|| format!("asking {} for the time", url))?;
+        let round_trip = started.elapsed();
+
+        let date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .ok_or_else(|| anyhow!("{} answered without a Date header", url))?;
+        let trusted = DateTime::parse_from_rfc2822(date)
+            .with_context(|| format!("{} sent an unparseable Date header {:?}", url, date))?
+            .with_timezone(&Utc);
+
+        // The source stamped its reply somewhere within the round trip and
+        // truncated it to the second
+        let local = sent_at + chrono::Duration::from_std(round_trip / 2).unwrap_or_default();
+        let skew_seconds = (local - trusted).num_milliseconds() as f64 / 1000.0 - 0.5;
+        let skew = ClockSkew {
+            skew_seconds,
+            source: url.to_string(),
+            checked_at: Utc::now().to_rfc3339(),
+            within_tolerance: skew_seconds.abs() <= max_skew.as_secs_f64(),
+        };
+
+        crate::metrics::record_clock_skew(url, skew_seconds);
+        if let Ok(mut latest) = self.skew.write() {
+            *latest = Some(skew.clone());
+        }
+        Ok(skew)
+    }
+}
+
+/// Checks the clock against `clock.time_source_url`, or the legacy gateway
+/// without one. Skipped when neither is configured; a failed check is
+/// logged and leaves health alone.
+pub async fn check_skew(state: &AppState) -> Option<ClockSkew> {
+    let config = state.config_watcher.get_config().await;
+    let Some(source) = config.clock.time_source(&config.canary_rollout) else {
+        info!("No time source configured; skipping the clock skew check");
+        return None;
+    };
+
+    let max_skew = config.clock.max_skew.get();
+    match state.clock.check(&state.upstreams, source, max_skew).await {
+        Ok(skew) if skew.within_tolerance => {
+            info!(skew_seconds = skew.skew_seconds, source, "System clock agrees with the time source");
+            Some(skew)
+        }
+        Ok(skew) => {
+            warn!(
+                event = "clock_skew_detected",
+                skew_seconds = skew.skew_seconds,
+                max_skew_seconds = max_skew.as_secs_f64(),
+                source,
+                "System clock disagrees with the time source; tokens and schedules may be judged at the wrong time"
+            );
+            Some(skew)
+        }
+        Err(e) => {
+            warn!("Clock skew check failed: {:#}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Field-level differences between two configs.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::AppConfig;
+
+/// One field that differs. Values are shown as in [`AppConfig::redacted`],
+/// so a changed secret is listed without revealing either value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ConfigChange {
+    /// Dotted path, e.g. `middleware.rate_limiting.requests_per_minute`.
+    pub path: String,
+    /// `null` when the field is absent.
+    #[schema(value_type = Object)]
+    pub current: Value,
+    #[schema(value_type = Object)]
+    pub candidate: Value,
+}
+
+/// Fields of `candidate` that differ from `current`, in path order. Lists
+/// are compared whole.
+pub fn diff(current: &AppConfig, candidate: &AppConfig) -> Vec<ConfigChange> {
+    let (before, after) = (serde_json::to_value(current), serde_json::to_value(candidate));
+    let (Ok(before), Ok(after)) = (before, after) else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    changed_paths(&before, &after, Location::default(), &mut paths);
+
+    let (before, after) = (current.redacted(), candidate.redacted());
+    paths
+        .into_iter()
+        .map(|Location { path, pointer }| ConfigChange {
+            current: before.pointer(&pointer).cloned().unwrap_or(Value::Null),
+            candidate: after.pointer(&pointer).cloned().unwrap_or(Value::Null),
+            path,
+        })
+        .collect()
+}
+
+/// A field as a dotted path for display and a JSON pointer for lookup; map
+/// keys may themselves contain dots or slashes.
+#[derive(Default)]
+struct Location {
+    path: String,
+    pointer: String,
+}
+
+impl Location {
+    fn child(&self, key: &str) -> Self {
+        Self {
+            path: if self.path.is_empty() { key.to_string() } else { format!("{}.{}", self.path, key) },
+            pointer: format!("{}/{}", self.pointer, key.replace('~', "~0").replace('/', "~1")),
+        }
+    }
+}
+
+fn changed_paths(before: &Value, after: &Value, location: Location, paths: &mut Vec<Location>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let (before, after) = (before.get(key).unwrap_or(&Value::Null), after.get(key).unwrap_or(&Value::Null));
+                changed_paths(before, after, location.child(key), paths);
+            }
+        }
+        (before, after) if before != after => paths.push(location),
+        _ => {}
+    }
+}
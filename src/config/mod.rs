@@ -1,8 +1,25 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+use utoipa::ToSchema;
 
+use crate::util::backoff::RetryPolicy;
+
+pub mod diff;
+pub mod overlay;
+pub mod partial;
+pub mod schedule;
+pub mod staged;
+pub mod units;
+pub mod validation;
 pub mod watcher;
 
+pub use schedule::{CronSchedule, TimeWindow};
+pub use units::{ByteSize, HumanDuration};
+
+pub use validation::{ConfigIssue, ConfigValidationError, Severity};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
@@ -11,6 +28,42 @@ pub struct AppConfig {
     pub mirror: MirrorConfig,
     pub canary_rollout: CanaryRolloutConfig,
     pub routes: Vec<RouteConfig>,
+    pub middleware: MiddlewareConfig,
+    #[serde(default)]
+    pub contract_check: ContractCheckConfig,
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub profiling: ProfilingConfig,
+    #[serde(default)]
+    pub versioning: VersioningConfig,
+    #[serde(default)]
+    pub slo: SloConfig,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    #[serde(default)]
+    pub docs: DocsConfig,
+    /// Basic auth in front of the docs and metrics; off when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_basic_auth: Option<AdminBasicAuthConfig>,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// Scheduled windows during which matching routes answer 503.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    #[serde(default)]
+    pub read_only: ReadOnlyConfig,
+    #[serde(default)]
+    pub config_reload: ConfigReloadConfig,
+    #[serde(default)]
+    pub handoff: HandoffConfig,
+    /// When set, reloading this config clears runtime feature overrides.
+    #[serde(default)]
+    pub reset_overrides: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,12 +72,387 @@ pub struct CanaryRolloutConfig {
     pub rollout_percentage: f64,
     pub step: f64,
     pub max_errors: f64,
+    /// How far, in percent, Rust p99 latency may fall behind the legacy
+    /// baseline before the gatekeeper rolls back.
+    #[serde(default = "default_max_latency_degradation")]
+    pub max_latency_degradation: f64,
+    /// Whether latency degradation is judged at all.
     pub monitor_latency_p99: bool,
     pub monitor_memory_cpu: bool,
     pub trigger_header: String,
-    pub success_window_seconds: u64,
+    #[serde(alias = "success_window_seconds", deserialize_with = "units::legacy_seconds")]
+    pub success_window: HumanDuration,
+    /// After an advancement, the effective percentage ramps linearly from
+    /// the old stage to the new one over this long. Zero switches at once.
+    #[serde(
+        default = "default_slow_start",
+        alias = "slow_start_seconds",
+        deserialize_with = "units::legacy_seconds"
+    )]
+    pub slow_start: HumanDuration,
     pub legacy_gateway_url: String,
+    /// How long a proxied request may wait on the legacy gateway, queue
+    /// time included. Falls back to `server.timeout` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_timeout: Option<HumanDuration>,
     pub webhook_url: String,
+    #[serde(default)]
+    pub readiness: RolloutReadinessConfig,
+    /// Shared rollout state for multi-replica deployments. Without it each
+    /// instance keeps its own state and runs its own gatekeeper.
+    #[serde(default)]
+    pub coordination: Option<CoordinationConfig>,
+    /// The legacy gateway's own header limits, so requests it would reject
+    /// fail here with a clear 431 instead of an opaque upstream 400.
+    #[serde(default, skip_serializing_if = "UpstreamHeaderLimits::is_empty")]
+    pub legacy_header_limits: UpstreamHeaderLimits,
+    /// Client certificate identity passed to the legacy gateway, which does
+    /// its own authorization on the subject DN.
+    #[serde(default)]
+    pub client_cert_forwarding: ClientCertForwarding,
+    /// How often a route whose smoke check failed is checked again.
+    #[serde(default = "default_smoke_retry_interval")]
+    pub smoke_retry_interval: HumanDuration,
+    #[serde(default)]
+    pub scoped_rollback: ScopedRollbackConfig,
+    #[serde(default)]
+    pub latency_drift: LatencyDriftConfig,
+    /// Stamp responses with the rollout generation they were routed under,
+    /// as `X-Rollout-Generation`.
+    #[serde(default)]
+    pub generation_header: bool,
+    /// What a client's rollout assignment is keyed on, so all its requests
+    /// go to the same backend. Unset, or missing from a request, each
+    /// request is sampled on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticky_key: Option<StickyKey>,
+    /// Hashed with the sticky key. Replicas agree on assignments as long as
+    /// they share it; changing it reshuffles which clients land on Rust.
+    #[serde(default)]
+    pub sticky_seed: String,
+    /// Record each client's assignment in a cookie and honor it on later
+    /// requests, so a browser session never straddles backends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticky_cookie: Option<StickyCookieConfig>,
+    /// Rollout percentages for parts of the API that move at their own pace,
+    /// e.g. a risky path held at 1% while the rest is at 50%.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route_overrides: Vec<RolloutOverride>,
+    /// The percentage the last global rollback left the rollout at. Until
+    /// the rollout is raised past it, route overrides get no more than the
+    /// rollout percentage. Written by the gatekeeper along with
+    /// `rollout_percentage`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_hold: Option<f64>,
+}
+
+impl CanaryRolloutConfig {
+    /// The deadline of a legacy gateway call, with `server_timeout` as the
+    /// fallback.
+    pub fn legacy_timeout(&self, server_timeout: HumanDuration) -> HumanDuration {
+        self.legacy_timeout.unwrap_or(server_timeout)
+    }
+
+    /// The override covering `method path`: the longest matching prefix,
+    /// and of those one naming the method before one for any method.
+    pub fn route_override(&self, method: &str, path: &str) -> Option<&RolloutOverride> {
+        self.route_overrides
+            .iter()
+            .filter(|entry| entry.method.as_ref().is_none_or(|m| m.eq_ignore_ascii_case(method)))
+            .filter(|entry| prefix_covers(&entry.path_prefix, path))
+            .max_by_key(|entry| (trim_trailing_slash(&entry.path_prefix).len(), entry.method.is_some()))
+    }
+
+    /// Whether a global rollback holds route overrides to the rollout
+    /// percentage.
+    pub fn overrides_held(&self) -> bool {
+        self.override_hold.is_some_and(|hold| self.rollout_percentage <= hold)
+    }
+
+    /// Share of `method path` traffic sent to Rust with the rollout at
+    /// `global`.
+    pub fn percentage_for(&self, method: &str, path: &str, global: f64) -> f64 {
+        self.route_override(method, path).map_or(global, |entry| entry.percentage)
+    }
+
+    /// The `route_group` metric label of `method path`: its override's
+    /// group, or `default`.
+    pub fn route_group(&self, method: &str, path: &str) -> String {
+        self.route_override(method, path)
+            .map_or_else(|| DEFAULT_ROUTE_GROUP.to_string(), RolloutOverride::group)
+    }
+}
+
+/// Route group of traffic no override covers.
+pub const DEFAULT_ROUTE_GROUP: &str = "default";
+
+/// A rollout percentage for the requests under a path prefix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutOverride {
+    /// Matches whole segments, so `/api/v1/pay` doesn't cover
+    /// `/api/v1/payments`.
+    pub path_prefix: String,
+    /// Only requests with this method; any method when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub percentage: f64,
+}
+
+impl RolloutOverride {
+    /// How the override is named in metrics and the gatekeeper status,
+    /// e.g. `POST /api/v1/payments`.
+    pub fn group(&self) -> String {
+        match &self.method {
+            Some(method) => format!("{} {}", method.to_ascii_uppercase(), self.path_prefix),
+            None => self.path_prefix.clone(),
+        }
+    }
+}
+
+/// The cookie recording a client's rollout assignment as `rust` or
+/// `legacy`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickyCookieConfig {
+    pub name: String,
+    /// How long a browser keeps the assignment; it's rolled again after.
+    pub ttl: HumanDuration,
+    /// Marks the cookie `Secure`; turn off only for plain-HTTP testing.
+    pub secure: bool,
+    pub same_site: CookieSameSite,
+}
+
+impl Default for StickyCookieConfig {
+    fn default() -> Self {
+        Self {
+            name: "gw_backend".to_string(),
+            ttl: HumanDuration::from_secs(24 * 3600),
+            secure: true,
+            same_site: CookieSameSite::Lax,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl CookieSameSite {
+    /// The attribute's value in `Set-Cookie`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+/// A client's sticky rollout key: `client_ip`, `header:<name>` or
+/// `cookie:<name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StickyKey {
+    /// The client address, as resolved through
+    /// `middleware.rate_limiting.trusted_proxies`.
+    ClientIp,
+    /// A request header's value, e.g. a session ID. Names are lowercase.
+    Header(String),
+    /// A cookie's value.
+    Cookie(String),
+}
+
+impl std::str::FromStr for StickyKey {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        if input == "client_ip" {
+            return Ok(Self::ClientIp);
+        }
+        if let Some(name) = input.strip_prefix("header:") {
+            return match axum::http::HeaderName::from_bytes(name.as_bytes()) {
+                Ok(_) => Ok(Self::Header(name.to_ascii_lowercase())),
+                Err(_) => Err(format!("{:?} is not a valid header name", name)),
+            };
+        }
+        match input.strip_prefix("cookie:") {
+            Some(name) if !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && !b"=;,".contains(&b)) => {
+                Ok(Self::Cookie(name.to_string()))
+            }
+            Some(name) => Err(format!("{:?} is not a valid cookie name", name)),
+            None => Err(format!("{:?} is not one of client_ip, header:<name> or cookie:<name>", input)),
+        }
+    }
+}
+
+impl std::fmt::Display for StickyKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ClientIp => f.write_str("client_ip"),
+            Self::Header(name) => write!(f, "header:{}", name),
+            Self::Cookie(name) => write!(f, "cookie:{}", name),
+        }
+    }
+}
+
+impl Serialize for StickyKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StickyKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Rolling back only the route that regressed, when one route's error rate
+/// explains a breach of `max_errors` on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopedRollbackConfig {
+    pub enabled: bool,
+    /// Rust-served requests a route needs in a check interval before its
+    /// error rate is judged.
+    pub min_requests: u64,
+}
+
+impl Default for ScopedRollbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_requests: 20,
+        }
+    }
+}
+
+/// Watching each route's latency distribution for a change of shape that
+/// stays under the p99 gate, such as p50 doubling. Sustained drift holds
+/// the rollout where it is; it never rolls back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyDriftConfig {
+    pub enabled: bool,
+    pub measure: DriftMeasure,
+    /// Scores above this count as drift: a KS statistic between 0 and 1, or
+    /// a population stability index, where 0.25 is the usual alarm level.
+    pub max_score: f64,
+    /// How much recent history, per variant and route, the latest interval
+    /// is compared with.
+    pub reference_window: HumanDuration,
+    pub evaluation_interval: HumanDuration,
+    /// Consecutive drifting intervals before advancement is blocked.
+    pub sustained_intervals: u32,
+    /// Requests both the latest interval and the reference need before a
+    /// route is scored.
+    pub min_samples: u64,
+}
+
+impl Default for LatencyDriftConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            measure: DriftMeasure::Ks,
+            max_score: 0.2,
+            reference_window: HumanDuration::from_secs(30 * 60),
+            evaluation_interval: HumanDuration::from_secs(60),
+            sustained_intervals: 3,
+            min_samples: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftMeasure {
+    /// Kolmogorov-Smirnov statistic: the largest gap between the two CDFs.
+    #[default]
+    Ks,
+    /// Population stability index over the histogram buckets.
+    Psi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinationKind {
+    Redis,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinationConfig {
+    pub kind: CoordinationKind,
+    /// `redis://` or `rediss://` URL.
+    pub url: String,
+    /// Prefix for every key and channel, so several gateways can share a
+    /// Redis.
+    #[serde(default = "default_coordination_prefix")]
+    pub key_prefix: String,
+    /// How often the shared state is re-read, on top of pub/sub
+    /// invalidation.
+    #[serde(default = "default_coordination_refresh")]
+    pub refresh_interval: HumanDuration,
+    /// How long a leader keeps the gatekeeper lease without renewing it;
+    /// also how long failover can take.
+    #[serde(default = "default_leader_lease")]
+    pub leader_lease: HumanDuration,
+}
+
+fn default_coordination_prefix() -> String {
+    "project-gateway:".to_string()
+}
+
+fn default_coordination_refresh() -> HumanDuration {
+    HumanDuration::from_secs(5)
+}
+
+fn default_leader_lease() -> HumanDuration {
+    HumanDuration::from_secs(15)
+}
+
+fn default_max_latency_degradation() -> f64 {
+    10.0
+}
+
+fn default_smoke_retry_interval() -> HumanDuration {
+    HumanDuration::from_secs(30)
+}
+
+fn default_slow_start() -> HumanDuration {
+    HumanDuration::from_secs(60)
+}
+
+/// Mirror-traffic thresholds that must hold before rollout may leave 0%.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolloutReadinessConfig {
+    /// Mirrored requests needed before readiness is judged at all.
+    pub min_mirror_samples: usize,
+    /// Percentage of mirror requests that must complete without error.
+    pub min_mirror_success_rate: f64,
+    /// Percentage of mirror responses allowed to differ in status from main.
+    pub max_mismatch_rate: f64,
+    /// Allowed ratio of mirror p99 latency to main p99 latency.
+    pub max_latency_ratio: f64,
+    /// Percentage of mirror responses allowed to break their route's
+    /// response expectations (content type, size, strict JSON).
+    pub max_contract_violation_rate: f64,
+}
+
+impl Default for RolloutReadinessConfig {
+    fn default() -> Self {
+        Self {
+            min_mirror_samples: 100,
+            min_mirror_success_rate: 99.0,
+            max_mismatch_rate: 1.0,
+            max_latency_ratio: 1.5,
+            max_contract_violation_rate: 0.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,13 +460,498 @@ pub struct RouteConfig {
     pub path: String,
     pub method: String,
     pub legacy_endpoint: String,
+    /// Upstream responses must carry one of these media types (`type/*`
+    /// allowed); anything else becomes a 502.
+    #[serde(default)]
+    pub expected_content_types: Vec<String>,
+    /// Upstream bodies larger than this are aborted with a 502.
+    #[serde(default)]
+    pub max_response_bytes: Option<ByteSize>,
+    /// Overrides `server.max_body_bytes` for requests to this route, in
+    /// either direction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<ByteSize>,
+    /// Reject upstream bodies (up to 1 MiB) that are not valid JSON.
+    #[serde(default)]
+    pub strict_json: bool,
+    /// Overrides `middleware.server_timing` for this route; `false` keeps
+    /// timing details off external-facing routes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_timing: Option<bool>,
+    /// Overrides `middleware.rate_limiting.headers` for this route; `false`
+    /// keeps quota details off routes that shouldn't reveal them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_headers: Option<bool>,
+    /// Overrides `middleware.logging.sample_rate` for this route, e.g. a
+    /// lower rate for a high-traffic read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_sample_rate: Option<f64>,
+    /// Request replayed against the Rust handler before the route takes any
+    /// rollout traffic; until it passes the route stays on legacy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoke: Option<SmokeCheck>,
+    /// Whether the request may be abandoned when its client disconnects.
+    /// Routes that write should say `false`, so they run to completion
+    /// rather than be left half applied.
+    #[serde(default = "default_cancel_safe")]
+    pub cancel_safe: bool,
+    /// Claims a caller needs once authenticated; enforced while
+    /// `middleware.auth` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<RouteAuthorization>,
+}
+
+fn default_cancel_safe() -> bool {
+    true
+}
+
+/// A caller holding any one of `roles` or `scopes` is authorized.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteAuthorization {
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Granted scopes ending in `*` cover everything with that prefix, e.g.
+    /// `users:*` covers `users:write`.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl RouteAuthorization {
+    pub fn is_empty(&self) -> bool {
+        self.roles.is_empty() && self.scopes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeCheck {
+    /// Defaults to the route's method.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Values for the route's `:name` or `{name}` segments.
+    #[serde(default)]
+    pub path_params: std::collections::HashMap<String, String>,
+    /// Sent as JSON.
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+    #[serde(default = "default_smoke_status")]
+    pub expected_status: u16,
+}
+
+fn default_smoke_status() -> u16 {
+    200
+}
+
+impl SmokeCheck {
+    /// The concrete path to request for `route`, or the first parameter
+    /// without a value.
+    pub fn path(&self, route: &str) -> Result<String, String> {
+        route
+            .split('/')
+            .map(|segment| {
+                let name = segment
+                    .strip_prefix(':')
+                    .or_else(|| segment.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')));
+                match name {
+                    Some(name) => self.path_params.get(name).cloned().ok_or_else(|| name.to_string()),
+                    None => Ok(segment.to_string()),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|segments| segments.join("/"))
+    }
+}
+
+impl RouteConfig {
+    /// Whether a concrete request path fits this route's pattern, with each
+    /// `:name` or `{name}` segment matching any one segment.
+    pub fn matches_path(&self, path: &str) -> bool {
+        let pattern: Vec<&str> = self.path.split('/').collect();
+        let segments: Vec<&str> = path.split('/').collect();
+        pattern.len() == segments.len()
+            && pattern.iter().zip(&segments).all(|(expected, segment)| {
+                let is_param = expected.starts_with(':') || (expected.starts_with('{') && expected.ends_with('}'));
+                if is_param {
+                    !segment.is_empty()
+                } else {
+                    expected == segment
+                }
+            })
+    }
+
+    /// Whether upstream responses on this route are checked at all; when not,
+    /// they are relayed without inspection.
+    pub fn validates_responses(&self) -> bool {
+        !self.expected_content_types.is_empty() || self.max_response_bytes.is_some() || self.strict_json
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContractCheckConfig {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    /// Generate a sample for every documented GET route without required parameters.
+    pub auto_generate_get: bool,
+    pub samples: Vec<ContractSample>,
+}
+
+impl Default for ContractCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 300,
+            auto_generate_get: true,
+            samples: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractSample {
+    pub method: String,
+    /// Concrete request path, including any query string.
+    pub path: String,
+    /// OpenAPI path template the sample exercises; defaults to `path`.
+    #[serde(default)]
+    pub route: Option<String>,
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+}
+
+/// Settings for the shared outbound HTTP client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpClientConfig {
+    /// Concurrent connections allowed per upstream host; excess requests wait.
+    pub max_connections_per_host: usize,
+    /// Unused connections kept open per upstream host. Defaults to
+    /// `max_connections_per_host`.
+    pub max_idle_per_host: Option<usize>,
+    /// How long an unused connection stays in the pool before it's closed.
+    pub pool_idle_timeout: HumanDuration,
+    /// Time allowed to open a connection, TLS handshake included.
+    pub connect_timeout: HumanDuration,
+    /// Forward proxy for all outbound requests. Read when the client is
+    /// built, so changes need a restart.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Sent on every outbound request; `{version}` and `{instance_id}` are
+    /// filled in. Read when the client is built, like the headers below.
+    pub user_agent: String,
+    /// Headers sent on every outbound request.
+    pub default_headers: BTreeMap<String, String>,
+    /// Headers added to, or replacing, `default_headers` for one kind of
+    /// upstream: `legacy`, `mirror`, `webhooks`, `jwks`, `introspection` or
+    /// `time_source`.
+    pub upstream_headers: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub prewarm: PrewarmConfig,
+    #[serde(default)]
+    pub health_check: UpstreamHealthCheckConfig,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_host: 100,
+            max_idle_per_host: None,
+            pool_idle_timeout: HumanDuration::from_secs(90),
+            connect_timeout: HumanDuration::from_secs(5),
+            proxy: None,
+            user_agent: default_user_agent(),
+            default_headers: BTreeMap::new(),
+            upstream_headers: BTreeMap::new(),
+            prewarm: PrewarmConfig::default(),
+            health_check: UpstreamHealthCheckConfig::default(),
+        }
+    }
+}
+
+/// Connections kept open to the legacy gateway and mirror target by
+/// periodic `HEAD` probes, so a rollback doesn't pay for a burst of new
+/// connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrewarmConfig {
+    pub enabled: bool,
+    /// Warm connections kept to each upstream.
+    pub min_connections: usize,
+    /// Extra warm connections to the legacy gateway at 100% rollout, scaled
+    /// down with the rollout percentage.
+    pub rollback_headroom: usize,
+    /// How often probes are sent; keep it under the upstream's keep-alive
+    /// timeout.
+    pub interval: HumanDuration,
+    /// Probes sent per second across all upstreams, at most.
+    pub max_probes_per_second: f64,
+}
+
+impl Default for PrewarmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_connections: 2,
+            rollback_headroom: 8,
+            interval: HumanDuration::from_secs(15),
+            max_probes_per_second: 5.0,
+        }
+    }
+}
+
+impl PrewarmConfig {
+    /// Warm connections wanted to the legacy gateway at `rollout_percentage`.
+    pub fn legacy_target(&self, rollout_percentage: f64) -> usize {
+        let headroom = self.rollback_headroom as f64 * rollout_percentage.clamp(0.0, 100.0) / 100.0;
+        self.min_connections + headroom.ceil() as usize
+    }
+
+    /// Probes one round may send.
+    pub fn probes_per_round(&self) -> usize {
+        (self.max_probes_per_second * self.interval.get().as_secs_f64()).floor() as usize
+    }
+}
+
+fn default_user_agent() -> String {
+    "project-gateway/{version} ({instance_id})".to_string()
+}
+
+/// Periodic probes of the legacy gateway and mirror target, reported under
+/// `upstream_services` in `GET /api/v1/health`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamHealthCheckConfig {
+    pub enabled: bool,
+    /// Probed with `HEAD`, or with a one-byte ranged `GET` on upstreams
+    /// that refuse `HEAD`.
+    pub path: String,
+    pub interval: HumanDuration,
+    pub timeout: HumanDuration,
+}
+
+impl Default for UpstreamHealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "/status".to_string(),
+            interval: HumanDuration::from_secs(10),
+            timeout: HumanDuration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// `http://` or `https://` URL of the proxy.
+    pub url: String,
+    /// Hosts reached directly: `*`, IPs, CIDRs (`10.0.0.0/8`), or domains,
+    /// which also match their subdomains.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// File holding the password (e.g. a mounted secret); takes precedence
+    /// over `password`.
+    #[serde(default)]
+    pub password_file: Option<String>,
+}
+
+/// How user identifiers are treated in logs, audit entries, and metric
+/// labels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    /// Replace user IDs with a keyed hash. Only disable in development.
+    pub pseudonymize_user_ids: bool,
+    /// Per-deployment HMAC key. Keep it stable so pseudonyms stay comparable
+    /// across restarts and replicas.
+    pub salt: Option<String>,
+    /// File holding the salt (e.g. a mounted secret); takes precedence over
+    /// `salt`.
+    pub salt_file: Option<String>,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            pseudonymize_user_ids: true,
+            salt: None,
+            salt_file: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
-    pub timeout_seconds: u64,
+    /// Bare numbers are read as seconds (the old `timeout_seconds` form).
+    #[serde(alias = "timeout_seconds", deserialize_with = "units::legacy_seconds")]
+    pub timeout: HumanDuration,
+    /// How long a request may wait for admission (e.g. an upstream connection
+    /// slot) before being rejected with 503. Queue time is deducted from the
+    /// upstream call's deadline. Bare numbers are read as milliseconds.
+    #[serde(
+        default = "default_queue_timeout",
+        alias = "queue_timeout_ms",
+        deserialize_with = "units::legacy_millis"
+    )]
+    pub queue_timeout: HumanDuration,
+    /// Path prefix the gateway is served under by the ingress, e.g. `/gateway`.
+    #[serde(default)]
+    pub public_base_path: String,
+    /// Externally visible origin, e.g. `https://api.example.com`. Used for
+    /// the OpenAPI `servers` list.
+    #[serde(default)]
+    pub public_url: Option<String>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Total size of all request header names and values.
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: ByteSize,
+    /// Number of request header lines. The HTTP server itself refuses more
+    /// than 100.
+    #[serde(default = "default_max_header_count")]
+    pub max_header_count: usize,
+    /// Tighter size limits for individual headers, by lowercase name, e.g.
+    /// `cookie: 16KiB`.
+    #[serde(default)]
+    pub header_size_limits: BTreeMap<String, ByteSize>,
+    /// Largest request body accepted; routes can set their own with
+    /// `max_body_bytes`.
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: ByteSize,
+    /// How long a listener replaced on reload (new `port`, `host` or TLS
+    /// setup) may keep serving its in-flight requests before its remaining
+    /// connections are closed.
+    #[serde(default = "default_drain_grace_period")]
+    pub drain_grace_period: HumanDuration,
+}
+
+fn default_drain_grace_period() -> HumanDuration {
+    HumanDuration::from_secs(30)
+}
+
+/// Header limits for one upstream, applied on top of the server's own when
+/// a request is proxied there. Unset fields inherit the server's limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamHeaderLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_header_bytes: Option<ByteSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_header_count: Option<usize>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub header_size_limits: BTreeMap<String, ByteSize>,
+}
+
+impl UpstreamHeaderLimits {
+    pub fn is_empty(&self) -> bool {
+        self.max_header_bytes.is_none() && self.max_header_count.is_none() && self.header_size_limits.is_empty()
+    }
+}
+
+fn default_max_header_bytes() -> ByteSize {
+    ByteSize::from_bytes(64 * 1024)
+}
+
+fn default_max_header_count() -> usize {
+    100
+}
+
+fn default_max_body_bytes() -> ByteSize {
+    ByteSize::from_bytes(1024 * 1024)
+}
+
+/// TLS termination with per-SNI certificates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// Default certificate, served when no SNI entry matches.
+    pub cert_path: String,
+    pub key_path: String,
+    /// Certificates selected by SNI host name; `*.example.com` matches one label.
+    #[serde(default)]
+    pub certificates: Vec<SniCertificateConfig>,
+    /// Certificates expiring within this many days are flagged.
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u32,
+    /// How often certificate files are checked for changes.
+    #[serde(default = "default_tls_reload_interval_seconds")]
+    pub reload_interval_seconds: u64,
+    /// Verify client certificates (mTLS). Read once at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientAuthConfig {
+    /// PEM bundle of the CAs client certificates must chain to.
+    pub ca_path: String,
+    /// Refuse handshakes without a client certificate. Otherwise they're
+    /// accepted and the request simply carries no client identity.
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// How a verified client certificate is passed on to an upstream. Any
+/// identity headers the client sent are stripped whatever the mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientCertForwarding {
+    #[default]
+    Off,
+    /// `X-Client-Cert-Subject`, `-SAN`, `-Serial` and `-Fingerprint`.
+    Headers,
+    /// `X-Forwarded-Client-Cert` in Envoy's format, with the URL-encoded PEM.
+    Xfcc,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SniCertificateConfig {
+    pub sni_hosts: Vec<String>,
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+fn default_expiry_warning_days() -> u32 {
+    30
+}
+
+fn default_tls_reload_interval_seconds() -> u64 {
+    30
+}
+
+impl ServerConfig {
+    /// `public_base_path` with a leading slash and no trailing slash; empty
+    /// when served from the root.
+    pub fn base_path(&self) -> String {
+        normalize_base_path(&self.public_base_path).unwrap_or_default()
+    }
+}
+
+/// Normalizes a path prefix to `/a/b` form, or `Some("")` for the root.
+/// Returns `None` for values that aren't a plain path (e.g. `//host`), so
+/// they can never leak into generated links.
+pub fn normalize_base_path(path: &str) -> Option<String> {
+    let trimmed = path.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Some(String::new());
+    }
+
+    let valid = trimmed.starts_with('/')
+        && !trimmed.starts_with("//")
+        && trimmed
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._~/".contains(c));
+    valid.then(|| trimmed.to_string())
+}
+
+fn default_queue_timeout() -> HumanDuration {
+    HumanDuration::from_secs(5)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,74 +966,1539 @@ pub struct TracingConfig {
     pub enabled: bool,
     pub jaeger_endpoint: String,
     pub service_name: String,
+    #[serde(default)]
+    pub sampling: TraceSamplingConfig,
+}
+
+/// Which requests' traces are kept, decided once each request completes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceSamplingConfig {
+    pub enabled: bool,
+    /// Share, 0-1, of requests kept that no other rule keeps.
+    pub ratio: f64,
+    /// Requests slower than this are always kept.
+    pub slow_threshold: HumanDuration,
+    /// Legacy-routed requests are always kept for this long after the
+    /// rollout generation changes.
+    pub rollout_change_window: HumanDuration,
+}
+
+impl Default for TraceSamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ratio: 0.01,
+            slow_threshold: HumanDuration::from_secs(1),
+            rollout_change_window: HumanDuration::from_secs(600),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MirrorConfig {
     pub enabled: bool,
     pub base_url: String,
-    pub timeout_ms: u64,
+    /// Bare numbers are read as milliseconds (the old `timeout_ms` form).
+    #[serde(alias = "timeout_ms", deserialize_with = "units::legacy_millis")]
+    pub timeout: HumanDuration,
+    /// Whether a mirror request the target never answered is sent again,
+    /// up to `max_retries` times. Superseded by `retry`.
     pub retry_failed: bool,
     pub max_retries: u32,
+    /// Replaces `retry_failed` and `max_retries` when set. Only requests
+    /// that got no response are retried; a `5xx` is a result to compare.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Share of requests mirrored outside any `schedule` window.
+    #[serde(default = "default_sample_percentage")]
+    pub sample_percentage: f64,
+    /// `UTC` or a fixed offset such as `+02:00` that `schedule` windows are
+    /// read in.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<MirrorWindow>,
+    #[serde(default)]
+    pub client_cert_forwarding: ClientCertForwarding,
+    /// Bodies are compared byte for byte up to this size; longer ones by
+    /// hash, with differences located within this prefix.
+    #[serde(default = "default_compared_prefix")]
+    pub compared_prefix: ByteSize,
+    /// Read at startup; changing it takes a restart.
+    #[serde(default)]
+    pub queue: MirrorQueueConfig,
+    /// Read at startup; changing it takes a restart.
+    #[serde(default)]
+    pub parity: MirrorParityConfig,
+    /// What `POST /admin/mirror/report` holds a mirroring campaign to.
+    #[serde(default)]
+    pub report: MirrorReportConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MiddlewareConfig {
-    pub cors: CorsConfig,
-    pub rate_limiting: RateLimitingConfig,
-    pub auth: AuthConfig,
-    pub logging: LoggingConfig,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorQueueKind {
+    /// Lost on restart.
+    Memory,
+    /// Segmented log under `path`; survives restarts.
+    Disk,
 }
 
+/// Where mirror requests wait to be sent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CorsConfig {
-    pub enabled: bool,
-    pub allow_origins: Vec<String>,
-    pub allow_methods: Vec<String>,
-    pub allow_headers: Vec<String>,
+#[serde(default)]
+pub struct MirrorQueueConfig {
+    pub kind: MirrorQueueKind,
+    /// Directory for the `disk` queue's segments and checkpoint.
+    pub path: String,
+    /// New requests are dropped while the queue holds this much.
+    pub max_bytes: ByteSize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RateLimitingConfig {
-    pub enabled: bool,
-    pub requests_per_minute: u32,
+impl Default for MirrorQueueConfig {
+    fn default() -> Self {
+        Self {
+            kind: MirrorQueueKind::Memory,
+            path: "data/mirror-queue".to_string(),
+            max_bytes: ByteSize::from_bytes(64 * 1024 * 1024),
+        }
+    }
 }
 
+/// Where the outcome of each mirrored request is kept for the sign-off
+/// report.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthConfig {
-    pub enabled: bool,
-    pub jwt_secret: String,
+#[serde(default)]
+pub struct MirrorParityConfig {
+    /// Directory of daily record files, so reports cover restarts. Unset
+    /// keeps recent records in memory only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Record files older than this are deleted at startup.
+    pub retention: HumanDuration,
+}
+
+impl Default for MirrorParityConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            retention: HumanDuration::from_secs(30 * 24 * 3600),
+        }
+    }
 }
 
+/// Thresholds a mirror report's verdict is judged against. Rates are
+/// percentages of mirrored requests.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LoggingConfig {
-    pub enabled: bool,
-    pub include_request_body: bool,
-    pub include_response_body: bool,
+#[serde(default)]
+pub struct MirrorReportConfig {
+    /// Fewer requests than this fail the report; routes with fewer aren't
+    /// judged on their own.
+    pub min_samples: u64,
+    /// Mirror answered with a different status.
+    pub max_mismatch_rate: f64,
+    /// Mirror never answered.
+    pub max_failure_rate: f64,
+    pub max_contract_violation_rate: f64,
+    /// p99 of mirror latency minus main latency.
+    pub max_p99_latency_delta_ms: f64,
+    /// Mismatch examples listed, most frequent first.
+    pub examples: usize,
 }
 
-impl AppConfig {
-    pub fn load() -> Result<Self> {
-        let config_path = std::env::var("CONFIG_PATH")
-            .unwrap_or_else(|_| "config/default.yaml".to_string());
-        
-        let mut builder = config::Config::builder()
-            .add_source(config::File::with_name(&config_path))
-            .add_source(config::Environment::with_prefix("GATEWAY"));
-        
-        // Override with environment variables if present
-        if let Ok(host) = std::env::var("HOST") {
-            builder = builder.set_override("server.host", host)?;
-        }
-        if let Ok(port) = std::env::var("PORT") {
-            builder = builder.set_override("server.port", port.parse::<u16>()?)?;
+impl Default for MirrorReportConfig {
+    fn default() -> Self {
+        Self {
+            min_samples: 1000,
+            max_mismatch_rate: 1.0,
+            max_failure_rate: 1.0,
+            max_contract_violation_rate: 0.5,
+            max_p99_latency_delta_ms: 100.0,
+            examples: 10,
         }
-        if let Ok(metrics_port) = std::env::var("METRICS_PORT") {
-            builder = builder.set_override("metrics.port", metrics_port.parse::<u16>()?)?;
+    }
+}
+
+/// A time window with its own mirror sample percentage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorWindow {
+    /// `[days] HH:MM-HH:MM`, e.g. `Mon-Fri 22:00-06:00`.
+    pub window: TimeWindow,
+    pub sample_percentage: f64,
+}
+
+fn default_sample_percentage() -> f64 {
+    100.0
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_compared_prefix() -> ByteSize {
+    ByteSize::from_bytes(64 * 1024)
+}
+
+impl MirrorConfig {
+    /// `retry`, or the policy `retry_failed` and `max_retries` describe.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match (&self.retry, self.retry_failed) {
+            (Some(policy), _) => policy.clone(),
+            (None, true) => RetryPolicy {
+                max_attempts: self.max_retries.saturating_add(1),
+                max_delay: HumanDuration::from_secs(2),
+                ..RetryPolicy::default()
+            },
+            (None, false) => RetryPolicy::never(),
+        }
+    }
+
+    /// The schedule window in force at `now`, if any.
+    pub fn active_window(&self, now: chrono::DateTime<chrono::Utc>) -> Option<&MirrorWindow> {
+        let offset = schedule::parse_utc_offset(&self.timezone)?;
+        self.schedule.iter().find(|entry| entry.window.contains(now, offset))
+    }
+
+    /// Share of requests to mirror at `now`.
+    pub fn sample_percentage_at(&self, now: chrono::DateTime<chrono::Utc>) -> f64 {
+        self.active_window(now)
+            .map(|entry| entry.sample_percentage)
+            .unwrap_or(self.sample_percentage)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiddlewareConfig {
+    pub cors: CorsConfig,
+    pub rate_limiting: RateLimitingConfig,
+    pub auth: AuthConfig,
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub server_timing: ServerTimingConfig,
+    #[serde(default)]
+    pub decompression: DecompressionConfig,
+    #[serde(default)]
+    pub csrf: CsrfConfig,
+    #[serde(default)]
+    pub load_shedding: LoadSheddingConfig,
+    #[serde(default)]
+    pub ip_filter: IpFilterConfig,
+    #[serde(default)]
+    pub request_id: RequestIdConfig,
+}
+
+/// The id that correlates a request across the gateway, the legacy
+/// gateway, and the mirror.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestIdConfig {
+    /// Read from the request, echoed in the response, and forwarded
+    /// upstream. Requests without a usable one get a UUIDv7.
+    pub header: String,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            header: "X-Request-Id".to_string(),
+        }
+    }
+}
+
+/// Turns clients away by address before anything else is done for them.
+/// Entries are IPs or CIDRs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpFilterConfig {
+    /// Always answered 403; checked before `allow`.
+    pub deny: Vec<String>,
+    /// When set, the only addresses let through to `allow_paths`.
+    pub allow: Vec<String>,
+    /// Path prefixes `allow` guards; empty guards every path.
+    pub allow_paths: Vec<String>,
+    /// Peers whose `X-Forwarded-For` is believed. Other peers are filtered
+    /// by their own address.
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for IpFilterConfig {
+    fn default() -> Self {
+        Self {
+            deny: Vec::new(),
+            allow: Vec::new(),
+            allow_paths: vec!["/admin".to_string()],
+            trusted_proxies: default_trusted_proxies(),
+        }
+    }
+}
+
+impl IpFilterConfig {
+    pub fn is_active(&self) -> bool {
+        !self.deny.is_empty() || !self.allow.is_empty()
+    }
+}
+
+/// A gateway-wide cap on requests in flight. Requests over it are answered
+/// 503 at once instead of waiting for a slow upstream to free a slot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadSheddingConfig {
+    /// Unlimited when unset.
+    pub max_in_flight: Option<usize>,
+    /// Lets `/health`, `/readyz` and `/api/v1/health` through uncounted, so
+    /// probes keep answering while the gateway sheds load.
+    pub exempt_health_checks: bool,
+}
+
+impl Default for LoadSheddingConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: None,
+            exempt_health_checks: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsrfMode {
+    /// The `XSRF-TOKEN` cookie must be echoed in the CSRF header.
+    DoubleSubmit,
+    /// `Origin`, or failing that `Referer`, must be an allowed origin.
+    Origin,
+}
+
+/// CSRF protection for cookie-authenticated routes. Only unsafe methods are
+/// checked, and never requests carrying a bearer token or API key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsrfConfig {
+    pub enabled: bool,
+    pub cookie_name: String,
+    pub header_name: String,
+    /// Tokens older than this are replaced on the next safe request and
+    /// rejected on unsafe ones.
+    pub token_lifetime: HumanDuration,
+    /// Marks the token cookie `Secure`; turn off only for plain-HTTP testing.
+    pub secure_cookie: bool,
+    /// Origins (`scheme://host[:port]`) accepted in `origin` mode.
+    pub allowed_origins: Vec<String>,
+    pub routes: Vec<CsrfRoute>,
+}
+
+impl Default for CsrfConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cookie_name: "XSRF-TOKEN".to_string(),
+            header_name: "X-XSRF-Token".to_string(),
+            token_lifetime: HumanDuration::from_secs(12 * 3600),
+            secure_cookie: true,
+            allowed_origins: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl CsrfConfig {
+    /// How `route` is protected, from the first matching entry in `routes`.
+    pub fn mode_for(&self, route: &str) -> Option<CsrfMode> {
+        self.routes
+            .iter()
+            .find(|entry| route_pattern_matches(&entry.route, route))
+            .map(|entry| entry.mode)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsrfRoute {
+    /// Route pattern; a trailing `*` matches by prefix.
+    pub route: String,
+    pub mode: CsrfMode,
+}
+
+/// Limits for decoding compressed bodies that the gateway inspects (strict
+/// JSON checks, mirror comparison, body logging). Relayed bodies are never
+/// decoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecompressionConfig {
+    /// Inspection is skipped for bodies that decode to more than this.
+    pub max_decoded_size: ByteSize,
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        Self {
+            max_decoded_size: ByteSize::from_bytes(8 * 1024 * 1024),
+        }
+    }
+}
+
+/// Shared budget for the in-memory stores (debug capture, token cache,
+/// mirror outcomes, concurrency state).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Eviction starts once the stores together hold more than this.
+    pub budget: ByteSize,
+    /// Eviction frees memory until usage is down to this share of `budget`.
+    pub evict_to_percentage: f64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            budget: ByteSize::from_bytes(256 * 1024 * 1024),
+            evict_to_percentage: 80.0,
+        }
+    }
+}
+
+/// Service level objectives, tracked per route group by the monitoring
+/// module and reported at `GET /monitoring/slo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SloConfig {
+    pub objectives: Vec<SloObjective>,
+    /// A 1h burn rate at or above this raises a fast-burn alert. The default
+    /// 14.4 spends 2% of a 30-day budget in an hour.
+    pub fast_burn_threshold: f64,
+    pub evaluation_interval: HumanDuration,
+    /// Let the gatekeeper roll back when a route taking rollout traffic
+    /// burns its budget fast.
+    pub rollback_on_fast_burn: bool,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            objectives: Vec::new(),
+            fast_burn_threshold: 14.4,
+            evaluation_interval: HumanDuration::from_secs(30),
+            rollback_on_fast_burn: false,
+        }
+    }
+}
+
+/// Startup check of the system clock against a trusted time source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// Answers `HEAD` with a `Date` header. The legacy gateway is asked when
+    /// unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_source_url: Option<String>,
+    /// Skew beyond which detailed health reports degraded.
+    pub max_skew: HumanDuration,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            time_source_url: None,
+            max_skew: HumanDuration::from_secs(10),
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Where the skew check gets trusted time from, if anywhere.
+    pub fn time_source<'a>(&'a self, canary: &'a CanaryRolloutConfig) -> Option<&'a str> {
+        self.time_source_url
+            .as_deref()
+            .or_else(|| Some(canary.legacy_gateway_url.as_str()).filter(|url| !url.is_empty()))
+    }
+}
+
+/// Who may read Swagger UI (`/docs`) and the OpenAPI spec.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocsConfig {
+    /// Read at startup; when off the routes aren't mounted at all.
+    pub enabled: bool,
+    /// Requires a token with the `docs:read` scope.
+    pub require_auth: bool,
+    /// Peers (IPs or CIDRs) the docs are served to; any peer when empty.
+    pub allowed_cidrs: Vec<String>,
+    /// Leaves `admin`-tagged paths out of the spec for readers without the
+    /// `docs:read` scope.
+    pub redact_admin_paths: bool,
+}
+
+impl Default for DocsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            require_auth: false,
+            allowed_cidrs: Vec::new(),
+            redact_admin_paths: false,
+        }
+    }
+}
+
+impl DocsConfig {
+    /// Whether access is limited, and so audited.
+    pub fn is_restricted(&self) -> bool {
+        self.require_auth || !self.allowed_cidrs.is_empty()
+    }
+}
+
+/// One set of credentials for the docs, the OpenAPI spec and the metrics,
+/// checked apart from JWT auth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminBasicAuthConfig {
+    pub username: String,
+    /// bcrypt (`$2b$...`) or argon2 (`$argon2id$...`) hash of the password.
+    pub password_hash: String,
+    #[serde(default = "default_basic_auth_realm")]
+    pub realm: String,
+}
+
+fn default_basic_auth_realm() -> String {
+    "project-gateway".to_string()
+}
+
+/// Limits on what is posted to the webhook. Events held back are summed up
+/// in a periodic digest; critical ones are never held back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Events posted to one destination per `window`.
+    pub max_per_window: u32,
+    pub window: HumanDuration,
+    /// How long after the first held-back event its digest is posted.
+    pub digest_interval: HumanDuration,
+    /// Least time between two events of one type about the same subject,
+    /// such as one route's smoke check. Events reporting a change of state
+    /// aren't held to it.
+    pub min_interval: HumanDuration,
+    /// `min_interval` for particular event types, e.g. `slo_fast_burn: 15m`.
+    pub min_intervals: BTreeMap<String, HumanDuration>,
+    /// Posts the webhook failed to take, from connection errors, `429` or
+    /// a `5xx`, are retried this way.
+    pub retry: RetryPolicy,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            max_per_window: 10,
+            window: HumanDuration::from_secs(600),
+            digest_interval: HumanDuration::from_secs(900),
+            min_interval: HumanDuration::from_secs(300),
+            min_intervals: BTreeMap::new(),
+            retry: RetryPolicy {
+                max_attempts: 3,
+                initial_delay: HumanDuration::from_secs(1),
+                max_delay: HumanDuration::from_secs(10),
+                max_elapsed: HumanDuration::from_secs(30),
+                ..RetryPolicy::default()
+            },
+        }
+    }
+}
+
+impl NotificationsConfig {
+    pub fn min_interval_for(&self, kind: &str) -> std::time::Duration {
+        self.min_intervals.get(kind).unwrap_or(&self.min_interval).get()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloObjective {
+    pub name: String,
+    /// Any method when unset.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Route pattern as in `routes`; a trailing `*` matches by prefix.
+    pub route: String,
+    /// Percentage of requests that must not fail with a 5xx, e.g. 99.9.
+    #[serde(default)]
+    pub availability: Option<f64>,
+    #[serde(default)]
+    pub latency: Option<LatencyObjective>,
+    /// Compliance period the error budget covers.
+    #[serde(default = "default_slo_window")]
+    pub window: HumanDuration,
+}
+
+impl SloObjective {
+    pub fn matches(&self, method: &str, route: &str) -> bool {
+        let method_matches = self
+            .method
+            .as_deref()
+            .is_none_or(|expected| expected.eq_ignore_ascii_case(method));
+        method_matches && route_pattern_matches(&self.route, route)
+    }
+}
+
+/// Whether `prefix` covers `path` segment by segment: `/health` covers
+/// `/health` and `/health/live` but not `/healthz`, and `/` covers all.
+pub(crate) fn prefix_covers(prefix: &str, path: &str) -> bool {
+    let prefix = trim_trailing_slash(prefix);
+    prefix == "/" || path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn trim_trailing_slash(path: &str) -> &str {
+    match path.strip_suffix('/') {
+        Some(trimmed) if !trimmed.is_empty() => trimmed,
+        _ => path,
+    }
+}
+
+/// Whether `route` fits `pattern`, where a trailing `*` matches by prefix.
+pub fn route_pattern_matches(pattern: &str, route: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => route.starts_with(prefix),
+        None => route == pattern,
+    }
+}
+
+/// Routes taken out of service for `duration` each time `start_cron` fires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// `[METHOD ]/pattern`, where a trailing `*` matches by prefix, e.g.
+    /// `POST /api/v1/users*`.
+    pub route_selector: String,
+    pub start_cron: CronSchedule,
+    pub duration: HumanDuration,
+    /// Returned as the detail of the 503 response.
+    #[serde(default = "default_maintenance_message")]
+    pub message: String,
+    /// UTC offset the cron schedule is read in, e.g. `+02:00`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+fn default_maintenance_message() -> String {
+    "This route is down for scheduled maintenance".to_string()
+}
+
+impl MaintenanceWindow {
+    pub fn selects(&self, method: &str, route: &str) -> bool {
+        route_selector_matches(&self.route_selector, method, route)
+    }
+
+    /// When the occurrence in force at `now` started, if one is.
+    pub fn started_at(&self, now: chrono::DateTime<chrono::Utc>) -> Option<chrono::DateTime<chrono::Utc>> {
+        let offset = schedule::parse_utc_offset(&self.timezone)?;
+        let duration = chrono::Duration::from_std(self.duration.get()).ok()?;
+        self.start_cron
+            .latest_at_or_before(now, duration, offset)
+            .filter(|start| now < *start + duration)
+    }
+}
+
+/// Rejecting writes while reads keep being served, e.g. during a database
+/// failover. Toggled here or through `POST /admin/read-only`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadOnlyConfig {
+    pub enabled: bool,
+    /// Sent as `Retry-After` on rejected writes.
+    pub retry_after: HumanDuration,
+    /// Returned as the detail of the 503 response.
+    pub message: String,
+    /// `[METHOD ]/pattern` selectors for writes that must keep working, such
+    /// as `POST /auth/token/refresh`.
+    pub exempt_routes: Vec<String>,
+}
+
+impl Default for ReadOnlyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retry_after: HumanDuration::from_secs(30),
+            message: "The service is read-only for now; writes are not accepted".to_string(),
+            exempt_routes: Vec::new(),
+        }
+    }
+}
+
+impl ReadOnlyConfig {
+    pub fn is_exempt(&self, method: &str, route: &str) -> bool {
+        self.exempt_routes
+            .iter()
+            .any(|selector| route_selector_matches(selector, method, route))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadMode {
+    /// A file that fails to load is rejected whole.
+    #[default]
+    Strict,
+    /// A file that fails to load is loaded one top-level section at a time;
+    /// sections that load are applied and the rest keep their values.
+    Partial,
+}
+
+/// How an edited config file is reloaded. Read from the config in force, so
+/// a change here applies from the reload after the one that brings it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigReloadConfig {
+    pub mode: ReloadMode,
+    /// A larger file is rejected before it's parsed, whatever the mode.
+    pub max_file_size: ByteSize,
+}
+
+impl Default for ConfigReloadConfig {
+    fn default() -> Self {
+        Self {
+            mode: ReloadMode::Strict,
+            max_file_size: ByteSize::from_bytes(4 * 1024 * 1024),
+        }
+    }
+}
+
+/// Limits on the runtime state `POST /admin/state/import` takes over from
+/// the instance being replaced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandoffConfig {
+    /// Snapshots exported longer ago than this are refused as stale.
+    pub max_snapshot_age: HumanDuration,
+    /// How far past this instance's clock a snapshot's export time may be;
+    /// further means one of the two clocks is off.
+    pub max_clock_skew: HumanDuration,
+}
+
+impl Default for HandoffConfig {
+    fn default() -> Self {
+        Self {
+            max_snapshot_age: HumanDuration::from_secs(300),
+            max_clock_skew: HumanDuration::from_secs(30),
+        }
+    }
+}
+
+/// Splits a `[METHOD ]/pattern` selector into its method, if it names one,
+/// and route pattern.
+pub fn split_route_selector(selector: &str) -> (Option<&str>, &str) {
+    match selector.trim().split_once(char::is_whitespace) {
+        Some((method, pattern)) => (Some(method), pattern.trim()),
+        None => (None, selector.trim()),
+    }
+}
+
+/// Whether a `[METHOD ]/pattern` selector covers `method` and `route`.
+pub fn route_selector_matches(selector: &str, method: &str, route: &str) -> bool {
+    let (expected, pattern) = split_route_selector(selector);
+    expected.is_none_or(|expected| expected.eq_ignore_ascii_case(method)) && route_pattern_matches(pattern, route)
+}
+
+/// `percentile` of requests must complete within `threshold`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyObjective {
+    pub threshold: HumanDuration,
+    #[serde(default = "default_latency_percentile")]
+    pub percentile: f64,
+}
+
+fn default_slo_window() -> HumanDuration {
+    HumanDuration::from_secs(30 * 24 * 3600)
+}
+
+fn default_latency_percentile() -> f64 {
+    99.0
+}
+
+/// Where a request's API version comes from when the path and the version
+/// header disagree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    #[default]
+    Path,
+    Header,
+}
+
+/// API versions the gateway negotiates. Each version's routes live under
+/// `{base_path}/{name}`; a request picks one by path or by `header`, and an
+/// unversioned path under `base_path` gets `default_version`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersioningConfig {
+    pub enabled: bool,
+    pub base_path: String,
+    pub default_version: String,
+    pub precedence: VersionSource,
+    pub header: String,
+    pub versions: Vec<ApiVersionConfig>,
+}
+
+impl Default for VersioningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_path: "/api".to_string(),
+            default_version: "v1".to_string(),
+            precedence: VersionSource::Path,
+            header: "Accept-Version".to_string(),
+            versions: vec![ApiVersionConfig {
+                name: "v1".to_string(),
+                deprecated: None,
+                sunset: None,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiVersionConfig {
+    /// `v` and a number, e.g. `v2`.
+    pub name: String,
+    /// Marks the version deprecated as of this date (UTC); responses carry
+    /// `Deprecation`.
+    #[serde(default)]
+    pub deprecated: Option<chrono::NaiveDate>,
+    /// When the version goes away; responses carry `Sunset`.
+    #[serde(default)]
+    pub sunset: Option<chrono::NaiveDate>,
+}
+
+impl VersioningConfig {
+    pub fn version(&self, name: &str) -> Option<&ApiVersionConfig> {
+        self.versions.iter().find(|version| version.name == name)
+    }
+}
+
+/// Per-request profiles kept in memory for `GET /admin/profiles`. Capturing
+/// also needs the gateway built with the `profiling` cargo feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfilingConfig {
+    pub enabled: bool,
+    /// Every Nth request is profiled; zero profiles only requests that ask.
+    pub sample_one_in: u64,
+    /// Peers (IPs or CIDRs) whose `X-Profile: 1` header forces a profile.
+    pub trusted_networks: Vec<String>,
+    /// Profiles kept; the oldest is dropped first.
+    pub max_profiles: usize,
+    /// Capture is skipped when the profiler isn't free within this long.
+    pub start_budget: HumanDuration,
+}
+
+impl Default for ProfilingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_one_in: 10_000,
+            trusted_networks: vec!["127.0.0.1/32".to_string(), "::1/128".to_string()],
+            max_profiles: 32,
+            start_budget: HumanDuration::from_millis(1),
+        }
+    }
+}
+
+/// `Server-Timing` response header with the gateway/upstream/auth/queue
+/// breakdown.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerTimingConfig {
+    pub enabled: bool,
+    /// Path prefixes that never get the header, for routes not listed in
+    /// `routes`.
+    pub exclude_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    pub allow_origins: Vec<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitingConfig {
+    pub enabled: bool,
+    /// Requests per minute per client identity, with bursts up to the same.
+    pub requests_per_minute: u32,
+    /// Per-client `requests_per_minute`, first match wins.
+    #[serde(default)]
+    pub overrides: Vec<RateLimitOverride>,
+    /// In-flight requests allowed per client identity; unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_per_client: Option<usize>,
+    /// Client identity (JWT subject, API key, or IP) to tier name.
+    #[serde(default)]
+    pub client_tiers: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub tiers: std::collections::HashMap<String, RateLimitTier>,
+    /// Peers, as IPs or CIDRs, whose `X-Forwarded-For` is believed when
+    /// identifying clients by IP. Other peers are identified by their own
+    /// address.
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+    /// Send `X-RateLimit-Limit`, `-Remaining` and `-Reset` on every response
+    /// the request rate limit applied to.
+    #[serde(default = "default_rate_limit_headers")]
+    pub headers: bool,
+    /// What clients are bucketed by; several keys are combined into one.
+    /// Unset, the JWT subject, then `X-API-Key`, then the IP, whichever
+    /// the request has first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_by: Vec<RateLimitKey>,
+    /// Where requests lacking one of the `key_by` keys are counted.
+    #[serde(default)]
+    pub missing_key: MissingKeyFallback,
+}
+
+fn default_rate_limit_headers() -> bool {
+    true
+}
+
+/// One part of a client's rate-limit key: `ip`, `jwt_sub` or
+/// `header:<name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The client address, as resolved through `trusted_proxies`.
+    Ip,
+    /// The authenticated JWT subject.
+    JwtSubject,
+    /// A request header's value, e.g. a tenant ID. Names are lowercase.
+    Header(String),
+}
+
+impl std::str::FromStr for RateLimitKey {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim() {
+            "ip" => Ok(Self::Ip),
+            "jwt_sub" => Ok(Self::JwtSubject),
+            other => match other.strip_prefix("header:") {
+                Some(name) if axum::http::HeaderName::from_bytes(name.as_bytes()).is_ok() => {
+                    Ok(Self::Header(name.to_ascii_lowercase()))
+                }
+                Some(name) => Err(format!("{:?} is not a valid header name", name)),
+                None => Err(format!("{:?} is not one of ip, jwt_sub or header:<name>", input)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ip => f.write_str("ip"),
+            Self::JwtSubject => f.write_str("jwt_sub"),
+            Self::Header(name) => write!(f, "header:{}", name),
+        }
+    }
+}
+
+impl Serialize for RateLimitKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for RateLimitKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Where a request goes when it lacks a `key_by` key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingKeyFallback {
+    /// Its own bucket, keyed by client IP.
+    #[default]
+    Ip,
+    /// One bucket shared by every such request.
+    Anonymous,
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1/32".to_string(), "::1/128".to_string()]
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimitTier {
+    /// Overrides `max_concurrent_per_client` for clients in this tier.
+    #[serde(default)]
+    pub max_concurrent_per_client: Option<usize>,
+}
+
+/// A client allowed a different request rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitOverride {
+    /// Client identity: JWT subject, API key, or IP; with `key_by`, the
+    /// key it produces.
+    pub subject: String,
+    pub requests_per_minute: u32,
+}
+
+impl RateLimitingConfig {
+    /// Request rate for a client, honouring its override.
+    pub fn requests_per_minute_for(&self, client: &str) -> u32 {
+        self.overrides
+            .iter()
+            .find(|o| o.subject == client)
+            .map(|o| o.requests_per_minute)
+            .unwrap_or(self.requests_per_minute)
+    }
+
+    /// Concurrency cap for a client, honouring its tier override.
+    pub fn concurrency_limit_for(&self, client: &str) -> Option<usize> {
+        self.client_tiers
+            .get(client)
+            .and_then(|tier| self.tiers.get(tier))
+            .and_then(|tier| tier.max_concurrent_per_client)
+            .or(self.max_concurrent_per_client)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    /// Single-secret form, used when `jwt_secrets` is empty.
+    #[serde(default)]
+    pub jwt_secret: String,
+    /// Accepted HMAC secrets, primary first. Tokens are validated against each
+    /// in order but only ever issued with the primary.
+    #[serde(default)]
+    pub jwt_secrets: Vec<String>,
+    /// Accept a verified client certificate in place of a bearer token; its
+    /// subject DN becomes the principal.
+    #[serde(default)]
+    pub client_certificates: bool,
+    /// How far `exp`, `nbf` and `iat` may be off to allow for clock drift
+    /// between the issuer and the gateway.
+    #[serde(default = "default_clock_skew_tolerance")]
+    pub clock_skew_tolerance: HumanDuration,
+    /// Required `iss` claim; not checked when unset.
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Accepted `aud` values, any one of which a token must name; not
+    /// checked when empty.
+    #[serde(default)]
+    pub audience: Vec<String>,
+    /// Key set for tokens signed with asymmetric keys (RS256, ES256, ...),
+    /// which are verified with the key named by their `kid` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_url: Option<String>,
+    /// How often the key set is re-fetched in the background.
+    #[serde(default = "default_jwks_refresh_interval")]
+    pub jwks_refresh_interval: HumanDuration,
+    /// Retries of a failed background fetch.
+    #[serde(default = "default_jwks_retry")]
+    pub jwks_retry: RetryPolicy,
+    /// Paths served without a token, such as probes and docs. A trailing
+    /// `/*` exempts everything below a path as well as the path itself.
+    #[serde(default = "default_auth_exempt_paths")]
+    pub exempt_paths: Vec<String>,
+    /// HMAC-signed requests from legacy service callers.
+    #[serde(default)]
+    pub request_signing: RequestSigningConfig,
+    /// RFC 7662 endpoint that validates bearer tokens which aren't JWTs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub introspection: Option<IntrospectionConfig>,
+    /// Headers carrying the verified caller to the legacy gateway and the
+    /// mirror.
+    #[serde(default)]
+    pub identity_headers: IdentityHeadersConfig,
+    /// Recent denials kept for `GET /admin/auth/failures`.
+    #[serde(default = "default_auth_failure_log_size")]
+    pub failure_log_size: usize,
+    /// Keys clients send in `X-API-Key`, with the quotas each is held to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<ApiKey>,
+    /// UTC hour (0-23) daily quotas start over at; monthly quotas start
+    /// over at the same hour on the 1st.
+    #[serde(default)]
+    pub quota_reset_hour: u32,
+}
+
+/// An API key and its request quotas. Requests carrying a key that isn't
+/// listed are not held to any quota.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Names the key in `/admin/quotas/{key_id}`, logs and metrics, where
+    /// the key itself never appears.
+    pub id: String,
+    pub key: String,
+    #[serde(default)]
+    pub quota: ApiKeyQuota,
+}
+
+/// Requests an API key may make per period; unlimited where unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyQuota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly: Option<u64>,
+}
+
+/// Inbound headers starting with `strip_prefix`, or named below, are
+/// dropped before a request goes upstream; the named ones are then set from
+/// the verified claims.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdentityHeadersConfig {
+    pub enabled: bool,
+    pub strip_prefix: String,
+    pub subject: String,
+    /// Comma-separated.
+    pub roles: String,
+    /// Space-separated, as in the `scope` claim.
+    pub scopes: String,
+    /// `jwt`, `jwks`, `introspection`, `client_certificate` or `signature`.
+    pub method: String,
+}
+
+impl Default for IdentityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strip_prefix: "X-Auth-".to_string(),
+            subject: "X-Auth-Subject".to_string(),
+            roles: "X-Auth-Roles".to_string(),
+            scopes: "X-Auth-Scopes".to_string(),
+            method: "X-Auth-Method".to_string(),
+        }
+    }
+}
+
+impl IdentityHeadersConfig {
+    /// The configured header names, in subject, roles, scopes, method order.
+    pub fn names(&self) -> [&str; 4] {
+        [&self.subject, &self.roles, &self.scopes, &self.method]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntrospectionConfig {
+    pub url: String,
+    /// Sent as HTTP Basic credentials; leave empty when the endpoint
+    /// doesn't authenticate its callers.
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+    /// How long an active token's claims are reused, never past its `exp`.
+    #[serde(default = "default_introspection_cache_ttl")]
+    pub cache_ttl: HumanDuration,
+    /// How long an inactive token is rejected without asking again.
+    #[serde(default = "default_introspection_negative_cache_ttl")]
+    pub negative_cache_ttl: HumanDuration,
+    #[serde(default = "default_introspection_timeout")]
+    pub timeout: HumanDuration,
+    /// What to do with a token the endpoint couldn't be asked about.
+    #[serde(default)]
+    pub on_failure: IntrospectionFailure,
+}
+
+/// `closed` rejects the request; `open` lets it through authenticated but
+/// without roles or scopes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntrospectionFailure {
+    #[default]
+    Closed,
+    Open,
+}
+
+fn default_introspection_cache_ttl() -> HumanDuration {
+    HumanDuration::from_secs(60)
+}
+
+fn default_introspection_negative_cache_ttl() -> HumanDuration {
+    HumanDuration::from_secs(10)
+}
+
+fn default_introspection_timeout() -> HumanDuration {
+    HumanDuration::from_secs(5)
+}
+
+/// Verification of `X-Signature` headers: an HMAC-SHA256 over the method,
+/// path and query, `X-Signature-Timestamp`, and body, in lowercase hex.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestSigningConfig {
+    /// Shared secrets callers sign with; verification is off while empty.
+    pub keys: Vec<SigningKey>,
+    /// Largest body buffered to check a signature; larger ones get 413.
+    pub max_body_bytes: ByteSize,
+    /// How far the signature timestamp may be from the gateway clock.
+    pub max_skew: HumanDuration,
+    /// Reject a signature already seen within `max_skew`.
+    pub reject_replays: bool,
+}
+
+impl Default for RequestSigningConfig {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            max_body_bytes: ByteSize::from_bytes(1024 * 1024),
+            max_skew: HumanDuration::from_secs(300),
+            reject_replays: true,
+        }
+    }
+}
+
+/// A named shared secret. Requests signed with it are authenticated as
+/// `name`, with `roles` for route authorization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKey {
+    pub name: String,
+    pub secret: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+fn default_clock_skew_tolerance() -> HumanDuration {
+    HumanDuration::from_secs(60)
+}
+
+fn default_jwks_refresh_interval() -> HumanDuration {
+    HumanDuration::from_secs(300)
+}
+
+fn default_jwks_retry() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 4,
+        initial_delay: HumanDuration::from_millis(500),
+        max_delay: HumanDuration::from_secs(10),
+        ..RetryPolicy::default()
+    }
+}
+
+fn default_auth_failure_log_size() -> usize {
+    100
+}
+
+fn default_auth_exempt_paths() -> Vec<String> {
+    ["/health", "/readyz", "/metrics", "/docs/*", "/api-docs/*"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+impl AuthConfig {
+    /// Effective secret list, primary first.
+    pub fn secrets(&self) -> Vec<&str> {
+        if self.jwt_secrets.is_empty() {
+            if self.jwt_secret.is_empty() {
+                Vec::new()
+            } else {
+                vec![self.jwt_secret.as_str()]
+            }
+        } else {
+            self.jwt_secrets.iter().map(String::as_str).collect()
+        }
+    }
+
+    pub fn primary_secret(&self) -> Option<&str> {
+        self.secrets().first().copied()
+    }
+
+    /// Whether `path` is served without a token. Paths compare case
+    /// sensitively and ignore a trailing slash; paths with dot segments or
+    /// encoded separators are never exempt, since an upstream may resolve
+    /// them to somewhere else.
+    pub fn is_exempt(&self, path: &str) -> bool {
+        let lowered = path.to_ascii_lowercase();
+        if lowered.contains("%2f") || lowered.contains("%2e") || lowered.contains("%5c") || path.contains('\\') {
+            return false;
+        }
+        if path.split('/').any(|segment| segment == "." || segment == "..") {
+            return false;
+        }
+
+        let path = trim_trailing_slash(path);
+        self.exempt_paths.iter().any(|pattern| match pattern.strip_suffix("/*") {
+            Some(base) => {
+                let base = trim_trailing_slash(base);
+                path == base || path.strip_prefix(base).is_some_and(|rest| rest.starts_with('/'))
+            }
+            None => path == trim_trailing_slash(pattern),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    pub enabled: bool,
+    pub include_request_body: bool,
+    pub include_response_body: bool,
+    /// Logged bodies are cut to this many bytes; no more than that is held
+    /// per body, whatever its length.
+    #[serde(default = "default_max_logged_body_size")]
+    pub max_body_size: ByteSize,
+    /// Adds the request and response headers to the access log.
+    #[serde(default)]
+    pub include_headers: bool,
+    /// Headers whose values are logged as `[REDACTED]`, matched without
+    /// regard to case.
+    #[serde(default = "default_redact_headers")]
+    pub redact_headers: Vec<String>,
+    /// Dot paths into logged JSON bodies whose values are logged as
+    /// `[REDACTED]`, such as `user.password`; `*` matches any one key.
+    #[serde(default)]
+    pub redact_json_fields: Vec<String>,
+    /// Requests taking longer than this, from arrival to the response
+    /// head, get a `slow_request` warning whether or not the access log is
+    /// on; zero turns that off. Bare numbers are read as milliseconds (the
+    /// old `slow_request_threshold_ms` form).
+    #[serde(default, alias = "slow_request_threshold_ms", deserialize_with = "units::legacy_millis")]
+    pub slow_request_threshold: HumanDuration,
+    /// How access log events are written, and which formatter the
+    /// gateway's logs go through. Read at startup for the formatter, so
+    /// switching it needs a restart to take full effect.
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Share of successful (2xx) requests the access log keeps, from 0.0 to
+    /// 1.0; errors and slow requests are always logged. Routes can override
+    /// it with `log_sample_rate`.
+    #[serde(default = "default_log_sample_rate")]
+    pub sample_rate: f64,
+    /// Access log level by path prefix, such as `/health: off`; the longest
+    /// matching prefix wins. Paths without one follow `enabled` and the
+    /// settings above.
+    #[serde(default)]
+    pub route_levels: BTreeMap<String, RouteLogLevel>,
+}
+
+fn default_log_sample_rate() -> f64 {
+    1.0
+}
+
+impl LoggingConfig {
+    /// The level for `path` from `route_levels`, if a prefix covers it. A
+    /// prefix matches whole segments, so `/health` covers `/health/live`
+    /// but not `/healthz`.
+    pub fn route_level(&self, path: &str) -> Option<RouteLogLevel> {
+        self.route_levels
+            .iter()
+            .filter(|(prefix, _)| prefix_covers(prefix, path))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteLogLevel {
+    /// No access log line and no slow request warning; metrics are still
+    /// recorded.
+    Off,
+    /// Only requests answered with a 5xx.
+    Error,
+    /// Logged with the body and header settings above, even while
+    /// `enabled` is off.
+    Info,
+    /// As `info`, plus headers and bodies, and never sampled.
+    Debug,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Structured fields, written out as JSON.
+    #[default]
+    Json,
+    /// Apache combined log lines, with the request id and backend appended,
+    /// written with the compact formatter.
+    Combined,
+}
+
+fn default_max_logged_body_size() -> ByteSize {
+    ByteSize::from_bytes(64 * 1024)
+}
+
+fn default_redact_headers() -> Vec<String> {
+    ["Authorization", "Proxy-Authorization", "Cookie", "Set-Cookie", "X-Api-Key"]
+        .map(String::from)
+        .to_vec()
+}
+
+impl AppConfig {
+    /// The configured route for a method and path, if any. `path` is the
+    /// matched route pattern when one is known.
+    pub fn route(&self, method: &str, path: &str) -> Option<&RouteConfig> {
+        self.routes
+            .iter()
+            .find(|route| route.path == path && route.method.eq_ignore_ascii_case(method))
+    }
+
+    /// The configured route a concrete request would be served by, preferring
+    /// a literal match over a parameterized one.
+    pub fn match_route(&self, method: &str, path: &str) -> Option<&RouteConfig> {
+        self.route(method, path).or_else(|| {
+            self.routes
+                .iter()
+                .find(|route| route.method.eq_ignore_ascii_case(method) && route.matches_path(path))
+        })
+    }
+
+    pub fn load() -> Result<Self> {
+        let config_path = std::env::var("CONFIG_PATH")
+            .unwrap_or_else(|_| "config/default.yaml".to_string());
+        Self::load_from(&config_path)
+    }
+
+    /// Loads and validates the YAML file at `config_path`, applying
+    /// environment overrides.
+    pub fn load_from(config_path: &str) -> Result<Self> {
+        Self::load_sources(config::File::new(config_path, config::FileFormat::Yaml), None)
+    }
+
+    /// Loads a config document given as YAML rather than read from a file.
+    pub fn load_str(document: &str) -> Result<Self> {
+        Self::load_sources(config::File::from_str(document, config::FileFormat::Yaml), None)
+    }
+
+    /// Like [`AppConfig::load_str`], with `env` standing in for the process
+    /// environment.
+    pub fn load_str_with_env(document: &str, env: HashMap<String, String>) -> Result<Self> {
+        let config = Self::build_sources(config::File::from_str(document, config::FileFormat::Yaml), None, Some(env))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads `base`, the config file's contents, with the `overlay` YAML
+    /// merged over it. Environment overrides still take precedence.
+    pub fn load_with_overlay(base: &str, overlay: &str) -> Result<Self> {
+        Self::load_sources(
+            config::File::from_str(base, config::FileFormat::Yaml),
+            Some(config::File::from_str(overlay, config::FileFormat::Yaml)),
+        )
+    }
+
+    /// Deserializes a config document, with environment overrides, without
+    /// validating it.
+    pub(crate) fn parse_str(document: &str) -> Result<Self> {
+        Self::build_sources(config::File::from_str(document, config::FileFormat::Yaml), None, None)
+    }
+
+    fn load_sources(
+        base: impl config::Source + Send + Sync + 'static,
+        overlay: Option<config::File<config::FileSourceString, config::FileFormat>>,
+    ) -> Result<Self> {
+        let config = Self::build_sources(base, overlay, None)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Builds the config from `base` and `overlay`, with overrides from
+    /// `env`, or from the process environment when it's `None`.
+    fn build_sources(
+        base: impl config::Source + Send + Sync + 'static,
+        overlay: Option<config::File<config::FileSourceString, config::FileFormat>>,
+        env: Option<HashMap<String, String>>,
+    ) -> Result<Self> {
+        let mut builder = config::Config::builder().add_source(base);
+        if let Some(overlay) = overlay {
+            builder = builder.add_source(overlay);
+        }
+        let var = |name: &str| match &env {
+            Some(env) => env.get(name).cloned(),
+            None => std::env::var(name).ok(),
+        };
+        builder = builder.add_source(config::Environment::with_prefix("GATEWAY").source(env.clone()));
+        
+        // Override with environment variables if present
+        if let Some(host) = var("HOST") {
+            builder = builder.set_override("server.host", host)?;
+        }
+        if let Some(port) = var("PORT") {
+            builder = builder.set_override("server.port", port.parse::<u16>()?)?;
+        }
+        if let Some(metrics_port) = var("METRICS_PORT") {
+            builder = builder.set_override("metrics.port", metrics_port.parse::<u16>()?)?;
+        }
+        if let Some(format) = var("GATEWAY_LOGGING__FORMAT") {
+            builder = builder.set_override("middleware.logging.format", format)?;
         }
         
         let settings = builder.build()?;
-        let config: AppConfig = settings.try_deserialize()?;
-        Ok(config)
+        Ok(settings.try_deserialize()?)
+    }
+
+    /// Rejects unusable configurations and logs warnings for suspicious ones.
+    ///
+    /// Runs on startup and on every reload, so a reload that introduces an
+    /// error-severity conflict is rejected and the previous config is kept.
+    /// The returned error downcasts to [`ConfigValidationError`].
+    pub fn validate(&self) -> Result<()> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = validation::check(self)
+            .into_iter()
+            .partition(|issue| issue.severity == Severity::Error);
+
+        for issue in &warnings {
+            warn!(section = issue.section, field = issue.field, "Config warning: {}", issue.message);
+        }
+        if !errors.is_empty() {
+            return Err(ConfigValidationError { issues: errors }.into());
+        }
+
+        Ok(())
+    }
+
+    /// Table of enabled features and their key parameters.
+    pub fn startup_report(&self) -> String {
+        validation::startup_report(self)
+    }
+
+    /// The config as JSON with secrets replaced by `[redacted]`.
+    pub fn redacted(&self) -> serde_json::Value {
+        const REDACTED: &str = "[redacted]";
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+
+        if let Some(secrets) = value.pointer_mut("/middleware/auth/jwt_secrets").and_then(|v| v.as_array_mut()) {
+            secrets.iter_mut().for_each(|secret| *secret = REDACTED.into());
+        }
+        if let Some(keys) = value.pointer_mut("/middleware/auth/request_signing/keys").and_then(|v| v.as_array_mut()) {
+            keys.iter_mut()
+                .filter_map(|key| key.get_mut("secret"))
+                .for_each(|secret| *secret = REDACTED.into());
+        }
+        if let Some(keys) = value.pointer_mut("/middleware/auth/api_keys").and_then(|v| v.as_array_mut()) {
+            keys.iter_mut()
+                .filter_map(|key| key.get_mut("key"))
+                .for_each(|key| *key = REDACTED.into());
+        }
+        for pointer in [
+            "/middleware/auth/jwt_secret",
+            "/middleware/auth/introspection/client_secret",
+            "/http_client/proxy/password",
+            "/admin_basic_auth/password_hash",
+            "/privacy/salt",
+        ] {
+            if let Some(secret) = value.pointer_mut(pointer).filter(|v| !v.is_null() && v.as_str() != Some("")) {
+                *secret = REDACTED.into();
+            }
+        }
+        for pointer in ["/http_client/proxy/url", "/canary_rollout/coordination/url"] {
+            if let Some(url) = value.pointer_mut(pointer).filter(|v| v.is_string()) {
+                *url = validation::redact_url(url.as_str().unwrap_or_default()).into();
+            }
+        }
+        if let Some(url) = value.pointer_mut("/canary_rollout/webhook_url").filter(|v| v.is_string()) {
+            *url = redact_webhook_url(url.as_str().unwrap_or_default()).into();
+        }
+        value
+    }
+}
+
+/// A webhook URL with its path and query, which carry the credential,
+/// replaced by `[redacted]`.
+pub fn redact_webhook_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.set_path("[redacted]");
+            parsed.set_query(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
     }
 }
\ No newline at end of file
@@ -0,0 +1,312 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use super::AppConfig;
+
+/// One value the gateway persisted, keyed by its dotted path in the config
+/// (`canary_rollout.rollout_percentage`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayEntry {
+    pub path: String,
+    pub value: Value,
+    /// What the base file held at `path` when the entry was written. Once an
+    /// operator changes that value by hand, their edit wins over the entry.
+    #[serde(default)]
+    pub base_value: Option<Value>,
+    pub updated_by: String,
+    pub updated_at: String,
+}
+
+/// Values applied to `percentage` of clients only, until they are promoted
+/// into the overlay's entries or discarded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StagedChanges {
+    pub percentage: f64,
+    pub entries: Vec<OverlayEntry>,
+}
+
+/// Runtime-owned config values, kept in a file of their own so the gateway
+/// never writes to the file operators edit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OverlayFile {
+    /// SHA-256 of the base file as of the last write.
+    #[serde(default)]
+    pub base_sha256: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<OverlayEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged: Option<StagedChanges>,
+}
+
+impl OverlayFile {
+    /// Reads `path`, treating a missing or empty file as an empty overlay.
+    pub fn read(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) if text.trim().is_empty() => Ok(Self::default()),
+            Ok(text) => serde_yaml::from_str(&text).with_context(|| format!("parsing {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Writes to a temporary file beside `path` and renames it into place, so
+    /// a reader sees either the previous overlay or this one in full.
+    pub fn write_atomic(&self, path: &Path) -> Result<()> {
+        let temp = temp_path(path);
+        let text = serde_yaml::to_string(self)?;
+        let mut file = std::fs::File::create(&temp).with_context(|| format!("creating {}", temp.display()))?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path).with_context(|| format!("replacing {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Replaces the entry for `entry.path`, or adds it.
+    pub fn upsert(&mut self, entry: OverlayEntry) {
+        match self.entries.iter_mut().find(|existing| existing.path == entry.path) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Drops entries whose base value an operator has since changed in
+    /// `base`, returning them.
+    pub fn rebase(&mut self, base: &Value) -> Vec<OverlayEntry> {
+        let (kept, superseded) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|entry| unset_if_null(lookup(base, &entry.path)) == unset_if_null(entry.base_value.as_ref()));
+        self.entries = kept;
+        superseded
+    }
+
+    /// This overlay with its staged entries applied on top, as the staged
+    /// variant sees it.
+    pub fn with_staged(&self) -> Option<OverlayFile> {
+        let staged = self.staged.as_ref()?;
+        let mut merged = OverlayFile {
+            staged: None,
+            ..self.clone()
+        };
+        for entry in &staged.entries {
+            merged.upsert(entry.clone());
+        }
+        Some(merged)
+    }
+
+    /// The entries as one nested YAML document, for merging over the base.
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut root = Value::Mapping(Mapping::new());
+        for entry in &self.entries {
+            let mut node = &mut root;
+            for key in entry.path.split('.') {
+                if !node.is_mapping() {
+                    *node = Value::Mapping(Mapping::new());
+                }
+                let Value::Mapping(mapping) = node else {
+                    unreachable!("just made a mapping");
+                };
+                node = mapping.entry(Value::from(key)).or_insert(Value::Null);
+            }
+            *node = entry.value.clone();
+        }
+        Ok(serde_yaml::to_string(&root)?)
+    }
+}
+
+/// The value at dotted `path` in a YAML tree.
+pub fn lookup<'a>(tree: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(tree, |node, key| node.get(key))
+}
+
+/// A `null` base value reads back from the overlay file as no value at all.
+fn unset_if_null(value: Option<&Value>) -> Option<&Value> {
+    value.filter(|value| !value.is_null())
+}
+
+/// Hex SHA-256 of a file's contents.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Sections whose values may be staged for a share of traffic. Routing and
+/// auth changes stay atomic.
+pub const STAGEABLE_SECTIONS: &[&str] = &["middleware.rate_limiting"];
+
+/// Whether dotted `path` lies within a [`STAGEABLE_SECTIONS`] entry.
+pub fn is_stageable(path: &str) -> bool {
+    STAGEABLE_SECTIONS
+        .iter()
+        .any(|section| path.strip_prefix(section).is_some_and(|rest| rest.is_empty() || rest.starts_with('.')))
+}
+
+/// `overrides.yaml` in the same directory as the config file.
+pub fn default_path(config_path: &str) -> PathBuf {
+    Path::new(config_path).with_file_name("overrides.yaml")
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// A config file with its overlay merged in.
+#[derive(Debug, Clone)]
+pub struct Merged {
+    pub config: AppConfig,
+    /// The overlay as merged, without entries an operator has overridden.
+    pub overlay: OverlayFile,
+    /// The config with the staged entries merged in as well, when any are.
+    pub staged: Option<AppConfig>,
+}
+
+/// Reads the base and overlay files and merges them. Entries an operator
+/// has overridden in the base are left out; the next write removes them. A
+/// staging that no longer loads over the base is left out too, and kept
+/// on disk until promoted or discarded.
+pub fn load(config_path: &Path, overlay_path: &Path) -> Result<Merged> {
+    let base = std::fs::read_to_string(config_path).with_context(|| format!("reading {}", config_path.display()))?;
+    merge(&base, overlay_path).with_context(|| format!("loading {}", config_path.display()))
+}
+
+/// [`load`] with `base` in place of the config file's contents.
+pub fn merge(base: &str, overlay_path: &Path) -> Result<Merged> {
+    let tree: Value = serde_yaml::from_str(base).context("parsing config")?;
+    let mut overlay = OverlayFile::read(overlay_path)?;
+    log_superseded(&overlay.rebase(&tree));
+
+    let config = AppConfig::load_with_overlay(base, &overlay.to_yaml()?)?;
+    let staged = match merge_staged(base, &overlay) {
+        Ok(staged) => staged,
+        Err(e) => {
+            warn!(path = %overlay_path.display(), "Ignoring staged config that no longer loads: {:#}", e);
+            None
+        }
+    };
+    Ok(Merged { config, overlay, staged })
+}
+
+fn merge_staged(base: &str, overlay: &OverlayFile) -> Result<Option<AppConfig>> {
+    overlay
+        .with_staged()
+        .map(|staged| AppConfig::load_with_overlay(base, &staged.to_yaml()?))
+        .transpose()
+}
+
+/// Sets `path` to `value` in the overlay and merges it over the base file
+/// as it is on disk now, so an edit the watcher hasn't reloaded yet is kept
+/// rather than overwritten. The overlay is only written once the merged
+/// config validates.
+pub fn persist(config_path: &Path, overlay_path: &Path, path: &str, value: Value, updated_by: &str) -> Result<Merged> {
+    rewrite(config_path, overlay_path, |overlay, tree| {
+        overlay.upsert(entry(tree, path, value, updated_by));
+        Ok(())
+    })
+}
+
+/// Stages `value` at `path` for `percentage` of clients, alongside anything
+/// already staged, and sets the staged share. Only paths within
+/// [`STAGEABLE_SECTIONS`] may be staged.
+pub fn stage(
+    config_path: &Path,
+    overlay_path: &Path,
+    path: &str,
+    value: Value,
+    percentage: f64,
+    updated_by: &str,
+) -> Result<Merged> {
+    if !is_stageable(path) {
+        bail!("{} can't be staged; only {} can", path, STAGEABLE_SECTIONS.join(", "));
+    }
+    if !(0.0..=100.0).contains(&percentage) {
+        bail!("staged percentage must be between 0 and 100");
+    }
+    rewrite(config_path, overlay_path, |overlay, tree| {
+        let staged = overlay.staged.get_or_insert_with(|| StagedChanges {
+            percentage,
+            entries: Vec::new(),
+        });
+        staged.percentage = percentage;
+        let entry = entry(tree, path, value, updated_by);
+        match staged.entries.iter_mut().find(|existing| existing.path == entry.path) {
+            Some(existing) => *existing = entry,
+            None => staged.entries.push(entry),
+        }
+        Ok(())
+    })
+}
+
+/// Makes the staged entries ordinary overlay entries, for all traffic.
+pub fn promote_staged(config_path: &Path, overlay_path: &Path, updated_by: &str) -> Result<Merged> {
+    rewrite(config_path, overlay_path, |overlay, tree| {
+        let staged = overlay.staged.take().context("nothing is staged")?;
+        for staged in staged.entries {
+            overlay.upsert(entry(tree, &staged.path, staged.value, updated_by));
+        }
+        Ok(())
+    })
+}
+
+/// Drops the staged entries.
+pub fn discard_staged(config_path: &Path, overlay_path: &Path) -> Result<Merged> {
+    rewrite(config_path, overlay_path, |overlay, _| {
+        overlay.staged.take().context("nothing is staged")?;
+        Ok(())
+    })
+}
+
+fn entry(tree: &Value, path: &str, value: Value, updated_by: &str) -> OverlayEntry {
+    OverlayEntry {
+        path: path.to_string(),
+        value,
+        base_value: lookup(tree, path).cloned(),
+        updated_by: updated_by.to_string(),
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// Applies `change` to the overlay over the base file as it is on disk,
+/// and writes it once both the merged and any staged config validate.
+fn rewrite(
+    config_path: &Path,
+    overlay_path: &Path,
+    change: impl FnOnce(&mut OverlayFile, &Value) -> Result<()>,
+) -> Result<Merged> {
+    let base = std::fs::read_to_string(config_path).with_context(|| format!("reading {}", config_path.display()))?;
+    let tree: Value = serde_yaml::from_str(&base).with_context(|| format!("parsing {}", config_path.display()))?;
+    let base_sha256 = content_hash(base.as_bytes());
+
+    let mut overlay = OverlayFile::read(overlay_path)?;
+    if overlay.base_sha256.as_ref().is_some_and(|recorded| *recorded != base_sha256) {
+        info!(
+            event = "config_base_changed",
+            path = %config_path.display(),
+            "Config file changed since the last overlay write; merging over the new contents"
+        );
+    }
+    log_superseded(&overlay.rebase(&tree));
+    change(&mut overlay, &tree)?;
+    overlay.base_sha256 = Some(base_sha256);
+
+    let config = AppConfig::load_with_overlay(&base, &overlay.to_yaml()?)?;
+    let staged = merge_staged(&base, &overlay).context("staged config")?;
+    overlay.write_atomic(overlay_path)?;
+    Ok(Merged { config, overlay, staged })
+}
+
+fn log_superseded(entries: &[OverlayEntry]) {
+    for entry in entries {
+        info!(
+            event = "config_overlay_superseded",
+            path = %entry.path,
+            updated_by = %entry.updated_by,
+            "Config file now sets this value by hand; dropping the persisted override"
+        );
+    }
+}
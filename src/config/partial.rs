@@ -0,0 +1,106 @@
+//! Loading what can be loaded of a config file that fails to, for
+//! `config_reload.mode: partial`.
+//!
+//! The file is split at its top-level keys and each section is tried on its
+//! own, in place of the same section of the last config that loaded. Sections
+//! that load are applied; the others keep their previous values. Splitting
+//! works on the text, so a section broken badly enough that the file isn't
+//! YAML at all is still isolated from the rest.
+
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+use super::AppConfig;
+
+/// A file with its broken sections replaced by their previous values.
+#[derive(Debug, Clone)]
+pub struct Salvaged {
+    pub document: String,
+    /// Sections taken from the file, in file order.
+    pub applied: Vec<String>,
+    /// Sections that didn't load and kept their previous values.
+    pub retained: Vec<String>,
+}
+
+/// The top-level sections of `document` with their text, in file order. A
+/// section runs from the line naming its key to the next such line, so
+/// comments and stray lines between sections belong to the one above. Text
+/// before the first key is dropped; a key given twice gets both texts.
+fn sections(document: &str) -> Vec<(String, String)> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    let mut current: Option<usize> = None;
+    for line in document.lines() {
+        if let Some(key) = top_level_key(line) {
+            current = Some(match sections.iter().position(|(name, _)| *name == key) {
+                Some(index) => index,
+                None => {
+                    sections.push((key, String::new()));
+                    sections.len() - 1
+                }
+            });
+        }
+        if let Some(index) = current {
+            sections[index].1.push_str(line);
+            sections[index].1.push('\n');
+        }
+    }
+    sections
+}
+
+/// The key a line starting a top-level entry names.
+fn top_level_key(line: &str) -> Option<String> {
+    let first = line.chars().next()?;
+    if first.is_whitespace() || matches!(first, '#' | '-' | '.' | '{' | '[') {
+        return None;
+    }
+    let (key, _) = line.split_once(':')?;
+    let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+/// The value `text` gives `key`, if it's YAML giving nothing else.
+fn section_value(key: &str, text: &str) -> Option<Value> {
+    let mut mapping: Mapping = serde_yaml::from_str(text).ok()?;
+    let value = mapping.remove(key)?;
+    mapping.is_empty().then_some(value)
+}
+
+/// Builds a document from `document`, whose full load failed, taking each
+/// section from it that loads in place of the same section of `previous`
+/// and the rest from `previous`. Sections missing from `document` are left
+/// out, as a full load would. Fails when no section loads.
+pub fn salvage(document: &str, previous: &str) -> Result<Salvaged> {
+    let previous: Mapping = serde_yaml::from_str(previous).context("parsing the previous config")?;
+
+    let mut salvaged = Mapping::new();
+    let (mut applied, mut retained) = (Vec::new(), Vec::new());
+    for (key, text) in sections(document) {
+        let name = Value::String(key.clone());
+        let loads = section_value(&key, &text).filter(|value| {
+            let mut trial = previous.clone();
+            trial.insert(name.clone(), value.clone());
+            serde_yaml::to_string(&trial).is_ok_and(|trial| AppConfig::parse_str(&trial).is_ok())
+        });
+        match loads {
+            Some(value) => {
+                salvaged.insert(name, value);
+                applied.push(key);
+            }
+            None => {
+                if let Some(value) = previous.get(&name) {
+                    salvaged.insert(name, value.clone());
+                }
+                retained.push(key);
+            }
+        }
+    }
+    if applied.is_empty() {
+        bail!("no section of the file loads");
+    }
+
+    Ok(Salvaged {
+        document: serde_yaml::to_string(&salvaged)?,
+        applied,
+        retained,
+    })
+}
@@ -0,0 +1,283 @@
+//! Weekly time windows such as `"Mon-Fri 22:00-06:00"` and cron schedules
+//! such as `"30 2 * * *"`, evaluated in a fixed UTC offset such as
+//! `"+02:00"`.
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, FixedOffset, Timelike, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, ops::Range, str::FromStr};
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// A daily time range on some days of the week. A range whose end is before
+/// its start runs past midnight and belongs to the day it starts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeWindow {
+    source: String,
+    /// Monday first.
+    days: [bool; 7],
+    start: u32,
+    end: u32,
+}
+
+fn parse_day(name: &str) -> Result<usize, String> {
+    DAY_NAMES
+        .iter()
+        .position(|day| name.trim().to_ascii_lowercase().starts_with(day) && name.trim().len() >= 3)
+        .ok_or_else(|| format!("unknown day {:?} (use Mon..Sun)", name))
+}
+
+fn parse_days(input: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse_day(from)?, parse_day(to)?);
+                // Ranges may wrap, e.g. Fri-Mon
+                let mut day = from;
+                loop {
+                    days[day] = true;
+                    if day == to {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => days[parse_day(part)?] = true,
+        }
+    }
+    Ok(days)
+}
+
+fn parse_time(input: &str, allow_midnight_end: bool) -> Result<u32, String> {
+    let (hours, minutes) = input
+        .split_once(':')
+        .ok_or_else(|| format!("{:?} is not a HH:MM time", input))?;
+    let hours: u32 = hours.parse().map_err(|_| format!("{:?} is not a HH:MM time", input))?;
+    let minutes: u32 = minutes.parse().map_err(|_| format!("{:?} is not a HH:MM time", input))?;
+    let valid = minutes < 60 && (hours < 24 || (allow_midnight_end && hours == 24 && minutes == 0));
+    if !valid || input.len() != 5 {
+        return Err(format!("{:?} is not a HH:MM time", input));
+    }
+    Ok(hours * 60 + minutes)
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let trimmed = input.trim();
+        let (days, times) = match trimmed.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_days(days.trim())?, times),
+            None => ([true; 7], trimmed),
+        };
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("{:?} is not a [days] HH:MM-HH:MM window", input))?;
+        let (start, end) = (parse_time(start, false)?, parse_time(end, true)?);
+        if start == end % MINUTES_PER_DAY && end != MINUTES_PER_DAY {
+            return Err(format!("window {:?} is empty", input));
+        }
+        Ok(Self {
+            source: trimmed.to_string(),
+            days,
+            start,
+            end,
+        })
+    }
+}
+
+impl TimeWindow {
+    /// Minute-of-week ranges covered, Monday 00:00 being 0. A window running
+    /// past Sunday midnight is split in two.
+    fn ranges(&self) -> Vec<Range<u32>> {
+        let length = if self.end > self.start {
+            self.end - self.start
+        } else {
+            MINUTES_PER_DAY - self.start + self.end
+        };
+        let mut ranges = Vec::new();
+        for (day, _) in self.days.iter().enumerate().filter(|(_, active)| **active) {
+            let start = day as u32 * MINUTES_PER_DAY + self.start;
+            let end = start + length;
+            if end > MINUTES_PER_WEEK {
+                ranges.push(start..MINUTES_PER_WEEK);
+                ranges.push(0..end - MINUTES_PER_WEEK);
+            } else {
+                ranges.push(start..end);
+            }
+        }
+        ranges
+    }
+
+    /// Whether `now`, seen at `offset`, falls inside the window.
+    pub fn contains(&self, now: DateTime<Utc>, offset: FixedOffset) -> bool {
+        let local = now.with_timezone(&offset);
+        let minute = local.weekday().num_days_from_monday() * MINUTES_PER_DAY + local.hour() * 60 + local.minute();
+        self.ranges().iter().any(|range| range.contains(&minute))
+    }
+
+    pub fn overlaps(&self, other: &TimeWindow) -> bool {
+        self.ranges()
+            .iter()
+            .any(|a| other.ranges().iter().any(|b| a.start < b.end && b.start < a.end))
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for TimeWindow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeWindow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+/// A five-field cron schedule: minute, hour, day of month, month and day of
+/// week (0 or 7 for Sunday). Fields take `*`, numbers, `a-b` ranges, `/n`
+/// steps and comma lists; names such as `MON` aren't supported. As in cron,
+/// a day matches when either day field does if both are restricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// Bitmask of the values a cron field allows.
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("{:?} is not a cron field for {}-{}", field, min, max);
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().map_err(|_| invalid())?, to.parse().map_err(|_| invalid())?),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // `5/15` runs from 5 to the end of the range
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(invalid());
+        }
+        for value in (from..=to).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = input.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(format!("{:?} is not a five-field cron schedule", input));
+        };
+        let mut days_of_week_mask = parse_cron_field(days_of_week, 0, 7)?;
+        if days_of_week_mask & (1 << 7) != 0 {
+            days_of_week_mask |= 1;
+        }
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: parse_cron_field(minutes, 0, 59)?,
+            hours: parse_cron_field(hours, 0, 23)?,
+            days_of_month: parse_cron_field(days_of_month, 1, 31)?,
+            months: parse_cron_field(months, 1, 12)?,
+            days_of_week: days_of_week_mask,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Whether the schedule fires in the minute of `time`.
+    pub fn matches<Tz: chrono::TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day_of_month = bit(self.days_of_month, time.day());
+        let day_of_week = bit(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        day && bit(self.minutes, time.minute()) && bit(self.hours, time.hour()) && bit(self.months, time.month())
+    }
+
+    /// The latest time at or before `now`, and no more than `lookback`
+    /// earlier, at which the schedule fired when read at `offset`.
+    pub fn latest_at_or_before(&self, now: DateTime<Utc>, lookback: Duration, offset: FixedOffset) -> Option<DateTime<Utc>> {
+        let latest = now.duration_trunc(Duration::minutes(1)).ok()?;
+        let mut minute = latest;
+        while now - minute <= lookback {
+            if self.matches(&minute.with_timezone(&offset)) {
+                return Some(minute);
+            }
+            minute -= Duration::minutes(1);
+        }
+        None
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for CronSchedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Parses `UTC`, `Z`, or a `+HH:MM` / `-HH:MM` offset.
+pub fn parse_utc_offset(input: &str) -> Option<FixedOffset> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("utc") || input.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match input.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = input[1..].split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
@@ -0,0 +1,57 @@
+//! Config changes tried on a share of traffic before they apply to all of
+//! it.
+//!
+//! Values in a [`STAGEABLE_SECTIONS`](super::overlay::STAGEABLE_SECTIONS)
+//! section can be staged in the overlay file with a percentage. Each client
+//! is hashed into a bucket in `[0, 100)`; clients in a bucket below the
+//! percentage get the staged values on every request, the rest the current
+//! ones, so both behaviours run side by side and a client never flips
+//! between them. Promoting makes the staged values ordinary overlay
+//! entries; discarding drops them.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{overlay::OverlayEntry, AppConfig};
+use crate::util::hash::unit_interval;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigVariant {
+    Current,
+    Staged,
+}
+
+impl ConfigVariant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigVariant::Current => "current",
+            ConfigVariant::Staged => "staged",
+        }
+    }
+}
+
+/// Where `key` falls in `[0, 100)`; the same key always lands in the same
+/// bucket, on every replica.
+pub fn bucket(key: &str) -> f64 {
+    unit_interval(&[key]) * 100.0
+}
+
+/// Staged values in force, and the config they make.
+#[derive(Debug, Clone)]
+pub struct StagedConfig {
+    pub percentage: f64,
+    pub entries: Vec<OverlayEntry>,
+    /// The current config with the staged values merged in.
+    pub config: AppConfig,
+}
+
+impl StagedConfig {
+    pub fn variant_for(&self, key: &str) -> ConfigVariant {
+        if bucket(key) < self.percentage {
+            ConfigVariant::Staged
+        } else {
+            ConfigVariant::Current
+        }
+    }
+}
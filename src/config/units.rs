@@ -0,0 +1,312 @@
+//! Config values with units: durations such as `"250ms"` or `"2m"` and sizes
+//! such as `"64KiB"`.
+//!
+//! Bare numbers are still accepted in the unit the field used to be named
+//! after (`timeout_seconds: 30`, `timeout_ms: 5000`) and are flagged so
+//! validation can warn about them. Serialization always emits the unit form.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// A duration read from config, with millisecond precision.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HumanDuration {
+    value: Duration,
+    legacy_numeric: bool,
+}
+
+impl HumanDuration {
+    pub const fn from_millis(millis: u64) -> Self {
+        Self {
+            value: Duration::from_millis(millis),
+            legacy_numeric: false,
+        }
+    }
+
+    pub const fn from_secs(secs: u64) -> Self {
+        Self {
+            value: Duration::from_secs(secs),
+            legacy_numeric: false,
+        }
+    }
+
+    pub fn get(&self) -> Duration {
+        self.value
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+
+    /// Whether the config gave a bare number in the field's legacy unit.
+    pub fn is_legacy_numeric(&self) -> bool {
+        self.legacy_numeric
+    }
+}
+
+impl PartialEq for HumanDuration {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl From<HumanDuration> for Duration {
+    fn from(duration: HumanDuration) -> Self {
+        duration.value
+    }
+}
+
+const DURATION_UNITS: [(&str, u64); 5] = [
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("m", 60_000),
+    ("s", 1_000),
+    ("ms", 1),
+];
+
+impl FromStr for HumanDuration {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_unit(input)?;
+        let scale = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| unit.eq_ignore_ascii_case(name))
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| format!("unknown duration unit {:?} in {:?} (use ms, s, m, h, or d)", unit, input))?;
+        let millis = scaled(number, scale, input)?;
+        Ok(Self::from_millis(millis))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = self.value.as_millis() as u64;
+        if millis == 0 {
+            return f.write_str("0s");
+        }
+        let (unit, scale) = DURATION_UNITS
+            .iter()
+            .find(|(_, scale)| millis.is_multiple_of(*scale))
+            .copied()
+            .unwrap_or(("ms", 1));
+        write!(f, "{}{}", millis / scale, unit)
+    }
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Requires a unit; fields that used to be plain numbers use
+/// [`legacy_seconds`] or [`legacy_millis`] instead.
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DurationVisitor { legacy_scale: None })
+    }
+}
+
+/// Deserializes a duration, reading bare numbers as seconds.
+pub fn legacy_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HumanDuration, D::Error> {
+    deserializer.deserialize_any(DurationVisitor { legacy_scale: Some(1_000) })
+}
+
+/// Deserializes a duration, reading bare numbers as milliseconds.
+pub fn legacy_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HumanDuration, D::Error> {
+    deserializer.deserialize_any(DurationVisitor { legacy_scale: Some(1) })
+}
+
+struct DurationVisitor {
+    /// Milliseconds per unit for bare numbers; `None` rejects them.
+    legacy_scale: Option<u64>,
+}
+
+impl DurationVisitor {
+    fn legacy<E: de::Error>(&self, number: f64, input: &dyn fmt::Display) -> Result<HumanDuration, E> {
+        let scale = self
+            .legacy_scale
+            .ok_or_else(|| E::custom(format!("duration {} needs a unit, e.g. \"{}s\"", input, input)))?;
+        let millis = scaled(number, scale, &input.to_string()).map_err(E::custom)?;
+        Ok(HumanDuration {
+            value: Duration::from_millis(millis),
+            legacy_numeric: true,
+        })
+    }
+}
+
+impl<'de> de::Visitor<'de> for DurationVisitor {
+    type Value = HumanDuration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a duration such as \"250ms\", \"30s\", or \"2m\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        self.legacy(value as f64, &value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        self.legacy(value as f64, &value)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        self.legacy(value, &value)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match value.trim().parse::<f64>() {
+            Ok(number) => self.legacy(number, &value.trim()),
+            Err(_) => value.parse().map_err(E::custom),
+        }
+    }
+}
+
+/// A size in bytes read from config.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteSize {
+    bytes: u64,
+    legacy_numeric: bool,
+}
+
+impl ByteSize {
+    pub const fn from_bytes(bytes: u64) -> Self {
+        Self {
+            bytes,
+            legacy_numeric: false,
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Whether the config gave a bare number of bytes.
+    pub fn is_legacy_numeric(&self) -> bool {
+        self.legacy_numeric
+    }
+}
+
+impl PartialEq for ByteSize {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+const SIZE_UNITS: [(&str, u64); 7] = [
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_unit(input)?;
+        let scale = SIZE_UNITS
+            .iter()
+            .find(|(name, _)| unit.eq_ignore_ascii_case(name))
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| format!("unknown size unit {:?} in {:?} (use B, KB, KiB, MB, MiB, GB, or GiB)", unit, input))?;
+        Ok(Self::from_bytes(scaled(number, scale, input)?))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, scale) = SIZE_UNITS
+            .iter()
+            .find(|(_, scale)| self.bytes != 0 && self.bytes.is_multiple_of(*scale))
+            .copied()
+            .unwrap_or(("B", 1));
+        write!(f, "{}{}", self.bytes / scale, unit)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts `"64KiB"`-style strings, or a bare number of bytes.
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+struct ByteSizeVisitor;
+
+impl ByteSizeVisitor {
+    fn legacy<E: de::Error>(number: f64, input: &dyn fmt::Display) -> Result<ByteSize, E> {
+        let bytes = scaled(number, 1, &input.to_string()).map_err(E::custom)?;
+        Ok(ByteSize {
+            bytes,
+            legacy_numeric: true,
+        })
+    }
+}
+
+impl<'de> de::Visitor<'de> for ByteSizeVisitor {
+    type Value = ByteSize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a size such as \"512KiB\" or \"5MiB\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Self::legacy(value as f64, &value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Self::legacy(value as f64, &value)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Self::legacy(value, &value)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match value.trim().parse::<f64>() {
+            Ok(number) => Self::legacy(number, &value.trim()),
+            Err(_) => value.parse().map_err(E::custom),
+        }
+    }
+}
+
+/// Splits `"1.5 MiB"` into its number and unit.
+fn split_unit(input: &str) -> Result<(f64, &str), String> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .ok_or_else(|| format!("{:?} is missing a unit", input))?;
+    let (number, unit) = trimmed.split_at(split);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("{:?} does not start with a number", input))?;
+    Ok((number, unit.trim()))
+}
+
+/// `number * scale`, rejecting negative, overflowing, and sub-unit results.
+fn scaled(number: f64, scale: u64, input: &str) -> Result<u64, String> {
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("{:?} must not be negative", input));
+    }
+    let value = number * scale as f64;
+    if value >= u64::MAX as f64 {
+        return Err(format!("{:?} is too large", input));
+    }
+    // Tolerate float noise such as 1.1 * 1000 = 1100.0000000000002
+    let rounded = value.round();
+    if (value - rounded).abs() > 1e-6 {
+        return Err(format!("{:?} is finer than the smallest supported unit", input));
+    }
+    Ok(rounded as u64)
+}
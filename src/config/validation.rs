@@ -0,0 +1,1177 @@
+use serde::Serialize;
+use std::fmt;
+
+use super::{normalize_base_path, AppConfig, CookieSameSite};
+use crate::util::backoff::RetryPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Logged at startup and reload; never blocks.
+    Warning,
+    /// Blocks startup and causes a reload to be rejected.
+    Error,
+}
+
+/// A single consistency problem found in a config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    pub section: &'static str,
+    pub field: &'static str,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}: {}", self.section, self.field, self.message)
+    }
+}
+
+/// Error-severity issues that make a config unusable.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid configuration: {}", summarize(.issues))]
+pub struct ConfigValidationError {
+    pub issues: Vec<ConfigIssue>,
+}
+
+fn summarize(issues: &[ConfigIssue]) -> String {
+    issues.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+    fn error(&mut self, section: &'static str, field: &'static str, message: impl Into<String>) {
+        self.push(section, field, message, Severity::Error);
+    }
+
+    fn warning(&mut self, section: &'static str, field: &'static str, message: impl Into<String>) {
+        self.push(section, field, message, Severity::Warning);
+    }
+
+    fn push(&mut self, section: &'static str, field: &'static str, message: impl Into<String>, severity: Severity) {
+        self.0.push(ConfigIssue {
+            section,
+            field,
+            message: message.into(),
+            severity,
+        });
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .map(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+        .unwrap_or(false)
+}
+
+/// Drops any credentials embedded in a URL.
+pub fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Cross-field and cross-section consistency checks.
+fn check_header_size_limits(
+    issues: &mut Issues,
+    section: &'static str,
+    limits: &std::collections::BTreeMap<String, super::ByteSize>,
+) {
+    for (name, limit) in limits {
+        if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            issues.error(section, "header_size_limits", format!("{:?} is not a valid header name", name));
+        } else if limit.bytes() == 0 {
+            issues.error(section, "header_size_limits", format!("limit for {} must be greater than zero", name));
+        }
+    }
+}
+
+fn check_retry_policy(issues: &mut Issues, section: &'static str, field: &'static str, policy: &RetryPolicy) {
+    if !(policy.multiplier >= 1.0 && policy.multiplier.is_finite()) {
+        issues.error(section, field, "multiplier must be at least 1");
+    }
+    if policy.max_delay.get() < policy.initial_delay.get() {
+        issues.error(section, field, "max_delay must be at least initial_delay");
+    }
+}
+
+/// Finding the maintenance occurrence in force scans back minute by minute
+/// over the window's duration, so windows are capped at a week.
+const MAX_MAINTENANCE_WINDOW: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 3600);
+
+/// Tolerating more drift than this would accept tokens long after expiry.
+const MAX_CLOCK_SKEW_TOLERANCE: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// The failure log is only for eyeballing recent denials.
+const MAX_AUTH_FAILURE_LOG_SIZE: usize = 10_000;
+
+pub fn check(config: &AppConfig) -> Vec<ConfigIssue> {
+    let mut issues = Issues(Vec::new());
+
+    let server = &config.server;
+    if server.timeout.is_zero() {
+        issues.error("server", "timeout", "must be greater than zero");
+    }
+    if server.max_header_bytes.bytes() == 0 {
+        issues.error("server", "max_header_bytes", "must be greater than zero");
+    }
+    if server.max_header_count == 0 {
+        issues.error("server", "max_header_count", "must be greater than zero");
+    }
+    if server.max_body_bytes.bytes() == 0 {
+        issues.error("server", "max_body_bytes", "must be greater than zero");
+    }
+    check_header_size_limits(&mut issues, "server", &server.header_size_limits);
+    let legacy_limits = &config.canary_rollout.legacy_header_limits;
+    if legacy_limits.max_header_bytes.is_some_and(|max| max.bytes() == 0) {
+        issues.error("canary_rollout.legacy_header_limits", "max_header_bytes", "must be greater than zero");
+    }
+    if legacy_limits.max_header_count == Some(0) {
+        issues.error("canary_rollout.legacy_header_limits", "max_header_count", "must be greater than zero");
+    }
+    check_header_size_limits(
+        &mut issues,
+        "canary_rollout.legacy_header_limits",
+        &config.canary_rollout.legacy_header_limits.header_size_limits,
+    );
+    if server.queue_timeout.is_zero() {
+        issues.error("server", "queue_timeout", "must be greater than zero");
+    }
+    if server.queue_timeout.get() > server.timeout.get() {
+        issues.warning(
+            "server",
+            "queue_timeout",
+            format!(
+                "{} exceeds the {} request timeout, so it will never fire",
+                server.queue_timeout, server.timeout
+            ),
+        );
+    }
+    if normalize_base_path(&server.public_base_path).is_none() {
+        issues.error("server", "public_base_path", "must be a plain path such as /gateway");
+    }
+    if let Some(public_url) = &server.public_url {
+        if !is_http_url(public_url) {
+            issues.error("server", "public_url", format!("{:?} is not an http(s) URL", public_url));
+        }
+    }
+
+    if let Some(tls) = server.tls.as_ref().filter(|tls| tls.enabled) {
+        if tls.cert_path.is_empty() || tls.key_path.is_empty() {
+            issues.error("server.tls", "cert_path", "TLS is enabled but the default certificate or key path is empty");
+        }
+        if tls.client_auth.as_ref().is_some_and(|client_auth| client_auth.ca_path.is_empty()) {
+            issues.error("server.tls", "client_auth", "client certificate verification needs a ca_path");
+        }
+        for entry in &tls.certificates {
+            if entry.sni_hosts.is_empty() {
+                issues.error(
+                    "server.tls",
+                    "certificates",
+                    format!("certificate {} has no sni_hosts", entry.cert_path),
+                );
+            }
+        }
+    }
+
+    let client_auth = server
+        .tls
+        .as_ref()
+        .is_some_and(|tls| tls.enabled && tls.client_auth.is_some());
+    if !client_auth {
+        let forwarding = [
+            ("canary_rollout", config.canary_rollout.client_cert_forwarding),
+            ("mirror", config.mirror.client_cert_forwarding),
+        ];
+        for (section, mode) in forwarding {
+            if mode != super::ClientCertForwarding::Off {
+                issues.warning(
+                    section,
+                    "client_cert_forwarding",
+                    "server.tls.client_auth is not configured, so there is no client identity to forward",
+                );
+            }
+        }
+        if config.middleware.auth.client_certificates {
+            issues.warning(
+                "middleware.auth",
+                "client_certificates",
+                "server.tls.client_auth is not configured, so no request carries a client certificate",
+            );
+        }
+    }
+
+    if config.memory.budget.bytes() == 0 {
+        issues.error("memory", "budget", "must be greater than zero");
+    }
+    if !(0.0..=100.0).contains(&config.memory.evict_to_percentage) {
+        issues.error("memory", "evict_to_percentage", "must be between 0 and 100");
+    }
+
+    let versioning = &config.versioning;
+    if versioning.enabled {
+        if normalize_base_path(&versioning.base_path).is_none_or(|base| base.is_empty()) {
+            issues.error("versioning", "base_path", "must be a plain path such as /api");
+        }
+        if axum::http::HeaderName::from_bytes(versioning.header.as_bytes()).is_err() {
+            issues.error("versioning", "header", format!("{:?} is not a valid header name", versioning.header));
+        }
+        if versioning.version(&versioning.default_version).is_none() {
+            issues.error(
+                "versioning",
+                "default_version",
+                format!("{:?} is not one of the configured versions", versioning.default_version),
+            );
+        }
+        let mut seen = std::collections::HashSet::new();
+        for version in &versioning.versions {
+            let valid = version
+                .name
+                .strip_prefix('v')
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()) && !rest.contains('/'));
+            if !valid {
+                issues.error("versioning", "versions", format!("{:?} must be v and a number, such as v2", version.name));
+            }
+            if !seen.insert(version.name.as_str()) {
+                issues.error("versioning", "versions", format!("{:?} is listed twice", version.name));
+            }
+            if let (Some(deprecated), Some(sunset)) = (version.deprecated, version.sunset) {
+                if sunset < deprecated {
+                    issues.warning(
+                        "versioning",
+                        "versions",
+                        format!("{} sunsets on {}, before its deprecation on {}", version.name, sunset, deprecated),
+                    );
+                }
+            }
+        }
+    }
+
+    let profiling = &config.profiling;
+    if profiling.enabled && !cfg!(feature = "profiling") {
+        issues.warning(
+            "profiling",
+            "enabled",
+            "this build lacks the profiling feature, so no profiles are captured",
+        );
+    }
+    if profiling.max_profiles == 0 {
+        issues.error("profiling", "max_profiles", "must be greater than zero");
+    }
+    if profiling.start_budget.is_zero() {
+        issues.error("profiling", "start_budget", "must be greater than zero");
+    }
+    for entry in &profiling.trusted_networks {
+        if let Err(e) = crate::profiling::parse_network(entry) {
+            issues.error("profiling", "trusted_networks", format!("{:#}", e));
+        }
+    }
+
+    let docs = &config.docs;
+    for entry in &docs.allowed_cidrs {
+        if let Err(e) = crate::profiling::parse_network(entry) {
+            issues.error("docs", "allowed_cidrs", format!("{:#}", e));
+        }
+    }
+    if docs.require_auth && config.middleware.auth.jwt_secrets.is_empty() && config.middleware.auth.jwks_url.is_none() {
+        issues.error("docs", "require_auth", "no JWT secret or jwks_url is configured to verify tokens with");
+    }
+
+    if let Some(basic) = &config.admin_basic_auth {
+        if basic.username.is_empty() || basic.username.contains(':') {
+            issues.error("admin_basic_auth", "username", "must be non-empty and not contain ':'");
+        }
+        if !crate::middleware::basic_auth::is_supported_hash(&basic.password_hash) {
+            issues.error("admin_basic_auth", "password_hash", "must be a bcrypt or argon2 hash");
+        }
+        if basic.realm.contains('"') {
+            issues.error("admin_basic_auth", "realm", "must not contain '\"'");
+        }
+        if docs.require_auth {
+            issues.error(
+                "admin_basic_auth",
+                "password_hash",
+                "docs.require_auth also wants the Authorization header; use one or the other",
+            );
+        }
+    }
+
+    let notifications = &config.notifications;
+    if notifications.max_per_window == 0 {
+        issues.error("notifications", "max_per_window", "must be at least 1");
+    }
+    if notifications.window.is_zero() {
+        issues.error("notifications", "window", "must be greater than zero");
+    }
+    if notifications.digest_interval.is_zero() {
+        issues.error("notifications", "digest_interval", "must be greater than zero");
+    }
+    check_retry_policy(&mut issues, "notifications", "retry", &notifications.retry);
+
+    let slo = &config.slo;
+    if slo.fast_burn_threshold <= 0.0 {
+        issues.error("slo", "fast_burn_threshold", "must be greater than zero");
+    }
+    if slo.evaluation_interval.is_zero() {
+        issues.error("slo", "evaluation_interval", "must be greater than zero");
+    }
+    let in_range = |target: f64| target > 0.0 && target < 100.0;
+    let mut seen = std::collections::HashSet::new();
+    for objective in &slo.objectives {
+        if objective.name.is_empty() {
+            issues.error("slo", "objectives", "every objective needs a name");
+        } else if !seen.insert(objective.name.as_str()) {
+            issues.error("slo", "objectives", format!("{:?} is listed twice", objective.name));
+        }
+        if objective.availability.is_none() && objective.latency.is_none() {
+            issues.error("slo", "objectives", format!("{} sets neither availability nor latency", objective.name));
+        }
+        if objective.availability.is_some_and(|target| !in_range(target)) {
+            issues.error("slo", "objectives", format!("{}: availability must be between 0 and 100", objective.name));
+        }
+        if let Some(latency) = &objective.latency {
+            if latency.threshold.is_zero() {
+                issues.error("slo", "objectives", format!("{}: latency threshold must be greater than zero", objective.name));
+            }
+            if !in_range(latency.percentile) {
+                issues.error("slo", "objectives", format!("{}: latency percentile must be between 0 and 100", objective.name));
+            }
+        }
+        if objective.window.is_zero() {
+            issues.error("slo", "objectives", format!("{}: window must be greater than zero", objective.name));
+        }
+    }
+
+    let is_route_selector = |selector: &str| {
+        let (method, pattern) = super::split_route_selector(selector);
+        pattern.starts_with('/') && method.is_none_or(|method| axum::http::Method::from_bytes(method.as_bytes()).is_ok())
+    };
+    for window in &config.maintenance_windows {
+        if !is_route_selector(&window.route_selector) {
+            issues.error(
+                "maintenance_windows",
+                "route_selector",
+                format!("{:?} is not a [METHOD ]/path selector", window.route_selector),
+            );
+        }
+        if window.duration.is_zero() {
+            issues.error("maintenance_windows", "duration", format!("{}: must be greater than zero", window.route_selector));
+        } else if window.duration.get() > MAX_MAINTENANCE_WINDOW {
+            issues.error("maintenance_windows", "duration", format!("{}: must be at most 7d", window.route_selector));
+        }
+        if super::schedule::parse_utc_offset(&window.timezone).is_none() {
+            issues.error(
+                "maintenance_windows",
+                "timezone",
+                format!("{:?} is not UTC or a +HH:MM offset", window.timezone),
+            );
+        }
+    }
+
+    for selector in &config.read_only.exempt_routes {
+        if !is_route_selector(selector) {
+            issues.error("read_only", "exempt_routes", format!("{:?} is not a [METHOD ]/path selector", selector));
+        }
+    }
+    if config.read_only.retry_after.is_zero() {
+        issues.error("read_only", "retry_after", "must be greater than zero");
+    }
+    if config.config_reload.max_file_size.bytes() == 0 {
+        issues.error("config_reload", "max_file_size", "must be greater than zero");
+    }
+    if config.handoff.max_snapshot_age.is_zero() {
+        issues.error("handoff", "max_snapshot_age", "must be greater than zero");
+    }
+
+    let csrf = &config.middleware.csrf;
+    let is_token = |name: &str| !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    if !is_token(&csrf.cookie_name) {
+        issues.error("middleware.csrf", "cookie_name", format!("{:?} is not a valid cookie name", csrf.cookie_name));
+    }
+    if axum::http::HeaderName::from_bytes(csrf.header_name.as_bytes()).is_err() {
+        issues.error("middleware.csrf", "header_name", format!("{:?} is not a valid header name", csrf.header_name));
+    }
+    if csrf.token_lifetime.is_zero() {
+        issues.error("middleware.csrf", "token_lifetime", "must be greater than zero");
+    }
+    for origin in &csrf.allowed_origins {
+        if crate::middleware::csrf::origin_of(origin).is_none_or(|parsed| parsed != origin.trim_end_matches('/').to_ascii_lowercase()) {
+            issues.error("middleware.csrf", "allowed_origins", format!("{:?} is not an origin like https://app.example.com", origin));
+        }
+    }
+    let origin_mode = csrf.routes.iter().any(|entry| entry.mode == super::CsrfMode::Origin);
+    if csrf.enabled && origin_mode && csrf.allowed_origins.is_empty() {
+        issues.warning(
+            "middleware.csrf",
+            "allowed_origins",
+            "routes in origin mode reject every unsafe request while no origins are allowed",
+        );
+    }
+
+    let request_id = &config.middleware.request_id;
+    if axum::http::HeaderName::from_bytes(request_id.header.as_bytes()).is_err() {
+        issues.error("middleware.request_id", "header", format!("{:?} is not a valid header name", request_id.header));
+    }
+
+    let logging = &config.middleware.logging;
+    for header in &logging.redact_headers {
+        if axum::http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+            issues.error("middleware.logging", "redact_headers", format!("{:?} is not a valid header name", header));
+        }
+    }
+    for prefix in logging.route_levels.keys() {
+        if !prefix.starts_with('/') {
+            issues.error("middleware.logging", "route_levels", format!("{:?} must start with /", prefix));
+        }
+    }
+    if !(0.0..=1.0).contains(&logging.sample_rate) {
+        issues.error("middleware.logging", "sample_rate", "must be between 0 and 1");
+    }
+    for field in &logging.redact_json_fields {
+        if field.split('.').any(str::is_empty) {
+            issues.error(
+                "middleware.logging",
+                "redact_json_fields",
+                format!("{:?} is not a dot path such as user.password", field),
+            );
+        }
+    }
+
+    if config.metrics.enabled && !config.metrics.path.starts_with('/') {
+        issues.error("metrics", "path", "must start with /");
+    }
+
+    let canary = &config.canary_rollout;
+    if !(0.0..=100.0).contains(&canary.rollout_percentage) {
+        issues.error("canary_rollout", "rollout_percentage", "must be between 0 and 100");
+    }
+    if canary.enabled && !is_http_url(&canary.legacy_gateway_url) {
+        issues.error(
+            "canary_rollout",
+            "legacy_gateway_url",
+            "canary routing sends traffic to the legacy gateway but its URL is missing or invalid",
+        );
+    }
+    if canary.max_latency_degradation < 0.0 {
+        issues.error("canary_rollout", "max_latency_degradation", "must not be negative");
+    }
+    if canary.success_window.is_zero() {
+        issues.error("canary_rollout", "success_window", "must be greater than zero");
+    }
+    if canary.enabled && canary.trigger_header.trim().is_empty() {
+        issues.error("canary_rollout", "trigger_header", "must not be empty when canary routing is enabled");
+    }
+
+    let drift = &canary.latency_drift;
+    if !(drift.max_score > 0.0 && drift.max_score.is_finite()) {
+        issues.error("canary_rollout.latency_drift", "max_score", "must be greater than zero");
+    }
+    if drift.evaluation_interval.is_zero() {
+        issues.error("canary_rollout.latency_drift", "evaluation_interval", "must be greater than zero");
+    } else if drift.reference_window.get() < drift.evaluation_interval.get() {
+        issues.error(
+            "canary_rollout.latency_drift",
+            "reference_window",
+            "must be at least one evaluation_interval",
+        );
+    }
+    if drift.sustained_intervals == 0 {
+        issues.error("canary_rollout.latency_drift", "sustained_intervals", "must be at least 1");
+    }
+
+    if let Some(coordination) = &canary.coordination {
+        let scheme_ok = reqwest::Url::parse(&coordination.url)
+            .is_ok_and(|url| matches!(url.scheme(), "redis" | "rediss"));
+        if !scheme_ok {
+            issues.error(
+                "canary_rollout.coordination",
+                "url",
+                format!("{:?} is not a redis:// or rediss:// URL", redact_url(&coordination.url)),
+            );
+        }
+        if coordination.refresh_interval.is_zero() {
+            issues.error("canary_rollout.coordination", "refresh_interval", "must be greater than zero");
+        }
+        if coordination.leader_lease.is_zero() {
+            issues.error("canary_rollout.coordination", "leader_lease", "must be greater than zero");
+        }
+    }
+
+    for route in &config.routes {
+        if route.log_sample_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+            issues.error(
+                "routes",
+                "log_sample_rate",
+                format!("{} {} must be between 0 and 1", route.method, route.path),
+            );
+        }
+        if route.max_response_bytes.is_some_and(|size| size.bytes() == 0) {
+            issues.error(
+                "routes",
+                "max_response_bytes",
+                format!("{} {} must allow more than zero bytes", route.method, route.path),
+            );
+        }
+        if route.max_body_bytes.is_some_and(|size| size.bytes() == 0) {
+            issues.error(
+                "routes",
+                "max_body_bytes",
+                format!("{} {} must allow more than zero bytes", route.method, route.path),
+            );
+        }
+        if let Some(invalid) = route
+            .expected_content_types
+            .iter()
+            .find(|content_type| content_type.split_once('/').is_none_or(|(kind, sub)| kind.is_empty() || sub.is_empty()))
+        {
+            issues.error(
+                "routes",
+                "expected_content_types",
+                format!("{} {}: {:?} is not a media type such as application/json", route.method, route.path, invalid),
+            );
+        }
+        if let Some(smoke) = &route.smoke {
+            if let Err(name) = smoke.path(&route.path) {
+                issues.error(
+                    "routes",
+                    "smoke.path_params",
+                    format!("{} {}: no value for path parameter {:?}", route.method, route.path, name),
+                );
+            }
+            if !(100..=599).contains(&smoke.expected_status) {
+                issues.error(
+                    "routes",
+                    "smoke.expected_status",
+                    format!("{} {}: {} is not an HTTP status", route.method, route.path, smoke.expected_status),
+                );
+            }
+        }
+    }
+    if canary.smoke_retry_interval.is_zero() && config.routes.iter().any(|route| route.smoke.is_some()) {
+        issues.error("canary_rollout", "smoke_retry_interval", "must be greater than zero");
+    }
+    if canary.legacy_timeout.is_some_and(|timeout| timeout.is_zero()) {
+        issues.error("canary_rollout", "legacy_timeout", "must be greater than zero");
+    }
+    if canary.override_hold.is_some_and(|hold| !(0.0..=100.0).contains(&hold)) {
+        issues.error("canary_rollout", "override_hold", "must be between 0 and 100");
+    }
+    let mut overridden = std::collections::HashSet::new();
+    for entry in &canary.route_overrides {
+        if !entry.path_prefix.starts_with('/') {
+            issues.error(
+                "canary_rollout",
+                "route_overrides",
+                format!("{:?} must start with /", entry.path_prefix),
+            );
+        }
+        if entry.method.as_ref().is_some_and(|method| method.parse::<axum::http::Method>().is_err()) {
+            issues.error(
+                "canary_rollout",
+                "route_overrides",
+                format!("{}: not an HTTP method", entry.group()),
+            );
+        }
+        if !(0.0..=100.0).contains(&entry.percentage) {
+            issues.error(
+                "canary_rollout",
+                "route_overrides",
+                format!("{}: percentage must be between 0 and 100", entry.group()),
+            );
+        }
+        if !overridden.insert(entry.group()) {
+            issues.error(
+                "canary_rollout",
+                "route_overrides",
+                format!("{} is listed more than once", entry.group()),
+            );
+        }
+    }
+    if let Some(cookie) = &canary.sticky_cookie {
+        if !is_token(&cookie.name) {
+            issues.error(
+                "canary_rollout.sticky_cookie",
+                "name",
+                format!("{:?} is not a valid cookie name", cookie.name),
+            );
+        }
+        if cookie.ttl.is_zero() {
+            issues.error("canary_rollout.sticky_cookie", "ttl", "must be greater than zero");
+        }
+        if cookie.same_site == CookieSameSite::None && !cookie.secure {
+            issues.error("canary_rollout.sticky_cookie", "same_site", "none requires secure, or browsers drop the cookie");
+        }
+    }
+
+    let mirror = &config.mirror;
+    if mirror.enabled && !is_http_url(&mirror.base_url) {
+        issues.error("mirror", "base_url", "mirroring is enabled but the mirror URL is missing or invalid");
+    }
+    if mirror.enabled && mirror.timeout.is_zero() {
+        issues.error("mirror", "timeout", "must be greater than zero when mirroring is enabled");
+    }
+    if let Some(retry) = &mirror.retry {
+        check_retry_policy(&mut issues, "mirror", "retry", retry);
+    }
+    if !(0.0..=100.0).contains(&mirror.sample_percentage) {
+        issues.error("mirror", "sample_percentage", "must be between 0 and 100");
+    }
+    if super::schedule::parse_utc_offset(&mirror.timezone).is_none() {
+        issues.error(
+            "mirror",
+            "timezone",
+            format!("{:?} is not UTC or a fixed offset such as +02:00", mirror.timezone),
+        );
+    }
+    if mirror.queue.max_bytes.bytes() == 0 {
+        issues.error("mirror.queue", "max_bytes", "must be greater than zero");
+    }
+    if mirror.queue.kind == super::MirrorQueueKind::Disk && mirror.queue.path.trim().is_empty() {
+        issues.error("mirror.queue", "path", "a disk queue needs a directory");
+    }
+    if mirror.parity.path.as_deref().is_some_and(|path| path.trim().is_empty()) {
+        issues.error("mirror.parity", "path", "must be a directory; leave it unset to keep records in memory");
+    }
+    let report = &mirror.report;
+    for (field, rate) in [
+        ("max_mismatch_rate", report.max_mismatch_rate),
+        ("max_failure_rate", report.max_failure_rate),
+        ("max_contract_violation_rate", report.max_contract_violation_rate),
+    ] {
+        if !(0.0..=100.0).contains(&rate) {
+            issues.error("mirror.report", field, "must be between 0 and 100");
+        }
+    }
+    if report.max_p99_latency_delta_ms < 0.0 {
+        issues.error("mirror.report", "max_p99_latency_delta_ms", "must not be negative");
+    }
+    for (index, entry) in mirror.schedule.iter().enumerate() {
+        if !(0.0..=100.0).contains(&entry.sample_percentage) {
+            issues.error(
+                "mirror",
+                "schedule",
+                format!("{}: sample_percentage must be between 0 and 100", entry.window),
+            );
+        }
+        for other in &mirror.schedule[index + 1..] {
+            if entry.window.overlaps(&other.window) {
+                issues.error(
+                    "mirror",
+                    "schedule",
+                    format!("windows {:?} and {:?} overlap", entry.window.to_string(), other.window.to_string()),
+                );
+            }
+        }
+    }
+
+    if !(0.0..=1.0).contains(&config.tracing.sampling.ratio) {
+        issues.error("tracing.sampling", "ratio", "must be between 0 and 1");
+    }
+
+    let auth = &config.middleware.auth;
+    let secrets = auth.secrets();
+    let signing = &auth.request_signing;
+    if auth.enabled && secrets.is_empty() && auth.jwks_url.is_none() && signing.keys.is_empty() && auth.introspection.is_none() {
+        issues.error(
+            "middleware.auth",
+            "jwt_secrets",
+            "auth is enabled but no JWT secret, jwks_url, signing key or introspection endpoint is configured",
+        );
+    }
+    if auth.failure_log_size > MAX_AUTH_FAILURE_LOG_SIZE {
+        issues.error(
+            "middleware.auth",
+            "failure_log_size",
+            format!("at most {} failures are kept", MAX_AUTH_FAILURE_LOG_SIZE),
+        );
+    }
+    if auth.issuer.as_deref().is_some_and(|issuer| issuer.trim().is_empty()) {
+        issues.error("middleware.auth", "issuer", "must not be empty; leave it unset to skip the check");
+    }
+    if auth.audience.iter().any(|audience| audience.trim().is_empty()) {
+        issues.error("middleware.auth", "audience", "must not contain empty values");
+    }
+    for name in auth.identity_headers.names() {
+        if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            issues.error("middleware.auth.identity_headers", "names", format!("{:?} is not a valid header name", name));
+        }
+    }
+    if let Some(introspection) = &auth.introspection {
+        if !is_http_url(&introspection.url) {
+            issues.error("middleware.auth.introspection", "url", "must be an http(s) URL");
+        }
+        if introspection.timeout.is_zero() {
+            issues.error("middleware.auth.introspection", "timeout", "must be greater than zero");
+        }
+        if introspection.client_id.is_empty() != introspection.client_secret.is_empty() {
+            issues.error("middleware.auth.introspection", "client_secret", "client_id and client_secret go together");
+        }
+    }
+    let mut names = std::collections::HashSet::new();
+    for key in &signing.keys {
+        if key.name.is_empty() || key.secret.is_empty() {
+            issues.error("middleware.auth.request_signing", "keys", "every key needs a name and a secret");
+        } else if !names.insert(key.name.as_str()) {
+            issues.error("middleware.auth.request_signing", "keys", format!("{:?} is listed twice", key.name));
+        }
+    }
+    let mut ids = std::collections::HashSet::new();
+    let mut keys = std::collections::HashSet::new();
+    for key in &auth.api_keys {
+        if key.id.is_empty() || key.key.is_empty() {
+            issues.error("middleware.auth", "api_keys", "every API key needs an id and a key");
+            continue;
+        }
+        if !ids.insert(key.id.as_str()) {
+            issues.error("middleware.auth", "api_keys", format!("id {:?} is listed twice", key.id));
+        }
+        if !keys.insert(key.key.as_str()) {
+            issues.error("middleware.auth", "api_keys", format!("{:?} has the same key as another entry", key.id));
+        }
+        if key.quota.daily == Some(0) || key.quota.monthly == Some(0) {
+            issues.error("middleware.auth", "api_keys", format!("{:?} has a zero quota; leave it unset for no limit", key.id));
+        }
+    }
+    if auth.quota_reset_hour > 23 {
+        issues.error("middleware.auth", "quota_reset_hour", "must be an hour from 0 to 23");
+    }
+    if signing.max_skew.is_zero() {
+        issues.error("middleware.auth.request_signing", "max_skew", "must be greater than zero");
+    }
+    if auth.jwks_url.as_deref().is_some_and(|url| !is_http_url(url)) {
+        issues.error("middleware.auth", "jwks_url", "must be an http(s) URL");
+    }
+    if auth.jwks_url.is_some() && auth.jwks_refresh_interval.is_zero() {
+        issues.error("middleware.auth", "jwks_refresh_interval", "must be greater than zero");
+    }
+    check_retry_policy(&mut issues, "middleware.auth", "jwks_retry", &auth.jwks_retry);
+    if secrets.len() > 2 {
+        issues.warning(
+            "middleware.auth",
+            "jwt_secrets",
+            format!(
+                "{} secrets configured; remove retired secrets once rotation completes",
+                secrets.len()
+            ),
+        );
+    }
+    if auth.clock_skew_tolerance.get() > MAX_CLOCK_SKEW_TOLERANCE {
+        issues.error("middleware.auth", "clock_skew_tolerance", "must be at most 5m");
+    }
+    for pattern in &auth.exempt_paths {
+        let wildcard = pattern.strip_suffix("/*").unwrap_or(pattern);
+        if !pattern.starts_with('/') || wildcard.contains('*') {
+            issues.error(
+                "middleware.auth",
+                "exempt_paths",
+                format!("'{}' must be a path, optionally ending in /*", pattern),
+            );
+        }
+    }
+
+    let clock = &config.clock;
+    if clock.time_source_url.as_deref().is_some_and(|url| !is_http_url(url)) {
+        issues.error("clock", "time_source_url", "must be an http(s) URL");
+    }
+    if clock.max_skew.is_zero() {
+        issues.error("clock", "max_skew", "must be greater than zero");
+    } else if auth.enabled && clock.max_skew.get() > auth.clock_skew_tolerance.get() {
+        issues.warning(
+            "clock",
+            "max_skew",
+            "exceeds middleware.auth.clock_skew_tolerance, so tokens can be rejected before health reports the skew",
+        );
+    }
+
+    let rate_limiting = &config.middleware.rate_limiting;
+    if rate_limiting.enabled && rate_limiting.requests_per_minute == 0 {
+        issues.error("middleware.rate_limiting", "requests_per_minute", "must be greater than zero");
+    }
+    for o in &rate_limiting.overrides {
+        if o.requests_per_minute == 0 {
+            issues.error(
+                "middleware.rate_limiting",
+                "overrides",
+                format!("client {:?} has requests_per_minute of zero", o.subject),
+            );
+        }
+    }
+    if rate_limiting.enabled && rate_limiting.max_concurrent_per_client == Some(0) {
+        issues.error("middleware.rate_limiting", "max_concurrent_per_client", "must be greater than zero");
+    }
+    for (client, tier) in &rate_limiting.client_tiers {
+        if !rate_limiting.tiers.contains_key(tier) {
+            issues.error(
+                "middleware.rate_limiting",
+                "client_tiers",
+                format!("client {:?} refers to undefined tier {:?}", client, tier),
+            );
+        }
+    }
+    for (name, tier) in &rate_limiting.tiers {
+        if tier.max_concurrent_per_client == Some(0) {
+            issues.error(
+                "middleware.rate_limiting",
+                "tiers",
+                format!("tier {:?} has max_concurrent_per_client of zero", name),
+            );
+        }
+    }
+    for entry in &rate_limiting.trusted_proxies {
+        if let Err(e) = crate::profiling::parse_network(entry) {
+            issues.error("middleware.rate_limiting", "trusted_proxies", format!("{:#}", e));
+        }
+    }
+    for (i, key) in rate_limiting.key_by.iter().enumerate() {
+        if rate_limiting.key_by[..i].contains(key) {
+            issues.warning("middleware.rate_limiting", "key_by", format!("{} is listed more than once", key));
+        }
+    }
+    if rate_limiting.key_by.contains(&super::RateLimitKey::JwtSubject) && !config.middleware.auth.enabled {
+        issues.warning(
+            "middleware.rate_limiting",
+            "key_by",
+            "jwt_sub needs auth enabled; without it every request goes to the missing_key fallback",
+        );
+    }
+
+    let logging = &config.middleware.logging;
+    if logging.max_body_size.bytes() == 0 && (logging.include_request_body || logging.include_response_body) {
+        issues.warning("middleware.logging", "max_body_size", "is zero, so every body will be logged empty");
+    }
+    if !logging.enabled && (logging.include_request_body || logging.include_response_body) {
+        issues.warning(
+            "middleware.logging",
+            "enabled",
+            "body logging is configured but request logging is disabled, so no bodies will be logged",
+        );
+    }
+
+    if config.middleware.load_shedding.max_in_flight == Some(0) {
+        issues.error("middleware.load_shedding", "max_in_flight", "must be greater than zero; leave it unset for no limit");
+    }
+
+    let ip_filter = &config.middleware.ip_filter;
+    for (field, entries) in [
+        ("deny", &ip_filter.deny),
+        ("allow", &ip_filter.allow),
+        ("trusted_proxies", &ip_filter.trusted_proxies),
+    ] {
+        for entry in entries {
+            if let Err(e) = crate::profiling::parse_network(entry) {
+                issues.error("middleware.ip_filter", field, format!("{:#}", e));
+            }
+        }
+    }
+    for path in &ip_filter.allow_paths {
+        if !path.starts_with('/') {
+            issues.error("middleware.ip_filter", "allow_paths", format!("{:?} must start with /", path));
+        }
+    }
+
+    if config.middleware.decompression.max_decoded_size.bytes() == 0 {
+        issues.warning(
+            "middleware.decompression",
+            "max_decoded_size",
+            "is zero, so no non-empty body will be inspected",
+        );
+    }
+
+    if config.contract_check.enabled && !is_http_url(&canary.legacy_gateway_url) {
+        issues.warning(
+            "contract_check",
+            "enabled",
+            "legacy gateway URL is missing or invalid; legacy contract checks will fail",
+        );
+    }
+
+    let legacy_durations = [
+        ("server", "timeout", server.timeout, "seconds"),
+        ("server", "queue_timeout", server.queue_timeout, "milliseconds"),
+        ("mirror", "timeout", mirror.timeout, "milliseconds"),
+        ("canary_rollout", "success_window", canary.success_window, "seconds"),
+        ("canary_rollout", "slow_start", canary.slow_start, "seconds"),
+        ("middleware.logging", "slow_request_threshold", logging.slow_request_threshold, "milliseconds"),
+    ];
+    for (section, field, duration, unit) in legacy_durations {
+        if duration.is_legacy_numeric() {
+            issues.warning(
+                section,
+                field,
+                format!("bare numbers are read as {} and are deprecated; write \"{}\"", unit, duration),
+            );
+        }
+    }
+    if logging.max_body_size.is_legacy_numeric() {
+        issues.warning(
+            "middleware.logging",
+            "max_body_size",
+            format!("bare numbers are read as bytes and are deprecated; write \"{}\"", logging.max_body_size),
+        );
+    }
+
+    if config.http_client.max_connections_per_host == 0 {
+        issues.error("http_client", "max_connections_per_host", "must be greater than zero");
+    }
+    if config.http_client.connect_timeout.is_zero() {
+        issues.error("http_client", "connect_timeout", "must be greater than zero");
+    }
+    let prewarm = &config.http_client.prewarm;
+    if prewarm.enabled {
+        if prewarm.interval.is_zero() {
+            issues.error("http_client.prewarm", "interval", "must be greater than zero");
+        }
+        if prewarm.max_probes_per_second <= 0.0 {
+            issues.error("http_client.prewarm", "max_probes_per_second", "must be greater than zero");
+        } else if prewarm.probes_per_round() < prewarm.legacy_target(100.0) {
+            issues.warning(
+                "http_client.prewarm",
+                "max_probes_per_second",
+                format!(
+                    "allows {} probes per {} round, fewer than the {} legacy connections wanted at full rollout",
+                    prewarm.probes_per_round(),
+                    prewarm.interval,
+                    prewarm.legacy_target(100.0)
+                ),
+            );
+        }
+        if prewarm.interval.get() >= config.http_client.pool_idle_timeout.get() {
+            issues.warning(
+                "http_client.prewarm",
+                "interval",
+                format!(
+                    "is not shorter than pool_idle_timeout ({}), so warm connections close between rounds",
+                    config.http_client.pool_idle_timeout
+                ),
+            );
+        }
+        if prewarm.legacy_target(100.0) > config.http_client.max_connections_per_host {
+            issues.warning(
+                "http_client.prewarm",
+                "rollback_headroom",
+                format!(
+                    "capped at the {} connections allowed per host",
+                    config.http_client.max_connections_per_host
+                ),
+            );
+        }
+    }
+    let http_client = &config.http_client;
+    let user_agent = crate::upstream::headers::render_user_agent(&http_client.user_agent, "instance");
+    if http_client.user_agent.trim().is_empty() || reqwest::header::HeaderValue::try_from(user_agent).is_err() {
+        issues.error("http_client", "user_agent", "must be a non-empty, valid header value");
+    }
+    if let Err(e) = crate::upstream::headers::header_map(&http_client.default_headers) {
+        issues.error("http_client", "default_headers", format!("{:#}", e));
+    }
+    for (name, headers) in &http_client.upstream_headers {
+        if crate::upstream::Upstream::from_name(name).is_none() {
+            issues.error(
+                "http_client",
+                "upstream_headers",
+                format!(
+                    "unknown upstream {:?}; expected one of {}",
+                    name,
+                    crate::upstream::Upstream::ALL.map(|upstream| upstream.as_str()).join(", ")
+                ),
+            );
+        }
+        if let Err(e) = crate::upstream::headers::header_map(headers) {
+            issues.error("http_client", "upstream_headers", format!("{}: {:#}", name, e));
+        }
+    }
+    let health_check = &config.http_client.health_check;
+    if health_check.enabled {
+        if !health_check.path.starts_with('/') {
+            issues.error("http_client.health_check", "path", "must start with '/'");
+        }
+        if health_check.interval.is_zero() {
+            issues.error("http_client.health_check", "interval", "must be greater than zero");
+        }
+        if health_check.timeout.is_zero() {
+            issues.error("http_client.health_check", "timeout", "must be greater than zero");
+        }
+    }
+    let privacy = &config.privacy;
+    if privacy.pseudonymize_user_ids {
+        match crate::privacy::salt(privacy) {
+            Err(e) => issues.error("privacy", "salt_file", format!("{:#}", e)),
+            Ok(None) => issues.error("privacy", "salt", "set salt or salt_file, or disable pseudonymize_user_ids"),
+            Ok(Some(salt)) if salt.is_empty() => issues.error("privacy", "salt", "must not be empty"),
+            Ok(Some(_)) => {}
+        }
+        if privacy.salt.is_some() && privacy.salt_file.is_some() {
+            issues.warning("privacy", "salt", "both salt and salt_file are set; salt_file wins");
+        }
+    } else {
+        issues.warning(
+            "privacy",
+            "pseudonymize_user_ids",
+            "user identifiers will be logged raw; only disable this in development",
+        );
+    }
+
+    if let Some(proxy) = &config.http_client.proxy {
+        if !is_http_url(&proxy.url) {
+            issues.error("http_client.proxy", "url", format!("{:?} is not an http(s) URL", redact_url(&proxy.url)));
+        }
+        if let Err(e) = crate::upstream::proxy::NoProxy::parse(&proxy.no_proxy) {
+            issues.error("http_client.proxy", "no_proxy", e.to_string());
+        }
+        if let Err(e) = crate::upstream::proxy::password(proxy) {
+            issues.error("http_client.proxy", "password_file", format!("{:#}", e));
+        }
+        if proxy.password.is_some() && proxy.password_file.is_some() {
+            issues.warning("http_client.proxy", "password", "both password and password_file are set; password_file wins");
+        }
+        if proxy.username.is_none() && (proxy.password.is_some() || proxy.password_file.is_some()) {
+            issues.warning("http_client.proxy", "username", "a proxy password is configured without a username and will not be sent");
+        }
+    }
+
+    issues.0
+}
+
+/// Table of enabled features and their key parameters, logged at startup.
+pub fn startup_report(config: &AppConfig) -> String {
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    let rate_limiting = &config.middleware.rate_limiting;
+    let logging = &config.middleware.logging;
+
+    let rows = [
+        (
+            "server",
+            "on",
+            format!(
+                "{}:{}, timeout {}, queue timeout {}, base path {:?}",
+                config.server.host,
+                config.server.port,
+                config.server.timeout,
+                config.server.queue_timeout,
+                config.server.base_path()
+            ),
+        ),
+        (
+            "canary_rollout",
+            on_off(config.canary_rollout.enabled),
+            format!(
+                "{}% rust, legacy {}",
+                config.canary_rollout.rollout_percentage, config.canary_rollout.legacy_gateway_url
+            ),
+        ),
+        (
+            "coordination",
+            on_off(config.canary_rollout.coordination.is_some()),
+            config
+                .canary_rollout
+                .coordination
+                .as_ref()
+                .map(|coordination| {
+                    format!(
+                        "{}, refresh {}, lease {}",
+                        redact_url(&coordination.url),
+                        coordination.refresh_interval,
+                        coordination.leader_lease
+                    )
+                })
+                .unwrap_or_else(|| "local-only".to_string()),
+        ),
+        (
+            "mirror",
+            on_off(config.mirror.enabled),
+            format!(
+                "{}, timeout {}, {}% sampled, {} scheduled window(s)",
+                config.mirror.base_url,
+                config.mirror.timeout,
+                config.mirror.sample_percentage,
+                config.mirror.schedule.len()
+            ),
+        ),
+        (
+            "auth",
+            on_off(config.middleware.auth.enabled),
+            format!(
+                "{} secret(s), {}, skew tolerance {}",
+                config.middleware.auth.secrets().len(),
+                if config.middleware.auth.jwks_url.is_some() { "JWKS" } else { "no JWKS" },
+                config.middleware.auth.clock_skew_tolerance
+            ),
+        ),
+        (
+            "rate_limiting",
+            on_off(rate_limiting.enabled),
+            format!(
+                "{}/min ({} override(s)), {} concurrent per client, {} tier(s), keyed by {}",
+                rate_limiting.requests_per_minute,
+                rate_limiting.overrides.len(),
+                rate_limiting
+                    .max_concurrent_per_client
+                    .map(|max| max.to_string())
+                    .unwrap_or_else(|| "unlimited".to_string()),
+                rate_limiting.tiers.len(),
+                if rate_limiting.key_by.is_empty() {
+                    "default".to_string()
+                } else {
+                    rate_limiting.key_by.iter().map(|key| key.to_string()).collect::<Vec<_>>().join("+")
+                }
+            ),
+        ),
+        (
+            "ip_filter",
+            on_off(config.middleware.ip_filter.is_active()),
+            format!(
+                "{} denied, {} allowed for {}",
+                config.middleware.ip_filter.deny.len(),
+                config.middleware.ip_filter.allow.len(),
+                if config.middleware.ip_filter.allow_paths.is_empty() {
+                    "every path".to_string()
+                } else {
+                    config.middleware.ip_filter.allow_paths.join(", ")
+                }
+            ),
+        ),
+        (
+            "logging",
+            on_off(logging.enabled),
+            format!(
+                "request bodies {}, response bodies {}",
+                on_off(logging.include_request_body),
+                on_off(logging.include_response_body)
+            ),
+        ),
+        (
+            "contract_check",
+            on_off(config.contract_check.enabled),
+            format!("every {}s", config.contract_check.interval_seconds),
+        ),
+        ("metrics", on_off(config.metrics.enabled), config.metrics.path.clone()),
+        (
+            "http_client",
+            "on",
+            format!(
+                "{} connections per host, proxy {}, pre-warm {}",
+                config.http_client.max_connections_per_host,
+                config
+                    .http_client
+                    .proxy
+                    .as_ref()
+                    .map(|proxy| redact_url(&proxy.url))
+                    .unwrap_or_else(|| "none".to_string()),
+                on_off(config.http_client.prewarm.enabled)
+            ),
+        ),
+    ];
+
+    let mut report = format!("{:<16} {:<8} {}\n", "FEATURE", "STATE", "PARAMETERS");
+    for (feature, state, parameters) in rows {
+        report.push_str(&format!("{:<16} {:<8} {}\n", feature, state, parameters));
+    }
+    report
+}
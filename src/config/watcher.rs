@@ -1,94 +1,611 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::Path;
-use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot, Mutex, MutexGuard, RwLock},
+    task::JoinHandle,
+};
 use tracing::{error, info, warn};
 
-use super::AppConfig;
+use super::{
+    overlay::{self, Merged, OverlayEntry},
+    partial::{self, Salvaged},
+    staged::{ConfigVariant, StagedConfig},
+    AppConfig, ConfigValidationError, HumanDuration, ReloadMode,
+};
+use crate::util::backoff::{Backoff, RetryPolicy};
+
+pub use crate::models::health::{ConfigReloadEvent, ConfigReloadOutcome};
+
+/// How a removed config file is polled for until it reappears.
+fn poll_backoff() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 0,
+        initial_delay: HumanDuration::from_millis(100),
+        max_delay: HumanDuration::from_secs(5),
+        ..RetryPolicy::default()
+    }
+}
+
+/// Observed state of the watched file, shared with the health endpoint.
+struct WatchStatus {
+    file_present: AtomicBool,
+    last_loaded_at: std::sync::RwLock<DateTime<Utc>>,
+    last_reload: std::sync::RwLock<Option<ConfigReloadEvent>>,
+}
+
+impl WatchStatus {
+    fn loaded(&self) {
+        if let Ok(mut last_loaded_at) = self.last_loaded_at.write() {
+            *last_loaded_at = Utc::now();
+        }
+    }
+
+    fn reloaded(&self, event: ConfigReloadEvent) {
+        if let Ok(mut last_reload) = self.last_reload.write() {
+            *last_reload = Some(event);
+        }
+    }
+}
+
+/// The overlay file runtime-owned values are persisted to.
+struct Overlay {
+    path: PathBuf,
+    /// Entries merged into the active config.
+    entries: std::sync::RwLock<Vec<OverlayEntry>>,
+    staged: std::sync::RwLock<Option<Arc<StagedConfig>>>,
+    /// Held while loading or writing, so a reload can't apply a merge that
+    /// predates a write and no two writes interleave.
+    lock: Mutex<()>,
+    writes: mpsc::UnboundedSender<PersistRequest>,
+}
+
+enum OverlayChange {
+    Set { path: String, value: serde_yaml::Value },
+    Stage { path: String, value: serde_yaml::Value, percentage: f64 },
+    PromoteStaged,
+    DiscardStaged,
+}
+
+struct PersistRequest {
+    change: OverlayChange,
+    updated_by: String,
+    reply: oneshot::Sender<Result<AppConfig>>,
+}
+
+impl Overlay {
+    /// Records what was merged from disk as in force.
+    fn adopt(&self, merged: &Merged) {
+        if let Ok(mut entries) = self.entries.write() {
+            *entries = merged.overlay.entries.clone();
+        }
+        let staged = merged.overlay.staged.as_ref().zip(merged.staged.as_ref()).map(|(changes, config)| {
+            Arc::new(StagedConfig {
+                percentage: changes.percentage,
+                entries: changes.entries.clone(),
+                config: config.clone(),
+            })
+        });
+        if let Ok(mut current) = self.staged.write() {
+            *current = staged;
+        }
+    }
+}
+
+/// The config file, and the overlay merged over it when there is one.
+struct Source {
+    path: PathBuf,
+    overlay: Option<Arc<Overlay>>,
+    /// The config file as last loaded, which a partial reload takes the
+    /// sections that don't load from.
+    last_document: std::sync::Mutex<Option<String>>,
+}
+
+impl Source {
+    /// Loads the config file, merging the overlay over it. How is up to
+    /// `config_reload` in `active`: a file over `max_file_size` is rejected
+    /// unread, and in partial mode one that fails to load is loaded section
+    /// by section, returning the sections applied and retained and why the
+    /// whole file didn't load.
+    fn load(&self, active: &AppConfig) -> Result<(AppConfig, Option<(Salvaged, String)>)> {
+        let settings = &active.config_reload;
+        let size = std::fs::metadata(&self.path)
+            .with_context(|| format!("reading {}", self.path.display()))?
+            .len();
+        if size > settings.max_file_size.bytes() {
+            bail!(
+                "{} is {} bytes, over config_reload.max_file_size of {}",
+                self.path.display(),
+                size,
+                settings.max_file_size
+            );
+        }
+        let document = std::fs::read_to_string(&self.path).with_context(|| format!("reading {}", self.path.display()))?;
+
+        let (config, merged, salvaged) = match self.candidate(Some(&document)) {
+            Ok((config, merged)) => (config, merged, None),
+            Err(e) if settings.mode == ReloadMode::Strict || e.downcast_ref::<ConfigValidationError>().is_some() => {
+                return Err(e)
+            }
+            Err(e) => {
+                warn!(path = %self.path.display(), "Configuration file failed to load, loading it section by section: {:#}", e);
+                let previous = match self.last_document.lock().ok().and_then(|last| last.clone()) {
+                    Some(previous) => previous,
+                    None => serde_yaml::to_string(active)?,
+                };
+                let error = format!("{:#}", e);
+                let salvaged = partial::salvage(&document, &previous).map_err(|salvage| e.context(salvage))?;
+                let (config, merged) = self.candidate(Some(&salvaged.document))?;
+                (config, merged, Some((salvaged, error)))
+            }
+        };
+        if let (Some(overlay), Some(merged)) = (&self.overlay, merged) {
+            overlay.adopt(&merged);
+        }
+        if let Ok(mut last_document) = self.last_document.lock() {
+            *last_document = Some(salvaged.as_ref().map_or(document, |(salvaged, _)| salvaged.document.clone()));
+        }
+        Ok((config, salvaged))
+    }
+
+    /// Loads and validates `document`, or the config file when `None`, with
+    /// the overlay merged over it, without adopting anything.
+    fn candidate(&self, document: Option<&str>) -> Result<(AppConfig, Option<Merged>)> {
+        let Some(overlay) = &self.overlay else {
+            let config = match document {
+                Some(document) => AppConfig::load_str(document)?,
+                None => AppConfig::load_from(&self.path.to_string_lossy())?,
+            };
+            return Ok((config, None));
+        };
+        let merged = match document {
+            Some(document) => overlay::merge(document, &overlay.path)?,
+            None => overlay::load(&self.path, &overlay.path)?,
+        };
+        Ok((merged.config.clone(), Some(merged)))
+    }
+
+    /// Waits out any overlay write in progress; hold the guard until the
+    /// loaded config is applied.
+    async fn serialize(&self) -> Option<MutexGuard<'_, ()>> {
+        match &self.overlay {
+            Some(overlay) => Some(overlay.lock.lock().await),
+            None => None,
+        }
+    }
+}
 
 pub struct ConfigWatcher {
+    source: Arc<Source>,
     config: Arc<RwLock<AppConfig>>,
-    _watcher: RecommendedWatcher,
     reload_tx: broadcast::Sender<AppConfig>,
+    status: Arc<WatchStatus>,
+    tasks: Vec<JoinHandle<()>>,
 }
 
 impl ConfigWatcher {
+    /// Starts watching `config_path`, serving `initial_config` until the file
+    /// changes. Must be called from within a Tokio runtime.
+    ///
+    /// The parent directory is watched rather than the file itself so that the
+    /// watch survives the file being deleted and recreated (editors that
+    /// replace files, ConfigMap remounts). While the file is missing the last
+    /// good config keeps being served and the path is polled with backoff.
     pub fn new(config_path: &str, initial_config: AppConfig) -> Result<Self> {
+        Self::start(config_path, initial_config, None)
+    }
+
+    /// Like [`new`](Self::new), loading the config with the values in
+    /// `overlay_path` merged over it. Values the gateway changes at runtime
+    /// are written there by [`persist`](Self::persist) instead of to the
+    /// config file, which stays the operator's.
+    pub fn with_overlay(config_path: &str, overlay_path: &Path) -> Result<Self> {
+        let merged = overlay::load(Path::new(config_path), overlay_path)?;
+        let (writes, write_rx) = mpsc::unbounded_channel();
+        let overlay = Arc::new(Overlay {
+            path: overlay_path.to_path_buf(),
+            entries: std::sync::RwLock::new(Vec::new()),
+            staged: std::sync::RwLock::new(None),
+            lock: Mutex::new(()),
+            writes,
+        });
+        overlay.adopt(&merged);
+        info!(path = %overlay_path.display(), "Merging persisted overrides over the configuration file");
+
+        let mut watcher = Self::start(config_path, merged.config, Some(overlay.clone()))?;
+        watcher.tasks.push(tokio::spawn(run_writer(
+            watcher.source.path.clone(),
+            overlay,
+            write_rx,
+            watcher.config.clone(),
+            watcher.reload_tx.clone(),
+            watcher.status.clone(),
+        )));
+        Ok(watcher)
+    }
+
+    fn start(config_path: &str, initial_config: AppConfig, overlay: Option<Arc<Overlay>>) -> Result<Self> {
         let config = Arc::new(RwLock::new(initial_config));
         let (reload_tx, _) = broadcast::channel(16);
-        
-        let config_clone = config.clone();
-        let reload_tx_clone = reload_tx.clone();
-        
-        let mut watcher = RecommendedWatcher::new(
-            move |res: Result<Event, notify::Error>| {
-                match res {
-                    Ok(event) => {
-                        if event.kind.is_modify() {
-                            info!("Configuration file changed, reloading...");
-                            
-                            match AppConfig::load() {
-                                Ok(new_config) => {
-                                    // Use blocking task to handle async operations in sync context
-                                    let config_clone = config_clone.clone();
-                                    let reload_tx_clone = reload_tx_clone.clone();
-                                    let new_config_clone = new_config.clone();
-                                    
-                                    std::thread::spawn(move || {
-                                        let rt = tokio::runtime::Handle::try_current()
-                                            .or_else(|_| {
-                                                tokio::runtime::Runtime::new()
-                                                    .map(|rt| rt.handle().clone())
-                                            });
-                                        
-                                        if let Ok(handle) = rt {
-                                            handle.spawn(async move {
-                                                let mut config_guard = config_clone.write().await;
-                                                *config_guard = new_config_clone.clone();
-                                                drop(config_guard);
-                                                
-                                                if let Err(e) = reload_tx_clone.send(new_config_clone) {
-                                                    warn!("No active config reload subscribers: {}", e);
-                                                } else {
-                                                    info!("Configuration reloaded successfully");
-                                                }
-                                            });
-                                        } else {
-                                            error!("Failed to get tokio runtime handle for config reload");
-                                        }
-                                    });
-                                }
-                                Err(e) => {
-                                    error!("Failed to reload configuration: {}", e);
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("File watcher error: {}", e);
-                    }
-                }
-            },
-            Config::default(),
-        )?;
-        
-        watcher.watch(Path::new(config_path), RecursiveMode::NonRecursive)?;
+        let path = PathBuf::from(config_path);
+        let status = Arc::new(WatchStatus {
+            file_present: AtomicBool::new(path.exists()),
+            last_loaded_at: std::sync::RwLock::new(Utc::now()),
+            last_reload: std::sync::RwLock::new(None),
+        });
+
+        let (change_tx, change_rx) = mpsc::unbounded_channel();
+        let watcher = watch_path(&path, change_tx.clone())?;
         info!("Started watching configuration file: {}", config_path);
-        
+
+        let source = Arc::new(Source {
+            last_document: std::sync::Mutex::new(std::fs::read_to_string(&path).ok()),
+            path,
+            overlay,
+        });
+        let task = tokio::spawn(run_reload_loop(
+            source.clone(),
+            watcher,
+            change_tx,
+            change_rx,
+            config.clone(),
+            reload_tx.clone(),
+            status.clone(),
+        ));
+
         Ok(ConfigWatcher {
+            source,
             config,
-            _watcher: watcher,
             reload_tx,
+            status,
+            tasks: vec![task],
         })
     }
-    
+
     pub async fn get_config(&self) -> AppConfig {
         self.config.read().await.clone()
     }
-    
+
+    /// Replaces the active configuration and notifies reload subscribers.
+    pub async fn apply(&self, new_config: AppConfig) {
+        apply_config(&self.config, &self.reload_tx, new_config).await;
+    }
+
+    /// Re-reads the config file now instead of waiting for a change event.
+    /// A file that fails to load or validate leaves the active config alone,
+    /// unless `config_reload.mode` is partial and some of its sections load;
+    /// [`last_reload`](Self::last_reload) tells which.
+    pub async fn reload(&self) -> Result<AppConfig> {
+        let _serialized = self.source.serialize().await;
+        let new_config = reload_file(&self.source, &self.config, &self.reload_tx, &self.status).await?;
+        info!(path = %self.source.path.display(), "Configuration reloaded on request");
+        Ok(new_config)
+    }
+
+    /// The config a reload would apply, without applying it: `document` in
+    /// place of the config file when given, with the overlay merged over it
+    /// either way. Errors are those a reload would be rejected with.
+    pub async fn prepare(&self, document: Option<&str>) -> Result<AppConfig> {
+        let _serialized = self.source.serialize().await;
+        self.source.candidate(document).map(|(config, _)| config)
+    }
+
     pub fn subscribe_to_reloads(&self) -> broadcast::Receiver<AppConfig> {
         self.reload_tx.subscribe()
     }
+
+    /// Whether runtime changes can be persisted to an overlay file.
+    pub fn has_overlay(&self) -> bool {
+        self.source.overlay.is_some()
+    }
+
+    /// Writes `value` at dotted `path` to the overlay file and applies the
+    /// config re-merged from disk. Writes go through a single task, one at
+    /// a time; a manual edit to the config file made since it was last
+    /// loaded is merged rather than overwritten.
+    pub async fn persist(&self, path: &str, value: impl serde::Serialize, updated_by: &str) -> Result<AppConfig> {
+        let change = OverlayChange::Set {
+            path: path.to_string(),
+            value: serde_yaml::to_value(value)?,
+        };
+        self.write_overlay(change, updated_by).await
+    }
+
+    /// Stages `value` at dotted `path` for `percentage` of clients, keeping
+    /// anything already staged; see [`super::staged`].
+    pub async fn stage(&self, path: &str, value: impl serde::Serialize, percentage: f64, updated_by: &str) -> Result<AppConfig> {
+        let change = OverlayChange::Stage {
+            path: path.to_string(),
+            value: serde_yaml::to_value(value)?,
+            percentage,
+        };
+        self.write_overlay(change, updated_by).await
+    }
+
+    /// Applies the staged values to all traffic.
+    pub async fn promote_staged(&self, updated_by: &str) -> Result<AppConfig> {
+        self.write_overlay(OverlayChange::PromoteStaged, updated_by).await
+    }
+
+    pub async fn discard_staged(&self, updated_by: &str) -> Result<AppConfig> {
+        self.write_overlay(OverlayChange::DiscardStaged, updated_by).await
+    }
+
+    async fn write_overlay(&self, change: OverlayChange, updated_by: &str) -> Result<AppConfig> {
+        let overlay = self.source.overlay.as_ref().ok_or_else(|| anyhow!("no overlay file to write to"))?;
+        let (reply, response) = oneshot::channel();
+        overlay
+            .writes
+            .send(PersistRequest {
+                change,
+                updated_by: updated_by.to_string(),
+                reply,
+            })
+            .map_err(|_| anyhow!("config overlay writer stopped"))?;
+        response.await.map_err(|_| anyhow!("config overlay writer stopped"))?
+    }
+
+    /// The staged values in force, if any.
+    pub fn staged(&self) -> Option<Arc<StagedConfig>> {
+        self.source.overlay.as_ref()?.staged.read().ok()?.clone()
+    }
+
+    /// The config a request from client `key` is evaluated against, and
+    /// which variant that is while something is staged.
+    pub async fn config_for(&self, key: &str) -> (Option<ConfigVariant>, AppConfig) {
+        match self.staged() {
+            Some(staged) => match staged.variant_for(key) {
+                ConfigVariant::Staged => (Some(ConfigVariant::Staged), staged.config.clone()),
+                ConfigVariant::Current => (Some(ConfigVariant::Current), self.get_config().await),
+            },
+            None => (None, self.get_config().await),
+        }
+    }
+
+    /// The overlay entries merged into the active config, or `None` without
+    /// an overlay file.
+    pub fn overlay_entries(&self) -> Option<Vec<OverlayEntry>> {
+        self.source
+            .overlay
+            .as_ref()
+            .map(|overlay| overlay.entries.read().map(|entries| entries.clone()).unwrap_or_default())
+    }
+
+    /// Whether the config file currently exists on disk.
+    pub fn file_present(&self) -> bool {
+        self.status.file_present.load(Ordering::Relaxed)
+    }
+
+    /// How the last reload of the config file went, whether by the watcher
+    /// or [`reload`](Self::reload).
+    pub fn last_reload(&self) -> Option<ConfigReloadEvent> {
+        self.status.last_reload.read().ok()?.clone()
+    }
+
+    /// When the active config was last successfully loaded.
+    pub fn last_loaded_at(&self) -> DateTime<Utc> {
+        self.status
+            .last_loaded_at
+            .read()
+            .map(|at| *at)
+            .unwrap_or_else(|_| Utc::now())
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(JoinHandle::abort);
+    }
 }
 
+/// The one task that writes the overlay file.
+async fn run_writer(
+    config_path: PathBuf,
+    overlay: Arc<Overlay>,
+    mut write_rx: mpsc::UnboundedReceiver<PersistRequest>,
+    config: Arc<RwLock<AppConfig>>,
+    reload_tx: broadcast::Sender<AppConfig>,
+    status: Arc<WatchStatus>,
+) {
+    while let Some(request) = write_rx.recv().await {
+        let _serialized = overlay.lock.lock().await;
+        let updated_by = &request.updated_by;
+        let (result, description) = match request.change {
+            OverlayChange::Set { path, value } => (
+                overlay::persist(&config_path, &overlay.path, &path, value, updated_by),
+                format!("override of {}", path),
+            ),
+            OverlayChange::Stage { path, value, percentage } => (
+                overlay::stage(&config_path, &overlay.path, &path, value, percentage, updated_by),
+                format!("staged change of {} for {}% of clients", path, percentage),
+            ),
+            OverlayChange::PromoteStaged => (
+                overlay::promote_staged(&config_path, &overlay.path, updated_by),
+                "promotion of the staged config".to_string(),
+            ),
+            OverlayChange::DiscardStaged => (
+                overlay::discard_staged(&config_path, &overlay.path),
+                "discard of the staged config".to_string(),
+            ),
+        };
+        let reply = match result {
+            Ok(merged) => {
+                overlay.adopt(&merged);
+                apply_config(&config, &reload_tx, merged.config.clone()).await;
+                status.loaded();
+                info!(updated_by = %updated_by, "Persisted configuration {}", description);
+                Ok(merged.config)
+            }
+            Err(e) => {
+                error!(updated_by = %updated_by, "Failed to persist configuration {}: {:#}", description, e);
+                Err(e)
+            }
+        };
+        let _ = request.reply.send(reply);
+    }
+}
+
+/// Loads the config file and applies what loads, recording the outcome.
+async fn reload_file(
+    source: &Source,
+    config: &RwLock<AppConfig>,
+    reload_tx: &broadcast::Sender<AppConfig>,
+    status: &WatchStatus,
+) -> Result<AppConfig> {
+    let active = config.read().await.clone();
+    let (new_config, salvaged) = match source.load(&active) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            status.reloaded(ConfigReloadEvent {
+                outcome: ConfigReloadOutcome::Rejected,
+                applied_sections: Vec::new(),
+                retained_sections: Vec::new(),
+                error: Some(format!("{:#}", e)),
+                at: Utc::now().to_rfc3339(),
+            });
+            return Err(e);
+        }
+    };
+    apply_config(config, reload_tx, new_config.clone()).await;
+    status.loaded();
+
+    let event = match salvaged {
+        Some((Salvaged { applied, retained, .. }, error)) => {
+            crate::metrics::record_config_partial_reload();
+            warn!(
+                applied = %applied.join(", "),
+                retained = %retained.join(", "),
+                "Configuration partially reloaded; sections that failed to load keep their previous values"
+            );
+            ConfigReloadEvent {
+                outcome: ConfigReloadOutcome::Partial,
+                applied_sections: applied,
+                retained_sections: retained,
+                error: Some(error),
+                at: Utc::now().to_rfc3339(),
+            }
+        }
+        None => ConfigReloadEvent {
+            outcome: ConfigReloadOutcome::Applied,
+            applied_sections: Vec::new(),
+            retained_sections: Vec::new(),
+            error: None,
+            at: Utc::now().to_rfc3339(),
+        },
+    };
+    status.reloaded(event);
+    Ok(new_config)
+}
+
+async fn apply_config(
+    config: &RwLock<AppConfig>,
+    reload_tx: &broadcast::Sender<AppConfig>,
+    new_config: AppConfig,
+) {
+    *config.write().await = new_config.clone();
+    if let Err(e) = reload_tx.send(new_config) {
+        warn!("No active config reload subscribers: {}", e);
+    }
+}
+
+/// Watches the parent directory of `path`, signalling only for events that
+/// touch the config file itself.
+fn watch_path(path: &Path, change_tx: mpsc::UnboundedSender<()>) -> Result<RecommendedWatcher> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("config path has no file name: {}", path.display()))?
+        .to_os_string();
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut watcher = RecommendedWatcher::new(
+        move |res: Result<Event, notify::Error>| match res {
+            Ok(event) => {
+                let touches_config = event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == Some(file_name.as_os_str()));
+                if touches_config && !event.kind.is_access() {
+                    let _ = change_tx.send(());
+                }
+            }
+            Err(e) => {
+                error!("File watcher error: {}", e);
+            }
+        },
+        Config::default(),
+    )?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+async fn run_reload_loop(
+    source: Arc<Source>,
+    // Held only to keep the OS watch alive; replaced when re-established
+    mut _watcher: RecommendedWatcher,
+    change_tx: mpsc::UnboundedSender<()>,
+    mut change_rx: mpsc::UnboundedReceiver<()>,
+    config: Arc<RwLock<AppConfig>>,
+    reload_tx: broadcast::Sender<AppConfig>,
+    status: Arc<WatchStatus>,
+) {
+    let path = &source.path;
+    let mut backoff = Backoff::new(poll_backoff());
+
+    loop {
+        if status.file_present.load(Ordering::Relaxed) {
+            if change_rx.recv().await.is_none() {
+                return;
+            }
+        } else {
+            // File is gone: wake on either a watch event or the next poll
+            tokio::select! {
+                _ = change_rx.recv() => {}
+                _ = tokio::time::sleep(backoff.next_delay().unwrap_or(Duration::from_secs(5))) => {}
+            }
+        }
+
+        // Coalesce bursts of events from a single save
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        while change_rx.try_recv().is_ok() {}
+
+        if !path.exists() {
+            if status.file_present.swap(false, Ordering::Relaxed) {
+                warn!(
+                    path = %path.display(),
+                    "Configuration file removed; serving last good configuration until it reappears"
+                );
+            }
+            continue;
+        }
+
+        if !status.file_present.swap(true, Ordering::Relaxed) {
+            info!(path = %path.display(), "Configuration file reappeared, re-establishing watch");
+            backoff.reset();
+            match watch_path(path, change_tx.clone()) {
+                Ok(new_watcher) => _watcher = new_watcher,
+                Err(e) => error!("Failed to re-establish config watch: {}", e),
+            }
+        }
+
+        info!("Configuration file changed, reloading...");
+        let _serialized = source.serialize().await;
+        match reload_file(&source, &config, &reload_tx, &status).await {
+            Ok(_) => info!("Configuration reloaded successfully"),
+            Err(e) => {
+                error!("Rejected configuration reload, keeping previous config: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+pub mod schema;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request},
+    Router,
+};
+use metrics::gauge;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{str::FromStr, sync::{Arc, RwLock}, time::Duration};
+use tower::ServiceExt;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{AppConfig, ContractCheckConfig, ContractSample},
+    metrics::labels::{label, Dimension},
+    upstream::{Upstream, UpstreamPool},
+    AppState,
+};
+
+const MAX_CHECKED_BODY_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct TargetResult {
+    pub status: Option<u16>,
+    pub passed: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RouteContractResult {
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub rust: TargetResult,
+    pub legacy: TargetResult,
+    pub passed: bool,
+    pub checked_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ContractReport {
+    pub last_run: Option<String>,
+    pub routes: Vec<RouteContractResult>,
+}
+
+/// Periodically replays sample requests against the in-process Rust handlers
+/// and the legacy upstream, validating both against the OpenAPI spec.
+///
+/// Results are informational: they never change routing, but
+/// `route_passing` lets readiness logic take them into account.
+#[derive(Default)]
+pub struct ContractChecker {
+    report: RwLock<ContractReport>,
+}
+
+impl ContractChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> ContractReport {
+        self.report.read().map(|report| report.clone()).unwrap_or_default()
+    }
+
+    /// Latest verdict for a route, or `None` if it hasn't been checked.
+    pub fn route_passing(&self, method: &str, route: &str) -> Option<bool> {
+        self.report.read().ok()?.routes.iter()
+            .find(|result| result.method.eq_ignore_ascii_case(method) && result.route == route)
+            .map(|result| result.passed)
+    }
+
+    pub async fn start(self: Arc<Self>, state: AppState, router: Router) {
+        let server = state.config_watcher.get_config().await.server;
+        let spec = serde_json::to_value(crate::docs::get_openapi_spec(&server)).unwrap_or_default();
+
+        loop {
+            let config = state.config_watcher.get_config().await;
+            if config.contract_check.enabled {
+                let report = self.run_once(&state.upstreams, &spec, router.clone(), &config).await;
+                let failing = report.routes.iter().filter(|result| !result.passed).count();
+                info!(
+                    checked = report.routes.len(),
+                    failing = failing,
+                    "Contract check completed"
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(config.contract_check.interval_seconds.max(1))).await;
+        }
+    }
+
+    pub async fn run_once(
+        &self,
+        upstreams: &UpstreamPool,
+        spec: &Value,
+        router: Router,
+        config: &AppConfig,
+    ) -> ContractReport {
+        let mut routes = Vec::new();
+
+        for sample in samples_for(&config.contract_check, spec) {
+            let route = sample.route.clone().unwrap_or_else(|| sample.path.clone());
+
+            let rust = match call_rust(&router, &sample, &config.canary_rollout.trigger_header).await {
+                Ok((status, body)) => target_result(spec, &sample.method, &route, status, &body),
+                Err(e) => failed_target(format!("Rust handler call failed: {}", e)),
+            };
+            let legacy = match call_legacy(upstreams, &config.canary_rollout.legacy_gateway_url, &sample).await {
+                Ok((status, body)) => target_result(spec, &sample.method, &route, status, &body),
+                Err(e) => failed_target(format!("Legacy request failed: {}", e)),
+            };
+
+            for (target, result) in [("rust", &rust), ("legacy", &legacy)] {
+                gauge!(
+                    "gateway_contract_check_passing",
+                    "route" => label(Dimension::Route, &route),
+                    "method" => label(Dimension::Method, &sample.method),
+                    "target" => target
+                )
+                .set(if result.passed { 1.0 } else { 0.0 });
+            }
+            if !rust.passed || !legacy.passed {
+                warn!(
+                    method = %sample.method,
+                    route = %route,
+                    rust_errors = ?rust.errors,
+                    legacy_errors = ?legacy.errors,
+                    "Contract check failed"
+                );
+            }
+
+            routes.push(RouteContractResult {
+                method: sample.method.to_uppercase(),
+                passed: rust.passed && legacy.passed,
+                route,
+                path: sample.path.clone(),
+                rust,
+                legacy,
+                checked_at: chrono::Utc::now().to_rfc3339(),
+            });
+        }
+
+        let report = ContractReport {
+            last_run: Some(chrono::Utc::now().to_rfc3339()),
+            routes,
+        };
+        if let Ok(mut current) = self.report.write() {
+            *current = report.clone();
+        }
+        report
+    }
+}
+
+/// Configured samples plus, when enabled, a generated sample for each GET
+/// operation that has no path template and no required parameters.
+pub fn samples_for(config: &ContractCheckConfig, spec: &Value) -> Vec<ContractSample> {
+    let mut samples = config.samples.clone();
+
+    if config.auto_generate_get {
+        let paths = spec.get("paths").and_then(Value::as_object).into_iter().flatten();
+        for (path, item) in paths {
+            let Some(operation) = item.get("get") else { continue };
+            let has_required_params = operation
+                .get("parameters")
+                .and_then(Value::as_array)
+                .map(|params| params.iter().any(|p| p.get("required").and_then(Value::as_bool).unwrap_or(false)))
+                .unwrap_or(false);
+            let already_sampled = samples.iter().any(|sample| {
+                sample.method.eq_ignore_ascii_case("GET")
+                    && sample.route.as_deref().unwrap_or(&sample.path) == path
+            });
+
+            if !path.contains('{') && !has_required_params && !already_sampled {
+                samples.push(ContractSample {
+                    method: "GET".to_string(),
+                    path: path.clone(),
+                    route: None,
+                    headers: Default::default(),
+                    body: None,
+                });
+            }
+        }
+    }
+
+    samples
+}
+
+/// Validates a response against the documented responses of an operation.
+/// Returns the list of violations; empty means the response conforms.
+pub fn validate_response(spec: &Value, method: &str, route: &str, status: u16, body: &[u8]) -> Vec<String> {
+    let Some(operation) = spec
+        .get("paths")
+        .and_then(|paths| paths.get(route))
+        .and_then(|item| item.get(method.to_lowercase()))
+    else {
+        return vec![format!("{} {} is not documented", method.to_uppercase(), route)];
+    };
+
+    let responses = operation.get("responses");
+    let Some(response) = responses
+        .and_then(|r| r.get(status.to_string()))
+        .or_else(|| responses.and_then(|r| r.get("default")))
+    else {
+        return vec![format!("status {} is not documented", status)];
+    };
+
+    let Some(schema) = response.pointer("/content/application~1json/schema") else {
+        return Vec::new();
+    };
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(instance) => {
+            let mut errors = Vec::new();
+            schema::validate(spec, schema, &instance, "$", &mut errors);
+            errors
+        }
+        Err(e) => vec![format!("body is not valid JSON: {}", e)],
+    }
+}
+
+fn target_result(spec: &Value, method: &str, route: &str, status: u16, body: &[u8]) -> TargetResult {
+    let errors = validate_response(spec, method, route, status, body);
+    TargetResult {
+        status: Some(status),
+        passed: errors.is_empty(),
+        errors,
+    }
+}
+
+fn failed_target(error: String) -> TargetResult {
+    TargetResult {
+        status: None,
+        passed: false,
+        errors: vec![error],
+    }
+}
+
+async fn call_rust(router: &Router, sample: &ContractSample, trigger_header: &str) -> anyhow::Result<(u16, Vec<u8>)> {
+    let mut builder = Request::builder()
+        .method(Method::from_str(&sample.method.to_uppercase())?)
+        .uri(&sample.path)
+        // Always exercise the in-process handler, never the canary proxy
+        .header(trigger_header, "rust");
+    for (name, value) in &sample.headers {
+        builder = builder.header(name, value);
+    }
+    let body = match &sample.body {
+        Some(json) => {
+            builder = builder.header("content-type", "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+
+    let response = router.clone().oneshot(builder.body(body)?).await?;
+    let status = response.status().as_u16();
+    let bytes = to_bytes(response.into_body(), MAX_CHECKED_BODY_BYTES).await?;
+    Ok((status, bytes.to_vec()))
+}
+
+async fn call_legacy(upstreams: &UpstreamPool, legacy_url: &str, sample: &ContractSample) -> anyhow::Result<(u16, Vec<u8>)> {
+    let method = reqwest::Method::from_str(&sample.method.to_uppercase())?;
+    let mut request = upstreams
+        .request(Upstream::Legacy, method, &format!("{}{}", legacy_url, sample.path))
+        .timeout(Duration::from_secs(10));
+    for (name, value) in &sample.headers {
+        request = request.header(name, value);
+    }
+    if let Some(json) = &sample.body {
+        request = request.json(json);
+    }
+
+    let response = request.send().await?;
+    let status = response.status().as_u16();
+    Ok((status, response.bytes().await?.to_vec()))
+}
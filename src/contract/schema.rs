@@ -0,0 +1,101 @@
+use serde_json::Value;
+
+/// Validates `instance` against an OpenAPI 3 schema object, resolving local
+/// `$ref`s against `spec`. Covers the subset utoipa emits: types, nullable,
+/// required/properties, items, enum, and allOf/oneOf/anyOf composition.
+pub fn validate(spec: &Value, schema: &Value, instance: &Value, location: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match resolve_ref(spec, reference) {
+            Some(resolved) => validate(spec, resolved, instance, location, errors),
+            None => errors.push(format!("{}: unresolvable schema reference {}", location, reference)),
+        }
+        return;
+    }
+
+    if instance.is_null() && schema.get("nullable").and_then(Value::as_bool).unwrap_or(false) {
+        return;
+    }
+
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for sub_schema in all_of {
+            validate(spec, sub_schema, instance, location, errors);
+        }
+    }
+
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(candidates) = schema.get(keyword).and_then(Value::as_array) {
+            let matches = candidates.iter().any(|candidate| {
+                let mut candidate_errors = Vec::new();
+                validate(spec, candidate, instance, location, &mut candidate_errors);
+                candidate_errors.is_empty()
+            });
+            if !matches {
+                errors.push(format!("{}: value matches none of the {} schemas", location, keyword));
+            }
+        }
+    }
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        if !type_matches(expected, instance) {
+            errors.push(format!("{}: expected {}, found {}", location, expected, type_name(instance)));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(instance) {
+            errors.push(format!("{}: value is not one of the allowed enum values", location));
+        }
+    }
+
+    if let Some(object) = instance.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    errors.push(format!("{}: missing required property '{}'", location, field));
+                }
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, property_schema) in properties {
+                if let Some(value) = object.get(name) {
+                    validate(spec, property_schema, value, &format!("{}.{}", location, name), errors);
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), instance.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            validate(spec, items, item, &format!("{}[{}]", location, index), errors);
+        }
+    }
+}
+
+fn resolve_ref<'a>(spec: &'a Value, reference: &str) -> Option<&'a Value> {
+    spec.pointer(reference.strip_prefix('#')?)
+}
+
+fn type_matches(expected: &str, instance: &Value) -> bool {
+    match expected {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        "number" => instance.is_number(),
+        "null" => instance.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::{sync::Mutex, time::Duration};
+use tokio::{
+    sync::{broadcast, mpsc},
+    time::Instant,
+};
+
+use super::{CoordinationStore, RolloutState};
+
+#[derive(Debug)]
+struct Shared {
+    state: Option<RolloutState>,
+    lease: Option<(String, Instant)>,
+    available: bool,
+    changes: broadcast::Sender<()>,
+}
+
+/// In-process store for replicas that share a process, as in tests. Can be
+/// made unavailable to exercise the degraded fallback.
+#[derive(Debug)]
+pub struct MemoryStore {
+    shared: Mutex<Shared>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            shared: Mutex::new(Shared {
+                state: None,
+                lease: None,
+                available: true,
+                changes: broadcast::channel(16).0,
+            }),
+        }
+    }
+
+    /// Simulates the backend going away and coming back. Going away also
+    /// drops every subscription.
+    pub fn set_available(&self, available: bool) {
+        let mut shared = self.shared.lock().expect("memory store lock");
+        shared.available = available;
+        if !available {
+            // Subscribers see their channel close and resubscribe later
+            shared.changes = broadcast::channel(16).0;
+        }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Shared) -> T) -> Result<T> {
+        let mut shared = self.shared.lock().expect("memory store lock");
+        if !shared.available {
+            bail!("memory coordination store is unavailable");
+        }
+        Ok(f(&mut shared))
+    }
+}
+
+#[async_trait]
+impl CoordinationStore for MemoryStore {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn load(&self) -> Result<Option<RolloutState>> {
+        self.with(|shared| shared.state.clone())
+    }
+
+    async fn store(&self, state: &RolloutState) -> Result<()> {
+        self.with(|shared| {
+            shared.state = Some(state.clone());
+            let _ = shared.changes.send(());
+        })
+    }
+
+    async fn acquire_lease(&self, holder: &str, ttl: Duration) -> Result<bool> {
+        self.with(|shared| {
+            let now = Instant::now();
+            let free = match &shared.lease {
+                Some((current, expires)) => current == holder || *expires <= now,
+                None => true,
+            };
+            if free {
+                shared.lease = Some((holder.to_string(), now + ttl));
+            }
+            free
+        })
+    }
+
+    async fn release_lease(&self, holder: &str) -> Result<()> {
+        self.with(|shared| {
+            if shared.lease.as_ref().is_some_and(|(current, _)| current == holder) {
+                shared.lease = None;
+            }
+        })
+    }
+
+    async fn subscribe(&self) -> Result<mpsc::Receiver<()>> {
+        let mut changes = self.with(|shared| shared.changes.subscribe())?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            while let Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) = changes.recv().await {
+                // A pending signal already covers this one
+                let _ = tx.try_send(());
+                if tx.is_closed() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+}
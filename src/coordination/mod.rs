@@ -0,0 +1,477 @@
+mod memory;
+mod redis;
+
+pub use memory::MemoryStore;
+pub use redis::RedisStore;
+pub(crate) use redis::timed;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, RwLock},
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{error, info, warn};
+
+use crate::config::{watcher::ConfigWatcher, AppConfig, CanaryRolloutConfig, CoordinationKind};
+
+pub use crate::models::rollout::{
+    CoordinationStatus, RolloutGeneration, RolloutMode, RolloutState, RolloutUpdate, MAX_ROLLOUT_HISTORY,
+};
+
+impl RolloutState {
+    fn initial(config: &AppConfig, instance_id: &str) -> Self {
+        let mut state = Self {
+            rollout_percentage: config.canary_rollout.rollout_percentage,
+            paused: false,
+            mode: RolloutMode::Automatic,
+            version: 0,
+            updated_by: instance_id.to_string(),
+            updated_at: chrono::Utc::now().timestamp() as u64,
+            history: VecDeque::new(),
+            override_hold: config.canary_rollout.override_hold,
+        };
+        state.record_generation();
+        state
+    }
+
+    /// Appends the current state to `history`, dropping the oldest entries
+    /// past [`MAX_ROLLOUT_HISTORY`].
+    fn record_generation(&mut self) {
+        self.history.push_back(RolloutGeneration {
+            generation: self.version,
+            rollout_percentage: self.rollout_percentage,
+            paused: self.paused,
+            mode: self.mode,
+            updated_by: self.updated_by.clone(),
+            updated_at: self.updated_at,
+        });
+        while self.history.len() > MAX_ROLLOUT_HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// Rollout generation a request was routed under, in the request and
+/// response extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestGeneration(pub u64);
+
+/// Backend holding the shared state and the gatekeeper leadership lease.
+#[async_trait]
+pub trait CoordinationStore: Send + Sync {
+    /// Backend name for the status endpoint.
+    fn kind(&self) -> &'static str;
+
+    /// The shared state, or `None` before any replica has written it.
+    async fn load(&self) -> Result<Option<RolloutState>>;
+
+    /// Writes the shared state and notifies the other replicas.
+    async fn store(&self, state: &RolloutState) -> Result<()>;
+
+    /// Takes or renews the leadership lease for `holder`. Returns whether
+    /// `holder` is the leader for the next `ttl`.
+    async fn acquire_lease(&self, holder: &str, ttl: Duration) -> Result<bool>;
+
+    /// Gives the lease up early, if `holder` has it.
+    async fn release_lease(&self, holder: &str) -> Result<()>;
+
+    /// Signals whenever another replica writes the state. The channel closes
+    /// when the subscription is lost.
+    async fn subscribe(&self) -> Result<mpsc::Receiver<()>>;
+}
+
+#[derive(Debug)]
+struct Local {
+    state: RolloutState,
+    leader: bool,
+    last_error: Option<String>,
+}
+
+/// Keeps the rollout percentage, pause flag and mode in step across
+/// replicas, and decides which replica runs the gatekeeper.
+///
+/// Without a store every replica is on its own and always leads. With one,
+/// the shared state wins over the local config: it is re-read every
+/// `refresh_interval` and on invalidation, and applied to the config watcher
+/// so canary routing and slow-start follow it. When the store can't be
+/// reached the replica falls back to its local state and runs its own
+/// gatekeeper until the store is back.
+pub struct RolloutCoordinator {
+    instance_id: String,
+    backend: &'static str,
+    store: Option<Arc<dyn CoordinationStore>>,
+    refresh_interval: Duration,
+    leader_lease: Duration,
+    config_watcher: Arc<ConfigWatcher>,
+    local: RwLock<Local>,
+    /// `local.state.version`, readable without the lock on every request.
+    generation: AtomicU64,
+}
+
+/// Identifies this process in leases, `updated_by`, the outbound
+/// User-Agent and `gateway_instance_info`: `GATEWAY_INSTANCE_ID` when set,
+/// otherwise the host name plus a suffix picked once at startup.
+pub fn instance_id() -> String {
+    static INSTANCE_ID: Lazy<String> = Lazy::new(|| {
+        std::env::var("GATEWAY_INSTANCE_ID")
+            .ok()
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| {
+                let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "gateway".to_string());
+                format!("{}-{}", host, &uuid::Uuid::new_v4().simple().to_string()[..8])
+            })
+    });
+    INSTANCE_ID.clone()
+}
+
+impl RolloutCoordinator {
+    pub fn new(
+        config_watcher: Arc<ConfigWatcher>,
+        config: &AppConfig,
+        instance_id: String,
+        store: Option<Arc<dyn CoordinationStore>>,
+        refresh_interval: Duration,
+        leader_lease: Duration,
+    ) -> Self {
+        let backend = store.as_ref().map_or("local", |store| store.kind());
+        Self {
+            local: RwLock::new(Local {
+                state: RolloutState::initial(config, &instance_id),
+                leader: store.is_none(),
+                last_error: None,
+            }),
+            instance_id,
+            backend,
+            store,
+            refresh_interval,
+            leader_lease,
+            config_watcher,
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Builds the coordinator `canary_rollout.coordination` asks for. A
+    /// backend that can't be set up leaves the replica local-only.
+    pub fn from_config(config_watcher: Arc<ConfigWatcher>, config: &AppConfig) -> Self {
+        let Some(coordination) = &config.canary_rollout.coordination else {
+            return Self::new(config_watcher, config, instance_id(), None, Duration::ZERO, Duration::ZERO);
+        };
+        let store = match coordination.kind {
+            CoordinationKind::Redis => RedisStore::new(coordination).map(|store| Arc::new(store) as Arc<dyn CoordinationStore>),
+        };
+        match store {
+            Ok(store) => Self::new(
+                config_watcher,
+                config,
+                instance_id(),
+                Some(store),
+                coordination.refresh_interval.get(),
+                coordination.leader_lease.get(),
+            ),
+            Err(e) => {
+                error!("Rollout coordination disabled, running local-only: {:#}", e);
+                Self::new(config_watcher, config, instance_id(), None, Duration::ZERO, Duration::ZERO)
+            }
+        }
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// The rollout generation this replica is routing under.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn is_coordinated(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Whether this replica should run the gatekeeper evaluation. Always
+    /// true without a store or while it is unreachable.
+    pub async fn is_leader(&self) -> bool {
+        let local = self.local.read().await;
+        local.leader || local.last_error.is_some()
+    }
+
+    /// The state this replica is acting on. The percentage and override
+    /// hold are the ones actually applied to the config.
+    pub async fn state(&self) -> RolloutState {
+        let mut state = self.local.read().await.state.clone();
+        let canary = self.config_watcher.get_config().await.canary_rollout;
+        state.rollout_percentage = canary.rollout_percentage;
+        state.override_hold = canary.override_hold;
+        state
+    }
+
+    pub async fn status(&self) -> CoordinationStatus {
+        let state = self.state().await;
+        let local = self.local.read().await;
+        CoordinationStatus {
+            backend: self.backend.to_string(),
+            instance_id: self.instance_id.clone(),
+            leader: local.leader || local.last_error.is_some(),
+            degraded: local.last_error.is_some(),
+            last_error: local.last_error.clone(),
+            state,
+        }
+    }
+
+    /// Applies `update` here and writes it to the store. A store failure
+    /// doesn't undo the local change; the replica goes degraded instead.
+    pub async fn update(&self, update: RolloutUpdate, updated_by: &str) -> RolloutState {
+        let mut state = self.state().await;
+        if let Some(percentage) = update.rollout_percentage {
+            if percentage > state.rollout_percentage {
+                state.override_hold = None;
+            }
+            state.rollout_percentage = percentage;
+        }
+        if let Some(paused) = update.paused {
+            state.paused = paused;
+        }
+        if let Some(mode) = update.mode {
+            state.mode = mode;
+        }
+        self.write(state, updated_by).await
+    }
+
+    /// Rolls the percentage back to `percentage`, holding route overrides to
+    /// the rollout percentage until it is raised again.
+    pub async fn roll_back(&self, percentage: f64, updated_by: &str) -> RolloutState {
+        let mut state = self.state().await;
+        state.rollout_percentage = percentage;
+        state.override_hold = Some(percentage);
+        self.write(state, updated_by).await
+    }
+
+    async fn write(&self, mut state: RolloutState, updated_by: &str) -> RolloutState {
+        state.version += 1;
+        state.updated_by = updated_by.to_string();
+        state.updated_at = chrono::Utc::now().timestamp() as u64;
+        state.record_generation();
+
+        if let Some(store) = &self.store {
+            match store.store(&state).await {
+                Ok(()) => self.recovered().await,
+                Err(e) => self.degrade(e).await,
+            }
+        }
+        self.adopt(state.clone()).await;
+        state
+    }
+
+    pub async fn set_percentage(&self, percentage: f64, updated_by: &str) -> RolloutState {
+        self.update(
+            RolloutUpdate {
+                rollout_percentage: Some(percentage),
+                ..Default::default()
+            },
+            updated_by,
+        )
+        .await
+    }
+
+    /// Takes over `state`, generation and history included, from another
+    /// instance. Refused with a store, whose shared state wins anyway.
+    /// Returns whether it was taken over.
+    pub async fn restore(&self, state: RolloutState) -> bool {
+        if self.store.is_some() {
+            return false;
+        }
+        self.adopt(state).await;
+        true
+    }
+
+    /// Makes `state` the local one, applying its percentage and override
+    /// hold to the config. With an overlay file they're persisted there too,
+    /// so they survive a restart. The generation is published once the percentage
+    /// applies, so requests are never stamped ahead of what routed them.
+    async fn adopt(&self, state: RolloutState) {
+        let generation = state.version;
+        self.apply(state).await;
+        self.generation.store(generation, Ordering::Release);
+    }
+
+    async fn apply(&self, state: RolloutState) {
+        let config = self.config_watcher.get_config().await;
+        let (percentage, hold) = (state.rollout_percentage, state.override_hold);
+        let updated_by = state.updated_by.clone();
+        self.local.write().await.state = state;
+        // The hold goes first, so overrides are held by the time a rollback
+        // lowers the percentage
+        if config.canary_rollout.override_hold != hold {
+            self.set("canary_rollout.override_hold", hold, &updated_by, |canary| {
+                canary.override_hold = hold
+            })
+            .await;
+        }
+        if config.canary_rollout.rollout_percentage != percentage {
+            info!(
+                from = config.canary_rollout.rollout_percentage,
+                to = percentage,
+                "Applying shared rollout percentage"
+            );
+            self.set("canary_rollout.rollout_percentage", percentage, &updated_by, |canary| {
+                canary.rollout_percentage = percentage
+            })
+            .await;
+        }
+    }
+
+    /// Persists `value` at `path` in the overlay file when there is one,
+    /// and otherwise applies it in memory through `assign`.
+    async fn set(
+        &self,
+        path: &str,
+        value: impl serde::Serialize,
+        updated_by: &str,
+        assign: impl FnOnce(&mut CanaryRolloutConfig),
+    ) {
+        if self.config_watcher.has_overlay() {
+            match self.config_watcher.persist(path, value, updated_by).await {
+                Ok(_) => return,
+                Err(e) => error!("{} not persisted, applying it in memory only: {:#}", path, e),
+            }
+        }
+        let mut config = self.config_watcher.get_config().await;
+        assign(&mut config.canary_rollout);
+        self.config_watcher.apply(config).await;
+    }
+
+    async fn degrade(&self, error: anyhow::Error) {
+        let mut local = self.local.write().await;
+        if local.last_error.is_none() {
+            error!(
+                "Rollout coordination backend unreachable, falling back to local-only mode: {:#}",
+                error
+            );
+        }
+        local.last_error = Some(format!("{:#}", error));
+    }
+
+    async fn recovered(&self) {
+        let mut local = self.local.write().await;
+        if local.last_error.take().is_some() {
+            info!("Rollout coordination backend reachable again");
+        }
+    }
+
+    /// Re-reads the shared state, seeding it from the local one when no
+    /// replica has written it yet.
+    pub async fn refresh(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        match store.load().await {
+            Ok(Some(shared)) => {
+                self.recovered().await;
+                let config = self.config_watcher.get_config().await;
+                let local = self.local.read().await.state.clone();
+                if shared.version < local.version {
+                    // Changed here while the store was unreachable
+                    match store.store(&self.state().await).await {
+                        Ok(()) => info!(version = local.version, "Published rollout state changed while degraded"),
+                        Err(e) => self.degrade(e).await,
+                    }
+                    return;
+                }
+                let applied = (config.canary_rollout.rollout_percentage, config.canary_rollout.override_hold);
+                if shared != local || applied != (shared.rollout_percentage, shared.override_hold) {
+                    self.adopt(shared).await;
+                }
+            }
+            Ok(None) => {
+                let state = self.state().await;
+                match store.store(&state).await {
+                    Ok(()) => {
+                        info!(rollout_percentage = state.rollout_percentage, "Seeded shared rollout state");
+                        self.recovered().await;
+                    }
+                    Err(e) => self.degrade(e).await,
+                }
+            }
+            Err(e) => self.degrade(e).await,
+        }
+    }
+
+    async fn renew_lease(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        match store.acquire_lease(&self.instance_id, self.leader_lease).await {
+            Ok(leader) => {
+                let mut local = self.local.write().await;
+                if leader != local.leader {
+                    info!(instance = %self.instance_id, leader, "Gatekeeper leadership changed");
+                }
+                local.leader = leader;
+            }
+            Err(e) => {
+                self.local.write().await.leader = false;
+                self.degrade(e).await;
+            }
+        }
+    }
+
+    /// Gives up leadership, e.g. on shutdown, so another replica takes over
+    /// without waiting for the lease to expire.
+    pub async fn resign(&self) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.release_lease(&self.instance_id).await {
+                warn!("Failed to release gatekeeper lease: {:#}", e);
+            }
+            self.local.write().await.leader = false;
+        }
+    }
+
+    /// Keeps the lease renewed and the local state in step with the store.
+    /// Returns immediately without a store.
+    pub async fn run(self: Arc<Self>) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        info!(instance = %self.instance_id, backend = self.backend, "Rollout coordination started");
+
+        let mut refresh = interval(self.refresh_interval);
+        refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut renew = interval(self.leader_lease / 3);
+        renew.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut invalidations: Option<mpsc::Receiver<()>> = None;
+
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    if invalidations.is_none() {
+                        invalidations = store.subscribe().await.ok();
+                    }
+                    self.refresh().await;
+                }
+                _ = renew.tick() => self.renew_lease().await,
+                signal = next_invalidation(&mut invalidations) => match signal {
+                    Some(()) => self.refresh().await,
+                    // Resubscribed on the next refresh
+                    None => invalidations = None,
+                },
+            }
+        }
+    }
+}
+
+async fn next_invalidation(invalidations: &mut Option<mpsc::Receiver<()>>) -> Option<()> {
+    match invalidations {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use std::{future::Future, time::Duration};
+use tokio::sync::{mpsc, OnceCell};
+
+use super::{CoordinationStore, RolloutState};
+use crate::config::CoordinationConfig;
+
+/// Upper bound on any single Redis round trip, so an unreachable server
+/// degrades the replica instead of stalling the coordination loop.
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Takes the lease when it is free or already ours, extending it either way.
+const ACQUIRE_LEASE: &str = r#"
+local holder = redis.call('GET', KEYS[1])
+if holder == false or holder == ARGV[1] then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+end
+return 0
+"#;
+
+/// Deletes the lease only if it is still ours.
+const RELEASE_LEASE: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// Keeps the shared state as JSON under `<prefix>rollout:state`, the lease
+/// under `<prefix>rollout:leader`, and announces writes on the
+/// `<prefix>rollout:changed` channel.
+pub struct RedisStore {
+    client: redis::Client,
+    /// Connected on first use, so startup doesn't depend on Redis.
+    connection: OnceCell<ConnectionManager>,
+    state_key: String,
+    leader_key: String,
+    channel: String,
+}
+
+impl RedisStore {
+    pub fn new(config: &CoordinationConfig) -> Result<Self> {
+        let client = redis::Client::open(config.url.as_str()).context("invalid coordination URL")?;
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            state_key: format!("{}rollout:state", config.key_prefix),
+            leader_key: format!("{}rollout:leader", config.key_prefix),
+            channel: format!("{}rollout:changed", config.key_prefix),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| timed(self.client.get_connection_manager()))
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+pub(crate) async fn timed<T>(operation: impl Future<Output = redis::RedisResult<T>>) -> Result<T> {
+    tokio::time::timeout(OPERATION_TIMEOUT, operation)
+        .await
+        .map_err(|_| anyhow!("redis operation timed out after {:?}", OPERATION_TIMEOUT))?
+        .context("redis operation failed")
+}
+
+#[async_trait]
+impl CoordinationStore for RedisStore {
+    fn kind(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn load(&self) -> Result<Option<RolloutState>> {
+        let mut connection = self.connection().await?;
+        let raw: Option<String> = timed(connection.get(&self.state_key)).await?;
+        raw.map(|raw| serde_json::from_str(&raw).context("malformed shared rollout state"))
+            .transpose()
+    }
+
+    async fn store(&self, state: &RolloutState) -> Result<()> {
+        let mut connection = self.connection().await?;
+        let raw = serde_json::to_string(state)?;
+        timed(
+            redis::pipe()
+                .atomic()
+                .set(&self.state_key, raw)
+                .ignore()
+                .publish(&self.channel, state.version)
+                .ignore()
+                .query_async::<_, ()>(&mut connection),
+        )
+        .await
+    }
+
+    async fn acquire_lease(&self, holder: &str, ttl: Duration) -> Result<bool> {
+        let mut connection = self.connection().await?;
+        let acquired: i32 = timed(
+            Script::new(ACQUIRE_LEASE)
+                .key(&self.leader_key)
+                .arg(holder)
+                .arg(ttl.as_millis() as u64)
+                .invoke_async(&mut connection),
+        )
+        .await?;
+        Ok(acquired == 1)
+    }
+
+    async fn release_lease(&self, holder: &str) -> Result<()> {
+        let mut connection = self.connection().await?;
+        timed(
+            Script::new(RELEASE_LEASE)
+                .key(&self.leader_key)
+                .arg(holder)
+                .invoke_async::<_, i32>(&mut connection),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<mpsc::Receiver<()>> {
+        let mut pubsub = timed(self.client.get_async_pubsub()).await?;
+        timed(pubsub.subscribe(&self.channel)).await?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message();
+            while messages.next().await.is_some() {
+                // A pending signal already covers this one
+                let _ = tx.try_send(());
+                if tx.is_closed() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+}
@@ -0,0 +1,122 @@
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    coordination::{CoordinationStatus, RolloutUpdate},
+    features::{Feature, FeatureState},
+    routes::admin::{
+        ConfigReloadResponse, FeatureOverrideRequest, MirrorStatus, RollbackRequest, RouteMatchQuery, RouteStatus,
+    },
+};
+
+use super::CtlError;
+
+/// Pins admin calls to this gateway; at a 0% rollout unpinned requests,
+/// `/admin` included, are proxied to the legacy gateway.
+const PIN_HEADER: (&str, &str) = ("X-Gateway-Version", "rust");
+
+/// Typed client over the admin API, speaking the server's own request and
+/// response types.
+pub struct AdminClient {
+    server: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl AdminClient {
+    pub fn new(server: &str, token: Option<String>) -> Self {
+        Self {
+            server: server.trim_end_matches('/').to_string(),
+            token,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.server, path))
+            .header(PIN_HEADER.0, PIN_HEADER.1);
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, CtlError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(CtlError::Api {
+                status: status.as_u16(),
+                message: api_message(status, body.trim()),
+            });
+        }
+        Ok(response.json().await?)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, CtlError> {
+        self.send(self.request(Method::GET, path)).await
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, method: Method, path: &str, body: &impl Serialize) -> Result<T, CtlError> {
+        self.send(self.request(method, path).json(body)).await
+    }
+
+    pub async fn rollout_status(&self) -> Result<CoordinationStatus, CtlError> {
+        self.get("/admin/rollout").await
+    }
+
+    pub async fn update_rollout(&self, update: &RolloutUpdate) -> Result<CoordinationStatus, CtlError> {
+        self.send_json(Method::PUT, "/admin/rollout", update).await
+    }
+
+    pub async fn advance_rollout(&self) -> Result<CoordinationStatus, CtlError> {
+        self.send(self.request(Method::POST, "/admin/rollout/advance")).await
+    }
+
+    pub async fn rollback(&self, request: &RollbackRequest) -> Result<CoordinationStatus, CtlError> {
+        self.send_json(Method::POST, "/admin/rollout/rollback", request).await
+    }
+
+    pub async fn config(&self) -> Result<serde_json::Value, CtlError> {
+        self.get("/admin/config").await
+    }
+
+    pub async fn reload_config(&self) -> Result<ConfigReloadResponse, CtlError> {
+        self.send(self.request(Method::POST, "/admin/config/reload")).await
+    }
+
+    pub async fn mirror_status(&self) -> Result<MirrorStatus, CtlError> {
+        self.get("/admin/mirror/status").await
+    }
+
+    pub async fn set_feature(&self, feature: Feature, request: &FeatureOverrideRequest) -> Result<FeatureState, CtlError> {
+        self.send_json(Method::PUT, &format!("/admin/features/{}", feature), request)
+            .await
+    }
+
+    pub async fn routes(&self) -> Result<Vec<RouteStatus>, CtlError> {
+        self.get("/admin/routes").await
+    }
+
+    pub async fn match_route(&self, query: &RouteMatchQuery) -> Result<RouteStatus, CtlError> {
+        self.send(self.request(Method::GET, "/admin/routes/match").query(query))
+            .await
+    }
+}
+
+/// The server's explanation, or what the status means for the admin API
+/// when the body is empty.
+fn api_message(status: StatusCode, body: &str) -> String {
+    if !body.is_empty() {
+        return body.to_string();
+    }
+    match status {
+        StatusCode::UNAUTHORIZED => "not authorized; check --token-file".to_string(),
+        StatusCode::NOT_FOUND => "not found".to_string(),
+        StatusCode::CONFLICT => "refused in the current state".to_string(),
+        _ => status.canonical_reason().unwrap_or("request failed").to_lowercase(),
+    }
+}
@@ -0,0 +1,318 @@
+//! `project-gateway ctl`: operate a running gateway through its admin API.
+//!
+//! Each subcommand is one admin call made with [`AdminClient`], printed as a
+//! table or, with `--output json`, as the API's own JSON. API errors exit
+//! with 1 and usage errors with 2.
+
+pub mod client;
+
+use serde::Serialize;
+use std::io::Write;
+
+use crate::{
+    coordination::{CoordinationStatus, RolloutUpdate},
+    features::{Feature, FeatureState},
+    routes::admin::{ConfigReloadResponse, FeatureOverrideRequest, MirrorStatus, RollbackRequest, RouteMatchQuery, RouteStatus},
+};
+pub use client::AdminClient;
+
+pub const USAGE: &str = "usage: project-gateway ctl [--server <url>] [--token-file <path>] [--output table|json] <command>
+
+commands:
+  rollout status|advance|pause|resume
+  rollout rollback [reason]
+  config show|reload
+  mirror status
+  mirror enable|disable [--ttl <seconds>]
+  routes list
+  routes match <method> <path>";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CtlError {
+    #[error("{0}\n{USAGE}")]
+    Usage(String),
+    #[error("API error {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl CtlError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CtlError::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+/// Global flags and the remaining subcommand words.
+#[derive(Debug)]
+pub struct Invocation {
+    pub server: String,
+    pub token_file: Option<String>,
+    pub output: OutputFormat,
+    pub command: Vec<String>,
+}
+
+impl Invocation {
+    pub fn parse(args: &[String]) -> Result<Self, CtlError> {
+        let mut invocation = Invocation {
+            server: std::env::var("GATEWAY_URL").unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            token_file: None,
+            output: OutputFormat::Table,
+            command: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| CtlError::Usage(format!("{} needs a value", arg)))
+            };
+            match arg.as_str() {
+                "--server" => invocation.server = value()?,
+                "--token-file" => invocation.token_file = Some(value()?),
+                "--output" | "-o" => {
+                    invocation.output = match value()?.as_str() {
+                        "table" => OutputFormat::Table,
+                        "json" => OutputFormat::Json,
+                        other => return Err(CtlError::Usage(format!("unknown output format {}", other))),
+                    }
+                }
+                _ => invocation.command.push(arg.clone()),
+            }
+        }
+        Ok(invocation)
+    }
+
+    fn client(&self) -> Result<AdminClient, CtlError> {
+        let token = match &self.token_file {
+            Some(path) => {
+                let token = std::fs::read_to_string(path)?;
+                Some(token.trim().to_string())
+            }
+            None => None,
+        };
+        Ok(AdminClient::new(&self.server, token))
+    }
+}
+
+/// Runs `ctl` with the arguments after `ctl`, writing the result to `out`.
+pub async fn run(args: &[String], out: &mut impl Write) -> Result<(), CtlError> {
+    let invocation = Invocation::parse(args)?;
+    let client = invocation.client()?;
+    let words: Vec<&str> = invocation.command.iter().map(String::as_str).collect();
+    let output = invocation.output;
+
+    match words.as_slice() {
+        ["rollout", "status"] => print_rollout(out, output, &client.rollout_status().await?),
+        ["rollout", "advance"] => print_rollout(out, output, &client.advance_rollout().await?),
+        ["rollout", "pause"] => print_rollout(out, output, &client.update_rollout(&paused(true)).await?),
+        ["rollout", "resume"] => print_rollout(out, output, &client.update_rollout(&paused(false)).await?),
+        ["rollout", "rollback", reason @ ..] => {
+            let request = RollbackRequest {
+                reason: (!reason.is_empty()).then(|| reason.join(" ")),
+            };
+            print_rollout(out, output, &client.rollback(&request).await?)
+        }
+        ["config", "show"] => {
+            let config = client.config().await?;
+            match output {
+                OutputFormat::Json => print_json(out, &config),
+                OutputFormat::Table => Ok(write!(out, "{}", serde_yaml::to_string(&config).unwrap_or_default())?),
+            }
+        }
+        ["config", "reload"] => print_reload(out, output, &client.reload_config().await?),
+        ["mirror", "status"] => print_mirror(out, output, &client.mirror_status().await?),
+        ["mirror", toggle @ ("enable" | "disable"), rest @ ..] => {
+            let request = FeatureOverrideRequest {
+                enabled: Some(*toggle == "enable"),
+                ttl_seconds: ttl(rest)?,
+            };
+            print_feature(out, output, &client.set_feature(Feature::Mirror, &request).await?)
+        }
+        ["routes", "list"] => print_routes(out, output, &client.routes().await?),
+        ["routes", "match", method, path] => {
+            let query = RouteMatchQuery {
+                method: method.to_uppercase(),
+                path: path.to_string(),
+            };
+            print_route(out, output, &client.match_route(&query).await?)
+        }
+        [] => Err(CtlError::Usage("missing command".to_string())),
+        _ => Err(CtlError::Usage(format!("unknown command: {}", words.join(" ")))),
+    }
+}
+
+fn paused(paused: bool) -> RolloutUpdate {
+    RolloutUpdate {
+        paused: Some(paused),
+        ..RolloutUpdate::default()
+    }
+}
+
+fn ttl(args: &[&str]) -> Result<Option<u64>, CtlError> {
+    match args {
+        [] => Ok(None),
+        ["--ttl", seconds] => seconds
+            .parse()
+            .map(Some)
+            .map_err(|_| CtlError::Usage(format!("--ttl takes whole seconds, not {}", seconds))),
+        _ => Err(CtlError::Usage(format!("unexpected arguments: {}", args.join(" ")))),
+    }
+}
+
+fn print_json(out: &mut impl Write, value: &impl Serialize) -> Result<(), CtlError> {
+    let json = serde_json::to_string_pretty(value).unwrap_or_default();
+    Ok(writeln!(out, "{}", json)?)
+}
+
+/// A serialized enum as its bare name, e.g. `automatic`.
+fn label(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+/// Left-aligned columns sized to their widest cell.
+fn write_table(out: &mut impl Write, header: &[&str], rows: &[Vec<String>]) -> Result<(), CtlError> {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|cell| cell.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn write_fields(out: &mut impl Write, fields: Vec<(&str, String)>) -> Result<(), CtlError> {
+    let rows: Vec<Vec<String>> = fields
+        .into_iter()
+        .map(|(name, value)| vec![name.to_string(), value])
+        .collect();
+    write_table(out, &["FIELD", "VALUE"], &rows)
+}
+
+fn print_rollout(out: &mut impl Write, output: OutputFormat, status: &CoordinationStatus) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, status);
+    }
+    let state = &status.state;
+    write_fields(
+        out,
+        vec![
+            ("rollout_percentage", state.rollout_percentage.to_string()),
+            ("paused", state.paused.to_string()),
+            ("mode", label(&state.mode)),
+            ("version", state.version.to_string()),
+            ("updated_by", state.updated_by.clone()),
+            ("backend", status.backend.clone()),
+            ("leader", status.leader.to_string()),
+            ("degraded", status.degraded.to_string()),
+        ],
+    )
+}
+
+fn print_reload(out: &mut impl Write, output: OutputFormat, reload: &ConfigReloadResponse) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, reload);
+    }
+    writeln!(out, "Configuration reloaded at {}", reload.loaded_at)?;
+    if !reload.retained_sections.is_empty() {
+        writeln!(
+            out,
+            "partial: applied {}; kept previous {}",
+            reload.applied_sections.join(", "),
+            reload.retained_sections.join(", ")
+        )?;
+    }
+    for warning in &reload.warnings {
+        writeln!(out, "warning: {}", warning)?;
+    }
+    Ok(())
+}
+
+fn print_mirror(out: &mut impl Write, output: OutputFormat, status: &MirrorStatus) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, status);
+    }
+    write_fields(
+        out,
+        vec![
+            ("enabled", status.enabled.to_string()),
+            ("sample_percentage", status.sample_percentage.to_string()),
+            ("default_sample_percentage", status.default_sample_percentage.to_string()),
+            ("active_window", optional(status.active_window.as_ref())),
+            ("timezone", status.timezone.clone()),
+            ("mirror_samples", optional(status.summary.as_ref().map(|summary| summary.samples))),
+            ("mirror_success_rate", optional(status.summary.as_ref().map(|summary| summary.success_rate))),
+        ],
+    )
+}
+
+fn print_feature(out: &mut impl Write, output: OutputFormat, state: &FeatureState) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, state);
+    }
+    write_fields(
+        out,
+        vec![
+            ("feature", state.name.to_string()),
+            ("effective", state.effective.to_string()),
+            ("config_value", state.config_value.to_string()),
+            ("override", optional(state.override_value)),
+            ("expires_at", optional(state.override_expires_at.as_ref())),
+        ],
+    )
+}
+
+fn route_row(route: &RouteStatus) -> Vec<String> {
+    vec![
+        route.method.clone(),
+        route.path.clone(),
+        route.live.to_string(),
+        optional(route.smoke.as_ref().map(|smoke| label(&smoke.state))),
+        route.legacy_endpoint.clone(),
+    ]
+}
+
+const ROUTE_HEADER: [&str; 5] = ["METHOD", "PATH", "LIVE", "SMOKE", "LEGACY"];
+
+fn print_routes(out: &mut impl Write, output: OutputFormat, routes: &[RouteStatus]) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, &routes);
+    }
+    let rows: Vec<Vec<String>> = routes.iter().map(route_row).collect();
+    write_table(out, &ROUTE_HEADER, &rows)
+}
+
+fn print_route(out: &mut impl Write, output: OutputFormat, route: &RouteStatus) -> Result<(), CtlError> {
+    if output == OutputFormat::Json {
+        return print_json(out, route);
+    }
+    write_table(out, &ROUTE_HEADER, &[route_row(route)])
+}
@@ -0,0 +1,119 @@
+//! `project-gateway dev`: the gateway with fake legacy and mirror targets.
+//!
+//! The fakes are started on ephemeral ports, and a config pointing the
+//! canary proxy and the mirror at them is generated from `base_config` and
+//! written to `generated_config` for the gateway to load and watch.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, path::PathBuf};
+
+use crate::{
+    config::{AppConfig, MirrorQueueKind},
+    test_util::{FakeLegacy, FakeUpstreamConfig, SeedUser},
+};
+
+/// `dev.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevConfig {
+    /// Gateway port; 0 picks a free one.
+    pub port: u16,
+    pub base_config: String,
+    pub generated_config: String,
+    /// Share of requests the Rust handlers serve; the rest go to the fake
+    /// legacy gateway.
+    pub rollout_percentage: f64,
+    pub legacy: FakeUpstreamConfig,
+    pub mirror: FakeUpstreamConfig,
+    /// Users the fake legacy gateway starts with.
+    pub seed_users: Vec<SeedUser>,
+}
+
+impl Default for DevConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            base_config: "config/default.yaml".to_string(),
+            generated_config: "target/dev/gateway.yaml".to_string(),
+            rollout_percentage: 50.0,
+            legacy: FakeUpstreamConfig::default(),
+            mirror: FakeUpstreamConfig::default(),
+            seed_users: Vec::new(),
+        }
+    }
+}
+
+impl DevConfig {
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+        serde_yaml::from_str(&contents).with_context(|| format!("parsing {}", path))
+    }
+}
+
+/// The running fakes and the config generated for them.
+pub struct DevEnvironment {
+    pub legacy: FakeLegacy,
+    pub mirror: FakeLegacy,
+    pub config_path: PathBuf,
+}
+
+impl DevEnvironment {
+    pub async fn start(dev: &DevConfig) -> Result<Self> {
+        let legacy = FakeLegacy::start("fake-legacy", dev.legacy.clone(), &dev.seed_users).await?;
+        let mirror = FakeLegacy::start("fake-mirror", dev.mirror.clone(), &dev.seed_users).await?;
+
+        let config = gateway_config(AppConfig::load_from(&dev.base_config)?, dev, &legacy, &mirror);
+        config.validate()?;
+        let config_path = PathBuf::from(&dev.generated_config);
+        if let Some(dir) = config_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let yaml = format!(
+            "# Generated by `project-gateway dev`; edits last until it restarts\n{}",
+            serde_yaml::to_string(&config)?
+        );
+        std::fs::write(&config_path, yaml).with_context(|| format!("writing {}", config_path.display()))?;
+
+        Ok(Self {
+            legacy,
+            mirror,
+            config_path,
+        })
+    }
+
+    /// The URLs worth knowing, printed once the gateway is listening.
+    pub fn summary(&self, addr: SocketAddr, config: &AppConfig) -> String {
+        let gateway = format!("http://127.0.0.1:{}", addr.port());
+        [
+            "Project Gateway dev mode".to_string(),
+            format!("  Gateway:      {}", gateway),
+            format!("  API docs:     {}/docs", gateway),
+            format!("  Metrics:      {}{}", gateway, config.metrics.path),
+            format!("  Rollout:      {}/admin/rollout", gateway),
+            format!("  Routes:       {}/admin/routes", gateway),
+            format!("  Gatekeeper:   {}/gatekeeper/status", gateway),
+            format!("  Fake legacy:  {}", self.legacy.url()),
+            format!("  Fake mirror:  {}", self.mirror.url()),
+            format!("  Config:       {}", self.config_path.display()),
+        ]
+        .join("\n")
+    }
+}
+
+/// `base` pointed at the fakes, with everything that needs outside services
+/// or credentials turned off.
+pub fn gateway_config(mut base: AppConfig, dev: &DevConfig, legacy: &FakeLegacy, mirror: &FakeLegacy) -> AppConfig {
+    base.server.port = dev.port;
+    base.server.tls = None;
+    base.canary_rollout.enabled = true;
+    base.canary_rollout.rollout_percentage = dev.rollout_percentage;
+    base.canary_rollout.legacy_gateway_url = legacy.url();
+    base.canary_rollout.webhook_url = String::new();
+    base.canary_rollout.coordination = None;
+    base.mirror.enabled = true;
+    base.mirror.base_url = mirror.url();
+    base.mirror.queue.kind = MirrorQueueKind::Memory;
+    base.middleware.auth.enabled = false;
+    base
+}
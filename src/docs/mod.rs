@@ -1,19 +1,79 @@
-use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
-use axum::Router;
+use std::{net::SocketAddr, sync::Arc};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ServerBuilder,
+    },
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::Config;
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Redirect, Response},
+    routing::get,
+    Extension, Router,
+};
+use tracing::info;
 
 use crate::{
-    routes::{health, users},
+    config::ServerConfig,
+    middleware::auth::{authenticate, Claims},
+    routes::{admin, health, links::PublicPrefix, monitoring, users, versions},
     AppState,
 };
 
+/// Scope a token needs to read restricted docs, or the full spec when admin
+/// paths are redacted.
+pub const DOCS_SCOPE: &str = "docs:read";
+const ADMIN_TAG: &str = "admin";
+
 #[derive(OpenApi)]
 #[openapi(
     paths(
         health::health,
         health::health_detailed,
+        health::readiness,
         users::list_users,
         users::create_user,
+        admin::effective_config,
+        admin::reload_config,
+        admin::validate_config,
+        admin::stage_config,
+        admin::promote_staged_config,
+        admin::discard_staged_config,
+        admin::contract_report,
+        admin::upstreams,
+        admin::tls_certificates,
+        admin::routes,
+        admin::match_route,
+        admin::mirror_status,
+        admin::mirror_report,
+        admin::notification_status,
+        admin::metrics_selfcheck,
+        admin::export_state,
+        admin::import_state,
+        admin::quota_usage,
+        admin::rollout_state,
+        admin::update_rollout,
+        admin::advance_rollout,
+        admin::rollback_rollout,
+        admin::evaluate_gatekeeper,
+        admin::list_features,
+        admin::set_feature,
+        admin::read_only_status,
+        admin::set_read_only,
+        admin::start_capture,
+        admin::capture_results,
+        admin::export_capture,
+        monitoring::slo_status,
+        monitoring::performance,
+        monitoring::heatmap,
+        versions::list_versions,
+        admin::auth_failures,
+        admin::list_profiles,
+        admin::get_profile,
     ),
     components(
         schemas(
@@ -21,18 +81,115 @@ use crate::{
             health::DetailedHealthResponse,
             health::ServerConfigInfo,
             health::UpstreamStatus,
+            crate::upstream::health::UpstreamProbe,
+            crate::upstream::health::ProbeMethod,
+            health::ReadinessResponse,
+            crate::memory::MemoryReport,
+            crate::memory::StoreUsage,
+            crate::clock::ClockSkew,
+            crate::listener::ListenerReport,
+            crate::listener::ListenerEvent,
+            crate::listener::ListenerEventKind,
+            crate::config::watcher::ConfigReloadEvent,
+            crate::config::watcher::ConfigReloadOutcome,
             users::User,
             users::CreateUserRequest,
             users::CreateUserResponse,
             users::UserListResponse,
+            crate::routes::error::ErrorResponse,
+            crate::routes::error::ErrorBody,
             crate::gatekeeper::GatekeeperStatus,
+            crate::gatekeeper::RolloutReadiness,
+            crate::gatekeeper::ScopedReduction,
+            crate::gatekeeper::SlowStartStatus,
+            crate::gatekeeper::SmokeStatus,
+            crate::gatekeeper::SmokeState,
+            crate::gatekeeper::GatekeeperEvaluation,
+            crate::gatekeeper::GatekeeperDecision,
+            crate::gatekeeper::HealthSnapshot,
+            crate::gatekeeper::HealthThresholds,
+            crate::gatekeeper::RouteSnapshot,
+            crate::gatekeeper::RuleEvaluation,
+            crate::gatekeeper::RuleOutcome,
+            crate::gatekeeper::ThresholdOverrides,
+            crate::gatekeeper::RollbackAction,
+            crate::gatekeeper::RollbackScope,
+            crate::coordination::CoordinationStatus,
+            crate::coordination::RolloutState,
+            crate::coordination::RolloutUpdate,
+            crate::coordination::RolloutMode,
+            crate::monitoring::MirrorSummary,
+            crate::monitoring::LatencyDecomposition,
+            crate::monitoring::LatencyPercentiles,
+            crate::monitoring::PerformanceMetrics,
+            crate::monitoring::PerformanceValidation,
+            crate::monitoring::drift::LatencyDriftStatus,
+            crate::monitoring::heatmap::LatencyHeatmapReport,
+            monitoring::PerformanceReport,
+            crate::contract::ContractReport,
+            crate::contract::RouteContractResult,
+            crate::contract::TargetResult,
+            crate::upstream::UpstreamPoolStats,
+            crate::tls::TlsCertificateInfo,
+            crate::features::Feature,
+            crate::features::FeatureState,
+            admin::ConfigReloadResponse,
+            admin::ConfigValidation,
+            admin::ConfigCheckIssue,
+            crate::config::diff::ConfigChange,
+            admin::StageConfigRequest,
+            admin::FeatureOverrideRequest,
+            admin::ReadOnlyRequest,
+            admin::ReadOnlyStatus,
+            admin::RollbackRequest,
+            admin::MirrorStatus,
+            crate::mirror::report::MirrorReportRequest,
+            crate::mirror::report::MirrorReport,
+            crate::mirror::report::Verdict,
+            crate::mirror::report::ThresholdCheck,
+            crate::mirror::report::ParityTotals,
+            crate::mirror::report::RouteParity,
+            crate::mirror::report::MismatchExample,
+            admin::RouteStatus,
+            crate::middleware::capture::CaptureRequest,
+            crate::middleware::capture::CaptureStatus,
+            crate::middleware::capture::CaptureResults,
+            crate::middleware::capture::CapturedExchange,
+            crate::middleware::capture::CapturedMessage,
+            crate::middleware::capture::CapturedTimings,
+            crate::middleware::auth_audit::AuthFailure,
+            crate::middleware::auth_audit::AuthFailureReason,
+            crate::profiling::ProfileSummary,
+            crate::profiling::Trigger,
+            crate::monitoring::slo::SloStatus,
+            crate::monitoring::slo::SliStatus,
+            crate::maintenance::ActiveMaintenance,
+            crate::notifications::NotificationStatus,
+            crate::metrics::selfcheck::MetricsSelfCheck,
+            crate::metrics::selfcheck::MetricFamilyCheck,
+            crate::notifications::DestinationStatus,
+            crate::handoff::StateSnapshot,
+            crate::handoff::GatekeeperState,
+            crate::handoff::StateImportReport,
+            crate::quota::QuotaReport,
+            crate::quota::QuotaUsage,
+            crate::quota::QuotaPeriod,
+            crate::monitoring::PerformanceBaseline,
+            crate::warmup::WarmupReport,
+            crate::warmup::WarmupStep,
+            versions::ApiVersionsResponse,
+            versions::ApiVersionInfo,
+            crate::config::VersionSource,
         )
     ),
+    modifiers(&BearerAuth),
     tags(
         (name = "health", description = "Health check endpoints"),
         (name = "users", description = "User management endpoints"),
+        (name = "versions", description = "API version negotiation"),
         (name = "monitoring", description = "Monitoring and status endpoints"),
         (name = "testing", description = "Testing and validation endpoints"),
+        (name = "admin", description = "Operational endpoints"),
     ),
     info(
         title = "Project Gateway API",
@@ -47,20 +204,169 @@ use crate::{
             url = "https://opensource.org/licenses/MIT",
         ),
     ),
-    servers(
-        (url = "http://localhost:3000", description = "Local development server"),
-        (url = "https://api.gateway.internal", description = "Production server"),
-    ),
 )]
 pub struct ApiDoc;
 
-pub fn create_swagger_router() -> Router<AppState> {
-    SwaggerUi::new("/docs")
-        .url("/api-docs/openapi.json", ApiDoc::openapi())
-        .into()
+/// Declares the JWT bearer scheme routes with `authorization` refer to.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+/// Swagger UI at `/docs` and the spec at `/api-docs/openapi.json`, mounted
+/// both at the root and under `base_path`, behind [`docs_access`].
+///
+/// The root mount serves ingresses that strip the prefix (announced via
+/// `X-Forwarded-Prefix`); the UI is pointed at the spec through whichever
+/// prefix the client used.
+pub fn create_swagger_router(state: &AppState, base_path: &str) -> Router<AppState> {
+    let mut prefixes = vec![String::new()];
+    if !base_path.is_empty() {
+        prefixes.push(base_path.to_string());
+    }
+
+    prefixes.iter().fold(Router::new(), |router, prefix| {
+        router
+            .route(&format!("{}/api-docs/openapi.json", prefix), get(openapi_json))
+            .route(&format!("{}/docs", prefix), get(docs_redirect))
+            .route(&format!("{}/docs/", prefix), get(swagger_index))
+            .route(&format!("{}/docs/*rest", prefix), get(swagger_file))
+    })
+    .route_layer(axum::middleware::from_fn_with_state(state.clone(), docs_access))
+}
+
+/// Applies `docs.allowed_cidrs` and `docs.require_auth`, and audit-logs
+/// every request while either is set. A token that authenticates is passed
+/// on as [`Claims`] so the spec can be redacted for readers without it.
+pub async fn docs_access(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let docs = &config.docs;
+    if !docs.is_restricted() && !docs.redact_admin_paths {
+        return next.run(request).await;
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let claims = match request.extensions().get::<Claims>() {
+        Some(claims) => Some(claims.clone()),
+        None => authenticate(&state, &config.middleware.auth, request.headers(), request.extensions()).await,
+    };
+    let outcome = if !docs.allowed_cidrs.is_empty()
+        && !peer.is_some_and(|peer| crate::profiling::is_trusted(&docs.allowed_cidrs, peer))
+    {
+        Err(StatusCode::FORBIDDEN)
+    } else if !docs.require_auth {
+        Ok(())
+    } else {
+        match &claims {
+            Some(claims) if claims.has_scope(DOCS_SCOPE) => Ok(()),
+            Some(_) => Err(StatusCode::FORBIDDEN),
+            None => Err(StatusCode::UNAUTHORIZED),
+        }
+    };
+
+    if docs.is_restricted() {
+        let actor = claims
+            .as_ref()
+            .map(|claims| state.pseudonymizer.pseudonymize(&claims.sub))
+            .unwrap_or_else(|| "anonymous".to_string());
+        info!(
+            audit = true,
+            path = request.uri().path(),
+            peer = peer.map(|peer| peer.to_string()),
+            actor = actor,
+            status = outcome.err().map(|status| status.as_u16()),
+            "Docs accessed"
+        );
+    }
+    if let Err(status) = outcome {
+        return status.into_response();
+    }
+
+    if let Some(claims) = claims {
+        request.extensions_mut().insert(claims);
+    }
+    next.run(request).await
+}
+
+/// The OpenAPI spec with `servers` taken from config: `public_url` plus the
+/// base path when configured, otherwise just the base path.
+pub fn get_openapi_spec(server: &ServerConfig) -> utoipa::openapi::OpenApi {
+    spec_with_server(server, &PublicPrefix(server.base_path()))
+}
+
+fn spec_with_server(server: &ServerConfig, prefix: &PublicPrefix) -> utoipa::openapi::OpenApi {
+    let mut spec = ApiDoc::openapi();
+    let url = match &server.public_url {
+        Some(public_url) => format!("{}{}", public_url.trim_end_matches('/'), prefix.0),
+        None if prefix.0.is_empty() => "/".to_string(),
+        None => prefix.0.clone(),
+    };
+    spec.servers = Some(vec![ServerBuilder::new()
+        .url(url)
+        .description(Some("Project Gateway"))
+        .build()]);
+    spec
+}
+
+async fn openapi_json(
+    State(state): State<AppState>,
+    prefix: PublicPrefix,
+    claims: Option<Extension<Claims>>,
+) -> Json<utoipa::openapi::OpenApi> {
+    let config = state.config_watcher.get_config().await;
+    let mut spec = spec_with_server(&config.server, &prefix);
+    if config.docs.redact_admin_paths && !claims.is_some_and(|Extension(claims)| claims.has_scope(DOCS_SCOPE)) {
+        redact_admin_paths(&mut spec);
+    }
+    Json(spec)
+}
+
+/// Drops operations tagged `admin`, and paths left without any.
+fn redact_admin_paths(spec: &mut utoipa::openapi::OpenApi) {
+    spec.paths.paths.retain(|_, item| {
+        item.operations.retain(|_, operation| {
+            !operation.tags.as_ref().is_some_and(|tags| tags.iter().any(|tag| tag == ADMIN_TAG))
+        });
+        !item.operations.is_empty()
+    });
+    if let Some(tags) = &mut spec.tags {
+        tags.retain(|tag| tag.name != ADMIN_TAG);
+    }
 }
 
-pub fn get_openapi_spec() -> utoipa::openapi::OpenApi {
-    ApiDoc::openapi()
+async fn docs_redirect(prefix: PublicPrefix) -> Redirect {
+    Redirect::to(&prefix.link("/docs/"))
 }
 
+async fn swagger_index(prefix: PublicPrefix) -> Response {
+    serve_swagger_file("", &prefix)
+}
+
+async fn swagger_file(Path(rest): Path<String>, prefix: PublicPrefix) -> Response {
+    serve_swagger_file(&rest, &prefix)
+}
+
+fn serve_swagger_file(file: &str, prefix: &PublicPrefix) -> Response {
+    let config = Arc::new(Config::new([prefix.link("/api-docs/openapi.json")]));
+
+    match utoipa_swagger_ui::serve(file, config) {
+        Ok(Some(file)) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, file.content_type)],
+            file.bytes.into_owned(),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::config::AppConfig;
+
+/// Middleware features that can be switched at runtime without a config edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    RequestLogging,
+    BodyLogging,
+    Mirror,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::RequestLogging, Feature::BodyLogging, Feature::Mirror];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::RequestLogging => "request_logging",
+            Feature::BodyLogging => "body_logging",
+            Feature::Mirror => "mirror",
+        }
+    }
+
+    /// The value the config file gives this feature.
+    pub fn config_value(&self, config: &AppConfig) -> bool {
+        match self {
+            Feature::RequestLogging => config.middleware.logging.enabled,
+            Feature::BodyLogging => {
+                config.middleware.logging.include_request_body
+                    || config.middleware.logging.include_response_body
+            }
+            Feature::Mirror => config.mirror.enabled,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == name)
+            .ok_or_else(|| format!("unknown feature: {}", name))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Override {
+    enabled: bool,
+    set_at: DateTime<Utc>,
+    expires_at: Option<(Instant, DateTime<Utc>)>,
+}
+
+impl Override {
+    fn expired(&self) -> bool {
+        self.expires_at
+            .map(|(deadline, _)| Instant::now() >= deadline)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FeatureState {
+    pub name: Feature,
+    /// Value from the config file.
+    pub config_value: bool,
+    /// Runtime override, if one is active.
+    pub override_value: Option<bool>,
+    pub override_set_at: Option<String>,
+    pub override_expires_at: Option<String>,
+    /// What middleware actually uses right now.
+    pub effective: bool,
+}
+
+/// Runtime overrides for middleware features.
+///
+/// Middleware consults this alongside its config flag. Overrides survive
+/// config reloads unless the reloaded config sets `reset_overrides: true`,
+/// and may carry a TTL after which the config value applies again.
+#[derive(Default)]
+pub struct FeatureOverrides {
+    overrides: RwLock<HashMap<Feature, Override>>,
+}
+
+impl FeatureOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The override for `feature` if one is active, otherwise `config_value`.
+    pub fn resolve(&self, feature: Feature, config_value: bool) -> bool {
+        self.override_value(feature).unwrap_or(config_value)
+    }
+
+    /// The value `feature` is overridden to, if an override is active.
+    pub fn override_value(&self, feature: Feature) -> Option<bool> {
+        self.active(feature).map(|active| active.enabled)
+    }
+
+    pub fn is_enabled(&self, feature: Feature, config: &AppConfig) -> bool {
+        self.resolve(feature, feature.config_value(config))
+    }
+
+    /// Overrides `feature` until `ttl` elapses, or indefinitely without one.
+    pub fn set(&self, feature: Feature, enabled: bool, ttl: Option<Duration>, actor: &str) {
+        let now = Utc::now();
+        let expires_at = ttl.map(|ttl| {
+            (
+                Instant::now() + ttl,
+                now + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+            )
+        });
+
+        if let Ok(mut overrides) = self.overrides.write() {
+            overrides.insert(
+                feature,
+                Override {
+                    enabled,
+                    set_at: now,
+                    expires_at,
+                },
+            );
+        }
+        info!(
+            audit = true,
+            feature = %feature,
+            enabled = enabled,
+            ttl_seconds = ttl.map(|ttl| ttl.as_secs()),
+            actor = actor,
+            "Feature override set"
+        );
+    }
+
+    /// Drops the override for `feature` so the config value applies again.
+    pub fn clear(&self, feature: Feature, actor: &str) {
+        let removed = self
+            .overrides
+            .write()
+            .ok()
+            .and_then(|mut overrides| overrides.remove(&feature));
+        if removed.is_some() {
+            info!(audit = true, feature = %feature, actor = actor, "Feature override cleared");
+        }
+    }
+
+    /// Drops every override; used when a reload sets `reset_overrides`.
+    pub fn reset(&self) {
+        let cleared = self
+            .overrides
+            .write()
+            .map(|mut overrides| overrides.drain().count())
+            .unwrap_or(0);
+        if cleared > 0 {
+            warn!(audit = true, cleared = cleared, "Feature overrides reset by config reload");
+        }
+    }
+
+    pub fn states(&self, config: &AppConfig) -> Vec<FeatureState> {
+        Feature::ALL
+            .into_iter()
+            .map(|feature| {
+                let config_value = feature.config_value(config);
+                let active = self.active(feature);
+                FeatureState {
+                    name: feature,
+                    config_value,
+                    override_value: active.as_ref().map(|active| active.enabled),
+                    override_set_at: active.as_ref().map(|active| active.set_at.to_rfc3339()),
+                    override_expires_at: active
+                        .as_ref()
+                        .and_then(|active| active.expires_at)
+                        .map(|(_, at)| at.to_rfc3339()),
+                    effective: active.map(|active| active.enabled).unwrap_or(config_value),
+                }
+            })
+            .collect()
+    }
+
+    fn active(&self, feature: Feature) -> Option<Override> {
+        let current = self.overrides.read().ok()?.get(&feature).cloned()?;
+        if !current.expired() {
+            return Some(current);
+        }
+
+        if let Ok(mut overrides) = self.overrides.write() {
+            // Re-check under the write lock in case it was replaced meanwhile
+            if overrides.get(&feature).map(Override::expired).unwrap_or(false) {
+                overrides.remove(&feature);
+                info!(audit = true, feature = %feature, "Feature override expired");
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,474 @@
+//! The gatekeeper's rules as a pure function of a snapshot of the monitors
+//! and the thresholds, so a decision can be explained and tried out with
+//! other thresholds without acting on it.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::scoped::{choose_scope, RollbackAction, RollbackScope, SliceStats};
+use crate::config::AppConfig;
+
+/// What the gatekeeper judges, as read from the monitors.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct HealthSnapshot {
+    pub rollout_percentage: f64,
+    /// Share of traffic the Rust path actually serves; below
+    /// `rollout_percentage` while a slow-start ramp runs.
+    pub effective_percentage: f64,
+    /// Rust error rate, in percent.
+    pub error_rate: f64,
+    /// How far Rust p99 latency trails the legacy baseline, in percent.
+    pub latency_degradation_percent: f64,
+    pub slow_start_running: bool,
+    /// SLOs whose error budget is burning fast.
+    pub fast_burning_slos: Vec<String>,
+    /// Variants and routes whose latency distribution has drifted for
+    /// `latency_drift.sustained_intervals`, as `variant route`.
+    #[serde(default)]
+    pub drifting_latency: Vec<String>,
+    /// A rollback happened within the cooldown.
+    pub in_cooldown: bool,
+    /// The rollout is in manual mode, so nothing is rolled back.
+    pub manual_mode: bool,
+    /// Per-route Rust traffic since the last route check.
+    pub routes: Vec<RouteSnapshot>,
+}
+
+/// One route's Rust traffic and the share of it currently sent to Rust.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RouteSnapshot {
+    pub method: String,
+    pub route: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub percentage: f64,
+}
+
+impl RouteSnapshot {
+    fn stats(&self) -> SliceStats {
+        SliceStats {
+            method: self.method.clone(),
+            route: self.route.clone(),
+            requests: self.requests,
+            errors: self.errors,
+        }
+    }
+}
+
+/// The thresholds the rules judge against, from `canary_rollout` and
+/// `slo.rollback_on_fast_burn`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct HealthThresholds {
+    pub max_errors: f64,
+    pub max_latency_degradation: f64,
+    pub monitor_latency_p99: bool,
+    pub rollback_on_fast_burn: bool,
+    /// Whether sustained latency drift holds the rollout.
+    #[serde(default)]
+    pub hold_on_latency_drift: bool,
+    pub step: f64,
+    pub scoped_rollback: bool,
+    pub min_route_requests: u64,
+}
+
+impl HealthThresholds {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let canary = &config.canary_rollout;
+        Self {
+            max_errors: canary.max_errors,
+            max_latency_degradation: canary.max_latency_degradation,
+            monitor_latency_p99: canary.monitor_latency_p99,
+            rollback_on_fast_burn: config.slo.rollback_on_fast_burn,
+            hold_on_latency_drift: canary.latency_drift.enabled,
+            step: canary.step,
+            scoped_rollback: canary.scoped_rollback.enabled,
+            min_route_requests: canary.scoped_rollback.min_requests,
+        }
+    }
+}
+
+/// Thresholds to try instead of the configured ones; unset fields keep
+/// their configured values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThresholdOverrides {
+    pub max_errors: Option<f64>,
+    pub max_latency_degradation: Option<f64>,
+    pub monitor_latency_p99: Option<bool>,
+    pub rollback_on_fast_burn: Option<bool>,
+    pub hold_on_latency_drift: Option<bool>,
+    pub step: Option<f64>,
+    pub scoped_rollback: Option<bool>,
+    pub min_route_requests: Option<u64>,
+}
+
+impl ThresholdOverrides {
+    pub fn apply(&self, thresholds: HealthThresholds) -> HealthThresholds {
+        HealthThresholds {
+            max_errors: self.max_errors.unwrap_or(thresholds.max_errors),
+            max_latency_degradation: self
+                .max_latency_degradation
+                .unwrap_or(thresholds.max_latency_degradation),
+            monitor_latency_p99: self.monitor_latency_p99.unwrap_or(thresholds.monitor_latency_p99),
+            rollback_on_fast_burn: self.rollback_on_fast_burn.unwrap_or(thresholds.rollback_on_fast_burn),
+            hold_on_latency_drift: self.hold_on_latency_drift.unwrap_or(thresholds.hold_on_latency_drift),
+            step: self.step.unwrap_or(thresholds.step),
+            scoped_rollback: self.scoped_rollback.unwrap_or(thresholds.scoped_rollback),
+            min_route_requests: self.min_route_requests.unwrap_or(thresholds.min_route_requests),
+        }
+    }
+
+    /// Names of the fields that are set.
+    pub fn overridden(&self) -> Vec<&'static str> {
+        [
+            ("max_errors", self.max_errors.is_some()),
+            ("max_latency_degradation", self.max_latency_degradation.is_some()),
+            ("monitor_latency_p99", self.monitor_latency_p99.is_some()),
+            ("rollback_on_fast_burn", self.rollback_on_fast_burn.is_some()),
+            ("hold_on_latency_drift", self.hold_on_latency_drift.is_some()),
+            ("step", self.step.is_some()),
+            ("scoped_rollback", self.scoped_rollback.is_some()),
+            ("min_route_requests", self.min_route_requests.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOutcome {
+    Pass,
+    Fail,
+    /// Not judged; `detail` says why.
+    Skipped,
+}
+
+/// One rule as evaluated: what it looked at, against what, and the result.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleEvaluation {
+    pub rule: String,
+    pub observed: f64,
+    pub threshold: Option<f64>,
+    pub outcome: RuleOutcome,
+    pub detail: String,
+}
+
+impl RuleEvaluation {
+    fn new(rule: &str, observed: f64, threshold: Option<f64>, outcome: RuleOutcome, detail: String) -> Self {
+        Self {
+            rule: rule.to_string(),
+            observed,
+            threshold,
+            outcome,
+            detail,
+        }
+    }
+}
+
+/// A dry run of the gatekeeper with some thresholds overridden.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GatekeeperEvaluation {
+    /// The thresholds judged against, overrides applied.
+    pub thresholds: HealthThresholds,
+    pub overridden: Vec<String>,
+    pub snapshot: HealthSnapshot,
+    pub decision: GatekeeperDecision,
+}
+
+/// What the gatekeeper makes of a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GatekeeperDecision {
+    /// Whether the global rules hold, after the cooldown.
+    pub healthy: bool,
+    pub rollback_reason: Option<String>,
+    /// Why the rollout shouldn't advance, short of rolling back.
+    #[serde(default)]
+    pub hold_reason: Option<String>,
+    /// Every rule, in the order they're applied.
+    pub rules: Vec<RuleEvaluation>,
+    /// The rollback that would follow, if any.
+    pub action: Option<RollbackAction>,
+}
+
+/// Applies the gatekeeper's rules to `snapshot`. The global rules come
+/// first; routes are only judged when those hold. The last failing global
+/// rule gives the reason. Latency drift only ever holds the rollout.
+pub fn decide(snapshot: &HealthSnapshot, thresholds: &HealthThresholds) -> GatekeeperDecision {
+    let mut rules = Vec::new();
+    let mut rollback_reason = None;
+
+    let error_rate = snapshot.error_rate;
+    if error_rate > thresholds.max_errors {
+        let reason = format!(
+            "Error rate {}% exceeds threshold {}%",
+            error_rate, thresholds.max_errors
+        );
+        rules.push(RuleEvaluation::new(
+            "error_rate",
+            error_rate,
+            Some(thresholds.max_errors),
+            RuleOutcome::Fail,
+            reason.clone(),
+        ));
+        rollback_reason = Some(reason);
+    } else {
+        let detail = format!("Error rate {}% is within {}%", error_rate, thresholds.max_errors);
+        rules.push(RuleEvaluation::new(
+            "error_rate",
+            error_rate,
+            Some(thresholds.max_errors),
+            RuleOutcome::Pass,
+            detail,
+        ));
+    }
+
+    let degradation = snapshot.latency_degradation_percent;
+    let threshold = Some(thresholds.max_latency_degradation);
+    let latency = if !thresholds.monitor_latency_p99 {
+        RuleEvaluation::new(
+            "latency_degradation",
+            degradation,
+            threshold,
+            RuleOutcome::Skipped,
+            "monitor_latency_p99 is off".to_string(),
+        )
+    } else if degradation > thresholds.max_latency_degradation && snapshot.slow_start_running {
+        // Cold caches and pools make latency noisy while a slow-start ramp
+        // runs, so only error rates are judged until it finishes
+        let detail = format!("Latency degraded by {}%, ignored during slow-start", degradation);
+        RuleEvaluation::new(
+            "latency_degradation",
+            degradation,
+            threshold,
+            RuleOutcome::Skipped,
+            detail,
+        )
+    } else if degradation > thresholds.max_latency_degradation {
+        let reason = format!(
+            "Latency degraded by {}% (threshold: {}%)",
+            degradation, thresholds.max_latency_degradation
+        );
+        rollback_reason = Some(reason.clone());
+        RuleEvaluation::new("latency_degradation", degradation, threshold, RuleOutcome::Fail, reason)
+    } else {
+        let detail = format!(
+            "Latency degraded by {}%, within {}%",
+            degradation, thresholds.max_latency_degradation
+        );
+        RuleEvaluation::new("latency_degradation", degradation, threshold, RuleOutcome::Pass, detail)
+    };
+    rules.push(latency);
+
+    let burning = snapshot.fast_burning_slos.len() as f64;
+    let slo = if !thresholds.rollback_on_fast_burn {
+        RuleEvaluation::new(
+            "slo_fast_burn",
+            burning,
+            None,
+            RuleOutcome::Skipped,
+            "rollback_on_fast_burn is off".to_string(),
+        )
+    } else if snapshot.rollout_percentage <= 0.0 {
+        RuleEvaluation::new(
+            "slo_fast_burn",
+            burning,
+            None,
+            RuleOutcome::Skipped,
+            "No rollout traffic".to_string(),
+        )
+    } else if snapshot.fast_burning_slos.is_empty() {
+        RuleEvaluation::new(
+            "slo_fast_burn",
+            burning,
+            None,
+            RuleOutcome::Pass,
+            "No SLO is burning fast".to_string(),
+        )
+    } else {
+        let reason = format!(
+            "SLO error budget burning fast: {}",
+            snapshot.fast_burning_slos.join(", ")
+        );
+        rollback_reason = Some(reason.clone());
+        RuleEvaluation::new("slo_fast_burn", burning, None, RuleOutcome::Fail, reason)
+    };
+    rules.push(slo);
+
+    let drifting = snapshot.drifting_latency.len() as f64;
+    let mut hold_reason = None;
+    let drift = if !thresholds.hold_on_latency_drift {
+        RuleEvaluation::new(
+            "latency_drift",
+            drifting,
+            None,
+            RuleOutcome::Skipped,
+            "latency_drift is off".to_string(),
+        )
+    } else if snapshot.drifting_latency.is_empty() {
+        RuleEvaluation::new(
+            "latency_drift",
+            drifting,
+            None,
+            RuleOutcome::Pass,
+            "No latency distribution is drifting".to_string(),
+        )
+    } else {
+        let reason = format!(
+            "Latency distribution drifting: {}",
+            snapshot.drifting_latency.join(", ")
+        );
+        hold_reason = Some(reason.clone());
+        let detail = format!("{reason}; the rollout is held, not rolled back");
+        RuleEvaluation::new("latency_drift", drifting, None, RuleOutcome::Fail, detail)
+    };
+    rules.push(drift);
+
+    let mut action = rollback_reason.as_ref().map(|reason| RollbackAction {
+        scope: RollbackScope::Global,
+        from: snapshot.effective_percentage,
+        // Global rollbacks stop at 1%
+        to: (snapshot.effective_percentage - thresholds.step).max(1.0),
+        reason: reason.clone(),
+    });
+
+    if action.is_some() {
+        let detail = "A global rule failed, so routes aren't judged".to_string();
+        rules.push(RuleEvaluation::new(
+            "route_error_rate",
+            0.0,
+            Some(thresholds.max_errors),
+            RuleOutcome::Skipped,
+            detail,
+        ));
+    } else {
+        let (rule, route_action) = judge_routes(snapshot, thresholds);
+        rules.push(rule);
+        action = route_action;
+    }
+
+    if snapshot.in_cooldown {
+        let detail = "A rollback happened within the cooldown; failures are ignored".to_string();
+        rules.push(RuleEvaluation::new(
+            "rollback_cooldown",
+            1.0,
+            None,
+            RuleOutcome::Fail,
+            detail,
+        ));
+        rollback_reason = None;
+        action = None;
+    } else {
+        rules.push(RuleEvaluation::new(
+            "rollback_cooldown",
+            0.0,
+            None,
+            RuleOutcome::Pass,
+            "Not in cooldown".to_string(),
+        ));
+    }
+
+    let manual = if snapshot.manual_mode { 1.0 } else { 0.0 };
+    if snapshot.manual_mode && action.is_some() {
+        let detail = "The rollout is in manual mode; nothing is rolled back".to_string();
+        rules.push(RuleEvaluation::new(
+            "manual_mode",
+            manual,
+            None,
+            RuleOutcome::Fail,
+            detail,
+        ));
+        action = None;
+    } else {
+        let detail = if snapshot.manual_mode {
+            "The rollout is in manual mode"
+        } else {
+            "The rollout is automatic"
+        };
+        rules.push(RuleEvaluation::new(
+            "manual_mode",
+            manual,
+            None,
+            RuleOutcome::Pass,
+            detail.to_string(),
+        ));
+    }
+
+    GatekeeperDecision {
+        healthy: rollback_reason.is_none(),
+        rollback_reason,
+        hold_reason,
+        rules,
+        action,
+    }
+}
+
+/// Judges the routes' error rates and the narrowest rollback that explains
+/// a breach: the one route that regressed, or global when several did or
+/// the rest of the traffic is failing too.
+pub fn judge_routes(
+    snapshot: &HealthSnapshot,
+    thresholds: &HealthThresholds,
+) -> (RuleEvaluation, Option<RollbackAction>) {
+    let slices: Vec<SliceStats> = snapshot.routes.iter().map(RouteSnapshot::stats).collect();
+    let judged = |slice: &&SliceStats| slice.requests >= thresholds.min_route_requests;
+    let worst = slices
+        .iter()
+        .filter(judged)
+        .map(SliceStats::error_rate)
+        .fold(0.0, f64::max);
+    let Some(scope) = choose_scope(&slices, thresholds.max_errors, thresholds.min_route_requests) else {
+        let detail = format!(
+            "No route with at least {} requests is above {}%",
+            thresholds.min_route_requests, thresholds.max_errors
+        );
+        return (
+            RuleEvaluation::new(
+                "route_error_rate",
+                worst,
+                Some(thresholds.max_errors),
+                RuleOutcome::Pass,
+                detail,
+            ),
+            None,
+        );
+    };
+
+    let breached: Vec<String> = slices
+        .iter()
+        .filter(judged)
+        .filter(|slice| slice.error_rate() > thresholds.max_errors)
+        .map(|slice| format!("{} {} at {:.1}%", slice.method, slice.route, slice.error_rate()))
+        .collect();
+    let reason = format!("Error rate above {}%: {}", thresholds.max_errors, breached.join(", "));
+    let rule = RuleEvaluation::new(
+        "route_error_rate",
+        worst,
+        Some(thresholds.max_errors),
+        RuleOutcome::Fail,
+        reason.clone(),
+    );
+    let action = match scope {
+        RollbackScope::Route { method, route } if thresholds.scoped_rollback => {
+            let from = snapshot
+                .routes
+                .iter()
+                .find(|snapshot| snapshot.method == method && snapshot.route == route)
+                .map_or(snapshot.effective_percentage, |snapshot| snapshot.percentage);
+            RollbackAction {
+                scope: RollbackScope::Route { method, route },
+                from,
+                to: (from - thresholds.step).max(0.0),
+                reason,
+            }
+        }
+        _ => RollbackAction {
+            scope: RollbackScope::Global,
+            from: snapshot.effective_percentage,
+            to: (snapshot.effective_percentage - thresholds.step).max(1.0),
+            reason,
+        },
+    };
+    (rule, Some(action))
+}
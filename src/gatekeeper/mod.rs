@@ -1,32 +1,151 @@
-use std::{
-    sync::{Arc, Mutex},
-    time::{Duration, Instant},
+mod decision;
+mod scoped;
+mod slow_start;
+mod smoke;
+
+pub use decision::{
+    decide, judge_routes, GatekeeperDecision, GatekeeperEvaluation, HealthSnapshot, HealthThresholds, RouteSnapshot,
+    RuleEvaluation, RuleOutcome, ThresholdOverrides,
 };
-use serde::{Deserialize, Serialize};
+pub use scoped::{choose_scope, RollbackAction, RollbackScope, ScopedReduction, ScopedRollbacks, SliceStats};
+pub use slow_start::{SlowStart, SlowStartStatus};
+pub use smoke::{SmokeGate, SmokeState, SmokeStatus};
+
+use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
 use tokio::time::interval;
 use tracing::{info, warn, error};
-use utoipa::ToSchema;
 
 use crate::{
-    config::CanaryRolloutConfig,
-    monitoring::{PerformanceMonitor, PerformanceValidation},
+    config::{AppConfig, RolloutReadinessConfig},
+    coordination::RolloutMode,
+    monitoring::{drift::LatencyDriftStatus, MirrorSummary},
+    notifications::{Notification, Severity},
     AppState,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct GatekeeperStatus {
-    pub is_healthy: bool,
-    pub current_rollout_percentage: f64,
-    pub error_rate: f64,
-    pub latency_degradation_percent: f64,
-    pub last_check: u64,
-    pub rollback_triggered: bool,
-    pub rollback_reason: Option<String>,
+pub use crate::models::gatekeeper::{GatekeeperStatus, RolloutReadiness};
+
+/// Pre-rollout mode applies while no live traffic reaches the Rust path but
+/// mirroring provides a signal.
+pub fn in_pre_rollout(config: &AppConfig) -> bool {
+    config.canary_rollout.rollout_percentage <= 0.0 && config.mirror.enabled
+}
+
+/// Readiness from the monitor's current mirror stats, or `None` outside
+/// pre-rollout mode.
+pub fn rollout_readiness(state: &AppState, config: &AppConfig) -> Option<RolloutReadiness> {
+    in_pre_rollout(config).then(|| {
+        evaluate_readiness(
+            state.performance_monitor.mirror_summary(),
+            &config.canary_rollout.readiness,
+        )
+    })
+}
+
+/// Latency drift that is holding the rollout.
+pub fn sustained_drift(state: &AppState, config: &AppConfig) -> Vec<LatencyDriftStatus> {
+    let drift = &config.canary_rollout.latency_drift;
+    if !drift.enabled {
+        return Vec::new();
+    }
+    state
+        .latency_drift
+        .status(drift)
+        .into_iter()
+        .filter(|status| status.sustained)
+        .collect()
+}
+
+/// Each route override's percentage in effect, by group.
+pub fn route_percentages(state: &AppState, config: &AppConfig) -> BTreeMap<String, f64> {
+    let canary = &config.canary_rollout;
+    canary
+        .route_overrides
+        .iter()
+        .map(|entry| (entry.group(), state.slow_start.override_percentage(canary, entry)))
+        .collect()
+}
+
+/// Judges mirror stats against the readiness thresholds.
+pub fn evaluate_readiness(mirror: Option<MirrorSummary>, thresholds: &RolloutReadinessConfig) -> RolloutReadiness {
+    let mut blocking_reasons = Vec::new();
+
+    match &mirror {
+        None => blocking_reasons.push("No mirror traffic observed yet".to_string()),
+        Some(summary) if summary.samples < thresholds.min_mirror_samples => {
+            blocking_reasons.push(format!(
+                "Only {} mirror samples, need {}",
+                summary.samples, thresholds.min_mirror_samples
+            ));
+        }
+        Some(summary) => {
+            if summary.success_rate < thresholds.min_mirror_success_rate {
+                blocking_reasons.push(format!(
+                    "Mirror success rate {:.2}% is below {}%",
+                    summary.success_rate, thresholds.min_mirror_success_rate
+                ));
+            }
+            if summary.mismatch_rate > thresholds.max_mismatch_rate {
+                blocking_reasons.push(format!(
+                    "Mirror mismatch rate {:.2}% exceeds {}%",
+                    summary.mismatch_rate, thresholds.max_mismatch_rate
+                ));
+            }
+            if summary.contract_violation_rate > thresholds.max_contract_violation_rate {
+                blocking_reasons.push(format!(
+                    "Mirror contract violation rate {:.2}% exceeds {}%",
+                    summary.contract_violation_rate, thresholds.max_contract_violation_rate
+                ));
+            }
+            if summary.latency_ratio() > thresholds.max_latency_ratio {
+                blocking_reasons.push(format!(
+                    "Mirror p99 latency is {:.2}x main, above {}x",
+                    summary.latency_ratio(),
+                    thresholds.max_latency_ratio
+                ));
+            }
+        }
+    }
+
+    RolloutReadiness {
+        ready: blocking_reasons.is_empty(),
+        blocking_reasons,
+        mirror,
+    }
+}
+
+/// When the gatekeeper last rolled back, kept in the app state so every
+/// `Gatekeeper` shares the cooldown and it can be handed to a new instance.
+#[derive(Debug, Default)]
+pub struct RollbackCooldown {
+    last_rollback: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl RollbackCooldown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, at: DateTime<Utc>) {
+        if let Ok(mut last_rollback) = self.last_rollback.lock() {
+            *last_rollback = Some(at);
+        }
+    }
+
+    pub fn last_rollback(&self) -> Option<DateTime<Utc>> {
+        self.last_rollback.lock().ok().and_then(|last_rollback| *last_rollback)
+    }
+
+    /// Whether a rollback happened within `cooldown` of now.
+    pub fn active(&self, cooldown: Duration) -> bool {
+        self.last_rollback()
+            .is_some_and(|last| (Utc::now() - last).to_std().unwrap_or_default() < cooldown)
+    }
 }
 
 pub struct Gatekeeper {
     state: AppState,
-    last_rollback: Arc<Mutex<Option<Instant>>>,
     rollback_cooldown: Duration,
 }
 
@@ -34,7 +153,6 @@ impl Gatekeeper {
     pub fn new(state: AppState) -> Self {
         Self {
             state,
-            last_rollback: Arc::new(Mutex::new(None)),
             rollback_cooldown: Duration::from_secs(300), // 5 minute cooldown
         }
     }
@@ -46,10 +164,25 @@ impl Gatekeeper {
         
         loop {
             interval.tick().await;
+
+            // With shared rollout state only the lease holder evaluates;
+            // the others follow whatever it decides
+            if !self.state.coordinator.is_leader().await {
+                continue;
+            }
             
             let status = self.check_health().await;
+            let manual = status
+                .coordination
+                .as_ref()
+                .is_some_and(|coordination| coordination.state.mode == RolloutMode::Manual);
             
-            if !status.is_healthy && !status.rollback_triggered {
+            if !status.is_healthy && manual {
+                warn!(
+                    reason = status.rollback_reason.as_deref().unwrap_or_default(),
+                    "Gatekeeper detected degradation but the rollout is in manual mode; not rolling back"
+                );
+            } else if !status.is_healthy && !status.rollback_triggered {
                 warn!(
                     error_rate = status.error_rate,
                     latency_degradation = status.latency_degradation_percent,
@@ -67,86 +200,152 @@ impl Gatekeeper {
                     "✅ Gatekeeper health check passed"
                 );
             }
+
+            self.check_routes().await;
+        }
+    }
+
+    /// The monitors' current readings, with `slices` as the route traffic.
+    async fn snapshot(&self, config: &AppConfig, slices: Vec<SliceStats>) -> HealthSnapshot {
+        let validation = self.state.performance_monitor.validate_performance();
+        let effective_percentage = self.state.slow_start.effective_percentage(&config.canary_rollout);
+        let routes = slices
+            .into_iter()
+            .map(|slice| RouteSnapshot {
+                percentage: self.state.scoped_rollbacks.percentage_for(
+                    &slice.method,
+                    &slice.route,
+                    self.state.slow_start.route_percentage(&config.canary_rollout, &slice.method, &slice.route),
+                ),
+                method: slice.method,
+                route: slice.route,
+                requests: slice.requests,
+                errors: slice.errors,
+            })
+            .collect();
+        HealthSnapshot {
+            rollout_percentage: config.canary_rollout.rollout_percentage,
+            effective_percentage,
+            error_rate: validation.error_rate_rust,
+            latency_degradation_percent: (-validation.latency_improvement_percent).max(0.0),
+            slow_start_running: self.state.slow_start.status(&config.canary_rollout).is_some(),
+            fast_burning_slos: self.state.slo_tracker.fast_burning(),
+            drifting_latency: self.state.latency_drift.sustained(&config.canary_rollout.latency_drift),
+            in_cooldown: self.in_cooldown(),
+            manual_mode: self.state.coordinator.state().await.mode == RolloutMode::Manual,
+            routes,
         }
     }
 
     async fn check_health(&self) -> GatekeeperStatus {
         let config = self.state.config_watcher.get_config().await;
-        let validation = self.state.performance_monitor.validate_performance();
-        
-        let current_rollout_percentage = config.canary_rollout.rollout_percentage;
-        let error_rate = validation.error_rate_rust;
-        
-        // Check if we're in rollback cooldown
-        let in_cooldown = {
-            if let Ok(last_rollback) = self.last_rollback.lock() {
-                if let Some(last) = *last_rollback {
-                    last.elapsed() < self.rollback_cooldown
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        };
+        let snapshot = self.snapshot(&config, Vec::new()).await;
+        let thresholds = HealthThresholds::from_config(&config);
+        let decision = decide(&snapshot, &thresholds);
+        if snapshot.slow_start_running && snapshot.latency_degradation_percent > thresholds.max_latency_degradation {
+            info!(
+                latency_degradation = snapshot.latency_degradation_percent,
+                "Ignoring latency degradation during slow-start"
+            );
+        }
 
-        let mut is_healthy = true;
-        let mut rollback_reason = None;
+        GatekeeperStatus {
+            is_healthy: decision.healthy,
+            current_rollout_percentage: snapshot.rollout_percentage,
+            effective_rollout_percentage: snapshot.effective_percentage,
+            slow_start: self.state.slow_start.status(&config.canary_rollout),
+            error_rate: snapshot.error_rate,
+            latency_degradation_percent: snapshot.latency_degradation_percent,
+            last_check: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            rollback_triggered: !decision.healthy && decision.rollback_reason.is_some(),
+            rollback_reason: decision.rollback_reason,
+            latency: self.state.performance_monitor.latency_decomposition(),
+            rollout_readiness: rollout_readiness(&self.state, &config),
+            coordination: Some(self.state.coordinator.status().await),
+            maintenance: self.state.maintenance.active(),
+            scoped_rollbacks: self.state.scoped_rollbacks.active(),
+            latency_drift: sustained_drift(&self.state, &config),
+            route_percentages: route_percentages(&self.state, &config),
+        }
+    }
 
-        // Check error rate threshold
-        if error_rate > config.canary_rollout.max_errors {
-            is_healthy = false;
-            rollback_reason = Some(format!(
-                "Error rate {}% exceeds threshold {}%",
-                error_rate, config.canary_rollout.max_errors
-            ));
+    /// What the gatekeeper would decide now with `overrides` applied to the
+    /// configured thresholds. Nothing is rolled back, notified, or reset.
+    pub async fn evaluate(&self, overrides: &ThresholdOverrides) -> GatekeeperEvaluation {
+        let config = self.state.config_watcher.get_config().await;
+        let snapshot = self.snapshot(&config, self.state.scoped_rollbacks.slices()).await;
+        let thresholds = overrides.apply(HealthThresholds::from_config(&config));
+        GatekeeperEvaluation {
+            decision: decide(&snapshot, &thresholds),
+            overridden: overrides.overridden().into_iter().map(str::to_string).collect(),
+            thresholds,
+            snapshot,
         }
+    }
 
-        // Check latency degradation (if we have baseline)
-        let latency_degradation_percent = if validation.latency_improvement_percent < 0.0 {
-            validation.latency_improvement_percent.abs()
-        } else {
-            0.0
-        };
+    /// Whether a rollback happened within the cooldown.
+    fn in_cooldown(&self) -> bool {
+        self.state.rollback_cooldown.active(self.rollback_cooldown)
+    }
 
-        if latency_degradation_percent > 10.0 {
-            is_healthy = false;
-            rollback_reason = Some(format!(
-                "Latency degraded by {}% (threshold: 10%)",
-                latency_degradation_percent
-            ));
+    /// Judges each route's Rust error rate since the last call and rolls
+    /// back the narrowest scope that explains a breach of `max_errors`: the
+    /// one route that regressed, or globally when several did or the rest of
+    /// the traffic is failing too.
+    pub async fn check_routes(&self) -> Option<RollbackAction> {
+        let config = self.state.config_watcher.get_config().await;
+        let canary = &config.canary_rollout;
+        let slices = self.state.scoped_rollbacks.take_slices();
+        self.state
+            .scoped_rollbacks
+            .release(|method, route| canary.percentage_for(method, route, canary.rollout_percentage));
+        if self.in_cooldown() {
+            return None;
         }
 
-        // Don't trigger rollback if we're in cooldown
-        if in_cooldown {
-            is_healthy = true;
-            rollback_reason = None;
+        let snapshot = self.snapshot(&config, slices).await;
+        let (_, action) = judge_routes(&snapshot, &HealthThresholds::from_config(&config));
+        let action = action?;
+        if snapshot.manual_mode {
+            warn!(scope = %action.scope, reason = %action.reason, "Route regression detected but the rollout is in manual mode; not rolling back");
+            return None;
         }
 
-        GatekeeperStatus {
-            is_healthy,
-            current_rollout_percentage,
-            error_rate,
-            latency_degradation_percent,
-            last_check: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            rollback_triggered: !is_healthy && rollback_reason.is_some(),
-            rollback_reason,
+        match &action.scope {
+            RollbackScope::Route { method, route } => {
+                self.state.rollback_cooldown.record(Utc::now());
+                self.state.scoped_rollbacks.reduce(method, route, action.to, &action.reason);
+                warn!(
+                    event = "scoped_rollback",
+                    scope = %action.scope,
+                    from = action.from,
+                    to = action.to,
+                    reason = %action.reason,
+                    "Rolled back one route; the rest of the rollout is unchanged"
+                );
+                self.send_rollback_alert(&action).await;
+                Some(action)
+            }
+            RollbackScope::Global => Some(self.trigger_rollback(&action.reason).await),
         }
     }
 
-    async fn trigger_rollback(&self, reason: &str) {
+    async fn trigger_rollback(&self, reason: &str) -> RollbackAction {
         error!("🚨 TRIGGERING AUTOMATIC ROLLBACK: {}", reason);
         
         // Update last rollback time
-        if let Ok(mut last_rollback) = self.last_rollback.lock() {
-            *last_rollback = Some(Instant::now());
-        }
+        self.state.rollback_cooldown.record(Utc::now());
 
         let current_config = self.state.config_watcher.get_config().await;
-        let current_percentage = current_config.canary_rollout.rollout_percentage;
+        // Roll back from what the Rust path is actually serving, not the
+        // target of an unfinished ramp
+        let current_percentage = self.state.slow_start.effective_percentage(&current_config.canary_rollout);
+        if self.state.slow_start.cancel() {
+            warn!("Rollback cancelled the in-progress slow-start ramp");
+        }
         
         // Calculate rollback percentage (reduce by step size, minimum 1%)
         let rollback_percentage = (current_percentage - current_config.canary_rollout.step).max(1.0);
@@ -156,58 +355,54 @@ impl Gatekeeper {
             current_percentage, rollback_percentage
         );
 
-        // Send webhook notification
-        self.send_rollback_alert(reason, current_percentage, rollback_percentage).await;
+        let action = RollbackAction {
+            scope: RollbackScope::Global,
+            from: current_percentage,
+            to: rollback_percentage,
+            reason: reason.to_string(),
+        };
+        self.send_rollback_alert(&action).await;
         
-        // Update configuration (in a real system, this would update the config file)
-        // For now, we'll log the action
+        // Applied in memory and shared with the other replicas, holding route
+        // overrides to it; without coordination the next reload of the
+        // config file replaces it
+        self.state.coordinator.roll_back(rollback_percentage, "gatekeeper").await;
         warn!(
             "ROLLBACK EXECUTED: {} -> {}% (reason: {})",
             current_percentage, rollback_percentage, reason
         );
+        action
     }
 
-    async fn send_rollback_alert(&self, reason: &str, from_percentage: f64, to_percentage: f64) {
+    async fn send_rollback_alert(&self, action: &RollbackAction) {
         let config = self.state.config_watcher.get_config().await;
-        
-        if config.canary_rollout.webhook_url.starts_with("http") {
-            let payload = serde_json::json!({
-                "text": format!(
-                    "🚨 AUTOMATIC ROLLBACK TRIGGERED\n\
-                     Reason: {}\n\
-                     Rollout: {}% → {}%\n\
-                     Time: {}\n\
-                     Service: project-gateway",
-                    reason,
-                    from_percentage,
-                    to_percentage,
-                    chrono::Utc::now().to_rfc3339()
-                ),
-                "username": "Gateway Gatekeeper",
-                "icon_emoji": ":warning:"
-            });
-
-            let client = reqwest::Client::new();
-            match client
-                .post(&config.canary_rollout.webhook_url)
-                .json(&payload)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        info!("Rollback alert sent successfully");
-                    } else {
-                        warn!("Failed to send rollback alert: {}", response.status());
-                    }
-                }
-                Err(e) => {
-                    error!("Error sending rollback alert: {}", e);
-                }
-            }
-        } else {
-            info!("Webhook URL not configured, skipping alert");
-        }
+        let payload = serde_json::json!({
+            "text": format!(
+                "🚨 AUTOMATIC ROLLBACK TRIGGERED\n\
+                 Reason: {}\n\
+                 Scope: {}\n\
+                 Rollout: {}% → {}%\n\
+                 Time: {}\n\
+                 Service: project-gateway",
+                action.reason,
+                action.scope,
+                action.from,
+                action.to,
+                chrono::Utc::now().to_rfc3339()
+            ),
+            "scope": action.scope,
+            "username": "Gateway Gatekeeper",
+            "icon_emoji": ":warning:"
+        });
+        let notification = Notification {
+            kind: "rollback",
+            subject: action.scope.to_string(),
+            // Taking the Rust path out of service altogether
+            severity: if action.to <= 0.0 { Severity::Critical } else { Severity::Warning },
+            state_change: true,
+            payload,
+        };
+        self.state.notifier.notify(&config, notification).await;
     }
 
     pub async fn get_status(&self) -> GatekeeperStatus {
@@ -219,10 +414,35 @@ impl Gatekeeper {
         self.trigger_rollback(reason).await;
     }
 
-    pub async fn advance_rollout(&self) {
+    /// Steps the rollout forward, unless latency drift is holding it. Leaving
+    /// 0% during the mirror-only phase is gated on rollout readiness. The new stage is reached gradually over
+    /// `slow_start`. Returns whether the rollout advanced.
+    pub async fn advance_rollout(&self) -> bool {
+        let rollout = self.state.coordinator.state().await;
+        if rollout.paused || rollout.mode == RolloutMode::Manual {
+            info!(paused = rollout.paused, mode = ?rollout.mode, "Not advancing rollout");
+            return false;
+        }
+
         let current_config = self.state.config_watcher.get_config().await;
         let current_percentage = current_config.canary_rollout.rollout_percentage;
         let step = current_config.canary_rollout.step;
+
+        let drifting = self.state.latency_drift.sustained(&current_config.canary_rollout.latency_drift);
+        if !drifting.is_empty() {
+            warn!(drifting = ?drifting, "Not advancing rollout: latency distribution drifting");
+            return false;
+        }
+
+        if let Some(readiness) = rollout_readiness(&self.state, &current_config) {
+            if !readiness.ready {
+                warn!(
+                    blocking_reasons = ?readiness.blocking_reasons,
+                    "Not advancing rollout from 0%: mirror readiness checks failing"
+                );
+                return false;
+            }
+        }
         
         let new_percentage = (current_percentage + step).min(100.0);
         
@@ -232,14 +452,21 @@ impl Gatekeeper {
                 current_percentage, new_percentage
             );
             
-            // In a real system, this would update the configuration
-            // For now, we'll log the advancement
+            // Ramp before applying so no request sees the full new share
+            let from = self.state.slow_start.effective_percentage(&current_config.canary_rollout);
+            self.state
+                .slow_start
+                .begin(from, new_percentage, current_config.canary_rollout.slow_start.get());
+
+            self.state.coordinator.set_percentage(new_percentage, "gatekeeper").await;
             info!(
                 "ROLLOUT ADVANCED: {} -> {}%",
                 current_percentage, new_percentage
             );
+            true
         } else {
             info!("Rollout already at 100%");
+            false
         }
     }
 }
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, RwLock},
+};
+use utoipa::ToSchema;
+
+pub use crate::models::gatekeeper::ScopedReduction;
+
+/// What a rollback applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RollbackScope {
+    /// The rollout percentage itself.
+    Global,
+    /// One route's share of rollout traffic.
+    Route { method: String, route: String },
+}
+
+impl std::fmt::Display for RollbackScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RollbackScope::Global => f.write_str("global"),
+            RollbackScope::Route { method, route } => write!(f, "route {} {}", method, route),
+        }
+    }
+}
+
+/// A rollback the gatekeeper decided on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RollbackAction {
+    pub scope: RollbackScope,
+    pub from: f64,
+    pub to: f64,
+    pub reason: String,
+}
+
+/// Rust-served requests to one route since the last check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SliceStats {
+    pub method: String,
+    pub route: String,
+    pub requests: u64,
+    pub errors: u64,
+}
+
+impl SliceStats {
+    /// Share of requests that failed, in percent.
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 * 100.0 / self.requests as f64
+        }
+    }
+}
+
+/// The narrowest scope that explains slices breaching `max_errors`, or
+/// `None` when none do. A route is only blamed when it is the one slice
+/// over the threshold and the rest of the traffic is within it; anything
+/// else rolls back globally.
+pub fn choose_scope(slices: &[SliceStats], max_errors: f64, min_requests: u64) -> Option<RollbackScope> {
+    let judged = |slice: &&SliceStats| slice.requests >= min_requests.max(1);
+    let mut breached = slices.iter().filter(judged).filter(|slice| slice.error_rate() > max_errors);
+    let culprit = breached.next()?;
+    if breached.next().is_some() {
+        return Some(RollbackScope::Global);
+    }
+
+    let rest = slices
+        .iter()
+        .filter(|slice| !std::ptr::eq(*slice, culprit))
+        .fold(SliceStats::default(), |total, slice| SliceStats {
+            requests: total.requests + slice.requests,
+            errors: total.errors + slice.errors,
+            ..total
+        });
+    if rest.error_rate() > max_errors {
+        return Some(RollbackScope::Global);
+    }
+    Some(RollbackScope::Route {
+        method: culprit.method.clone(),
+        route: culprit.route.clone(),
+    })
+}
+
+/// Per-route error counts for the gatekeeper and the route reductions it
+/// has applied. A reduction lasts until the route's percentage, global or
+/// from its override, drops to it.
+#[derive(Default)]
+pub struct ScopedRollbacks {
+    slices: Mutex<BTreeMap<(String, String), SliceStats>>,
+    reductions: RwLock<BTreeMap<(String, String), ScopedReduction>>,
+}
+
+impl ScopedRollbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request the Rust handler served.
+    pub fn record(&self, method: &str, route: &str, is_error: bool) {
+        let Ok(mut slices) = self.slices.lock() else {
+            return;
+        };
+        let slice = slices
+            .entry((method.to_uppercase(), route.to_string()))
+            .or_insert_with(|| SliceStats {
+                method: method.to_uppercase(),
+                route: route.to_string(),
+                ..SliceStats::default()
+            });
+        slice.requests += 1;
+        slice.errors += u64::from(is_error);
+    }
+
+    /// The counts so far in this interval, leaving them in place.
+    pub fn slices(&self) -> Vec<SliceStats> {
+        self.slices
+            .lock()
+            .map(|slices| slices.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The counts since the last call, starting a new interval.
+    pub fn take_slices(&self) -> Vec<SliceStats> {
+        self.slices
+            .lock()
+            .map(|mut slices| std::mem::take(&mut *slices).into_values().collect())
+            .unwrap_or_default()
+    }
+
+    /// Share of `method route` traffic that may go to Rust when the rollout
+    /// is at `global`.
+    pub fn percentage_for(&self, method: &str, route: &str, global: f64) -> f64 {
+        self.reductions
+            .read()
+            .ok()
+            .and_then(|reductions| {
+                reductions
+                    .get(&(method.to_uppercase(), route.to_string()))
+                    .map(|reduction| reduction.percentage.min(global))
+            })
+            .unwrap_or(global)
+    }
+
+    pub fn reduce(&self, method: &str, route: &str, percentage: f64, reason: &str) {
+        if let Ok(mut reductions) = self.reductions.write() {
+            reductions.insert(
+                (method.to_uppercase(), route.to_string()),
+                ScopedReduction {
+                    method: method.to_uppercase(),
+                    route: route.to_string(),
+                    percentage,
+                    reason: reason.to_string(),
+                    since: chrono::Utc::now().to_rfc3339(),
+                },
+            );
+        }
+    }
+
+    /// Drops reductions their route's percentage has fallen to, `base`
+    /// giving it for a method and route.
+    pub fn release(&self, base: impl Fn(&str, &str) -> f64) {
+        if let Ok(mut reductions) = self.reductions.write() {
+            reductions.retain(|_, reduction| reduction.percentage < base(&reduction.method, &reduction.route));
+        }
+    }
+
+    /// Puts reductions exported by another instance in force, replacing any
+    /// for the same routes.
+    pub fn restore(&self, restored: &[ScopedReduction]) {
+        if let Ok(mut reductions) = self.reductions.write() {
+            for reduction in restored {
+                let method = reduction.method.to_uppercase();
+                reductions.insert((method.clone(), reduction.route.clone()), ScopedReduction { method, ..reduction.clone() });
+            }
+        }
+    }
+
+    /// Reductions in force, ordered by route.
+    pub fn active(&self) -> Vec<ScopedReduction> {
+        self.reductions
+            .read()
+            .map(|reductions| reductions.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
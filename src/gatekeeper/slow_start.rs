@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+use tracing::info;
+
+use crate::config::{watcher::ConfigWatcher, AppConfig, CanaryRolloutConfig, RolloutOverride};
+
+pub use crate::models::gatekeeper::SlowStartStatus;
+
+/// An in-progress ramp of the effective rollout percentage.
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    from: f64,
+    to: f64,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Ramp {
+    fn elapsed(&self) -> Duration {
+        self.started.elapsed().min(self.duration)
+    }
+
+    fn percentage(&self) -> f64 {
+        let progress = self.elapsed().as_secs_f64() / self.duration.as_secs_f64();
+        self.from + (self.to - self.from) * progress
+    }
+
+    fn finished(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+}
+
+/// Ramps the effective rollout percentage linearly after an advancement so
+/// the Rust path warms up instead of taking the whole step at once.
+///
+/// The configured percentage stays the target; a ramp only applies while it
+/// is heading to that target, so a reload that changes the percentage
+/// supersedes it.
+///
+/// Route overrides are held to the effective percentage while a ramp runs
+/// and while a global rollback's `override_hold` is in force, so neither a
+/// ramp nor a rollback is bypassed on an overridden route.
+#[derive(Debug, Default)]
+pub struct SlowStart {
+    ramp: Mutex<Option<Ramp>>,
+    /// A percentage taken over from another instance; the ramp toward it
+    /// that the reload applying it would start is skipped.
+    restored: Mutex<Option<f64>>,
+}
+
+impl SlowStart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts ramping from `from` to `to` over `duration`. A ramp already
+    /// heading to `to` keeps its progress; a zero duration or a decrease
+    /// applies immediately.
+    pub fn begin(&self, from: f64, to: f64, duration: Duration) {
+        let restored = self.restored.lock().ok().and_then(|mut restored| restored.take());
+        let Ok(mut ramp) = self.ramp.lock() else {
+            return;
+        };
+        if restored == Some(to) {
+            return;
+        }
+        if duration.is_zero() || to <= from {
+            *ramp = None;
+            return;
+        }
+        if ramp.is_some_and(|current| current.to == to && !current.finished()) {
+            return;
+        }
+
+        info!(from, to, seconds = duration.as_secs_f64(), "Slow-start ramp started");
+        *ramp = Some(Ramp {
+            from,
+            to,
+            started: Instant::now(),
+            duration,
+        });
+    }
+
+    /// Takes over the ramp another instance was running toward `target`,
+    /// with its progress, or applies `target` at once when it wasn't
+    /// ramping. Call before `target` is applied, which must raise the
+    /// percentage when no ramp is given.
+    pub fn restore(&self, ramp: Option<&SlowStartStatus>, target: f64) {
+        let ramp = ramp.filter(|ramp| ramp.to_percentage == target).and_then(|ramp| {
+            let elapsed = Duration::from_secs_f64(ramp.elapsed_seconds);
+            Some(Ramp {
+                from: ramp.from_percentage,
+                to: ramp.to_percentage,
+                started: Instant::now().checked_sub(elapsed)?,
+                duration: Duration::from_secs_f64(ramp.duration_seconds),
+            })
+        });
+        if let Ok(mut restored) = self.restored.lock() {
+            *restored = ramp.is_none().then_some(target);
+        }
+        if let Ok(mut current) = self.ramp.lock() {
+            *current = ramp;
+        }
+    }
+
+    /// Drops any in-progress ramp. Returns whether one was running.
+    pub fn cancel(&self) -> bool {
+        self.ramp
+            .lock()
+            .ok()
+            .and_then(|mut ramp| ramp.take())
+            .is_some_and(|ramp| !ramp.finished())
+    }
+
+    fn active(&self, config: &CanaryRolloutConfig) -> Option<Ramp> {
+        let ramp = (*self.ramp.lock().ok()?)?;
+        (ramp.to == config.rollout_percentage && !ramp.finished()).then_some(ramp)
+    }
+
+    /// The percentage of traffic the Rust path should receive right now.
+    pub fn effective_percentage(&self, config: &CanaryRolloutConfig) -> f64 {
+        self.active(config)
+            .map(|ramp| ramp.percentage())
+            .unwrap_or(config.rollout_percentage)
+    }
+
+    /// The percentage of `entry`'s traffic the Rust path should receive
+    /// right now.
+    pub fn override_percentage(&self, config: &CanaryRolloutConfig, entry: &RolloutOverride) -> f64 {
+        if config.overrides_held() || self.active(config).is_some() {
+            entry.percentage.min(self.effective_percentage(config))
+        } else {
+            entry.percentage
+        }
+    }
+
+    /// The percentage of `method path` traffic the Rust path should receive
+    /// right now, from the route override covering it if there is one.
+    pub fn route_percentage(&self, config: &CanaryRolloutConfig, method: &str, path: &str) -> f64 {
+        match config.route_override(method, path) {
+            Some(entry) => self.override_percentage(config, entry),
+            None => self.effective_percentage(config),
+        }
+    }
+
+    /// The ramp heading to the configured percentage, if one is running.
+    pub fn status(&self, config: &CanaryRolloutConfig) -> Option<SlowStartStatus> {
+        self.active(config).map(|ramp| SlowStartStatus {
+            from_percentage: ramp.from,
+            to_percentage: ramp.to,
+            effective_percentage: ramp.percentage(),
+            elapsed_seconds: ramp.elapsed().as_secs_f64(),
+            duration_seconds: ramp.duration.as_secs_f64(),
+        })
+    }
+
+    /// Starts a ramp whenever a config reload raises the rollout percentage,
+    /// and cancels it when a reload lowers it.
+    pub fn follow_reloads(self: &Arc<Self>, config_watcher: &ConfigWatcher, initial: &AppConfig) {
+        let mut reloads = config_watcher.subscribe_to_reloads();
+        let mut previous = initial.canary_rollout.clone();
+        let slow_start = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match reloads.recv().await {
+                    Ok(config) => {
+                        let canary = config.canary_rollout;
+                        if canary.rollout_percentage > previous.rollout_percentage {
+                            let from = slow_start.effective_percentage(&previous);
+                            slow_start.begin(from, canary.rollout_percentage, canary.slow_start.get());
+                        } else if canary.rollout_percentage < previous.rollout_percentage {
+                            slow_start.cancel();
+                        }
+                        previous = canary;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,262 @@
+use axum::{
+    body::Body,
+    http::{Method, Request},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use tower::ServiceExt;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{AppConfig, RouteConfig, SmokeCheck},
+    notifications::{Notification, Notifier, Severity},
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SmokeState {
+    /// Not checked since the route last became eligible.
+    Pending,
+    /// Passed; the route takes its share of rollout traffic.
+    Live,
+    /// Failed; the route stays on legacy and is checked again later.
+    Failing,
+}
+
+/// Smoke check outcome for one route, as shown by `GET /admin/routes`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SmokeStatus {
+    pub state: SmokeState,
+    pub expected_status: u16,
+    /// Status the Rust handler last answered with.
+    pub last_status: Option<u16>,
+    /// Why the last check failed.
+    pub error: Option<String>,
+    pub attempts: u32,
+    pub last_checked_at: Option<String>,
+}
+
+impl SmokeStatus {
+    fn pending(check: &SmokeCheck) -> Self {
+        Self {
+            state: SmokeState::Pending,
+            expected_status: check.expected_status,
+            last_status: None,
+            error: None,
+            attempts: 0,
+            last_checked_at: None,
+        }
+    }
+}
+
+fn key(method: &str, path: &str) -> String {
+    format!("{} {}", method.to_uppercase(), path)
+}
+
+/// Holds routes with a `smoke` check on legacy until the check has passed
+/// against the in-process Rust handler.
+///
+/// A route is checked when it becomes eligible for Rust traffic, i.e. when
+/// the rollout percentage goes from zero to above zero, and again every
+/// `canary_rollout.smoke_retry_interval` while it fails. Dropping the
+/// percentage back to zero makes every route prove itself again.
+#[derive(Default)]
+pub struct SmokeGate {
+    routes: RwLock<HashMap<String, SmokeStatus>>,
+}
+
+impl SmokeGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether rollout sampling may send `method path` to the Rust handler.
+    /// Routes without a smoke check always may.
+    pub fn is_live(&self, config: &AppConfig, method: &str, path: &str) -> bool {
+        if config.route(method, path).and_then(|route| route.smoke.as_ref()).is_none() {
+            return true;
+        }
+        self.routes
+            .read()
+            .ok()
+            .and_then(|routes| routes.get(&key(method, path)).map(|status| status.state == SmokeState::Live))
+            .unwrap_or(false)
+    }
+
+    /// Smoke status of a configured route, or `None` if it has no check.
+    pub fn status(&self, route: &RouteConfig) -> Option<SmokeStatus> {
+        let check = route.smoke.as_ref()?;
+        let status = self
+            .routes
+            .read()
+            .ok()
+            .and_then(|routes| routes.get(&key(&route.method, &route.path)).cloned());
+        Some(status.unwrap_or_else(|| SmokeStatus::pending(check)))
+    }
+
+    /// Every route's latest outcome, keyed by `METHOD path`.
+    pub fn export(&self) -> BTreeMap<String, SmokeStatus> {
+        self.routes
+            .read()
+            .map(|routes| routes.iter().map(|(key, status)| (key.clone(), status.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Takes over outcomes exported by another instance, so routes live
+    /// there are live here without being checked again. Routes that have
+    /// lost their check are dropped on the next run.
+    pub fn restore(&self, restored: &BTreeMap<String, SmokeStatus>) {
+        if let Ok(mut routes) = self.routes.write() {
+            for (held, status) in restored {
+                let (method, path) = held.split_once(' ').unwrap_or(("", held));
+                routes.insert(key(method, path), status.clone());
+            }
+        }
+    }
+
+    fn eligible(config: &AppConfig) -> bool {
+        config.canary_rollout.enabled && config.canary_rollout.rollout_percentage > 0.0
+    }
+
+    /// Checks every route that isn't live yet, if rollout traffic is flowing
+    /// at all. Otherwise forgets earlier results.
+    pub async fn run_once(&self, notifier: &Notifier, router: &Router, config: &AppConfig) {
+        if !Self::eligible(config) {
+            if let Ok(mut routes) = self.routes.write() {
+                routes.clear();
+            }
+            return;
+        }
+        if let Ok(mut routes) = self.routes.write() {
+            routes.retain(|held, _| {
+                config
+                    .routes
+                    .iter()
+                    .any(|route| route.smoke.is_some() && key(&route.method, &route.path) == *held)
+            });
+        }
+
+        for route in &config.routes {
+            let Some(check) = &route.smoke else { continue };
+            let key = key(&route.method, &route.path);
+            let previous = self.routes.read().ok().and_then(|routes| routes.get(&key).cloned());
+            if previous.as_ref().is_some_and(|status| status.state == SmokeState::Live) {
+                continue;
+            }
+
+            let result = call_rust(router, route, check, &config.canary_rollout.trigger_header).await;
+            let mut status = previous.unwrap_or_else(|| SmokeStatus::pending(check));
+            status.attempts += 1;
+            status.expected_status = check.expected_status;
+            status.last_checked_at = Some(chrono::Utc::now().to_rfc3339());
+            status.last_status = result.as_ref().ok().copied();
+            status.error = match result {
+                Ok(actual) if actual == check.expected_status => None,
+                Ok(actual) => Some(format!("expected status {}, got {}", check.expected_status, actual)),
+                Err(e) => Some(format!("{:#}", e)),
+            };
+
+            let was_failing = status.state == SmokeState::Failing;
+            status.state = if status.error.is_none() { SmokeState::Live } else { SmokeState::Failing };
+            crate::metrics::record_smoke_check(&route.method, &route.path, status.state == SmokeState::Live);
+            match &status.error {
+                None => info!(
+                    method = %route.method,
+                    route = %route.path,
+                    attempts = status.attempts,
+                    "Smoke check passed; route is live for rollout traffic"
+                ),
+                Some(error) => warn!(
+                    event = "smoke_check_failed",
+                    method = %route.method,
+                    route = %route.path,
+                    attempts = status.attempts,
+                    error = %error,
+                    "Smoke check failed; route held at 0% rollout"
+                ),
+            }
+            if status.state == SmokeState::Failing && !was_failing {
+                notify_failure(notifier, config, route, &status);
+            }
+
+            if let Ok(mut routes) = self.routes.write() {
+                routes.insert(key, status);
+            }
+        }
+    }
+
+    /// Runs the checks on every config reload and every
+    /// `smoke_retry_interval`.
+    pub async fn start(self: Arc<Self>, state: AppState, router: Router) {
+        let mut reloads = state.config_watcher.subscribe_to_reloads();
+        loop {
+            let config = state.config_watcher.get_config().await;
+            self.run_once(&state.notifier, &router, &config).await;
+
+            tokio::select! {
+                reload = reloads.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(config.canary_rollout.smoke_retry_interval.get()) => {}
+            }
+        }
+    }
+}
+
+async fn call_rust(router: &Router, route: &RouteConfig, check: &SmokeCheck, trigger_header: &str) -> anyhow::Result<u16> {
+    let method = check.method.as_deref().unwrap_or(&route.method);
+    let path = check
+        .path(&route.path)
+        .map_err(|name| anyhow::anyhow!("no value for path parameter {:?}", name))?;
+    let mut builder = Request::builder()
+        .method(Method::from_str(&method.to_uppercase())?)
+        .uri(path)
+        // Always exercise the in-process handler, never the canary proxy
+        .header(trigger_header, "rust");
+    let body = match &check.body {
+        Some(json) => {
+            builder = builder.header("content-type", "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+
+    let response = router.clone().oneshot(builder.body(body)?).await?;
+    Ok(response.status().as_u16())
+}
+
+/// Posts the first failure of a route to the rollout webhook, without
+/// holding up the remaining checks. A route flapping between live and
+/// failing is held to `notifications.min_interval`.
+fn notify_failure(notifier: &Notifier, config: &AppConfig, route: &RouteConfig, status: &SmokeStatus) {
+    let payload = serde_json::json!({
+        "text": format!(
+            "Smoke check failed for {} {}\n\
+             Error: {}\n\
+             The route stays on legacy and is retried every {}.\n\
+             Service: project-gateway",
+            route.method.to_uppercase(),
+            route.path,
+            status.error.as_deref().unwrap_or_default(),
+            config.canary_rollout.smoke_retry_interval,
+        ),
+        "username": "Gateway Gatekeeper",
+    });
+    let notification = Notification {
+        kind: "smoke_check_failed",
+        subject: format!("{} {}", route.method.to_uppercase(), route.path),
+        severity: Severity::Warning,
+        state_change: false,
+        payload,
+    };
+    notifier.notify_in_background(config, notification);
+}
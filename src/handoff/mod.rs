@@ -0,0 +1,237 @@
+//! Runtime state handed from one instance to its replacement.
+//!
+//! At a blue/green swap the new instance would otherwise start with no
+//! rollout history, no gatekeeper cooldown or streaks, no baseline and no
+//! upstream probe results, and behave differently from the one it replaces
+//! for its first few minutes. The old instance exports a [`StateSnapshot`]
+//! and the new one imports it before it reports ready.
+//!
+//! Nothing identifying a client is carried: per-client rate and concurrency
+//! state, cached tokens, debug captures and auth failures stay behind. The
+//! gateway has no response cache, so there are no cache keys to carry.
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, time::Duration};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    config::HandoffConfig,
+    coordination::RolloutState,
+    gatekeeper::{ScopedReduction, SlowStartStatus, SmokeStatus},
+    monitoring::{drift::LatencyDriftStatus, PerformanceBaseline},
+    upstream::health::UpstreamProbe,
+    AppState,
+};
+
+/// Layout of the snapshots this build exports and imports.
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// What a replacement instance needs to pick up where this one leaves off.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StateSnapshot {
+    /// Layout of the snapshot; an instance imports only its own.
+    pub format: u32,
+    /// Version of the gateway that exported it.
+    pub gateway_version: String,
+    pub instance_id: String,
+    /// RFC 3339, by the exporting instance's clock.
+    pub exported_at: String,
+    /// Rollout percentage, pause flag, mode and generation history.
+    pub rollout: RolloutState,
+    /// The slow-start ramp that was running, if any.
+    pub slow_start: Option<SlowStartStatus>,
+    pub gatekeeper: GatekeeperState,
+    pub baseline: Option<PerformanceBaseline>,
+    /// Latest health probe of each upstream.
+    pub upstreams: Vec<UpstreamProbe>,
+}
+
+/// What the gatekeeper has learned beyond the rollout state itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct GatekeeperState {
+    /// RFC 3339; the rollback cooldown runs from it.
+    pub last_rollback_at: Option<String>,
+    pub scoped_rollbacks: Vec<ScopedReduction>,
+    /// Smoke check outcomes keyed by `METHOD path`.
+    pub smoke_checks: BTreeMap<String, SmokeStatus>,
+    /// Each series' last score and drifting streak.
+    pub latency_drift: Vec<LatencyDriftStatus>,
+}
+
+/// What an import took over.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StateImportReport {
+    /// The instance the snapshot came from.
+    pub instance_id: String,
+    pub exported_at: String,
+    /// How long before the import the snapshot was exported.
+    pub age_seconds: f64,
+    /// Sections taken over.
+    pub restored: Vec<String>,
+    /// Sections left as they were, with why.
+    pub skipped: BTreeMap<String, String>,
+}
+
+/// Why a snapshot was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportRefusal {
+    /// This instance already reports ready and may be taking traffic.
+    AlreadyReady,
+    UnsupportedFormat(u32),
+    UnreadableTimestamp(String),
+    Stale { age: Duration, max_age: Duration },
+    FromTheFuture { ahead: Duration, max_skew: Duration },
+}
+
+impl ImportRefusal {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ImportRefusal::AlreadyReady => StatusCode::CONFLICT,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl fmt::Display for ImportRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportRefusal::AlreadyReady => f.write_str("state can only be imported before the instance reports ready"),
+            ImportRefusal::UnsupportedFormat(format) => {
+                write!(f, "snapshot format {} is not supported, expected {}", format, SNAPSHOT_FORMAT)
+            }
+            ImportRefusal::UnreadableTimestamp(exported_at) => {
+                write!(f, "exported_at {:?} is not an RFC 3339 timestamp", exported_at)
+            }
+            ImportRefusal::Stale { age, max_age } => write!(
+                f,
+                "snapshot was exported {}s ago, more than handoff.max_snapshot_age ({}s)",
+                age.as_secs(),
+                max_age.as_secs()
+            ),
+            ImportRefusal::FromTheFuture { ahead, max_skew } => write!(
+                f,
+                "snapshot was exported {}s in this instance's future, more than handoff.max_clock_skew ({}s); check both clocks",
+                ahead.as_secs(),
+                max_skew.as_secs()
+            ),
+        }
+    }
+}
+
+/// The runtime state of this instance as of now.
+pub async fn export(state: &AppState) -> StateSnapshot {
+    let config = state.config_watcher.get_config().await;
+    StateSnapshot {
+        format: SNAPSHOT_FORMAT,
+        gateway_version: env!("CARGO_PKG_VERSION").to_string(),
+        instance_id: state.coordinator.instance_id().to_string(),
+        exported_at: state.clock.now().to_rfc3339(),
+        rollout: state.coordinator.state().await,
+        slow_start: state.slow_start.status(&config.canary_rollout),
+        gatekeeper: GatekeeperState {
+            last_rollback_at: state.rollback_cooldown.last_rollback().map(|at| at.to_rfc3339()),
+            scoped_rollbacks: state.scoped_rollbacks.active(),
+            smoke_checks: state.smoke_gate.export(),
+            latency_drift: state.latency_drift.status(&config.canary_rollout.latency_drift),
+        },
+        baseline: state.performance_monitor.get_baseline(),
+        upstreams: state.upstream_health.probes(),
+    }
+}
+
+/// How long before `now` the snapshot was exported, if it may be imported
+/// at all.
+pub fn check(snapshot: &StateSnapshot, config: &HandoffConfig, now: DateTime<Utc>) -> Result<Duration, ImportRefusal> {
+    if snapshot.format != SNAPSHOT_FORMAT {
+        return Err(ImportRefusal::UnsupportedFormat(snapshot.format));
+    }
+    let exported_at = DateTime::parse_from_rfc3339(&snapshot.exported_at)
+        .map_err(|_| ImportRefusal::UnreadableTimestamp(snapshot.exported_at.clone()))?
+        .with_timezone(&Utc);
+    let (max_age, max_skew) = (config.max_snapshot_age.get(), config.max_clock_skew.get());
+    match (now - exported_at).to_std() {
+        Ok(age) if age > max_age => Err(ImportRefusal::Stale { age, max_age }),
+        Ok(age) => Ok(age),
+        Err(_) => {
+            let ahead = (exported_at - now).to_std().unwrap_or_default();
+            if ahead > max_skew {
+                Err(ImportRefusal::FromTheFuture { ahead, max_skew })
+            } else {
+                Ok(Duration::ZERO)
+            }
+        }
+    }
+}
+
+/// Takes over `snapshot`, if this instance isn't ready yet and the snapshot
+/// passes [`check`]. A completed import is audit-logged here, attributed
+/// to `actor`.
+pub async fn import(state: &AppState, snapshot: StateSnapshot, actor: &str) -> Result<StateImportReport, ImportRefusal> {
+    if state.warmup.is_ready() {
+        return Err(ImportRefusal::AlreadyReady);
+    }
+    let config = state.config_watcher.get_config().await;
+    let age = check(&snapshot, &config.handoff, state.clock.now())?;
+
+    let mut restored = Vec::new();
+    let mut skipped = BTreeMap::new();
+
+    if state.coordinator.is_coordinated() {
+        skipped.insert("rollout".to_string(), "the coordination store's shared state applies".to_string());
+    } else {
+        let target = snapshot.rollout.rollout_percentage;
+        if snapshot.slow_start.is_some() || target > config.canary_rollout.rollout_percentage {
+            state.slow_start.restore(snapshot.slow_start.as_ref(), target);
+        }
+        state.coordinator.restore(snapshot.rollout).await;
+        restored.push("rollout".to_string());
+    }
+
+    let gatekeeper = snapshot.gatekeeper;
+    if let Some(at) = gatekeeper
+        .last_rollback_at
+        .as_deref()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+    {
+        state.rollback_cooldown.record(at.with_timezone(&Utc));
+    }
+    state.scoped_rollbacks.restore(&gatekeeper.scoped_rollbacks);
+    state.smoke_gate.restore(&gatekeeper.smoke_checks);
+    state.latency_drift.restore(&gatekeeper.latency_drift);
+    restored.push("gatekeeper".to_string());
+
+    match snapshot.baseline {
+        Some(baseline) => {
+            state.performance_monitor.restore_baseline(baseline);
+            restored.push("baseline".to_string());
+        }
+        None => {
+            skipped.insert("baseline".to_string(), "none was established".to_string());
+        }
+    }
+
+    state.upstream_health.restore(&snapshot.upstreams);
+    restored.push("upstreams".to_string());
+
+    warn!(
+        audit = true,
+        actor = actor,
+        from_instance = %snapshot.instance_id,
+        from_version = %snapshot.gateway_version,
+        exported_at = %snapshot.exported_at,
+        age_seconds = age.as_secs_f64(),
+        restored = ?restored,
+        skipped = ?skipped,
+        "Runtime state imported"
+    );
+    Ok(StateImportReport {
+        instance_id: snapshot.instance_id,
+        exported_at: snapshot.exported_at,
+        age_seconds: age.as_secs_f64(),
+        restored,
+        skipped,
+    })
+}
@@ -1,15 +1,229 @@
+#[cfg(feature = "server")]
 use std::sync::Arc;
 
+#[cfg(feature = "server")]
+pub mod app;
+#[cfg(feature = "server")]
+pub mod bootstrap;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "server")]
+pub mod clock;
+#[cfg(feature = "server")]
 pub mod config;
+#[cfg(feature = "server")]
+pub mod contract;
+#[cfg(feature = "server")]
+pub mod coordination;
+#[cfg(feature = "server")]
+pub mod ctl;
+#[cfg(feature = "dev-tools")]
+pub mod dev;
+#[cfg(feature = "server")]
 pub mod docs;
+#[cfg(feature = "server")]
+pub mod features;
+#[cfg(feature = "server")]
 pub mod gatekeeper;
+#[cfg(feature = "server")]
+pub mod handoff;
+#[cfg(feature = "server")]
+pub mod listener;
+#[cfg(feature = "server")]
+pub mod maintenance;
+#[cfg(feature = "server")]
+pub mod memory;
+#[cfg(feature = "server")]
 pub mod metrics;
+#[cfg(feature = "server")]
 pub mod middleware;
+#[cfg(feature = "server")]
+pub mod mirror;
+pub mod models;
+#[cfg(feature = "server")]
 pub mod monitoring;
+#[cfg(feature = "server")]
+pub mod notifications;
+#[cfg(feature = "server")]
+pub mod privacy;
+#[cfg(feature = "server")]
+pub mod profiling;
+#[cfg(feature = "server")]
+pub mod quota;
+#[cfg(feature = "server")]
 pub mod routes;
+#[cfg(feature = "dev-tools")]
+pub mod test_util;
+#[cfg(feature = "server")]
+pub mod tls;
+#[cfg(feature = "server")]
+pub mod upstream;
+#[cfg(feature = "server")]
+pub mod util;
+#[cfg(feature = "server")]
+pub mod warmup;
 
+#[cfg(feature = "server")]
 #[derive(Clone)]
 pub struct AppState {
     pub config_watcher: Arc<config::watcher::ConfigWatcher>,
+    pub clock: Arc<clock::Clock>,
     pub performance_monitor: Arc<monitoring::PerformanceMonitor>,
+    pub auth_cache: Arc<middleware::auth::AuthCache>,
+    pub auth_failures: Arc<middleware::auth_audit::AuthFailureLog>,
+    pub jwks: Arc<middleware::jwks::JwksCache>,
+    pub contract_checker: Arc<contract::ContractChecker>,
+    pub upstreams: Arc<upstream::UpstreamPool>,
+    pub upstream_health: Arc<upstream::health::UpstreamHealth>,
+    pub notifier: Arc<notifications::Notifier>,
+    pub feature_overrides: Arc<features::FeatureOverrides>,
+    pub concurrency_limiter: Arc<middleware::rate_limit::ConcurrencyLimiter>,
+    pub request_rate_limiter: Arc<middleware::rate_limit::RequestRateLimiter>,
+    pub quotas: Arc<quota::QuotaTracker>,
+    pub load_shedder: Arc<middleware::load_shedding::LoadShedder>,
+    pub ip_filter: Arc<middleware::ip_filter::IpFilter>,
+    pub debug_capture: Arc<middleware::capture::DebugCapture>,
+    pub slow_start: Arc<gatekeeper::SlowStart>,
+    pub smoke_gate: Arc<gatekeeper::SmokeGate>,
+    pub scoped_rollbacks: Arc<gatekeeper::ScopedRollbacks>,
+    pub rollback_cooldown: Arc<gatekeeper::RollbackCooldown>,
+    pub slo_tracker: Arc<monitoring::slo::SloTracker>,
+    pub latency_drift: Arc<monitoring::drift::LatencyDrift>,
+    pub latency_heatmap: Arc<monitoring::heatmap::LatencyHeatmap>,
+    pub trace_sampler: Arc<monitoring::trace_sampling::TraceSampler>,
+    pub maintenance: Arc<maintenance::Maintenance>,
+    pub pseudonymizer: Arc<privacy::Pseudonymizer>,
+    pub profiler: Arc<profiling::Profiler>,
+    pub coordinator: Arc<coordination::RolloutCoordinator>,
+    pub memory_budget: Arc<memory::MemoryBudget>,
+    pub mirror_queue: Arc<mirror::MirrorQueue>,
+    pub mirror_parity: Arc<mirror::parity::ParityStore>,
+    pub warmup: Arc<warmup::Warmup>,
+    pub listener: Arc<listener::ListenerState>,
+    /// Set when the gateway terminates TLS itself.
+    pub tls: Option<Arc<tls::TlsManager>>,
+}
+
+#[cfg(feature = "server")]
+impl AppState {
+    /// TLS of the listener accepting connections: the listener
+    /// supervisor's, or the startup manager when there is no supervisor.
+    pub fn tls_manager(&self) -> Option<Arc<tls::TlsManager>> {
+        if self.listener.is_bound() {
+            self.listener.tls()
+        } else {
+            self.tls.clone()
+        }
+    }
+
+    pub async fn new(
+        config_watcher: Arc<config::watcher::ConfigWatcher>,
+        performance_monitor: Arc<monitoring::PerformanceMonitor>,
+    ) -> Self {
+        let config = config_watcher.get_config().await;
+
+        let feature_overrides = Arc::new(features::FeatureOverrides::new());
+        let mut reloads = config_watcher.subscribe_to_reloads();
+        let overrides = feature_overrides.clone();
+        tokio::spawn(async move {
+            loop {
+                match reloads.recv().await {
+                    Ok(config) if config.reset_overrides => overrides.reset(),
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let slow_start = Arc::new(gatekeeper::SlowStart::new());
+        slow_start.follow_reloads(&config_watcher, &config);
+
+        let pseudonymizer = Arc::new(privacy::Pseudonymizer::new(&config.privacy).unwrap_or_else(|e| {
+            tracing::error!("Pseudonymizing with a per-process salt: {:#}", e);
+            privacy::Pseudonymizer::ephemeral()
+        }));
+        pseudonymizer.follow_reloads(&config_watcher);
+        metrics::labels::LABEL_GUARD.configure(&config);
+        metrics::labels::LABEL_GUARD.follow_reloads(&config_watcher);
+
+        let coordinator = Arc::new(coordination::RolloutCoordinator::from_config(config_watcher.clone(), &config));
+
+        // Registered in eviction order; rate and concurrency state is never evicted
+        let auth_cache = Arc::new(middleware::auth::AuthCache::new());
+        let concurrency_limiter = Arc::new(middleware::rate_limit::ConcurrencyLimiter::new());
+        let request_rate_limiter = Arc::new(middleware::rate_limit::RequestRateLimiter::new());
+        let debug_capture = Arc::new(middleware::capture::DebugCapture::new());
+        let mirror_queue = Arc::new(mirror::MirrorQueue::open(&config.mirror.queue).unwrap_or_else(|e| {
+            tracing::error!("Mirroring through an in-memory queue: {:#}", e);
+            mirror::MirrorQueue::memory(config.mirror.queue.max_bytes.bytes())
+        }));
+        let mirror_parity = Arc::new(
+            mirror::parity::ParityStore::open(&config.mirror.parity, chrono::Utc::now()).unwrap_or_else(|e| {
+                tracing::error!("Keeping mirror parity records in memory: {:#}", e);
+                mirror::parity::ParityStore::memory()
+            }),
+        );
+        let memory_budget = Arc::new(memory::MemoryBudget::new(&config.memory));
+        memory_budget.register("debug_capture", Some(0), debug_capture.clone());
+        memory_budget.register("auth_cache", Some(1), auth_cache.clone());
+        memory_budget.register("mirror_outcomes", Some(2), performance_monitor.clone());
+        memory_budget.register("mirror_queue", Some(3), mirror_queue.clone());
+        memory_budget.register("concurrency_limiter", None, concurrency_limiter.clone());
+        memory_budget.register("request_rate_limiter", None, request_rate_limiter.clone());
+        memory_budget.start(&config_watcher);
+
+        let sweeper = Arc::new(memory::expiring::Sweeper::new());
+        sweeper.register(auth_cache.clone());
+        sweeper.register(concurrency_limiter.clone());
+        sweeper.register(request_rate_limiter.clone());
+        sweeper.start();
+
+        let upstreams = Arc::new(upstream::UpstreamPool::new(&config.http_client));
+        mirror::MirrorWorker::new(
+            mirror_queue.clone(),
+            config_watcher.clone(),
+            upstreams.clone(),
+            performance_monitor.clone(),
+            mirror_parity.clone(),
+        )
+        .spawn();
+
+        Self {
+            config_watcher,
+            clock: Arc::new(clock::Clock::system()),
+            performance_monitor,
+            auth_cache,
+            auth_failures: Arc::new(middleware::auth_audit::AuthFailureLog::new()),
+            jwks: Arc::new(middleware::jwks::JwksCache::new()),
+            contract_checker: Arc::new(contract::ContractChecker::new()),
+            notifier: Arc::new(notifications::Notifier::new(upstreams.clone())),
+            upstreams,
+            upstream_health: Arc::new(upstream::health::UpstreamHealth::new()),
+            feature_overrides,
+            concurrency_limiter,
+            request_rate_limiter,
+            quotas: Arc::new(quota::QuotaTracker::from_config(&config)),
+            load_shedder: Arc::new(middleware::load_shedding::LoadShedder::new()),
+            ip_filter: Arc::new(middleware::ip_filter::IpFilter::new()),
+            debug_capture,
+            slow_start,
+            smoke_gate: Arc::new(gatekeeper::SmokeGate::new()),
+            scoped_rollbacks: Arc::new(gatekeeper::ScopedRollbacks::new()),
+            rollback_cooldown: Arc::new(gatekeeper::RollbackCooldown::new()),
+            slo_tracker: Arc::new(monitoring::slo::SloTracker::new()),
+            latency_drift: Arc::new(monitoring::drift::LatencyDrift::new()),
+            latency_heatmap: Arc::new(monitoring::heatmap::LatencyHeatmap::new()),
+            trace_sampler: Arc::new(monitoring::trace_sampling::TraceSampler::new()),
+            maintenance: Arc::new(maintenance::Maintenance::new()),
+            pseudonymizer,
+            profiler: Arc::new(profiling::Profiler::new()),
+            coordinator,
+            memory_budget,
+            mirror_queue,
+            mirror_parity,
+            warmup: Arc::new(warmup::Warmup::new()),
+            listener: Arc::new(listener::ListenerState::new()),
+            tls: None,
+        }
+    }
 }
@@ -0,0 +1,392 @@
+//! The listening socket, and moving it when a reload changes where or how
+//! the gateway listens.
+//!
+//! A rebind sets up the new listener first: TLS, then the bind. If either
+//! fails the old listener keeps serving. Otherwise new connections go to
+//! the new listener at once, and the old one stops accepting and gets
+//! `server.drain_grace_period` to finish what it has in flight.
+
+use anyhow::{Context, Result};
+use axum::{extract::ConnectInfo, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{broadcast, oneshot},
+    task::{JoinHandle, JoinSet},
+};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{AppConfig, ServerConfig},
+    tls::{client_cert::ClientCertIdentity, TlsManager},
+    AppState,
+};
+
+pub use crate::models::health::{ListenerEvent, ListenerEventKind, ListenerReport};
+
+/// Events kept for the detailed health check.
+const MAX_EVENTS: usize = 20;
+
+/// The settings a listener is set up with; a reload that changes any of
+/// them rebinds. Certificate files are reloaded in place by the
+/// [`TlsManager`] and don't need a new listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    /// `(ca_path, required)` of the mTLS client verifier, which is built
+    /// with the acceptor.
+    pub client_auth: Option<(String, bool)>,
+}
+
+impl ListenerSettings {
+    pub fn of(server: &ServerConfig) -> Self {
+        let tls = server.tls.as_ref().filter(|tls| tls.enabled);
+        Self {
+            host: server.host.clone(),
+            port: server.port,
+            tls: tls.is_some(),
+            client_auth: tls
+                .and_then(|tls| tls.client_auth.as_ref())
+                .map(|client_auth| (client_auth.ca_path.clone(), client_auth.required)),
+        }
+    }
+
+    fn scheme(&self) -> &'static str {
+        if self.tls {
+            "https"
+        } else {
+            "http"
+        }
+    }
+}
+
+/// What the gateway is listening on, for the detailed health check.
+pub struct ListenerState {
+    current: RwLock<Option<(String, chrono::DateTime<chrono::Utc>)>>,
+    events: Mutex<VecDeque<ListenerEvent>>,
+    /// TLS of the listener accepting connections.
+    tls: RwLock<Option<Arc<TlsManager>>>,
+}
+
+impl Default for ListenerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListenerState {
+    pub fn new() -> Self {
+        Self {
+            current: RwLock::new(None),
+            events: Mutex::new(VecDeque::new()),
+            tls: RwLock::new(None),
+        }
+    }
+
+    /// `None` until a supervisor has bound a listener.
+    pub fn report(&self) -> Option<ListenerReport> {
+        let (address, since) = self.current.read().ok()?.clone()?;
+        let events = self
+            .events
+            .lock()
+            .map(|events| events.iter().cloned().collect())
+            .unwrap_or_default();
+        Some(ListenerReport {
+            address,
+            since: since.to_rfc3339(),
+            events,
+        })
+    }
+
+    /// Whether a supervisor has bound a listener.
+    pub fn is_bound(&self) -> bool {
+        self.current.read().is_ok_and(|current| current.is_some())
+    }
+
+    /// TLS of the listener accepting connections.
+    pub fn tls(&self) -> Option<Arc<TlsManager>> {
+        self.tls.read().ok()?.clone()
+    }
+
+    fn serving(&self, address: &str, tls: Option<Arc<TlsManager>>) {
+        if let Ok(mut current) = self.current.write() {
+            *current = Some((address.to_string(), chrono::Utc::now()));
+        }
+        if let Ok(mut current) = self.tls.write() {
+            *current = tls;
+        }
+    }
+
+    fn record(&self, kind: ListenerEventKind, address: &str, previous_address: Option<&str>, detail: Option<String>) {
+        crate::metrics::record_listener_event(kind);
+        if let Ok(mut events) = self.events.lock() {
+            events.push_back(ListenerEvent {
+                kind,
+                address: address.to_string(),
+                previous_address: previous_address.map(str::to_string),
+                detail,
+                at: chrono::Utc::now().to_rfc3339(),
+            });
+            while events.len() > MAX_EVENTS {
+                events.pop_front();
+            }
+        }
+    }
+}
+
+struct ActiveListener {
+    settings: ListenerSettings,
+    /// `scheme://address`
+    address: String,
+    local_addr: SocketAddr,
+    stop: oneshot::Sender<Duration>,
+    task: JoinHandle<usize>,
+}
+
+/// Owns the listener and replaces it when a reload changes its settings.
+pub struct ListenerSupervisor {
+    state: AppState,
+    app: Router,
+    tls: Option<Arc<TlsManager>>,
+    active: ActiveListener,
+    /// Subscribed before binding so no reload after it goes unseen.
+    reloads: broadcast::Receiver<AppConfig>,
+}
+
+impl ListenerSupervisor {
+    /// Binds the listener for the current config, failing if it can't be
+    /// set up. TLS uses the manager already loaded into `state`.
+    pub async fn bind(state: AppState, app: Router) -> Result<Self> {
+        let reloads = state.config_watcher.subscribe_to_reloads();
+        let config = state.config_watcher.get_config().await;
+        let settings = ListenerSettings::of(&config.server);
+        let tls = state.tls.clone().filter(|_| settings.tls);
+        let active = start(&settings, &app, tls.as_deref()).await?;
+        state.listener.serving(&active.address, tls.clone());
+        state.listener.record(ListenerEventKind::Bound, &active.address, None, None);
+        Ok(Self {
+            state,
+            app,
+            tls,
+            active,
+            reloads,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.active.local_addr
+    }
+
+    /// Follows config reloads until the config watcher goes away.
+    pub async fn run(mut self) {
+        loop {
+            match self.reloads.recv().await {
+                Ok(config) => {
+                    self.apply(&config).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let config = self.state.config_watcher.get_config().await;
+                    self.apply(&config).await;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Rebinds if `config` changes the listener settings. Returns whether
+    /// serving moved to a new listener.
+    pub async fn apply(&mut self, config: &AppConfig) -> bool {
+        let settings = ListenerSettings::of(&config.server);
+        if settings == self.active.settings {
+            return false;
+        }
+
+        let replacement = match self.set_up(&settings, config).await {
+            Ok(replacement) => replacement,
+            Err(e) => {
+                let detail = format!("{:#}", e);
+                error!(
+                    current = %self.active.address,
+                    host = %settings.host,
+                    port = settings.port,
+                    "Keeping the current listener; the reloaded one couldn't be set up: {}",
+                    detail
+                );
+                self.state
+                    .listener
+                    .record(ListenerEventKind::RebindFailed, &self.active.address, None, Some(detail));
+                return false;
+            }
+        };
+
+        let (active, tls) = replacement;
+        let previous = std::mem::replace(&mut self.active, active);
+        self.tls = tls;
+        let listener = &self.state.listener;
+        listener.serving(&self.active.address, self.tls.clone());
+        listener.record(
+            ListenerEventKind::Rebound,
+            &self.active.address,
+            Some(&previous.address),
+            None,
+        );
+        info!(from = %previous.address, to = %self.active.address, "🌐 Server listening on {}", self.active.address);
+
+        let grace = config.server.drain_grace_period.get();
+        let listener = self.state.listener.clone();
+        tokio::spawn(async move {
+            let _ = previous.stop.send(grace);
+            let cut_off = previous.task.await.unwrap_or_default();
+            let detail = (cut_off > 0).then(|| format!("{} connection(s) closed at the end of the grace period", cut_off));
+            info!(address = %previous.address, cut_off, "Drained the replaced listener");
+            listener.record(ListenerEventKind::Drained, &previous.address, None, detail);
+        });
+        true
+    }
+
+    /// TLS for `settings`, if any, then the bound listener.
+    async fn set_up(
+        &self,
+        settings: &ListenerSettings,
+        config: &AppConfig,
+    ) -> Result<(ActiveListener, Option<Arc<TlsManager>>)> {
+        let tls = match config.server.tls.as_ref().filter(|tls| tls.enabled) {
+            // Same verifier: the running manager picks up any new certificate paths
+            Some(tls) if self.active.settings.tls && self.active.settings.client_auth == settings.client_auth => {
+                let manager = self.tls.clone();
+                if let Some(manager) = &manager {
+                    manager.refresh(tls);
+                }
+                manager
+            }
+            Some(tls) => {
+                let manager = Arc::new(TlsManager::new(tls)?);
+                tokio::spawn(manager.clone().start(self.state.config_watcher.clone()));
+                Some(manager)
+            }
+            None => None,
+        };
+        let active = start(settings, &self.app, tls.as_deref()).await?;
+        Ok((active, tls))
+    }
+}
+
+/// Binds `settings` and starts serving on it.
+async fn start(settings: &ListenerSettings, app: &Router, tls: Option<&TlsManager>) -> Result<ActiveListener> {
+    let acceptor = tls.map(TlsManager::acceptor).transpose()?;
+    let listener = TcpListener::bind((settings.host.as_str(), settings.port))
+        .await
+        .with_context(|| format!("binding {}:{}", settings.host, settings.port))?;
+    let local_addr = listener.local_addr()?;
+    let (stop, stopped) = oneshot::channel();
+    let task = tokio::spawn(serve(listener, app.clone(), acceptor, stopped));
+    Ok(ActiveListener {
+        settings: settings.clone(),
+        address: format!("{}://{}", settings.scheme(), local_addr),
+        local_addr,
+        stop,
+        task,
+    })
+}
+
+/// Serves `app` on `listener` until `stop` yields a grace period, then
+/// stops accepting and lets open connections finish their requests for
+/// that long. Returns how many connections were still open when it ran
+/// out and were closed.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    acceptor: Option<TlsAcceptor>,
+    stop: impl Future<Output = Result<Duration, oneshot::error::RecvError>>,
+) -> usize {
+    let graceful = GracefulShutdown::new();
+    let mut connections = JoinSet::new();
+    tokio::pin!(stop);
+
+    let grace = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, remote_addr) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                let app = app.clone();
+                let acceptor = acceptor.clone();
+                let watcher = graceful.watcher();
+                connections.spawn(async move {
+                    match acceptor {
+                        Some(acceptor) => {
+                            let stream = match acceptor.accept(stream).await {
+                                Ok(stream) => stream,
+                                Err(e) => {
+                                    debug!(remote_addr = %remote_addr, "TLS handshake failed: {}", e);
+                                    return;
+                                }
+                            };
+                            let identity = crate::tls::peer_identity(&stream, remote_addr);
+                            serve_connection(stream, app, remote_addr, identity, watcher).await
+                        }
+                        None => serve_connection(stream, app, remote_addr, None, watcher).await,
+                    }
+                });
+            }
+            // Finished connections are reaped so the set doesn't grow
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            // A dropped sender stops the listener without a grace period
+            grace = &mut stop => break grace.unwrap_or_default(),
+        }
+    };
+    drop(listener);
+
+    if tokio::time::timeout(grace, graceful.shutdown()).await.is_ok() {
+        // Connections still finishing their handshake were never watched
+        connections.abort_all();
+        return 0;
+    }
+    while connections.try_join_next().is_some() {}
+    let cut_off = connections.len();
+    connections.abort_all();
+    cut_off
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    app: Router,
+    remote_addr: SocketAddr,
+    identity: Option<Arc<ClientCertIdentity>>,
+    watcher: hyper_util::server::graceful::Watcher,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = app.map_request(move |mut request: axum::http::Request<hyper::body::Incoming>| {
+        request.extensions_mut().insert(ConnectInfo(remote_addr));
+        if let Some(identity) = &identity {
+            request.extensions_mut().insert(identity.clone());
+        }
+        request
+    });
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(service));
+    if let Err(e) = watcher.watch(connection.into_owned()).await {
+        debug!(remote_addr = %remote_addr, "Connection closed with error: {}", e);
+    }
+}
@@ -1,190 +1,110 @@
 use anyhow::Result;
-use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
-    Router,
-};
-use serde_json::{json, Value};
-use std::{
-    net::SocketAddr,
-    sync::Arc,
-    time::Duration,
-};
-use tokio::net::TcpListener;
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::CorsLayer,
-    compression::CompressionLayer,
-    timeout::TimeoutLayer,
-    trace::TraceLayer,
-};
-use tracing::{info};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa::ToSchema;
-
-mod config;
-mod docs;
-mod gatekeeper;
-mod metrics;
-mod middleware;
-mod monitoring;
-mod routes;
-
-use config::{watcher::ConfigWatcher, AppConfig};
-
-#[derive(Clone)]
-pub struct AppState {
-    config_watcher: Arc<ConfigWatcher>,
-    performance_monitor: Arc<monitoring::PerformanceMonitor>,
-}
 
-#[derive(serde::Serialize, ToSchema)]
-pub struct MirrorTestResponse {
-    pub message: String,
-    pub timestamp: String,
-    pub note: String,
-}
+use project_gateway::{
+    app::create_app,
+    bootstrap::Bootstrap,
+    config::{overlay, watcher::ConfigWatcher, AppConfig, LogFormat},
+    clock, ctl, gatekeeper, listener::ListenerSupervisor,
+    middleware::{canary::simulate, logging},
+    monitoring, privacy, tls::TlsManager,
+    upstream, AppState,
+};
 
-/// Mirror test endpoint
-///
-/// Test endpoint for validating mirror functionality.
-#[utoipa::path(
-    get,
-    path = "/mirror/test",
-    tag = "testing",
-    responses(
-        (status = 200, description = "Mirror test response", body = MirrorTestResponse)
-    )
-)]
-async fn mirror_test_handler() -> Json<MirrorTestResponse> {
-    Json(MirrorTestResponse {
-        message: "Mirror test endpoint".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-        note: "This request should be mirrored if mirror mode is enabled".to_string(),
-    })
-}
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("pseudonymize") {
+        return pseudonymize(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("simulate-canary") {
+        return simulate_canary(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("ctl") {
+        if let Err(e) = ctl::run(&args[1..], &mut std::io::stdout()).await {
+            eprintln!("{}", e);
+            std::process::exit(e.exit_code());
+        }
+        return Ok(());
+    }
+    #[cfg(feature = "dev-tools")]
+    if args.first().map(String::as_str) == Some("dev") {
+        return dev(&args[1..]).await;
+    }
 
-/// Gatekeeper status endpoint
-///
-/// Returns the current status of the gatekeeper and rollout system.
-#[utoipa::path(
-    get,
-    path = "/gatekeeper/status",
-    tag = "monitoring",
-    responses(
-        (status = 200, description = "Gatekeeper status", body = gatekeeper::GatekeeperStatus)
-    )
-)]
-async fn gatekeeper_status_handler(State(state): State<AppState>) -> Json<gatekeeper::GatekeeperStatus> {
-    // Mock gatekeeper status for now
-    Json(gatekeeper::GatekeeperStatus {
-        is_healthy: true,
-        current_rollout_percentage: 100.0,
-        error_rate: 0.1,
-        latency_degradation_percent: 0.0,
-        last_check: chrono::Utc::now().timestamp() as u64,
-        rollback_triggered: false,
-        rollback_reason: None,
-    })
-}
+    // Load environment variables
+    dotenvy::dotenv().ok();
 
-async fn health_check() -> Json<Value> {
-    Json(json!({
-        "status": "healthy",
-        "service": "project-gateway",
-        "version": env!("CARGO_PKG_VERSION"),
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    }))
-}
+    let config_path = std::env::var("CONFIG_PATH")
+        .unwrap_or_else(|_| "config/default.yaml".to_string());
+    // Only the log format is read here; `serve` loads the config again,
+    // with its warnings and errors logged
+    let format = AppConfig::load_from(&config_path).map(|config| config.middleware.logging.format);
+    init_tracing(match format.unwrap_or_default() {
+        LogFormat::Json => LogStyle::Json,
+        LogFormat::Combined => LogStyle::Combined,
+    });
+    info!("🚀 Starting Project Gateway v{}", env!("CARGO_PKG_VERSION"));
 
-async fn create_app(state: AppState) -> Result<Router> {
-    let mut app = Router::new()
-        // Health endpoints
-        .route("/health", get(routes::health::health))
-        .route("/api/v1/health", get(routes::health::health_detailed))
-        
-        // User management endpoints
-        .route("/api/v1/users", get(routes::users::list_users))
-        .route("/api/v1/users", post(routes::users::create_user))
-        
-        // Monitoring endpoints
-        .route("/gatekeeper/status", get(gatekeeper_status_handler))
-        
-        // Testing endpoints
-        .route("/mirror/test", get(mirror_test_handler))
-        
-        // Swagger UI and OpenAPI documentation
-        .merge(docs::create_swagger_router())
-        .route("/api-docs/openapi.json", get(|| async {
-            Json(docs::get_openapi_spec())
-        }));
-
-    // Add middleware stack
-    app = app.layer(
-        ServiceBuilder::new()
-            .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive())
-            .layer(CompressionLayer::new())
-            .layer(TimeoutLayer::new(Duration::from_secs(30)))
-    );
+    serve(&config_path, |_, _| {}).await
+}
 
-    // Add canary routing middleware if enabled
-    let config = state.config_watcher.get_config().await;
-    if config.canary_rollout.enabled {
-        app = app.layer(axum::middleware::from_fn_with_state(
-            state.clone(),
-            middleware::canary::canary_routing_middleware,
-        ));
-    }
+/// How the gateway's own log lines are written.
+enum LogStyle {
+    Json,
+    /// Bare access lines in the combined format, and one line per event
+    /// for everything else.
+    Combined,
+    /// Human-readable, for local development.
+    #[cfg_attr(not(feature = "dev-tools"), allow(dead_code))]
+    Pretty,
+}
 
-    // Add mirror middleware if enabled
-    if config.mirror.enabled {
-        app = app.layer(axum::middleware::from_fn_with_state(
-            state.clone(),
-            middleware::mirror::mirror_middleware,
-        ));
+fn init_tracing(style: LogStyle) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "project_gateway=debug,tower_http=debug".into());
+    let registry = tracing_subscriber::registry().with(filter);
+    match style {
+        LogStyle::Json => registry.with(tracing_subscriber::fmt::layer().json()).init(),
+        LogStyle::Combined => registry.with(logging::combined_layers(std::io::stdout)).init(),
+        LogStyle::Pretty => registry.with(tracing_subscriber::fmt::layer().pretty()).init(),
     }
-
-    app = app.with_state(state);
-    
-    Ok(app)
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "project_gateway=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer().json())
-        .init();
-
-    info!("🚀 Starting Project Gateway v{}", env!("CARGO_PKG_VERSION"));
-
-    // Load environment variables
-    dotenvy::dotenv().ok();
-
+/// Runs the gateway on the config at `config_path`, calling `on_listening`
+/// once the listener is bound.
+async fn serve(config_path: &str, on_listening: impl FnOnce(SocketAddr, &AppConfig)) -> Result<()> {
     // Create configuration watcher
-    let config_watcher = Arc::new(ConfigWatcher::new("config/default.yaml").await?);
-    
+    let overrides_path = std::env::var("CONFIG_OVERRIDES_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| overlay::default_path(config_path));
+    let config_watcher = Arc::new(ConfigWatcher::with_overlay(config_path, &overrides_path)?);
+    info!("Startup configuration:\n{}", config_watcher.get_config().await.startup_report());
+
     // Create performance monitor
     let performance_monitor = Arc::new(monitoring::PerformanceMonitor::new());
 
     // Create application state
-    let state = AppState {
-        config_watcher: config_watcher.clone(),
-        performance_monitor: performance_monitor.clone(),
-    };
+    let mut state = AppState::new(config_watcher.clone(), performance_monitor.clone()).await;
 
-    // Start config reload monitoring
-    let config_watcher_clone = config_watcher.clone();
-    tokio::spawn(async move {
-        config_watcher_clone.start_watching().await;
-    });
+    // Load TLS certificates up front so a bad default certificate fails startup
+    let startup_config = config_watcher.get_config().await;
+    if let Some(tls_config) = startup_config.server.tls.as_ref().filter(|tls| tls.enabled) {
+        let manager = Arc::new(TlsManager::new(tls_config)?);
+        tokio::spawn(manager.clone().start(config_watcher.clone()));
+        state.tls = Some(manager);
+    }
+
+    // The state is complete; bind the listener now so anything connecting
+    // from here on gets 503 starting_up until the router takes over. Later
+    // reloads that move the listener rebind without a restart.
+    let bootstrap = Arc::new(Bootstrap::new());
+    let listener = ListenerSupervisor::bind(state.clone(), bootstrap.service()).await?;
+    let addr = listener.local_addr();
+    let scheme = if state.tls.is_some() { "https" } else { "http" };
+    info!("🌐 Server listening on {}://{}; answering starting_up until the router is built", scheme, addr);
 
     // Start performance monitoring task
     let performance_monitor_clone = performance_monitor.clone();
@@ -192,6 +112,9 @@ async fn main() -> Result<()> {
         performance_monitor_clone.start_monitoring(60).await; // 60 second intervals
     });
 
+    // Keep rollout state in step with the other replicas
+    tokio::spawn(state.coordinator.clone().run());
+
     // Start gatekeeper monitoring
     let gatekeeper = Arc::new(gatekeeper::Gatekeeper::new(state.clone()));
     let gatekeeper_clone = gatekeeper.clone();
@@ -199,22 +122,157 @@ async fn main() -> Result<()> {
         gatekeeper_clone.start_monitoring(30).await; // 30 second intervals
     });
 
-    // Create the application
-    let app = create_app(state).await?;
-
-    // Get server configuration
+    // Create the application and hand the listener over to it
+    let app = create_app(state.clone()).await?;
+    bootstrap.ready(app.clone());
     let config = config_watcher.get_config().await;
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
+    info!("📚 API Documentation available at {}://{}/docs", scheme, addr);
+    info!("📊 Metrics available at {}://{}{}", scheme, addr, config.metrics.path);
+    on_listening(addr, &config);
+
+    // Start OpenAPI contract checks against the in-process router
+    tokio::spawn(state.contract_checker.clone().start(state.clone(), app.clone()));
+
+    // Hold routes with smoke checks on legacy until they pass
+    tokio::spawn(state.smoke_gate.clone().start(state.clone(), app.clone()));
+
+    // Pay first-request costs before /readyz reports ready
+    let warmup_state = state.clone();
+    let warmup_app = app.clone();
+    tokio::spawn(async move {
+        warmup_state.warmup.run(&warmup_state, &warmup_app).await;
+    });
+
+    // Track SLO burn rates and alert on fast burns
+    tokio::spawn(state.slo_tracker.clone().start(state.clone()));
+
+    // Score each route's latency distribution against its recent history
+    tokio::spawn(state.latency_drift.clone().start(state.clone()));
+
+    // Keep connections open for a rollback to fall back on
+    tokio::spawn(upstream::prewarm::start(state.clone()));
+
+    // Probe legacy and the mirror target for the detailed health check
+    tokio::spawn(state.upstream_health.clone().start(state.clone()));
+
+    // Keep the JWKS for asymmetric tokens fresh
+    tokio::spawn(state.jwks.clone().start(state.clone()));
+
+    // Compare the system clock with a trusted time source
+    let clock_state = state.clone();
+    tokio::spawn(async move {
+        clock::check_skew(&clock_state).await;
+    });
+
+    // Open and close scheduled maintenance windows
+    tokio::spawn(state.maintenance.clone().start(state.clone()));
+
+    // Post digests of the notifications held back by rate limiting
+    tokio::spawn(state.notifier.clone().start(state.clone()));
+
+    listener.run().await;
+
+    Ok(())
+}
 
-    info!("🌐 Server listening on http://{}", addr);
-    info!("📚 API Documentation available at http://{}/docs", addr);
-    info!("📊 Metrics available at http://{}:{}/metrics", 
-          config.server.host, config.server.metrics_port);
 
-    // Start main server with graceful shutdown
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+#[cfg(feature = "dev-tools")]
+const DEV_USAGE: &str = "usage: project-gateway dev [--config <dev.yaml>]";
 
+/// `project-gateway dev`: runs the gateway against fake legacy and mirror
+/// targets described in `config/dev.yaml`, with readable logs.
+#[cfg(feature = "dev-tools")]
+async fn dev(args: &[String]) -> Result<()> {
+    let dev_config_path = match args {
+        [] => "config/dev.yaml",
+        [flag, path] if flag == "--config" => path.as_str(),
+        _ => anyhow::bail!(DEV_USAGE),
+    };
+    let dev_config = project_gateway::dev::DevConfig::load(dev_config_path)?;
+    init_tracing(LogStyle::Pretty);
+    let environment = project_gateway::dev::DevEnvironment::start(&dev_config).await?;
+    info!(
+        legacy = %environment.legacy.url(),
+        mirror = %environment.mirror.url(),
+        config = %environment.config_path.display(),
+        "Started fake upstreams"
+    );
+    serve(&environment.config_path.to_string_lossy(), |addr, config| {
+        println!("{}", environment.summary(addr, config));
+    })
+    .await
+}
+
+/// `project-gateway pseudonymize <id>...`: prints the pseudonym each ID is
+/// logged under with the current config's salt, for searching logs.
+fn pseudonymize(ids: &[String]) -> Result<()> {
+    if ids.is_empty() {
+        anyhow::bail!("usage: project-gateway pseudonymize <user-id>...");
+    }
+    dotenvy::dotenv().ok();
+    let config = AppConfig::load()?;
+    if !config.privacy.pseudonymize_user_ids {
+        eprintln!("pseudonymization is disabled in this config; IDs are logged as-is");
+    }
+    let pseudonymizer = privacy::Pseudonymizer::new(&config.privacy)?;
+    for id in ids {
+        println!("{}\t{}", id, pseudonymizer.pseudonymize(id));
+    }
     Ok(())
 }
 
+const SIMULATE_USAGE: &str = "usage: project-gateway simulate-canary --access-log <file.jsonl> \
+[--config <file.yaml>] (--percentage <p> | --sweep <p1,p2,...>) [--tolerance <points>] [--seed <n>]";
+
+/// `project-gateway simulate-canary`: replays an access log through the
+/// canary decision engine and reports the resulting split. Exits non-zero
+/// when any split deviates from its target by more than the tolerance.
+fn simulate_canary(args: &[String]) -> Result<()> {
+    let mut access_log = None;
+    let mut config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config/default.yaml".to_string());
+    let mut percentages = Vec::new();
+    let mut tolerance = 1.0;
+    let mut seed = 0;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{} needs a value\n{}", flag, SIMULATE_USAGE))?;
+        match flag.as_str() {
+            "--access-log" => access_log = Some(value.clone()),
+            "--config" => config_path = value.clone(),
+            "--percentage" => percentages = vec![value.parse()?],
+            "--sweep" => {
+                percentages = value
+                    .split(',')
+                    .map(|p| p.trim().parse())
+                    .collect::<Result<_, _>>()?
+            }
+            "--tolerance" => tolerance = value.parse()?,
+            "--seed" => seed = value.parse()?,
+            _ => anyhow::bail!("unknown flag {}\n{}", flag, SIMULATE_USAGE),
+        }
+    }
+    let Some(access_log) = access_log else {
+        anyhow::bail!(SIMULATE_USAGE);
+    };
+    if percentages.is_empty() || percentages.iter().any(|p: &f64| !(0.0..=100.0).contains(p)) {
+        anyhow::bail!("percentages must be between 0 and 100\n{}", SIMULATE_USAGE);
+    }
+
+    let config = AppConfig::load_from(&config_path)?;
+    let file = std::fs::File::open(&access_log)
+        .map_err(|e| anyhow::anyhow!("opening {}: {}", access_log, e))?;
+    let (records, skipped) = simulate::read_access_log(std::io::BufReader::new(file))?;
+    eprintln!("Replaying {} requests ({} other log lines skipped)", records.len(), skipped);
+
+    let runs = simulate::sweep(&records, &config.canary_rollout, &percentages, tolerance, seed);
+    print!("{}", simulate::render(&runs));
+
+    let deviations: usize = runs.iter().map(|run| run.deviations.len()).sum();
+    if deviations > 0 {
+        anyhow::bail!("{} split(s) deviate by more than {} points", deviations, tolerance);
+    }
+    Ok(())
+}
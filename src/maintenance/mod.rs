@@ -0,0 +1,128 @@
+//! Scheduled maintenance windows.
+//!
+//! Each `maintenance_windows` entry takes the routes its selector matches
+//! out of service for `duration` whenever its cron schedule fires. The
+//! scheduler re-evaluates the windows every second and logs when one starts
+//! or ends; the maintenance middleware answers requests to blocked routes
+//! with 503 until the window's end.
+
+use chrono::{DateTime, Utc};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::{AppConfig, MaintenanceWindow},
+    AppState,
+};
+
+pub use crate::models::gatekeeper::ActiveMaintenance;
+
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(1);
+
+impl ActiveMaintenance {
+    fn of(window: &MaintenanceWindow, now: DateTime<Utc>) -> Option<Self> {
+        let started_at = window.started_at(now)?;
+        Some(Self {
+            route_selector: window.route_selector.clone(),
+            message: window.message.clone(),
+            started_at,
+            ends_at: started_at + chrono::Duration::from_std(window.duration.get()).ok()?,
+        })
+    }
+
+    /// Whether requests with this method to this route are blocked.
+    pub fn selects(&self, method: &str, route: &str) -> bool {
+        crate::config::route_selector_matches(&self.route_selector, method, route)
+    }
+}
+
+/// Transitions found by one evaluation.
+#[derive(Debug, Default)]
+pub struct Transitions {
+    pub started: Vec<ActiveMaintenance>,
+    pub ended: Vec<ActiveMaintenance>,
+}
+
+/// The maintenance windows in force as of the last evaluation.
+#[derive(Default)]
+pub struct Maintenance {
+    active: RwLock<Vec<ActiveMaintenance>>,
+}
+
+impl Maintenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Works out which windows are in force at `now`, logging those that
+    /// started or ended since the last evaluation.
+    pub fn evaluate_at(&self, config: &AppConfig, now: DateTime<Utc>) -> Transitions {
+        let current: Vec<ActiveMaintenance> = config
+            .maintenance_windows
+            .iter()
+            .filter_map(|window| ActiveMaintenance::of(window, now))
+            .collect();
+        let Ok(mut active) = self.active.write() else {
+            return Transitions::default();
+        };
+
+        let mut transitions = Transitions::default();
+        for window in active.iter().filter(|window| !current.contains(window)) {
+            info!(
+                event = "maintenance_ended",
+                route_selector = %window.route_selector,
+                started_at = %window.started_at,
+                "Maintenance window ended"
+            );
+            transitions.ended.push(window.clone());
+        }
+        for window in current.iter().filter(|window| !active.contains(window)) {
+            warn!(
+                event = "maintenance_started",
+                route_selector = %window.route_selector,
+                ends_at = %window.ends_at,
+                "Maintenance window started; matching routes answer 503"
+            );
+            transitions.started.push(window.clone());
+        }
+        crate::metrics::record_maintenance_windows_active(current.len());
+        *active = current;
+        transitions
+    }
+
+    /// Windows in force, in config order.
+    pub fn active(&self) -> Vec<ActiveMaintenance> {
+        self.active.read().map(|active| active.clone()).unwrap_or_default()
+    }
+
+    /// The window blocking a request to `route`, ending last if several do.
+    pub fn blocking(&self, method: &str, route: &str) -> Option<ActiveMaintenance> {
+        let active = self.active.read().ok()?;
+        active
+            .iter()
+            .filter(|window| window.selects(method, route))
+            .max_by_key(|window| window.ends_at)
+            .cloned()
+    }
+
+    /// Re-evaluates every second, and at once after a config reload.
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        let mut reloads = state.config_watcher.subscribe_to_reloads();
+        loop {
+            let config = state.config_watcher.get_config().await;
+            self.evaluate_at(&config, state.clock.now());
+
+            tokio::select! {
+                reload = reloads.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(EVALUATION_INTERVAL) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,434 @@
+//! Per-client state that expires on its own.
+//!
+//! [`ExpiringMap`] gives every entry a TTL and the whole map a size cap.
+//! Expired entries are dropped by [`ExpiringMap::sweep`], which a background
+//! [`Sweeper`] calls every tick with a fixed budget: each shard keeps its
+//! entries in a due-time heap, so a tick only touches entries that are due
+//! and never holds a shard's lock for more than `budget` of them.
+
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::{BinaryHeap, HashMap},
+    hash::{BuildHasher, Hash, RandomState},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+const SHARDS: usize = 16;
+/// How often the background sweeper runs.
+pub const SWEEP_INTERVAL: Duration = Duration::from_millis(250);
+/// Most entries a store examines per sweep tick.
+pub const SWEEP_BUDGET: usize = 1024;
+/// Most due entries examined when making room for an insert into a full
+/// shard before giving up and going over the cap.
+const CAPACITY_PROBES: usize = 16;
+
+/// What one sweep tick did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SweepStats {
+    /// Heap entries looked at, including stale ones.
+    pub examined: usize,
+    pub expired: usize,
+    pub took: Duration,
+}
+
+/// A store with expiring entries the background sweeper should visit.
+pub trait Sweep: Send + Sync {
+    fn sweep(&self, budget: usize) -> SweepStats;
+}
+
+struct Entry<V> {
+    value: V,
+    ttl: Duration,
+    expires_at: Instant,
+    /// Matches the one live [`Due`] for this entry; older ones are stale.
+    generation: u64,
+}
+
+/// When an entry is next due to be checked; ordered soonest first.
+struct Due<K> {
+    at: Instant,
+    generation: u64,
+    key: K,
+}
+
+impl<K> PartialEq for Due<K> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.generation) == (other.at, other.generation)
+    }
+}
+
+impl<K> Eq for Due<K> {}
+
+impl<K> PartialOrd for Due<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for Due<K> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.at, other.generation).cmp(&(self.at, self.generation))
+    }
+}
+
+struct Shard<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    due: BinaryHeap<Due<K>>,
+    next_generation: u64,
+}
+
+/// Concurrent map whose entries expire `ttl` after they were last written.
+///
+/// Pinned entries (per the function given to [`ExpiringMap::with_pin`]) are
+/// never dropped, however long they go untouched; their TTL restarts each
+/// time a sweep finds them pinned.
+pub struct ExpiringMap<K, V> {
+    name: &'static str,
+    shards: Box<[Mutex<Shard<K, V>>]>,
+    hasher: RandomState,
+    shard_capacity: usize,
+    cursor: AtomicUsize,
+    len: AtomicUsize,
+    bytes: AtomicU64,
+    weigh: fn(&K, &V) -> u64,
+    pinned: fn(&V) -> bool,
+}
+
+fn default_weight<K, V>(_: &K, _: &V) -> u64 {
+    (std::mem::size_of::<K>() + std::mem::size_of::<Entry<V>>() + std::mem::size_of::<Due<K>>()) as u64
+}
+
+impl<K: Hash + Eq + Clone, V> ExpiringMap<K, V> {
+    /// An empty map holding at most about `capacity` entries, labelled
+    /// `name` in the `gateway_state_*` metrics.
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        let shards = (0..SHARDS)
+            .map(|_| {
+                Mutex::new(Shard {
+                    entries: HashMap::new(),
+                    due: BinaryHeap::new(),
+                    next_generation: 0,
+                })
+            })
+            .collect();
+        Self {
+            name,
+            shards,
+            hasher: RandomState::new(),
+            shard_capacity: capacity.div_ceil(SHARDS).max(1),
+            cursor: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            weigh: default_weight::<K, V>,
+            pinned: |_| false,
+        }
+    }
+
+    /// Sizes entries for [`ExpiringMap::memory_usage`].
+    pub fn with_weigher(mut self, weigh: fn(&K, &V) -> u64) -> Self {
+        self.weigh = weigh;
+        self
+    }
+
+    /// Keeps entries for which `pinned` holds from expiring or being evicted.
+    pub fn with_pin(mut self, pinned: fn(&V) -> bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, Shard<K, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate bytes held, per the weigher.
+    pub fn memory_usage(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    fn removed(&self, key: &K, entry: &Entry<V>) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        self.bytes.fetch_sub((self.weigh)(key, &entry.value), Ordering::Relaxed);
+    }
+
+    /// Adds an entry to `shard`, first making room if the shard is full.
+    fn add(&self, shard: &mut Shard<K, V>, key: K, value: V, ttl: Duration, now: Instant) {
+        if shard.entries.len() >= self.shard_capacity {
+            let evicted = self.evict_due(shard, 1, CAPACITY_PROBES);
+            crate::metrics::record_state_evictions(self.name, "capacity", evicted);
+        }
+        let generation = shard.next_generation;
+        shard.next_generation += 1;
+        let expires_at = now + ttl;
+        shard.due.push(Due {
+            at: expires_at,
+            generation,
+            key: key.clone(),
+        });
+        self.len.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add((self.weigh)(&key, &value), Ordering::Relaxed);
+        shard.entries.insert(
+            key,
+            Entry {
+                value,
+                ttl,
+                expires_at,
+                generation,
+            },
+        );
+    }
+
+    /// Drops up to `count` unpinned entries, soonest due first, looking at no
+    /// more than `probes` heap entries. Returns how many were dropped.
+    fn evict_due(&self, shard: &mut Shard<K, V>, count: usize, probes: usize) -> usize {
+        let mut evicted = 0;
+        let mut skipped = Vec::new();
+        for _ in 0..probes {
+            if evicted >= count {
+                break;
+            }
+            let Some(due) = shard.due.pop() else { break };
+            match shard.entries.get(&due.key) {
+                Some(entry) if entry.generation == due.generation => {
+                    if (self.pinned)(&entry.value) {
+                        skipped.push(due);
+                    } else if let Some(entry) = shard.entries.remove(&due.key) {
+                        self.removed(&due.key, &entry);
+                        evicted += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        shard.due.extend(skipped);
+        evicted
+    }
+
+    pub fn insert(&self, key: K, value: V, ttl: Duration) {
+        self.insert_at(key, value, ttl, Instant::now());
+    }
+
+    /// Inserts or replaces `key`, expiring `ttl` after `now`.
+    pub fn insert_at(&self, key: K, value: V, ttl: Duration, now: Instant) {
+        let mut shard = self.shard(&key);
+        if let Some(entry) = shard.entries.remove(&key) {
+            self.removed(&key, &entry);
+        }
+        self.add(&mut shard, key, value, ttl, now);
+    }
+
+    /// Applies `update` to the entry for `key`, creating it with `init` if
+    /// it is missing or expired, and restarts its TTL.
+    pub fn upsert_at<R>(
+        &self,
+        key: &K,
+        ttl: Duration,
+        now: Instant,
+        init: impl FnOnce() -> V,
+        update: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        let mut shard = self.shard(key);
+        let live = shard.entries.get(key).is_some_and(|entry| entry.expires_at > now);
+        if !live {
+            if let Some(entry) = shard.entries.remove(key) {
+                self.removed(key, &entry);
+            }
+            self.add(&mut shard, key.clone(), init(), ttl, now);
+        }
+        let Some(entry) = shard.entries.get_mut(key) else {
+            unreachable!("entry was just added");
+        };
+        entry.ttl = ttl;
+        entry.expires_at = now + ttl;
+        let before = (self.weigh)(key, &entry.value);
+        let result = update(&mut entry.value);
+        let after = (self.weigh)(key, &entry.value);
+        self.bytes.fetch_add(after, Ordering::Relaxed);
+        self.bytes.fetch_sub(before, Ordering::Relaxed);
+        result
+    }
+
+    pub fn upsert<R>(&self, key: &K, ttl: Duration, init: impl FnOnce() -> V, update: impl FnOnce(&mut V) -> R) -> R {
+        self.upsert_at(key, ttl, Instant::now(), init, update)
+    }
+
+    /// Reads the entry for `key` without restarting its TTL; expired entries
+    /// are dropped and read as missing.
+    pub fn read_at<R>(&self, key: &K, now: Instant, read: impl FnOnce(&V) -> R) -> Option<R> {
+        let mut shard = self.shard(key);
+        let entry = shard.entries.get(key)?;
+        if entry.expires_at > now || (self.pinned)(&entry.value) {
+            return Some(read(&entry.value));
+        }
+        if let Some(entry) = shard.entries.remove(key) {
+            self.removed(key, &entry);
+        }
+        None
+    }
+
+    pub fn read<R>(&self, key: &K, read: impl FnOnce(&V) -> R) -> Option<R> {
+        self.read_at(key, Instant::now(), read)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut shard = self.shard(key);
+        let entry = shard.entries.remove(key)?;
+        self.removed(key, &entry);
+        Some(entry.value)
+    }
+
+    /// Visits every entry, locking one shard at a time.
+    pub fn for_each(&self, mut visit: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            let shard = shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            for (key, entry) in &shard.entries {
+                visit(key, &entry.value);
+            }
+        }
+    }
+
+    /// Drops every unpinned entry for which `keep` is false, one shard at a
+    /// time. A full pass; the background sweep is the bounded alternative.
+    pub fn retain(&self, mut keep: impl FnMut(&K, &V) -> bool) -> usize {
+        let mut dropped = 0;
+        for shard in self.shards.iter() {
+            let mut shard = shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            shard.entries.retain(|key, entry| {
+                if (self.pinned)(&entry.value) || keep(key, &entry.value) {
+                    return true;
+                }
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                self.bytes.fetch_sub((self.weigh)(key, &entry.value), Ordering::Relaxed);
+                dropped += 1;
+                false
+            });
+        }
+        dropped
+    }
+
+    /// Frees at least `bytes` if it can, dropping the entries due soonest
+    /// first. Returns roughly how much was freed.
+    pub fn evict_bytes(&self, bytes: u64) -> u64 {
+        let before = self.memory_usage();
+        let mut evicted = 0;
+        while before - self.memory_usage().min(before) < bytes && !self.is_empty() {
+            let mut progressed = false;
+            for shard in self.shards.iter() {
+                let mut shard = shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let dropped = self.evict_due(&mut shard, 64, SWEEP_BUDGET);
+                evicted += dropped;
+                progressed |= dropped > 0;
+                if before - self.memory_usage().min(before) >= bytes {
+                    break;
+                }
+            }
+            if !progressed {
+                break;
+            }
+        }
+        crate::metrics::record_state_evictions(self.name, "memory", evicted);
+        before - self.memory_usage().min(before)
+    }
+
+    /// Drops expired entries, examining at most `budget` heap entries across
+    /// shards. Shards are visited round-robin from where the last tick
+    /// stopped, so a large backlog is worked off over several ticks.
+    pub fn sweep_at(&self, now: Instant, budget: usize) -> SweepStats {
+        let started = Instant::now();
+        let mut stats = SweepStats::default();
+        let first = self.cursor.load(Ordering::Relaxed);
+        for offset in 0..self.shards.len() {
+            if stats.examined >= budget {
+                break;
+            }
+            let index = (first + offset) % self.shards.len();
+            let mut guard = self.shards[index].lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let shard = &mut *guard;
+            while stats.examined < budget {
+                if shard.due.peek().is_none_or(|due| due.at > now) {
+                    break;
+                }
+                let Some(due) = shard.due.pop() else { break };
+                stats.examined += 1;
+                let Some(entry) = shard.entries.get_mut(&due.key).filter(|entry| entry.generation == due.generation)
+                else {
+                    continue;
+                };
+                if (self.pinned)(&entry.value) {
+                    entry.expires_at = now + entry.ttl;
+                }
+                if entry.expires_at > now {
+                    let at = entry.expires_at;
+                    shard.due.push(Due { at, ..due });
+                } else if let Some(entry) = shard.entries.remove(&due.key) {
+                    self.removed(&due.key, &entry);
+                    stats.expired += 1;
+                }
+            }
+            // Resume from a shard that may still have due entries
+            let next = if stats.examined >= budget { index } else { index + 1 };
+            self.cursor.store(next % self.shards.len(), Ordering::Relaxed);
+        }
+        stats.took = started.elapsed();
+        crate::metrics::record_state_sweep(self.name, self.len(), stats.expired, stats.took);
+        stats
+    }
+}
+
+impl<K: Hash + Eq + Clone + Send, V: Send> Sweep for ExpiringMap<K, V> {
+    fn sweep(&self, budget: usize) -> SweepStats {
+        self.sweep_at(Instant::now(), budget)
+    }
+}
+
+/// Sweeps every registered store each [`SWEEP_INTERVAL`].
+#[derive(Default)]
+pub struct Sweeper {
+    stores: RwLock<Vec<Arc<dyn Sweep>>>,
+}
+
+impl Sweeper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, store: Arc<dyn Sweep>) {
+        if let Ok(mut stores) = self.stores.write() {
+            stores.push(store);
+        }
+    }
+
+    /// One tick over every store, each within `budget`.
+    pub fn sweep(&self, budget: usize) -> Vec<SweepStats> {
+        let stores: Vec<Arc<dyn Sweep>> = self.stores.read().map(|stores| stores.clone()).unwrap_or_default();
+        stores.iter().map(|store| store.sweep(budget)).collect()
+    }
+
+    pub fn start(self: &Arc<Self>) {
+        let sweeper = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(SWEEP_INTERVAL);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tick.tick().await;
+                sweeper.sweep(SWEEP_BUDGET);
+            }
+        });
+    }
+}
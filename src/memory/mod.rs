@@ -0,0 +1,185 @@
+//! One memory budget shared by the gateway's in-memory stores.
+//!
+//! Each store reports an approximate footprint. When the total passes the
+//! budget, stores give memory back in a fixed order until the total is down
+//! to the configured low-water mark. Stores without an eviction rank, such
+//! as the per-client concurrency state, are counted but never evicted.
+
+use metrics::{counter, gauge};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::{watcher::ConfigWatcher, MemoryConfig},
+    metrics::labels::{label, Dimension},
+};
+
+pub use crate::models::health::{MemoryReport, StoreUsage};
+
+pub mod expiring;
+
+/// How often the budget is enforced in the background, on top of the checks
+/// stores trigger as they grow.
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A store whose memory counts against the budget.
+pub trait MemoryConsumer: Send + Sync {
+    /// Approximate bytes held. Called on every budget check, so keep it cheap.
+    fn memory_usage(&self) -> u64;
+
+    /// Frees at least `bytes` if it can, returning roughly how much was freed.
+    fn evict(&self, bytes: u64) -> u64;
+}
+
+struct Registered {
+    name: &'static str,
+    /// Lower ranks are evicted first; `None` is never evicted.
+    eviction_rank: Option<u8>,
+    store: Arc<dyn MemoryConsumer>,
+}
+
+pub struct MemoryBudget {
+    budget: AtomicU64,
+    low_water: AtomicU64,
+    stores: RwLock<Vec<Registered>>,
+}
+
+fn low_water(config: &MemoryConfig) -> u64 {
+    (config.budget.bytes() as f64 * config.evict_to_percentage.clamp(0.0, 100.0) / 100.0) as u64
+}
+
+impl MemoryBudget {
+    pub fn new(config: &MemoryConfig) -> Self {
+        gauge!("gateway_memory_budget_bytes").set(config.budget.bytes() as f64);
+        Self {
+            budget: AtomicU64::new(config.budget.bytes()),
+            low_water: AtomicU64::new(low_water(config)),
+            stores: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Adds a store. `eviction_rank` orders eviction, lowest first; `None`
+    /// means the store is tracked but never evicted.
+    pub fn register(&self, name: &'static str, eviction_rank: Option<u8>, store: Arc<dyn MemoryConsumer>) {
+        if let Ok(mut stores) = self.stores.write() {
+            stores.push(Registered {
+                name,
+                eviction_rank,
+                store,
+            });
+            stores.sort_by_key(|registered| registered.eviction_rank.unwrap_or(u8::MAX));
+        }
+    }
+
+    pub fn budget_bytes(&self) -> u64 {
+        self.budget.load(Ordering::Relaxed)
+    }
+
+    /// Current usage per store, publishing the usage gauges.
+    pub fn report(&self) -> MemoryReport {
+        let stores: Vec<StoreUsage> = self
+            .stores
+            .read()
+            .map(|stores| {
+                stores
+                    .iter()
+                    .map(|registered| StoreUsage {
+                        name: registered.name.to_string(),
+                        bytes: registered.store.memory_usage(),
+                        evictable: registered.eviction_rank.is_some(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let used_bytes = stores.iter().map(|store| store.bytes).sum();
+        for store in &stores {
+            gauge!("gateway_memory_usage_bytes", "store" => label(Dimension::Store, &store.name)).set(store.bytes as f64);
+        }
+        MemoryReport {
+            budget_bytes: self.budget_bytes(),
+            low_water_bytes: self.low_water.load(Ordering::Relaxed),
+            used_bytes,
+            stores,
+        }
+    }
+
+    fn used_bytes(&self) -> u64 {
+        self.stores
+            .read()
+            .map(|stores| stores.iter().map(|registered| registered.store.memory_usage()).sum())
+            .unwrap_or(0)
+    }
+
+    /// Evicts from stores in rank order while the total is over budget,
+    /// until it is back at the low-water mark. Returns the bytes freed.
+    ///
+    /// Cheap when under budget, so stores can call it after every insert.
+    pub fn enforce(&self) -> u64 {
+        let used = self.used_bytes();
+        let budget = self.budget_bytes();
+        if used <= budget {
+            return 0;
+        }
+
+        let mut excess = used - self.low_water.load(Ordering::Relaxed).min(budget);
+        let mut freed_total = 0;
+        if let Ok(stores) = self.stores.read() {
+            for registered in stores.iter().filter(|registered| registered.eviction_rank.is_some()) {
+                if excess == 0 {
+                    break;
+                }
+                let freed = registered.store.evict(excess);
+                if freed > 0 {
+                    counter!("gateway_memory_evicted_bytes_total", "store" => label(Dimension::Store, registered.name)).increment(freed);
+                    info!(store = registered.name, freed_bytes = freed, "Evicted under memory pressure");
+                }
+                excess = excess.saturating_sub(freed);
+                freed_total += freed;
+            }
+        }
+
+        let report = self.report();
+        if report.used_bytes > budget {
+            warn!(
+                used_bytes = report.used_bytes,
+                budget_bytes = budget,
+                "Memory use is over budget with nothing left to evict"
+            );
+        }
+        freed_total
+    }
+
+    /// Applies budget changes from config reloads and enforces the budget
+    /// periodically.
+    pub fn start(self: &Arc<Self>, config_watcher: &ConfigWatcher) {
+        let mut reloads = config_watcher.subscribe_to_reloads();
+        let budget = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(ENFORCE_INTERVAL);
+            loop {
+                tokio::select! {
+                    reload = reloads.recv() => match reload {
+                        Ok(config) => {
+                            budget.budget.store(config.memory.budget.bytes(), Ordering::Relaxed);
+                            budget.low_water.store(low_water(&config.memory), Ordering::Relaxed);
+                            gauge!("gateway_memory_budget_bytes").set(config.memory.budget.bytes() as f64);
+                            budget.enforce();
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = tick.tick() => {
+                        budget.enforce();
+                        budget.report();
+                    }
+                }
+            }
+        });
+    }
+}
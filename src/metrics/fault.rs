@@ -0,0 +1,25 @@
+//! Breaking the metrics pipeline on purpose, for tests of
+//! [`super::selfcheck`].
+
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DETACHED: AtomicBool = AtomicBool::new(false);
+
+/// A recorder nothing records to.
+static UNUSED_RECORDER: Lazy<PrometheusHandle> = Lazy::new(|| PrometheusBuilder::new().build_recorder().handle());
+
+/// While `detached`, the exporter renders a recorder other than the one the
+/// gateway records to, as if the two had been wired up separately.
+pub fn detach_exporter(detached: bool) {
+    DETACHED.store(detached, Ordering::Relaxed);
+}
+
+pub(super) fn exporter_detached() -> bool {
+    DETACHED.load(Ordering::Relaxed)
+}
+
+pub(super) fn detached_render() -> String {
+    UNUSED_RECORDER.render()
+}
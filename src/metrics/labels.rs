@@ -0,0 +1,260 @@
+//! Bounds on the values metric labels can take.
+//!
+//! Every label whose value isn't a compile-time constant goes through
+//! [`label`]. Each [`Dimension`] has a cap on distinct values and, where
+//! there is a natural source such as the route table, an allowlist. Values
+//! outside the allowlist, or new values once the cap is reached, are
+//! reported as [`OTHER`] and counted in
+//! `gateway_metric_label_overflow_total{dimension}`. Labels given as
+//! `&'static str` are bounded by the code and skip the guard.
+
+use metrics::counter;
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
+use tracing::warn;
+use utoipa::OpenApi;
+
+use crate::config::{watcher::ConfigWatcher, AppConfig, RolloutOverride, DEFAULT_ROUTE_GROUP};
+
+/// What an out-of-bounds label value is replaced with.
+pub const OTHER: &str = "other";
+
+const HTTP_METHODS: [&str; 9] = ["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS", "CONNECT", "TRACE"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    /// Route patterns: the configured routes, documented endpoints and
+    /// contract samples.
+    Route,
+    Method,
+    /// `scheme://host:port` of an upstream.
+    Upstream,
+    /// A client identity, already pseudonymized or hashed.
+    Client,
+    /// Configured API versions.
+    ApiVersion,
+    /// Configured SLO names.
+    Slo,
+    /// TLS certificate names.
+    Certificate,
+    /// The time source the clock is checked against.
+    ClockSource,
+    /// In-memory stores under the memory budget.
+    Store,
+    /// Index into `jwt_secrets`.
+    SecretIndex,
+    /// Rollout override groups, plus `default`.
+    RouteGroup,
+    /// Rollout generation.
+    Generation,
+}
+
+impl Dimension {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dimension::Route => "route",
+            Dimension::Method => "method",
+            Dimension::Upstream => "upstream",
+            Dimension::Client => "client",
+            Dimension::ApiVersion => "api_version",
+            Dimension::Slo => "slo",
+            Dimension::Certificate => "certificate",
+            Dimension::ClockSource => "clock_source",
+            Dimension::Store => "store",
+            Dimension::SecretIndex => "secret_index",
+            Dimension::RouteGroup => "route_group",
+            Dimension::Generation => "generation",
+        }
+    }
+
+    /// Distinct values kept before new ones become [`OTHER`].
+    pub fn default_cap(&self) -> usize {
+        match self {
+            Dimension::Route => 500,
+            Dimension::Method => HTTP_METHODS.len(),
+            Dimension::Upstream => 64,
+            Dimension::Client => 1000,
+            Dimension::ApiVersion => 32,
+            Dimension::Slo => 64,
+            Dimension::Certificate => 64,
+            Dimension::ClockSource => 8,
+            Dimension::Store => 32,
+            Dimension::SecretIndex => 16,
+            Dimension::RouteGroup => 64,
+            Dimension::Generation => 100,
+        }
+    }
+}
+
+struct Bounds {
+    allowlist: Option<HashSet<String>>,
+    cap: usize,
+    seen: HashSet<String>,
+    /// The cap has been hit and logged.
+    full: bool,
+}
+
+impl Bounds {
+    fn new(cap: usize) -> Self {
+        Self {
+            allowlist: None,
+            cap,
+            seen: HashSet::new(),
+            full: false,
+        }
+    }
+
+    fn admits(&self, value: &str) -> bool {
+        self.allowlist.as_ref().is_none_or(|allowlist| allowlist.contains(value))
+    }
+}
+
+/// Per-dimension allowlists and caps, and the values admitted so far.
+pub struct LabelGuard {
+    dimensions: RwLock<HashMap<Dimension, Bounds>>,
+}
+
+impl Default for LabelGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LabelGuard {
+    /// A guard with every dimension at its default cap and only methods
+    /// allowlisted.
+    pub fn new() -> Self {
+        let guard = Self {
+            dimensions: RwLock::new(HashMap::new()),
+        };
+        guard.register(Dimension::Method, Some(HTTP_METHODS.map(str::to_string).to_vec()), HTTP_METHODS.len());
+        guard
+    }
+
+    /// Sets the allowlist (`None` for any value) and cap of `dimension`.
+    /// Admitted values no longer on the allowlist are forgotten.
+    pub fn register(&self, dimension: Dimension, allowlist: Option<Vec<String>>, cap: usize) {
+        let Ok(mut dimensions) = self.dimensions.write() else {
+            return;
+        };
+        let allowlist: Option<HashSet<String>> = allowlist.map(|values| values.into_iter().collect());
+        let bounds = dimensions.entry(dimension).or_insert_with(|| Bounds::new(cap));
+        bounds.cap = cap;
+        bounds.allowlist = allowlist;
+        let mut seen = std::mem::take(&mut bounds.seen);
+        seen.retain(|value| bounds.admits(value));
+        bounds.seen = seen;
+    }
+
+    /// `value`, or [`OTHER`] when it is off the allowlist or would exceed
+    /// the cap.
+    pub fn label(&self, dimension: Dimension, value: &str) -> String {
+        if self.admit(dimension, value) {
+            value.to_string()
+        } else {
+            counter!("gateway_metric_label_overflow_total", "dimension" => dimension.as_str()).increment(1);
+            OTHER.to_string()
+        }
+    }
+
+    fn admit(&self, dimension: Dimension, value: &str) -> bool {
+        if let Ok(dimensions) = self.dimensions.read() {
+            match dimensions.get(&dimension) {
+                Some(bounds) if !bounds.admits(value) => return false,
+                Some(bounds) if bounds.seen.contains(value) => return true,
+                _ => {}
+            }
+        }
+        let Ok(mut dimensions) = self.dimensions.write() else {
+            return false;
+        };
+        let bounds = dimensions.entry(dimension).or_insert_with(|| Bounds::new(dimension.default_cap()));
+        if !bounds.admits(value) {
+            return false;
+        }
+        if bounds.seen.contains(value) {
+            return true;
+        }
+        if bounds.seen.len() >= bounds.cap {
+            if !std::mem::replace(&mut bounds.full, true) {
+                warn!(dimension = dimension.as_str(), cap = bounds.cap, "Metric label cap reached; new values become \"other\"");
+            }
+            return false;
+        }
+        bounds.seen.insert(value.to_string());
+        true
+    }
+
+    /// Adds `values` to the allowlist of `dimension`, starting one at its
+    /// default cap if it had none.
+    pub fn allow(&self, dimension: Dimension, values: impl IntoIterator<Item = String>) {
+        let Ok(mut dimensions) = self.dimensions.write() else {
+            return;
+        };
+        let bounds = dimensions.entry(dimension).or_insert_with(|| Bounds::new(dimension.default_cap()));
+        bounds.allowlist.get_or_insert_with(HashSet::new).extend(values);
+    }
+
+    /// Distinct values admitted for `dimension`.
+    pub fn distinct(&self, dimension: Dimension) -> usize {
+        self.dimensions
+            .read()
+            .ok()
+            .and_then(|dimensions| dimensions.get(&dimension).map(|bounds| bounds.seen.len()))
+            .unwrap_or(0)
+    }
+
+    /// Allows the routes, API versions, SLO names and rollout override
+    /// groups in `config`. Values a later config drops stay allowed, within
+    /// the cap, so series already exported keep their labels.
+    pub fn configure(&self, config: &AppConfig) {
+        self.allow(Dimension::Route, route_allowlist(config));
+        let versions = config.versioning.versions.iter().map(|version| version.name.clone());
+        self.allow(Dimension::ApiVersion, versions.chain(["unknown".to_string()]));
+        self.allow(Dimension::Slo, config.slo.objectives.iter().map(|objective| objective.name.clone()));
+        let groups = config.canary_rollout.route_overrides.iter().map(RolloutOverride::group);
+        self.allow(Dimension::RouteGroup, groups.chain([DEFAULT_ROUTE_GROUP.to_string()]));
+    }
+
+    /// Re-reads the allowlists on every config reload.
+    pub fn follow_reloads(&'static self, config_watcher: &ConfigWatcher) {
+        let mut reloads = config_watcher.subscribe_to_reloads();
+        tokio::spawn(async move {
+            loop {
+                match reloads.recv().await {
+                    Ok(config) => self.configure(&config),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+/// Configured route paths, the gateway's documented endpoints in both
+/// OpenAPI (`{id}`) and router (`:id`) form, contract sample routes and
+/// `unmatched`.
+fn route_allowlist(config: &AppConfig) -> Vec<String> {
+    let documented: Vec<String> = crate::docs::ApiDoc::openapi().paths.paths.into_keys().collect();
+    let mut routes: Vec<String> = config.routes.iter().map(|route| route.path.clone()).collect();
+    for path in documented {
+        routes.push(path.replace('{', ":").replace('}', ""));
+        routes.push(path);
+    }
+    for sample in &config.contract_check.samples {
+        routes.push(sample.route.clone().unwrap_or_else(|| sample.path.clone()));
+    }
+    routes.push("unmatched".to_string());
+    routes
+}
+
+/// The guard every metric in the gateway goes through.
+pub static LABEL_GUARD: Lazy<LabelGuard> = Lazy::new(LabelGuard::new);
+
+/// [`LabelGuard::label`] on the shared guard.
+pub fn label(dimension: Dimension, value: &str) -> String {
+    LABEL_GUARD.label(dimension, value)
+}
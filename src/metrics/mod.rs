@@ -1,5 +1,15 @@
 use metrics::{counter, histogram, Counter, Histogram};
-use once_cell::sync::Lazy;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::{Lazy, OnceCell};
+
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod labels;
+pub mod selfcheck;
+
+use labels::{label, Dimension};
+
+static PROMETHEUS_HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
 
 pub struct MirrorMetrics {
     pub requests_total: Counter,
@@ -8,7 +18,6 @@ pub struct MirrorMetrics {
 }
 
 pub struct GatewayMetrics {
-    pub requests_total: Counter,
     pub errors_5xx_total: Counter,
     pub latency_seconds: Histogram,
     pub rust_requests_total: Counter,
@@ -22,16 +31,17 @@ pub static MIRROR_METRICS: Lazy<MirrorMetrics> = Lazy::new(|| MirrorMetrics {
 });
 
 pub static GATEWAY_METRICS: Lazy<GatewayMetrics> = Lazy::new(|| GatewayMetrics {
-    requests_total: counter!("gateway_requests_total"),
     errors_5xx_total: counter!("gateway_5xx_total"),
     latency_seconds: histogram!("gateway_latency_seconds"),
     rust_requests_total: counter!("gateway_rust_requests_total"),
     legacy_requests_total: counter!("gateway_legacy_requests_total"),
 });
 
-pub fn record_gateway_request(gateway_type: &str, status_code: u16, latency_seconds: f64) {
+/// `generation` is the rollout generation the request was routed under;
+/// it only moves when the rollout state does, so it stays a small label.
+pub fn record_gateway_request(gateway_type: &str, status_code: u16, latency_seconds: f64, generation: u64) {
     // Record total requests
-    GATEWAY_METRICS.requests_total.increment(1);
+    counter!("gateway_requests_total", "generation" => label(Dimension::Generation, &generation.to_string())).increment(1);
     
     // Record latency
     GATEWAY_METRICS.latency_seconds.record(latency_seconds);
@@ -49,12 +59,374 @@ pub fn record_gateway_request(gateway_type: &str, status_code: u16, latency_seco
     }
 }
 
+/// Creates the static metric handles now rather than on the first request.
+/// Call after [`install_recorder`].
+pub fn warm_up() {
+    Lazy::force(&GATEWAY_METRICS);
+    Lazy::force(&MIRROR_METRICS);
+    selfcheck::warm_up();
+}
+
+/// Installs the global Prometheus recorder (once) and returns its handle.
+///
+/// Must run before any `Lazy` metric handle is first touched, otherwise those
+/// handles stay bound to the no-op recorder.
+pub fn install_recorder() -> PrometheusHandle {
+    PROMETHEUS_HANDLE
+        .get_or_init(|| {
+            let handle = PrometheusBuilder::new()
+                .install_recorder()
+                .expect("failed to install Prometheus recorder");
+            metrics::gauge!(
+                "gateway_instance_info",
+                "instance_id" => crate::coordination::instance_id(),
+                "version" => env!("CARGO_PKG_VERSION")
+            )
+            .set(1.0);
+            handle
+        })
+        .clone()
+}
+
+pub fn record_request_bytes(route: &str, bytes: u64) {
+    counter!("gateway_request_bytes_total", "route" => label(Dimension::Route, route)).increment(bytes);
+}
+
+pub fn record_response_bytes(route: &str, variant: &'static str, bytes: u64) {
+    let route = label(Dimension::Route, route);
+    counter!("gateway_response_bytes_total", "route" => route.clone(), "variant" => variant).increment(bytes);
+    histogram!(
+        "gateway_response_size_bytes",
+        "route" => route,
+        "variant" => variant
+    )
+    .record(bytes as f64);
+}
+
+/// Outcome of a route's smoke check; `gateway_route_live` is 1 once the
+/// route may take rollout traffic.
+pub fn record_smoke_check(method: &str, route: &str, passed: bool) {
+    let (method, route) = (label(Dimension::Method, &method.to_uppercase()), label(Dimension::Route, route));
+    counter!(
+        "gateway_smoke_checks_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "result" => if passed { "passed" } else { "failed" }
+    )
+    .increment(1);
+    metrics::gauge!("gateway_route_live", "method" => method, "route" => route)
+        .set(if passed { 1.0 } else { 0.0 });
+}
+
+/// A body `feature` couldn't look inside: its encoding is unsupported, it
+/// decodes past the size limit, or it is corrupt.
+pub fn record_inspection_skipped(feature: &'static str, reason: &'static str) {
+    counter!("gateway_body_inspection_skipped_total", "feature" => feature, "reason" => reason).increment(1);
+}
+
+/// An upstream response on `route` that broke its configured expectations;
+/// `source` is `legacy` or `mirror`.
+pub fn record_contract_violation(route: &str, source: &'static str, kind: &'static str) {
+    counter!(
+        "gateway_upstream_contract_violations_total",
+        "route" => label(Dimension::Route, route),
+        "source" => source,
+        "kind" => kind
+    )
+    .increment(1);
+}
+
+/// A request in rollout override `route_group` (or `default`) served by
+/// `variant`.
+pub fn record_route_group_request(route_group: &str, variant: &str, status_code: u16) {
+    let route_group = label(Dimension::RouteGroup, route_group);
+    counter!("gateway_route_group_requests_total", "route_group" => route_group.clone(), "variant" => variant.to_string())
+        .increment(1);
+    if status_code >= 500 {
+        counter!("gateway_route_group_errors_total", "route_group" => route_group, "variant" => variant.to_string())
+            .increment(1);
+    }
+}
+
+/// A request on `route` slower than the slow request threshold.
+pub fn record_slow_request(route: &str) {
+    counter!("gateway_slow_requests_total", "route" => label(Dimension::Route, route)).increment(1);
+}
+
+/// A 2xx request on `route` left out of the access log by sampling.
+pub fn record_access_log_suppressed(route: &str) {
+    counter!("gateway_access_log_suppressed_total", "route" => label(Dimension::Route, route)).increment(1);
+}
+
+/// A request rejected with 431; `limit` is which limit it broke and `scope`
+/// whose (`server` or the upstream's).
+pub fn record_header_limit_rejection(limit: &'static str, scope: &'static str) {
+    counter!("gateway_header_limit_rejections_total", "limit" => limit, "scope" => scope).increment(1);
+}
+
+/// A request rejected with 413; `reason` is `content_length` when the
+/// declared length was over the limit, `stream` when the body grew past it.
+pub fn record_body_limit_rejection(route: &str, reason: &'static str) {
+    counter!("gateway_body_limit_rejections_total", "route" => label(Dimension::Route, route), "reason" => reason)
+        .increment(1);
+}
+
+/// A request turned away because its API key used up its `period`
+/// (`daily` or `monthly`) quota.
+pub fn record_quota_exhausted(key_id: &str, period: &'static str) {
+    counter!("gateway_quota_exhausted_total", "key_id" => key_id.to_string(), "period" => period).increment(1);
+}
+
+/// A request let through uncounted because the quota store failed.
+pub fn record_quota_store_error() {
+    counter!("gateway_quota_store_errors_total").increment(1);
+}
+
+/// A state-changing request turned away by a CSRF check; `reason` is its
+/// problem code.
+pub fn record_csrf_rejection(reason: &'static str) {
+    counter!("gateway_csrf_rejections_total", "reason" => reason).increment(1);
+}
+
+/// A request its route's authorization turned away; `reason` is its
+/// error code.
+pub fn record_authorization_denial(route: &str, reason: &'static str) {
+    counter!("gateway_authorization_denials_total", "route" => label(Dimension::Route, route), "reason" => reason)
+        .increment(1);
+}
+
+/// A request denied by authentication or authorization; `reason` is an
+/// [`crate::middleware::auth_audit::AuthFailureReason`].
+pub fn record_auth_failure(reason: &'static str) {
+    counter!("gateway_auth_failures_total", "reason" => reason).increment(1);
+}
+
+/// A response served while config is staged; `variant` is `current` or
+/// `staged`.
+pub fn record_config_variant_response(variant: &'static str, status: u16) {
+    let status_class = format!("{}xx", status / 100);
+    counter!("gateway_config_variant_responses_total", "variant" => variant, "status_class" => status_class).increment(1);
+}
+
+/// A config reload that applied only the sections of the file that loaded.
+pub fn record_config_partial_reload() {
+    counter!("gateway_config_partial_reload_total").increment(1);
+}
+
+/// A completed request's trace sampling decision; `reason` is why it was
+/// kept, or `dropped`.
+pub fn record_trace_sampling(reason: &'static str) {
+    counter!("gateway_trace_sampling_decisions_total", "reason" => reason).increment(1);
+}
+
+/// A JWT accepted with `jwt_secrets[index]`; once older indexes stop
+/// counting up, their secrets can be removed.
+pub fn record_jwt_secret_use(index: usize) {
+    counter!("gateway_jwt_secret_validations_total", "secret_index" => label(Dimension::SecretIndex, &index.to_string()))
+        .increment(1);
+}
+
+/// An opaque token validated by introspection; `outcome` is `active`,
+/// `inactive`, `cached` or `error`.
+pub fn record_token_introspection(outcome: &'static str) {
+    counter!("gateway_token_introspections_total", "outcome" => outcome).increment(1);
+}
+
+/// A webhook notification; `outcome` is `sent`, `rate_limited` or
+/// `too_soon`.
+pub fn record_notification(kind: &'static str, outcome: &'static str) {
+    counter!("gateway_notifications_total", "kind" => kind, "outcome" => outcome).increment(1);
+}
+
+/// One upstream health probe; `gateway_upstream_healthy` holds the latest
+/// verdict.
+pub fn record_upstream_probe(upstream: &'static str, method: &'static str, healthy: bool, latency: std::time::Duration) {
+    let outcome = if healthy { "healthy" } else { "unhealthy" };
+    counter!("gateway_upstream_health_probes_total", "upstream" => upstream, "method" => method, "outcome" => outcome)
+        .increment(1);
+    histogram!("gateway_upstream_health_probe_seconds", "upstream" => upstream).record(latency.as_secs_f64());
+    metrics::gauge!("gateway_upstream_healthy", "upstream" => upstream).set(if healthy { 1.0 } else { 0.0 });
+}
+
+/// Attempts beyond the first made by a retried `operation`.
+pub fn record_retries(operation: &'static str, retries: u32) {
+    if retries > 0 {
+        counter!("gateway_retries_total", "operation" => operation).increment(u64::from(retries));
+    }
+}
+
+/// System clock minus the trusted time source, from the latest check.
+pub fn record_clock_skew(source: &str, seconds: f64) {
+    metrics::gauge!("gateway_clock_skew_seconds", "source" => label(Dimension::ClockSource, source)).set(seconds);
+}
+
+/// Maintenance windows currently in force.
+pub fn record_maintenance_windows_active(windows: usize) {
+    metrics::gauge!("gateway_maintenance_windows_active").set(windows as f64);
+}
+
+/// A request answered 503 because its route is under maintenance.
+pub fn record_maintenance_rejection(route: &str) {
+    counter!("gateway_maintenance_rejections_total", "route" => label(Dimension::Route, route)).increment(1);
+}
+
+/// A write answered 503 because the gateway is in read-only mode.
+pub fn record_read_only_rejection(method: &str) {
+    counter!("gateway_read_only_rejections_total", "method" => label(Dimension::Method, method)).increment(1);
+}
+
+/// Share of requests currently mirrored, after the mirror schedule.
+pub fn record_mirror_sample_percentage(percentage: f64) {
+    metrics::gauge!("gateway_mirror_sample_percentage").set(percentage);
+}
+
+/// A request chosen for profiling; `outcome` is `captured`, or
+/// `start_budget` when the profiler wasn't free in time.
+pub fn record_profile(trigger: &'static str, outcome: &'static str) {
+    counter!("gateway_profiles_total", "trigger" => trigger, "outcome" => outcome).increment(1);
+}
+
+/// A request negotiated to an API `version`; `lifecycle` is `stable`,
+/// `deprecated`, or `unsupported` for a rejected version (labelled
+/// `unknown` so clients can't mint label values).
+pub fn record_api_request(version: &str, lifecycle: &'static str) {
+    counter!("gateway_api_requests_total", "version" => label(Dimension::ApiVersion, version), "lifecycle" => lifecycle)
+        .increment(1);
+}
+
+/// Error-budget burn rate of an SLO over `window` (`1h` or `6h`).
+pub fn record_slo_burn_rate(slo: &str, window: &'static str, burn_rate: f64) {
+    metrics::gauge!("gateway_slo_burn_rate", "slo" => label(Dimension::Slo, slo), "window" => window).set(burn_rate);
+}
+
+/// Latest drift score of `variant`'s latency distribution on `route`.
+pub fn record_latency_drift(variant: &'static str, route: &str, score: f64) {
+    metrics::gauge!("gateway_latency_drift_score", "variant" => variant, "route" => label(Dimension::Route, route))
+        .set(score);
+}
+
+/// Requests admitted past load shedding and not yet answered.
+pub fn record_in_flight_requests(in_flight: usize) {
+    metrics::gauge!("gateway_in_flight_requests").set(in_flight as f64);
+}
+
+/// A request to `route` turned away with 503 because the gateway was at
+/// `load_shedding.max_in_flight`.
+pub fn record_load_shed(route: &str) {
+    counter!("gateway_load_shed_total", "route" => label(Dimension::Route, route)).increment(1);
+}
+
+/// A request answered 403 by `ip_filter`, because its client was `denied` or
+/// `not_allowed`.
+pub fn record_ip_blocked(reason: &'static str) {
+    counter!("gateway_ip_blocked_total", "reason" => reason).increment(1);
+}
+
+/// How long the listener answered `starting_up` before the router took
+/// over.
+pub fn record_bootstrap_duration(duration: std::time::Duration) {
+    metrics::gauge!("gateway_bootstrap_duration_seconds").set(duration.as_secs_f64());
+}
+
+/// A request turned away with `starting_up`.
+pub fn record_bootstrap_rejection() {
+    counter!("gateway_bootstrap_rejected_requests_total").increment(1);
+}
+
+/// A listener bound, rebound, drained, or kept after a failed rebind.
+pub fn record_listener_event(kind: crate::listener::ListenerEventKind) {
+    use crate::listener::ListenerEventKind;
+    let kind = match kind {
+        ListenerEventKind::Bound => "bound",
+        ListenerEventKind::Rebound => "rebound",
+        ListenerEventKind::Drained => "drained",
+        ListenerEventKind::RebindFailed => "rebind_failed",
+    };
+    counter!("gateway_listener_events_total", "kind" => kind).increment(1);
+}
+
+/// One sweep tick over a per-client store: entries left and how long it took.
+pub fn record_state_sweep(store: &'static str, entries: usize, expired: usize, took: std::time::Duration) {
+    metrics::gauge!("gateway_state_entries", "store" => store).set(entries as f64);
+    record_state_evictions(store, "expired", expired);
+    histogram!("gateway_state_sweep_seconds", "store" => store).record(took.as_secs_f64());
+}
+
+/// Entries dropped from a per-client store; `reason` is `expired`,
+/// `capacity` or `memory`.
+pub fn record_state_evictions(store: &'static str, reason: &'static str, count: usize) {
+    if count > 0 {
+        counter!("gateway_state_evictions_total", "store" => store, "reason" => reason).increment(count as u64);
+    }
+}
+
+/// Mirror requests waiting to be sent, and what the `disk` queue's
+/// segments take up.
+pub fn record_mirror_queue(depth: u64, disk_bytes: u64) {
+    metrics::gauge!("gateway_mirror_queue_depth").set(depth as f64);
+    metrics::gauge!("gateway_mirror_queue_disk_bytes").set(disk_bytes as f64);
+}
+
+/// A request whose client went away before its response was ready;
+/// `stage` is how far processing had got. Such requests are left out of
+/// request and error counts.
+pub fn record_client_disconnect(method: &str, route: &str, stage: &'static str) {
+    counter!(
+        "gateway_client_disconnects_total",
+        "method" => label(Dimension::Method, method),
+        "route" => label(Dimension::Route, route),
+        "stage" => stage
+    )
+    .increment(1);
+}
+
+/// How a mirror response's body compared with the main one; `result` is
+/// `match`, `mismatch` or `not_compared`.
+pub fn record_mirror_body_comparison(result: &'static str) {
+    counter!("gateway_mirror_body_comparisons_total", "result" => result).increment(1);
+}
+
+/// Main response bodies the mirror comparison stopped capturing; `reason`
+/// is `interrupted` or `fell_behind`.
+pub fn record_mirror_capture_abandoned(reason: &'static str) {
+    counter!("gateway_mirror_captures_abandoned_total", "reason" => reason).increment(1);
+}
+
+/// Mirror requests found in the `disk` queue at startup, to be sent after
+/// the restart.
+pub fn record_mirror_queue_replayed(count: u64) {
+    counter!("gateway_mirror_queue_replayed_total").increment(count);
+}
+
+/// Mirror requests never sent; `reason` is `full`, `memory` or `io_error`.
+pub fn record_mirror_queue_dropped(reason: &'static str, count: u64) {
+    if count > 0 {
+        counter!("gateway_mirror_queue_dropped_total", "reason" => reason).increment(count);
+    }
+}
+
+pub fn record_queue_wait(waited: std::time::Duration) {
+    histogram!("gateway_queue_seconds").record(waited.as_secs_f64());
+}
+
+/// Splits proxied legacy latency into upstream phases and gateway overhead.
+pub fn record_upstream_latency(first_byte: std::time::Duration, full_body: std::time::Duration, overhead: std::time::Duration) {
+    histogram!("gateway_upstream_seconds", "phase" => "first_byte").record(first_byte.as_secs_f64());
+    histogram!("gateway_upstream_seconds", "phase" => "full_body").record(full_body.as_secs_f64());
+    histogram!("gateway_overhead_seconds").record(overhead.as_secs_f64());
+}
+
+/// The exporter's output, as `/metrics` serves it; `None` before
+/// [`install_recorder`].
+pub fn render() -> Option<String> {
+    #[cfg(feature = "fault-injection")]
+    if fault::exporter_detached() {
+        return Some(fault::detached_render());
+    }
+    PROMETHEUS_HANDLE.get().map(|handle| handle.render())
+}
+
 pub async fn metrics_handler() -> String {
-    let encoder = prometheus::TextEncoder::new();
-    let metric_families = prometheus::gather();
-    
-    encoder
-        .encode_to_string(&metric_families)
-        .unwrap_or_else(|_| "Error encoding metrics".to_string())
+    render().unwrap_or_else(|| "Error encoding metrics".to_string())
 }
 
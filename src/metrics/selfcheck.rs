@@ -0,0 +1,105 @@
+//! End-to-end check of the metrics pipeline.
+//!
+//! A probe counter and histogram are recorded through static handles, as
+//! the request metrics are, and the exporter's output is scraped before and
+//! after to confirm both series moved by what was recorded. A handle bound
+//! to another recorder, or an exporter rendering one the gateway doesn't
+//! record to, shows up here as a missing or unmoved series instead of as an
+//! empty dashboard.
+
+use metrics::{counter, histogram, Counter, Histogram};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+pub use crate::models::monitoring::{MetricFamilyCheck, MetricsSelfCheck};
+
+const PROBE_COUNTER: &str = "gateway_selfcheck_probe_total";
+const PROBE_HISTOGRAM: &str = "gateway_selfcheck_probe_seconds";
+/// Exact in binary, so the histogram's sum moves by exactly this much.
+const PROBE_SAMPLE: f64 = 0.25;
+
+struct ProbeMetrics {
+    total: Counter,
+    seconds: Histogram,
+}
+
+static PROBE_METRICS: Lazy<ProbeMetrics> = Lazy::new(|| ProbeMetrics {
+    total: counter!(PROBE_COUNTER),
+    seconds: histogram!(PROBE_HISTOGRAM),
+});
+
+/// Creates the probe handles along with the request metrics' handles, so
+/// they're bound to whichever recorder those are.
+pub(super) fn warm_up() {
+    Lazy::force(&PROBE_METRICS);
+}
+
+/// Held while a check runs, so two checks don't see each other's probes.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// The value of the unlabeled `series` in Prometheus text output.
+fn sample(scrape: &str, series: &str) -> Option<f64> {
+    scrape
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.trim().parse().ok())
+}
+
+fn family(name: &str, kind: &str, expected: f64, before: &str, after: &str, series: &str) -> MetricFamilyCheck {
+    let observed = sample(after, series).map(|after_value| after_value - sample(before, series).unwrap_or(0.0));
+    let detail = match observed {
+        None => Some(format!("{} missing from the scrape", series)),
+        Some(delta) if delta != expected => Some(format!("{} moved by {}, expected {}", series, delta, expected)),
+        Some(_) => None,
+    };
+    MetricFamilyCheck {
+        family: name.to_string(),
+        kind: kind.to_string(),
+        passed: detail.is_none(),
+        expected_delta: expected,
+        observed_delta: observed,
+        detail,
+    }
+}
+
+/// Records one probe of each kind and checks the exporter's output shows it.
+pub fn run() -> MetricsSelfCheck {
+    let _running = RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let checked_at = chrono::Utc::now().to_rfc3339();
+    let Some(before) = super::render() else {
+        let failed = |name: &str, kind: &str| MetricFamilyCheck {
+            family: name.to_string(),
+            kind: kind.to_string(),
+            passed: false,
+            expected_delta: 1.0,
+            observed_delta: None,
+            detail: Some("no Prometheus recorder installed".to_string()),
+        };
+        return MetricsSelfCheck {
+            passed: false,
+            families: vec![failed(PROBE_COUNTER, "counter"), failed(PROBE_HISTOGRAM, "histogram")],
+            checked_at,
+        };
+    };
+
+    PROBE_METRICS.total.increment(1);
+    PROBE_METRICS.seconds.record(PROBE_SAMPLE);
+    let after = super::render().unwrap_or_default();
+
+    let counter = family(PROBE_COUNTER, "counter", 1.0, &before, &after, PROBE_COUNTER);
+    let count_series = format!("{}_count", PROBE_HISTOGRAM);
+    let sum_series = format!("{}_sum", PROBE_HISTOGRAM);
+    let mut histogram = family(PROBE_HISTOGRAM, "histogram", 1.0, &before, &after, &count_series);
+    if histogram.passed {
+        let sum = family(PROBE_HISTOGRAM, "histogram", PROBE_SAMPLE, &before, &after, &sum_series);
+        histogram.passed = sum.passed;
+        histogram.detail = sum.detail;
+    }
+
+    let families = vec![counter, histogram];
+    MetricsSelfCheck {
+        passed: families.iter().all(|family| family.passed),
+        families,
+        checked_at,
+    }
+}
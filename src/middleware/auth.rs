@@ -1,16 +1,549 @@
-// TODO: Implement JWT authentication middleware
-// This will be implemented in Phase 1 completion
-
+use anyhow::{anyhow, Result};
 use axum::{
-    extract::Request,
-    http::StatusCode,
+    extract::{Request, State},
+    http::{header, Extensions, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
+use jsonwebtoken::{decode, decode_header, encode, jwk::JwkSet, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::AuthConfig,
+    memory::{
+        expiring::{ExpiringMap, Sweep, SweepStats},
+        MemoryConsumer,
+    },
+    middleware::{
+        auth_audit::{self, AuthFailureReason},
+        introspection,
+        request_signing::{self, SIGNATURE_HEADER},
+        timing::{RequestTiming, Stage},
+    },
+    routes::error::{self, ApiError},
+    tls::client_cert::ClientCertIdentity, AppState,
+};
+
+const MAX_CACHED_TOKENS: usize = 10_000;
+
+/// JWT claims the gateway understands. Validated claims are stored in the
+/// request extensions for downstream layers, and in the response extensions
+/// for outer ones such as access logging.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+    /// Space-separated OAuth scopes, e.g. `docs:read`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+impl Claims {
+    /// Whether a granted scope covers `scope`; a granted scope ending in
+    /// `*` covers everything with that prefix.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope.as_deref().is_some_and(|scopes| {
+            scopes.split_whitespace().any(|granted| match granted.strip_suffix('*') {
+                Some(prefix) => scope.starts_with(prefix),
+                None => granted == scope,
+            })
+        })
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|granted| granted == role)
+    }
+}
+
+/// An `aud` claim, which may be a single string or a list.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(one) => one == audience,
+            Audience::Many(many) => many.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+/// A registered claim that doesn't match the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimMismatch {
+    Issuer,
+    Audience,
+}
+
+impl ClaimMismatch {
+    pub fn claim(&self) -> &'static str {
+        match self {
+            ClaimMismatch::Issuer => "iss",
+            ClaimMismatch::Audience => "aud",
+        }
+    }
+}
+
+/// Checks `iss` and `aud` against `issuer` and `audience`, skipping each
+/// when it isn't configured.
+pub fn check_claims(config: &AuthConfig, iss: Option<&str>, aud: Option<&Audience>) -> Result<(), ClaimMismatch> {
+    if config.issuer.as_deref().is_some_and(|issuer| iss != Some(issuer)) {
+        return Err(ClaimMismatch::Issuer);
+    }
+    let accepted = aud.is_some_and(|aud| config.audience.iter().any(|audience| aud.contains(audience)));
+    if !config.audience.is_empty() && !accepted {
+        return Err(ClaimMismatch::Audience);
+    }
+    Ok(())
+}
+
+/// Registered claims checked by the gateway rather than the library: times
+/// against the gateway clock, with `clock_skew_tolerance` either way, and
+/// `iss`/`aud` against the config.
+#[derive(Deserialize)]
+struct Decoded {
+    #[serde(flatten)]
+    claims: Claims,
+    nbf: Option<u64>,
+    iat: Option<u64>,
+    iss: Option<String>,
+    aud: Option<Audience>,
+}
+
+impl Decoded {
+    fn is_accepted(&self, config: &AuthConfig, now: u64, leeway: u64) -> bool {
+        self.is_current(now, leeway) && check_claims(config, self.iss.as_deref(), self.aud.as_ref()).is_ok()
+    }
+
+    fn is_current(&self, now: u64, leeway: u64) -> bool {
+        let not_expired = self.claims.exp.saturating_add(leeway) >= now;
+        let started = self.nbf.is_none_or(|nbf| nbf <= now.saturating_add(leeway));
+        let issued = self.iat.is_none_or(|iat| iat <= now.saturating_add(leeway));
+        not_expired && started && issued
+    }
+}
+
+#[derive(Clone)]
+struct CachedToken {
+    /// Fingerprint of the secret that validated the token.
+    secret_id: [u8; 32],
+    claims: Claims,
+    /// Rechecked on every hit, in case `issuer` or `audience` changed.
+    iss: Option<String>,
+    aud: Option<Audience>,
+}
+
+/// An introspection endpoint's answer about an opaque token; `None` when
+/// the token was inactive.
+#[derive(Clone)]
+struct Introspected {
+    /// Fingerprint of the endpoint and credentials that answered.
+    endpoint_id: [u8; 32],
+    token: Option<IntrospectedToken>,
+}
+
+/// An active opaque token as introspection described it.
+#[derive(Debug, Clone)]
+pub struct IntrospectedToken {
+    pub claims: Claims,
+    /// Rechecked on every use, in case `issuer` or `audience` changed.
+    pub iss: Option<String>,
+    pub aud: Option<Audience>,
+}
+
+impl IntrospectedToken {
+    /// The token's claims, if its `iss` and `aud` match the config.
+    pub fn accepted(self, config: &AuthConfig) -> Result<Claims, ClaimMismatch> {
+        check_claims(config, self.iss.as_deref(), self.aud.as_ref()).map(|()| self.claims)
+    }
+}
+
+/// Cache of already-validated tokens, keyed by token hash.
+///
+/// Each entry remembers which secret validated it so that removing a secret
+/// from the config invalidates its entries on the very next request. Entries
+/// expire with their token. Request signatures already accepted are kept
+/// alongside until their timestamp falls outside the allowed skew, and
+/// introspection answers for their configured TTLs.
+pub struct AuthCache {
+    entries: ExpiringMap<[u8; 32], CachedToken>,
+    signatures: ExpiringMap<[u8; 32], bool>,
+    introspected: ExpiringMap<[u8; 32], Introspected>,
+}
+
+/// Rough size of one cache entry: both hashes, the claims, and map overhead.
+fn entry_bytes(_: &[u8; 32], cached: &CachedToken) -> u64 {
+    let iss = cached.iss.as_ref().map_or(0, String::len);
+    (64 + std::mem::size_of::<CachedToken>() + cached.claims.sub.len() + iss + 32) as u64
+}
+
+impl Default for AuthCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthCache {
+    pub fn new() -> Self {
+        Self {
+            entries: ExpiringMap::new("auth_cache", MAX_CACHED_TOKENS).with_weigher(entry_bytes),
+            signatures: ExpiringMap::new("signature_replays", MAX_CACHED_TOKENS),
+            introspected: ExpiringMap::new("introspected_tokens", MAX_CACHED_TOKENS),
+        }
+    }
+
+    /// The cached answer about an opaque token, `Some(None)` when it was
+    /// inactive. Answers from another endpoint or client don't count.
+    pub fn introspected(&self, token_id: &[u8; 32], endpoint_id: &[u8; 32]) -> Option<Option<IntrospectedToken>> {
+        self.introspected
+            .read(token_id, Introspected::clone)
+            .filter(|cached| cached.endpoint_id == *endpoint_id)
+            .map(|cached| cached.token)
+    }
+
+    pub fn remember_introspection(
+        &self,
+        token_id: [u8; 32],
+        endpoint_id: [u8; 32],
+        token: Option<IntrospectedToken>,
+        ttl: Duration,
+    ) {
+        self.introspected.insert(token_id, Introspected { endpoint_id, token }, ttl);
+    }
+
+    /// Records a request signature, returning whether it is new.
+    pub fn remember_signature(&self, signature_id: [u8; 32], ttl: Duration) -> bool {
+        !self.signatures.upsert(&signature_id, ttl, || false, |seen| std::mem::replace(seen, true))
+    }
+
+    /// Cached claims and the index of the secret that verified them.
+    fn get(
+        &self,
+        config: &AuthConfig,
+        token_id: &[u8; 32],
+        secret_ids: &[[u8; 32]],
+        now: u64,
+        leeway: u64,
+    ) -> Option<(Claims, usize)> {
+        let cached = self.entries.read(token_id, CachedToken::clone)?;
+        let index = secret_ids.iter().position(|secret_id| *secret_id == cached.secret_id);
+        let current = cached.claims.exp.saturating_add(leeway) >= now
+            && check_claims(config, cached.iss.as_deref(), cached.aud.as_ref()).is_ok();
+
+        match index {
+            Some(index) if current => Some((cached.claims, index)),
+            _ => {
+                self.entries.remove(token_id);
+                None
+            }
+        }
+    }
+
+    fn insert(&self, token_id: [u8; 32], secret_id: [u8; 32], decoded: &Decoded, now: u64, leeway: u64) {
+        let claims = decoded.claims.clone();
+        let ttl = Duration::from_secs(claims.exp.saturating_add(leeway).saturating_sub(now) + 1);
+        let (iss, aud) = (decoded.iss.clone(), decoded.aud.clone());
+        self.entries.insert(token_id, CachedToken { secret_id, claims, iss, aud }, ttl);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Sweep for AuthCache {
+    fn sweep(&self, budget: usize) -> SweepStats {
+        [self.entries.sweep(budget), self.signatures.sweep(budget), self.introspected.sweep(budget)]
+            .into_iter()
+            .fold(SweepStats::default(), |total, stats| SweepStats {
+                examined: total.examined + stats.examined,
+                expired: total.expired + stats.expired,
+                took: total.took + stats.took,
+            })
+    }
+}
+
+impl MemoryConsumer for AuthCache {
+    fn memory_usage(&self) -> u64 {
+        self.entries.memory_usage() + self.signatures.memory_usage() + self.introspected.memory_usage()
+    }
+
+    /// Drops the tokens closest to expiry first.
+    fn evict(&self, bytes: u64) -> u64 {
+        self.entries.evict_bytes(bytes)
+    }
+}
+
+pub(crate) fn fingerprint(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Signs claims with the primary secret. Secondary secrets are only ever
+/// used for validation.
+pub fn issue_token(config: &AuthConfig, claims: &Claims) -> Result<String> {
+    let secret = config
+        .primary_secret()
+        .ok_or_else(|| anyhow!("no JWT secret configured"))?;
+
+    Ok(encode(
+        &Header::new(Algorithm::HS256),
+        claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
+
+/// Signature-only validation; [`Decoded`] checks the claims.
+fn claims_unchecked(algorithm: Algorithm) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.validate_exp = false;
+    validation.validate_nbf = false;
+    validation.validate_aud = false;
+    validation
+}
+
+/// Validates a token against each configured secret in order, consulting the
+/// cache first.
+pub fn validate_token(cache: &AuthCache, config: &AuthConfig, token: &str) -> Option<Claims> {
+    validate_token_at(cache, config, token, now_secs())
+}
+
+/// [`validate_token`] as of `now` (Unix seconds). `exp`, `nbf` and `iat`
+/// may each be off by up to `clock_skew_tolerance`; `iss` and `aud` must
+/// match `issuer` and `audience` when those are set.
+pub fn validate_token_at(cache: &AuthCache, config: &AuthConfig, token: &str, now: u64) -> Option<Claims> {
+    let secrets = config.secrets();
+    let secret_ids: Vec<[u8; 32]> = secrets.iter().map(|secret| fingerprint(secret)).collect();
+    let token_id = fingerprint(token);
+    let leeway = config.clock_skew_tolerance.get().as_secs();
+
+    if let Some((claims, index)) = cache.get(config, &token_id, &secret_ids, now, leeway) {
+        crate::metrics::record_jwt_secret_use(index);
+        return Some(claims);
+    }
+
+    let validation = claims_unchecked(Algorithm::HS256);
+    for (index, (secret, secret_id)) in secrets.iter().zip(secret_ids).enumerate() {
+        if let Ok(data) = decode::<Decoded>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation) {
+            if !data.claims.is_accepted(config, now, leeway) {
+                return None;
+            }
+            crate::metrics::record_jwt_secret_use(index);
+            cache.insert(token_id, secret_id, &data.claims, now, leeway);
+            return Some(data.claims.claims);
+        }
+    }
+
+    None
+}
+
+/// The `kid` of a token to verify against the JWKS: one signed with an
+/// asymmetric algorithm while `jwks_url` is set. Other tokens are checked
+/// against the shared secrets.
+pub fn jwks_key_id(config: &AuthConfig, token: &str) -> Option<String> {
+    config.jwks_url.as_ref()?;
+    let header = decode_header(token).ok()?;
+    let asymmetric = !matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512);
+    header.kid.filter(|_| asymmetric)
+}
+
+/// Validates a token signed with the key in `keys` its `kid` names, as of
+/// `now` (Unix seconds). The key's own `alg`, when it has one, must match
+/// the token's.
+pub fn validate_jwks_token_at(cache: &AuthCache, config: &AuthConfig, keys: &JwkSet, token: &str, now: u64) -> Option<Claims> {
+    let header = decode_header(token).ok()?;
+    let jwk = keys.find(header.kid.as_deref()?)?;
+    if let Some(key_algorithm) = jwk.common.key_algorithm {
+        if key_algorithm.to_string().parse::<Algorithm>().ok() != Some(header.alg) {
+            return None;
+        }
+    }
+
+    // A key replaced under the same kid invalidates tokens cached with it
+    let key_id = fingerprint(&serde_json::to_string(jwk).ok()?);
+    let token_id = fingerprint(token);
+    let leeway = config.clock_skew_tolerance.get().as_secs();
+    if let Some((claims, _)) = cache.get(config, &token_id, &[key_id], now, leeway) {
+        return Some(claims);
+    }
+
+    let validation = claims_unchecked(header.alg);
+    let data = decode::<Decoded>(token, &DecodingKey::from_jwk(jwk).ok()?, &validation).ok()?;
+    if !data.claims.is_accepted(config, now, leeway) {
+        return None;
+    }
+    cache.insert(token_id, key_id, &data.claims, now, leeway);
+    Some(data.claims.claims)
+}
+
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Claims for a request authenticated by its verified client certificate,
+/// when `client_certificates` allows it. The subject DN is the principal.
+fn client_certificate_claims(extensions: &Extensions, config: &AuthConfig) -> Option<Claims> {
+    if !config.client_certificates {
+        return None;
+    }
+    let identity = extensions.get::<Arc<ClientCertIdentity>>()?;
+    Some(Claims {
+        sub: identity.subject.clone(),
+        exp: identity.not_after,
+        scope: None,
+        roles: Vec::new(),
+    })
+}
+
+/// How a request was authenticated; stored in the request extensions next
+/// to its claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Jwt,
+    Jwks,
+    Introspection,
+    ClientCertificate,
+    Signature,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Jwt => "jwt",
+            AuthMethod::Jwks => "jwks",
+            AuthMethod::Introspection => "introspection",
+            AuthMethod::ClientCertificate => "client_certificate",
+            AuthMethod::Signature => "signature",
+        }
+    }
+}
+
+/// Claims of the request's bearer token or, without one, of its client
+/// certificate; `None` when neither authenticates it.
+pub async fn authenticate(
+    state: &AppState,
+    auth: &AuthConfig,
+    headers: &HeaderMap,
+    extensions: &Extensions,
+) -> Option<Claims> {
+    authenticate_with_method(state, auth, headers, extensions).await.map(|(claims, _)| claims)
+}
+
+/// [`authenticate`], also saying how the claims were verified.
+pub async fn authenticate_with_method(
+    state: &AppState,
+    auth: &AuthConfig,
+    headers: &HeaderMap,
+    extensions: &Extensions,
+) -> Option<(Claims, AuthMethod)> {
+    let Some(token) = bearer_token(headers) else {
+        return client_certificate_claims(extensions, auth).map(|claims| (claims, AuthMethod::ClientCertificate));
+    };
+    let now = u64::try_from(state.clock.now().timestamp()).unwrap_or(0);
+    match (jwks_key_id(auth, token), &auth.jwks_url) {
+        (Some(kid), Some(url)) => {
+            let keys = state.jwks.keys_for(&state.upstreams, url, &kid).await;
+            validate_jwks_token_at(&state.auth_cache, auth, &keys, token, now).map(|claims| (claims, AuthMethod::Jwks))
+        }
+        _ => match &auth.introspection {
+            Some(introspection) if decode_header(token).is_err() => {
+                introspection::validate_at(&state.auth_cache, &state.upstreams, auth, introspection, token, now)
+                    .await
+                    .map(|claims| (claims, AuthMethod::Introspection))
+            }
+            _ => validate_token_at(&state.auth_cache, auth, token, now).map(|claims| (claims, AuthMethod::Jwt)),
+        },
+    }
+}
+
+/// 401 for a request without acceptable credentials. Only a claim mismatch
+/// is explained; other failures just say the credentials weren't accepted.
+fn rejection(reason: AuthFailureReason, request_id: String) -> ApiError {
+    let error = match reason.claim_mismatch() {
+        // Name the claim so the caller can fix its token; the value isn't echoed
+        Some(mismatch) => ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_token",
+            format!("The token's {} claim is not accepted", mismatch.claim()),
+        )
+        .with_detail("claim", mismatch.claim()),
+        None if reason == AuthFailureReason::MissingCredentials => ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "authentication_required",
+            "This route needs a bearer token, request signature or client certificate",
+        ),
+        None => ApiError::new(StatusCode::UNAUTHORIZED, "invalid_token", "The credentials were not accepted"),
+    };
+    error.with_request_id(request_id)
+}
+
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let config = state.config_watcher.get_config().await;
+    let auth = &config.middleware.auth;
+
+    if !auth.enabled || auth.is_exempt(request.uri().path()) {
+        return Ok(next.run(request).await);
+    }
+
+    let started = Instant::now();
+    let signing = &auth.request_signing;
+    let claims = if !signing.keys.is_empty() && request.headers().contains_key(SIGNATURE_HEADER) {
+        let now = u64::try_from(state.clock.now().timestamp()).unwrap_or(0);
+        let request_id = error::request_id(request.extensions());
+        let (verified, claims) = request_signing::verify_request(&state.auth_cache, signing, request, now)
+            .await
+            .map_err(|error| error.with_request_id(request_id))?;
+        request = verified;
+        claims.map(|claims| (claims, AuthMethod::Signature))
+    } else {
+        authenticate_with_method(&state, auth, request.headers(), request.extensions()).await
+    };
+    if let Some(timing) = request.extensions().get::<RequestTiming>() {
+        timing.record_auth(started.elapsed());
+        if claims.is_some() {
+            timing.enter(Stage::Authenticated);
+        }
+    }
+    state.memory_budget.enforce();
+    let Some((claims, method)) = claims else {
+        let now = u64::try_from(state.clock.now().timestamp()).unwrap_or(0);
+        let (reason, subject) = auth_audit::diagnose(&state, auth, request.headers(), now);
+        state.auth_failures.record(&state, &config, &request, reason, subject.as_deref());
+        return Err(rejection(reason, error::request_id(request.extensions())));
+    };
 
-#[allow(dead_code)]
-pub async fn auth_middleware(request: Request, next: Next) -> Result<Response, StatusCode> {
-    // TODO: Implement JWT validation
-    // For now, pass through all requests
-    Ok(next.run(request).await)
-}
\ No newline at end of file
+    request.extensions_mut().insert(claims.clone());
+    request.extensions_mut().insert(method);
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(claims);
+    Ok(response)
+}
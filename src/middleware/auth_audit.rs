@@ -0,0 +1,223 @@
+//! Audit trail of requests turned away by authentication or authorization.
+//!
+//! Each denial is logged as an `audit = true` event, counted in
+//! `gateway_auth_failures_total{reason}` and kept in a ring buffer of the
+//! last `middleware.auth.failure_log_size` served at
+//! `GET /admin/auth/failures`. Why a token failed is worked out only after
+//! it has been rejected, by reading its header and claims without checking
+//! the signature; the token itself is never logged or stored, and its
+//! `sub` is pseudonymized like every other user ID.
+
+use axum::{extract::Request, http::HeaderMap};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Mutex};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{AppConfig, AuthConfig},
+    middleware::{
+        auth::{bearer_token, check_claims, Audience, ClaimMismatch},
+        introspection,
+        rate_limit::client_ip,
+        request_signing::SIGNATURE_HEADER,
+    },
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthFailureReason {
+    /// No bearer token, signature or client certificate.
+    MissingCredentials,
+    /// Neither a JWT nor an opaque token introspection could vouch for.
+    MalformedToken,
+    BadSignature,
+    Expired,
+    /// `nbf` or `iat` still in the future.
+    NotYetValid,
+    /// `iss` other than the configured issuer.
+    WrongIssuer,
+    /// `aud` naming none of the configured audiences.
+    WrongAudience,
+    /// A `kid` the JWKS doesn't have.
+    UnknownKey,
+    /// An opaque token introspection reported inactive or couldn't check.
+    InactiveToken,
+    BadRequestSignature,
+    /// Authenticated, but without a role or scope the route accepts.
+    InsufficientScope,
+}
+
+impl AuthFailureReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthFailureReason::MissingCredentials => "missing_credentials",
+            AuthFailureReason::MalformedToken => "malformed_token",
+            AuthFailureReason::BadSignature => "bad_signature",
+            AuthFailureReason::Expired => "expired",
+            AuthFailureReason::NotYetValid => "not_yet_valid",
+            AuthFailureReason::WrongIssuer => "wrong_issuer",
+            AuthFailureReason::WrongAudience => "wrong_audience",
+            AuthFailureReason::UnknownKey => "unknown_key",
+            AuthFailureReason::InactiveToken => "inactive_token",
+            AuthFailureReason::BadRequestSignature => "bad_request_signature",
+            AuthFailureReason::InsufficientScope => "insufficient_scope",
+        }
+    }
+
+    /// The registered claim at fault, for failures the caller is told about.
+    pub fn claim_mismatch(&self) -> Option<ClaimMismatch> {
+        match self {
+            AuthFailureReason::WrongIssuer => Some(ClaimMismatch::Issuer),
+            AuthFailureReason::WrongAudience => Some(ClaimMismatch::Audience),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClaimMismatch> for AuthFailureReason {
+    fn from(mismatch: ClaimMismatch) -> Self {
+        match mismatch {
+            ClaimMismatch::Issuer => AuthFailureReason::WrongIssuer,
+            ClaimMismatch::Audience => AuthFailureReason::WrongAudience,
+        }
+    }
+}
+
+/// One denied request.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuthFailure {
+    pub at: String,
+    pub method: String,
+    pub path: String,
+    pub reason: AuthFailureReason,
+    /// The client address, as the rate limiter sees it.
+    pub client_ip: Option<String>,
+    /// Pseudonymized `sub` of the token, when it could be read.
+    pub subject: Option<String>,
+}
+
+/// Registered claims read from a token that failed validation.
+#[derive(Deserialize)]
+struct Unverified {
+    sub: Option<String>,
+    exp: Option<u64>,
+    nbf: Option<u64>,
+    iat: Option<u64>,
+    iss: Option<String>,
+    aud: Option<Audience>,
+}
+
+fn read_unverified(token: &str, alg: Algorithm) -> Option<Unverified> {
+    let mut validation = Validation::new(alg);
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims.clear();
+    decode::<Unverified>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()
+        .map(|data| data.claims)
+}
+
+/// Why authentication rejected a request with `headers`, as of `now` (Unix
+/// seconds), and the token's unverified `sub`.
+pub fn diagnose(state: &AppState, auth: &AuthConfig, headers: &HeaderMap, now: u64) -> (AuthFailureReason, Option<String>) {
+    if !auth.request_signing.keys.is_empty() && headers.contains_key(SIGNATURE_HEADER) {
+        return (AuthFailureReason::BadRequestSignature, None);
+    }
+    let Some(token) = bearer_token(headers) else {
+        return (AuthFailureReason::MissingCredentials, None);
+    };
+    let Ok(header) = decode_header(token) else {
+        return match &auth.introspection {
+            Some(introspection) => match introspection::mismatched_claim(&state.auth_cache, auth, introspection, token) {
+                Some((mismatch, sub)) => (AuthFailureReason::from(mismatch), Some(sub)),
+                None => (AuthFailureReason::InactiveToken, None),
+            },
+            None => (AuthFailureReason::MalformedToken, None),
+        };
+    };
+    let Some(claims) = read_unverified(token, header.alg) else {
+        return (AuthFailureReason::MalformedToken, None);
+    };
+
+    let leeway = auth.clock_skew_tolerance.get().as_secs();
+    let reason = if claims.exp.is_some_and(|exp| exp.saturating_add(leeway) < now) {
+        AuthFailureReason::Expired
+    } else if [claims.nbf, claims.iat].into_iter().flatten().any(|at| at > now.saturating_add(leeway)) {
+        AuthFailureReason::NotYetValid
+    } else if let Err(mismatch) = check_claims(auth, claims.iss.as_deref(), claims.aud.as_ref()) {
+        AuthFailureReason::from(mismatch)
+    } else {
+        match (crate::middleware::auth::jwks_key_id(auth, token), &auth.jwks_url) {
+            (Some(kid), Some(url)) if state.jwks.keys(url).find(&kid).is_none() => AuthFailureReason::UnknownKey,
+            _ => AuthFailureReason::BadSignature,
+        }
+    };
+    (reason, claims.sub)
+}
+
+/// The most recent denials.
+#[derive(Default)]
+pub struct AuthFailureLog {
+    entries: Mutex<VecDeque<AuthFailure>>,
+}
+
+impl AuthFailureLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Logs and counts a denial of `request` and keeps it among the last
+    /// `auth.failure_log_size`. `subject` is the raw `sub`; only its pseudonym
+    /// is kept.
+    pub fn record(
+        &self,
+        state: &AppState,
+        config: &AppConfig,
+        request: &Request,
+        reason: AuthFailureReason,
+        subject: Option<&str>,
+    ) {
+        let (method, path) = (request.method(), request.uri().path());
+        let capacity = config.middleware.auth.failure_log_size;
+        let trusted_proxies = &config.middleware.rate_limiting.trusted_proxies;
+        let client_ip = client_ip(request.headers(), request.extensions(), trusted_proxies);
+        let subject = subject.map(|sub| state.pseudonymizer.pseudonymize(sub));
+        info!(
+            audit = true,
+            method = %method,
+            path = path,
+            reason = reason.as_str(),
+            client_ip = client_ip.as_deref(),
+            subject = subject.as_deref(),
+            "Request denied by auth"
+        );
+        crate::metrics::record_auth_failure(reason.as_str());
+
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        entries.push_back(AuthFailure {
+            at: chrono::Utc::now().to_rfc3339(),
+            method: method.to_string(),
+            path: path.to_string(),
+            reason,
+            client_ip,
+            subject,
+        });
+        while entries.len() > capacity {
+            entries.pop_front();
+        }
+    }
+
+    /// Newest first.
+    pub fn recent(&self) -> Vec<AuthFailure> {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    config::RouteAuthorization,
+    middleware::{auth::Claims, auth_audit::AuthFailureReason},
+    routes::error::ApiError,
+    AppState,
+};
+
+/// Whether `claims` hold one of the roles or scopes `required` accepts.
+pub fn is_authorized(required: &RouteAuthorization, claims: &Claims) -> bool {
+    required.is_empty()
+        || required.roles.iter().any(|role| claims.has_role(role))
+        || required.scopes.iter().any(|scope| claims.has_scope(scope))
+}
+
+/// 401 or 403 naming what the route accepts.
+fn denied(status: StatusCode, code: &'static str, message: &str, required: &RouteAuthorization) -> ApiError {
+    ApiError::new(status, code, message)
+        .with_detail("required_roles", required.roles.clone())
+        .with_detail("required_scopes", required.scopes.clone())
+}
+
+/// Applies the matched route's `authorization` to the claims left by the
+/// auth middleware. A route needing claims is refused to requests without
+/// any, such as those to an exempt path.
+pub async fn authorization_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    let Some(required) = config
+        .match_route(request.method().as_str(), route)
+        .and_then(|route| route.authorization.as_ref())
+        .filter(|required| !required.is_empty())
+    else {
+        return next.run(request).await;
+    };
+
+    let rejection = match request.extensions().get::<Claims>() {
+        None => Some((StatusCode::UNAUTHORIZED, "authentication_required", "This route needs an authenticated caller")),
+        Some(claims) if !is_authorized(required, claims) => Some((
+            StatusCode::FORBIDDEN,
+            "insufficient_scope",
+            "The caller holds none of the roles or scopes this route accepts",
+        )),
+        Some(_) => None,
+    };
+    if let Some((status, code, message)) = rejection {
+        crate::metrics::record_authorization_denial(route, code);
+        let (reason, subject) = match request.extensions().get::<Claims>() {
+            Some(claims) => (AuthFailureReason::InsufficientScope, Some(claims.sub.as_str())),
+            None => (AuthFailureReason::MissingCredentials, None),
+        };
+        state.auth_failures.record(&state, &config, &request, reason, subject);
+        return denied(status, code, message, required)
+            .for_request(request.extensions())
+            .into_response();
+    }
+    next.run(request).await
+}
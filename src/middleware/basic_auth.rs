@@ -0,0 +1,93 @@
+//! HTTP basic auth for the docs, the OpenAPI spec and the metrics, kept
+//! apart from JWT auth so scrapers and readers need no token.
+
+use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use tracing::debug;
+
+use crate::{
+    config::{AdminBasicAuthConfig, AppConfig},
+    AppState,
+};
+
+/// Whether `hash` is a bcrypt or argon2 hash this module can verify.
+pub fn is_supported_hash(hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        return PasswordHash::new(hash).is_ok();
+    }
+    ["$2a$", "$2b$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) && hash.parse::<bcrypt::HashParts>().is_ok()
+}
+
+/// Whether `password` matches `hash`; unreadable hashes match nothing.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        PasswordHash::new(hash).is_ok_and(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+    } else {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    }
+}
+
+/// Whether `path` is one of the protected endpoints, with or without the
+/// base path.
+pub fn is_protected(config: &AppConfig, path: &str) -> bool {
+    let base_path = config.server.base_path();
+    let path = path.strip_prefix(base_path.as_str()).filter(|rest| rest.starts_with('/')).unwrap_or(path);
+    path == "/metrics"
+        || path == config.metrics.path
+        || path == "/docs"
+        || path.starts_with("/docs/")
+        || path == "/api-docs/openapi.json"
+}
+
+/// Username and password from a `Basic` authorization header.
+fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    let (username, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+fn challenge(config: &AdminBasicAuthConfig) -> Response {
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    if let Ok(value) = HeaderValue::from_str(&format!("Basic realm=\"{}\", charset=\"UTF-8\"", config.realm)) {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+    }
+    response
+}
+
+/// Asks for the `admin_basic_auth` credentials on the docs and metrics
+/// endpoints; passes everything through when the section is absent.
+pub async fn basic_auth_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let Some(basic) = config.admin_basic_auth.as_ref() else {
+        return next.run(request).await;
+    };
+    if !is_protected(&config, request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let Some((username, password)) = credentials(request.headers()) else {
+        return challenge(basic);
+    };
+    // Hashing is slow by design, so keep it off the runtime threads
+    let hash = basic.password_hash.clone();
+    let matches = username == basic.username
+        && tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+            .await
+            .unwrap_or(false);
+    if !matches {
+        debug!(path = request.uri().path(), "Rejected admin basic auth credentials");
+        return challenge(basic);
+    }
+    next.run(request).await
+}
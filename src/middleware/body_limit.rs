@@ -0,0 +1,162 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+};
+use tracing::warn;
+
+use crate::{
+    config::AppConfig,
+    middleware::recording::route_label,
+    routes::error::{self, ApiError},
+    AppState,
+};
+
+/// The error a [`LimitedBody`] ends with once it grows past its limit.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyTooLarge {
+    pub limit: u64,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body is larger than {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+pin_project! {
+    /// Body wrapper that passes a request body through until it grows past
+    /// `limit` bytes, then fails with [`BodyTooLarge`] and raises `exceeded`,
+    /// so whatever was reading it stops there.
+    pub struct LimitedBody<B> {
+        #[pin]
+        inner: B,
+        remaining: u64,
+        limit: u64,
+        exceeded: Arc<AtomicBool>,
+    }
+}
+
+impl<B> LimitedBody<B> {
+    pub fn new(inner: B, limit: u64, exceeded: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            limit,
+            exceeded,
+        }
+    }
+}
+
+impl<B> http_body::Body for LimitedBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = match ready!(this.inner.poll_frame(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return Poll::Ready(Some(Err(axum::Error::new(e)))),
+            None => return Poll::Ready(None),
+        };
+        if let Some(data) = frame.data_ref() {
+            let len = data.len() as u64;
+            if len > *this.remaining {
+                this.exceeded.store(true, Ordering::Relaxed);
+                return Poll::Ready(Some(Err(axum::Error::new(BodyTooLarge { limit: *this.limit }))));
+            }
+            *this.remaining -= len;
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// The body limit for a request to `route`: the route's own
+/// `max_body_bytes` wins over the server's.
+pub fn limit_for(config: &AppConfig, method: &str, route: &str) -> u64 {
+    config
+        .match_route(method, route)
+        .and_then(|route| route.max_body_bytes)
+        .unwrap_or(config.server.max_body_bytes)
+        .bytes()
+}
+
+fn rejection(request_id: String, limit: u64) -> Response {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "payload_too_large",
+        format!("The request body is larger than the {} byte limit", limit),
+    )
+    .with_detail("limit_bytes", limit)
+    .with_request_id(request_id)
+    .into_response()
+}
+
+/// Answers 413 to requests whose body is over the limit, before any other
+/// layer buffers, mirrors or forwards it. A declared `Content-Length` over
+/// the limit is rejected without reading the body; a body that grows past
+/// it while streaming is cut off there, and whatever the inner layers
+/// answered is replaced with the 413.
+pub async fn body_limit_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let path = request.uri().path().to_string();
+    let matched = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str().to_string());
+    let limit = limit_for(&config, request.method().as_str(), matched.as_deref().unwrap_or(&path));
+    let route = route_label(&request);
+    let request_id = error::request_id(request.extensions());
+
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if let Some(declared) = declared.filter(|declared| *declared > limit) {
+        crate::metrics::record_body_limit_rejection(&route, "content_length");
+        warn!(path, declared, limit, "Rejected request with a body over the limit");
+        return rejection(request_id, limit);
+    }
+
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let (parts, body) = request.into_parts();
+    let request = Request::from_parts(parts, Body::new(LimitedBody::new(body, limit, exceeded.clone())));
+
+    let response = next.run(request).await;
+    if !exceeded.load(Ordering::Relaxed) {
+        return response;
+    }
+    crate::metrics::record_body_limit_rejection(&route, "stream");
+    warn!(path, limit, "Cut off a request body that grew past the limit");
+    rejection(request_id, limit)
+}
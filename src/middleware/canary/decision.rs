@@ -0,0 +1,139 @@
+use super::Backend;
+use crate::{
+    config::{CanaryRolloutConfig, StickyKey},
+    middleware::{csrf::cookie, rate_limit::client_ip},
+    util::hash::unit_interval,
+};
+
+/// Request attributes the routing decision depends on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestAttributes<'a> {
+    /// Value of the configured trigger header, if present and valid UTF-8.
+    pub trigger_header: Option<&'a str>,
+    /// Where the request's sticky key hashes to in `[0, 1)`, when a sticky
+    /// key is configured and the request carries it.
+    pub sticky_sample: Option<f64>,
+    /// Backend named by the sticky cookie, when it's configured, the
+    /// request carries a valid one and no route override covers the request.
+    pub sticky_cookie: Option<Backend>,
+}
+
+impl<'a> RequestAttributes<'a> {
+    /// `trusted_proxies` resolve the client address for a `client_ip`
+    /// sticky key.
+    pub fn from_request<B>(
+        request: &'a axum::http::Request<B>,
+        config: &CanaryRolloutConfig,
+        trusted_proxies: &[String],
+    ) -> Self {
+        let headers = request.headers();
+        let sticky_value = config.sticky_key.as_ref().and_then(|key| match key {
+            StickyKey::ClientIp => client_ip(headers, request.extensions(), trusted_proxies),
+            StickyKey::Header(name) => headers.get(name.as_str()).and_then(|value| value.to_str().ok()).map(str::to_string),
+            StickyKey::Cookie(name) => cookie(headers, name).map(str::to_string),
+        });
+        Self {
+            trigger_header: headers
+                .get(config.trigger_header.as_str())
+                .and_then(|value| value.to_str().ok()),
+            sticky_sample: sticky_value
+                .filter(|value| !value.is_empty())
+                .map(|value| sticky_sample(&config.sticky_seed, &value)),
+            // The cookie holds one assignment for all routes, which a route
+            // override's own percentage would be bypassed by
+            sticky_cookie: config
+                .sticky_cookie
+                .as_ref()
+                .filter(|_| config.route_override(request.method().as_str(), request.uri().path()).is_none())
+                .and_then(|sticky| cookie(headers, &sticky.name))
+                .and_then(|value| match value {
+                    "rust" => Some(Backend::Rust),
+                    "legacy" => Some(Backend::Legacy),
+                    _ => None,
+                }),
+        }
+    }
+}
+
+/// Where `key` falls in `[0, 1)` under `seed`. The same on every replica
+/// and across restarts, so a key keeps its backend, and a key on Rust at
+/// one percentage stays there at any higher one.
+pub fn sticky_sample(seed: &str, key: &str) -> f64 {
+    unit_interval(&[seed, key])
+}
+
+/// Where a request goes and why.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoutingDecision {
+    /// Canary routing is off: serve in-process without recording a variant.
+    Disabled,
+    /// The trigger header pinned the request to a backend.
+    HeaderOverride(Backend),
+    /// The sticky cookie carried the client's earlier assignment.
+    StickyCookie(Backend),
+    /// Sampled against the rollout percentage; `sample` is in `[0, 100)`.
+    Rollout { backend: Backend, sample: f64 },
+}
+
+impl RoutingDecision {
+    pub fn backend(&self) -> Backend {
+        match self {
+            RoutingDecision::Disabled => Backend::Rust,
+            RoutingDecision::HeaderOverride(backend) => *backend,
+            RoutingDecision::StickyCookie(backend) => *backend,
+            RoutingDecision::Rollout { backend, .. } => *backend,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RoutingDecision::Disabled => "canary_disabled",
+            RoutingDecision::HeaderOverride(_) => "header_override",
+            RoutingDecision::StickyCookie(_) => "sticky_cookie",
+            RoutingDecision::Rollout { .. } => "rollout",
+        }
+    }
+}
+
+/// Decides where a request goes. Pure and allocation-free.
+///
+/// Precedence: disabled short-circuits everything; then a trigger header of
+/// `rust` or `legacy` (case-insensitive); then the sticky cookie, unless
+/// its backend has no share of traffic left; anything else falls through
+/// to the rollout percentage, sampled at the request's sticky sample if it has
+/// one and at `random`, a uniform sample in `[0, 1)`, otherwise.
+pub fn decide(attributes: &RequestAttributes<'_>, config: &CanaryRolloutConfig, random: f64) -> RoutingDecision {
+    if !config.enabled {
+        return RoutingDecision::Disabled;
+    }
+
+    match attributes.trigger_header {
+        Some(value) if value.eq_ignore_ascii_case("rust") => {
+            return RoutingDecision::HeaderOverride(Backend::Rust);
+        }
+        Some(value) if value.eq_ignore_ascii_case("legacy") => {
+            return RoutingDecision::HeaderOverride(Backend::Legacy);
+        }
+        _ => {}
+    }
+
+    // A cookie for a backend rolled down to 0%, e.g. Rust after a rollback,
+    // is ignored so the client is assigned, and the cookie rewritten, afresh
+    match attributes.sticky_cookie {
+        Some(Backend::Rust) if config.rollout_percentage > 0.0 => {
+            return RoutingDecision::StickyCookie(Backend::Rust);
+        }
+        Some(Backend::Legacy) if config.rollout_percentage < 100.0 => {
+            return RoutingDecision::StickyCookie(Backend::Legacy);
+        }
+        _ => {}
+    }
+
+    let sample = attributes.sticky_sample.unwrap_or(random) * 100.0;
+    let backend = if sample < config.rollout_percentage {
+        Backend::Rust
+    } else {
+        Backend::Legacy
+    };
+    RoutingDecision::Rollout { backend, sample }
+}
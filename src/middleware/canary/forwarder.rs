@@ -0,0 +1,361 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::MatchedPath,
+    http::{HeaderMap, HeaderName, Request, Response, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::{sync::Arc, time::Instant};
+use tokio::time::timeout_at;
+use tracing::{error, info, warn};
+
+use super::{decision::RoutingDecision, Backend};
+use crate::{
+    config::AppConfig,
+    coordination::RequestGeneration,
+    middleware::{
+        body_limit,
+        header_limits::{self, HeaderLimits},
+        identity, request_id,
+        timing::{RequestTiming, Stage},
+    },
+    monitoring::UpstreamTiming,
+    privacy::redaction::Redactor,
+    routes::error::{self, ApiError},
+    tls::client_cert::{self, ClientCertIdentity},
+    upstream::{validation, Upstream},
+    AppState,
+};
+
+/// Headers that describe a single hop and must not be forwarded (RFC 7230 §6.1).
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+];
+
+pub fn is_hop_by_hop(name: &HeaderName) -> bool {
+    HOP_BY_HOP_HEADERS.contains(&name.as_str())
+}
+
+/// Copies end-to-end headers, dropping hop-by-hop ones and any header named
+/// in `Connection`.
+pub fn end_to_end_headers(headers: &HeaderMap) -> HeaderMap {
+    let connection_listed: Vec<String> = headers
+        .get_all("connection")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+
+    headers
+        .iter()
+        .filter(|(name, _)| !is_hop_by_hop(name) && !connection_listed.iter().any(|listed| listed == name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Executes a routing decision, recording per-variant metrics.
+pub async fn forward(
+    decision: RoutingDecision,
+    request: Request<Body>,
+    next: Next,
+    config: &AppConfig,
+    state: &AppState,
+    start_time: Instant,
+) -> Response<Body> {
+    match decision {
+        RoutingDecision::Disabled => next.run(request).await,
+        _ if decision.backend() == Backend::Rust => serve_in_process(request, next, state, start_time).await,
+        _ => {
+            let mut response = route_to_legacy_gateway(request, config, start_time, state).await;
+            response.extensions_mut().insert(Backend::Legacy);
+            response
+        }
+    }
+}
+
+async fn serve_in_process(
+    request: Request<Body>,
+    next: Next,
+    state: &AppState,
+    start_time: Instant,
+) -> Response<Body> {
+    let method = request.method().clone();
+    let route = request.extensions().get::<MatchedPath>().cloned();
+    let generation = request.extensions().get::<RequestGeneration>().map_or(0, |generation| generation.0);
+    if let Some(timing) = request.extensions().get::<RequestTiming>() {
+        timing.enter(Stage::Handler);
+    }
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(Backend::Rust);
+    let latency = start_time.elapsed();
+
+    // Record metrics for Rust gateway
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    let is_error = response.status().is_server_error();
+
+    state.performance_monitor.record_request("rust", latency_ms, is_error);
+    if let Some(route) = route {
+        state.latency_drift.record("rust", route.as_str(), latency_ms);
+        state.latency_heatmap.record("rust", route.as_str(), latency_ms);
+        state.scoped_rollbacks.record(method.as_str(), route.as_str(), is_error);
+    }
+
+    crate::metrics::record_gateway_request(
+        "rust",
+        response.status().as_u16(),
+        latency.as_secs_f64(),
+        generation,
+    );
+
+    response
+}
+
+async fn route_to_legacy_gateway(
+    request: Request<Body>,
+    app_config: &AppConfig,
+    start_time: Instant,
+    state: &AppState,
+) -> Response<Body> {
+    let config = &app_config.canary_rollout;
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let request_id = error::request_id(request.extensions());
+    let timing = request.extensions().get::<RequestTiming>().cloned().unwrap_or_default();
+    let generation = request.extensions().get::<RequestGeneration>().map_or(0, |generation| generation.0);
+    let matched_route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string());
+    let route_path = matched_route.clone().unwrap_or_else(|| uri.path().to_string());
+    let checked_route = app_config
+        .route(method.as_str(), &route_path)
+        .filter(|route| route.validates_responses());
+
+    // Construct legacy gateway URL
+    let legacy_url = format!("{}{}", config.legacy_gateway_url, uri.path_and_query().map(|pq| pq.as_str()).unwrap_or(""));
+
+    // Fail fast on requests the legacy gateway would reject for their headers
+    let mut forwarded_headers = end_to_end_headers(request.headers());
+    state.upstreams.strip_outbound_headers(&mut forwarded_headers);
+    client_cert::apply(
+        &mut forwarded_headers,
+        request.extensions().get::<Arc<ClientCertIdentity>>().map(Arc::as_ref),
+        config.client_cert_forwarding,
+    );
+    identity::apply(&mut forwarded_headers, request.extensions(), &app_config.middleware.auth.identity_headers);
+    request_id::apply(&mut forwarded_headers, request.extensions(), &app_config.middleware.request_id);
+    if !config.legacy_header_limits.is_empty() {
+        let limits = HeaderLimits::for_upstream(&app_config.server, &config.legacy_header_limits);
+        if let Err(exceeded) = limits.check(&forwarded_headers) {
+            warn!(
+                path = uri.path(),
+                limit = exceeded.kind(),
+                detail = %exceeded.detail(),
+                "Rejected request over the legacy gateway's header limits"
+            );
+            return header_limits::rejection(&exceeded, "legacy");
+        }
+    }
+
+    // Read the body within the request's body limit. Anything larger has
+    // already been turned away with a 413 by the body limit layer, which
+    // also replaces this response if the body grows past it while read here.
+    let limit = body_limit::limit_for(app_config, method.as_str(), &route_path);
+    let body = match to_bytes(request.into_body(), limit as usize).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(method = %method, path = uri.path(), error = %e, "Failed to read a request body for the legacy gateway");
+            return ApiError::new(StatusCode::BAD_REQUEST, "unreadable_body", "The request body could not be read")
+                .with_request_id(request_id)
+                .into_response();
+        }
+    };
+
+    // Prepare request to legacy gateway, identifying ourselves as the source
+    let legacy_request = state
+        .upstreams
+        .request(Upstream::Legacy, method.clone(), &legacy_url)
+        .headers(forwarded_headers)
+        .header("X-Routed-By", "Rust-Gateway-Canary")
+        .body(body);
+
+    // Wait for a connection slot separately from the upstream's own response time
+    let queue_timeout = app_config.server.queue_timeout.get();
+    timing.enter(Stage::Queued);
+    let Some(pool_permit) = state.upstreams.acquire_timeout(&legacy_url, queue_timeout).await else {
+        timing.record_queue(queue_timeout);
+        warn!(
+            method = %method,
+            path = uri.path(),
+            queue_ms = queue_timeout.as_millis(),
+            "Request queued too long waiting for a legacy gateway connection"
+        );
+
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "queued_too_long",
+            format!("No upstream connection available within {}ms", queue_timeout.as_millis()),
+        )
+        .with_request_id(request_id)
+        .into_response();
+    };
+    timing.record_queue(pool_permit.wait);
+    timing.enter(Stage::Upstream);
+    let upstream_start = Instant::now();
+
+    // Time spent queued comes out of the upstream call's budget, which
+    // covers reading the response body as well as its headers
+    let upstream_timeout = app_config.canary_rollout.legacy_timeout(app_config.server.timeout);
+    let deadline = tokio::time::Instant::now() + timing.remaining(upstream_timeout.get());
+    let timed_out = |request_id: String| {
+        let latency = start_time.elapsed();
+        timing.record_upstream(upstream_start.elapsed());
+        error!(timeout = %upstream_timeout, "Legacy gateway request timeout");
+
+        let latency_ms = latency.as_millis() as f64;
+        state.performance_monitor.record_request("legacy", latency_ms, true);
+        crate::metrics::record_gateway_request("legacy", 504, latency.as_secs_f64(), generation);
+
+        ApiError::new(
+            StatusCode::GATEWAY_TIMEOUT,
+            "upstream_timeout",
+            format!("Legacy gateway request timed out after {}", upstream_timeout),
+        )
+        .with_request_id(request_id)
+        .into_response()
+    };
+    match timeout_at(deadline, legacy_request.send()).await {
+        Ok(Ok(legacy_response)) => {
+            let first_byte = upstream_start.elapsed();
+            timing.record_upstream_first_byte(first_byte);
+            timing.enter(Stage::UpstreamBody);
+            let status = legacy_response.status();
+            let headers = end_to_end_headers(legacy_response.headers());
+
+            // Get response body, enforcing the route's expectations if it has any
+            let read = async {
+                match checked_route {
+                    Some(route) => {
+                        let max_decoded = app_config.middleware.decompression.max_decoded_size.bytes();
+                        validation::read_validated(route, legacy_response, max_decoded).await
+                    }
+                    None => legacy_response.bytes().await.map(Ok),
+                }
+            };
+            let Ok(body) = timeout_at(deadline, read).await else {
+                return timed_out(request_id);
+            };
+            let full_body = upstream_start.elapsed();
+            let latency = start_time.elapsed();
+            timing.record_upstream(full_body);
+
+            match body {
+                Ok(Err(violation)) => {
+                    let redactor = Redactor::new(&app_config.middleware.logging);
+                    let excerpt = violation.excerpt.as_deref().map(|excerpt| redactor.body(excerpt));
+                    warn!(
+                        method = %method,
+                        path = uri.path(),
+                        route = %route_path,
+                        status = status.as_u16(),
+                        kind = violation.kind.as_str(),
+                        detail = %violation.detail,
+                        body_excerpt = excerpt.as_deref(),
+                        "Legacy gateway response violated the route contract"
+                    );
+                    crate::metrics::record_contract_violation(&route_path, "legacy", violation.kind.as_str());
+                    state.performance_monitor.record_request("legacy", latency.as_secs_f64() * 1000.0, true);
+                    crate::metrics::record_gateway_request("legacy", 502, latency.as_secs_f64(), generation);
+
+                    ApiError::new(StatusCode::BAD_GATEWAY, "upstream_contract_violation", violation.detail)
+                        .with_request_id(request_id)
+                        .into_response()
+                }
+                Ok(Ok(body_bytes)) => {
+                    // Everything outside the upstream call is gateway overhead
+                    let overhead = latency.saturating_sub(full_body);
+
+                    info!(
+                        method = %method,
+                        path = uri.path(),
+                        status = status.as_u16(),
+                        latency_ms = latency.as_millis(),
+                        queue_ms = timing.queue_time().as_millis(),
+                        upstream_first_byte_ms = first_byte.as_millis(),
+                        upstream_ms = full_body.as_millis(),
+                        overhead_ms = overhead.as_millis(),
+                        "Legacy gateway response"
+                    );
+
+                    // Record metrics for legacy gateway
+                    let latency_ms = latency.as_secs_f64() * 1000.0;
+                    let is_error = status.is_server_error();
+
+                    state.performance_monitor.record_request("legacy", latency_ms, is_error);
+                    if let Some(route) = &matched_route {
+                        state.latency_drift.record("legacy", route, latency_ms);
+                        state.latency_heatmap.record("legacy", route, latency_ms);
+                    }
+                    state.performance_monitor.record_legacy_upstream(UpstreamTiming {
+                        first_byte_ms: first_byte.as_secs_f64() * 1000.0,
+                        full_body_ms: full_body.as_secs_f64() * 1000.0,
+                        overhead_ms: overhead.as_secs_f64() * 1000.0,
+                    });
+
+                    crate::metrics::record_gateway_request(
+                        "legacy",
+                        status.as_u16(),
+                        latency.as_secs_f64(),
+                        generation,
+                    );
+                    crate::metrics::record_upstream_latency(first_byte, full_body, overhead);
+
+                    let mut response = Response::new(Body::from(body_bytes));
+                    *response.status_mut() = status;
+                    *response.headers_mut() = headers;
+                    response
+                }
+                Err(e) => {
+                    error!("Failed to read legacy gateway response body: {}", e);
+                    state.performance_monitor.record_request("legacy", latency.as_millis() as f64, true);
+                    crate::metrics::record_gateway_request("legacy", 502, latency.as_secs_f64(), generation);
+
+                    ApiError::new(
+                        StatusCode::BAD_GATEWAY,
+                        "upstream_read_failed",
+                        "Failed to read the legacy gateway's response body",
+                    )
+                    .with_request_id(request_id)
+                    .into_response()
+                }
+            }
+        }
+        Ok(Err(e)) => {
+            let latency = start_time.elapsed();
+            timing.record_upstream(upstream_start.elapsed());
+            error!("Legacy gateway request failed: {}", e);
+
+            let latency_ms = latency.as_millis() as f64;
+            state.performance_monitor.record_request("legacy", latency_ms, true);
+            crate::metrics::record_gateway_request("legacy", 502, latency.as_secs_f64(), generation);
+
+            ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                "upstream_unavailable",
+                format!("Legacy gateway request failed: {}", e),
+            )
+            .with_request_id(request_id)
+            .into_response()
+        }
+        Err(_) => timed_out(request_id),
+    }
+}
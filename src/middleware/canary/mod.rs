@@ -0,0 +1,136 @@
+pub mod decision;
+pub mod forwarder;
+pub mod simulate;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, HeaderValue, Request, Response},
+    middleware::Next,
+};
+use std::time::Instant;
+use tracing::{debug, info};
+
+use crate::{config::StickyCookieConfig, coordination::RequestGeneration, AppState};
+use decision::{RequestAttributes, RoutingDecision};
+
+pub const GENERATION_HEADER: &str = "x-rollout-generation";
+
+/// Backend that served a request, attached to the response extensions so
+/// outer layers can label their metrics by variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Rust,
+    Legacy,
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Rust => "rust",
+            Backend::Legacy => "legacy",
+        }
+    }
+}
+
+pub async fn canary_routing_middleware(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let start_time = Instant::now();
+    let mut config = state.config_watcher.get_config().await;
+    // Route on the slow-start percentage while a ramp is running, or on
+    // the percentage of a route override covering the request
+    let (method, path) = (request.method().as_str(), request.uri().path());
+    config.canary_rollout.rollout_percentage = state.slow_start.route_percentage(&config.canary_rollout, method, path);
+    let route_group = config.canary_rollout.route_group(method, path);
+    let overridden = config.canary_rollout.route_override(method, path).is_some();
+    let generation = RequestGeneration(state.coordinator.generation());
+    request.extensions_mut().insert(generation);
+
+    let attributes = RequestAttributes::from_request(
+        &request,
+        &config.canary_rollout,
+        &config.middleware.rate_limiting.trusted_proxies,
+    );
+    let assigned = decision::decide(&attributes, &config.canary_rollout, rand::random());
+    let mut decision = assigned;
+
+    // Routes whose smoke check hasn't passed take no rollout traffic, and
+    // routes the gatekeeper rolled back on their own take a reduced share.
+    // Sessions the sticky cookie keeps on Rust leave such routes entirely.
+    let held = match decision {
+        RoutingDecision::Rollout { backend: Backend::Rust, sample } => {
+            Some(RoutingDecision::Rollout { backend: Backend::Legacy, sample })
+        }
+        RoutingDecision::StickyCookie(Backend::Rust) => Some(RoutingDecision::StickyCookie(Backend::Legacy)),
+        _ => None,
+    };
+    if let Some(held) = held {
+        let method = request.method().as_str();
+        if let Some(route) = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str) {
+            let global = config.canary_rollout.rollout_percentage;
+            let scoped = state.scoped_rollbacks.percentage_for(method, route, global);
+            let rolled_back = match decision {
+                RoutingDecision::Rollout { sample, .. } => sample >= scoped,
+                _ => scoped < global,
+            };
+            if !state.smoke_gate.is_live(&config, method, route) {
+                debug!(route = route, "Route held on legacy until its smoke check passes");
+                decision = held;
+            } else if rolled_back {
+                debug!(route = route, "Route sent to legacy by a scoped rollback");
+                decision = held;
+            }
+        }
+    }
+
+    match decision {
+        RoutingDecision::HeaderOverride(backend) => {
+            info!(backend = backend.as_str(), "Header override: routing to {} gateway", backend.as_str());
+        }
+        RoutingDecision::Rollout { backend: Backend::Rust, sample } => {
+            info!(
+                rollout_percentage = config.canary_rollout.rollout_percentage,
+                random_value = sample,
+                sticky = attributes.sticky_sample.is_some(),
+                "Canary routing: using Rust gateway"
+            );
+        }
+        _ => {}
+    }
+
+    let mut response = forwarder::forward(decision, request, next, &config, &state, start_time).await;
+    if let Some(backend) = response.extensions().get::<Backend>() {
+        crate::metrics::record_route_group_request(&route_group, backend.as_str(), response.status().as_u16());
+    }
+    // A rolled assignment is recorded whatever route this request was held
+    // on, so the client's other requests follow it. One rolled on a route
+    // override's percentage isn't, as it says nothing about other routes.
+    if let (Some(sticky), RoutingDecision::Rollout { backend, .. }) = (&config.canary_rollout.sticky_cookie, assigned) {
+        if let Some(cookie) = sticky_cookie(sticky, backend).filter(|_| !overridden) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    response.extensions_mut().insert(generation);
+    if config.canary_rollout.generation_header {
+        response.headers_mut().insert(GENERATION_HEADER, HeaderValue::from(generation.0));
+    }
+    response
+}
+
+/// `Set-Cookie` value recording an assignment to `backend`.
+fn sticky_cookie(config: &StickyCookieConfig, backend: Backend) -> Option<HeaderValue> {
+    let mut cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite={}",
+        config.name,
+        backend.as_str(),
+        config.ttl.get().as_secs(),
+        config.same_site.as_str()
+    );
+    if config.secure {
+        cookie.push_str("; Secure");
+    }
+    HeaderValue::from_str(&cookie).ok()
+}
@@ -0,0 +1,280 @@
+//! Offline replay of recorded requests through the canary decision engine,
+//! to check that a rollout percentage splits real traffic as intended.
+
+use anyhow::{Context, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    io::BufRead,
+};
+
+use super::{
+    decision::{decide, sticky_sample, RequestAttributes, RoutingDecision},
+    Backend,
+};
+use crate::config::CanaryRolloutConfig;
+
+/// Routes with fewer rollout-decided requests than this are reported but
+/// never flagged; their share is too noisy to judge.
+pub const MIN_ROUTE_SAMPLES: u64 = 200;
+
+/// One recorded request.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReplayRecord {
+    #[serde(default)]
+    pub method: Option<String>,
+    pub path: String,
+    /// Route template, when the log has it; `path` otherwise.
+    #[serde(default)]
+    pub route: Option<String>,
+    /// Client key a sticky assignment would be based on.
+    #[serde(default)]
+    pub sticky_key: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl ReplayRecord {
+    fn route(&self) -> &str {
+        self.route.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// Reads a JSON-lines access log. Lines may be flat records or the
+/// gateway's own JSON logs, whose fields sit under `fields`; lines without a
+/// path (other log events) are skipped. Returns the records and how many
+/// lines were skipped.
+pub fn read_access_log(reader: impl BufRead) -> Result<(Vec<ReplayRecord>, usize)> {
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.context("reading access log")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: serde_json::Value =
+            serde_json::from_str(&line).with_context(|| format!("access log line {} is not JSON", number + 1))?;
+        if let Some(fields) = value.get_mut("fields") {
+            value = fields.take();
+        }
+        match serde_json::from_value::<ReplayRecord>(value) {
+            Ok(record) => records.push(record),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((records, skipped))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VariantCounts {
+    pub rust: u64,
+    pub legacy: u64,
+}
+
+impl VariantCounts {
+    fn add(&mut self, backend: Backend) {
+        match backend {
+            Backend::Rust => self.rust += 1,
+            Backend::Legacy => self.legacy += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.rust + self.legacy
+    }
+
+    /// Percentage of requests sent to Rust.
+    pub fn rust_percentage(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.rust as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+/// Outcome of replaying the log at one percentage.
+#[derive(Debug, Clone)]
+pub struct SimulationRun {
+    pub percentage: f64,
+    /// Every request, whatever decided it.
+    pub overall: VariantCounts,
+    /// Requests left to the rollout percentage; this is what should match
+    /// `percentage`.
+    pub rollout: VariantCounts,
+    pub routes: BTreeMap<String, VariantCounts>,
+    /// Requests pinned by the trigger header, by header value.
+    pub overrides: BTreeMap<String, VariantCounts>,
+    /// Sticky keys seen on both variants.
+    pub split_keys: usize,
+    pub sticky_keys: usize,
+    pub deviations: Vec<String>,
+}
+
+/// Replays `records` through [`decide`] at `percentage`, drawing the random
+/// input from `rng` as production draws it from the thread RNG. Canary
+/// routing is treated as enabled whatever the config says.
+pub fn simulate(
+    records: &[ReplayRecord],
+    config: &CanaryRolloutConfig,
+    percentage: f64,
+    tolerance: f64,
+    rng: &mut impl Rng,
+) -> SimulationRun {
+    let mut config = config.clone();
+    config.enabled = true;
+    config.rollout_percentage = percentage;
+
+    let mut run = SimulationRun {
+        percentage,
+        overall: VariantCounts::default(),
+        rollout: VariantCounts::default(),
+        routes: BTreeMap::new(),
+        overrides: BTreeMap::new(),
+        split_keys: 0,
+        sticky_keys: 0,
+        deviations: Vec::new(),
+    };
+    let mut key_variants: HashMap<&str, HashSet<&'static str>> = HashMap::new();
+
+    for record in records {
+        let request = request_for(record);
+        let mut attributes = RequestAttributes::from_request(&request, &config, &[]);
+        // A replayed request has no peer address, so a recorded key stands
+        // in for whatever the configured sticky key would have found
+        if config.sticky_key.is_some() && attributes.sticky_sample.is_none() {
+            attributes.sticky_sample = record.sticky_key.as_deref().map(|key| sticky_sample(&config.sticky_seed, key));
+        }
+        let decision = decide(&attributes, &config, rng.gen());
+        let backend = decision.backend();
+
+        run.overall.add(backend);
+        match decision {
+            RoutingDecision::HeaderOverride(_) => {
+                let value = attributes.trigger_header.unwrap_or_default().to_ascii_lowercase();
+                run.overrides.entry(value).or_default().add(backend);
+            }
+            _ => {
+                run.rollout.add(backend);
+                run.routes.entry(record.route().to_string()).or_default().add(backend);
+            }
+        }
+        if let Some(key) = &record.sticky_key {
+            key_variants.entry(key).or_default().insert(backend.as_str());
+        }
+    }
+
+    run.sticky_keys = key_variants.len();
+    run.split_keys = key_variants.values().filter(|variants| variants.len() > 1).count();
+
+    let mut check = |scope: &str, counts: &VariantCounts| {
+        let deviation = counts.rust_percentage() - percentage;
+        if deviation.abs() > tolerance {
+            run.deviations.push(format!(
+                "{}: {:.2}% rust over {} requests, {:+.2} points from {}%",
+                scope,
+                counts.rust_percentage(),
+                counts.total(),
+                deviation,
+                percentage
+            ));
+        }
+    };
+    if run.rollout.total() > 0 {
+        check("overall", &run.rollout);
+    }
+    for (route, counts) in &run.routes {
+        if counts.total() >= MIN_ROUTE_SAMPLES {
+            check(route, counts);
+        }
+    }
+    run
+}
+
+/// The request the middleware would have seen, as far as the decision
+/// engine looks at it.
+fn request_for(record: &ReplayRecord) -> axum::http::Request<()> {
+    let mut builder = axum::http::Request::builder()
+        .method(record.method.as_deref().unwrap_or("GET"))
+        .uri(record.path.as_str());
+    for (name, value) in &record.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    builder.body(()).unwrap_or_else(|_| {
+        // Unparseable path or header: keep the headers that do parse
+        let mut request = axum::http::Request::new(());
+        for (name, value) in &record.headers {
+            if let (Ok(name), Ok(value)) = (
+                axum::http::HeaderName::try_from(name.as_str()),
+                axum::http::HeaderValue::try_from(value.as_str()),
+            ) {
+                request.headers_mut().insert(name, value);
+            }
+        }
+        request
+    })
+}
+
+/// Replays the log once per percentage with the same seed.
+pub fn sweep(
+    records: &[ReplayRecord],
+    config: &CanaryRolloutConfig,
+    percentages: &[f64],
+    tolerance: f64,
+    seed: u64,
+) -> Vec<SimulationRun> {
+    percentages
+        .iter()
+        .map(|percentage| simulate(records, config, *percentage, tolerance, &mut StdRng::seed_from_u64(seed)))
+        .collect()
+}
+
+/// Human-readable report: a summary table across percentages, then the
+/// per-route and per-override breakdown of each run.
+pub fn render(runs: &[SimulationRun]) -> String {
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{:>8} {:>10} {:>10} {:>10} {:>10} {:>12}",
+        "TARGET", "REQUESTS", "RUST", "LEGACY", "RUST %", "SPLIT KEYS"
+    );
+    for run in runs {
+        let _ = writeln!(
+            report,
+            "{:>7}% {:>10} {:>10} {:>10} {:>9.2}% {:>12}",
+            run.percentage,
+            run.rollout.total(),
+            run.rollout.rust,
+            run.rollout.legacy,
+            run.rollout.rust_percentage(),
+            format!("{}/{}", run.split_keys, run.sticky_keys)
+        );
+    }
+
+    for run in runs {
+        let _ = writeln!(report, "\nAt {}%:", run.percentage);
+        for (route, counts) in &run.routes {
+            let _ = writeln!(
+                report,
+                "  {:<40} {:>8} requests {:>7.2}% rust",
+                route,
+                counts.total(),
+                counts.rust_percentage()
+            );
+        }
+        for (value, counts) in &run.overrides {
+            let _ = writeln!(
+                report,
+                "  override {:<31} {:>8} requests {:>7.2}% rust",
+                format!("{:?}", value),
+                counts.total(),
+                counts.rust_percentage()
+            );
+        }
+        for deviation in &run.deviations {
+            let _ = writeln!(report, "  DEVIATION {}", deviation);
+        }
+    }
+    report
+}
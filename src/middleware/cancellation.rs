@@ -0,0 +1,100 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tracing::{error, info};
+
+use crate::{middleware::timing::RequestTiming, AppState};
+
+/// Whether the client is still waiting for the response, for layers whose
+/// work only matters while it is (such as queueing a mirror request).
+#[derive(Clone, Debug, Default)]
+pub struct ClientConnection(Arc<AtomicBool>);
+
+impl ClientConnection {
+    pub fn is_gone(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Records a request its client abandoned, unless disarmed once the
+/// response is ready.
+struct Abandoned {
+    connection: ClientConnection,
+    timing: RequestTiming,
+    method: String,
+    path: String,
+    route: String,
+    cancelled: bool,
+    armed: bool,
+}
+
+impl Drop for Abandoned {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        self.connection.0.store(true, Ordering::Relaxed);
+        let stage = self.timing.stage();
+        crate::metrics::record_client_disconnect(&self.method, &self.route, stage.as_str());
+        info!(
+            event = "client_disconnected",
+            method = %self.method,
+            path = %self.path,
+            route = %self.route,
+            stage = stage.as_str(),
+            latency_ms = self.timing.received_at().elapsed().as_millis(),
+            cancelled = self.cancelled,
+            "Client disconnected before the response was ready"
+        );
+    }
+}
+
+/// Notices clients that leave before their response is ready. The server
+/// drops a request's future when its connection closes, which cancels the
+/// handler and any upstream call in flight; nothing further down records
+/// the request, so it stays out of error rates and the mirror. Routes with
+/// `cancel_safe: false` run on their own task instead and finish whether or
+/// not anyone is waiting, so a write is never left half applied.
+pub async fn cancellation_middleware(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let cancel_safe = config.route(&method, &route).is_none_or(|route| route.cancel_safe);
+
+    let connection = ClientConnection::default();
+    request.extensions_mut().insert(connection.clone());
+    let mut abandoned = Abandoned {
+        connection,
+        timing: request.extensions().get::<RequestTiming>().cloned().unwrap_or_default(),
+        method,
+        path: request.uri().path().to_string(),
+        route,
+        cancelled: cancel_safe,
+        armed: true,
+    };
+
+    let response = if cancel_safe {
+        next.run(request).await
+    } else {
+        match tokio::spawn(next.run(request)).await {
+            Ok(response) => response,
+            Err(e) => {
+                error!(path = %abandoned.path, "Request task failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    };
+    abandoned.armed = false;
+    response
+}
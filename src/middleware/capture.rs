@@ -0,0 +1,650 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::{recording::CountingBody, timing::RequestTiming};
+use crate::{memory::MemoryConsumer, AppState};
+
+/// Longest capture an operator can request.
+pub const MAX_CAPTURE_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Most exchanges a single capture can hold.
+pub const MAX_CAPTURE_REQUESTS: usize = 1000;
+/// Bodies are kept up to this many bytes; the rest is dropped.
+const MAX_CAPTURED_BODY_BYTES: usize = 16 * 1024;
+
+const REDACTED_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CaptureRequest {
+    /// Route template (e.g. `/api/v1/users/:id`) or exact request path.
+    pub route: String,
+    #[serde(default = "default_capture_duration_seconds")]
+    pub duration_seconds: u64,
+    #[serde(default = "default_capture_max_requests")]
+    pub max_requests: usize,
+    #[serde(default)]
+    pub include_bodies: bool,
+}
+
+fn default_capture_duration_seconds() -> u64 {
+    600
+}
+
+fn default_capture_max_requests() -> usize {
+    100
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CaptureStatus {
+    pub route: String,
+    pub include_bodies: bool,
+    pub max_requests: usize,
+    pub started_by: String,
+    pub started_at: String,
+    pub expires_at: String,
+    pub captured: usize,
+    pub active: bool,
+    /// `expired` or `max_requests` once the capture has stopped.
+    pub ended_reason: Option<String>,
+    /// Oldest exchanges dropped to stay within the memory budget.
+    #[serde(default)]
+    pub evicted: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CapturedMessage {
+    /// Headers with credentials replaced by `[redacted]`.
+    pub headers: BTreeMap<String, String>,
+    /// Body, when bodies were requested.
+    pub body: Option<String>,
+    /// `base64` when the body isn't UTF-8 and `body` holds it encoded.
+    #[serde(default)]
+    pub body_encoding: Option<String>,
+    pub body_truncated: bool,
+}
+
+/// Where an exchange's time went, in milliseconds. Whatever isn't listed
+/// was spent in the gateway itself.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CapturedTimings {
+    /// Waiting for a legacy gateway connection.
+    pub queue_ms: f64,
+    /// From sending the request to the legacy gateway's response headers.
+    pub upstream_wait_ms: Option<f64>,
+    /// Reading the legacy gateway's response body.
+    pub upstream_receive_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CapturedExchange {
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    /// e.g. `HTTP/1.1`.
+    #[serde(default)]
+    pub http_version: String,
+    pub status: u16,
+    pub latency_ms: f64,
+    #[serde(default)]
+    pub timings: CapturedTimings,
+    pub request: CapturedMessage,
+    pub response: CapturedMessage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CaptureResults {
+    pub capture: CaptureStatus,
+    pub exchanges: Vec<CapturedExchange>,
+}
+
+struct CaptureSession {
+    id: u64,
+    settings: CaptureRequest,
+    actor: String,
+    started_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    deadline: Instant,
+    /// Matching requests admitted so far, including ones still in flight.
+    admitted: usize,
+    exchanges: Vec<CapturedExchange>,
+    evicted: usize,
+    ended_reason: Option<&'static str>,
+}
+
+impl CaptureSession {
+    fn status(&self) -> CaptureStatus {
+        CaptureStatus {
+            route: self.settings.route.clone(),
+            include_bodies: self.settings.include_bodies,
+            max_requests: self.settings.max_requests,
+            started_by: self.actor.clone(),
+            started_at: self.started_at.to_rfc3339(),
+            expires_at: self.expires_at.to_rfc3339(),
+            captured: self.exchanges.len(),
+            active: self.ended_reason.is_none(),
+            ended_reason: self.ended_reason.map(str::to_string),
+            evicted: self.evicted,
+        }
+    }
+
+    fn matches(&self, route: Option<&str>, path: &str) -> bool {
+        route == Some(self.settings.route.as_str()) || path == self.settings.route
+    }
+}
+
+/// A slot in the active capture, handed to the middleware for one request.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureSlot {
+    id: u64,
+    include_bodies: bool,
+}
+
+/// Records sanitized request/response pairs for a single route for a
+/// limited time.
+///
+/// Only one capture runs at a time. Requests check an atomic flag first, so
+/// with no capture active the middleware costs a single relaxed load.
+#[derive(Default)]
+pub struct DebugCapture {
+    active: AtomicBool,
+    next_id: AtomicU64,
+    session: Mutex<Option<CaptureSession>>,
+    /// Approximate footprint of the stored exchanges, for the memory budget.
+    bytes: AtomicU64,
+}
+
+/// Rough size of a stored exchange: its strings plus struct overhead.
+fn exchange_bytes(exchange: &CapturedExchange) -> u64 {
+    let message = |message: &CapturedMessage| {
+        message.headers.iter().map(|(name, value)| name.len() + value.len() + 48).sum::<usize>()
+            + message.body.as_ref().map_or(0, String::len)
+    };
+    (std::mem::size_of::<CapturedExchange>()
+        + exchange.timestamp.len()
+        + exchange.method.len()
+        + exchange.path.len()
+        + message(&exchange.request)
+        + message(&exchange.response)) as u64
+}
+
+impl DebugCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Starts a capture, replacing the results of any finished one. Fails
+    /// with the running capture's status if one is still active.
+    pub fn start(self: &Arc<Self>, settings: CaptureRequest, actor: &str) -> Result<CaptureStatus, Box<CaptureStatus>> {
+        let mut session = self.session.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(current) = session.as_mut() {
+            end_if_expired(current, &self.active);
+            if current.ended_reason.is_none() {
+                return Err(Box::new(current.status()));
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let duration = Duration::from_secs(settings.duration_seconds);
+        let now = Utc::now();
+        let started = CaptureSession {
+            id,
+            settings,
+            actor: actor.to_string(),
+            started_at: now,
+            expires_at: now + chrono::Duration::seconds(duration.as_secs() as i64),
+            deadline: Instant::now() + duration,
+            admitted: 0,
+            exchanges: Vec::new(),
+            evicted: 0,
+            ended_reason: None,
+        };
+        let status = started.status();
+        *session = Some(started);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.active.store(true, Ordering::Relaxed);
+
+        info!(
+            audit = true,
+            route = %status.route,
+            duration_seconds = duration.as_secs(),
+            max_requests = status.max_requests,
+            include_bodies = status.include_bodies,
+            actor = actor,
+            "Debug capture started"
+        );
+
+        // Clear the flag at the deadline even if no request arrives to notice
+        let capture = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            if let Ok(mut session) = capture.session.lock() {
+                if let Some(current) = session.as_mut().filter(|current| current.id == id) {
+                    end_if_expired(current, &capture.active);
+                }
+            }
+        });
+
+        Ok(status)
+    }
+
+    /// The current or most recent capture.
+    pub fn status(&self) -> Option<CaptureStatus> {
+        let mut session = self.session.lock().ok()?;
+        let current = session.as_mut()?;
+        end_if_expired(current, &self.active);
+        Some(current.status())
+    }
+
+    pub fn results(&self) -> Option<CaptureResults> {
+        let mut session = self.session.lock().ok()?;
+        let current = session.as_mut()?;
+        end_if_expired(current, &self.active);
+        Some(CaptureResults {
+            capture: current.status(),
+            exchanges: current.exchanges.clone(),
+        })
+    }
+
+    /// Admits a request to the active capture if it matches and the capture
+    /// still has room, ending the capture once the request cap is reached.
+    pub fn admit(&self, route: Option<&str>, path: &str) -> Option<CaptureSlot> {
+        let mut session = self.session.lock().ok()?;
+        let current = session.as_mut()?;
+        end_if_expired(current, &self.active);
+        if current.ended_reason.is_some() || !current.matches(route, path) {
+            return None;
+        }
+
+        current.admitted += 1;
+        if current.admitted >= current.settings.max_requests {
+            end(current, &self.active, "max_requests");
+        }
+        Some(CaptureSlot {
+            id: current.id,
+            include_bodies: current.settings.include_bodies,
+        })
+    }
+
+    fn record(&self, slot: CaptureSlot, exchange: CapturedExchange) {
+        if let Ok(mut session) = self.session.lock() {
+            if let Some(current) = session.as_mut().filter(|current| current.id == slot.id) {
+                self.bytes.fetch_add(exchange_bytes(&exchange), Ordering::Relaxed);
+                current.exchanges.push(exchange);
+            }
+        }
+    }
+}
+
+impl MemoryConsumer for DebugCapture {
+    fn memory_usage(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Drops the oldest exchanges; the capture keeps running.
+    fn evict(&self, bytes: u64) -> u64 {
+        let Ok(mut session) = self.session.lock() else {
+            return 0;
+        };
+        let Some(current) = session.as_mut() else {
+            return 0;
+        };
+        let mut freed = 0;
+        let mut dropped = 0;
+        for exchange in &current.exchanges {
+            if freed >= bytes {
+                break;
+            }
+            freed += exchange_bytes(exchange);
+            dropped += 1;
+        }
+        current.exchanges.drain(..dropped);
+        current.evicted += dropped;
+        self.bytes.fetch_sub(freed, Ordering::Relaxed);
+        freed
+    }
+}
+
+fn end_if_expired(session: &mut CaptureSession, active: &AtomicBool) {
+    if session.ended_reason.is_none() && Instant::now() >= session.deadline {
+        end(session, active, "expired");
+    }
+}
+
+fn end(session: &mut CaptureSession, active: &AtomicBool, reason: &'static str) {
+    session.ended_reason = Some(reason);
+    active.store(false, Ordering::Relaxed);
+    info!(
+        audit = true,
+        route = %session.settings.route,
+        reason = reason,
+        admitted = session.admitted,
+        "Debug capture ended"
+    );
+}
+
+/// Records exchanges for the route under debug capture, if any.
+///
+/// Bodies are copied as they stream rather than buffered, and the exchange is
+/// recorded once the response body has been sent.
+pub async fn capture_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.debug_capture.is_active() {
+        return next.run(request).await;
+    }
+
+    let route = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str().to_string());
+    let Some(slot) = state.debug_capture.admit(route.as_deref(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let start = Instant::now();
+    let timing = request.extensions().get::<RequestTiming>().cloned();
+    let timestamp = Utc::now().to_rfc3339();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let http_version = format!("{:?}", request.version());
+
+    let (parts, body) = request.into_parts();
+    let request_headers = sanitized_headers(&parts.headers);
+    let (body, request_tee) = tee(body, slot.include_bodies);
+    let response = next.run(Request::from_parts(parts, body)).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let timings = timing.map(|timing| captured_timings(&timing)).unwrap_or_default();
+
+    let (parts, body) = response.into_parts();
+    let response_headers = sanitized_headers(&parts.headers);
+    let (body, response_tee) = tee(body, slot.include_bodies);
+    let status = parts.status.as_u16();
+
+    let capture = state.debug_capture.clone();
+    let memory_budget = state.memory_budget.clone();
+    let body = CountingBody::new(body, move |_| {
+        capture.record(
+            slot,
+            CapturedExchange {
+                timestamp,
+                method,
+                path,
+                http_version,
+                status,
+                latency_ms,
+                timings,
+                request: captured_message(request_headers, request_tee),
+                response: captured_message(response_headers, response_tee),
+            },
+        );
+        memory_budget.enforce();
+    });
+
+    Response::from_parts(parts, Body::new(body))
+}
+
+fn sanitized_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn captured_timings(timing: &RequestTiming) -> CapturedTimings {
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    let upstream = timing.upstream_phases();
+    CapturedTimings {
+        queue_ms: millis(timing.queue_time()),
+        upstream_wait_ms: upstream.map(|(wait, _)| millis(wait)),
+        upstream_receive_ms: upstream.map(|(_, receive)| millis(receive)),
+    }
+}
+
+fn captured_message(headers: BTreeMap<String, String>, tee: Option<Arc<Mutex<TeeBuffer>>>) -> CapturedMessage {
+    let buffer = tee.and_then(|tee| tee.lock().ok().map(|buffer| (body_text(&buffer.bytes, buffer.truncated), buffer.truncated)));
+    let (body, body_encoding, body_truncated) = match buffer {
+        Some(((body, encoding), truncated)) => (Some(body), encoding, truncated),
+        None => (None, None, false),
+    };
+    CapturedMessage {
+        headers,
+        body,
+        body_encoding,
+        body_truncated,
+    }
+}
+
+/// The body as text, or base64 with its encoding when it isn't UTF-8.
+fn body_text(bytes: &[u8], truncated: bool) -> (String, Option<String>) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), None),
+        // Text cut off mid-character by the size cap is still text
+        Err(e) if truncated && e.error_len().is_none() => {
+            (String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned(), None)
+        }
+        Err(_) => (BASE64.encode(bytes), Some("base64".to_string())),
+    }
+}
+
+/// Exchanges as a HAR 1.2 log, for HTTP tools and for vendors who want
+/// captures in a format they can load.
+///
+/// Queueing for a legacy gateway connection is reported as `blocked`,
+/// waiting for its response headers as `wait` and reading its body as
+/// `receive`. Connections are pooled, so `dns`, `connect` and `ssl` are
+/// unknown (-1). The rest of the exchange's time was spent in the gateway
+/// and is reported as `_gateway`, so `time` is still the sum of the phases.
+/// Bodies that aren't UTF-8 are base64-encoded.
+pub fn to_har(exchanges: &[CapturedExchange]) -> serde_json::Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "project-gateway", "version": env!("CARGO_PKG_VERSION") },
+            "entries": exchanges.iter().map(har_entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+fn har_entry(exchange: &CapturedExchange) -> serde_json::Value {
+    let timings = &exchange.timings;
+    let wait = timings.upstream_wait_ms.unwrap_or(0.0);
+    let receive = timings.upstream_receive_ms.unwrap_or(0.0);
+    let gateway = (exchange.latency_ms - timings.queue_ms - wait - receive).max(0.0);
+
+    let request = &exchange.request;
+    let scheme = request.headers.get("x-forwarded-proto").map_or("http", String::as_str);
+    let host = request.headers.get("host").map_or("localhost", String::as_str);
+    let mut har_request = json!({
+        "method": exchange.method,
+        "url": format!("{}://{}{}", scheme, host, exchange.path),
+        "httpVersion": exchange.http_version,
+        "cookies": [],
+        "headers": har_headers(&request.headers),
+        "queryString": [],
+        "headersSize": -1,
+        "bodySize": har_body_size(request),
+    });
+    if let Some(text) = request.body.as_ref().filter(|body| !body.is_empty()) {
+        let mut post_data = json!({ "mimeType": har_mime_type(request), "text": text });
+        if let Some(encoding) = &request.body_encoding {
+            // HAR only defines an encoding for responses
+            post_data["_encoding"] = json!(encoding);
+        }
+        har_request["postData"] = post_data;
+    }
+
+    let response = &exchange.response;
+    let mut content = json!({ "size": har_body_size(response), "mimeType": har_mime_type(response) });
+    if let Some(text) = &response.body {
+        content["text"] = json!(text);
+    }
+    if let Some(encoding) = &response.body_encoding {
+        content["encoding"] = json!(encoding);
+    }
+    if response.body_truncated {
+        content["comment"] = json!(format!("Body truncated to {} bytes", MAX_CAPTURED_BODY_BYTES));
+    }
+    let status_text = axum::http::StatusCode::from_u16(exchange.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+
+    json!({
+        "startedDateTime": exchange.timestamp,
+        "time": exchange.latency_ms,
+        "request": har_request,
+        "response": {
+            "status": exchange.status,
+            "statusText": status_text,
+            "httpVersion": exchange.http_version,
+            "cookies": [],
+            "headers": har_headers(&response.headers),
+            "content": content,
+            "redirectURL": response.headers.get("location").cloned().unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": har_body_size(response),
+        },
+        "cache": {},
+        "timings": {
+            "blocked": timings.queue_ms,
+            "dns": -1,
+            "connect": -1,
+            "ssl": -1,
+            "send": 0,
+            "wait": wait,
+            "receive": receive,
+            "_gateway": gateway,
+        },
+    })
+}
+
+fn har_headers(headers: &BTreeMap<String, String>) -> Vec<serde_json::Value> {
+    headers.iter().map(|(name, value)| json!({ "name": name, "value": value })).collect()
+}
+
+fn har_mime_type(message: &CapturedMessage) -> &str {
+    message.headers.get("content-type").map_or("", String::as_str)
+}
+
+/// Bytes of body, or -1 when it wasn't captured in full.
+fn har_body_size(message: &CapturedMessage) -> i64 {
+    let Some(body) = message.body.as_ref().filter(|_| !message.body_truncated) else {
+        return -1;
+    };
+    let bytes = match message.body_encoding.as_deref() {
+        Some("base64") => BASE64.decode(body).map_or(0, |bytes| bytes.len()),
+        _ => body.len(),
+    };
+    bytes as i64
+}
+
+#[derive(Default)]
+pub(crate) struct TeeBuffer {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) truncated: bool,
+}
+
+fn tee(body: Body, enabled: bool) -> (Body, Option<Arc<Mutex<TeeBuffer>>>) {
+    if !enabled {
+        return (body, None);
+    }
+    let (body, buffer) = copy_prefix(body, MAX_CAPTURED_BODY_BYTES);
+    (body, Some(buffer))
+}
+
+/// Wraps `body` so the first `limit` bytes of data are copied into the
+/// returned buffer as they stream through.
+pub(crate) fn copy_prefix(body: Body, limit: usize) -> (Body, Arc<Mutex<TeeBuffer>>) {
+    let buffer = Arc::new(Mutex::new(TeeBuffer::default()));
+    let body = Body::new(TeeBody {
+        inner: body,
+        buffer: buffer.clone(),
+        limit,
+    });
+    (body, buffer)
+}
+
+pin_project! {
+    /// Body wrapper that copies the first `limit` bytes of data into a
+    /// shared buffer as it streams through.
+    struct TeeBody<B> {
+        #[pin]
+        inner: B,
+        buffer: Arc<Mutex<TeeBuffer>>,
+        limit: usize,
+    }
+}
+
+impl<B> http_body::Body for TeeBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+
+        if let Some(data) = frame.as_ref().and_then(|frame| frame.as_ref().ok()).and_then(Frame::data_ref) {
+            if let Ok(mut buffer) = this.buffer.lock() {
+                let room = this.limit.saturating_sub(buffer.bytes.len());
+                buffer.truncated |= data.len() > room;
+                buffer.bytes.extend_from_slice(&data[..data.len().min(room)]);
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
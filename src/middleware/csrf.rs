@@ -0,0 +1,212 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde_json::json;
+use tracing::debug;
+
+use crate::{
+    config::{CsrfConfig, CsrfMode},
+    middleware::rate_limit::API_KEY_HEADER,
+    AppState,
+};
+
+/// Why a request failed its CSRF check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrfRejection {
+    TokenMissing,
+    TokenMismatch,
+    TokenExpired,
+    OriginRejected,
+}
+
+impl CsrfRejection {
+    /// Problem `code` and metric label.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CsrfRejection::TokenMissing => "csrf_token_missing",
+            CsrfRejection::TokenMismatch => "csrf_token_mismatch",
+            CsrfRejection::TokenExpired => "csrf_token_expired",
+            CsrfRejection::OriginRejected => "csrf_origin_rejected",
+        }
+    }
+
+    fn detail(&self) -> &'static str {
+        match self {
+            CsrfRejection::TokenMissing => "The CSRF cookie or header is missing",
+            CsrfRejection::TokenMismatch => "The CSRF header does not match the CSRF cookie",
+            CsrfRejection::TokenExpired => "The CSRF token has expired; fetch a new one with a GET request",
+            CsrfRejection::OriginRejected => "The request's Origin or Referer is not an allowed origin",
+        }
+    }
+}
+
+/// A new token: its issue time in Unix seconds and 32 random bytes from the
+/// OS generator, hex-encoded.
+pub fn generate_token(issued_at: i64) -> String {
+    let mut random = [0u8; 32];
+    OsRng.fill_bytes(&mut random);
+    let random: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.{}", issued_at, random)
+}
+
+fn issued_at(token: &str) -> Option<i64> {
+    let (issued_at, random) = token.split_once('.')?;
+    (random.len() == 64 && random.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| issued_at.parse().ok())
+        .flatten()
+}
+
+fn expired(config: &CsrfConfig, token: &str, now: i64) -> bool {
+    issued_at(token).is_none_or(|issued_at| now - issued_at >= config.token_lifetime.get().as_secs() as i64)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+/// Bearer tokens and API keys aren't sent by browsers on their own, so
+/// requests using them can't be forged cross-site.
+fn uses_token_auth(headers: &HeaderMap) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.len() > 7 && value[..7].eq_ignore_ascii_case("bearer "));
+    bearer || headers.contains_key(API_KEY_HEADER)
+}
+
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// `scheme://host[:port]` of a URL, lowercased.
+pub fn origin_of(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    (!scheme.is_empty() && !authority.is_empty()).then(|| format!("{}://{}", scheme, authority).to_ascii_lowercase())
+}
+
+/// Checks an unsafe request against `mode`.
+pub fn check(config: &CsrfConfig, mode: CsrfMode, headers: &HeaderMap, now: i64) -> Result<(), CsrfRejection> {
+    match mode {
+        CsrfMode::DoubleSubmit => {
+            let cookie = cookie(headers, &config.cookie_name);
+            let header = headers
+                .get(config.header_name.as_str())
+                .and_then(|value| value.to_str().ok());
+            let (Some(cookie), Some(header)) = (cookie, header) else {
+                return Err(CsrfRejection::TokenMissing);
+            };
+            if !constant_time_eq(cookie.as_bytes(), header.trim().as_bytes()) {
+                return Err(CsrfRejection::TokenMismatch);
+            }
+            if expired(config, cookie, now) {
+                return Err(CsrfRejection::TokenExpired);
+            }
+            Ok(())
+        }
+        CsrfMode::Origin => {
+            let origin = match headers.get(header::ORIGIN).and_then(|value| value.to_str().ok()) {
+                Some(origin) => origin_of(origin),
+                None => headers
+                    .get(header::REFERER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(origin_of),
+            };
+            let allowed = origin.is_some_and(|origin| {
+                config
+                    .allowed_origins
+                    .iter()
+                    .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(&origin))
+            });
+            if allowed {
+                Ok(())
+            } else {
+                Err(CsrfRejection::OriginRejected)
+            }
+        }
+    }
+}
+
+fn token_cookie(config: &CsrfConfig, token: &str) -> Option<HeaderValue> {
+    let mut cookie = format!(
+        "{}={}; Path=/; Max-Age={}; SameSite=Strict",
+        config.cookie_name,
+        token,
+        config.token_lifetime.get().as_secs()
+    );
+    if config.secure_cookie {
+        cookie.push_str("; Secure");
+    }
+    HeaderValue::from_str(&cookie).ok()
+}
+
+/// 403 problem+json with the rejection's code.
+fn forbidden(rejection: CsrfRejection) -> Response {
+    let problem = json!({
+        "type": "about:blank",
+        "title": "Forbidden",
+        "status": 403,
+        "detail": rejection.detail(),
+        "code": rejection.code(),
+    });
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("content-type", "application/problem+json")
+        .body(Body::from(problem.to_string()))
+        .unwrap()
+}
+
+/// Rejects cross-site state changes on the routes listed under
+/// `middleware.csrf.routes`. In double-submit mode, safe requests without a
+/// current token are handed a fresh one.
+pub async fn csrf_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let csrf = &config.middleware.csrf;
+    if !csrf.enabled || uses_token_auth(request.headers()) {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    let Some(mode) = csrf.mode_for(route) else {
+        return next.run(request).await;
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if !is_safe(request.method()) {
+        if let Err(rejection) = check(csrf, mode, request.headers(), now) {
+            debug!(path = request.uri().path(), code = rejection.code(), "Rejected request failing CSRF check");
+            crate::metrics::record_csrf_rejection(rejection.code());
+            return forbidden(rejection);
+        }
+        return next.run(request).await;
+    }
+
+    let needs_token = mode == CsrfMode::DoubleSubmit
+        && cookie(request.headers(), &csrf.cookie_name).is_none_or(|token| expired(csrf, token, now));
+    let mut response = next.run(request).await;
+    if needs_token {
+        if let Some(cookie) = token_cookie(csrf, &generate_token(now)) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    response
+}
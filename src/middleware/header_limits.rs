@@ -0,0 +1,165 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::{
+    config::{ByteSize, ServerConfig, UpstreamHeaderLimits},
+    AppState,
+};
+
+/// Request header limits in effect for one hop.
+#[derive(Debug, Clone)]
+pub struct HeaderLimits {
+    pub max_header_bytes: u64,
+    pub max_header_count: usize,
+    /// By lowercase header name.
+    pub header_size_limits: BTreeMap<String, u64>,
+}
+
+/// Which limit a request broke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderLimitExceeded {
+    TotalBytes { actual: u64, limit: u64 },
+    Count { actual: usize, limit: usize },
+    Header { name: String, actual: u64, limit: u64 },
+}
+
+impl HeaderLimitExceeded {
+    /// Metric label and problem `limit` member.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HeaderLimitExceeded::TotalBytes { .. } => "total_bytes",
+            HeaderLimitExceeded::Count { .. } => "count",
+            HeaderLimitExceeded::Header { .. } => "header_bytes",
+        }
+    }
+
+    /// Names the limit and sizes, never the offending value.
+    pub fn detail(&self) -> String {
+        match self {
+            HeaderLimitExceeded::TotalBytes { actual, limit } => {
+                format!("Request headers total {} bytes; the limit is {}", actual, limit)
+            }
+            HeaderLimitExceeded::Count { actual, limit } => {
+                format!("Request has {} headers; the limit is {}", actual, limit)
+            }
+            HeaderLimitExceeded::Header { name, actual, limit } => {
+                format!("Header {} is {} bytes; the limit is {}", name, actual, limit)
+            }
+        }
+    }
+}
+
+fn bytes(sizes: &BTreeMap<String, ByteSize>) -> impl Iterator<Item = (String, u64)> + '_ {
+    sizes.iter().map(|(name, size)| (name.to_ascii_lowercase(), size.bytes()))
+}
+
+impl HeaderLimits {
+    pub fn for_server(server: &ServerConfig) -> Self {
+        Self {
+            max_header_bytes: server.max_header_bytes.bytes(),
+            max_header_count: server.max_header_count,
+            header_size_limits: bytes(&server.header_size_limits).collect(),
+        }
+    }
+
+    /// The server's limits tightened by an upstream's; the stricter of each
+    /// applies.
+    pub fn for_upstream(server: &ServerConfig, upstream: &UpstreamHeaderLimits) -> Self {
+        let mut limits = Self::for_server(server);
+        if let Some(max) = upstream.max_header_bytes {
+            limits.max_header_bytes = limits.max_header_bytes.min(max.bytes());
+        }
+        if let Some(max) = upstream.max_header_count {
+            limits.max_header_count = limits.max_header_count.min(max);
+        }
+        for (name, max) in bytes(&upstream.header_size_limits) {
+            let limit = limits.header_size_limits.entry(name).or_insert(max);
+            *limit = (*limit).min(max);
+        }
+        limits
+    }
+
+    /// Checks `headers` against these limits. A header sent several times
+    /// counts once per line, and its per-header size is the sum of its
+    /// values.
+    pub fn check(&self, headers: &HeaderMap) -> Result<(), HeaderLimitExceeded> {
+        if headers.len() > self.max_header_count {
+            return Err(HeaderLimitExceeded::Count {
+                actual: headers.len(),
+                limit: self.max_header_count,
+            });
+        }
+
+        let mut total = 0u64;
+        for name in headers.keys() {
+            let size: u64 = headers
+                .get_all(name)
+                .iter()
+                .map(|value| (name.as_str().len() + value.len()) as u64)
+                .sum();
+            if let Some(&limit) = self.header_size_limits.get(name.as_str()) {
+                if size > limit {
+                    return Err(HeaderLimitExceeded::Header {
+                        name: name.as_str().to_string(),
+                        actual: size,
+                        limit,
+                    });
+                }
+            }
+            total += size;
+        }
+        if total > self.max_header_bytes {
+            return Err(HeaderLimitExceeded::TotalBytes {
+                actual: total,
+                limit: self.max_header_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 431 problem+json for a request rejected by `scope`'s limits (`server`,
+/// or the upstream it would have been proxied to).
+pub fn rejection(exceeded: &HeaderLimitExceeded, scope: &'static str) -> Response {
+    crate::metrics::record_header_limit_rejection(exceeded.kind(), scope);
+    let mut problem = json!({
+        "type": "about:blank",
+        "title": "Request Header Fields Too Large",
+        "status": 431,
+        "detail": exceeded.detail(),
+        "limit": exceeded.kind(),
+        "scope": scope,
+    });
+    if let HeaderLimitExceeded::Header { name, .. } = exceeded {
+        problem["header"] = json!(name);
+    }
+    Response::builder()
+        .status(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        .header("content-type", "application/problem+json")
+        .body(Body::from(problem.to_string()))
+        .unwrap()
+}
+
+/// Rejects requests over the server's header limits before any other layer
+/// buffers, logs, or forwards them.
+pub async fn header_limits_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    if let Err(exceeded) = HeaderLimits::for_server(&config.server).check(request.headers()) {
+        warn!(
+            path = request.uri().path(),
+            limit = exceeded.kind(),
+            detail = %exceeded.detail(),
+            "Rejected request over header limits"
+        );
+        return rejection(&exceeded, "server");
+    }
+    next.run(request).await
+}
@@ -0,0 +1,47 @@
+//! The verified caller, forwarded to the legacy gateway and the mirror.
+//!
+//! Whatever identity headers the client sent are dropped first, so only
+//! the gateway can set them.
+
+use axum::http::{Extensions, HeaderMap, HeaderName, HeaderValue};
+
+use crate::{
+    config::IdentityHeadersConfig,
+    middleware::auth::{AuthMethod, Claims},
+};
+
+/// Replaces the client's identity headers with the request's verified
+/// claims. Without claims, the headers are only stripped.
+pub fn apply(headers: &mut HeaderMap, extensions: &Extensions, config: &IdentityHeadersConfig) {
+    if !config.enabled {
+        return;
+    }
+    let prefix = config.strip_prefix.to_ascii_lowercase();
+    let configured = config.names();
+    let spoofed: Vec<HeaderName> = headers
+        .keys()
+        .filter(|name| {
+            (!prefix.is_empty() && name.as_str().starts_with(&prefix))
+                || configured.iter().any(|configured| name.as_str().eq_ignore_ascii_case(configured))
+        })
+        .cloned()
+        .collect();
+    for name in spoofed {
+        headers.remove(name);
+    }
+
+    let Some(claims) = extensions.get::<Claims>() else { return };
+    let method = extensions.get::<AuthMethod>().map(AuthMethod::as_str);
+    let values = [
+        (&config.subject, Some(claims.sub.clone())),
+        (&config.roles, (!claims.roles.is_empty()).then(|| claims.roles.join(","))),
+        (&config.scopes, claims.scope.clone().filter(|scope| !scope.is_empty())),
+        (&config.method, method.map(str::to_string)),
+    ];
+    for (name, value) in values {
+        let (Ok(name), Some(Ok(value))) = (HeaderName::from_bytes(name.as_bytes()), value.map(HeaderValue::try_from)) else {
+            continue;
+        };
+        headers.insert(name, value);
+    }
+}
@@ -0,0 +1,123 @@
+//! RFC 7662 introspection of opaque bearer tokens.
+//!
+//! Tokens that don't parse as JWTs are posted to the configured endpoint
+//! with the client credentials. Answers are cached by token hash: active
+//! tokens for `cache_ttl` but never past their `exp`, inactive ones for
+//! `negative_cache_ttl`. Failed calls aren't cached. An active token's `iss`
+//! and `aud` are checked like a JWT's, on every use.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::{
+    config::{AuthConfig, IntrospectionConfig, IntrospectionFailure},
+    middleware::auth::{fingerprint, Audience, AuthCache, ClaimMismatch, Claims, IntrospectedToken},
+    upstream::{Upstream, UpstreamPool},
+};
+
+/// Subject of requests let through by `on_failure: open`.
+pub const UNVERIFIED_SUBJECT: &str = "unverified";
+
+#[derive(Debug, Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    sub: Option<String>,
+    username: Option<String>,
+    client_id: Option<String>,
+    exp: Option<u64>,
+    scope: Option<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+    iss: Option<String>,
+    aud: Option<Audience>,
+}
+
+/// Identifies the endpoint and client an answer came from; another may not
+/// agree about the token.
+fn endpoint_id(config: &IntrospectionConfig) -> [u8; 32] {
+    fingerprint(&format!("{}\n{}\n{}", config.url, config.client_id, config.client_secret))
+}
+
+async fn introspect(upstreams: &UpstreamPool, config: &IntrospectionConfig, token: &str) -> Result<IntrospectionResponse> {
+    let mut request = upstreams
+        .request(Upstream::Introspection, reqwest::Method::POST, &config.url)
+        .timeout(config.timeout.get())
+        .form(&[("token", token), ("token_type_hint", "access_token")]);
+    if !config.client_id.is_empty() {
+        request = request.basic_auth(&config.client_id, Some(&config.client_secret));
+    }
+    Ok(request.send().await?.error_for_status()?.json().await?)
+}
+
+/// Claims for an opaque token as of `now` (Unix seconds), from the cache or
+/// the introspection endpoint, if its `iss` and `aud` satisfy `auth`.
+pub async fn validate_at(
+    cache: &AuthCache,
+    upstreams: &UpstreamPool,
+    auth: &AuthConfig,
+    config: &IntrospectionConfig,
+    token: &str,
+    now: u64,
+) -> Option<Claims> {
+    let token_id = fingerprint(token);
+    let endpoint_id = endpoint_id(config);
+    if let Some(cached) = cache.introspected(&token_id, &endpoint_id) {
+        crate::metrics::record_token_introspection("cached");
+        return cached.and_then(|token| token.accepted(auth).ok());
+    }
+
+    match introspect(upstreams, config, token).await {
+        Ok(response) if response.active && response.exp.is_none_or(|exp| exp > now) => {
+            let ttl = config.cache_ttl.get().min(Duration::from_secs(response.exp.map_or(u64::MAX, |exp| exp - now)));
+            let introspected = IntrospectedToken {
+                claims: Claims {
+                    sub: response.sub.or(response.username).or(response.client_id).unwrap_or_default(),
+                    exp: response.exp.unwrap_or_else(|| now.saturating_add(ttl.as_secs())),
+                    scope: response.scope,
+                    roles: response.roles,
+                },
+                iss: response.iss,
+                aud: response.aud,
+            };
+            if !ttl.is_zero() {
+                cache.remember_introspection(token_id, endpoint_id, Some(introspected.clone()), ttl);
+            }
+            crate::metrics::record_token_introspection("active");
+            introspected.accepted(auth).ok()
+        }
+        Ok(_) => {
+            debug!("Introspection reported the token inactive");
+            if !config.negative_cache_ttl.is_zero() {
+                cache.remember_introspection(token_id, endpoint_id, None, config.negative_cache_ttl.get());
+            }
+            crate::metrics::record_token_introspection("inactive");
+            None
+        }
+        Err(e) => {
+            warn!(on_failure = ?config.on_failure, "Token introspection failed: {:#}", e);
+            crate::metrics::record_token_introspection("error");
+            (config.on_failure == IntrospectionFailure::Open).then(|| Claims {
+                sub: UNVERIFIED_SUBJECT.to_string(),
+                exp: now,
+                scope: None,
+                roles: Vec::new(),
+            })
+        }
+    }
+}
+
+/// The claim an opaque token was refused for, with its subject, when
+/// introspection vouched for it but its `iss` or `aud` don't match `auth`.
+/// Only a cached answer is consulted.
+pub fn mismatched_claim(
+    cache: &AuthCache,
+    auth: &AuthConfig,
+    config: &IntrospectionConfig,
+    token: &str,
+) -> Option<(ClaimMismatch, String)> {
+    let introspected = cache.introspected(&fingerprint(token), &endpoint_id(config))??;
+    let sub = introspected.claims.sub.clone();
+    introspected.accepted(auth).err().map(|mismatch| (mismatch, sub))
+}
@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+use tracing::warn;
+
+use crate::{
+    config::{prefix_covers, IpFilterConfig},
+    middleware::rate_limit::client_ip,
+    routes::error::ApiError,
+    AppState,
+};
+
+/// Why a client was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Block {
+    /// Its address is in `deny`.
+    Denied,
+    /// `allow` guards the path and its address isn't in it.
+    NotAllowed,
+}
+
+impl Block {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Block::Denied => "denied",
+            Block::NotAllowed => "not_allowed",
+        }
+    }
+}
+
+/// `ip_filter` with its networks parsed.
+struct Rules {
+    config: IpFilterConfig,
+    deny: Vec<(IpAddr, u8)>,
+    allow: Vec<(IpAddr, u8)>,
+}
+
+impl Rules {
+    /// Entries that don't parse are left out; validation rejects configs
+    /// with any.
+    fn parse(config: &IpFilterConfig) -> Self {
+        let networks = |entries: &[String]| {
+            entries
+                .iter()
+                .filter_map(|entry| crate::profiling::parse_network(entry).ok())
+                .collect()
+        };
+        Self {
+            config: config.clone(),
+            deny: networks(&config.deny),
+            allow: networks(&config.allow),
+        }
+    }
+
+    fn guards(&self, path: &str) -> bool {
+        !self.allow.is_empty()
+            && (self.config.allow_paths.is_empty()
+                || self.config.allow_paths.iter().any(|prefix| prefix_covers(prefix, path)))
+    }
+}
+
+fn contains(networks: &[(IpAddr, u8)], ip: IpAddr) -> bool {
+    networks
+        .iter()
+        .any(|(network, prefix)| crate::upstream::proxy::in_network(ip, *network, *prefix))
+}
+
+/// The `ip_filter` lists, parsed again only when the config changes them.
+pub struct IpFilter {
+    rules: Mutex<Option<Arc<Rules>>>,
+}
+
+impl Default for IpFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IpFilter {
+    pub fn new() -> Self {
+        Self { rules: Mutex::new(None) }
+    }
+
+    fn rules(&self, config: &IpFilterConfig) -> Arc<Rules> {
+        let mut rules = self.rules.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match rules.as_ref() {
+            Some(current) if current.config == *config => current.clone(),
+            _ => {
+                let parsed = Arc::new(Rules::parse(config));
+                *rules = Some(parsed.clone());
+                parsed
+            }
+        }
+    }
+
+    /// Whether a request from `client` to `path` is turned away. A client
+    /// whose address is unknown, such as a forwarded hop that isn't an IP,
+    /// could be anyone: it is denied whenever `deny` lists anything, and
+    /// isn't allowed either.
+    pub fn check(&self, config: &IpFilterConfig, client: Option<IpAddr>, path: &str) -> Option<Block> {
+        let rules = self.rules(config);
+        if client.map_or(!rules.deny.is_empty(), |ip| contains(&rules.deny, ip)) {
+            return Some(Block::Denied);
+        }
+        if rules.guards(path) && !client.is_some_and(|ip| contains(&rules.allow, ip)) {
+            return Some(Block::NotAllowed);
+        }
+        None
+    }
+}
+
+/// Answers 403 to clients `ip_filter` turns away. The client is the peer,
+/// or the address it forwarded for when it's one of `trusted_proxies`.
+pub async fn ip_filter_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let ip_filter = &config.middleware.ip_filter;
+    if !ip_filter.is_active() {
+        return next.run(request).await;
+    }
+
+    let client = client_ip(request.headers(), request.extensions(), &ip_filter.trusted_proxies);
+    let path = request.uri().path();
+    let Some(block) = state
+        .ip_filter
+        .check(ip_filter, client.as_deref().and_then(|ip| ip.parse().ok()), path)
+    else {
+        return next.run(request).await;
+    };
+
+    crate::metrics::record_ip_blocked(block.as_str());
+    warn!(client = client.as_deref(), path, reason = block.as_str(), "Request blocked by client address");
+    ApiError::new(StatusCode::FORBIDDEN, "ip_blocked", "Requests from this address are not accepted")
+        .for_request(request.extensions())
+        .into_response()
+}
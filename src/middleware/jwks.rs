@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::JwkSet;
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::{
+    upstream::{Upstream, UpstreamPool},
+    util::backoff::retry,
+    AppState,
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// After a forced refresh that still lacks the requested `kid`, further
+/// forced refreshes wait this long, so tokens with made-up key IDs can't
+/// make every request fetch the key set.
+const UNKNOWN_KID_BACKOFF: Duration = Duration::from_secs(30);
+
+struct Fetched {
+    url: String,
+    keys: Arc<JwkSet>,
+}
+
+/// The last good key set fetched from `middleware.auth.jwks_url`.
+///
+/// Requests only ever read the cached set; it is refreshed in the background
+/// every `jwks_refresh_interval`, and on demand when a token names a key it
+/// doesn't have. A failed fetch keeps the previous set.
+#[derive(Default)]
+pub struct JwksCache {
+    fetched: RwLock<Option<Fetched>>,
+    /// One forced refresh at a time; waiters reuse its result.
+    refreshing: Mutex<()>,
+    last_miss: RwLock<Option<Instant>>,
+}
+
+impl JwksCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached key set for `url`, empty until a fetch has succeeded.
+    pub fn keys(&self, url: &str) -> Arc<JwkSet> {
+        self.fetched
+            .read()
+            .ok()
+            .and_then(|fetched| {
+                fetched
+                    .as_ref()
+                    .filter(|fetched| fetched.url == url)
+                    .map(|fetched| fetched.keys.clone())
+            })
+            .unwrap_or_else(|| Arc::new(JwkSet { keys: Vec::new() }))
+    }
+
+    /// Fetches the key set at `url`, replacing the cached one on success.
+    pub async fn refresh(&self, upstreams: &UpstreamPool, url: &str) -> Result<Arc<JwkSet>> {
+        let keys: JwkSet = upstreams
+            .request(Upstream::Jwks, reqwest::Method::GET, url)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("fetching {}", url))?
+            .json()
+            .await
+            .with_context(|| format!("parsing the key set from {}", url))?;
+        let keys = Arc::new(keys);
+        if let Ok(mut fetched) = self.fetched.write() {
+            *fetched = Some(Fetched {
+                url: url.to_string(),
+                keys: keys.clone(),
+            });
+        }
+        Ok(keys)
+    }
+
+    /// The key set, re-fetched first when it has no key `kid`. The caller
+    /// rejects the token if the key is still missing afterwards.
+    pub async fn keys_for(&self, upstreams: &UpstreamPool, url: &str, kid: &str) -> Arc<JwkSet> {
+        let keys = self.keys(url);
+        if keys.find(kid).is_some() {
+            return keys;
+        }
+
+        let _refreshing = self.refreshing.lock().await;
+        // Someone else's refresh may have brought the key in meanwhile
+        let keys = self.keys(url);
+        if keys.find(kid).is_some() {
+            return keys;
+        }
+        let backing_off = self
+            .last_miss
+            .read()
+            .ok()
+            .and_then(|last_miss| *last_miss)
+            .is_some_and(|last_miss| last_miss.elapsed() < UNKNOWN_KID_BACKOFF);
+        if backing_off {
+            return keys;
+        }
+
+        let keys = match self.refresh(upstreams, url).await {
+            Ok(keys) => keys,
+            Err(e) => {
+                warn!("JWKS refresh for unknown key {:?} failed, keeping the last good key set: {:#}", kid, e);
+                keys
+            }
+        };
+        if keys.find(kid).is_none() {
+            if let Ok(mut last_miss) = self.last_miss.write() {
+                *last_miss = Some(Instant::now());
+            }
+        }
+        keys
+    }
+
+    /// Re-fetches the key set every `jwks_refresh_interval`, and at once
+    /// after a config reload.
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        let mut reloads = state.config_watcher.subscribe_to_reloads();
+        loop {
+            let config = state.config_watcher.get_config().await;
+            let auth = &config.middleware.auth;
+            if let Some(url) = auth.jwks_url.as_deref().filter(|_| auth.enabled) {
+                let refreshed = retry(&auth.jwks_retry, |_| self.refresh(&state.upstreams, url)).await;
+                crate::metrics::record_retries("jwks_refresh", refreshed.retries());
+                match refreshed.result {
+                    Ok(keys) => info!(keys = keys.keys.len(), url, attempts = refreshed.attempts, "Refreshed JWKS"),
+                    Err(e) => warn!(
+                        attempts = refreshed.attempts,
+                        "JWKS refresh failed, keeping the last good key set: {:#}",
+                        e
+                    ),
+                }
+            }
+
+            tokio::select! {
+                reload = reloads.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(auth.jwks_refresh_interval.get()) => {}
+            }
+        }
+    }
+}
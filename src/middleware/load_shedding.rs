@@ -0,0 +1,125 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+
+use crate::{middleware::recording::CountingBody, routes::error::ApiError, AppState};
+
+/// Let through uncounted when `exempt_health_checks` is on.
+pub const HEALTH_PATHS: &[&str] = &["/health", "/readyz", "/api/v1/health"];
+
+/// Gateway-wide cap on requests in flight, as a semaphore sized to the
+/// configured limit.
+pub struct LoadShedder {
+    slots: Mutex<Option<(usize, Arc<Semaphore>)>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// A request counted as in flight until dropped, holding a slot when a
+/// limit is set.
+pub struct InFlight {
+    _permit: Option<OwnedSemaphorePermit>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let now = self.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        crate::metrics::record_in_flight_requests(now);
+    }
+}
+
+impl Default for LoadShedder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadShedder {
+    pub fn new() -> Self {
+        Self {
+            slots: Mutex::new(None),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Admits a request under `limit`, or `None` when every slot is taken.
+    ///
+    /// A changed limit gets a fresh semaphore; requests admitted under the
+    /// old one finish normally but no longer count against the cap.
+    pub fn try_admit(&self, limit: Option<usize>) -> Option<InFlight> {
+        let permit = match limit {
+            Some(limit) => {
+                let limit = limit.max(1);
+                let semaphore = {
+                    let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                    match slots.as_ref() {
+                        Some((current, semaphore)) if *current == limit => semaphore.clone(),
+                        _ => {
+                            let semaphore = Arc::new(Semaphore::new(limit));
+                            *slots = Some((limit, semaphore.clone()));
+                            semaphore
+                        }
+                    }
+                };
+                Some(semaphore.try_acquire_owned().ok()?)
+            }
+            None => None,
+        };
+
+        let now = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        crate::metrics::record_in_flight_requests(now);
+        Some(InFlight {
+            _permit: permit,
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    /// Requests currently admitted.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+/// Answers 503 at once when `load_shedding.max_in_flight` requests are
+/// already being served, rather than letting them queue behind a slow
+/// upstream.
+pub async fn load_shedding_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let shedding = &config.middleware.load_shedding;
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    if shedding.exempt_health_checks && HEALTH_PATHS.contains(&path) {
+        return next.run(request).await;
+    }
+
+    let Some(in_flight) = state.load_shedder.try_admit(shedding.max_in_flight) else {
+        let limit = shedding.max_in_flight.unwrap_or_default();
+        warn!(limit, path, "Shedding load: too many requests in flight");
+        crate::metrics::record_load_shed(path);
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server_busy",
+            format!("The gateway is serving its limit of {} requests; retry shortly", limit),
+        )
+        .for_request(request.extensions())
+        .into_response();
+    };
+
+    // Counted until the response body has been fully sent
+    let (parts, body) = next.run(request).await.into_parts();
+    let body = CountingBody::new(body, move |_| drop(in_flight));
+    Response::from_parts(parts, Body::new(body))
+}
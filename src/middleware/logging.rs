@@ -1,11 +1,483 @@
-// TODO: Implement custom logging middleware
-// This will be implemented in Phase 1 completion
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use std::{
+    borrow::Cow,
+    io::Write,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    fmt::{self, Write as _},
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    info, warn, Event, Subscriber,
+};
+use tracing_subscriber::{
+    filter::filter_fn,
+    fmt::MakeWriter,
+    layer::{self, Layer},
+    registry::LookupSpan,
+};
 
-use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use crate::{
+    config::{LogFormat, RouteLogLevel},
+    coordination::RequestGeneration,
+    features::Feature,
+    middleware::{
+        auth::Claims,
+        canary::Backend,
+        rate_limit::client_ip,
+        recording::{route_label, CountingBody},
+        request_id::RequestId,
+        timing::{RequestTiming, TimingBreakdown},
+    },
+    privacy::redaction::Redactor,
+    upstream::encoding::{self, ContentCoding},
+    util::hash::unit_interval,
+    AppState,
+};
 
-#[allow(dead_code)]
-pub async fn logging_middleware(request: Request, next: Next) -> Result<Response, StatusCode> {
-    // TODO: Implement custom request/response logging
-    // For now, rely on tower-http TraceLayer
-    Ok(next.run(request).await)
+/// What an [`ObservedBody`] has seen go through it.
+#[derive(Debug, Default)]
+struct Observed {
+    bytes: u64,
+    /// The first bytes, up to the body's `keep`.
+    head: Vec<u8>,
+}
+
+impl Observed {
+    /// The body as logged, and whether it was cut short. A body seen whole
+    /// is decoded first; one cut short is logged only if it isn't encoded,
+    /// since the start of an encoded body can't be decoded on its own.
+    fn render(&self, headers: &HeaderMap, limit: usize, max_decoded: u64) -> Option<(String, bool)> {
+        let seen_whole = self.bytes == self.head.len() as u64;
+        let decoded = if seen_whole {
+            encoding::inspect("body_logging", headers, &self.head, max_decoded)?
+        } else if matches!(ContentCoding::from_headers(headers), Ok(ContentCoding::Identity)) {
+            Cow::Borrowed(self.head.as_slice())
+        } else {
+            return None;
+        };
+        let shown = &decoded[..decoded.len().min(limit)];
+        let truncated = !seen_whole || shown.len() < decoded.len();
+        Some((String::from_utf8_lossy(shown).into_owned(), truncated))
+    }
+}
+
+pin_project! {
+    /// Body wrapper that counts the bytes read through it and keeps a copy
+    /// of the first `keep` of them, so a body can be logged without being
+    /// buffered.
+    struct ObservedBody<B> {
+        #[pin]
+        inner: B,
+        keep: usize,
+        observed: Arc<Mutex<Observed>>,
+    }
+}
+
+impl<B> ObservedBody<B> {
+    fn new(inner: B, keep: usize, observed: Arc<Mutex<Observed>>) -> Self {
+        Self { inner, keep, observed }
+    }
+}
+
+impl<B> http_body::Body for ObservedBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        if let Some(data) = frame.as_ref().and_then(|frame| frame.as_ref().ok()).and_then(Frame::data_ref) {
+            let mut observed = this.observed.lock().expect("observed body lock");
+            observed.bytes += data.len() as u64;
+            let room = this.keep.saturating_sub(observed.head.len());
+            observed.head.extend_from_slice(&data[..data.len().min(room)]);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Whether a body of this `Content-Type` is text worth logging. Bodies
+/// without one are taken to be.
+fn is_textual(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return true;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/x-ndjson"
+                | "application/xml"
+                | "application/x-www-form-urlencoded"
+                | "application/javascript"
+                | "application/graphql"
+                | "application/yaml"
+        )
+}
+
+/// Headers to decode a captured body with, when `capture` is on and the
+/// body is text.
+fn capturing(capture: bool, headers: &HeaderMap) -> Option<HeaderMap> {
+    (capture && is_textual(headers)).then(|| headers.clone())
+}
+
+/// An access log entry in Apache's combined log format, followed by the
+/// request id and the backend that served the request, each `-` when
+/// unknown. The request line has the path without its query, as in the JSON
+/// format.
+struct CombinedEntry {
+    client_ip: Option<String>,
+    user: Option<String>,
+    received: DateTime<Utc>,
+    request_line: String,
+    status: u16,
+    bytes_out: u64,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+    backend: Backend,
+}
+
+/// Target of combined format access lines, which [`combined_layers`] write
+/// bare.
+pub const ACCESS_LOG_TARGET: &str = "project_gateway::access_log";
+
+/// Logging for the combined format: access lines exactly as formatted, one
+/// per line, and every other event through the compact formatter.
+pub fn combined_layers<S, W>(writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Clone + Send + Sync + 'static,
+{
+    let rest = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_writer(writer.clone())
+        .with_filter(filter_fn(|metadata| metadata.target() != ACCESS_LOG_TARGET));
+    AccessLines { writer }.and_then(rest)
+}
+
+/// Writes the message of each [`ACCESS_LOG_TARGET`] event, without a
+/// timestamp, level, target or fields around it.
+struct AccessLines<W> {
+    writer: W,
+}
+
+impl<S, W> Layer<S> for AccessLines<W>
+where
+    S: Subscriber,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+        if event.metadata().target() != ACCESS_LOG_TARGET {
+            return;
+        }
+        let mut line = Message::default();
+        event.record(&mut line);
+        line.0.push('\n');
+        let _ = self.writer.make_writer().write_all(line.0.as_bytes());
+    }
+}
+
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        }
+    }
+}
+
+/// `value` in double quotes with quotes and backslashes escaped, or `"-"`.
+fn quoted(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => "\"-\"".to_string(),
+    }
+}
+
+impl fmt::Display for CombinedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} [{}] {} {} {} {} {} {} {}",
+            self.client_ip.as_deref().unwrap_or("-"),
+            self.user.as_deref().unwrap_or("-"),
+            self.received.format("%d/%b/%Y:%H:%M:%S %z"),
+            quoted(Some(&self.request_line)),
+            self.status,
+            match self.bytes_out {
+                0 => "-".to_string(),
+                bytes => bytes.to_string(),
+            },
+            quoted(self.referer.as_deref()),
+            quoted(self.user_agent.as_deref()),
+            quoted(self.request_id.as_deref()),
+            self.backend.as_str(),
+        )
+    }
+}
+
+/// Whether the access log keeps a request sampled at `rate`. Decided on a
+/// hash of its id, so every replica makes the same call for the same
+/// request; one without an id is kept.
+fn sampled(request_id: Option<&RequestId>, rate: f64) -> bool {
+    let Some(request_id) = request_id.filter(|_| rate < 1.0) else {
+        return true;
+    };
+    unit_interval(&[request_id.as_str()]) < rate
+}
+
+/// Warns about, and counts, a request that took longer than `threshold`
+/// from when the gateway received it to its response head, and says
+/// whether it did. A zero threshold never warns; `quiet` counts without
+/// warning.
+fn warn_if_slow(
+    threshold: Duration,
+    route: &str,
+    received_at: Instant,
+    response: &Response,
+    breakdown: Option<TimingBreakdown>,
+    quiet: bool,
+) -> bool {
+    let elapsed = received_at.elapsed();
+    if threshold.is_zero() || elapsed <= threshold {
+        return false;
+    }
+    crate::metrics::record_slow_request(route);
+    if quiet {
+        return true;
+    }
+    let backend = response.extensions().get::<Backend>().copied().unwrap_or(Backend::Rust);
+    warn!(
+        target: "slow_request",
+        route,
+        backend = backend.as_str(),
+        status = response.status().as_u16(),
+        latency_ms = elapsed.as_millis() as u64,
+        threshold_ms = threshold.as_millis() as u64,
+        gateway_ms = breakdown.map(|b| b.gateway_ms()),
+        upstream_ms = breakdown.and_then(|b| b.upstream_ms()),
+        auth_ms = breakdown.map(|b| b.auth_ms()),
+        queue_ms = breakdown.map(|b| b.queue_ms()),
+        "Request exceeded the slow request threshold"
+    );
+    true
+}
+
+/// Logs each request once its response has been sent, and optionally its
+/// headers and bodies, subject to both the logging config and any runtime
+/// feature overrides. Bodies are never buffered: the first `max_body_size`
+/// bytes are copied as they stream through, and binary content types are
+/// left out. Headers and bodies are redacted before they are logged. Slow
+/// requests are warned about even while the access log is off. Successful
+/// requests may be sampled. `route_levels` can quiet a path or turn on
+/// headers and bodies for it, and is read per request so a reload applies
+/// at once. With the `combined` format the event's message
+/// is the combined log line, and only the optional headers and bodies are
+/// added as fields.
+pub async fn logging_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let logging = &config.middleware.logging;
+    let overrides = &state.feature_overrides;
+
+    let slow_threshold = logging.slow_request_threshold.get();
+    let route = route_label(&request);
+    let timing = request.extensions().get::<RequestTiming>().cloned();
+    let start = Instant::now();
+    let received_at = timing.as_ref().map_or(start, RequestTiming::received_at);
+
+    // A route's level, when it has one, decides over `enabled`, but a
+    // runtime override of the feature decides over both
+    let level = logging.route_level(request.uri().path());
+    let debug = level == Some(RouteLogLevel::Debug);
+    let log_requests = overrides.override_value(Feature::RequestLogging).unwrap_or(match level {
+        Some(level) => level != RouteLogLevel::Off,
+        None => logging.enabled,
+    });
+    if !log_requests {
+        if slow_threshold.is_zero() {
+            return next.run(request).await;
+        }
+        let response = next.run(request).await;
+        let breakdown = timing.map(|timing| timing.breakdown());
+        let quiet = level == Some(RouteLogLevel::Off);
+        warn_if_slow(slow_threshold, &route, received_at, &response, breakdown, quiet);
+        return response;
+    }
+    let body_logging = overrides.override_value(Feature::BodyLogging);
+    let log_request_body = body_logging.unwrap_or(debug || logging.include_request_body);
+    let log_response_body = body_logging.unwrap_or(debug || logging.include_response_body);
+    let log_headers = logging.include_headers || (debug && body_logging != Some(false));
+
+    let received = Utc::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let request_line = format!("{} {} {:?}", method, path, request.version());
+    let referer = request
+        .headers()
+        .get(header::REFERER)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+    let request_id = request.extensions().get::<RequestId>().cloned();
+    let client_ip = client_ip(request.headers(), request.extensions(), &config.middleware.rate_limiting.trusted_proxies);
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+    let limit = logging.max_body_size.bytes() as usize;
+    let max_decoded = config.middleware.decompression.max_decoded_size.bytes();
+    let redactor = Redactor::new(logging);
+    let logged_request_headers = log_headers.then(|| redactor.headers(request.headers()));
+
+    let request_headers = capturing(log_request_body, request.headers());
+    let request_observed = Arc::new(Mutex::new(Observed::default()));
+    let (parts, body) = request.into_parts();
+    let keep = if request_headers.is_some() { limit } else { 0 };
+    let body = ObservedBody::new(body, keep, request_observed.clone());
+    let request = Request::from_parts(parts, Body::new(body));
+
+    let mut response = next.run(request).await;
+    let latency_ms = start.elapsed().as_millis();
+
+    // Taken once and handed to the Server-Timing layer so both agree
+    let breakdown = timing.map(|timing| timing.breakdown());
+    if let Some(breakdown) = breakdown {
+        response.extensions_mut().insert(breakdown);
+    }
+    let slow = warn_if_slow(slow_threshold, &route, received_at, &response, breakdown, false);
+    if level == Some(RouteLogLevel::Error) && !response.status().is_server_error() {
+        return response;
+    }
+
+    // Only successful requests are sampled; errors and slow ones always show
+    let sample_rate = config
+        .route(method.as_str(), &route)
+        .and_then(|route| route.log_sample_rate)
+        .unwrap_or(logging.sample_rate);
+    if response.status().is_success() && !slow && !debug && !sampled(request_id.as_ref(), sample_rate) {
+        crate::metrics::record_access_log_suppressed(&route);
+        return response;
+    }
+
+    let generation = response.extensions().get::<RequestGeneration>().map(|generation| generation.0);
+    let user = response
+        .extensions()
+        .get::<Claims>()
+        .map(|claims| state.pseudonymizer.pseudonymize(&claims.sub));
+    let status = response.status().as_u16();
+    let mut combined = (logging.format == LogFormat::Combined).then(|| CombinedEntry {
+        client_ip: client_ip.clone(),
+        user: user.clone(),
+        received,
+        request_line,
+        status,
+        bytes_out: 0,
+        referer,
+        user_agent: user_agent.clone(),
+        request_id: request_id.as_ref().map(|id| id.as_str().to_string()),
+        backend: response.extensions().get::<Backend>().copied().unwrap_or(Backend::Rust),
+    });
+
+    let logged_response_headers = log_headers.then(|| redactor.headers(response.headers()));
+    let response_headers = capturing(log_response_body, response.headers());
+    let response_observed = Arc::new(Mutex::new(Observed::default()));
+    let (parts, body) = response.into_parts();
+    let keep = if response_headers.is_some() { limit } else { 0 };
+    let body = ObservedBody::new(body, keep, response_observed.clone());
+    let body = CountingBody::new(body, move |bytes_out| {
+        let request_observed = request_observed.lock().expect("observed body lock");
+        let request_body = request_headers
+            .as_ref()
+            .and_then(|headers| request_observed.render(headers, limit, max_decoded))
+            .map(|(body, truncated)| (redactor.body(&body).into_owned(), truncated));
+        let response_body = response_headers
+            .as_ref()
+            .and_then(|headers| response_observed.lock().expect("observed body lock").render(headers, limit, max_decoded))
+            .map(|(body, truncated)| (redactor.body(&body).into_owned(), truncated));
+
+        if let Some(entry) = combined.as_mut() {
+            entry.bytes_out = bytes_out;
+            info!(target: ACCESS_LOG_TARGET, "{}", entry);
+            // Headers and bodies have no place in the combined line, so
+            // they follow it as an event of their own
+            let detailed = logged_request_headers.is_some()
+                || logged_response_headers.is_some()
+                || request_body.is_some()
+                || response_body.is_some();
+            if detailed {
+                info!(
+                    request_id = entry.request_id.as_deref(),
+                    request_headers = logged_request_headers.as_deref(),
+                    response_headers = logged_response_headers.as_deref(),
+                    request_body = request_body.as_ref().map(|(body, _)| body.as_str()),
+                    request_body_truncated = request_body.as_ref().map(|(_, truncated)| *truncated),
+                    response_body = response_body.as_ref().map(|(body, _)| body.as_str()),
+                    response_body_truncated = response_body.as_ref().map(|(_, truncated)| *truncated),
+                    "Request detail"
+                );
+            }
+            return;
+        }
+        info!(
+            method = %method,
+            path = %path,
+            request_id = request_id.as_ref().map(RequestId::as_str),
+            user = user.as_deref(),
+            client_ip = client_ip.as_deref(),
+            user_agent = user_agent.as_deref(),
+            rollout_generation = generation,
+            status = status,
+            latency_ms = latency_ms,
+            bytes_in = request_observed.bytes,
+            bytes_out = bytes_out,
+            gateway_ms = breakdown.map(|b| b.gateway_ms()),
+            upstream_ms = breakdown.and_then(|b| b.upstream_ms()),
+            auth_ms = breakdown.map(|b| b.auth_ms()),
+            queue_ms = breakdown.map(|b| b.queue_ms()),
+            request_headers = logged_request_headers.as_deref(),
+            response_headers = logged_response_headers.as_deref(),
+            request_body = request_body.as_ref().map(|(body, _)| body.as_str()),
+            request_body_truncated = request_body.as_ref().map(|(_, truncated)| *truncated),
+            response_body = response_body.as_ref().map(|(body, _)| body.as_str()),
+            response_body_truncated = response_body.as_ref().map(|(_, truncated)| *truncated),
+            "Request completed"
+        );
+    });
+
+    Response::from_parts(parts, Body::new(body))
 }
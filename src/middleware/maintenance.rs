@@ -0,0 +1,43 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, Extensions, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::debug;
+
+use crate::{maintenance::ActiveMaintenance, routes::error::ApiError, AppState};
+
+/// `Retry-After` as an HTTP-date.
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// 503 telling the client when the window ends.
+fn unavailable(window: &ActiveMaintenance, extensions: &Extensions) -> Response {
+    let error = ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "maintenance", window.message.clone())
+        .for_request(extensions)
+        .with_detail("route_selector", window.route_selector.clone())
+        .with_detail("ends_at", json!(window.ends_at));
+    ([(header::RETRY_AFTER, http_date(window.ends_at))], error).into_response()
+}
+
+/// Turns away requests to routes with a maintenance window in force. The
+/// windows themselves are opened and closed by the maintenance scheduler.
+pub async fn maintenance_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().as_str();
+    let path = request.uri().path();
+    let route = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str());
+    let blocking = route
+        .and_then(|route| state.maintenance.blocking(method, route))
+        .or_else(|| state.maintenance.blocking(method, path));
+    let Some(window) = blocking else {
+        return next.run(request).await;
+    };
+
+    debug!(path, route_selector = %window.route_selector, "Rejected request to a route under maintenance");
+    crate::metrics::record_maintenance_rejection(route.unwrap_or(path));
+    unavailable(&window, request.extensions())
+}
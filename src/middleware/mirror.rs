@@ -1,14 +1,51 @@
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, Response},
+    extract::{MatchedPath, State},
+    http::{header::CONTENT_ENCODING, Request, Response},
     middleware::Next,
 };
-use std::time::Instant;
-use tokio::time::timeout;
-use tracing::{info, error};
+use std::{sync::Arc, time::Instant};
+use tokio::sync::oneshot;
+use tracing::debug;
 
-use crate::{metrics::MIRROR_METRICS, AppState};
+use crate::{
+    coordination::RequestGeneration,
+    features::Feature,
+    middleware::{
+        cancellation::ClientConnection,
+        identity,
+        recording::CountingBody,
+        request_id::{self, RequestId},
+    },
+    mirror::{
+        tee::{tee, BodyDigest},
+        MirrorJob,
+    },
+    tls::client_cert::{self, ClientCertIdentity},
+    upstream::encoding::{self, ContentCoding},
+    AppState,
+};
+
+const COMPARISON: &str = "mirror_comparison";
+
+/// Decoded size of the main response: its streamed size when it isn't
+/// encoded, otherwise the length of its captured body once decoded.
+fn main_decoded_size(
+    headers: &axum::http::HeaderMap,
+    wire_bytes: u64,
+    body: Option<&BodyDigest>,
+    max_decoded: u64,
+) -> Option<i64> {
+    if let Ok(ContentCoding::Identity) = ContentCoding::from_headers(headers) {
+        return Some(wire_bytes as i64);
+    }
+    let body = body?;
+    if !body.is_complete() {
+        crate::metrics::record_inspection_skipped(COMPARISON, "decompression_limit");
+        return None;
+    }
+    encoding::inspect(COMPARISON, headers, &body.prefix, max_decoded).map(|decoded| decoded.len() as i64)
+}
 
 pub async fn mirror_middleware(
     State(state): State<AppState>,
@@ -18,69 +55,107 @@ pub async fn mirror_middleware(
     let start = Instant::now();
     let current_config = state.config_watcher.get_config().await;
     
-    if !current_config.mirror.enabled {
+    if !state.feature_overrides.is_enabled(Feature::Mirror, &current_config) {
+        return next.run(request).await;
+    }
+
+    let sample_percentage = current_config.mirror.sample_percentage_at(state.clock.now());
+    crate::metrics::record_mirror_sample_percentage(sample_percentage);
+    if rand::random::<f64>() * 100.0 >= sample_percentage {
         return next.run(request).await;
     }
 
     // Clone request data for mirroring
     let method = request.method().clone();
     let uri = request.uri().clone();
-    let headers = request.headers().clone();
-    
+    let mut headers = request.headers().clone();
+    client_cert::apply(
+        &mut headers,
+        request.extensions().get::<Arc<ClientCertIdentity>>().map(Arc::as_ref),
+        current_config.mirror.client_cert_forwarding,
+    );
+    identity::apply(&mut headers, request.extensions(), &current_config.middleware.auth.identity_headers);
+    request_id::apply(&mut headers, request.extensions(), &current_config.middleware.request_id);
+    let request_id = request.extensions().get::<RequestId>().map(|request_id| request_id.as_str().to_string());
+    let route_path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string())
+        .unwrap_or_else(|| uri.path().to_string());
+
+    let connection = request.extensions().get::<ClientConnection>().cloned();
+
     // Process main request first
     let response = next.run(request).await;
     let main_latency = start.elapsed();
-    
-    // Fire and forget mirror request
-    let mirror_url = format!("{}{}", current_config.mirror.base_url, uri.path_and_query().map(|pq| pq.as_str()).unwrap_or(""));
-    let client = reqwest::Client::new();
-    
+    let main_status = response.status();
+
+    // Count the main response body as it streams, and tee it to the
+    // comparison. Encoded bodies are compared decoded, so more of them is
+    // kept.
+    let max_decoded = current_config.middleware.decompression.max_decoded_size.bytes();
+    let compared_prefix = current_config.mirror.compared_prefix.bytes() as usize;
+    let (main_size_tx, main_size_rx) = oneshot::channel();
+    let (parts, body) = response.into_parts();
+    let main_headers = parts.headers.clone();
+    let rollout_generation = parts.extensions.get::<RequestGeneration>().map(|generation| generation.0);
+    let capture_limit = match ContentCoding::from_headers(&main_headers) {
+        Ok(ContentCoding::Identity) => compared_prefix,
+        _ => compared_prefix.max(max_decoded as usize),
+    };
+    let content_encoding = main_headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let (body, main_body) = tee(body, capture_limit, content_encoding);
+    let body = CountingBody::new(body, move |bytes| {
+        let _ = main_size_tx.send(bytes);
+    });
+    let response = Response::from_parts(parts, Body::new(body));
+    let size_wait = current_config.mirror.timeout.get();
+
+    // Queued once the main body is done, so the comparison has its size
+    let mut job = MirrorJob {
+        method: method.to_string(),
+        path_and_query: uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("").to_string(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect(),
+        route: route_path,
+        main_status: main_status.as_u16(),
+        main_latency_ms: main_latency.as_secs_f64() * 1000.0,
+        main_bytes: None,
+        main_body: None,
+        rollout_generation,
+        request_id,
+    };
+    let queue = state.mirror_queue.clone();
     tokio::spawn(async move {
-        let mirror_start = Instant::now();
-        
-        let mut mirror_request = client.request(method.clone(), &mirror_url);
-        
-        // Copy headers
-        for (key, value) in headers.iter() {
-            if key != "host" {
-                mirror_request = mirror_request.header(key, value);
+        let main = tokio::time::timeout(size_wait, async { tokio::join!(main_size_rx, main_body.finish()) }).await;
+        let (bytes, mut body) = match main {
+            Ok((bytes, Ok(body))) => (bytes.ok(), Some(body)),
+            Ok((bytes, Err(abort))) => {
+                crate::metrics::record_mirror_capture_abandoned(abort.as_str());
+                (bytes.ok(), None)
             }
+            Err(_) => (None, None),
+        };
+        job.main_bytes = bytes.and_then(|bytes| main_decoded_size(&main_headers, bytes, body.as_ref(), max_decoded));
+        if let Some(body) = &mut body {
+            body.prefix.truncate(compared_prefix);
         }
-        
-        // Add mirror header
-        mirror_request = mirror_request.header("X-Mirrored-By", "Rust-Gateway");
-        
-        // Send mirror request
-        match mirror_request.send().await {
-            Ok(mirror_response) => {
-                let mirror_latency = mirror_start.elapsed();
-                let status = mirror_response.status().as_u16() as i32;
-                
-                // Record metrics
-                MIRROR_METRICS.requests_total.increment(1);
-                MIRROR_METRICS.latency_seconds.record(mirror_latency.as_secs_f64());
-                
-                // Log the mirror result
-                info!(
-                    path = uri.path(),
-                    mirror_status = status,
-                    mirror_latency_ms = mirror_latency.as_millis(),
-                    main_latency_ms = main_latency.as_millis(),
-                    latency_delta_ms = mirror_latency.as_millis() as i64 - main_latency.as_millis() as i64,
-                    "Mirror request completed"
-                );
-            }
-            Err(e) => {
-                MIRROR_METRICS.failures_total.increment(1);
-                error!(
-                    path = uri.path(),
-                    error = %e,
-                    "Mirror request failed"
-                );
-            }
+        job.main_body = body;
+        // Nothing to compare with a response nobody received
+        if connection.is_some_and(|connection| connection.is_gone()) {
+            debug!(path = uri.path(), "Mirror request skipped: the client disconnected");
+            return;
+        }
+        if !queue.push(job) {
+            debug!(path = uri.path(), "Mirror request dropped by the mirror queue");
         }
     });
-    
+
     response
 }
 
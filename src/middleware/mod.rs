@@ -1,6 +1,32 @@
 // Middleware modules
 pub mod auth;
+pub mod auth_audit;
+pub mod authorization;
+pub mod basic_auth;
+pub mod body_limit;
 pub mod canary;
+pub mod cancellation;
+pub mod capture;
+pub mod csrf;
+pub mod header_limits;
+pub mod identity;
+pub mod introspection;
+pub mod ip_filter;
+pub mod jwks;
+pub mod load_shedding;
 pub mod logging;
+pub mod maintenance;
 pub mod mirror;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod quota;
 pub mod rate_limit;
+pub mod read_only;
+pub mod recording;
+pub mod request_id;
+pub mod request_signing;
+pub mod slo;
+pub mod timeout;
+pub mod timing;
+pub mod trace_sampling;
+pub mod versioning;
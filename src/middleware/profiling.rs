@@ -0,0 +1,52 @@
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+
+use crate::{
+    middleware::timing::{RequestTiming, TimingBreakdown},
+    profiling::Profile,
+    AppState,
+};
+
+/// Profiles sampled and explicitly requested requests. Sits just inside
+/// the recording layer so the profile covers everything the gateway does.
+pub async fn profiling_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let profiling = &config.profiling;
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let Some(trigger) = state.profiler.trigger(profiling, request.headers(), peer) else {
+        return next.run(request).await;
+    };
+    let Some(_permit) = state.profiler.start(profiling.start_budget.get()).await else {
+        crate::metrics::record_profile(trigger.as_str(), "start_budget");
+        return next.run(request).await;
+    };
+
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let timing = request.extensions().get::<RequestTiming>().cloned();
+
+    let response = next.run(request).await;
+
+    // Taken afresh rather than reusing the access log's, which misses the
+    // outer layers
+    let breakdown = timing
+        .map(|timing| timing.breakdown())
+        .or_else(|| response.extensions().get::<TimingBreakdown>().copied());
+    if let Some(breakdown) = breakdown {
+        let profile = Profile::new(&method, &route, response.status().as_u16(), trigger, &breakdown);
+        state.profiler.store(profile, profiling.max_profiles);
+        crate::metrics::record_profile(trigger.as_str(), "captured");
+    }
+    response
+}
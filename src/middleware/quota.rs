@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::{middleware::rate_limit::API_KEY_HEADER, quota, routes::error::ApiError, AppState};
+
+/// Holds requests carrying a listed `X-API-Key` to the key's daily and
+/// monthly quotas, answering 429 `quota_exhausted` once one is used up.
+/// Inside the rate limiter, so requests it turns away aren't counted. When
+/// the quota store can't be reached requests are let through uncounted.
+pub async fn quota_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let auth = &config.middleware.auth;
+    let key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|presented| quota::api_key(auth, presented.trim()));
+    let Some(key) = key else {
+        return next.run(request).await;
+    };
+
+    let exhausted = match state.quotas.take(key, auth.quota_reset_hour, state.clock.now()).await {
+        Ok(Ok(())) => return next.run(request).await,
+        Ok(Err(exhausted)) => exhausted,
+        Err(e) => {
+            crate::metrics::record_quota_store_error();
+            warn!(key_id = %key.id, "Letting a request through uncounted, quota store unavailable: {:#}", e);
+            return next.run(request).await;
+        }
+    };
+
+    let usage = &exhausted.usage;
+    crate::metrics::record_quota_exhausted(&key.id, usage.period.as_str());
+    warn!(
+        key_id = %key.id,
+        period = %usage.period,
+        limit = usage.limit,
+        resets_at = %usage.resets_at,
+        path = request.uri().path(),
+        "API key exhausted its quota"
+    );
+    let retry_after = exhausted.retry_after.as_secs().max(1);
+    let rejection = ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "quota_exhausted",
+        format!("The {} quota of {} requests for this API key is used up", usage.period, usage.limit),
+    )
+    .for_request(request.extensions())
+    .with_detail("period", usage.period.as_str())
+    .with_detail("limit", usage.limit)
+    .with_detail("resets_at", usage.resets_at.clone());
+    ([(header::RETRY_AFTER, retry_after.to_string())], rejection).into_response()
+}
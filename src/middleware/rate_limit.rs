@@ -1,11 +1,614 @@
-// TODO: Implement rate limiting middleware
-// This will be implemented in Phase 1 completion
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, Extensions, HeaderMap, HeaderValue, Response, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use metrics::{counter, gauge};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
 
-use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use crate::{
+    config::{staged::ConfigVariant, AppConfig, MissingKeyFallback, RateLimitKey, RateLimitingConfig},
+    memory::{
+        expiring::{ExpiringMap, Sweep, SweepStats},
+        MemoryConsumer,
+    },
+    metrics::labels::{self, Dimension},
+    middleware::{auth::Claims, recording::CountingBody},
+    privacy::Pseudonymizer,
+    routes::error::ApiError,
+    AppState,
+};
 
-#[allow(dead_code)]
-pub async fn rate_limit_middleware(request: Request, next: Next) -> Result<Response, StatusCode> {
-    // TODO: Implement rate limiting logic
-    // For now, pass through all requests
-    Ok(next.run(request).await)
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+pub const RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// Clients reported individually in the concurrency gauge.
+const TOP_CLIENTS: usize = 10;
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a client's semaphore is kept after its last request finishes.
+const IDLE_TTL: Duration = Duration::from_secs(60);
+/// Client identities tracked at once; idle clients are evicted past this.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// The client's address: the peer's, or when the peer is one of
+/// `trusted_proxies`, the right-most `X-Forwarded-For` hop that isn't. A
+/// client can put anything in the header, but only the hops appended by
+/// trusted proxies are believed.
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions, trusted_proxies: &[String]) -> Option<String> {
+    let peer = extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip())?;
+    let trusted = |ip: &IpAddr| crate::profiling::is_trusted(trusted_proxies, *ip);
+    if !trusted(&peer) {
+        return Some(peer.to_string());
+    }
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .collect();
+    let client = hops
+        .iter()
+        .rev()
+        .find(|hop| !hop.parse::<IpAddr>().is_ok_and(|ip| trusted(&ip)))
+        .or(hops.first());
+    Some(client.map_or_else(|| peer.to_string(), |hop| hop.to_string()))
+}
+
+/// Who a request is attributed to for rate and concurrency limiting.
+///
+/// `key` is used for limiting and tier lookup; `label` is safe to put in
+/// metrics and logs (header values such as API keys are fingerprinted and
+/// subjects pseudonymized).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    pub key: String,
+    pub label: String,
+}
+
+/// Shared bucket for requests without a `key_by` key under
+/// [`MissingKeyFallback::Anonymous`].
+pub const ANONYMOUS_KEY: &str = "anonymous";
+
+/// What a key extractor can see of a request.
+pub struct KeySource<'a> {
+    pub headers: &'a HeaderMap,
+    pub extensions: &'a Extensions,
+    pub pseudonymizer: &'a Pseudonymizer,
+    pub trusted_proxies: &'a [String],
+}
+
+impl<'a> KeySource<'a> {
+    pub fn of<B>(request: &'a Request<B>, pseudonymizer: &'a Pseudonymizer, trusted_proxies: &'a [String]) -> Self {
+        Self {
+            headers: request.headers(),
+            extensions: request.extensions(),
+            pseudonymizer,
+            trusted_proxies,
+        }
+    }
+}
+
+/// Derives a client's rate-limit key from a request.
+pub trait KeyExtractor {
+    /// `None` when the request doesn't carry what this extractor keys on.
+    fn extract(&self, source: &KeySource<'_>) -> Option<ClientIdentity>;
+}
+
+fn fingerprint(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    digest[..4].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl KeyExtractor for RateLimitKey {
+    fn extract(&self, source: &KeySource<'_>) -> Option<ClientIdentity> {
+        match self {
+            RateLimitKey::Ip => {
+                let ip = client_ip(source.headers, source.extensions, source.trusted_proxies)?;
+                Some(ClientIdentity {
+                    label: format!("ip:{}", ip),
+                    key: ip,
+                })
+            }
+            RateLimitKey::JwtSubject => {
+                let claims = source.extensions.get::<Claims>()?;
+                Some(ClientIdentity {
+                    key: claims.sub.clone(),
+                    label: format!("sub:{}", source.pseudonymizer.pseudonymize(&claims.sub)),
+                })
+            }
+            RateLimitKey::Header(name) => {
+                let value = source.headers.get(name.as_str())?.to_str().ok()?.trim();
+                (!value.is_empty()).then(|| ClientIdentity {
+                    key: value.to_string(),
+                    label: format!("{}:{}", name, fingerprint(value)),
+                })
+            }
+        }
+    }
+}
+
+/// Several keys joined with `|` into one, in order. Missing when any of
+/// them is, so a request is never bucketed by a partial key.
+impl KeyExtractor for [RateLimitKey] {
+    fn extract(&self, source: &KeySource<'_>) -> Option<ClientIdentity> {
+        let parts = self
+            .iter()
+            .map(|key| key.extract(source))
+            .collect::<Option<Vec<_>>>()?;
+        if let [single] = parts.as_slice() {
+            return Some(single.clone());
+        }
+        Some(ClientIdentity {
+            key: parts.iter().map(|part| part.key.as_str()).collect::<Vec<_>>().join("|"),
+            label: parts.iter().map(|part| part.label.as_str()).collect::<Vec<_>>().join("|"),
+        })
+    }
+}
+
+/// The keying without `key_by`: the authenticated JWT subject, then
+/// `X-API-Key`, then the [`client_ip`].
+pub struct DefaultKey;
+
+impl KeyExtractor for DefaultKey {
+    fn extract(&self, source: &KeySource<'_>) -> Option<ClientIdentity> {
+        RateLimitKey::JwtSubject.extract(source).or_else(|| {
+            let api_key = source.headers.get(API_KEY_HEADER)?.to_str().ok()?;
+            Some(ClientIdentity {
+                key: api_key.to_string(),
+                label: format!("key:{}", fingerprint(api_key)),
+            })
+        })
+    }
+}
+
+impl ClientIdentity {
+    /// The default keying, falling back to the client IP.
+    pub fn of<B>(request: &Request<B>, pseudonymizer: &Pseudonymizer, trusted_proxies: &[String]) -> Self {
+        let source = KeySource::of(request, pseudonymizer, trusted_proxies);
+        DefaultKey.extract(&source).unwrap_or_else(|| Self::by_ip(&source))
+    }
+
+    /// Keyed as `rate_limiting` says: by `key_by` when set, with requests
+    /// lacking a key sent to the `missing_key` fallback.
+    pub fn keyed<B>(request: &Request<B>, rate_limiting: &RateLimitingConfig, pseudonymizer: &Pseudonymizer) -> Self {
+        if rate_limiting.key_by.is_empty() {
+            return Self::of(request, pseudonymizer, &rate_limiting.trusted_proxies);
+        }
+        let source = KeySource::of(request, pseudonymizer, &rate_limiting.trusted_proxies);
+        rate_limiting
+            .key_by
+            .extract(&source)
+            .unwrap_or_else(|| match rate_limiting.missing_key {
+                MissingKeyFallback::Ip => Self::by_ip(&source),
+                MissingKeyFallback::Anonymous => Self {
+                    key: ANONYMOUS_KEY.to_string(),
+                    label: ANONYMOUS_KEY.to_string(),
+                },
+            })
+    }
+
+    fn by_ip(source: &KeySource<'_>) -> Self {
+        RateLimitKey::Ip.extract(source).unwrap_or_else(|| Self {
+            key: "unknown".to_string(),
+            label: "ip:unknown".to_string(),
+        })
+    }
+}
+
+struct ClientSlot {
+    label: String,
+    limit: usize,
+    semaphore: Arc<Semaphore>,
+    last_used: Instant,
+}
+
+impl ClientSlot {
+    fn in_flight(&self) -> usize {
+        self.limit.saturating_sub(self.semaphore.available_permits())
+    }
+
+    fn idle(&self) -> bool {
+        // Every outstanding permit holds a clone of the semaphore
+        Arc::strong_count(&self.semaphore) == 1
+    }
+}
+
+struct Published {
+    labels: HashSet<String>,
+    at: Instant,
+}
+
+/// Per-client concurrency caps backed by one semaphore per client identity.
+///
+/// A client's semaphore expires [`IDLE_TTL`] after its last request started,
+/// but never while it has requests in flight.
+pub struct ConcurrencyLimiter {
+    clients: ExpiringMap<String, ClientSlot>,
+    published: Mutex<Published>,
+}
+
+fn slot_bytes(key: &str, slot: &ClientSlot) -> u64 {
+    (key.len() + slot.label.len() + std::mem::size_of::<ClientSlot>() + std::mem::size_of::<Semaphore>() + 32) as u64
+}
+
+impl Default for ConcurrencyLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self {
+            clients: ExpiringMap::new("concurrency_limiter", MAX_TRACKED_CLIENTS)
+                .with_weigher(|key: &String, slot| slot_bytes(key, slot))
+                .with_pin(|slot| !slot.idle()),
+            published: Mutex::new(Published {
+                labels: HashSet::new(),
+                at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes one of the client's `limit` slots, or `None` if all are in use.
+    ///
+    /// A changed limit (config reload, tier change) gets a fresh semaphore;
+    /// requests already admitted under the old limit finish normally.
+    pub fn try_acquire(&self, client: &ClientIdentity, limit: usize) -> Option<OwnedSemaphorePermit> {
+        let limit = limit.max(1);
+        let permit = self.clients.upsert(
+            &client.key,
+            IDLE_TTL,
+            || ClientSlot {
+                label: client.label.clone(),
+                limit,
+                semaphore: Arc::new(Semaphore::new(limit)),
+                last_used: Instant::now(),
+            },
+            |slot| {
+                if slot.limit != limit {
+                    slot.limit = limit;
+                    slot.semaphore = Arc::new(Semaphore::new(limit));
+                }
+                slot.last_used = Instant::now();
+                slot.semaphore.clone().try_acquire_owned().ok()
+            },
+        );
+
+        if let Ok(mut published) = self.published.try_lock() {
+            if published.at.elapsed() >= PUBLISH_INTERVAL {
+                self.publish_top_clients(&mut published);
+            }
+        }
+        permit
+    }
+
+    /// Drops semaphores for clients with nothing in flight whose last request
+    /// started more than `idle_for` ago. Returns how many were removed.
+    pub fn collect_idle(&self, idle_for: Duration) -> usize {
+        self.clients.retain(|_, slot| slot.last_used.elapsed() < idle_for)
+    }
+
+    /// Number of client identities currently tracked.
+    pub fn tracked_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Requests in flight for a client identity.
+    pub fn in_flight(&self, key: &str) -> usize {
+        self.clients
+            .read(&key.to_string(), ClientSlot::in_flight)
+            .unwrap_or(0)
+    }
+
+    /// Publishes in-flight counts for the busiest clients, zeroing clients
+    /// that dropped out of the top N so stale values don't linger.
+    fn publish_top_clients(&self, published: &mut Published) {
+        let mut busiest: Vec<(String, usize)> = Vec::new();
+        self.clients.for_each(|_, slot| {
+            let in_flight = slot.in_flight();
+            if in_flight > 0 {
+                busiest.push((slot.label.clone(), in_flight));
+            }
+        });
+        busiest.sort_by_key(|(_, in_flight)| std::cmp::Reverse(*in_flight));
+        busiest.truncate(TOP_CLIENTS);
+
+        let current: HashSet<String> = busiest.iter().map(|(label, _)| label.clone()).collect();
+        for label in published.labels.difference(&current) {
+            gauge!("gateway_client_concurrency", "client" => labels::label(Dimension::Client, label)).set(0.0);
+        }
+        for (label, in_flight) in &busiest {
+            gauge!("gateway_client_concurrency", "client" => labels::label(Dimension::Client, label))
+                .set(*in_flight as f64);
+        }
+
+        published.labels = current;
+        published.at = Instant::now();
+    }
+}
+
+impl Sweep for ConcurrencyLimiter {
+    fn sweep(&self, budget: usize) -> SweepStats {
+        self.clients.sweep(budget)
+    }
+}
+
+/// Counted against the budget but never evicted: dropping a client's
+/// semaphore would reset its in-flight count and let it exceed its cap.
+impl MemoryConsumer for ConcurrencyLimiter {
+    fn memory_usage(&self) -> u64 {
+        self.clients.memory_usage()
+    }
+
+    fn evict(&self, _bytes: u64) -> u64 {
+        0
+    }
+}
+
+struct TokenBucket {
+    per_minute: u32,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// A client's bucket as left by a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    /// Requests per minute under the rule that applied to the client.
+    pub limit: u32,
+    /// Whole tokens left in the bucket.
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset: Duration,
+}
+
+impl Quota {
+    /// Sets `X-RateLimit-Limit`, `-Remaining` and `-Reset`, the last in
+    /// seconds from now.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        let reset = self.reset.as_secs_f64().ceil() as u64;
+        for (name, value) in [
+            (LIMIT_HEADER, self.limit as u64),
+            (REMAINING_HEADER, self.remaining as u64),
+            (RESET_HEADER, reset),
+        ] {
+            headers.insert(name, HeaderValue::from(value));
+        }
+    }
+}
+
+/// A request the client's bucket had no token for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimited {
+    pub quota: Quota,
+    /// Until the next token.
+    pub retry_after: Duration,
+}
+
+/// Per-client request rates, as a token bucket per client identity holding
+/// up to a minute's worth of requests.
+///
+/// A bucket expires once it would have refilled completely, so an expired
+/// client starts again with a full bucket exactly as if it had been kept.
+pub struct RequestRateLimiter {
+    buckets: ExpiringMap<String, TokenBucket>,
+}
+
+fn bucket_bytes(key: &str) -> u64 {
+    (key.len() + std::mem::size_of::<TokenBucket>() + 32) as u64
+}
+
+impl Default for RequestRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestRateLimiter {
+    pub fn new() -> Self {
+        Self {
+            buckets: ExpiringMap::new("request_rate_limiter", MAX_TRACKED_CLIENTS)
+                .with_weigher(|key: &String, _| bucket_bytes(key)),
+        }
+    }
+
+    /// Takes a token from the client's bucket, or says how long until one
+    /// is available. Either way the bucket's state comes back for the
+    /// `X-RateLimit-*` headers.
+    pub fn try_take(&self, key: &str, per_minute: u32) -> Result<Quota, RateLimited> {
+        self.try_take_at(key, per_minute, Instant::now())
+    }
+
+    pub fn try_take_at(&self, key: &str, per_minute: u32, now: Instant) -> Result<Quota, RateLimited> {
+        let per_minute = per_minute.max(1);
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+        self.buckets.upsert_at(
+            &key.to_string(),
+            Duration::from_secs(60),
+            now,
+            || TokenBucket {
+                per_minute,
+                tokens: capacity,
+                refilled_at: now,
+            },
+            |bucket| {
+                let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+                bucket.refilled_at = now;
+                // A lowered limit takes effect at once; a raised one fills up over time
+                bucket.per_minute = per_minute;
+                let taken = bucket.tokens >= 1.0;
+                if taken {
+                    bucket.tokens -= 1.0;
+                }
+                let quota = Quota {
+                    limit: per_minute,
+                    remaining: bucket.tokens.floor() as u32,
+                    reset: Duration::from_secs_f64((capacity - bucket.tokens) / per_second),
+                };
+                if taken {
+                    Ok(quota)
+                } else {
+                    Err(RateLimited {
+                        quota,
+                        retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / per_second),
+                    })
+                }
+            },
+        )
+    }
+
+    /// Number of client identities currently tracked.
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+impl Sweep for RequestRateLimiter {
+    fn sweep(&self, budget: usize) -> SweepStats {
+        self.buckets.sweep(budget)
+    }
+}
+
+/// Counted against the budget but never evicted: dropping a bucket would
+/// hand the client a fresh minute's worth of requests.
+impl MemoryConsumer for RequestRateLimiter {
+    fn memory_usage(&self) -> u64 {
+        self.buckets.memory_usage()
+    }
+
+    fn evict(&self, _bytes: u64) -> u64 {
+        0
+    }
+}
+
+/// Short hash of a client identity for per-client metrics. Four hex digits
+/// keep the series count bounded however many clients there are; collisions
+/// merge a few clients' counts, which is fine for spotting heavy users.
+pub fn usage_label(client: &ClientIdentity, pseudonymizer: &Pseudonymizer) -> String {
+    let digest = Sha256::digest(pseudonymizer.pseudonymize(&client.key).as_bytes());
+    digest[..2].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether the `X-RateLimit-*` headers go out on this route: the route's own
+/// setting wins over `rate_limiting.headers`.
+pub fn rate_limit_headers_enabled(config: &AppConfig, method: &str, route: &str) -> bool {
+    config
+        .route(method, route)
+        .and_then(|route| route.rate_limit_headers)
+        .unwrap_or(config.middleware.rate_limiting.headers)
+}
+
+/// Caps request rate and in-flight requests per client. Excess requests are
+/// rejected with 429 rather than queued, so one consumer can't monopolize the
+/// gateway. Limits come from the staged config for clients in its share.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response<Body> {
+    let current = state.config_watcher.get_config().await;
+    let client = ClientIdentity::keyed(&request, &current.middleware.rate_limiting, &state.pseudonymizer);
+    // While rate limits are staged, each client sticks to one variant
+    let (variant, config) = state.config_watcher.config_for(&client.key).await;
+    let rate_limiting = &config.middleware.rate_limiting;
+    if !rate_limiting.enabled {
+        return with_variant(variant, next.run(request).await);
+    }
+
+    let usage = labels::label(Dimension::Client, &usage_label(&client, &state.pseudonymizer));
+    counter!("gateway_client_requests_total", "client" => usage.clone()).increment(1);
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let show_quota = rate_limit_headers_enabled(&config, request.method().as_str(), &route);
+    let with_quota = |quota: Quota, mut response: Response<Body>| {
+        if show_quota {
+            quota.apply(response.headers_mut());
+        }
+        with_variant(variant, response)
+    };
+
+    let per_minute = rate_limiting.requests_per_minute_for(&client.key);
+    let quota = match state.request_rate_limiter.try_take(&client.key, per_minute) {
+        Ok(quota) => quota,
+        Err(limited) => {
+            counter!("gateway_client_rate_limited_total", "client" => usage).increment(1);
+            warn!(
+                client = %client.label,
+                requests_per_minute = per_minute,
+                path = request.uri().path(),
+                config_variant = variant.map(|variant| variant.as_str()),
+                "Client exceeded request rate limit"
+            );
+
+            let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let rejection = ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limit_exceeded",
+                format!("At most {} requests per minute are allowed per client", per_minute),
+            )
+            .for_request(request.extensions())
+            .with_detail("retry_after_seconds", retry_after);
+            let rejection = ([(header::RETRY_AFTER, retry_after.to_string())], rejection).into_response();
+            return with_quota(limited.quota, rejection);
+        }
+    };
+
+    let Some(limit) = rate_limiting.concurrency_limit_for(&client.key) else {
+        return with_quota(quota, next.run(request).await);
+    };
+
+    let Some(permit) = state.concurrency_limiter.try_acquire(&client, limit) else {
+        counter!("gateway_client_concurrency_rejected_total", "client" => labels::label(Dimension::Client, &client.label))
+            .increment(1);
+        warn!(
+            client = %client.label,
+            limit = limit,
+            path = request.uri().path(),
+            config_variant = variant.map(|variant| variant.as_str()),
+            "Client exceeded concurrent request limit"
+        );
+
+        let rejection = ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "concurrency_limit_exceeded",
+            format!("At most {} concurrent requests are allowed per client", limit),
+        )
+        .for_request(request.extensions())
+        .into_response();
+        return with_quota(quota, rejection);
+    };
+
+    // Hold the slot until the response body has been fully sent
+    let (parts, body) = next.run(request).await.into_parts();
+    let body = CountingBody::new(body, move |_| drop(permit));
+    with_quota(quota, Response::from_parts(parts, Body::new(body)))
+}
+
+/// Counts the response against the config variant that served it, so the
+/// staged and current error rates can be compared before promoting.
+fn with_variant(variant: Option<ConfigVariant>, response: Response<Body>) -> Response<Body> {
+    if let Some(variant) = variant {
+        crate::metrics::record_config_variant_response(variant.as_str(), response.status().as_u16());
+    }
+    response
 }
@@ -0,0 +1,69 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::json;
+use tracing::debug;
+
+use crate::{config::ReadOnlyConfig, AppState};
+
+/// Problem `code` of a write turned away in read-only mode.
+pub const READ_ONLY_CODE: &str = "read_only";
+
+/// Marks a response as a read-only rejection, so SLOs and the gatekeeper
+/// don't count it against the route.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnlyRejection;
+
+fn is_write(method: &Method) -> bool {
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH | Method::DELETE)
+}
+
+/// 503 problem+json asking the client to retry the write later.
+fn unavailable(config: &ReadOnlyConfig) -> Response {
+    let problem = json!({
+        "type": "about:blank",
+        "title": "Service Unavailable",
+        "status": 503,
+        "detail": config.message,
+        "code": READ_ONLY_CODE,
+    });
+    let mut response = Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("content-type", "application/problem+json")
+        .header(header::RETRY_AFTER, config.retry_after.get().as_secs().max(1).to_string())
+        .body(Body::from(problem.to_string()))
+        .unwrap();
+    response.extensions_mut().insert(ReadOnlyRejection);
+    response
+}
+
+/// Turns away writes while `read_only` is enabled, except on exempt routes
+/// and the admin API, which has to stay writable to switch the mode off.
+/// Reads pass through untouched.
+pub async fn read_only_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !is_write(request.method()) {
+        return next.run(request).await;
+    }
+    let config = state.config_watcher.get_config().await;
+    let read_only = &config.read_only;
+    if !read_only.enabled {
+        return next.run(request).await;
+    }
+
+    let method = request.method().as_str();
+    let path = request.uri().path();
+    let route = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str());
+    let exempt = route.is_some_and(|route| route.starts_with("/admin/") || read_only.is_exempt(method, route))
+        || read_only.is_exempt(method, path);
+    if exempt {
+        return next.run(request).await;
+    }
+
+    debug!(method, path, "Rejected write in read-only mode");
+    crate::metrics::record_read_only_rejection(method);
+    unavailable(read_only)
+}
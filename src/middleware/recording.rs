@@ -0,0 +1,128 @@
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{Request, Response},
+    middleware::Next,
+};
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use crate::middleware::{canary::Backend, timing::RequestTiming};
+
+type OnComplete = Box<dyn FnOnce(u64) + Send>;
+
+pin_project! {
+    /// Body wrapper that counts the data bytes flowing through it and reports
+    /// the total exactly once: at end of stream, or on drop if the stream was
+    /// abandoned early.
+    pub struct CountingBody<B> {
+        #[pin]
+        inner: B,
+        bytes: u64,
+        on_complete: Option<OnComplete>,
+    }
+
+    impl<B> PinnedDrop for CountingBody<B> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(on_complete) = this.on_complete.take() {
+                on_complete(*this.bytes);
+            }
+        }
+    }
+}
+
+impl<B> CountingBody<B> {
+    pub fn new(inner: B, on_complete: impl FnOnce(u64) + Send + 'static) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+}
+
+impl<B> http_body::Body for CountingBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner.as_mut().poll_frame(cx));
+
+        let finished = match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    *this.bytes += data.len() as u64;
+                }
+                this.inner.is_end_stream()
+            }
+            Some(Err(_)) => false,
+            None => true,
+        };
+        if finished {
+            if let Some(on_complete) = this.on_complete.take() {
+                on_complete(*this.bytes);
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Label used for per-route metrics: the matched route template, never the raw path.
+pub fn route_label<B>(request: &Request<B>) -> String {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string())
+}
+
+/// Records request and response sizes per route and variant without buffering
+/// either body.
+pub async fn recording_middleware(request: Request<Body>, next: Next) -> Response<Body> {
+    let route = route_label(&request);
+
+    let (mut parts, body) = request.into_parts();
+    parts.extensions.insert(RequestTiming::new());
+    let request_route = route.clone();
+    let body = CountingBody::new(body, move |bytes| {
+        crate::metrics::record_request_bytes(&request_route, bytes);
+    });
+    let request = Request::from_parts(parts, Body::new(body));
+
+    let response = next.run(request).await;
+
+    let variant = response
+        .extensions()
+        .get::<Backend>()
+        .copied()
+        .unwrap_or(Backend::Rust);
+
+    let (parts, body) = response.into_parts();
+    let body = CountingBody::new(body, move |bytes| {
+        crate::metrics::record_response_bytes(&route, variant.as_str(), bytes);
+    });
+
+    Response::from_parts(parts, Body::new(body))
+}
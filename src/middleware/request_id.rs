@@ -0,0 +1,86 @@
+//! The id that correlates a request across the gateway, the legacy gateway
+//! and the mirror.
+//!
+//! A usable id sent by the client is kept; otherwise the gateway generates
+//! a UUIDv7, so generated ids sort by when the request arrived. The id is
+//! echoed in the response, included in error bodies and the request's
+//! tracing span, and forwarded with the outbound requests.
+
+use axum::{
+    extract::{Request, State},
+    http::{Extensions, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Span;
+
+use crate::{config::RequestIdConfig, AppState};
+
+/// Longest client-supplied id that is kept.
+const MAX_LENGTH: usize = 128;
+
+/// The request's id, in its extensions once [`request_id_middleware`] has
+/// run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::now_v7().to_string())
+    }
+
+    /// The id a client sent, if it is 1 to 128 visible characters once
+    /// trimmed.
+    pub fn from_header(value: &HeaderValue) -> Option<Self> {
+        let id = value.to_str().ok()?.trim();
+        (!id.is_empty() && id.len() <= MAX_LENGTH).then(|| Self(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Sets the configured header on an outbound request to the request's id,
+/// replacing whatever the client sent under it.
+pub fn apply(headers: &mut HeaderMap, extensions: &Extensions, config: &RequestIdConfig) {
+    let Some(request_id) = extensions.get::<RequestId>() else { return };
+    if let (Ok(name), Ok(value)) = (
+        HeaderName::from_bytes(config.header.as_bytes()),
+        HeaderValue::from_str(request_id.as_str()),
+    ) {
+        headers.insert(name, value);
+    }
+}
+
+/// The `TraceLayer` span for a request: tower-http's default fields plus
+/// the request id.
+pub fn make_span<B>(request: &axum::http::Request<B>) -> Span {
+    let request_id = request.extensions().get::<RequestId>().map(RequestId::as_str);
+    tracing::debug_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id,
+    )
+}
+
+/// Gives every request an id before anything else handles it, and echoes
+/// it in the response under the configured header.
+pub async fn request_id_middleware(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let header = HeaderName::from_bytes(config.middleware.request_id.header.as_bytes()).ok();
+    let request_id = header
+        .as_ref()
+        .and_then(|header| request.headers().get(header))
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::generate);
+    request.extensions_mut().insert(request_id.clone());
+
+    let mut response = next.run(request).await;
+    if let (Some(header), Ok(value)) = (header, HeaderValue::from_str(request_id.as_str())) {
+        response.headers_mut().insert(header, value);
+    }
+    response
+}
@@ -0,0 +1,40 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    middleware::{read_only::ReadOnlyRejection, timing::RequestTiming},
+    AppState,
+};
+
+/// Counts each response against the SLOs its route falls under, with the
+/// latency to the response head measured from when the gateway received
+/// the request.
+pub async fn slo_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    if config.slo.objectives.is_empty() {
+        return next.run(request).await;
+    }
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let timing = request.extensions().get::<RequestTiming>().cloned();
+    let started = std::time::Instant::now();
+
+    let response = next.run(request).await;
+    // Writes refused in read-only mode were refused on purpose
+    if response.extensions().get::<ReadOnlyRejection>().is_some() {
+        return response;
+    }
+
+    let received_at = timing.map(|timing| timing.received_at()).unwrap_or(started);
+    state
+        .slo_tracker
+        .record(&config.slo, &method, &route, response.status().as_u16(), received_at.elapsed());
+    response
+}
@@ -0,0 +1,35 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::{
+    routes::error::{self, ApiError},
+    AppState,
+};
+
+/// Answers 408 when a handler takes longer than `server.timeout`. The
+/// timeout is read per request, so a reload applies to the next one.
+pub async fn timeout_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let limit = state.config_watcher.get_config().await.server.timeout;
+    let request_id = error::request_id(request.extensions());
+    let (method, path) = (request.method().clone(), request.uri().path().to_string());
+
+    match timeout(limit.get(), next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!(method = %method, path, timeout = %limit, "Request timed out");
+            ApiError::new(
+                StatusCode::REQUEST_TIMEOUT,
+                "request_timeout",
+                format!("Request timed out after {}", limit),
+            )
+            .with_request_id(request_id)
+            .into_response()
+        }
+    }
+}
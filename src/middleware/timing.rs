@@ -0,0 +1,266 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{config::AppConfig, AppState};
+
+/// Auth time is reported in steps this coarse so the header can't be used
+/// to tell cached from uncached or valid from invalid tokens.
+const AUTH_RESOLUTION: Duration = Duration::from_millis(10);
+/// Marks that no upstream call was made.
+const NOT_PROXIED: u64 = u64::MAX;
+
+/// Timing marks for a single request, shared through the request extensions
+/// so every layer can contribute to (and read) the breakdown.
+#[derive(Clone, Debug)]
+pub struct RequestTiming {
+    inner: Arc<TimingMarks>,
+}
+
+#[derive(Debug)]
+struct TimingMarks {
+    received_at: Instant,
+    queue_nanos: AtomicU64,
+    auth_nanos: AtomicU64,
+    upstream_nanos: AtomicU64,
+    upstream_first_byte_nanos: AtomicU64,
+    stage: AtomicU8,
+}
+
+/// How far processing of a request has got, reported when its client
+/// leaves before the response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Received,
+    Authenticated,
+    /// Waiting for a legacy gateway connection.
+    Queued,
+    /// Waiting for the legacy gateway's response headers.
+    Upstream,
+    /// Reading the legacy gateway's response body.
+    UpstreamBody,
+    /// In the Rust handler.
+    Handler,
+}
+
+impl Stage {
+    const ALL: [Stage; 6] = [
+        Stage::Received,
+        Stage::Authenticated,
+        Stage::Queued,
+        Stage::Upstream,
+        Stage::UpstreamBody,
+        Stage::Handler,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Received => "received",
+            Stage::Authenticated => "authenticated",
+            Stage::Queued => "queued",
+            Stage::Upstream => "upstream",
+            Stage::UpstreamBody => "upstream_body",
+            Stage::Handler => "handler",
+        }
+    }
+}
+
+/// Where a request's time went, as reported in `Server-Timing` and the
+/// access log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimingBreakdown {
+    /// Gateway time outside the upstream call and queueing.
+    pub gateway: Duration,
+    /// The upstream call, when the request was proxied.
+    pub upstream: Option<Duration>,
+    pub auth: Duration,
+    pub queue: Duration,
+}
+
+impl Default for RequestTiming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestTiming {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(TimingMarks {
+                received_at: Instant::now(),
+                queue_nanos: AtomicU64::new(0),
+                auth_nanos: AtomicU64::new(0),
+                upstream_nanos: AtomicU64::new(NOT_PROXIED),
+                upstream_first_byte_nanos: AtomicU64::new(NOT_PROXIED),
+                stage: AtomicU8::new(Stage::Received as u8),
+            }),
+        }
+    }
+
+    pub fn received_at(&self) -> Instant {
+        self.inner.received_at
+    }
+
+    /// Adds time spent waiting on an admission-control primitive.
+    pub fn record_queue(&self, waited: Duration) {
+        self.inner
+            .queue_nanos
+            .fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+        crate::metrics::record_queue_wait(waited);
+    }
+
+    /// Total time spent queued so far.
+    pub fn queue_time(&self) -> Duration {
+        Duration::from_nanos(self.inner.queue_nanos.load(Ordering::Relaxed))
+    }
+
+    /// What is left of `budget` once queueing time is taken out.
+    pub fn remaining(&self, budget: Duration) -> Duration {
+        budget.saturating_sub(self.queue_time())
+    }
+
+    /// Records time spent authenticating the request.
+    pub fn record_auth(&self, took: Duration) {
+        self.inner.auth_nanos.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Records the upstream call, from sending the request to the last body
+    /// byte.
+    pub fn record_upstream(&self, took: Duration) {
+        self.inner.upstream_nanos.store(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Records when the upstream's response headers arrived, counted from
+    /// sending the request.
+    pub fn record_upstream_first_byte(&self, took: Duration) {
+        self.inner
+            .upstream_first_byte_nanos
+            .store(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The upstream call split into waiting for the response headers and
+    /// reading the body, when the request was proxied.
+    pub fn upstream_phases(&self) -> Option<(Duration, Duration)> {
+        let total = self.inner.upstream_nanos.load(Ordering::Relaxed);
+        if total == NOT_PROXIED {
+            return None;
+        }
+        let first_byte = match self.inner.upstream_first_byte_nanos.load(Ordering::Relaxed) {
+            NOT_PROXIED => total,
+            nanos => nanos.min(total),
+        };
+        Some((Duration::from_nanos(first_byte), Duration::from_nanos(total - first_byte)))
+    }
+
+    pub fn enter(&self, stage: Stage) {
+        self.inner.stage.store(stage as u8, Ordering::Relaxed);
+    }
+
+    pub fn stage(&self) -> Stage {
+        Stage::ALL[self.inner.stage.load(Ordering::Relaxed) as usize]
+    }
+
+    /// The breakdown as of now; everything not spent upstream or queued
+    /// counts as gateway time.
+    pub fn breakdown(&self) -> TimingBreakdown {
+        let upstream = match self.inner.upstream_nanos.load(Ordering::Relaxed) {
+            NOT_PROXIED => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        };
+        let queue = self.queue_time();
+        TimingBreakdown {
+            gateway: self
+                .inner
+                .received_at
+                .elapsed()
+                .saturating_sub(upstream.unwrap_or_default())
+                .saturating_sub(queue),
+            upstream,
+            auth: Duration::from_nanos(self.inner.auth_nanos.load(Ordering::Relaxed)),
+            queue,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+impl TimingBreakdown {
+    pub fn gateway_ms(&self) -> u64 {
+        millis(self.gateway)
+    }
+
+    pub fn upstream_ms(&self) -> Option<u64> {
+        self.upstream.map(millis)
+    }
+
+    /// Auth time rounded to the nearest [`AUTH_RESOLUTION`].
+    pub fn auth_ms(&self) -> u64 {
+        let step = AUTH_RESOLUTION.as_millis() as u64;
+        (self.auth.as_micros() as u64 + step * 500) / (step * 1000) * step
+    }
+
+    pub fn queue_ms(&self) -> u64 {
+        millis(self.queue)
+    }
+
+    /// The `Server-Timing` header value, in whole milliseconds.
+    pub fn header_value(&self) -> String {
+        let mut entries = vec![format!("gateway;dur={}", self.gateway_ms())];
+        if let Some(upstream) = self.upstream_ms() {
+            entries.push(format!("upstream;dur={}", upstream));
+        }
+        entries.push(format!("auth;dur={}", self.auth_ms()));
+        entries.push(format!("queue;dur={}", self.queue_ms()));
+        entries.join(", ")
+    }
+}
+
+/// Whether `Server-Timing` goes out on this request: the route's own
+/// setting wins, then the path exclusions, then the global switch.
+pub fn server_timing_enabled(config: &AppConfig, method: &str, route: &str) -> bool {
+    let server_timing = &config.middleware.server_timing;
+    if let Some(enabled) = config.route(method, route).and_then(|route| route.server_timing) {
+        return enabled;
+    }
+    server_timing.enabled && !server_timing.exclude_paths.iter().any(|prefix| route.starts_with(prefix.as_str()))
+}
+
+/// Adds the `Server-Timing` header. Uses the breakdown the access log
+/// recorded when there is one, so both report the same numbers.
+pub async fn server_timing_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let timing = request.extensions().get::<RequestTiming>().cloned();
+
+    let mut response = next.run(request).await;
+
+    if !server_timing_enabled(&config, &method, &route) {
+        return response;
+    }
+    let breakdown = response
+        .extensions()
+        .get::<TimingBreakdown>()
+        .copied()
+        .or_else(|| timing.map(|timing| timing.breakdown()));
+    if let Some(value) = breakdown.and_then(|breakdown| HeaderValue::from_str(&breakdown.header_value()).ok()) {
+        response.headers_mut().insert("server-timing", value);
+    }
+    response
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+use crate::{
+    coordination::RequestGeneration,
+    middleware::canary::Backend,
+    monitoring::trace_sampling::{decide, CompletedRequest},
+    AppState,
+};
+
+/// Decides whether each completed request's trace is kept and counts the
+/// decision in `gateway_trace_sampling_decisions_total{reason}`.
+pub async fn trace_sampling_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let sampling = &config.tracing.sampling;
+    if !sampling.enabled {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let now = Instant::now();
+    let since_rollout_change = response
+        .extensions()
+        .get::<RequestGeneration>()
+        .and_then(|generation| state.trace_sampler.since_rollout_change(generation.0, now));
+    let completed = CompletedRequest {
+        status: response.status().as_u16(),
+        latency: now.duration_since(started),
+        legacy_routed: response.extensions().get::<Backend>() == Some(&Backend::Legacy),
+        since_rollout_change,
+    };
+    let reason = decide(&completed, sampling, rand::random());
+    crate::metrics::record_trace_sampling(reason.as_str());
+    response
+}
@@ -0,0 +1,156 @@
+use axum::{
+    extract::{Request, State},
+    http::{uri::PathAndQuery, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::debug;
+
+use crate::{
+    config::{ApiVersionConfig, VersionSource, VersioningConfig},
+    routes::error::ApiError,
+    AppState,
+};
+
+/// Where `GET /api/versions` lives; never negotiated itself.
+pub const VERSIONS_PATH: &str = "/api/versions";
+
+/// Outcome of negotiating a request's API version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Negotiation {
+    /// Not under the versioned base path, or versioning is off.
+    Unversioned,
+    /// Served by `version` at `path`, which differs from the request path
+    /// when the version came from the header or the default.
+    Version { version: String, path: String },
+    /// Asked for a version that isn't configured.
+    Unknown(String),
+}
+
+/// `v` followed by a digit, as in `v1` or `v2.1`.
+fn is_version_segment(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Picks the API version for a request to `path` with `header` as the
+/// version header's value.
+pub fn negotiate(config: &VersioningConfig, path: &str, header: Option<&str>) -> Negotiation {
+    if !config.enabled || path == VERSIONS_PATH {
+        return Negotiation::Unversioned;
+    }
+    let base = config.base_path.trim_end_matches('/');
+    let Some(rest) = path.strip_prefix(base).filter(|rest| rest.is_empty() || rest.starts_with('/')) else {
+        return Negotiation::Unversioned;
+    };
+
+    let (segment, tail) = match rest.trim_start_matches('/').split_once('/') {
+        Some((segment, tail)) => (segment, format!("/{}", tail)),
+        None => (rest.trim_start_matches('/'), String::new()),
+    };
+    let (from_path, tail) = if is_version_segment(segment) {
+        (Some(segment), tail)
+    } else {
+        (None, rest.to_string())
+    };
+    let from_header = header.map(str::trim).filter(|value| !value.is_empty());
+
+    let version = match config.precedence {
+        VersionSource::Path => from_path.or(from_header),
+        VersionSource::Header => from_header.or(from_path),
+    }
+    .unwrap_or(&config.default_version);
+    if config.version(version).is_none() {
+        return Negotiation::Unknown(version.to_string());
+    }
+    Negotiation::Version {
+        version: version.to_string(),
+        path: format!("{}/{}{}", base, version, tail),
+    }
+}
+
+/// `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) values for a version.
+pub fn lifecycle_headers(version: &ApiVersionConfig) -> Vec<(&'static str, String)> {
+    let midnight = |date: chrono::NaiveDate| date.and_time(chrono::NaiveTime::MIN).and_utc();
+    let mut headers = Vec::new();
+    if let Some(deprecated) = version.deprecated {
+        headers.push(("deprecation", format!("@{}", midnight(deprecated).timestamp())));
+    }
+    if let Some(sunset) = version.sunset {
+        headers.push(("sunset", midnight(sunset).format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+    }
+    headers
+}
+
+/// `deprecated` or `stable`, as listed by `GET /api/versions`.
+pub fn lifecycle(version: &ApiVersionConfig) -> &'static str {
+    if version.deprecated.is_some() {
+        "deprecated"
+    } else {
+        "stable"
+    }
+}
+
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// 406 listing the versions that are supported.
+fn not_acceptable(config: &VersioningConfig, version: &str) -> ApiError {
+    let supported: Vec<&str> = config.versions.iter().map(|version| version.name.as_str()).collect();
+    ApiError::new(
+        StatusCode::NOT_ACCEPTABLE,
+        "unsupported_version",
+        format!("API version {:?} is not supported", version),
+    )
+    .with_detail("supported_versions", supported)
+}
+
+/// Negotiates the API version before routing, so a version picked by header
+/// or default lands on that version's routes, and marks responses from
+/// deprecated versions.
+pub async fn versioning_middleware(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.config_watcher.get_config().await;
+    let versioning = &config.versioning;
+    let header = request
+        .headers()
+        .get(versioning.header.as_str())
+        .and_then(|value| value.to_str().ok());
+
+    let (version, path) = match negotiate(versioning, request.uri().path(), header) {
+        Negotiation::Unversioned => return next.run(request).await,
+        Negotiation::Unknown(version) => {
+            crate::metrics::record_api_request("unknown", "unsupported");
+            return not_acceptable(versioning, &version)
+                .for_request(request.extensions())
+                .into_response();
+        }
+        Negotiation::Version { version, path } => (version, path),
+    };
+    if path != request.uri().path() {
+        if let Some(uri) = with_path(request.uri(), &path) {
+            debug!(from = request.uri().path(), to = %path, version = %version, "Routing to negotiated API version");
+            *request.uri_mut() = uri;
+        }
+    }
+
+    let Some(api_version) = versioning.version(&version) else {
+        return next.run(request).await;
+    };
+    crate::metrics::record_api_request(&version, lifecycle(api_version));
+    let mut response = next.run(request).await;
+    for (name, value) in lifecycle_headers(api_version) {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
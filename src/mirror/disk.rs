@@ -0,0 +1,345 @@
+//! Segmented append-only log behind the `disk` mirror queue.
+//!
+//! Records are `[length: u32 LE][crc32: u32 LE][payload]`, appended to
+//! segment files named after the sequence number of their first record.
+//! The consumer's position is checkpointed after every acknowledgement, and
+//! segments it has moved past are deleted. On open, a record that is cut
+//! short or fails its checksum ends the log: its segment is truncated there
+//! and any later segments are removed.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+const HEADER_BYTES: u64 = 8;
+const CHECKPOINT: &str = "checkpoint";
+/// Segments roll over at this size, or a quarter of the log's limit if
+/// smaller, so consumed records are deleted in reasonably small steps.
+const SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+
+struct Segment {
+    first_seq: u64,
+    records: u64,
+    bytes: u64,
+    path: PathBuf,
+}
+
+impl Segment {
+    fn end_seq(&self) -> u64 {
+        self.first_seq + self.records
+    }
+}
+
+struct Reader {
+    first_seq: u64,
+    /// Sequence number of the record the file is positioned at.
+    next_seq: u64,
+    file: BufReader<File>,
+}
+
+/// What [`DiskLog::open`] found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// Records written but not acknowledged before the last shutdown.
+    pub pending: u64,
+    /// Bytes cut from corrupt or incomplete segments.
+    pub truncated_bytes: u64,
+}
+
+pub struct DiskLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    segments: VecDeque<Segment>,
+    writer: File,
+    reader: Option<Reader>,
+    next_write_seq: u64,
+    next_read_seq: u64,
+    committed: u64,
+    in_flight: BTreeSet<u64>,
+}
+
+fn segment_path(dir: &Path, first_seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.log", first_seq))
+}
+
+/// Reads one record, or `None` at a clean end of file. A short or corrupt
+/// record is an `InvalidData` error.
+fn read_record(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_BYTES as usize];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::Error::new(ErrorKind::InvalidData, "record header cut short")),
+            read => filled += read,
+        }
+    }
+    let length = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => io::Error::new(ErrorKind::InvalidData, "record cut short"),
+        _ => e,
+    })?;
+    if crc32fast::hash(&payload) != checksum {
+        return Err(io::Error::new(ErrorKind::InvalidData, "record checksum mismatch"));
+    }
+    Ok(Some(payload))
+}
+
+/// Counts a segment's intact records and the bytes they take up.
+fn scan(path: &Path) -> io::Result<(u64, u64)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut records, mut bytes) = (0, 0);
+    loop {
+        match read_record(&mut reader) {
+            Ok(Some(payload)) => {
+                records += 1;
+                bytes += HEADER_BYTES + payload.len() as u64;
+            }
+            Ok(None) => return Ok((records, bytes)),
+            Err(e) if e.kind() == ErrorKind::InvalidData => return Ok((records, bytes)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl DiskLog {
+    /// Opens the log in `dir`, creating it if needed, and recovers what the
+    /// last process left behind.
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<(Self, Recovery)> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut recovery = Recovery::default();
+
+        let mut first_seqs: Vec<u64> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                name.to_str()?.strip_suffix(".log")?.parse().ok()
+            })
+            .collect();
+        first_seqs.sort_unstable();
+
+        let mut segments: VecDeque<Segment> = VecDeque::new();
+        let mut broken = false;
+        for first_seq in first_seqs {
+            let path = segment_path(&dir, first_seq);
+            let contiguous = segments.back().is_none_or(|last| last.end_seq() == first_seq);
+            if broken || !contiguous {
+                let size = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+                warn!(segment = %path.display(), "Removing mirror queue segment after a corrupt or missing one");
+                recovery.truncated_bytes += size;
+                fs::remove_file(&path)?;
+                continue;
+            }
+            let (records, bytes) = scan(&path)?;
+            let size = fs::metadata(&path)?.len();
+            if bytes < size {
+                warn!(
+                    segment = %path.display(),
+                    kept_bytes = bytes,
+                    dropped_bytes = size - bytes,
+                    "Truncating corrupt tail of mirror queue segment"
+                );
+                OpenOptions::new().write(true).open(&path)?.set_len(bytes)?;
+                recovery.truncated_bytes += size - bytes;
+                broken = true;
+            }
+            segments.push_back(Segment {
+                first_seq,
+                records,
+                bytes,
+                path,
+            });
+        }
+
+        let next_write_seq = segments.back().map(Segment::end_seq).unwrap_or(0);
+        let checkpoint = match fs::read_to_string(dir.join(CHECKPOINT)) {
+            Ok(checkpoint) => checkpoint.trim().parse().unwrap_or(0),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let first_seq = segments.front().map(|segment| segment.first_seq).unwrap_or(next_write_seq);
+        let committed = checkpoint.clamp(first_seq, next_write_seq);
+        recovery.pending = next_write_seq - committed;
+
+        if segments.back().is_none_or(|last| last.bytes > 0) {
+            let path = segment_path(&dir, next_write_seq);
+            File::create(&path)?;
+            segments.push_back(Segment {
+                first_seq: next_write_seq,
+                records: 0,
+                bytes: 0,
+                path,
+            });
+        }
+        let writer = OpenOptions::new().append(true).open(&segments.back().unwrap().path)?;
+
+        let mut log = Self {
+            dir,
+            max_bytes,
+            segment_bytes: (max_bytes / 4).clamp(64 * 1024, SEGMENT_BYTES),
+            segments,
+            writer,
+            reader: None,
+            next_write_seq,
+            next_read_seq: committed,
+            committed,
+            in_flight: BTreeSet::new(),
+        };
+        log.write_checkpoint()?;
+        log.remove_consumed()?;
+        Ok((log, recovery))
+    }
+
+    /// Records written and not yet acknowledged.
+    pub fn depth(&self) -> u64 {
+        self.next_write_seq - self.committed
+    }
+
+    pub fn disk_bytes(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.bytes).sum()
+    }
+
+    /// Appends a record, returning `false` without writing it when the log
+    /// is at `max_bytes`.
+    pub fn append(&mut self, payload: &[u8]) -> io::Result<bool> {
+        let record_bytes = HEADER_BYTES + payload.len() as u64;
+        let current = self.segments.back().unwrap();
+        if self.disk_bytes() + record_bytes > self.max_bytes {
+            // A drained segment being written to is only freed once another
+            // takes over
+            if current.records == 0 || current.end_seq() > self.committed {
+                return Ok(false);
+            }
+            self.roll()?;
+            self.remove_consumed()?;
+            if self.disk_bytes() + record_bytes > self.max_bytes {
+                return Ok(false);
+            }
+        } else if current.records > 0 && current.bytes + record_bytes > self.segment_bytes {
+            self.roll()?;
+        }
+
+        let mut record = Vec::with_capacity(record_bytes as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        record.extend_from_slice(payload);
+        self.writer.write_all(&record)?;
+
+        let current = self.segments.back_mut().unwrap();
+        current.records += 1;
+        current.bytes += record_bytes;
+        self.next_write_seq += 1;
+        Ok(true)
+    }
+
+    /// Starts a new segment for appends.
+    fn roll(&mut self) -> io::Result<()> {
+        let path = segment_path(&self.dir, self.next_write_seq);
+        self.writer = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.segments.push_back(Segment {
+            first_seq: self.next_write_seq,
+            records: 0,
+            bytes: 0,
+            path,
+        });
+        Ok(())
+    }
+
+    /// The next unread record and its sequence number, which stays in flight
+    /// until [`ack`](Self::ack)ed.
+    pub fn read_next(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        let seq = self.next_read_seq;
+        if seq >= self.next_write_seq {
+            return Ok(None);
+        }
+        match self.read_at(seq) {
+            Ok(payload) => {
+                self.next_read_seq += 1;
+                self.in_flight.insert(seq);
+                Ok(Some((seq, payload)))
+            }
+            Err(e) => {
+                // Unreadable records can't be sent; move on to the next segment
+                let segment = self.segments.iter().find(|segment| segment.end_seq() > seq);
+                let skip_to = segment.map(Segment::end_seq).unwrap_or(self.next_write_seq);
+                warn!(seq, skipped = skip_to - seq, error = %e, "Skipping unreadable mirror queue records");
+                self.reader = None;
+                self.next_read_seq = skip_to;
+                self.advance_committed()?;
+                Err(e)
+            }
+        }
+    }
+
+    fn read_at(&mut self, seq: u64) -> io::Result<Vec<u8>> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.first_seq <= seq && seq < segment.end_seq())
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no segment holds this record"))?;
+        let reusable = self
+            .reader
+            .as_ref()
+            .is_some_and(|reader| reader.first_seq == segment.first_seq && reader.next_seq == seq);
+        if !reusable {
+            let mut file = BufReader::new(File::open(&segment.path)?);
+            for _ in segment.first_seq..seq {
+                read_record(&mut file)?;
+            }
+            self.reader = Some(Reader {
+                first_seq: segment.first_seq,
+                next_seq: seq,
+                file,
+            });
+        }
+        let reader = self.reader.as_mut().unwrap();
+        let payload = read_record(&mut reader.file)?
+            .ok_or_else(|| io::Error::new(ErrorKind::UnexpectedEof, "record missing"))?;
+        reader.next_seq += 1;
+        Ok(payload)
+    }
+
+    /// Marks a record as handled. The checkpoint moves up to the oldest
+    /// record still in flight.
+    pub fn ack(&mut self, seq: u64) -> io::Result<()> {
+        self.in_flight.remove(&seq);
+        self.advance_committed()
+    }
+
+    fn advance_committed(&mut self) -> io::Result<()> {
+        let committed = self.in_flight.first().copied().unwrap_or(self.next_read_seq);
+        if committed <= self.committed {
+            return Ok(());
+        }
+        self.committed = committed;
+        self.write_checkpoint()?;
+        self.remove_consumed()
+    }
+
+    fn write_checkpoint(&self) -> io::Result<()> {
+        let temp = self.dir.join(format!("{}.tmp", CHECKPOINT));
+        fs::write(&temp, self.committed.to_string())?;
+        fs::rename(temp, self.dir.join(CHECKPOINT))
+    }
+
+    /// Deletes segments before the one being written whose records are all
+    /// acknowledged.
+    fn remove_consumed(&mut self) -> io::Result<()> {
+        while self.segments.len() > 1 && self.segments[0].end_seq() <= self.committed {
+            let segment = self.segments.pop_front().unwrap();
+            if self.reader.as_ref().is_some_and(|reader| reader.first_seq == segment.first_seq) {
+                self.reader = None;
+            }
+            fs::remove_file(&segment.path)?;
+        }
+        Ok(())
+    }
+}
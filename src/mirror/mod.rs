@@ -0,0 +1,495 @@
+//! Queue between the mirror middleware and the worker that sends mirror
+//! requests.
+//!
+//! The middleware enqueues each sampled request once the main response is
+//! done, along with what the comparison needs from it. By default the queue
+//! lives in memory and is lost on restart. With `mirror.queue.kind: disk` it
+//! is a [`DiskLog`], so requests waiting when the gateway stops are sent
+//! after it starts again. Delivery is at least once: a request that was
+//! being sent when the process died is sent again.
+
+pub mod disk;
+pub mod parity;
+pub mod report;
+pub mod tee;
+
+use anyhow::Context;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::{
+    sync::{Notify, Semaphore},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{watcher::ConfigWatcher, MirrorQueueConfig, MirrorQueueKind},
+    memory::MemoryConsumer,
+    metrics::MIRROR_METRICS,
+    monitoring::{MirrorOutcome, PerformanceMonitor},
+    privacy::redaction::Redactor,
+    upstream::{encoding, validation, Upstream, UpstreamPool},
+    util::backoff::retry,
+};
+pub use disk::{DiskLog, Recovery};
+use parity::{ParityRecord, ParityStore};
+use tee::{BodyComparison, BodyDigest};
+
+const COMPARISON: &str = "mirror_comparison";
+/// Mirror requests in flight at once; the upstream pool still caps
+/// connections per host.
+const MAX_IN_FLIGHT: usize = 64;
+
+/// A request to mirror and what the main response it is compared with
+/// looked like.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirrorJob {
+    pub method: String,
+    pub path_and_query: String,
+    pub headers: Vec<(String, Vec<u8>)>,
+    /// Matched route pattern, for the route's response contract.
+    pub route: String,
+    pub main_status: u16,
+    pub main_latency_ms: f64,
+    /// Decoded size of the main response, when it could be measured.
+    pub main_bytes: Option<i64>,
+    /// The main body as the comparison saw it, unless its capture was
+    /// abandoned.
+    #[serde(default)]
+    pub main_body: Option<BodyDigest>,
+    /// Rollout generation the main request was routed under.
+    #[serde(default)]
+    pub rollout_generation: Option<u64>,
+    /// The main request's id, also among `headers`.
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+impl MirrorJob {
+    pub fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_bytes(value)) {
+                headers.append(name, value);
+            }
+        }
+        headers
+    }
+
+    /// A parity record of this job's mirror request, which got
+    /// `mirror_status` or no answer at all.
+    fn parity_record(&self, mirror_status: Option<u16>, mirror_latency_ms: f64) -> ParityRecord {
+        ParityRecord {
+            at: chrono::Utc::now(),
+            method: self.method.clone(),
+            route: self.route.clone(),
+            request_id: self.request_id.clone(),
+            main_status: self.main_status,
+            mirror_status,
+            main_latency_ms: self.main_latency_ms,
+            mirror_latency_ms,
+            contract_violation: false,
+            body_match: None,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        let headers: usize = self.headers.iter().map(|(name, value)| name.len() + value.len()).sum();
+        let body = self.main_body.as_ref().map_or(0, |body| body.prefix.len() + body.sha256.len());
+        (std::mem::size_of::<Self>() + self.method.len() + self.path_and_query.len() + self.route.len() + headers + body)
+            as u64
+    }
+}
+
+/// A job handed to the worker; pass it back to [`MirrorQueue::ack`] once
+/// sent.
+pub struct Delivery {
+    pub job: MirrorJob,
+    seq: Option<u64>,
+}
+
+enum Backend {
+    Memory { jobs: VecDeque<(MirrorJob, u64)>, bytes: u64 },
+    Disk(DiskLog),
+}
+
+pub struct MirrorQueue {
+    kind: MirrorQueueKind,
+    max_bytes: u64,
+    backend: Mutex<Backend>,
+    ready: Notify,
+}
+
+impl MirrorQueue {
+    pub fn memory(max_bytes: u64) -> Self {
+        Self::with_backend(
+            MirrorQueueKind::Memory,
+            max_bytes,
+            Backend::Memory {
+                jobs: VecDeque::new(),
+                bytes: 0,
+            },
+        )
+    }
+
+    /// Opens the configured queue. A `disk` queue picks up what the last
+    /// process left unsent.
+    pub fn open(config: &MirrorQueueConfig) -> anyhow::Result<Self> {
+        let max_bytes = config.max_bytes.bytes();
+        if config.kind == MirrorQueueKind::Memory {
+            return Ok(Self::memory(max_bytes));
+        }
+        let (log, recovery) = DiskLog::open(&config.path, max_bytes)
+            .with_context(|| format!("failed to open mirror queue at {}", config.path))?;
+        if recovery.pending > 0 || recovery.truncated_bytes > 0 {
+            info!(
+                path = %config.path,
+                pending = recovery.pending,
+                truncated_bytes = recovery.truncated_bytes,
+                "Recovered mirror queue"
+            );
+        }
+        crate::metrics::record_mirror_queue_replayed(recovery.pending);
+        let queue = Self::with_backend(MirrorQueueKind::Disk, max_bytes, Backend::Disk(log));
+        queue.publish();
+        if recovery.pending > 0 {
+            queue.ready.notify_one();
+        }
+        Ok(queue)
+    }
+
+    fn with_backend(kind: MirrorQueueKind, max_bytes: u64, backend: Backend) -> Self {
+        Self {
+            kind,
+            max_bytes,
+            backend: Mutex::new(backend),
+            ready: Notify::new(),
+        }
+    }
+
+    pub fn kind(&self) -> MirrorQueueKind {
+        self.kind
+    }
+
+    /// Jobs waiting; a `disk` queue also counts those being sent.
+    pub fn depth(&self) -> u64 {
+        match &*self.backend.lock().unwrap() {
+            Backend::Memory { jobs, .. } => jobs.len() as u64,
+            Backend::Disk(log) => log.depth(),
+        }
+    }
+
+    fn publish(&self) {
+        let (depth, disk_bytes) = match &*self.backend.lock().unwrap() {
+            Backend::Memory { jobs, .. } => (jobs.len() as u64, 0),
+            Backend::Disk(log) => (log.depth(), log.disk_bytes()),
+        };
+        crate::metrics::record_mirror_queue(depth, disk_bytes);
+    }
+
+    /// Enqueues a job, returning `false` when it was dropped because the
+    /// queue is full or couldn't be written.
+    pub fn push(&self, job: MirrorJob) -> bool {
+        let accepted = match &mut *self.backend.lock().unwrap() {
+            Backend::Memory { jobs, bytes } => {
+                let size = job.size();
+                let fits = *bytes + size <= self.max_bytes;
+                if fits {
+                    *bytes += size;
+                    jobs.push_back((job, size));
+                }
+                Ok(fits)
+            }
+            Backend::Disk(log) => rmp_serde::to_vec_named(&job)
+                .map_err(|e| e.to_string())
+                .and_then(|payload| log.append(&payload).map_err(|e| e.to_string())),
+        };
+        match accepted {
+            Ok(true) => {
+                self.publish();
+                self.ready.notify_one();
+                true
+            }
+            Ok(false) => {
+                crate::metrics::record_mirror_queue_dropped("full", 1);
+                false
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to enqueue mirror request");
+                crate::metrics::record_mirror_queue_dropped("io_error", 1);
+                false
+            }
+        }
+    }
+
+    fn try_pop(&self) -> Option<Delivery> {
+        let mut backend = self.backend.lock().unwrap();
+        match &mut *backend {
+            Backend::Memory { jobs, bytes } => {
+                let (job, size) = jobs.pop_front()?;
+                *bytes -= size;
+                Some(Delivery { job, seq: None })
+            }
+            Backend::Disk(log) => loop {
+                match log.read_next() {
+                    Ok(Some((seq, payload))) => match rmp_serde::from_slice(&payload) {
+                        Ok(job) => return Some(Delivery { job, seq: Some(seq) }),
+                        Err(e) => {
+                            warn!(seq, error = %e, "Dropping undecodable mirror queue record");
+                            crate::metrics::record_mirror_queue_dropped("io_error", 1);
+                            if let Err(e) = log.ack(seq) {
+                                error!(error = %e, "Failed to checkpoint mirror queue");
+                            }
+                        }
+                    },
+                    Ok(None) => return None,
+                    Err(_) => crate::metrics::record_mirror_queue_dropped("io_error", 1),
+                }
+            },
+        }
+    }
+
+    /// Waits for the next job.
+    pub async fn pop(&self) -> Delivery {
+        loop {
+            if let Some(delivery) = self.try_pop() {
+                self.publish();
+                return delivery;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Marks a delivered job as sent, so a `disk` queue won't replay it.
+    pub fn ack(&self, delivery: &Delivery) {
+        if let (Some(seq), Backend::Disk(log)) = (delivery.seq, &mut *self.backend.lock().unwrap()) {
+            if let Err(e) = log.ack(seq) {
+                error!(error = %e, "Failed to checkpoint mirror queue");
+            }
+        }
+        self.publish();
+    }
+}
+
+impl MemoryConsumer for MirrorQueue {
+    fn memory_usage(&self) -> u64 {
+        match &*self.backend.lock().unwrap() {
+            Backend::Memory { bytes, .. } => *bytes,
+            Backend::Disk(_) => 0,
+        }
+    }
+
+    /// Drops the oldest waiting jobs of a `memory` queue.
+    fn evict(&self, target: u64) -> u64 {
+        let Backend::Memory { jobs, bytes } = &mut *self.backend.lock().unwrap() else {
+            return 0;
+        };
+        let (mut freed, mut dropped) = (0, 0);
+        while freed < target {
+            let Some((_, size)) = jobs.pop_front() else {
+                break;
+            };
+            *bytes -= size;
+            freed += size;
+            dropped += 1;
+        }
+        crate::metrics::record_mirror_queue_dropped("memory", dropped);
+        freed
+    }
+}
+
+/// Sends queued jobs to `mirror.base_url` and records how they compared.
+pub struct MirrorWorker {
+    queue: Arc<MirrorQueue>,
+    config_watcher: Arc<ConfigWatcher>,
+    upstreams: Arc<UpstreamPool>,
+    performance_monitor: Arc<PerformanceMonitor>,
+    parity: Arc<ParityStore>,
+}
+
+impl MirrorWorker {
+    pub fn new(
+        queue: Arc<MirrorQueue>,
+        config_watcher: Arc<ConfigWatcher>,
+        upstreams: Arc<UpstreamPool>,
+        performance_monitor: Arc<PerformanceMonitor>,
+        parity: Arc<ParityStore>,
+    ) -> Self {
+        Self {
+            queue,
+            config_watcher,
+            upstreams,
+            performance_monitor,
+            parity,
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        let worker = Arc::new(self);
+        let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+        tokio::spawn(async move {
+            loop {
+                let permit = in_flight.clone().acquire_owned().await.expect("never closed");
+                let delivery = worker.queue.pop().await;
+                let worker = worker.clone();
+                tokio::spawn(async move {
+                    worker.send(&delivery.job).await;
+                    worker.queue.ack(&delivery);
+                    drop(permit);
+                });
+            }
+        })
+    }
+
+    async fn send(&self, job: &MirrorJob) {
+        let config = self.config_watcher.get_config().await;
+        let Ok(method) = Method::from_bytes(job.method.as_bytes()) else {
+            return;
+        };
+        let mirror_url = format!("{}{}", config.mirror.base_url, job.path_and_query);
+        let path = job.path_and_query.split('?').next().unwrap_or_default();
+        let max_decoded = config.middleware.decompression.max_decoded_size.bytes();
+        let compared_prefix = config.mirror.compared_prefix.bytes() as usize;
+        let checked_route = config
+            .route(method.as_str(), &job.route)
+            .filter(|route| route.validates_responses());
+
+        let pool_permit = self.upstreams.acquire(&mirror_url).await;
+        let mut headers = job.header_map();
+        self.upstreams.strip_outbound_headers(&mut headers);
+
+        // Send mirror request, again only if it got no response at all
+        let sent = retry(&config.mirror.retry_policy(), |_| {
+            let mut mirror_request = self.upstreams.request(Upstream::Mirror, method.clone(), &mirror_url);
+            for (key, value) in headers.iter() {
+                if key != "host" {
+                    mirror_request = mirror_request.header(key, value);
+                }
+            }
+            let mirror_request = mirror_request.header("X-Mirrored-By", "Rust-Gateway");
+            async move {
+                let mirror_start = Instant::now();
+                mirror_request
+                    .send()
+                    .await
+                    .map(|response| (response, mirror_start))
+                    .map_err(|e| (e, mirror_start))
+            }
+        })
+        .await;
+        crate::metrics::record_retries("mirror", sent.retries());
+
+        match sent.result {
+            Ok((mirror_response, mirror_start)) => {
+                let mirror_latency = mirror_start.elapsed();
+                let status = mirror_response.status().as_u16() as i32;
+                let mirror_headers = mirror_response.headers().clone();
+                // Parity includes the route's response expectations
+                let body = match checked_route {
+                    Some(route) => validation::read_validated(route, mirror_response, max_decoded).await,
+                    None => mirror_response.bytes().await.map(Ok),
+                };
+                let violation = match &body {
+                    Ok(Err(violation)) => Some(violation.clone()),
+                    _ => None,
+                };
+                if let Some(violation) = &violation {
+                    crate::metrics::record_contract_violation(&job.route, "mirror", violation.kind.as_str());
+                    let redactor = Redactor::new(&config.middleware.logging);
+                    let excerpt = violation.excerpt.as_deref().map(|excerpt| redactor.body(excerpt));
+                    warn!(
+                        path,
+                        route = %job.route,
+                        kind = violation.kind.as_str(),
+                        detail = %violation.detail,
+                        body_excerpt = excerpt.as_deref(),
+                        "Mirror response violated the route contract"
+                    );
+                }
+                let body = match body {
+                    Ok(Ok(body)) => Some(body),
+                    _ => None,
+                };
+                // Sizes are compared decoded, whatever each side was encoded with
+                let mirror_bytes = body.as_ref().and_then(|body| {
+                    encoding::inspect(COMPARISON, &mirror_headers, body, max_decoded).map(|decoded| decoded.len() as i64)
+                });
+                let comparison = body.as_ref().zip(job.main_body.as_ref()).and_then(|(body, main_body)| {
+                    let encoding = mirror_headers
+                        .get(axum::http::header::CONTENT_ENCODING)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string);
+                    main_body.compare(&BodyDigest::of(body, compared_prefix, encoding))
+                });
+                crate::metrics::record_mirror_body_comparison(match comparison {
+                    Some(BodyComparison::Match) => "match",
+                    Some(BodyComparison::Differs { .. }) => "mismatch",
+                    None => "not_compared",
+                });
+                let main_bytes = job.main_bytes;
+
+                // Record metrics
+                MIRROR_METRICS.requests_total.increment(1);
+                MIRROR_METRICS.latency_seconds.record(mirror_latency.as_secs_f64());
+                self.performance_monitor.record_mirror(MirrorOutcome {
+                    success: true,
+                    mismatch: status != job.main_status as i32,
+                    contract_violation: violation.is_some(),
+                    mirror_latency_ms: mirror_latency.as_secs_f64() * 1000.0,
+                    main_latency_ms: job.main_latency_ms,
+                });
+                self.parity.record(ParityRecord {
+                    contract_violation: violation.is_some(),
+                    body_match: comparison.map(|comparison| comparison == BodyComparison::Match),
+                    ..job.parity_record(Some(status as u16), mirror_latency.as_secs_f64() * 1000.0)
+                });
+
+                // Log the mirror result
+                info!(
+                    path,
+                    rollout_generation = job.rollout_generation,
+                    mirror_status = status,
+                    mirror_latency_ms = mirror_latency.as_millis(),
+                    pool_wait_ms = pool_permit.wait.as_millis(),
+                    attempts = sent.attempts,
+                    main_latency_ms = job.main_latency_ms as u64,
+                    latency_delta_ms = mirror_latency.as_millis() as i64 - job.main_latency_ms as i64,
+                    mirror_bytes = mirror_bytes,
+                    main_bytes = main_bytes,
+                    size_delta_bytes = mirror_bytes.zip(main_bytes).map(|(mirror, main)| mirror - main),
+                    body_match = comparison.map(|comparison| comparison == BodyComparison::Match),
+                    body_first_difference = match comparison {
+                        Some(BodyComparison::Differs { first_difference }) => first_difference,
+                        _ => None,
+                    },
+                    "Mirror request completed"
+                );
+            }
+            Err((e, mirror_start)) => {
+                MIRROR_METRICS.failures_total.increment(1);
+                let mirror_latency_ms = mirror_start.elapsed().as_secs_f64() * 1000.0;
+                self.performance_monitor.record_mirror(MirrorOutcome {
+                    success: false,
+                    mismatch: false,
+                    contract_violation: false,
+                    mirror_latency_ms,
+                    main_latency_ms: job.main_latency_ms,
+                });
+                self.parity.record(job.parity_record(None, mirror_latency_ms));
+                error!(
+                    path,
+                    rollout_generation = job.rollout_generation,
+                    attempts = sent.attempts,
+                    retry_delay_ms = sent.total_delay.as_millis(),
+                    error = %e,
+                    "Mirror request failed"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! How each mirrored request compared with its main response, kept for
+//! the sign-off report.
+//!
+//! With `mirror.parity.path` set, records are appended as JSON lines to one
+//! file per UTC day under it, so a report covers the whole mirroring
+//! campaign across restarts. Files past `retention` are deleted when the
+//! store opens. A line cut short by a crash is skipped when read. Without a
+//! path the latest records are kept in memory and lost on restart.
+//!
+//! Records carry the route pattern and the request ID, never the query
+//! string, headers or bodies.
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+use tracing::{error, info, warn};
+
+use crate::config::MirrorParityConfig;
+
+/// Records kept by a store without a path.
+const MAX_MEMORY_RECORDS: usize = 10_000;
+const EXTENSION: &str = "jsonl";
+
+/// One mirrored request and how it compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParityRecord {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    /// Matched route pattern.
+    pub route: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub main_status: u16,
+    /// `None` when the mirror never answered.
+    pub mirror_status: Option<u16>,
+    pub main_latency_ms: f64,
+    pub mirror_latency_ms: f64,
+    #[serde(default)]
+    pub contract_violation: bool,
+    /// Whether the bodies matched, when they could be compared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_match: Option<bool>,
+}
+
+enum Backend {
+    Memory(VecDeque<ParityRecord>),
+    Disk {
+        dir: PathBuf,
+        /// The file for the day last written to.
+        current: Option<(NaiveDate, File)>,
+    },
+}
+
+pub struct ParityStore {
+    backend: Mutex<Backend>,
+}
+
+fn day_path(dir: &std::path::Path, day: NaiveDate) -> PathBuf {
+    dir.join(format!("{}.{}", day.format("%Y-%m-%d"), EXTENSION))
+}
+
+/// Record files in `dir` with the day each covers.
+fn day_files(dir: &std::path::Path) -> std::io::Result<Vec<(NaiveDate, PathBuf)>> {
+    let mut files: Vec<(NaiveDate, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != EXTENSION {
+                return None;
+            }
+            let day = NaiveDate::parse_from_str(path.file_stem()?.to_str()?, "%Y-%m-%d").ok()?;
+            Some((day, path))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+impl ParityStore {
+    pub fn memory() -> Self {
+        Self {
+            backend: Mutex::new(Backend::Memory(VecDeque::new())),
+        }
+    }
+
+    /// Opens the configured store, deleting record files older than
+    /// `retention` as of `now`.
+    pub fn open(config: &MirrorParityConfig, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let Some(path) = &config.path else {
+            return Ok(Self::memory());
+        };
+        let dir = PathBuf::from(path);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create mirror parity store at {}", path))?;
+        let files = day_files(&dir).with_context(|| format!("failed to read mirror parity store at {}", path))?;
+        let oldest = (now - chrono::Duration::from_std(config.retention.get()).unwrap_or(chrono::Duration::MAX)).date_naive();
+        let (expired, kept): (Vec<_>, Vec<_>) = files.into_iter().partition(|(day, _)| *day < oldest);
+        for (_, file) in &expired {
+            if let Err(e) = fs::remove_file(file) {
+                warn!(file = %file.display(), error = %e, "Failed to delete expired mirror parity records");
+            }
+        }
+        info!(path, days = kept.len(), expired = expired.len(), "Opened mirror parity store");
+        Ok(Self {
+            backend: Mutex::new(Backend::Disk { dir, current: None }),
+        })
+    }
+
+    /// Whether records survive a restart.
+    pub fn is_persistent(&self) -> bool {
+        matches!(*self.backend.lock().unwrap(), Backend::Disk { .. })
+    }
+
+    /// Adds a record. A record that can't be written is logged and lost;
+    /// mirroring carries on.
+    pub fn record(&self, record: ParityRecord) {
+        match &mut *self.backend.lock().unwrap() {
+            Backend::Memory(records) => {
+                if records.len() >= MAX_MEMORY_RECORDS {
+                    records.pop_front();
+                }
+                records.push_back(record);
+            }
+            Backend::Disk { dir, current } => {
+                if let Err(e) = append(dir, current, &record) {
+                    error!(error = %e, "Failed to write mirror parity record");
+                    *current = None;
+                }
+            }
+        }
+    }
+
+    /// Records from `from` up to, not including, `to`, oldest first.
+    pub fn read(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<ParityRecord>> {
+        let in_range = |record: &ParityRecord| record.at >= from && record.at < to;
+        let dir = match &*self.backend.lock().unwrap() {
+            Backend::Memory(records) => return Ok(records.iter().filter(|record| in_range(record)).cloned().collect()),
+            Backend::Disk { dir, .. } => dir.clone(),
+        };
+
+        let mut records = Vec::new();
+        let mut unreadable = 0;
+        for (day, path) in day_files(&dir)? {
+            if day < from.date_naive() || day > to.date_naive() {
+                continue;
+            }
+            let file = File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+            for line in BufReader::new(file).lines() {
+                let line = line.with_context(|| format!("failed to read {}", path.display()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<ParityRecord>(&line) {
+                    Ok(record) if in_range(&record) => records.push(record),
+                    Ok(_) => {}
+                    Err(_) => unreadable += 1,
+                }
+            }
+        }
+        if unreadable > 0 {
+            warn!(unreadable, "Skipped unreadable mirror parity records");
+        }
+        records.sort_by_key(|record| record.at);
+        Ok(records)
+    }
+}
+
+fn append(dir: &std::path::Path, current: &mut Option<(NaiveDate, File)>, record: &ParityRecord) -> anyhow::Result<()> {
+    let day = record.at.date_naive();
+    if current.as_ref().is_none_or(|(open, _)| *open != day) {
+        let path = day_path(dir, day);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        // End a line the last process cut short, so it doesn't swallow the
+        // next record
+        if !ends_with_newline(&mut file)? {
+            file.write_all(b"\n")?;
+        }
+        *current = Some((day, file));
+    }
+    let (_, file) = current.as_mut().unwrap();
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Whether `file` is empty or its last byte ends a line.
+fn ends_with_newline(file: &mut File) -> std::io::Result<bool> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
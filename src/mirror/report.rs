@@ -0,0 +1,421 @@
+//! Sign-off report over a mirroring campaign, compiled from the parity
+//! store.
+//!
+//! Totals, status matrices and latency deltas are given overall and per
+//! route, and the verdict holds them to `mirror.report`. A route with fewer
+//! than `min_samples` requests is reported but not judged on its own.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+use super::parity::ParityRecord;
+use crate::{
+    config::{AppConfig, MirrorReportConfig},
+    monitoring::LatencyPercentiles,
+    notifications::{Notification, Notifier, Severity},
+};
+
+/// Status matrix column for requests the mirror never answered.
+const FAILED: &str = "failed";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MirrorReportRequest {
+    pub from: DateTime<Utc>,
+    /// Now when unset.
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Include the report rendered as Markdown.
+    #[serde(default)]
+    pub markdown: bool,
+    /// Post the Markdown report to the rollout webhook.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Fail,
+}
+
+impl Verdict {
+    fn of(passed: bool) -> Self {
+        if passed {
+            Verdict::Pass
+        } else {
+            Verdict::Fail
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Pass => "PASS",
+            Verdict::Fail => "FAIL",
+        }
+    }
+}
+
+/// Mirrored requests and how they compared. Failure rate is a share of all
+/// requests; the other rates are shares of those the mirror answered.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ParityTotals {
+    pub requests: u64,
+    /// The mirror never answered.
+    pub failures: u64,
+    /// The mirror answered with a different status.
+    pub status_mismatches: u64,
+    pub contract_violations: u64,
+    /// Bodies compared and found to differ.
+    pub body_mismatches: u64,
+    pub failure_rate: f64,
+    pub mismatch_rate: f64,
+    pub contract_violation_rate: f64,
+    /// Main status, then mirror status (`failed` when it never answered),
+    /// to request count.
+    pub status_matrix: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Mirror latency minus main latency, of answered requests.
+    pub latency_delta_ms: Option<LatencyPercentiles>,
+}
+
+impl ParityTotals {
+    fn of<'a>(records: impl IntoIterator<Item = &'a ParityRecord>) -> Self {
+        let mut totals = ParityTotals::default();
+        let mut deltas = Vec::new();
+        for record in records {
+            totals.requests += 1;
+            let mirror_status = match record.mirror_status {
+                Some(status) => {
+                    deltas.push(record.mirror_latency_ms - record.main_latency_ms);
+                    totals.status_mismatches += u64::from(status != record.main_status);
+                    totals.contract_violations += u64::from(record.contract_violation);
+                    totals.body_mismatches += u64::from(record.body_match == Some(false));
+                    status.to_string()
+                }
+                None => {
+                    totals.failures += 1;
+                    FAILED.to_string()
+                }
+            };
+            *totals
+                .status_matrix
+                .entry(record.main_status.to_string())
+                .or_default()
+                .entry(mirror_status)
+                .or_default() += 1;
+        }
+
+        let answered = totals.requests - totals.failures;
+        totals.failure_rate = percent(totals.failures, totals.requests);
+        totals.mismatch_rate = percent(totals.status_mismatches, answered);
+        totals.contract_violation_rate = percent(totals.contract_violations, answered);
+        totals.latency_delta_ms = LatencyPercentiles::from_samples(&deltas);
+        totals
+    }
+
+    fn p99_latency_delta_ms(&self) -> f64 {
+        self.latency_delta_ms.as_ref().map(|delta| delta.p99_ms).unwrap_or(0.0)
+    }
+
+    /// The rate and latency checks; sample counts are checked by the caller.
+    fn checks(&self, thresholds: &MirrorReportConfig) -> Vec<ThresholdCheck> {
+        vec![
+            ThresholdCheck::at_most("mismatch_rate", self.mismatch_rate, thresholds.max_mismatch_rate),
+            ThresholdCheck::at_most("failure_rate", self.failure_rate, thresholds.max_failure_rate),
+            ThresholdCheck::at_most(
+                "contract_violation_rate",
+                self.contract_violation_rate,
+                thresholds.max_contract_violation_rate,
+            ),
+            ThresholdCheck::at_most(
+                "p99_latency_delta_ms",
+                self.p99_latency_delta_ms(),
+                thresholds.max_p99_latency_delta_ms,
+            ),
+        ]
+    }
+}
+
+fn percent(count: u64, of: u64) -> f64 {
+    if of == 0 {
+        0.0
+    } else {
+        count as f64 / of as f64 * 100.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ThresholdCheck {
+    pub check: String,
+    pub observed: f64,
+    pub threshold: f64,
+    pub passed: bool,
+}
+
+impl ThresholdCheck {
+    fn at_most(check: &str, observed: f64, threshold: f64) -> Self {
+        Self {
+            check: check.to_string(),
+            observed,
+            threshold,
+            passed: observed <= threshold,
+        }
+    }
+
+    fn at_least(check: &str, observed: f64, threshold: f64) -> Self {
+        Self {
+            check: check.to_string(),
+            observed,
+            threshold,
+            passed: observed >= threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RouteParity {
+    pub method: String,
+    pub route: String,
+    pub totals: ParityTotals,
+    /// `None` when the route had too few requests to be judged.
+    pub verdict: Option<Verdict>,
+    /// Names of the checks the route failed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_checks: Vec<String>,
+}
+
+/// Requests whose statuses differed the same way on the same route. Only
+/// the route pattern and request ID are shown; never paths, queries,
+/// headers or bodies.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MismatchExample {
+    pub method: String,
+    pub route: String,
+    pub main_status: u16,
+    pub mirror_status: u16,
+    pub count: u64,
+    pub last_seen: DateTime<Utc>,
+    /// Of the latest such request, to find it in the logs.
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MirrorReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    /// The records came from a store that survives restarts; otherwise the
+    /// report only covers this process's recent requests.
+    pub persisted: bool,
+    pub verdict: Verdict,
+    /// Campaign-wide checks, including `min_samples` and `failing_routes`.
+    pub checks: Vec<ThresholdCheck>,
+    pub totals: ParityTotals,
+    /// Busiest first.
+    pub routes: Vec<RouteParity>,
+    /// Most frequent first, up to `mirror.report.examples`.
+    pub top_mismatches: Vec<MismatchExample>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markdown: Option<String>,
+}
+
+impl MirrorReport {
+    /// Compiles the report for `[from, to)` from `records`.
+    pub fn compile(
+        records: &[ParityRecord],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        persisted: bool,
+        thresholds: &MirrorReportConfig,
+        generated_at: DateTime<Utc>,
+    ) -> Self {
+        let mut by_route: BTreeMap<(&str, &str), Vec<&ParityRecord>> = BTreeMap::new();
+        for record in records {
+            by_route.entry((&record.method, &record.route)).or_default().push(record);
+        }
+        let mut routes: Vec<RouteParity> = by_route
+            .into_iter()
+            .map(|((method, route), records)| {
+                let totals = ParityTotals::of(records);
+                let judged = totals.requests >= thresholds.min_samples;
+                let failed_checks: Vec<String> = if judged {
+                    totals
+                        .checks(thresholds)
+                        .into_iter()
+                        .filter(|check| !check.passed)
+                        .map(|check| check.check)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                RouteParity {
+                    method: method.to_string(),
+                    route: route.to_string(),
+                    verdict: judged.then(|| Verdict::of(failed_checks.is_empty())),
+                    failed_checks,
+                    totals,
+                }
+            })
+            .collect();
+        routes.sort_by_key(|route| std::cmp::Reverse(route.totals.requests));
+
+        let totals = ParityTotals::of(records);
+        let failing_routes = routes.iter().filter(|route| route.verdict == Some(Verdict::Fail)).count();
+        let mut checks = vec![ThresholdCheck::at_least(
+            "min_samples",
+            totals.requests as f64,
+            thresholds.min_samples as f64,
+        )];
+        checks.extend(totals.checks(thresholds));
+        checks.push(ThresholdCheck::at_most("failing_routes", failing_routes as f64, 0.0));
+
+        Self {
+            from,
+            to,
+            generated_at,
+            persisted,
+            verdict: Verdict::of(checks.iter().all(|check| check.passed)),
+            checks,
+            totals,
+            routes,
+            top_mismatches: top_mismatches(records, thresholds.examples),
+            markdown: None,
+        }
+    }
+
+    /// The report for people: verdict, checks, routes and top mismatches.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Mirror parity report: {}\n\n\
+             {} to {}, {} mirrored requests{}.\n\n\
+             | Check | Observed | Threshold | Result |\n\
+             |---|---:|---:|---|\n",
+            self.verdict.as_str(),
+            self.from.to_rfc3339(),
+            self.to.to_rfc3339(),
+            self.totals.requests,
+            if self.persisted { "" } else { " (this process only; no parity store is configured)" },
+        );
+        for check in &self.checks {
+            out += &format!(
+                "| {} | {:.2} | {:.2} | {} |\n",
+                check.check,
+                check.observed,
+                check.threshold,
+                if check.passed { "pass" } else { "**fail**" }
+            );
+        }
+
+        out += "\n## Routes\n\n\
+                | Route | Requests | Mismatch % | Failure % | Contract % | p99 Δ ms | Verdict |\n\
+                |---|---:|---:|---:|---:|---:|---|\n";
+        for route in &self.routes {
+            let totals = &route.totals;
+            out += &format!(
+                "| {} {} | {} | {:.2} | {:.2} | {:.2} | {:.1} | {} |\n",
+                route.method,
+                route.route,
+                totals.requests,
+                totals.mismatch_rate,
+                totals.failure_rate,
+                totals.contract_violation_rate,
+                totals.p99_latency_delta_ms(),
+                match route.verdict {
+                    Some(Verdict::Pass) => "pass".to_string(),
+                    Some(Verdict::Fail) => format!("**fail** ({})", route.failed_checks.join(", ")),
+                    None => "too few requests".to_string(),
+                }
+            );
+        }
+
+        if !self.top_mismatches.is_empty() {
+            out += "\n## Top mismatches\n\n\
+                    | Route | Main | Mirror | Count | Latest request ID |\n\
+                    |---|---:|---:|---:|---|\n";
+            for example in &self.top_mismatches {
+                out += &format!(
+                    "| {} {} | {} | {} | {} | {} |\n",
+                    example.method,
+                    example.route,
+                    example.main_status,
+                    example.mirror_status,
+                    example.count,
+                    example.request_id.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        out
+    }
+
+    /// Posts the Markdown report to the rollout webhook, waiting for it to
+    /// be answered.
+    pub async fn notify(&self, notifier: &Notifier, config: &AppConfig) {
+        let payload = serde_json::json!({
+            "text": format!(
+                "Mirror parity report: {}\n\
+                 {} to {}: {} requests, {:.2}% status mismatches, {:.2}% failures\n\
+                 Service: project-gateway",
+                self.verdict.as_str(),
+                self.from.to_rfc3339(),
+                self.to.to_rfc3339(),
+                self.totals.requests,
+                self.totals.mismatch_rate,
+                self.totals.failure_rate,
+            ),
+            "username": "Gateway Mirror Report",
+            "verdict": self.verdict,
+            "attachment": {
+                "filename": format!("mirror-report-{}.md", self.to.format("%Y%m%dT%H%M%SZ")),
+                "content_type": "text/markdown",
+                "content": self.to_markdown(),
+            },
+        });
+        let notification = Notification {
+            kind: "mirror_report",
+            subject: format!("{}/{}", self.from.to_rfc3339(), self.to.to_rfc3339()),
+            severity: match self.verdict {
+                Verdict::Pass => Severity::Info,
+                Verdict::Fail => Severity::Warning,
+            },
+            // Asked for, so never held back as a repeat
+            state_change: true,
+            payload,
+        };
+        notifier.notify(config, notification).await;
+    }
+}
+
+fn top_mismatches(records: &[ParityRecord], limit: usize) -> Vec<MismatchExample> {
+    let mut groups: HashMap<(&str, &str, u16, u16), MismatchExample> = HashMap::new();
+    for record in records {
+        let Some(mirror_status) = record.mirror_status.filter(|status| *status != record.main_status) else {
+            continue;
+        };
+        let example = groups
+            .entry((&record.method, &record.route, record.main_status, mirror_status))
+            .or_insert_with(|| MismatchExample {
+                method: record.method.clone(),
+                route: record.route.clone(),
+                main_status: record.main_status,
+                mirror_status,
+                count: 0,
+                last_seen: record.at,
+                request_id: None,
+            });
+        example.count += 1;
+        if record.at >= example.last_seen {
+            example.last_seen = record.at;
+            example.request_id = record.request_id.clone();
+        }
+    }
+    let mut examples: Vec<MismatchExample> = groups.into_values().collect();
+    examples.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| (&a.method, &a.route, a.main_status, a.mirror_status).cmp(&(&b.method, &b.route, b.main_status, b.mirror_status)))
+    });
+    examples.truncate(limit);
+    examples
+}
@@ -0,0 +1,224 @@
+//! Streaming tee between the main response and the mirror comparison.
+//!
+//! The client's body passes through untouched: chunks are forwarded as the
+//! client reads them, and a reference to each is handed to the comparison
+//! over a bounded queue. The comparison hashes every chunk and keeps only
+//! the first `capture_limit` bytes, so a large body costs a prefix and a
+//! hash rather than a second copy. The tee never waits for the comparison:
+//! if the queue is full the capture is abandoned and the client stream
+//! carries on.
+
+use axum::body::Body;
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{ready, Context, Poll},
+};
+use tokio::sync::mpsc;
+
+/// Chunks the comparison may fall behind the client by before its capture
+/// is abandoned.
+pub const QUEUE_CHUNKS: usize = 64;
+
+/// What the comparison learned about a body it saw whole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyDigest {
+    pub bytes: u64,
+    /// Hex SHA-256 of the body as streamed.
+    pub sha256: String,
+    /// The first bytes of the body, up to the capture limit.
+    pub prefix: Vec<u8>,
+    /// `Content-Encoding` the body was streamed with.
+    pub content_encoding: Option<String>,
+}
+
+impl BodyDigest {
+    pub fn of(body: &[u8], capture_limit: usize, content_encoding: Option<String>) -> Self {
+        Self {
+            bytes: body.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(body)),
+            prefix: body[..body.len().min(capture_limit)].to_vec(),
+            content_encoding,
+        }
+    }
+
+    /// Whether `prefix` holds the whole body.
+    pub fn is_complete(&self) -> bool {
+        self.prefix.len() as u64 == self.bytes
+    }
+
+    /// Compares with another body's digest. Bodies with different encodings
+    /// aren't comparable and give `None`.
+    pub fn compare(&self, other: &BodyDigest) -> Option<BodyComparison> {
+        if self.content_encoding != other.content_encoding {
+            return None;
+        }
+        if self.bytes == other.bytes && self.sha256 == other.sha256 {
+            return Some(BodyComparison::Match);
+        }
+
+        let common = self.prefix.len().min(other.prefix.len());
+        let first_difference = self.prefix[..common]
+            .iter()
+            .zip(&other.prefix[..common])
+            .position(|(a, b)| a != b)
+            .map(|offset| offset as u64)
+            // Equal as far as both go: one of them ends there, or the
+            // difference lies beyond what was captured
+            .or_else(|| (self.is_complete() || other.is_complete()).then_some(common as u64));
+        Some(BodyComparison::Differs { first_difference })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyComparison {
+    Match,
+    /// `first_difference` is `None` when the bodies agree on the captured
+    /// prefixes and only their hashes tell them apart.
+    Differs { first_difference: Option<u64> },
+}
+
+/// Why the comparison didn't see the whole body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeeAbort {
+    /// The client went away or the body failed before its end.
+    Interrupted,
+    /// The comparison fell more than [`QUEUE_CHUNKS`] behind the client.
+    FellBehind,
+}
+
+impl TeeAbort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeeAbort::Interrupted => "interrupted",
+            TeeAbort::FellBehind => "fell_behind",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum End {
+    Complete,
+    Aborted(TeeAbort),
+}
+
+/// Wraps `body` so the comparison side can digest it as it streams.
+pub fn tee(body: Body, capture_limit: usize, content_encoding: Option<String>) -> (Body, TeeReader) {
+    let (chunks_tx, chunks_rx) = mpsc::channel(QUEUE_CHUNKS);
+    let end = Arc::new(OnceLock::new());
+    let body = Body::new(TeeBody {
+        inner: body,
+        chunks: Some(chunks_tx),
+        end: end.clone(),
+    });
+    let reader = TeeReader {
+        chunks: chunks_rx,
+        end,
+        capture_limit,
+        content_encoding,
+    };
+    (body, reader)
+}
+
+/// The comparison's end of a [`tee`].
+pub struct TeeReader {
+    chunks: mpsc::Receiver<Bytes>,
+    end: Arc<OnceLock<End>>,
+    capture_limit: usize,
+    content_encoding: Option<String>,
+}
+
+impl TeeReader {
+    /// Digests chunks as they arrive until the client's stream is done.
+    pub async fn finish(mut self) -> Result<BodyDigest, TeeAbort> {
+        let mut hasher = Sha256::new();
+        let mut prefix = Vec::new();
+        let mut bytes = 0u64;
+        while let Some(chunk) = self.chunks.recv().await {
+            hasher.update(&chunk);
+            let room = self.capture_limit.saturating_sub(prefix.len());
+            prefix.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            bytes += chunk.len() as u64;
+        }
+
+        // The sending side records how it ended before letting go of the queue
+        match self.end.get() {
+            Some(End::Complete) => Ok(BodyDigest {
+                bytes,
+                sha256: format!("{:x}", hasher.finalize()),
+                prefix,
+                content_encoding: self.content_encoding,
+            }),
+            Some(End::Aborted(abort)) => Err(*abort),
+            None => Err(TeeAbort::Interrupted),
+        }
+    }
+}
+
+pin_project! {
+    struct TeeBody<B> {
+        #[pin]
+        inner: B,
+        chunks: Option<mpsc::Sender<Bytes>>,
+        end: Arc<OnceLock<End>>,
+    }
+
+    impl<B> PinnedDrop for TeeBody<B> {
+        fn drop(this: Pin<&mut Self>) {
+            let _ = this.project().end.set(End::Aborted(TeeAbort::Interrupted));
+        }
+    }
+}
+
+impl<B> http_body::Body for TeeBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner.as_mut().poll_frame(cx));
+
+        let end = match &frame {
+            Some(Ok(frame)) => {
+                let forwarded = match (frame.data_ref(), this.chunks.as_ref()) {
+                    (Some(data), Some(chunks)) => chunks.try_send(data.clone()).is_ok(),
+                    _ => true,
+                };
+                if !forwarded {
+                    Some(End::Aborted(TeeAbort::FellBehind))
+                } else if this.inner.is_end_stream() {
+                    Some(End::Complete)
+                } else {
+                    None
+                }
+            }
+            Some(Err(_)) => Some(End::Aborted(TeeAbort::Interrupted)),
+            None => Some(End::Complete),
+        };
+        if let Some(end) = end {
+            let _ = this.end.set(end);
+            *this.chunks = None;
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadOnlyRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadOnlyStatus {
+    pub enabled: bool,
+    pub retry_after_seconds: u64,
+    pub exempt_routes: Vec<String>,
+    /// Whether the setting is kept in the overrides file; otherwise it lasts
+    /// until the config file is next reloaded.
+    pub persisted: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RollbackRequest {
+    /// Recorded in the logs and the rollback alert.
+    pub reason: Option<String>,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+/// Body of every JSON error the gateway produces itself.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// Machine-readable reason, e.g. `rate_limit_exceeded`.
+    pub code: String,
+    pub message: String,
+    /// The request's `X-Request-Id`, or one generated for it.
+    pub request_id: String,
+    /// Members particular to the code, such as `retry_after_seconds`.
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub details: Map<String, Value>,
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use super::{
+    monitoring::{LatencyDecomposition, LatencyDriftStatus, MirrorSummary},
+    rollout::CoordinationStatus,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GatekeeperStatus {
+    pub is_healthy: bool,
+    pub current_rollout_percentage: f64,
+    /// Share of traffic actually sent to the Rust path; below the configured
+    /// percentage while a slow-start ramp runs.
+    #[serde(default)]
+    pub effective_rollout_percentage: f64,
+    #[serde(default)]
+    pub slow_start: Option<SlowStartStatus>,
+    pub error_rate: f64,
+    pub latency_degradation_percent: f64,
+    pub last_check: u64,
+    pub rollback_triggered: bool,
+    pub rollback_reason: Option<String>,
+    /// Rust vs legacy latency, with legacy split into upstream time and the
+    /// gateway's own proxy overhead.
+    #[serde(default)]
+    pub latency: LatencyDecomposition,
+    /// Present during the mirror-only phase (0% rollout with mirroring on).
+    #[serde(default)]
+    pub rollout_readiness: Option<RolloutReadiness>,
+    /// Shared rollout state and whether this replica leads the gatekeeper.
+    #[serde(default)]
+    pub coordination: Option<CoordinationStatus>,
+    /// Maintenance windows in force.
+    #[serde(default)]
+    pub maintenance: Vec<ActiveMaintenance>,
+    /// Routes held below the rollout percentage after they regressed.
+    #[serde(default)]
+    pub scoped_rollbacks: Vec<ScopedReduction>,
+    /// Routes whose latency distribution has drifted long enough to hold
+    /// the rollout.
+    #[serde(default)]
+    pub latency_drift: Vec<LatencyDriftStatus>,
+    /// Rollout percentage of each route override, by group such as
+    /// `POST /api/v1/payments`. Other routes follow the rollout percentage.
+    #[serde(default)]
+    pub route_percentages: BTreeMap<String, f64>,
+}
+
+/// Whether mirror traffic looks good enough to start sending live traffic
+/// to the Rust path.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RolloutReadiness {
+    pub ready: bool,
+    pub blocking_reasons: Vec<String>,
+    pub mirror: Option<MirrorSummary>,
+}
+
+/// Slow-start progress, as reported in the gatekeeper status.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SlowStartStatus {
+    pub from_percentage: f64,
+    pub to_percentage: f64,
+    pub effective_percentage: f64,
+    pub elapsed_seconds: f64,
+    pub duration_seconds: f64,
+}
+
+/// A route held below the global rollout percentage after it regressed.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScopedReduction {
+    pub method: String,
+    pub route: String,
+    /// Share of the route's traffic that may go to Rust.
+    pub percentage: f64,
+    pub reason: String,
+    pub since: String,
+}
+
+/// A maintenance window in force.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ActiveMaintenance {
+    pub route_selector: String,
+    pub message: String,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
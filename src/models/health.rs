@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub service: String,
+    pub version: String,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DetailedHealthResponse {
+    pub status: String,
+    pub service: String,
+    pub version: String,
+    pub timestamp: String,
+    pub config_loaded: bool,
+    pub hot_reload_enabled: bool,
+    /// False while the config file is missing and the last good config is served.
+    pub config_file_present: bool,
+    pub config_last_loaded_at: String,
+    pub server_config: ServerConfigInfo,
+    pub upstream_services: UpstreamStatus,
+    /// Memory budget and each in-memory store's approximate footprint.
+    pub memory: MemoryReport,
+    /// The startup clock check; skew beyond `clock.max_skew` makes the
+    /// status `degraded`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_skew: Option<ClockSkew>,
+    /// The listener serving requests and its recent transitions; absent
+    /// when the gateway wasn't started with a listener supervisor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listener: Option<ListenerReport>,
+    /// How the last reload of the config file went; absent until one is
+    /// attempted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_last_reload: Option<ConfigReloadEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ServerConfigInfo {
+    pub host: String,
+    pub port: u16,
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamStatus {
+    /// `disabled`, `unknown` before the first probe, `healthy` or
+    /// `unhealthy` if any upstream failed its latest probe.
+    pub status: String,
+    pub note: String,
+    /// Latest probe of each upstream, with the method it negotiated.
+    pub upstreams: Vec<UpstreamProbe>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    /// What startup warm-up initialized and how long each step took.
+    pub warmup: Option<WarmupReport>,
+}
+
+/// Footprint of one store, as reported by `GET /health/detailed`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StoreUsage {
+    pub name: String,
+    pub bytes: u64,
+    pub evictable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryReport {
+    pub budget_bytes: u64,
+    /// Usage eviction brings the total back down to.
+    pub low_water_bytes: u64,
+    pub used_bytes: u64,
+    /// In eviction order; stores that are never evicted come last.
+    pub stores: Vec<StoreUsage>,
+}
+
+/// The system clock compared with a trusted source.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClockSkew {
+    /// Positive when the local clock runs ahead of the source.
+    pub skew_seconds: f64,
+    pub source: String,
+    pub checked_at: String,
+    pub within_tolerance: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMethod {
+    Head,
+    Get,
+}
+
+/// The latest probe of one upstream.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamProbe {
+    /// `legacy` or `mirror`.
+    pub name: String,
+    pub upstream: String,
+    pub url: String,
+    pub healthy: bool,
+    /// Method of the latest probe.
+    pub method: ProbeMethod,
+    /// False once the upstream has refused `HEAD`; it's probed with `GET`
+    /// from then on.
+    pub head_supported: bool,
+    pub status: Option<u16>,
+    pub latency_ms: f64,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WarmupStep {
+    pub name: String,
+    pub took_ms: f64,
+    /// What the step initialized.
+    pub detail: String,
+    /// A failed step keeps the gateway from reporting ready.
+    #[serde(default)]
+    pub failed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WarmupReport {
+    pub steps: Vec<WarmupStep>,
+    pub took_ms: f64,
+    pub completed_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerEventKind {
+    /// The listener bound at startup.
+    Bound,
+    /// A reload moved serving to a new listener.
+    Rebound,
+    /// A replaced listener finished its in-flight requests, or was closed
+    /// at the end of the grace period.
+    Drained,
+    /// A reload's listener couldn't be set up; the old one kept serving.
+    RebindFailed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListenerEvent {
+    pub kind: ListenerEventKind,
+    /// The listener the event is about, as `scheme://address`.
+    pub address: String,
+    /// The listener serving before a rebind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_address: Option<String>,
+    /// Why a rebind failed, or how many connections a drain cut off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListenerReport {
+    /// The listener accepting new connections, as `scheme://address`.
+    pub address: String,
+    pub since: String,
+    /// Most recent last.
+    pub events: Vec<ListenerEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigReloadOutcome {
+    Applied,
+    /// Only some sections loaded; the rest kept their previous values.
+    Partial,
+    /// The active config stayed in force.
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConfigReloadEvent {
+    pub outcome: ConfigReloadOutcome,
+    /// Top-level sections taken from the file in a partial reload.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub applied_sections: Vec<String>,
+    /// Top-level sections that didn't load and kept their previous values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retained_sections: Vec<String>,
+    /// Why the file didn't load in full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub at: String,
+}
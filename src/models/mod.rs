@@ -0,0 +1,12 @@
+//! Request and response bodies of the API, shared by the handlers and
+//! `client::GatewayClient` so the two can't drift.
+//! Only serde, chrono, uuid and utoipa are used here, which keeps the
+//! `client` feature free of the server's dependencies.
+
+pub mod admin;
+pub mod error;
+pub mod gatekeeper;
+pub mod health;
+pub mod monitoring;
+pub mod rollout;
+pub mod users;
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LatencyPercentiles {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub avg_ms: f64,
+}
+
+/// Latency decomposition for the status endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct LatencyDecomposition {
+    pub rust_ms: Option<LatencyPercentiles>,
+    pub legacy_total_ms: Option<LatencyPercentiles>,
+    pub legacy_upstream_first_byte_ms: Option<LatencyPercentiles>,
+    pub legacy_upstream_ms: Option<LatencyPercentiles>,
+    pub gateway_overhead_ms: Option<LatencyPercentiles>,
+}
+
+/// Mirror traffic over the recent sample window.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MirrorSummary {
+    pub samples: usize,
+    pub success_rate: f64,
+    pub mismatch_rate: f64,
+    #[serde(default)]
+    pub contract_violation_rate: f64,
+    pub mirror_p99_ms: f64,
+    pub main_p99_ms: f64,
+}
+
+impl MirrorSummary {
+    /// Mirror p99 relative to main p99; 1.0 when main latency is unknown.
+    pub fn latency_ratio(&self) -> f64 {
+        if self.main_p99_ms > 0.0 {
+            self.mirror_p99_ms / self.main_p99_ms
+        } else {
+            1.0
+        }
+    }
+}
+
+/// One SLI of an objective over its compliance window.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SliStatus {
+    /// Target share of good requests, in percent.
+    pub target: f64,
+    /// Share of good requests over the window, in percent; `None` without
+    /// traffic.
+    pub achieved: Option<f64>,
+    pub compliant: bool,
+    /// Share of the window's error budget left; negative once overspent.
+    pub error_budget_remaining: f64,
+    pub burn_rate_1h: f64,
+    pub burn_rate_6h: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SloStatus {
+    pub name: String,
+    pub method: Option<String>,
+    pub route: String,
+    pub window: String,
+    pub requests: u64,
+    pub availability: Option<SliStatus>,
+    pub latency: Option<SliStatus>,
+    /// The faster-burning SLI's rate over the last hour.
+    pub burn_rate_1h: f64,
+    pub burn_rate_6h: f64,
+    /// Whether the 1h burn rate is at or above `slo.fast_burn_threshold`.
+    pub fast_burn: bool,
+}
+
+/// How far one variant's latency distribution on a route has moved from its
+/// recent history.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LatencyDriftStatus {
+    /// `rust` or `legacy`.
+    pub variant: String,
+    pub route: String,
+    /// The latest interval's score; `None` until both it and the reference
+    /// have `min_samples` requests.
+    pub score: Option<f64>,
+    /// Requests in the latest interval.
+    pub samples: u64,
+    pub reference_samples: u64,
+    /// Consecutive intervals scored above `max_score`.
+    pub drifting_intervals: u32,
+    /// Drifting for `sustained_intervals` or more, which holds the rollout.
+    pub sustained: bool,
+}
+
+/// One variant's recent latency distribution, for drawing as a heatmap
+/// column.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LatencyHeatmapReport {
+    /// `rust` or `legacy`.
+    pub variant: String,
+    /// The route asked for; `None` when every route is merged.
+    pub route: Option<String>,
+    pub window: String,
+    /// Routes with requests in the window.
+    pub routes: usize,
+    pub samples: u64,
+    /// Bucket edges in milliseconds, ascending, one more than there are
+    /// counts. Each HDR bucket is at most 1/8 of its value wide, so the
+    /// edges are close to log-scaled. Empty when there were no requests.
+    pub boundaries_ms: Vec<f64>,
+    /// Requests in each bucket; they add up to `samples`.
+    pub counts: Vec<u64>,
+}
+
+/// One probe series and whether the scrape showed it move as recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MetricFamilyCheck {
+    pub family: String,
+    /// `counter` or `histogram`.
+    pub kind: String,
+    pub passed: bool,
+    pub expected_delta: f64,
+    /// How far the series moved between the scrapes before and after the
+    /// probe; `None` when it was missing afterwards.
+    pub observed_delta: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MetricsSelfCheck {
+    /// Whether every family passed.
+    pub passed: bool,
+    pub families: Vec<MetricFamilyCheck>,
+    pub checked_at: String,
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use utoipa::ToSchema;
+
+/// Who drives the rollout percentage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RolloutMode {
+    /// The gatekeeper advances and rolls back on its own.
+    #[default]
+    Automatic,
+    /// Only operators change the percentage; the gatekeeper still reports
+    /// degradation but doesn't act on it.
+    Manual,
+}
+
+/// Generations kept in [`RolloutState::history`].
+pub const MAX_ROLLOUT_HISTORY: usize = 100;
+
+/// Live rollout state shared by every replica.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RolloutState {
+    pub rollout_percentage: f64,
+    /// A paused rollout is never advanced; rollbacks still happen.
+    pub paused: bool,
+    pub mode: RolloutMode,
+    /// Bumped on every write. This is the rollout generation responses,
+    /// access logs, metrics and mirror records are stamped with.
+    pub version: u64,
+    pub updated_by: String,
+    /// Unix seconds.
+    pub updated_at: u64,
+    /// The most recent generations, oldest first, so a stamped generation
+    /// can be joined to the state it was served under.
+    #[serde(default)]
+    pub history: VecDeque<RolloutGeneration>,
+    /// The percentage the last global rollback left the rollout at, holding
+    /// route overrides to the rollout percentage until it's raised past it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_hold: Option<f64>,
+}
+
+impl RolloutState {
+    /// The state as of `generation`, while it is still in the history.
+    pub fn generation(&self, generation: u64) -> Option<&RolloutGeneration> {
+        self.history.iter().find(|entry| entry.generation == generation)
+    }
+}
+
+/// The rollout state as of one generation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RolloutGeneration {
+    pub generation: u64,
+    pub rollout_percentage: f64,
+    pub paused: bool,
+    pub mode: RolloutMode,
+    pub updated_by: String,
+    /// Unix seconds.
+    pub updated_at: u64,
+}
+
+/// A change to the shared rollout state; unset fields are left alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RolloutUpdate {
+    pub rollout_percentage: Option<f64>,
+    pub paused: Option<bool>,
+    pub mode: Option<RolloutMode>,
+}
+
+/// How this replica takes part in coordination, as reported in the
+/// gatekeeper status.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CoordinationStatus {
+    /// `local` or the configured backend kind.
+    pub backend: String,
+    pub instance_id: String,
+    /// Whether this replica runs the gatekeeper evaluation.
+    pub leader: bool,
+    /// The backend is unreachable and this replica is acting on its own
+    /// local state.
+    pub degraded: bool,
+    pub last_error: Option<String>,
+    pub state: RolloutState,
+}
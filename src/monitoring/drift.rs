@@ -0,0 +1,195 @@
+//! Distribution drift of each variant's latency per route.
+//!
+//! Requests land in a histogram for the current evaluation interval. At each
+//! evaluation that interval is scored against the intervals of the trailing
+//! `reference_window`, then joins them. Drift sustained over
+//! `sustained_intervals` evaluations holds the rollout; it is not a reason
+//! to roll back.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use super::stats::{ks_statistic, population_stability_index, LatencyHistogram};
+use crate::{
+    config::{DriftMeasure, LatencyDriftConfig},
+    AppState,
+};
+
+pub use crate::models::monitoring::LatencyDriftStatus;
+
+#[derive(Default)]
+struct Series {
+    current: LatencyHistogram,
+    /// Histograms of past intervals, oldest first.
+    reference: VecDeque<LatencyHistogram>,
+    last: Option<Scored>,
+    drifting_intervals: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Scored {
+    score: Option<f64>,
+    samples: u64,
+    reference_samples: u64,
+}
+
+/// Keyed by variant, then route.
+#[derive(Default)]
+pub struct LatencyDrift {
+    enabled: AtomicBool,
+    series: Mutex<BTreeMap<(&'static str, String), Series>>,
+}
+
+impl LatencyDrift {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request served by `variant` on `route`; a no-op while drift
+    /// detection is off.
+    pub fn record(&self, variant: &'static str, route: &str, latency_ms: f64) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(mut series) = self.series.lock() {
+            series.entry((variant, route.to_string())).or_default().current.record(latency_ms);
+        }
+    }
+
+    /// Scores every series' current interval against its reference and
+    /// starts a new interval.
+    pub fn evaluate(&self, config: &LatencyDriftConfig) -> Vec<LatencyDriftStatus> {
+        self.enabled.store(config.enabled, Ordering::Relaxed);
+        let Ok(mut all) = self.series.lock() else {
+            return Vec::new();
+        };
+        if !config.enabled {
+            all.clear();
+            return Vec::new();
+        }
+
+        let interval = config.evaluation_interval.get().as_secs_f64();
+        let kept = ((config.reference_window.get().as_secs_f64() / interval) as usize).max(1);
+        for ((variant, route), series) in all.iter_mut() {
+            let mut reference = LatencyHistogram::new();
+            for past in &series.reference {
+                reference.merge(past);
+            }
+            let current = std::mem::take(&mut series.current);
+            let enough = current.count() >= config.min_samples && reference.count() >= config.min_samples;
+            let score = enough
+                .then(|| match config.measure {
+                    DriftMeasure::Ks => ks_statistic(&reference, &current),
+                    DriftMeasure::Psi => population_stability_index(&reference, &current),
+                })
+                .flatten();
+            // Too little traffic to judge leaves the streak where it was
+            match score {
+                Some(score) if score > config.max_score => series.drifting_intervals += 1,
+                Some(_) => series.drifting_intervals = 0,
+                None => {}
+            }
+            if let Some(score) = score {
+                crate::metrics::record_latency_drift(variant, route, score);
+            }
+            series.last = Some(Scored {
+                score,
+                samples: current.count(),
+                reference_samples: reference.count(),
+            });
+            series.reference.push_back(current);
+            while series.reference.len() > kept {
+                series.reference.pop_front();
+            }
+        }
+        drop(all);
+        self.status(config)
+    }
+
+    /// Each series as of its last evaluation.
+    pub fn status(&self, config: &LatencyDriftConfig) -> Vec<LatencyDriftStatus> {
+        let Ok(all) = self.series.lock() else {
+            return Vec::new();
+        };
+        all.iter()
+            .filter_map(|((variant, route), series)| {
+                let last = series.last?;
+                Some(LatencyDriftStatus {
+                    variant: variant.to_string(),
+                    route: route.clone(),
+                    score: last.score,
+                    samples: last.samples,
+                    reference_samples: last.reference_samples,
+                    drifting_intervals: series.drifting_intervals,
+                    sustained: series.drifting_intervals >= config.sustained_intervals,
+                })
+            })
+            .collect()
+    }
+
+    /// Takes over each series' last score and drifting streak from statuses
+    /// exported by another instance. The reference histograms aren't
+    /// carried, so scoring resumes once this instance has built its own.
+    pub fn restore(&self, statuses: &[LatencyDriftStatus]) {
+        let Ok(mut all) = self.series.lock() else {
+            return;
+        };
+        for status in statuses {
+            let variant = match status.variant.as_str() {
+                "rust" => "rust",
+                "legacy" => "legacy",
+                _ => continue,
+            };
+            let series = all.entry((variant, status.route.clone())).or_default();
+            series.drifting_intervals = status.drifting_intervals;
+            series.last = Some(Scored {
+                score: status.score,
+                samples: status.samples,
+                reference_samples: status.reference_samples,
+            });
+        }
+    }
+
+    /// Series whose drift is sustained, as `variant route`.
+    pub fn sustained(&self, config: &LatencyDriftConfig) -> Vec<String> {
+        if !config.enabled {
+            return Vec::new();
+        }
+        self.status(config)
+            .into_iter()
+            .filter(|status| status.sustained)
+            .map(|status| format!("{} {}", status.variant, status.route))
+            .collect()
+    }
+
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        let mut reloads = state.config_watcher.subscribe_to_reloads();
+        loop {
+            let config = state.config_watcher.get_config().await;
+            let drift = &config.canary_rollout.latency_drift;
+            for status in self.evaluate(drift).iter().filter(|status| status.sustained) {
+                tracing::warn!(
+                    variant = %status.variant,
+                    route = %status.route,
+                    score = status.score,
+                    intervals = status.drifting_intervals,
+                    "Latency distribution drifting from its reference; holding the rollout"
+                );
+            }
+
+            tokio::select! {
+                reload = reloads.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(drift.evaluation_interval.get()) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Recent latency of each variant per route, in a shape a dashboard can
+//! draw as a heatmap.
+//!
+//! Every request lands in the histogram for its [`SLOT`], and each series
+//! keeps the slots of the last [`MAX_WINDOW`]. A query merges the slots in
+//! its window and groups the buckets between the fastest and the slowest
+//! request, so it costs the same however busy the route is and never sorts
+//! samples.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::stats::{bucket_bounds, LatencyHistogram, BUCKETS};
+use crate::config::HumanDuration;
+
+pub use crate::models::monitoring::LatencyHeatmapReport;
+
+/// Granularity of the window a heatmap covers.
+pub const SLOT: Duration = Duration::from_secs(15);
+/// The longest window a heatmap can cover.
+pub const MAX_WINDOW: Duration = Duration::from_secs(30 * 60);
+/// A heatmap can't have finer buckets than the histograms it is read from.
+pub const MAX_BUCKETS: usize = BUCKETS;
+
+#[derive(Default)]
+struct Series {
+    /// Histograms by slot, oldest first. Slots without requests are left out.
+    slots: VecDeque<(u64, LatencyHistogram)>,
+}
+
+/// Keyed by variant, then route.
+pub struct LatencyHeatmap {
+    started: Instant,
+    series: Mutex<BTreeMap<(&'static str, String), Series>>,
+}
+
+impl Default for LatencyHeatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHeatmap {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn current_slot(&self) -> u64 {
+        self.started.elapsed().as_secs() / SLOT.as_secs()
+    }
+
+    /// Counts a request served by `variant` on `route`.
+    pub fn record(&self, variant: &'static str, route: &str, latency_ms: f64) {
+        let slot = self.current_slot();
+        let kept = MAX_WINDOW.as_secs() / SLOT.as_secs();
+        let Ok(mut all) = self.series.lock() else {
+            return;
+        };
+        let series = all.entry((variant, route.to_string())).or_default();
+        if series.slots.back().is_none_or(|(last, _)| *last != slot) {
+            series.slots.push_back((slot, LatencyHistogram::new()));
+        }
+        while series.slots.front().is_some_and(|(first, _)| first + kept <= slot) {
+            series.slots.pop_front();
+        }
+        if let Some((_, histogram)) = series.slots.back_mut() {
+            histogram.record(latency_ms);
+        }
+    }
+
+    /// `variant`'s latency over the last `window` on `route`, or on every
+    /// route together, in at most `buckets` buckets. The window is rounded
+    /// up to whole slots and the current slot counts as one.
+    pub fn report(
+        &self,
+        variant: &str,
+        route: Option<&str>,
+        window: Duration,
+        buckets: usize,
+    ) -> LatencyHeatmapReport {
+        let now = self.current_slot();
+        let slots = window.as_secs().div_ceil(SLOT.as_secs()).max(1);
+        let mut merged = LatencyHistogram::new();
+        let mut routes = 0;
+        if let Ok(all) = self.series.lock() {
+            let matching = all.iter().filter(|((series_variant, series_route), _)| {
+                *series_variant == variant && route.is_none_or(|route| route == series_route)
+            });
+            for (_, series) in matching {
+                let before = merged.count();
+                for (_, histogram) in series.slots.iter().rev().take_while(|(slot, _)| slot + slots > now) {
+                    merged.merge(histogram);
+                }
+                if merged.count() > before {
+                    routes += 1;
+                }
+            }
+        }
+
+        let (boundaries_ms, counts) = group(&merged, buckets);
+        LatencyHeatmapReport {
+            variant: variant.to_string(),
+            route: route.map(str::to_string),
+            window: HumanDuration::from_millis(window.as_millis() as u64).to_string(),
+            routes,
+            samples: merged.count(),
+            boundaries_ms,
+            counts,
+        }
+    }
+}
+
+/// Joins runs of neighbouring buckets, from the first non-empty one to the
+/// last, so there are at most `buckets` of them. Returns the edges, one
+/// more than there are counts, and the counts; both empty for an empty
+/// histogram.
+fn group(histogram: &LatencyHistogram, buckets: usize) -> (Vec<f64>, Vec<u64>) {
+    let counts = histogram.counts();
+    let (Some(first), Some(last)) = (
+        counts.iter().position(|&count| count > 0),
+        counts.iter().rposition(|&count| count > 0),
+    ) else {
+        return (Vec::new(), Vec::new());
+    };
+    let width = (last - first + 1).div_ceil(buckets.max(1));
+    let mut boundaries = vec![bucket_bounds(first).0];
+    let mut grouped = Vec::new();
+    for start in (first..=last).step_by(width) {
+        let end = (start + width).min(last + 1);
+        grouped.push(counts[start..end].iter().sum());
+        boundaries.push(bucket_bounds(end - 1).1);
+    }
+    (boundaries, grouped)
+}
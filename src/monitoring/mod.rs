@@ -1,3 +1,9 @@
+pub mod drift;
+pub mod heatmap;
+pub mod slo;
+pub mod stats;
+pub mod trace_sampling;
+
 use std::{
     sync::{Arc, Mutex},
     time::Duration,
@@ -5,8 +11,57 @@ use std::{
 use serde::{Deserialize, Serialize};
 use tokio::time::interval;
 use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::memory::MemoryConsumer;
+
+pub use crate::models::monitoring::{LatencyDecomposition, LatencyPercentiles, MirrorSummary};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const MAX_SAMPLES: usize = 1000;
+
+impl LatencyPercentiles {
+    pub(crate) fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let len = sorted.len();
+
+        Some(Self {
+            p50_ms: sorted[len / 2],
+            p95_ms: sorted[((len as f64) * 0.95) as usize],
+            p99_ms: sorted[((len as f64) * 0.99) as usize],
+            avg_ms: sorted.iter().sum::<f64>() / len as f64,
+        })
+    }
+}
+
+/// How long a proxied legacy request spent in the upstream versus in the
+/// gateway itself. `overhead_ms` is everything outside the upstream call:
+/// connection acquisition, header copying, and response conversion.
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamTiming {
+    pub first_byte_ms: f64,
+    pub full_body_ms: f64,
+    pub overhead_ms: f64,
+}
+
+/// Result of one mirrored request, compared with the main response.
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorOutcome {
+    /// The mirror request completed (any status).
+    pub success: bool,
+    /// The mirror returned a different status than the main response.
+    pub mismatch: bool,
+    /// The mirror response broke its route's response expectations.
+    pub contract_violation: bool,
+    pub mirror_latency_ms: f64,
+    pub main_latency_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PerformanceMetrics {
     pub p99_latency_ms: f64,
     pub p95_latency_ms: f64,
@@ -17,9 +72,29 @@ pub struct PerformanceMetrics {
     pub cpu_usage_percent: f64,
     pub memory_usage_mb: f64,
     pub timestamp: u64,
+    /// Upstream time to the full response body (legacy only).
+    #[serde(default)]
+    pub upstream_latency_ms: Option<LatencyPercentiles>,
+    /// Upstream time to the response headers (legacy only).
+    #[serde(default)]
+    pub upstream_first_byte_ms: Option<LatencyPercentiles>,
+    /// Time the gateway itself added on the proxy path (legacy only).
+    #[serde(default)]
+    pub gateway_overhead_ms: Option<LatencyPercentiles>,
 }
 
-#[derive(Debug, Clone)]
+impl PerformanceMetrics {
+    /// p99 to compare across variants: upstream time when known, so the
+    /// gateway's own proxy overhead isn't charged to the legacy gateway.
+    pub fn comparable_p99_ms(&self) -> f64 {
+        self.upstream_latency_ms
+            .as_ref()
+            .map(|upstream| upstream.p99_ms)
+            .unwrap_or(self.p99_latency_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PerformanceBaseline {
     pub rust_metrics: PerformanceMetrics,
     pub legacy_metrics: PerformanceMetrics,
@@ -33,9 +108,19 @@ pub struct PerformanceMonitor {
     legacy_errors: Arc<Mutex<u64>>,
     rust_requests: Arc<Mutex<u64>>,
     legacy_requests: Arc<Mutex<u64>>,
+    legacy_upstream_latencies: Arc<Mutex<Vec<f64>>>,
+    legacy_first_byte_latencies: Arc<Mutex<Vec<f64>>>,
+    legacy_overheads: Arc<Mutex<Vec<f64>>>,
+    mirror_outcomes: Arc<Mutex<Vec<MirrorOutcome>>>,
     baseline: Arc<Mutex<Option<PerformanceBaseline>>>,
 }
 
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PerformanceMonitor {
     pub fn new() -> Self {
         Self {
@@ -45,6 +130,10 @@ impl PerformanceMonitor {
             legacy_errors: Arc::new(Mutex::new(0)),
             rust_requests: Arc::new(Mutex::new(0)),
             legacy_requests: Arc::new(Mutex::new(0)),
+            legacy_upstream_latencies: Arc::new(Mutex::new(Vec::new())),
+            legacy_first_byte_latencies: Arc::new(Mutex::new(Vec::new())),
+            legacy_overheads: Arc::new(Mutex::new(Vec::new())),
+            mirror_outcomes: Arc::new(Mutex::new(Vec::new())),
             baseline: Arc::new(Mutex::new(None)),
         }
     }
@@ -52,14 +141,7 @@ impl PerformanceMonitor {
     pub fn record_request(&self, gateway_type: &str, latency_ms: f64, is_error: bool) {
         match gateway_type {
             "rust" => {
-                if let Ok(mut latencies) = self.rust_latencies.lock() {
-                    latencies.push(latency_ms);
-                    // Keep only last 1000 measurements for memory efficiency
-                    if latencies.len() > 1000 {
-                        let len = latencies.len();
-                        latencies.drain(0..len - 1000);
-                    }
-                }
+                push_sample(&self.rust_latencies, latency_ms);
                 if let Ok(mut requests) = self.rust_requests.lock() {
                     *requests += 1;
                 }
@@ -70,13 +152,7 @@ impl PerformanceMonitor {
                 }
             }
             "legacy" => {
-                if let Ok(mut latencies) = self.legacy_latencies.lock() {
-                    latencies.push(latency_ms);
-                    if latencies.len() > 1000 {
-                        let len = latencies.len();
-                        latencies.drain(0..len - 1000);
-                    }
-                }
+                push_sample(&self.legacy_latencies, latency_ms);
                 if let Ok(mut requests) = self.legacy_requests.lock() {
                     *requests += 1;
                 }
@@ -90,6 +166,54 @@ impl PerformanceMonitor {
         }
     }
 
+    /// Records the upstream/overhead split of a proxied legacy request,
+    /// alongside its total recorded via `record_request`.
+    pub fn record_legacy_upstream(&self, timing: UpstreamTiming) {
+        push_sample(&self.legacy_first_byte_latencies, timing.first_byte_ms);
+        push_sample(&self.legacy_upstream_latencies, timing.full_body_ms);
+        push_sample(&self.legacy_overheads, timing.overhead_ms);
+    }
+
+    pub fn record_mirror(&self, outcome: MirrorOutcome) {
+        push_sample(&self.mirror_outcomes, outcome);
+    }
+
+    /// Summary of recent mirror outcomes, or `None` before any were recorded.
+    pub fn mirror_summary(&self) -> Option<MirrorSummary> {
+        let outcomes = self.mirror_outcomes.lock().ok()?;
+        if outcomes.is_empty() {
+            return None;
+        }
+
+        let samples = outcomes.len();
+        let percent = |count: usize| count as f64 / samples as f64 * 100.0;
+        let completed: Vec<&MirrorOutcome> = outcomes.iter().filter(|outcome| outcome.success).collect();
+        let p99 = |latencies: Vec<f64>| {
+            LatencyPercentiles::from_samples(&latencies)
+                .map(|percentiles| percentiles.p99_ms)
+                .unwrap_or(0.0)
+        };
+
+        Some(MirrorSummary {
+            samples,
+            success_rate: percent(completed.len()),
+            mismatch_rate: percent(completed.iter().filter(|outcome| outcome.mismatch).count()),
+            contract_violation_rate: percent(completed.iter().filter(|outcome| outcome.contract_violation).count()),
+            mirror_p99_ms: p99(completed.iter().map(|outcome| outcome.mirror_latency_ms).collect()),
+            main_p99_ms: p99(outcomes.iter().map(|outcome| outcome.main_latency_ms).collect()),
+        })
+    }
+
+    pub fn latency_decomposition(&self) -> LatencyDecomposition {
+        LatencyDecomposition {
+            rust_ms: percentiles_of(&self.rust_latencies),
+            legacy_total_ms: percentiles_of(&self.legacy_latencies),
+            legacy_upstream_first_byte_ms: percentiles_of(&self.legacy_first_byte_latencies),
+            legacy_upstream_ms: percentiles_of(&self.legacy_upstream_latencies),
+            gateway_overhead_ms: percentiles_of(&self.legacy_overheads),
+        }
+    }
+
     pub fn get_current_metrics(&self, gateway_type: &str) -> Option<PerformanceMetrics> {
         match gateway_type {
             "rust" => self.calculate_metrics(
@@ -97,11 +221,14 @@ impl PerformanceMonitor {
                 &self.rust_requests,
                 &self.rust_errors,
             ),
-            "legacy" => self.calculate_metrics(
-                &self.legacy_latencies,
-                &self.legacy_requests,
-                &self.legacy_errors,
-            ),
+            "legacy" => self
+                .calculate_metrics(&self.legacy_latencies, &self.legacy_requests, &self.legacy_errors)
+                .map(|metrics| PerformanceMetrics {
+                    upstream_latency_ms: percentiles_of(&self.legacy_upstream_latencies),
+                    upstream_first_byte_ms: percentiles_of(&self.legacy_first_byte_latencies),
+                    gateway_overhead_ms: percentiles_of(&self.legacy_overheads),
+                    ..metrics
+                }),
             _ => None,
         }
     }
@@ -112,23 +239,10 @@ impl PerformanceMonitor {
         requests: &Arc<Mutex<u64>>,
         errors: &Arc<Mutex<u64>>,
     ) -> Option<PerformanceMetrics> {
-        let latencies = latencies.lock().ok()?;
+        let latency = percentiles_of(latencies)?;
         let request_count = *requests.lock().ok()?;
         let error_count = *errors.lock().ok()?;
 
-        if latencies.is_empty() {
-            return None;
-        }
-
-        let mut sorted_latencies = latencies.clone();
-        sorted_latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-        let len = sorted_latencies.len();
-        let p99_latency_ms = sorted_latencies[((len as f64) * 0.99) as usize];
-        let p95_latency_ms = sorted_latencies[((len as f64) * 0.95) as usize];
-        let p50_latency_ms = sorted_latencies[len / 2];
-        let avg_latency_ms = sorted_latencies.iter().sum::<f64>() / len as f64;
-
         let error_rate = if request_count > 0 {
             (error_count as f64 / request_count as f64) * 100.0
         } else {
@@ -139,10 +253,10 @@ impl PerformanceMonitor {
         let (cpu_usage_percent, memory_usage_mb) = get_system_metrics();
 
         Some(PerformanceMetrics {
-            p99_latency_ms,
-            p95_latency_ms,
-            p50_latency_ms,
-            avg_latency_ms,
+            p99_latency_ms: latency.p99_ms,
+            p95_latency_ms: latency.p95_ms,
+            p50_latency_ms: latency.p50_ms,
+            avg_latency_ms: latency.avg_ms,
             request_count,
             error_rate,
             cpu_usage_percent,
@@ -151,12 +265,15 @@ impl PerformanceMonitor {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            upstream_latency_ms: None,
+            upstream_first_byte_ms: None,
+            gateway_overhead_ms: None,
         })
     }
 
     pub fn set_baseline(&self, rust_metrics: PerformanceMetrics, legacy_metrics: PerformanceMetrics) {
-        let improvement_factor = if legacy_metrics.p99_latency_ms > 0.0 {
-            legacy_metrics.p99_latency_ms / rust_metrics.p99_latency_ms
+        let improvement_factor = if legacy_metrics.comparable_p99_ms() > 0.0 {
+            legacy_metrics.comparable_p99_ms() / rust_metrics.p99_latency_ms
         } else {
             1.0
         };
@@ -164,6 +281,7 @@ impl PerformanceMonitor {
         info!(
             rust_p99 = rust_metrics.p99_latency_ms,
             legacy_p99 = legacy_metrics.p99_latency_ms,
+            legacy_upstream_p99 = legacy_metrics.comparable_p99_ms(),
             improvement_factor = improvement_factor,
             "Performance baseline established"
         );
@@ -179,6 +297,13 @@ impl PerformanceMonitor {
         }
     }
 
+    /// Takes over a baseline established elsewhere, as it was.
+    pub fn restore_baseline(&self, baseline: PerformanceBaseline) {
+        if let Ok(mut baseline_lock) = self.baseline.lock() {
+            *baseline_lock = Some(baseline);
+        }
+    }
+
     pub fn get_baseline(&self) -> Option<PerformanceBaseline> {
         self.baseline.lock().ok()?.clone()
     }
@@ -190,9 +315,11 @@ impl PerformanceMonitor {
 
         match (rust_metrics, legacy_metrics, baseline) {
             (Some(rust), Some(legacy), Some(baseline)) => {
+                // Rust handler time vs legacy upstream time; proxy overhead
+                // is reported separately rather than counted against legacy
+                let legacy_p99 = baseline.legacy_metrics.comparable_p99_ms();
                 let latency_improvement = if rust.p99_latency_ms > 0.0 {
-                    ((baseline.legacy_metrics.p99_latency_ms - rust.p99_latency_ms) 
-                     / baseline.legacy_metrics.p99_latency_ms) * 100.0
+                    ((legacy_p99 - rust.p99_latency_ms) / legacy_p99) * 100.0
                 } else {
                     0.0
                 };
@@ -223,6 +350,7 @@ impl PerformanceMonitor {
                     overall_success: latency_improvement >= 50.0 
                         && (memory_improvement >= 70.0 || cpu_improvement >= 70.0) 
                         && rust.error_rate <= 0.5,
+                    gateway_overhead_ms: legacy.gateway_overhead_ms,
                 }
             }
             _ => PerformanceValidation::default(),
@@ -244,6 +372,7 @@ impl PerformanceMonitor {
                 rust_error_rate = validation.error_rate_rust,
                 legacy_error_rate = validation.error_rate_legacy,
                 overall_success = validation.overall_success,
+                gateway_overhead_p99_ms = validation.gateway_overhead_ms.as_ref().map(|overhead| overhead.p99_ms),
                 "Performance validation update"
             );
 
@@ -256,7 +385,7 @@ impl PerformanceMonitor {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PerformanceValidation {
     pub latency_improvement_percent: f64,
     pub memory_improvement_percent: f64,
@@ -267,6 +396,9 @@ pub struct PerformanceValidation {
     pub meets_resource_target: bool,
     pub meets_error_target: bool,
     pub overall_success: bool,
+    /// Proxy overhead on the legacy path, excluded from the latency comparison.
+    #[serde(default)]
+    pub gateway_overhead_ms: Option<LatencyPercentiles>,
 }
 
 impl Default for PerformanceValidation {
@@ -281,10 +413,46 @@ impl Default for PerformanceValidation {
             meets_resource_target: false,
             meets_error_target: true,
             overall_success: false,
+            gateway_overhead_ms: None,
+        }
+    }
+}
+
+/// Only the mirror outcome ring is evictable; the latency samples feed
+/// rollback decisions and are already capped at `MAX_SAMPLES`.
+impl MemoryConsumer for PerformanceMonitor {
+    fn memory_usage(&self) -> u64 {
+        let outcomes = self.mirror_outcomes.lock().map(|outcomes| outcomes.len()).unwrap_or(0);
+        (outcomes * std::mem::size_of::<MirrorOutcome>()) as u64
+    }
+
+    /// Drops the oldest mirror outcomes.
+    fn evict(&self, bytes: u64) -> u64 {
+        let Ok(mut outcomes) = self.mirror_outcomes.lock() else {
+            return 0;
+        };
+        let size = std::mem::size_of::<MirrorOutcome>() as u64;
+        let dropped = (bytes.div_ceil(size) as usize).min(outcomes.len());
+        outcomes.drain(..dropped);
+        dropped as u64 * size
+    }
+}
+
+fn push_sample<T>(samples: &Mutex<Vec<T>>, value: T) {
+    if let Ok(mut samples) = samples.lock() {
+        samples.push(value);
+        // Keep only the most recent measurements for memory efficiency
+        if samples.len() > MAX_SAMPLES {
+            let len = samples.len();
+            samples.drain(0..len - MAX_SAMPLES);
         }
     }
 }
 
+fn percentiles_of(samples: &Mutex<Vec<f64>>) -> Option<LatencyPercentiles> {
+    LatencyPercentiles::from_samples(&samples.lock().ok()?)
+}
+
 fn get_system_metrics() -> (f64, f64) {
     // Simplified system metrics - in production, use proper system monitoring
     // This is a placeholder implementation
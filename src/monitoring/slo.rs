@@ -0,0 +1,310 @@
+//! Rolling SLO compliance and error-budget burn rates.
+//!
+//! Requests matching an objective are counted in one-minute buckets: total,
+//! failed (5xx), and slower than the latency threshold. A burn rate is the
+//! share of bad requests over a window divided by the share the objective
+//! allows, so 1.0 spends the budget exactly over the objective's window.
+
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::{AppConfig, SloConfig, SloObjective},
+    notifications::{Notification, Notifier, Severity},
+    AppState,
+};
+
+pub use crate::models::monitoring::{SliStatus, SloStatus};
+
+/// Burn-rate windows, as labelled on `gateway_slo_burn_rate`.
+pub const BURN_WINDOWS: [(&str, Duration); 2] = [("1h", Duration::from_secs(3600)), ("6h", Duration::from_secs(6 * 3600))];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    minute: i64,
+    total: u64,
+    failed: u64,
+    slow: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    total: u64,
+    failed: u64,
+    slow: u64,
+}
+
+impl SloStatus {
+    fn burn_rate(&self, window: &str) -> f64 {
+        if window == "1h" {
+            self.burn_rate_1h
+        } else {
+            self.burn_rate_6h
+        }
+    }
+}
+
+/// Per-objective request counts and which objectives are fast-burning.
+#[derive(Default)]
+pub struct SloTracker {
+    buckets: RwLock<HashMap<String, Mutex<VecDeque<Bucket>>>>,
+    fast_burning: Mutex<HashSet<String>>,
+}
+
+fn minute(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(60)
+}
+
+/// Buckets kept for an objective: its window, or the longest burn window.
+fn retention_minutes(objective: &SloObjective) -> i64 {
+    let longest = BURN_WINDOWS.iter().map(|(_, window)| *window).max().unwrap_or_default();
+    (objective.window.get().max(longest).as_secs() / 60) as i64
+}
+
+impl SloTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a finished request against every objective it falls under.
+    pub fn record(&self, config: &SloConfig, method: &str, route: &str, status: u16, latency: Duration) {
+        self.record_at(config, method, route, status, latency, Utc::now());
+    }
+
+    pub fn record_at(
+        &self,
+        config: &SloConfig,
+        method: &str,
+        route: &str,
+        status: u16,
+        latency: Duration,
+        at: DateTime<Utc>,
+    ) {
+        for objective in config.objectives.iter().filter(|objective| objective.matches(method, route)) {
+            let slow = objective
+                .latency
+                .as_ref()
+                .is_some_and(|latency_objective| latency > latency_objective.threshold.get());
+            self.count(objective, minute(at), status >= 500, slow);
+        }
+    }
+
+    fn count(&self, objective: &SloObjective, minute: i64, failed: bool, slow: bool) {
+        let known = self
+            .buckets
+            .read()
+            .map(|buckets| buckets.contains_key(&objective.name))
+            .unwrap_or(false);
+        if !known {
+            if let Ok(mut buckets) = self.buckets.write() {
+                buckets.entry(objective.name.clone()).or_default();
+            }
+        }
+        let Ok(buckets) = self.buckets.read() else { return };
+        let Some(Ok(mut series)) = buckets.get(&objective.name).map(|series| series.lock()) else {
+            return;
+        };
+
+        let index = series.partition_point(|bucket| bucket.minute < minute);
+        if series.get(index).is_none_or(|bucket| bucket.minute != minute) {
+            series.insert(
+                index,
+                Bucket {
+                    minute,
+                    ..Bucket::default()
+                },
+            );
+        }
+        let bucket = &mut series[index];
+        bucket.total += 1;
+        bucket.failed += failed as u64;
+        bucket.slow += slow as u64;
+
+        let oldest = minute - retention_minutes(objective);
+        while series.front().is_some_and(|bucket| bucket.minute <= oldest) {
+            series.pop_front();
+        }
+    }
+
+    /// Counts for `objective` over the `window` ending at `now`.
+    fn counts(&self, objective: &SloObjective, window: Duration, now: DateTime<Utc>) -> Counts {
+        let newest = minute(now);
+        let oldest = newest - (window.as_secs() / 60) as i64;
+        let Ok(buckets) = self.buckets.read() else {
+            return Counts::default();
+        };
+        let Some(Ok(series)) = buckets.get(&objective.name).map(|series| series.lock()) else {
+            return Counts::default();
+        };
+        series
+            .iter()
+            .filter(|bucket| bucket.minute > oldest && bucket.minute <= newest)
+            .fold(Counts::default(), |counts, bucket| Counts {
+                total: counts.total + bucket.total,
+                failed: counts.failed + bucket.failed,
+                slow: counts.slow + bucket.slow,
+            })
+    }
+
+    /// Compliance and burn rates of every objective as of `now`.
+    pub fn status_at(&self, config: &SloConfig, now: DateTime<Utc>) -> Vec<SloStatus> {
+        config
+            .objectives
+            .iter()
+            .map(|objective| {
+                let window = self.counts(objective, objective.window.get(), now);
+                let recent: Vec<Counts> = BURN_WINDOWS
+                    .iter()
+                    .map(|(_, length)| self.counts(objective, *length, now))
+                    .collect();
+                let sli = |target: f64, bad: fn(&Counts) -> u64| {
+                    sli_status(target, bad(&window), window.total, &recent, bad)
+                };
+                let availability = objective.availability.map(|target| sli(target, |counts| counts.failed));
+                let latency = objective
+                    .latency
+                    .as_ref()
+                    .map(|latency| sli(latency.percentile, |counts| counts.slow));
+
+                let fastest = |rate: fn(&SliStatus) -> f64| {
+                    [&availability, &latency]
+                        .iter()
+                        .filter_map(|sli| sli.as_ref().map(rate))
+                        .fold(0.0, f64::max)
+                };
+                let burn_rate_1h = fastest(|sli| sli.burn_rate_1h);
+                let burn_rate_6h = fastest(|sli| sli.burn_rate_6h);
+                SloStatus {
+                    name: objective.name.clone(),
+                    method: objective.method.clone(),
+                    route: objective.route.clone(),
+                    window: objective.window.to_string(),
+                    requests: window.total,
+                    availability,
+                    latency,
+                    burn_rate_1h,
+                    burn_rate_6h,
+                    fast_burn: burn_rate_1h >= config.fast_burn_threshold,
+                }
+            })
+            .collect()
+    }
+
+    pub fn status(&self, config: &SloConfig) -> Vec<SloStatus> {
+        self.status_at(config, Utc::now())
+    }
+
+    /// Names of the objectives currently burning fast.
+    pub fn fast_burning(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .fast_burning
+            .lock()
+            .map(|burning| burning.iter().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Publishes the burn-rate gauges and returns the objectives that have
+    /// started burning fast since the last evaluation.
+    pub fn evaluate_at(&self, config: &SloConfig, now: DateTime<Utc>) -> Vec<SloStatus> {
+        let statuses = self.status_at(config, now);
+        let Ok(mut burning) = self.fast_burning.lock() else {
+            return Vec::new();
+        };
+        burning.retain(|name| statuses.iter().any(|status| status.name == *name));
+
+        let mut raised = Vec::new();
+        for status in statuses {
+            for (window, _) in BURN_WINDOWS {
+                crate::metrics::record_slo_burn_rate(&status.name, window, status.burn_rate(window));
+            }
+            if status.fast_burn && burning.insert(status.name.clone()) {
+                warn!(
+                    event = "slo_fast_burn",
+                    slo = %status.name,
+                    route = %status.route,
+                    burn_rate_1h = status.burn_rate_1h,
+                    burn_rate_6h = status.burn_rate_6h,
+                    threshold = config.fast_burn_threshold,
+                    "SLO error budget is burning fast"
+                );
+                raised.push(status);
+            } else if !status.fast_burn && burning.remove(&status.name) {
+                info!(slo = %status.name, burn_rate_1h = status.burn_rate_1h, "SLO burn rate back under the fast-burn threshold");
+            }
+        }
+        raised
+    }
+
+    /// Evaluates every `slo.evaluation_interval`, posting newly raised
+    /// fast-burn alerts to the rollout webhook.
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        let mut reloads = state.config_watcher.subscribe_to_reloads();
+        loop {
+            let config = state.config_watcher.get_config().await;
+            for status in self.evaluate_at(&config.slo, Utc::now()) {
+                notify_fast_burn(&state.notifier, &config, &status);
+            }
+
+            tokio::select! {
+                reload = reloads.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(config.slo.evaluation_interval.get()) => {}
+            }
+        }
+    }
+}
+
+fn sli_status(target: f64, bad: u64, total: u64, recent: &[Counts], bad_of: fn(&Counts) -> u64) -> SliStatus {
+    let allowed = (1.0 - target / 100.0).max(f64::EPSILON);
+    let bad_share = |bad: u64, total: u64| if total == 0 { 0.0 } else { bad as f64 / total as f64 };
+    let burn_rate = |counts: &Counts| bad_share(bad_of(counts), counts.total) / allowed;
+    let achieved = (total > 0).then(|| (1.0 - bad_share(bad, total)) * 100.0);
+    SliStatus {
+        target,
+        achieved,
+        compliant: achieved.is_none_or(|achieved| achieved >= target),
+        error_budget_remaining: 1.0 - bad_share(bad, total) / allowed,
+        burn_rate_1h: recent.first().map(burn_rate).unwrap_or_default(),
+        burn_rate_6h: recent.get(1).map(burn_rate).unwrap_or_default(),
+    }
+}
+
+/// Posts a fast-burn alert to the rollout webhook without holding up the
+/// evaluation. An objective that keeps crossing the threshold is held to
+/// `notifications.min_interval`.
+pub fn notify_fast_burn(notifier: &Notifier, config: &AppConfig, status: &SloStatus) {
+    let payload = serde_json::json!({
+        "text": format!(
+            "SLO {} is burning its error budget fast\n\
+             Route: {}{}\n\
+             Burn rate: {:.1}x over 1h, {:.1}x over 6h (threshold {}x)\n\
+             Service: project-gateway",
+            status.name,
+            status.method.as_deref().map(|method| format!("{} ", method.to_uppercase())).unwrap_or_default(),
+            status.route,
+            status.burn_rate_1h,
+            status.burn_rate_6h,
+            config.slo.fast_burn_threshold,
+        ),
+        "username": "Gateway SLO Monitor",
+        "slo": status.name,
+    });
+    let notification = Notification {
+        kind: "slo_fast_burn",
+        subject: status.name.clone(),
+        severity: Severity::Warning,
+        state_change: false,
+        payload,
+    };
+    notifier.notify_in_background(config, notification);
+}
@@ -0,0 +1,135 @@
+//! Latency histograms and measures of how far two of them differ in shape.
+//!
+//! Buckets follow the HDR layout: every power of two between
+//! [`MIN_LATENCY_MS`] and [`MAX_LATENCY_MS`] is split into
+//! [`SUB_BUCKETS`] equal parts, so each bucket is at most 1/8 of its value
+//! wide whatever the magnitude.
+
+/// Lower edge of the first bucket; anything faster lands in it.
+pub const MIN_LATENCY_MS: f64 = 0.0625;
+/// Anything slower lands in the last bucket.
+pub const MAX_LATENCY_MS: f64 = 65_536.0;
+pub const SUB_BUCKETS: usize = 8;
+/// Powers of two between the two bounds.
+const OCTAVES: usize = 20;
+pub const BUCKETS: usize = OCTAVES * SUB_BUCKETS;
+
+/// Share given to an empty bucket by the PSI, which is undefined at zero.
+const PSI_FLOOR: f64 = 1e-4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            total: 0,
+        }
+    }
+
+    pub fn from_samples(samples: impl IntoIterator<Item = f64>) -> Self {
+        let mut histogram = Self::new();
+        for latency_ms in samples {
+            histogram.record(latency_ms);
+        }
+        histogram
+    }
+
+    pub fn record(&mut self, latency_ms: f64) {
+        self.counts[bucket_of(latency_ms)] += 1;
+        self.total += 1;
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (count, added) in self.counts.iter_mut().zip(&other.counts) {
+            *count += added;
+        }
+        self.total += other.total;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Samples in each bucket, fastest first.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Each bucket's share of the samples.
+    fn shares(&self) -> impl Iterator<Item = f64> + '_ {
+        let total = self.total as f64;
+        self.counts.iter().map(move |&count| count as f64 / total)
+    }
+}
+
+/// The lower and upper edge of bucket `index`, in milliseconds. The first
+/// and last buckets also hold everything beyond [`MIN_LATENCY_MS`] and
+/// [`MAX_LATENCY_MS`].
+pub fn bucket_bounds(index: usize) -> (f64, f64) {
+    let octave = MIN_LATENCY_MS * ((index / SUB_BUCKETS) as f64).exp2();
+    let sub = (index % SUB_BUCKETS) as f64;
+    let width = octave / SUB_BUCKETS as f64;
+    (octave + sub * width, octave + (sub + 1.0) * width)
+}
+
+fn bucket_of(latency_ms: f64) -> usize {
+    // NaN and anything at or below the floor go to the first bucket
+    if latency_ms.is_nan() || latency_ms <= MIN_LATENCY_MS {
+        return 0;
+    }
+    if latency_ms >= MAX_LATENCY_MS {
+        return BUCKETS - 1;
+    }
+    let scaled = latency_ms / MIN_LATENCY_MS;
+    let octave = scaled.log2().floor();
+    let sub = ((scaled / octave.exp2() - 1.0) * SUB_BUCKETS as f64) as usize;
+    octave as usize * SUB_BUCKETS + sub.min(SUB_BUCKETS - 1)
+}
+
+/// The Kolmogorov-Smirnov statistic over the buckets: the largest gap
+/// between the two cumulative distributions, from 0 (same shape) to 1 (no
+/// overlap). `None` when either histogram is empty.
+pub fn ks_statistic(reference: &LatencyHistogram, current: &LatencyHistogram) -> Option<f64> {
+    if reference.total == 0 || current.total == 0 {
+        return None;
+    }
+    let (mut reference_cdf, mut current_cdf, mut gap) = (0.0, 0.0, 0.0_f64);
+    for (expected, observed) in reference.shares().zip(current.shares()) {
+        reference_cdf += expected;
+        current_cdf += observed;
+        gap = gap.max((reference_cdf - current_cdf).abs());
+    }
+    Some(gap.min(1.0))
+}
+
+/// The population stability index of `current` against `reference`:
+/// the sum over buckets of `(observed - expected) * ln(observed / expected)`.
+/// Below 0.1 is usually read as no change and above 0.25 as a significant
+/// shift. Empty buckets count as a share of 1e-4. `None` when either
+/// histogram is empty.
+pub fn population_stability_index(reference: &LatencyHistogram, current: &LatencyHistogram) -> Option<f64> {
+    if reference.total == 0 || current.total == 0 {
+        return None;
+    }
+    let index = reference
+        .shares()
+        .zip(current.shares())
+        .filter(|(expected, observed)| *expected > 0.0 || *observed > 0.0)
+        .map(|(expected, observed)| {
+            let (expected, observed) = (expected.max(PSI_FLOOR), observed.max(PSI_FLOOR));
+            (observed - expected) * (observed / expected).ln()
+        })
+        .sum();
+    Some(index)
+}
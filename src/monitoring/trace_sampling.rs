@@ -0,0 +1,107 @@
+//! Which requests' traces are kept, decided when each request completes.
+//!
+//! A fixed head-sampling ratio mostly misses the requests worth looking at.
+//! Here errors, slow requests and legacy-routed requests shortly after a
+//! rollout change are always kept, and the rest at
+//! `tracing.sampling.ratio`. Decisions are counted by reason.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::TraceSamplingConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleReason {
+    Error,
+    Slow,
+    /// Legacy-routed within the window after a rollout change.
+    RolloutChange,
+    /// Fell in the base ratio.
+    Ratio,
+    /// Not kept.
+    Dropped,
+}
+
+impl SampleReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleReason::Error => "error",
+            SampleReason::Slow => "slow",
+            SampleReason::RolloutChange => "rollout_change",
+            SampleReason::Ratio => "ratio",
+            SampleReason::Dropped => "dropped",
+        }
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        *self != SampleReason::Dropped
+    }
+}
+
+/// What sampling looks at once a request is done.
+#[derive(Debug, Clone, Copy)]
+pub struct CompletedRequest {
+    pub status: u16,
+    pub latency: Duration,
+    pub legacy_routed: bool,
+    /// How long ago the rollout generation last changed, when this process
+    /// has seen it change.
+    pub since_rollout_change: Option<Duration>,
+}
+
+/// Whether to keep a request's trace and why. The first rule that applies
+/// wins, in the order of [`SampleReason`]. `random` is a uniform sample in
+/// `[0, 1)`.
+pub fn decide(request: &CompletedRequest, config: &TraceSamplingConfig, random: f64) -> SampleReason {
+    if request.status >= 500 {
+        SampleReason::Error
+    } else if request.latency > config.slow_threshold.get() {
+        SampleReason::Slow
+    } else if request.legacy_routed
+        && request
+            .since_rollout_change
+            .is_some_and(|since| since <= config.rollout_change_window.get())
+    {
+        SampleReason::RolloutChange
+    } else if random < config.ratio {
+        SampleReason::Ratio
+    } else {
+        SampleReason::Dropped
+    }
+}
+
+/// Tracks when the rollout generation requests run under last changed.
+#[derive(Default)]
+pub struct TraceSampler {
+    /// The latest generation seen, and when it was first seen if that was
+    /// a change from an earlier one.
+    generation: Mutex<Option<(u64, Option<Instant>)>>,
+}
+
+impl TraceSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notes `generation` as of `now` and returns how long ago it changed.
+    /// The first generation seen isn't a change.
+    pub fn since_rollout_change(&self, generation: u64, now: Instant) -> Option<Duration> {
+        let mut current = self.generation.lock().ok()?;
+        match *current {
+            Some((seen, changed_at)) if seen == generation => changed_at.map(|at| now.saturating_duration_since(at)),
+            Some((seen, _)) if generation < seen => None,
+            Some(_) => {
+                *current = Some((generation, Some(now)));
+                Some(Duration::ZERO)
+            }
+            None => {
+                *current = Some((generation, None));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Rate limiting for webhook notifications.
+//!
+//! Every alert the gateway posts goes through the `Notifier`. A destination
+//! takes at most `notifications.max_per_window` events per window, and one
+//! event type about one subject (a route, an SLO) is posted at most once per
+//! `min_interval` unless the event reports a change of state. Events held
+//! back are counted by type and summed up in a digest posted
+//! `digest_interval` after the first of them. Critical events, such as a
+//! rollback to 0%, are always posted.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{AppConfig, NotificationsConfig},
+    upstream::{Upstream, UpstreamPool},
+    util::backoff::{retry_if, RetryPolicy},
+    AppState,
+};
+
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    /// Never held back.
+    Critical,
+}
+
+/// An event bound for the webhook.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    /// Event type, as counted in digests and `gateway_notifications_total`.
+    pub kind: &'static str,
+    /// What the event is about; `min_interval` applies per kind and subject.
+    pub subject: String,
+    pub severity: Severity,
+    /// Reports a change of state rather than a repeat, so isn't held to
+    /// `min_interval`.
+    pub state_change: bool,
+    pub payload: serde_json::Value,
+}
+
+/// Whether an event goes out now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Send,
+    /// The destination has had `max_per_window` events this window.
+    RateLimited,
+    /// The same event about the same subject went out within `min_interval`.
+    TooSoon,
+}
+
+impl Admission {
+    fn outcome(self) -> &'static str {
+        match self {
+            Admission::Send => "sent",
+            Admission::RateLimited => "rate_limited",
+            Admission::TooSoon => "too_soon",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Destination {
+    window_started: Option<Instant>,
+    sent_in_window: u32,
+    /// Held back since the last digest, by kind.
+    suppressed: BTreeMap<String, u64>,
+    digest_due: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    destinations: HashMap<String, Destination>,
+    last_sent: HashMap<(&'static str, String), Instant>,
+    suppressed_total: u64,
+    digests_sent: u64,
+}
+
+/// Held-back events summed up for one destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub destination: String,
+    pub suppressed: BTreeMap<String, u64>,
+}
+
+impl Digest {
+    pub fn total(&self) -> u64 {
+        self.suppressed.values().sum()
+    }
+
+    pub fn payload(&self, config: &NotificationsConfig) -> serde_json::Value {
+        let counts: Vec<String> = self.suppressed.iter().map(|(kind, count)| format!("• {} × {}", kind, count)).collect();
+        serde_json::json!({
+            "text": format!(
+                "{} notifications held back in the last {}\n\
+                 {}\n\
+                 Service: project-gateway",
+                self.total(),
+                config.digest_interval,
+                counts.join("\n"),
+            ),
+            "username": "Gateway Notifications",
+            "suppressed": self.suppressed,
+            "suppressed_total": self.total(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DestinationStatus {
+    /// Webhook URL with the path and query redacted.
+    pub destination: String,
+    pub sent_in_window: u32,
+    pub window_resets_in_seconds: Option<u64>,
+    /// Events held back for the next digest, by type.
+    pub suppressed: BTreeMap<String, u64>,
+    pub next_digest_in_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NotificationStatus {
+    pub max_per_window: u32,
+    pub window_seconds: u64,
+    pub destinations: Vec<DestinationStatus>,
+    /// Events held back since startup.
+    pub suppressed_total: u64,
+    pub digests_sent: u64,
+}
+
+pub struct Notifier {
+    upstreams: Arc<UpstreamPool>,
+    inner: Mutex<Inner>,
+}
+
+impl Notifier {
+    pub fn new(upstreams: Arc<UpstreamPool>) -> Self {
+        Self {
+            upstreams,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Decides whether `notification` goes to `destination` now, counting
+    /// it towards the window if so and towards the next digest if not.
+    pub fn admit_at(&self, config: &NotificationsConfig, destination: &str, notification: &Notification, now: Instant) -> Admission {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Admission::Send;
+        };
+        let inner = &mut *inner;
+        let key = (notification.kind, notification.subject.clone());
+        let state = inner.destinations.entry(destination.to_string()).or_default();
+        if state.window_started.is_none_or(|started| now.duration_since(started) >= config.window.get()) {
+            state.window_started = Some(now);
+            state.sent_in_window = 0;
+        }
+
+        let admission = if notification.severity == Severity::Critical {
+            Admission::Send
+        } else if !notification.state_change
+            && inner
+                .last_sent
+                .get(&key)
+                .is_some_and(|sent| now.duration_since(*sent) < config.min_interval_for(notification.kind))
+        {
+            Admission::TooSoon
+        } else if state.sent_in_window >= config.max_per_window {
+            Admission::RateLimited
+        } else {
+            Admission::Send
+        };
+
+        if admission == Admission::Send {
+            state.sent_in_window += 1;
+            inner.last_sent.insert(key, now);
+        } else {
+            *state.suppressed.entry(notification.kind.to_string()).or_default() += 1;
+            state.digest_due.get_or_insert(now + config.digest_interval.get());
+            inner.suppressed_total += 1;
+        }
+        crate::metrics::record_notification(notification.kind, admission.outcome());
+        admission
+    }
+
+    /// Posts `notification` to the rollout webhook unless it's held back.
+    pub async fn notify(&self, config: &AppConfig, notification: Notification) {
+        if let Some(request) = self.prepare(config, &notification) {
+            request.send(notification.kind).await;
+        }
+    }
+
+    /// Like `notify`, without waiting for the webhook to answer.
+    pub fn notify_in_background(&self, config: &AppConfig, notification: Notification) {
+        if let Some(request) = self.prepare(config, &notification) {
+            tokio::spawn(request.send(notification.kind));
+        }
+    }
+
+    fn prepare(&self, config: &AppConfig, notification: &Notification) -> Option<WebhookPost> {
+        let destination = &config.canary_rollout.webhook_url;
+        if !destination.starts_with("http") {
+            info!(kind = notification.kind, "Webhook URL not configured, skipping alert");
+            return None;
+        }
+        match self.admit_at(&config.notifications, destination, notification, Instant::now()) {
+            Admission::Send => Some(WebhookPost {
+                upstreams: self.upstreams.clone(),
+                destination: destination.clone(),
+                payload: notification.payload.clone(),
+                retry: config.notifications.retry.clone(),
+            }),
+            admission => {
+                info!(
+                    kind = notification.kind,
+                    subject = %notification.subject,
+                    outcome = admission.outcome(),
+                    "Notification held back for the next digest"
+                );
+                None
+            }
+        }
+    }
+
+    /// Takes the digests due by `now`, resetting their counts.
+    pub fn take_due_digests_at(&self, now: Instant) -> Vec<Digest> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+        let mut due = Vec::new();
+        for (destination, state) in inner.destinations.iter_mut() {
+            if state.digest_due.is_some_and(|at| at <= now) {
+                state.digest_due = None;
+                due.push(Digest {
+                    destination: destination.clone(),
+                    suppressed: std::mem::take(&mut state.suppressed),
+                });
+            }
+        }
+        inner.digests_sent += due.len() as u64;
+        due
+    }
+
+    /// Posts the digests due by `now`.
+    pub async fn send_due_digests_at(&self, config: &AppConfig, now: Instant) {
+        for digest in self.take_due_digests_at(now) {
+            info!(
+                destination = %crate::config::redact_webhook_url(&digest.destination),
+                suppressed = digest.total(),
+                "Posting notification digest"
+            );
+            crate::metrics::record_notification("digest", "sent");
+            let request = WebhookPost {
+                upstreams: self.upstreams.clone(),
+                payload: digest.payload(&config.notifications),
+                destination: digest.destination,
+                retry: config.notifications.retry.clone(),
+            };
+            request.send("digest").await;
+        }
+    }
+
+    pub fn status_at(&self, config: &NotificationsConfig, now: Instant) -> NotificationStatus {
+        let seconds_until = |at: Instant| at.saturating_duration_since(now).as_secs();
+        let inner = self.inner.lock().ok();
+        let mut destinations: Vec<DestinationStatus> = inner
+            .iter()
+            .flat_map(|inner| inner.destinations.iter())
+            .map(|(destination, state)| {
+                let window_open = state.window_started.filter(|started| now.duration_since(*started) < config.window.get());
+                DestinationStatus {
+                    destination: crate::config::redact_webhook_url(destination),
+                    sent_in_window: if window_open.is_some() { state.sent_in_window } else { 0 },
+                    window_resets_in_seconds: window_open.map(|started| seconds_until(started + config.window.get())),
+                    suppressed: state.suppressed.clone(),
+                    next_digest_in_seconds: state.digest_due.map(seconds_until),
+                }
+            })
+            .collect();
+        destinations.sort_by(|a, b| a.destination.cmp(&b.destination));
+        NotificationStatus {
+            max_per_window: config.max_per_window,
+            window_seconds: config.window.get().as_secs(),
+            destinations,
+            suppressed_total: inner.as_ref().map(|inner| inner.suppressed_total).unwrap_or_default(),
+            digests_sent: inner.as_ref().map(|inner| inner.digests_sent).unwrap_or_default(),
+        }
+    }
+
+    /// Posts digests as they fall due.
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        loop {
+            let config = state.config_watcher.get_config().await;
+            self.send_due_digests_at(&config, Instant::now()).await;
+            tokio::time::sleep(DIGEST_CHECK_INTERVAL).await;
+        }
+    }
+}
+
+/// A post to the webhook, retried per `notifications.retry`.
+struct WebhookPost {
+    upstreams: Arc<UpstreamPool>,
+    destination: String,
+    payload: serde_json::Value,
+    retry: RetryPolicy,
+}
+
+impl WebhookPost {
+    async fn send(self, kind: &'static str) {
+        let sent = retry_if(
+            &self.retry,
+            |_| async {
+                let request = self.upstreams.request(Upstream::Webhooks, reqwest::Method::POST, &self.destination);
+                match request.json(&self.payload).send().await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(WebhookError::Status(response.status())),
+                    Err(e) => Err(WebhookError::Send(e)),
+                }
+            },
+            WebhookError::is_transient,
+        )
+        .await;
+        crate::metrics::record_retries("webhook", sent.retries());
+        match sent.result {
+            Ok(()) => info!(kind, attempts = sent.attempts, "Notification sent"),
+            Err(WebhookError::Status(status)) => {
+                warn!(kind, %status, attempts = sent.attempts, "Webhook rejected the notification")
+            }
+            Err(WebhookError::Send(e)) => warn!(kind, attempts = sent.attempts, "Error sending notification: {}", e),
+        }
+    }
+}
+
+enum WebhookError {
+    Status(reqwest::StatusCode),
+    Send(reqwest::Error),
+}
+
+impl WebhookError {
+    fn is_transient(&self) -> bool {
+        match self {
+            WebhookError::Status(status) => status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            WebhookError::Send(_) => true,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+pub mod redaction;
+
+use anyhow::{anyhow, Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::{Arc, RwLock};
+use tracing::{error, info};
+
+use crate::config::{watcher::ConfigWatcher, PrivacyConfig};
+
+/// Hex characters kept from the HMAC; enough to tell users apart in logs
+/// without being a usable identifier on its own.
+const PSEUDONYM_LEN: usize = 12;
+
+/// The pseudonymization salt, read from `salt_file` when one is configured.
+pub fn salt(config: &PrivacyConfig) -> Result<Option<String>> {
+    match &config.salt_file {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|contents| Some(contents.trim_end_matches(['\r', '\n']).to_string()))
+            .with_context(|| format!("reading pseudonymization salt file {}", path)),
+        None => Ok(config.salt.clone()),
+    }
+}
+
+/// Keyed, truncated HMAC-SHA256 of `id`.
+pub fn pseudonym(salt: &[u8], id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt).expect("HMAC accepts keys of any length");
+    mac.update(id.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()[..PSEUDONYM_LEN]
+        .to_string()
+}
+
+/// Replaces user identifiers with stable pseudonyms before they reach logs,
+/// audit entries, or metric labels. The same salt always maps an ID to the
+/// same pseudonym, so one user's requests can still be correlated.
+pub struct Pseudonymizer {
+    /// `None` passes identifiers through unchanged (development only).
+    salt: RwLock<Option<Vec<u8>>>,
+}
+
+impl Pseudonymizer {
+    pub fn new(config: &PrivacyConfig) -> Result<Self> {
+        Ok(Self {
+            salt: RwLock::new(Self::load(config)?),
+        })
+    }
+
+    /// Pseudonymizes with a salt that lives only as long as this process.
+    /// Used when the configured salt can't be read, so identifiers are never
+    /// logged raw, at the cost of correlating across restarts.
+    pub fn ephemeral() -> Self {
+        Self {
+            salt: RwLock::new(Some(rand::random::<[u8; 32]>().to_vec())),
+        }
+    }
+
+    fn load(config: &PrivacyConfig) -> Result<Option<Vec<u8>>> {
+        if !config.pseudonymize_user_ids {
+            return Ok(None);
+        }
+        match salt(config)? {
+            Some(salt) if !salt.is_empty() => Ok(Some(salt.into_bytes())),
+            _ => Err(anyhow!("pseudonymization is enabled but no salt is configured")),
+        }
+    }
+
+    pub fn pseudonymize(&self, id: &str) -> String {
+        match self.salt.read().ok().as_deref() {
+            Some(Some(salt)) => pseudonym(salt, id),
+            _ => id.to_string(),
+        }
+    }
+
+    /// Picks up salt and switch changes on config reload. A salt that fails
+    /// to load keeps the previous one in place.
+    pub fn follow_reloads(self: &Arc<Self>, config_watcher: &ConfigWatcher) {
+        let mut reloads = config_watcher.subscribe_to_reloads();
+        let pseudonymizer = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match reloads.recv().await {
+                    Ok(config) => match Self::load(&config.privacy) {
+                        Ok(salt) => {
+                            if let Ok(mut current) = pseudonymizer.salt.write() {
+                                if *current != salt {
+                                    info!(enabled = salt.is_some(), "Pseudonymization settings reloaded");
+                                    *current = salt;
+                                }
+                            }
+                        }
+                        Err(e) => error!("Keeping previous pseudonymization salt: {:#}", e),
+                    },
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,186 @@
+//! Scrubs secrets from headers and bodies before they are logged.
+//!
+//! Headers listed in `middleware.logging.redact_headers` and JSON fields
+//! matching `redact_json_fields` have their values replaced by
+//! [`REDACTED`]. A body that parses as JSON is redacted by path. One that
+//! starts like JSON but doesn't parse, usually because it was cut short for
+//! the log, is redacted by the last key of each path wherever that key is
+//! followed by a string or scalar value, since the path can no longer be
+//! followed. Any other body is left as it is.
+
+use axum::http::HeaderMap;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+use crate::config::LoggingConfig;
+
+/// What a redacted value is logged as.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The redaction rules from one logging config.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// Lowercased header names.
+    headers: Vec<String>,
+    /// Each pattern split on `.`.
+    fields: Vec<Vec<String>>,
+}
+
+impl Redactor {
+    pub fn new(config: &LoggingConfig) -> Self {
+        Self {
+            headers: config.redact_headers.iter().map(|name| name.to_ascii_lowercase()).collect(),
+            fields: config
+                .redact_json_fields
+                .iter()
+                .map(|field| field.split('.').map(String::from).collect())
+                .collect(),
+        }
+    }
+
+    /// Whether the value of header `name` is redacted.
+    pub fn redacts_header(&self, name: &str) -> bool {
+        self.headers.iter().any(|redacted| redacted.eq_ignore_ascii_case(name))
+    }
+
+    /// `headers` as a JSON object of name to value, for the log. Repeated
+    /// headers are joined with `, `.
+    pub fn headers(&self, headers: &HeaderMap) -> String {
+        let mut logged = Map::new();
+        for name in headers.keys() {
+            let value = if self.redacts_header(name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                headers
+                    .get_all(name)
+                    .iter()
+                    .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            logged.insert(name.as_str().to_string(), Value::String(value));
+        }
+        Value::Object(logged).to_string()
+    }
+
+    /// `body` with matching JSON fields redacted. Bodies with nothing to
+    /// redact are returned unchanged, formatting included.
+    pub fn body<'a>(&self, body: &'a str) -> Cow<'a, str> {
+        if self.fields.is_empty() {
+            return Cow::Borrowed(body);
+        }
+        match serde_json::from_str::<Value>(body) {
+            Ok(mut value) => {
+                let mut redacted = false;
+                for path in &self.fields {
+                    redacted |= redact_path(&mut value, path);
+                }
+                if redacted {
+                    Cow::Owned(value.to_string())
+                } else {
+                    Cow::Borrowed(body)
+                }
+            }
+            Err(_) if body.trim_start().starts_with(['{', '[']) => {
+                let keys: Vec<&str> = self
+                    .fields
+                    .iter()
+                    .filter_map(|path| path.last().map(String::as_str))
+                    .filter(|key| *key != "*")
+                    .collect();
+                redact_keys(body, &keys).map_or(Cow::Borrowed(body), Cow::Owned)
+            }
+            Err(_) => Cow::Borrowed(body),
+        }
+    }
+}
+
+/// Redacts what `path` reaches in `value`, walking through arrays as if
+/// each element were at the array's place. Returns whether anything was.
+fn redact_path(value: &mut Value, path: &[String]) -> bool {
+    match value {
+        Value::Array(items) => items.iter_mut().fold(false, |redacted, item| redact_path(item, path) | redacted),
+        Value::Object(object) => {
+            let Some((first, rest)) = path.split_first() else { return false };
+            let mut redacted = false;
+            for (key, child) in object.iter_mut() {
+                if first != "*" && key != first {
+                    continue;
+                }
+                if rest.is_empty() {
+                    *child = Value::String(REDACTED.to_string());
+                    redacted = true;
+                } else {
+                    redacted |= redact_path(child, rest);
+                }
+            }
+            redacted
+        }
+        _ => false,
+    }
+}
+
+/// Redacts the string or scalar value after each `"key":` for any of
+/// `keys`, in JSON that doesn't parse. A value that runs to the end of
+/// the text is redacted to the end. `None` when nothing matched.
+fn redact_keys(text: &str, keys: &[&str]) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut at = 0;
+    while at < bytes.len() {
+        if bytes[at] != b'"' {
+            at += 1;
+            continue;
+        }
+        let (end, closed) = string_end(bytes, at);
+        let value_start = skip_whitespace(bytes, end);
+        let is_key = closed && bytes.get(value_start) == Some(&b':');
+        if is_key && keys.contains(&&text[at + 1..end - 1]) {
+            let value_start = skip_whitespace(bytes, value_start + 1);
+            let value_end = match bytes.get(value_start) {
+                Some(b'"') => string_end(bytes, value_start).0,
+                Some(b'{' | b'[') | None => value_start,
+                Some(_) => bytes[value_start..]
+                    .iter()
+                    .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+                    .map_or(bytes.len(), |length| value_start + length),
+            };
+            if value_end > value_start {
+                redacted.push_str(&text[copied..value_start]);
+                redacted.push('"');
+                redacted.push_str(REDACTED);
+                redacted.push('"');
+                copied = value_end;
+            }
+            at = value_end.max(value_start);
+            continue;
+        }
+        at = end;
+    }
+    (copied > 0).then(|| {
+        redacted.push_str(&text[copied..]);
+        redacted
+    })
+}
+
+/// The index just past the JSON string starting at `start`, and whether it
+/// was closed before the text ran out.
+fn string_end(bytes: &[u8], start: usize) -> (usize, bool) {
+    let mut at = start + 1;
+    while at < bytes.len() {
+        match bytes[at] {
+            b'\\' => at += 2,
+            b'"' => return (at + 1, true),
+            _ => at += 1,
+        }
+    }
+    (bytes.len(), false)
+}
+
+fn skip_whitespace(bytes: &[u8], from: usize) -> usize {
+    bytes[from.min(bytes.len())..]
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .map_or(bytes.len(), |skipped| from + skipped)
+}
@@ -0,0 +1,145 @@
+//! A minimal flamegraph renderer: one row per stack depth, the root at the
+//! bottom, each frame as wide as the time spent in it and its children.
+
+use std::fmt::Write;
+
+use super::ProfileSummary;
+
+const WIDTH: f64 = 1200.0;
+const ROW_HEIGHT: f64 = 18.0;
+const TITLE_HEIGHT: f64 = 30.0;
+/// Frames narrower than this are left out, as other renderers do.
+const MIN_FRAME_WIDTH: f64 = 0.1;
+
+#[derive(Default)]
+struct Frame {
+    name: String,
+    micros: u64,
+    children: Vec<Frame>,
+}
+
+impl Frame {
+    fn insert(&mut self, stack: &str, micros: u64) {
+        self.micros += micros;
+        let Some((name, rest)) = split_first(stack) else {
+            return;
+        };
+        let index = match self.children.iter().position(|child| child.name == name) {
+            Some(index) => index,
+            None => {
+                self.children.push(Frame {
+                    name: name.to_string(),
+                    ..Frame::default()
+                });
+                self.children.len() - 1
+            }
+        };
+        self.children[index].insert(rest, micros);
+    }
+
+    fn depth(&self) -> usize {
+        1 + self.children.iter().map(Frame::depth).max().unwrap_or(0)
+    }
+}
+
+fn split_first(stack: &str) -> Option<(&str, &str)> {
+    if stack.is_empty() {
+        return None;
+    }
+    Some(stack.split_once(';').unwrap_or((stack, "")))
+}
+
+pub(super) fn render(summary: &ProfileSummary, stacks: &[(String, u64)]) -> String {
+    let mut root = Frame {
+        name: "all".to_string(),
+        ..Frame::default()
+    };
+    for (stack, micros) in stacks {
+        root.insert(stack, *micros);
+    }
+    let height = TITLE_HEIGHT + root.depth() as f64 * ROW_HEIGHT;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r##"<?xml version="1.0" standalone="no"?>
+<svg version="1.1" width="{width}" height="{height}" viewBox="0 0 {width} {height}" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{width}" height="{height}" fill="#eeeeee"/>
+<text x="{center}" y="20" font-family="Verdana" font-size="14" text-anchor="middle">Profile {id}: {method} {route} ({status}, {trigger})</text>
+"##,
+        width = WIDTH,
+        height = height,
+        center = WIDTH / 2.0,
+        id = summary.id,
+        method = escape(&summary.method),
+        route = escape(&summary.route),
+        status = summary.status,
+        trigger = summary.trigger.as_str(),
+    );
+    let scale = if root.micros == 0 { 0.0 } else { WIDTH / root.micros as f64 };
+    draw(&mut svg, &root, 0.0, 0, height, scale, root.micros);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn draw(svg: &mut String, frame: &Frame, x: f64, depth: usize, height: f64, scale: f64, total: u64) {
+    // An empty profile shows just its root
+    let width = match total {
+        0 if depth == 0 => WIDTH,
+        _ => frame.micros as f64 * scale,
+    };
+    if width < MIN_FRAME_WIDTH {
+        return;
+    }
+    let y = height - (depth + 1) as f64 * ROW_HEIGHT;
+    let share = if total == 0 { 100.0 } else { frame.micros as f64 * 100.0 / total as f64 };
+    let _ = writeln!(
+        svg,
+        r#"<g><title>{name} ({micros} us, {share:.2}%)</title><rect x="{x:.2}" y="{y}" width="{width:.2}" height="{h}" fill="{fill}" rx="2"/><text x="{tx:.2}" y="{ty}" font-family="Verdana" font-size="12">{label}</text></g>"#,
+        name = escape(&frame.name),
+        micros = frame.micros,
+        share = share,
+        x = x,
+        y = y,
+        width = width,
+        h = ROW_HEIGHT - 1.0,
+        fill = color(&frame.name),
+        tx = x + 3.0,
+        ty = y + ROW_HEIGHT - 5.0,
+        label = escape(&truncate(&frame.name, width)),
+    );
+
+    let mut offset = x;
+    for child in &frame.children {
+        draw(svg, child, offset, depth + 1, height, scale, total);
+        offset += child.micros as f64 * scale;
+    }
+}
+
+/// Warm colours derived from the frame name, so a frame keeps its colour
+/// from one profile to the next.
+fn color(name: &str) -> String {
+    let hash = name.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    format!("rgb({},{},{})", 205 + hash % 50, 80 + (hash >> 8) % 130, (hash >> 16) % 55)
+}
+
+/// Shortens a label to what fits in `width` pixels at roughly 7px a glyph.
+fn truncate(name: &str, width: f64) -> String {
+    let fits = ((width - 6.0) / 7.0).max(0.0) as usize;
+    if name.chars().count() <= fits {
+        return name.to_string();
+    }
+    if fits < 3 {
+        return String::new();
+    }
+    let mut short: String = name.chars().take(fits - 2).collect();
+    short.push_str("..");
+    short
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
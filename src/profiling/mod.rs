@@ -0,0 +1,227 @@
+//! Per-request profiles for `GET /admin/profiles`.
+//!
+//! A request is profiled when it is the Nth since the last sampled one or
+//! when a trusted peer asks with `X-Profile: 1`. Only one request is profiled
+//! at a time; a request that can't get the profiler within the start budget
+//! goes unprofiled rather than wait. A profile is a wall-clock breakdown of
+//! where the request spent its time, kept as folded stacks and rendered on
+//! demand as a flamegraph SVG or a pprof protobuf.
+
+mod flamegraph;
+mod pprof;
+
+use anyhow::{anyhow, Context, Result};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use utoipa::ToSchema;
+
+use crate::{config::ProfilingConfig, middleware::timing::TimingBreakdown};
+
+/// Header a trusted client sets to `1` to have its request profiled.
+pub const PROFILE_HEADER: &str = "x-profile";
+
+/// Why a request was profiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Picked by the 1-in-N sampler.
+    Sampled,
+    /// Asked for with `X-Profile: 1`.
+    Requested,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::Sampled => "sampled",
+            Trigger::Requested => "requested",
+        }
+    }
+}
+
+/// A profile as listed by `GET /admin/profiles`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProfileSummary {
+    pub id: u64,
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+    pub trigger: Trigger,
+    pub captured_at: String,
+    /// Wall-clock time from receiving the request to its response headers.
+    pub duration_us: u64,
+}
+
+/// One profiled request.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub summary: ProfileSummary,
+    /// Semicolon-separated frames, root first, with the microseconds spent.
+    pub stacks: Vec<(String, u64)>,
+}
+
+impl Profile {
+    /// Builds the profile of a finished request from its timing breakdown.
+    pub fn new(method: &str, route: &str, status: u16, trigger: Trigger, breakdown: &TimingBreakdown) -> Self {
+        let root = format!("{} {}", method.to_uppercase(), route);
+        let micros = |duration: Duration| duration.as_micros() as u64;
+        let mut stacks = vec![
+            (format!("{};gateway", root), micros(breakdown.gateway.saturating_sub(breakdown.auth))),
+            (format!("{};gateway;auth", root), micros(breakdown.auth)),
+            (format!("{};queue", root), micros(breakdown.queue)),
+        ];
+        if let Some(upstream) = breakdown.upstream {
+            stacks.push((format!("{};upstream", root), micros(upstream)));
+        }
+        let total = breakdown.gateway + breakdown.queue + breakdown.upstream.unwrap_or_default();
+
+        Self {
+            summary: ProfileSummary {
+                id: 0,
+                method: method.to_uppercase(),
+                route: route.to_string(),
+                status,
+                trigger,
+                captured_at: chrono::Utc::now().to_rfc3339(),
+                duration_us: micros(total),
+            },
+            stacks,
+        }
+    }
+
+    /// The stacks in the folded format `flamegraph.pl` and `inferno` read.
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+            .collect()
+    }
+
+    pub fn svg(&self) -> String {
+        flamegraph::render(&self.summary, &self.stacks)
+    }
+
+    /// A gzipped pprof `Profile` message with wall-clock microsecond samples.
+    pub fn pprof(&self) -> Result<Vec<u8>> {
+        pprof::encode(&self.summary, &self.stacks)
+    }
+}
+
+/// Chooses, captures, and keeps profiles.
+pub struct Profiler {
+    requests: AtomicU64,
+    next_id: AtomicU64,
+    /// One permit: a single request is profiled at a time.
+    slot: Semaphore,
+    profiles: RwLock<VecDeque<Arc<Profile>>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            next_id: AtomicU64::new(1),
+            slot: Semaphore::new(1),
+            profiles: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Whether to profile a request, and why. Counts the request towards
+    /// the sampler whenever profiling is enabled.
+    pub fn trigger(&self, config: &ProfilingConfig, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<Trigger> {
+        if !config.enabled {
+            return None;
+        }
+        let seen = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        let requested = headers.get(PROFILE_HEADER).is_some_and(|value| value == "1")
+            && peer.is_some_and(|peer| is_trusted(&config.trusted_networks, peer));
+        if requested {
+            Some(Trigger::Requested)
+        } else if config.sample_one_in > 0 && seen.is_multiple_of(config.sample_one_in) {
+            Some(Trigger::Sampled)
+        } else {
+            None
+        }
+    }
+
+    /// Takes the profiler, or gives up once `budget` has passed.
+    pub async fn start(&self, budget: Duration) -> Option<SemaphorePermit<'_>> {
+        if let Ok(permit) = self.slot.try_acquire() {
+            return Some(permit);
+        }
+        tokio::time::timeout(budget, self.slot.acquire())
+            .await
+            .ok()
+            .and_then(|permit| permit.ok())
+    }
+
+    /// Keeps `profile`, dropping the oldest past `max_profiles`, and returns
+    /// its id.
+    pub fn store(&self, mut profile: Profile, max_profiles: usize) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        profile.summary.id = id;
+        if let Ok(mut profiles) = self.profiles.write() {
+            profiles.push_back(Arc::new(profile));
+            while profiles.len() > max_profiles {
+                profiles.pop_front();
+            }
+        }
+        id
+    }
+
+    /// Kept profiles, newest first.
+    pub fn list(&self) -> Vec<ProfileSummary> {
+        self.profiles
+            .read()
+            .map(|profiles| profiles.iter().rev().map(|profile| profile.summary.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Profile>> {
+        self.profiles
+            .read()
+            .ok()
+            .and_then(|profiles| profiles.iter().find(|profile| profile.summary.id == id).cloned())
+    }
+}
+
+/// Parses an IP or CIDR entry of `trusted_networks`.
+pub fn parse_network(entry: &str) -> Result<(IpAddr, u8)> {
+    let (addr, prefix) = entry.split_once('/').unwrap_or((entry, ""));
+    let addr: IpAddr = addr.trim().parse().with_context(|| format!("invalid network {:?}", entry))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    if prefix.is_empty() {
+        return Ok((addr, max));
+    }
+    let prefix = prefix
+        .trim()
+        .parse()
+        .ok()
+        .filter(|prefix| *prefix <= max)
+        .ok_or_else(|| anyhow!("invalid prefix length in {:?}", entry))?;
+    Ok((addr, prefix))
+}
+
+/// Whether `peer` is in any of `networks`, entries that don't parse aside.
+pub(crate) fn is_trusted(networks: &[String], peer: IpAddr) -> bool {
+    networks
+        .iter()
+        .filter_map(|entry| parse_network(entry).ok())
+        .any(|(network, prefix)| crate::upstream::proxy::in_network(peer, network, prefix))
+}
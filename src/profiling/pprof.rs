@@ -0,0 +1,170 @@
+//! Encodes profiles as pprof's `profile.proto`, gzipped, as `go tool pprof`
+//! and most profile viewers expect. Only the fields a wall-clock profile
+//! needs are written.
+
+use anyhow::Result;
+use flate2::{write::GzEncoder, Compression};
+use std::{collections::HashMap, io::Write};
+
+use super::ProfileSummary;
+
+// Field numbers from profile.proto
+const PROFILE_SAMPLE_TYPE: u32 = 1;
+const PROFILE_SAMPLE: u32 = 2;
+const PROFILE_LOCATION: u32 = 4;
+const PROFILE_FUNCTION: u32 = 5;
+const PROFILE_STRING_TABLE: u32 = 6;
+const PROFILE_TIME_NANOS: u32 = 9;
+const PROFILE_DURATION_NANOS: u32 = 10;
+const PROFILE_PERIOD_TYPE: u32 = 11;
+const PROFILE_PERIOD: u32 = 12;
+const VALUE_TYPE_TYPE: u32 = 1;
+const VALUE_TYPE_UNIT: u32 = 2;
+const SAMPLE_LOCATION_ID: u32 = 1;
+const SAMPLE_VALUE: u32 = 2;
+const LOCATION_ID: u32 = 1;
+const LOCATION_LINE: u32 = 4;
+const LINE_FUNCTION_ID: u32 = 1;
+const FUNCTION_ID: u32 = 1;
+const FUNCTION_NAME: u32 = 2;
+const FUNCTION_SYSTEM_NAME: u32 = 3;
+
+const VARINT: u32 = 0;
+const LENGTH_DELIMITED: u32 = 2;
+
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(buf: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        Self::varint(&mut self.0, ((field << 3) | wire_type) as u64);
+    }
+
+    fn uint(&mut self, field: u32, value: u64) -> &mut Self {
+        if value != 0 {
+            self.key(field, VARINT);
+            Self::varint(&mut self.0, value);
+        }
+        self
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) -> &mut Self {
+        self.key(field, LENGTH_DELIMITED);
+        Self::varint(&mut self.0, bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn message(&mut self, field: u32, message: Message) -> &mut Self {
+        self.bytes(field, &message.0)
+    }
+
+    fn packed(&mut self, field: u32, values: &[u64]) -> &mut Self {
+        let mut packed = Vec::new();
+        for value in values {
+            Self::varint(&mut packed, *value);
+        }
+        self.bytes(field, &packed)
+    }
+}
+
+/// The string table; index 0 is always the empty string.
+struct Strings {
+    table: Vec<String>,
+    index: HashMap<String, u64>,
+}
+
+impl Strings {
+    fn new() -> Self {
+        Self {
+            table: vec![String::new()],
+            index: HashMap::from([(String::new(), 0)]),
+        }
+    }
+
+    fn get(&mut self, value: &str) -> u64 {
+        if let Some(index) = self.index.get(value) {
+            return *index;
+        }
+        let index = self.table.len() as u64;
+        self.table.push(value.to_string());
+        self.index.insert(value.to_string(), index);
+        index
+    }
+}
+
+fn value_type(strings: &mut Strings, kind: &str, unit: &str) -> Message {
+    let mut message = Message::default();
+    message.uint(VALUE_TYPE_TYPE, strings.get(kind)).uint(VALUE_TYPE_UNIT, strings.get(unit));
+    message
+}
+
+pub(super) fn encode(summary: &ProfileSummary, stacks: &[(String, u64)]) -> Result<Vec<u8>> {
+    let mut strings = Strings::new();
+    let mut profile = Message::default();
+    let sample_type = value_type(&mut strings, "wall", "microseconds");
+    profile.message(PROFILE_SAMPLE_TYPE, sample_type);
+
+    // Every distinct frame gets one function and one location, sharing an id
+    let mut frames: Vec<String> = Vec::new();
+    for (stack, micros) in stacks {
+        let mut location_ids: Vec<u64> = stack
+            .split(';')
+            .map(|frame| match frames.iter().position(|known| known == frame) {
+                Some(index) => index as u64 + 1,
+                None => {
+                    frames.push(frame.to_string());
+                    frames.len() as u64
+                }
+            })
+            .collect();
+        // pprof lists the leaf first
+        location_ids.reverse();
+        let mut sample = Message::default();
+        sample.packed(SAMPLE_LOCATION_ID, &location_ids).packed(SAMPLE_VALUE, &[*micros]);
+        profile.message(PROFILE_SAMPLE, sample);
+    }
+
+    for (index, frame) in frames.iter().enumerate() {
+        let id = index as u64 + 1;
+        let mut line = Message::default();
+        line.uint(LINE_FUNCTION_ID, id);
+        let mut location = Message::default();
+        location.uint(LOCATION_ID, id).message(LOCATION_LINE, line);
+        profile.message(PROFILE_LOCATION, location);
+
+        let name = strings.get(frame);
+        let mut function = Message::default();
+        function
+            .uint(FUNCTION_ID, id)
+            .uint(FUNCTION_NAME, name)
+            .uint(FUNCTION_SYSTEM_NAME, name);
+        profile.message(PROFILE_FUNCTION, function);
+    }
+
+    let captured_at = chrono::DateTime::parse_from_rfc3339(&summary.captured_at)
+        .ok()
+        .and_then(|at| at.timestamp_nanos_opt())
+        .unwrap_or_default();
+    profile
+        .uint(PROFILE_TIME_NANOS, captured_at.max(0) as u64)
+        .uint(PROFILE_DURATION_NANOS, summary.duration_us.saturating_mul(1000));
+    let period_type = value_type(&mut strings, "wall", "microseconds");
+    profile.message(PROFILE_PERIOD_TYPE, period_type).uint(PROFILE_PERIOD, 1);
+
+    for value in &strings.table {
+        profile.bytes(PROFILE_STRING_TABLE, value.as_bytes());
+    }
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&profile.0)?;
+    Ok(gzip.finish()?)
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Mutex};
+
+use super::{QuotaCounter, QuotaStore, Taken};
+
+/// Counters in this process, lost on restart and not shared with other
+/// replicas.
+#[derive(Debug, Default)]
+pub struct MemoryQuotaStore {
+    counters: Mutex<HashMap<String, (u64, DateTime<Utc>)>>,
+}
+
+impl MemoryQuotaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl QuotaStore for MemoryQuotaStore {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn take(&self, counters: &[QuotaCounter], now: DateTime<Utc>) -> Result<Taken> {
+        let mut stored = self.counters.lock().expect("quota store lock");
+        stored.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let current = |name: &str| stored.get(name).map_or(0, |(used, _)| *used);
+        let mut used: Vec<u64> = counters.iter().map(|counter| current(&counter.name)).collect();
+        let counted = counters.iter().zip(&used).all(|(counter, used)| *used < counter.limit);
+        if counted {
+            for (counter, used) in counters.iter().zip(used.iter_mut()) {
+                *used += 1;
+                stored.insert(counter.name.clone(), (*used, counter.expires_at));
+            }
+        }
+        Ok(Taken { counted, used })
+    }
+
+    async fn used(&self, names: &[String]) -> Result<Vec<u64>> {
+        let stored = self.counters.lock().expect("quota store lock");
+        Ok(names
+            .iter()
+            .map(|name| stored.get(name).map_or(0, |(used, _)| *used))
+            .collect())
+    }
+}
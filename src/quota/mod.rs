@@ -0,0 +1,255 @@
+//! Daily and monthly request quotas per API key.
+//!
+//! Unlike the per-minute rate limit, a quota is a hard cap on the requests
+//! a key makes in a calendar period. Counters are kept per key and period
+//! in a [`QuotaStore`]: in memory by default, or in the coordination Redis
+//! when `canary_rollout.coordination` is configured, where every replica
+//! counts against the same totals and the counts survive a restart.
+
+mod memory;
+mod redis;
+
+pub use memory::MemoryQuotaStore;
+pub use redis::RedisQuotaStore;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fmt, sync::Arc};
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::config::{ApiKey, AppConfig, AuthConfig, CoordinationKind};
+
+/// How long a counter is kept past the end of its period, so a replica
+/// whose clock is a little behind still finds it.
+const COUNTER_GRACE: Duration = Duration::hours(1);
+
+/// The span a quota counts requests over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+impl QuotaPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaPeriod::Daily => "daily",
+            QuotaPeriod::Monthly => "monthly",
+        }
+    }
+
+    /// Start and end of the period `now` falls in. Days start at
+    /// `reset_hour` UTC, months at that hour on the 1st.
+    pub fn bounds(&self, now: DateTime<Utc>, reset_hour: u32) -> (DateTime<Utc>, DateTime<Utc>) {
+        let at = |date: NaiveDate| {
+            date.and_hms_opt(reset_hour.min(23), 0, 0)
+                .expect("an hour from 0 to 23 is a valid time")
+                .and_utc()
+        };
+        match self {
+            QuotaPeriod::Daily => {
+                let today = at(now.date_naive());
+                let start = if now >= today { today } else { today - Duration::days(1) };
+                (start, start + Duration::days(1))
+            }
+            QuotaPeriod::Monthly => {
+                let first = now.date_naive().with_day(1).expect("every month has a 1st");
+                let first = if now >= at(first) { first } else { first - Months::new(1) };
+                (at(first), at(first + Months::new(1)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for QuotaPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One period's count for a key, as a store sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaCounter {
+    /// Names the key and the period's start, so a new period starts at zero.
+    pub name: String,
+    pub limit: u64,
+    /// When the store may forget the counter.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The outcome of counting a request against several counters at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Taken {
+    /// False when a counter was already at its limit; nothing was counted.
+    pub counted: bool,
+    /// Each counter's count afterwards, in order.
+    pub used: Vec<u64>,
+}
+
+/// Backend holding the quota counters.
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Backend name for the quota endpoint.
+    fn kind(&self) -> &'static str;
+
+    /// Counts a request made at `now` against every counter, unless one of
+    /// them is already at its limit, atomically with other replicas'
+    /// requests. Stores that expire counters by their own clock ignore
+    /// `now`.
+    async fn take(&self, counters: &[QuotaCounter], now: DateTime<Utc>) -> Result<Taken>;
+
+    /// Current counts, zero for counters never taken from.
+    async fn used(&self, names: &[String]) -> Result<Vec<u64>>;
+}
+
+/// Where a key stands in one period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QuotaUsage {
+    pub period: QuotaPeriod,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    /// RFC 3339; when the count starts over.
+    pub resets_at: String,
+}
+
+/// Where a key stands in each period it has a quota for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QuotaReport {
+    pub key_id: String,
+    /// `memory` or `redis`.
+    pub backend: String,
+    pub quotas: Vec<QuotaUsage>,
+}
+
+/// A request turned away because one of its key's quotas is used up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaExhausted {
+    pub usage: QuotaUsage,
+    /// Until the exhausted quota starts over.
+    pub retry_after: std::time::Duration,
+}
+
+/// The listed key `presented` matches, if any. Keys are compared by
+/// digest, so the time taken doesn't tell how much of a key was right.
+pub fn api_key<'a>(auth: &'a AuthConfig, presented: &str) -> Option<&'a ApiKey> {
+    let presented = Sha256::digest(presented.as_bytes());
+    auth.api_keys
+        .iter()
+        .find(|key| Sha256::digest(key.key.as_bytes()) == presented)
+}
+
+/// One of a key's quotas in the period under way.
+struct KeyQuota {
+    period: QuotaPeriod,
+    resets_at: DateTime<Utc>,
+    counter: QuotaCounter,
+}
+
+impl KeyQuota {
+    fn usage(&self, used: u64) -> QuotaUsage {
+        let limit = self.counter.limit;
+        QuotaUsage {
+            period: self.period,
+            limit,
+            used: used.min(limit),
+            remaining: limit.saturating_sub(used),
+            resets_at: self.resets_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Counts requests against the quotas in `middleware.auth.api_keys`. The
+/// store is chosen at startup.
+pub struct QuotaTracker {
+    store: Arc<dyn QuotaStore>,
+}
+
+impl QuotaTracker {
+    pub fn new(store: Arc<dyn QuotaStore>) -> Self {
+        Self { store }
+    }
+
+    /// Kept in the coordination Redis when one is configured, in memory
+    /// otherwise or when its URL is invalid.
+    pub fn from_config(config: &AppConfig) -> Self {
+        let Some(coordination) = &config.canary_rollout.coordination else {
+            return Self::new(Arc::new(MemoryQuotaStore::new()));
+        };
+        let store = match coordination.kind {
+            CoordinationKind::Redis => RedisQuotaStore::new(coordination),
+        };
+        match store {
+            Ok(store) => Self::new(Arc::new(store)),
+            Err(e) => {
+                error!("Counting quotas in memory: {:#}", e);
+                Self::new(Arc::new(MemoryQuotaStore::new()))
+            }
+        }
+    }
+
+    pub fn backend(&self) -> &'static str {
+        self.store.kind()
+    }
+
+    fn quotas(key: &ApiKey, now: DateTime<Utc>, reset_hour: u32) -> Vec<KeyQuota> {
+        [(QuotaPeriod::Daily, key.quota.daily), (QuotaPeriod::Monthly, key.quota.monthly)]
+            .into_iter()
+            .filter_map(|(period, limit)| {
+                let limit = limit?;
+                let (start, resets_at) = period.bounds(now, reset_hour);
+                Some(KeyQuota {
+                    period,
+                    resets_at,
+                    counter: QuotaCounter {
+                        name: format!("quota:{}:{}:{}", key.id, period, start.format("%Y%m%dT%H")),
+                        limit,
+                        expires_at: resets_at + COUNTER_GRACE,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Counts a request by `key` at `now`, or says which quota it has used
+    /// up. A key without quotas is always let through.
+    pub async fn take(&self, key: &ApiKey, reset_hour: u32, now: DateTime<Utc>) -> Result<Result<(), QuotaExhausted>> {
+        let quotas = Self::quotas(key, now, reset_hour);
+        if quotas.is_empty() {
+            return Ok(Ok(()));
+        }
+        let counters: Vec<QuotaCounter> = quotas.iter().map(|quota| quota.counter.clone()).collect();
+        let taken = self.store.take(&counters, now).await?;
+        if taken.counted {
+            return Ok(Ok(()));
+        }
+        // The quota that frees up last is the one the client waits for
+        let exhausted = quotas
+            .iter()
+            .zip(taken.used)
+            .filter(|(quota, used)| *used >= quota.counter.limit)
+            .max_by_key(|(quota, _)| quota.resets_at)
+            .map(|(quota, used)| QuotaExhausted {
+                usage: quota.usage(used),
+                retry_after: (quota.resets_at - now).to_std().unwrap_or_default(),
+            });
+        Ok(exhausted.map_or(Ok(()), Err))
+    }
+
+    /// Where `key` stands at `now` in each period it has a quota for.
+    pub async fn report(&self, key: &ApiKey, reset_hour: u32, now: DateTime<Utc>) -> Result<QuotaReport> {
+        let quotas = Self::quotas(key, now, reset_hour);
+        let names: Vec<String> = quotas.iter().map(|quota| quota.counter.name.clone()).collect();
+        let used = if names.is_empty() { Vec::new() } else { self.store.used(&names).await? };
+        Ok(QuotaReport {
+            key_id: key.id.clone(),
+            backend: self.backend().to_string(),
+            quotas: quotas.iter().zip(used).map(|(quota, used)| quota.usage(used)).collect(),
+        })
+    }
+}
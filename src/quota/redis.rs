@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, Script};
+use tokio::sync::OnceCell;
+
+use super::{QuotaCounter, QuotaStore, Taken};
+use crate::{config::CoordinationConfig, coordination::timed};
+
+/// Counts against every key unless one is at its limit (`ARGV` holds each
+/// key's limit and expiry in turn). Returns whether it counted, then each
+/// key's count.
+const TAKE: &str = r#"
+local used = {}
+local counted = 1
+for i, key in ipairs(KEYS) do
+    used[i] = tonumber(redis.call('GET', key) or '0')
+    if used[i] >= tonumber(ARGV[2 * i - 1]) then
+        counted = 0
+    end
+end
+if counted == 1 then
+    for i, key in ipairs(KEYS) do
+        used[i] = redis.call('INCR', key)
+        redis.call('EXPIREAT', key, ARGV[2 * i])
+    end
+end
+table.insert(used, 1, counted)
+return used
+"#;
+
+/// Keeps each counter under `<prefix><counter name>` in the coordination
+/// Redis, expiring once its period is over.
+pub struct RedisQuotaStore {
+    client: redis::Client,
+    /// Connected on first use, so startup doesn't depend on Redis.
+    connection: OnceCell<ConnectionManager>,
+    key_prefix: String,
+}
+
+impl RedisQuotaStore {
+    pub fn new(config: &CoordinationConfig) -> Result<Self> {
+        let client = redis::Client::open(config.url.as_str()).context("invalid coordination URL")?;
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            key_prefix: config.key_prefix.clone(),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| timed(self.client.get_connection_manager()))
+            .await?;
+        Ok(connection.clone())
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.key_prefix, name)
+    }
+}
+
+#[async_trait]
+impl QuotaStore for RedisQuotaStore {
+    fn kind(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn take(&self, counters: &[QuotaCounter], _now: DateTime<Utc>) -> Result<Taken> {
+        let mut connection = self.connection().await?;
+        let script = Script::new(TAKE);
+        let mut invocation = script.prepare_invoke();
+        for counter in counters {
+            invocation
+                .key(self.key(&counter.name))
+                .arg(counter.limit)
+                .arg(counter.expires_at.timestamp());
+        }
+        let mut reply: Vec<u64> = timed(invocation.invoke_async(&mut connection)).await?;
+        anyhow::ensure!(reply.len() == counters.len() + 1, "unexpected reply from the quota script");
+        let counted = reply.remove(0) == 1;
+        Ok(Taken { counted, used: reply })
+    }
+
+    async fn used(&self, names: &[String]) -> Result<Vec<u64>> {
+        let mut connection = self.connection().await?;
+        let keys: Vec<String> = names.iter().map(|name| self.key(name)).collect();
+        let used: Vec<Option<u64>> = timed(redis::cmd("MGET").arg(&keys).query_async(&mut connection)).await?;
+        Ok(used.into_iter().map(Option::unwrap_or_default).collect())
+    }
+}
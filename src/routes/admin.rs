@@ -0,0 +1,1180 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{
+        diff::{diff, ConfigChange},
+        staged::StagedConfig,
+        ConfigIssue, ConfigValidationError, ReadOnlyConfig, Severity,
+    },
+    contract::ContractReport,
+    coordination::{CoordinationStatus, RolloutUpdate},
+    features::{Feature, FeatureState},
+    handoff::{self, StateImportReport, StateSnapshot},
+    gatekeeper::{Gatekeeper, GatekeeperEvaluation, SmokeStatus, ThresholdOverrides},
+    maintenance::ActiveMaintenance,
+    metrics::selfcheck::{self, MetricsSelfCheck},
+    mirror::report::{MirrorReport, MirrorReportRequest},
+    monitoring::MirrorSummary,
+    notifications::NotificationStatus,
+    profiling::ProfileSummary,
+    quota::QuotaReport,
+    middleware::{
+        auth::Claims,
+        auth_audit::AuthFailure,
+        capture::{self, CaptureRequest, CaptureResults, CaptureStatus, MAX_CAPTURE_DURATION, MAX_CAPTURE_REQUESTS},
+    },
+    tls::TlsCertificateInfo,
+    upstream::UpstreamPoolStats,
+    AppState,
+};
+
+pub use crate::models::admin::{ReadOnlyRequest, ReadOnlyStatus, RollbackRequest};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeatureOverrideRequest {
+    /// New state for the feature; `null` removes the override.
+    pub enabled: Option<bool>,
+    /// How long the override lasts before the config value resumes.
+    pub ttl_seconds: Option<u64>,
+}
+
+impl ReadOnlyStatus {
+    fn new(config: &ReadOnlyConfig, persisted: bool) -> Self {
+        Self {
+            enabled: config.enabled,
+            retry_after_seconds: config.retry_after.get().as_secs(),
+            exempt_routes: config.exempt_routes.clone(),
+            persisted,
+        }
+    }
+}
+
+/// Contract check report
+///
+/// Returns the latest per-route OpenAPI contract check results for the Rust
+/// handlers and the legacy gateway.
+#[utoipa::path(
+    get,
+    path = "/admin/contract-report",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Latest contract check report", body = ContractReport)
+    )
+)]
+pub async fn contract_report(State(state): State<AppState>) -> Json<ContractReport> {
+    Json(state.contract_checker.report())
+}
+
+/// Effective configuration
+///
+/// Returns the configuration currently in force, with secrets such as JWT
+/// keys, proxy credentials, and webhook paths redacted. Values the gateway
+/// persisted to the overrides file are listed under `overlay`, with the
+/// config file's value each one overrides, and values staged for a share of
+/// clients under `staged`.
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Redacted effective configuration", body = Object)
+    )
+)]
+pub async fn effective_config(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let mut config = state.config_watcher.get_config().await.redacted();
+    if let (Some(entries), Some(fields)) = (state.config_watcher.overlay_entries(), config.as_object_mut()) {
+        fields.insert("overlay".to_string(), serde_json::to_value(entries).unwrap_or_default());
+    }
+    if let (Some(staged), Some(fields)) = (state.config_watcher.staged(), config.as_object_mut()) {
+        fields.insert("staged".to_string(), staged_json(&staged));
+    }
+    Json(config)
+}
+
+fn staged_json(staged: &StagedConfig) -> serde_json::Value {
+    serde_json::json!({
+        "percentage": staged.percentage,
+        "entries": staged.entries,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StageConfigRequest {
+    /// Dotted path within a stageable section, e.g.
+    /// `middleware.rate_limiting.max_concurrent_per_client`.
+    pub path: String,
+    #[schema(value_type = Object)]
+    pub value: serde_json::Value,
+    /// Share of clients, 0-100, that get the staged values.
+    pub percentage: f64,
+}
+
+/// Stage a config change
+///
+/// Persists `value` at `path` for `percentage` of clients only; the others
+/// keep the current value. Only `middleware.rate_limiting` may be staged.
+/// Staging another path adds to what is already staged, and the percentage
+/// applies to all of it. Needs an overrides file.
+#[utoipa::path(
+    post,
+    path = "/admin/config/stage",
+    tag = "admin",
+    request_body = StageConfigRequest,
+    responses(
+        (status = 200, description = "What is now staged", body = Object),
+        (status = 422, description = "Change rejected; the reason is in the body")
+    )
+)]
+pub async fn stage_config(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<StageConfigRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    state
+        .config_watcher
+        .stage(&payload.path, payload.value, payload.percentage, &actor)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    tracing::info!(
+        audit = true,
+        actor = %actor,
+        path = %payload.path,
+        percentage = payload.percentage,
+        "Config change staged"
+    );
+    let staged = state.config_watcher.staged().map(|staged| staged_json(&staged));
+    Ok(Json(staged.unwrap_or_default()))
+}
+
+/// Promote the staged config
+///
+/// Applies the staged values to all traffic, as ordinary overrides.
+#[utoipa::path(
+    post,
+    path = "/admin/config/promote-staged",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Staged values promoted"),
+        (status = 422, description = "Nothing staged, or the result doesn't validate")
+    )
+)]
+pub async fn promote_staged_config(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    state
+        .config_watcher
+        .promote_staged(&actor)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    tracing::info!(audit = true, actor = %actor, "Staged config promoted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Discard the staged config
+///
+/// Drops the staged values; every client gets the current config again.
+#[utoipa::path(
+    post,
+    path = "/admin/config/discard-staged",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Staged values discarded"),
+        (status = 422, description = "Nothing staged")
+    )
+)]
+pub async fn discard_staged_config(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    state
+        .config_watcher
+        .discard_staged(&actor)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    tracing::info!(audit = true, actor = %actor, "Staged config discarded");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConfigReloadResponse {
+    pub loaded_at: String,
+    /// Validation warnings in the reloaded config.
+    pub warnings: Vec<String>,
+    /// Top-level sections taken from the file in a partial reload.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub applied_sections: Vec<String>,
+    /// Top-level sections that didn't load in a partial reload and kept
+    /// their previous values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retained_sections: Vec<String>,
+}
+
+/// Reload the configuration
+///
+/// Re-reads the config file now rather than waiting for the file watcher.
+/// A file that fails to parse or validate is rejected and the active
+/// configuration stays in force, unless `config_reload.mode` is `partial`
+/// and some of its top-level sections load on their own.
+#[utoipa::path(
+    post,
+    path = "/admin/config/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Configuration reloaded", body = ConfigReloadResponse),
+        (status = 422, description = "Config file rejected; the reason is in the body")
+    )
+)]
+pub async fn reload_config(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<Json<ConfigReloadResponse>, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    let config = state.config_watcher.reload().await.map_err(|e| {
+        tracing::warn!(actor = %actor, "Rejected requested configuration reload: {:#}", e);
+        (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e))
+    })?;
+    tracing::info!(actor = %actor, "Configuration reloaded");
+    let warnings = crate::config::validation::check(&config)
+        .into_iter()
+        .filter(|issue| issue.severity == crate::config::Severity::Warning)
+        .map(|issue| issue.to_string())
+        .collect();
+    let event = state.config_watcher.last_reload();
+    Ok(Json(ConfigReloadResponse {
+        loaded_at: state.config_watcher.last_loaded_at().to_rfc3339(),
+        warnings,
+        applied_sections: event.as_ref().map(|event| event.applied_sections.clone()).unwrap_or_default(),
+        retained_sections: event.map(|event| event.retained_sections).unwrap_or_default(),
+    }))
+}
+
+/// A validation issue found in a candidate config.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConfigCheckIssue {
+    pub section: String,
+    pub field: String,
+    pub message: String,
+}
+
+impl From<&ConfigIssue> for ConfigCheckIssue {
+    fn from(issue: &ConfigIssue) -> Self {
+        Self {
+            section: issue.section.to_string(),
+            field: issue.field.to_string(),
+            message: issue.message.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct ValidateConfigQuery {
+    /// File to read the candidate config from instead of the request body.
+    pub path: Option<String>,
+}
+
+/// What a reload of a candidate config would do.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConfigValidation {
+    /// Whether a reload would accept the config.
+    pub valid: bool,
+    /// Why the candidate couldn't be read or parsed, before validation.
+    pub load_error: Option<String>,
+    pub errors: Vec<ConfigCheckIssue>,
+    pub warnings: Vec<ConfigCheckIssue>,
+    /// Fields that would change from the active config; empty unless valid.
+    pub changes: Vec<ConfigChange>,
+}
+
+/// Validate a config without applying it
+///
+/// Runs a candidate config through the same loading, validation and
+/// overlay merge as a reload and reports the result and the fields it
+/// would change. The candidate is the YAML request body, the file named by
+/// `path`, or with neither, the config file as it is on disk now. Nothing
+/// is applied.
+#[utoipa::path(
+    post,
+    path = "/admin/config/validate",
+    tag = "admin",
+    params(ValidateConfigQuery),
+    request_body(content = String, content_type = "application/yaml", description = "Candidate config document"),
+    responses(
+        (status = 200, description = "Validation result and changes", body = ConfigValidation)
+    )
+)]
+pub async fn validate_config(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Query(query): Query<ValidateConfigQuery>,
+    body: Bytes,
+) -> Json<ConfigValidation> {
+    let actor = audit_actor(&state, claims);
+    tracing::info!(audit = true, actor = %actor, path = query.path.as_deref(), "Configuration dry run");
+    let document = match (&query.path, body.is_empty()) {
+        (Some(path), _) => std::fs::read_to_string(path)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("reading {}: {}", path, e)),
+        (None, false) => String::from_utf8(body.to_vec())
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("request body is not UTF-8")),
+        (None, true) => Ok(None),
+    };
+    let candidate = match document {
+        Ok(document) => state.config_watcher.prepare(document.as_deref()).await,
+        Err(e) => Err(e),
+    };
+
+    let mut validation = ConfigValidation {
+        valid: false,
+        load_error: None,
+        errors: Vec::new(),
+        warnings: Vec::new(),
+        changes: Vec::new(),
+    };
+    match candidate {
+        Ok(candidate) => {
+            validation.valid = true;
+            validation.warnings = crate::config::validation::check(&candidate)
+                .iter()
+                .filter(|issue| issue.severity == Severity::Warning)
+                .map(ConfigCheckIssue::from)
+                .collect();
+            validation.changes = diff(&state.config_watcher.get_config().await, &candidate);
+        }
+        Err(e) => match e.downcast_ref::<ConfigValidationError>() {
+            Some(invalid) => validation.errors = invalid.issues.iter().map(ConfigCheckIssue::from).collect(),
+            None => validation.load_error = Some(format!("{:#}", e)),
+        },
+    }
+    Json(validation)
+}
+
+/// Upstream connection pools
+///
+/// Returns per-upstream connection pool statistics: connections in use,
+/// idle connections, and requests waiting for a connection.
+#[utoipa::path(
+    get,
+    path = "/admin/upstreams",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Per-upstream pool statistics", body = [UpstreamPoolStats])
+    )
+)]
+pub async fn upstreams(State(state): State<AppState>) -> Json<Vec<UpstreamPoolStats>> {
+    Json(state.upstreams.stats())
+}
+
+/// TLS certificates
+///
+/// Lists the certificates served per SNI host with their expiry and the last
+/// reload error, if any. Empty when the gateway does not terminate TLS.
+#[utoipa::path(
+    get,
+    path = "/admin/tls",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Loaded TLS certificates", body = [TlsCertificateInfo])
+    )
+)]
+pub async fn tls_certificates(State(state): State<AppState>) -> Json<Vec<TlsCertificateInfo>> {
+    Json(state.tls_manager().map(|tls| tls.certificates()).unwrap_or_default())
+}
+
+/// A configured route and whether rollout traffic may reach its Rust handler.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RouteStatus {
+    pub method: String,
+    pub path: String,
+    pub legacy_endpoint: String,
+    /// Rollout sampling may pick the Rust handler; `false` while a smoke
+    /// check holds the route on legacy.
+    pub live: bool,
+    /// Absent for routes without a smoke check.
+    pub smoke: Option<SmokeStatus>,
+    /// The maintenance window taking the route out of service, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<ActiveMaintenance>,
+}
+
+/// Configured routes
+///
+/// Lists the configured routes with whether each takes rollout traffic and,
+/// for routes with a smoke check, the latest result.
+#[utoipa::path(
+    get,
+    path = "/admin/routes",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Configured routes", body = [RouteStatus])
+    )
+)]
+pub async fn routes(State(state): State<AppState>) -> Json<Vec<RouteStatus>> {
+    let config = state.config_watcher.get_config().await;
+    Json(
+        config
+            .routes
+            .iter()
+            .map(|route| RouteStatus {
+                method: route.method.to_uppercase(),
+                path: route.path.clone(),
+                legacy_endpoint: route.legacy_endpoint.clone(),
+                live: state.smoke_gate.is_live(&config, &route.method, &route.path),
+                smoke: state.smoke_gate.status(route),
+                maintenance: state.maintenance.blocking(&route.method, &route.path),
+            })
+            .collect(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct RouteMatchQuery {
+    pub method: String,
+    /// Concrete request path, e.g. `/api/v1/users/42`.
+    pub path: String,
+}
+
+/// Match a request to a route
+///
+/// Returns the configured route a request with this method and path would
+/// be served by, and whether it takes rollout traffic.
+#[utoipa::path(
+    get,
+    path = "/admin/routes/match",
+    tag = "admin",
+    params(RouteMatchQuery),
+    responses(
+        (status = 200, description = "Matching route", body = RouteStatus),
+        (status = 404, description = "No configured route matches")
+    )
+)]
+pub async fn match_route(
+    State(state): State<AppState>,
+    Query(query): Query<RouteMatchQuery>,
+) -> Result<Json<RouteStatus>, StatusCode> {
+    let config = state.config_watcher.get_config().await;
+    let route = config.match_route(&query.method, &query.path).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(RouteStatus {
+        method: route.method.to_uppercase(),
+        path: route.path.clone(),
+        legacy_endpoint: route.legacy_endpoint.clone(),
+        live: state.smoke_gate.is_live(&config, &route.method, &route.path),
+        smoke: state.smoke_gate.status(route),
+        maintenance: state.maintenance.blocking(&route.method, &route.path),
+    }))
+}
+
+/// Mirror sampling in force right now.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MirrorStatus {
+    /// Mirroring is on, after any feature override.
+    pub enabled: bool,
+    /// Share of requests mirrored now.
+    pub sample_percentage: f64,
+    /// Share mirrored outside schedule windows.
+    pub default_sample_percentage: f64,
+    /// The schedule window in force, if any.
+    pub active_window: Option<String>,
+    pub timezone: String,
+    /// Recent mirror outcomes; absent before any were recorded.
+    pub summary: Option<MirrorSummary>,
+}
+
+/// Mirror status
+///
+/// Returns whether mirroring is on, the sample percentage in force after the
+/// mirror schedule, and a summary of recent mirror outcomes.
+#[utoipa::path(
+    get,
+    path = "/admin/mirror/status",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Mirror status", body = MirrorStatus)
+    )
+)]
+pub async fn mirror_status(State(state): State<AppState>) -> Json<MirrorStatus> {
+    let config = state.config_watcher.get_config().await;
+    let now = state.clock.now();
+    let mirror = &config.mirror;
+    let sample_percentage = mirror.sample_percentage_at(now);
+    crate::metrics::record_mirror_sample_percentage(sample_percentage);
+    Json(MirrorStatus {
+        enabled: state.feature_overrides.is_enabled(Feature::Mirror, &config),
+        sample_percentage,
+        default_sample_percentage: mirror.sample_percentage,
+        active_window: mirror.active_window(now).map(|entry| entry.window.to_string()),
+        timezone: mirror.timezone.clone(),
+        summary: state.performance_monitor.mirror_summary(),
+    })
+}
+
+/// Mirror parity report
+///
+/// Compiles the sign-off report for mirroring between `from` and `to` (now
+/// by default) from the parity store: per-route totals, status matrices,
+/// mismatch rates, the most frequent mismatches, latency delta percentiles,
+/// and a verdict against `mirror.report`. With `mirror.parity.path` set the
+/// report covers every process since `from`. `markdown` adds a rendering
+/// for people, and `notify` posts it to the rollout webhook.
+#[utoipa::path(
+    post,
+    path = "/admin/mirror/report",
+    tag = "admin",
+    request_body = MirrorReportRequest,
+    responses(
+        (status = 200, description = "Mirror parity report", body = MirrorReport),
+        (status = 400, description = "`from` isn't before `to`"),
+        (status = 500, description = "The parity store couldn't be read")
+    )
+)]
+pub async fn mirror_report(
+    State(state): State<AppState>,
+    Json(request): Json<MirrorReportRequest>,
+) -> Result<Json<MirrorReport>, (StatusCode, String)> {
+    let config = state.config_watcher.get_config().await;
+    let now = state.clock.now();
+    let to = request.to.unwrap_or(now);
+    if request.from >= to {
+        return Err((StatusCode::BAD_REQUEST, "`from` must be before `to`".to_string()));
+    }
+    let records = state
+        .mirror_parity
+        .read(request.from, to)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    let mut report = MirrorReport::compile(
+        &records,
+        request.from,
+        to,
+        state.mirror_parity.is_persistent(),
+        &config.mirror.report,
+        now,
+    );
+    if request.notify {
+        report.notify(&state.notifier, &config).await;
+    }
+    if request.markdown {
+        report.markdown = Some(report.to_markdown());
+    }
+    Ok(Json(report))
+}
+
+/// Metrics pipeline self-check
+///
+/// Records a probe counter and histogram sample the way request metrics are
+/// recorded, then checks the exporter's output shows each move by what was
+/// recorded. A family that's missing or off answers 503.
+#[utoipa::path(
+    get,
+    path = "/admin/metrics/selfcheck",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every probe series was exported", body = MetricsSelfCheck),
+        (status = 503, description = "A probe series was missing or off", body = MetricsSelfCheck)
+    )
+)]
+pub async fn metrics_selfcheck() -> (StatusCode, Json<MetricsSelfCheck>) {
+    let check = selfcheck::run();
+    if !check.passed {
+        tracing::error!(families = ?check.families, "Metrics self-check failed");
+    }
+    let status = if check.passed { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(check))
+}
+
+/// Notification status
+///
+/// Returns how many webhook notifications each destination has had this
+/// window, the events held back for the next digest by type, and when that
+/// digest is due.
+#[utoipa::path(
+    get,
+    path = "/admin/notifications/status",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Notification rate limiting status", body = NotificationStatus)
+    )
+)]
+pub async fn notification_status(State(state): State<AppState>) -> Json<NotificationStatus> {
+    let config = state.config_watcher.get_config().await;
+    Json(state.notifier.status_at(&config.notifications, std::time::Instant::now()))
+}
+
+/// Toggleable features
+///
+/// Lists the middleware features that can be switched at runtime, with their
+/// config value, any active override, and the effective state.
+#[utoipa::path(
+    get,
+    path = "/admin/features",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Feature states", body = [FeatureState])
+    )
+)]
+pub async fn list_features(State(state): State<AppState>) -> Json<Vec<FeatureState>> {
+    let config = state.config_watcher.get_config().await;
+    Json(state.feature_overrides.states(&config))
+}
+
+/// Override a feature
+///
+/// Switches a middleware feature on or off at runtime, optionally for a
+/// limited time. Overrides survive config reloads unless the reloaded config
+/// sets `reset_overrides: true`.
+#[utoipa::path(
+    put,
+    path = "/admin/features/{name}",
+    tag = "admin",
+    params(
+        ("name" = String, Path, description = "Feature name, e.g. body_logging")
+    ),
+    request_body = FeatureOverrideRequest,
+    responses(
+        (status = 200, description = "Updated feature state", body = FeatureState),
+        (status = 404, description = "Unknown feature")
+    )
+)]
+pub async fn set_feature(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<FeatureOverrideRequest>,
+) -> Result<Json<FeatureState>, StatusCode> {
+    let feature: Feature = name.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    let actor = audit_actor(&state, claims);
+
+    match payload.enabled {
+        Some(enabled) => state.feature_overrides.set(
+            feature,
+            enabled,
+            payload.ttl_seconds.map(Duration::from_secs),
+            &actor,
+        ),
+        None => state.feature_overrides.clear(feature, &actor),
+    }
+
+    let config = state.config_watcher.get_config().await;
+    state
+        .feature_overrides
+        .states(&config)
+        .into_iter()
+        .find(|current| current.name == feature)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Read-only mode
+///
+/// Whether writes are currently turned away.
+#[utoipa::path(
+    get,
+    path = "/admin/read-only",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Read-only mode", body = ReadOnlyStatus)
+    )
+)]
+pub async fn read_only_status(State(state): State<AppState>) -> Json<ReadOnlyStatus> {
+    let config = state.config_watcher.get_config().await;
+    Json(ReadOnlyStatus::new(&config.read_only, state.config_watcher.has_overlay()))
+}
+
+/// Switch read-only mode
+///
+/// Turns read-only mode on or off. With an overrides file the setting is
+/// persisted there and survives restarts; without one it applies until the
+/// config file is next reloaded.
+#[utoipa::path(
+    post,
+    path = "/admin/read-only",
+    tag = "admin",
+    request_body = ReadOnlyRequest,
+    responses(
+        (status = 200, description = "Read-only mode after the change", body = ReadOnlyStatus),
+        (status = 422, description = "The overrides file couldn't be written")
+    )
+)]
+pub async fn set_read_only(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<ReadOnlyRequest>,
+) -> Result<Json<ReadOnlyStatus>, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    let persisted = state.config_watcher.has_overlay();
+    let config = if persisted {
+        state
+            .config_watcher
+            .persist("read_only.enabled", payload.enabled, &actor)
+            .await
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?
+    } else {
+        let mut config = state.config_watcher.get_config().await;
+        config.read_only.enabled = payload.enabled;
+        state.config_watcher.apply(config.clone()).await;
+        config
+    };
+    tracing::warn!(
+        audit = true,
+        actor = %actor,
+        enabled = payload.enabled,
+        persisted = persisted,
+        "Read-only mode switched"
+    );
+    Ok(Json(ReadOnlyStatus::new(&config.read_only, persisted)))
+}
+
+/// Start a debug capture
+///
+/// Records sanitized requests and responses for one route until the duration
+/// elapses or `max_requests` matching requests have been seen. Credentials in
+/// headers are redacted and bodies are size-capped. Only one capture may run
+/// at a time.
+#[utoipa::path(
+    post,
+    path = "/admin/debug/capture",
+    tag = "admin",
+    request_body = CaptureRequest,
+    responses(
+        (status = 201, description = "Capture started", body = CaptureStatus),
+        (status = 400, description = "Invalid duration or request cap"),
+        (status = 409, description = "A capture is already running", body = CaptureStatus)
+    )
+)]
+pub async fn start_capture(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<CaptureRequest>,
+) -> (StatusCode, Json<Option<CaptureStatus>>) {
+    if payload.route.is_empty()
+        || payload.duration_seconds == 0
+        || payload.duration_seconds > MAX_CAPTURE_DURATION.as_secs()
+        || payload.max_requests == 0
+        || payload.max_requests > MAX_CAPTURE_REQUESTS
+    {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+
+    let actor = audit_actor(&state, claims);
+
+    match state.debug_capture.start(payload, &actor) {
+        Ok(status) => (StatusCode::CREATED, Json(Some(status))),
+        Err(running) => (StatusCode::CONFLICT, Json(Some(*running))),
+    }
+}
+
+/// Debug capture results
+///
+/// Returns the current or most recent capture and the exchanges it recorded.
+#[utoipa::path(
+    get,
+    path = "/admin/debug/capture/results",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Captured exchanges", body = CaptureResults),
+        (status = 404, description = "No capture has been started")
+    )
+)]
+pub async fn capture_results(State(state): State<AppState>) -> Result<Json<CaptureResults>, StatusCode> {
+    state.debug_capture.results().map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct CaptureExportQuery {
+    /// Export format; only `har` (the default) is supported.
+    pub format: Option<String>,
+    /// Only exchanges on this route template or path.
+    pub route: Option<String>,
+    /// Only exchanges that started at or after this RFC 3339 time.
+    pub from: Option<DateTime<Utc>>,
+    /// Only exchanges that started before this RFC 3339 time.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Export debug capture results
+///
+/// Returns the exchanges of the current or most recent capture as a HAR 1.2
+/// document, optionally narrowed to a route and a time range. Headers are
+/// redacted as in the results, and bodies that aren't UTF-8 are
+/// base64-encoded.
+#[utoipa::path(
+    get,
+    path = "/admin/debug/capture/export",
+    tag = "admin",
+    params(CaptureExportQuery),
+    responses(
+        (status = 200, description = "HAR 1.2 document", content_type = "application/json"),
+        (status = 400, description = "Unknown format"),
+        (status = 404, description = "No capture has been started")
+    )
+)]
+pub async fn export_capture(State(state): State<AppState>, Query(query): Query<CaptureExportQuery>) -> Response {
+    if query.format.as_deref().unwrap_or("har") != "har" {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let Some(results) = state.debug_capture.results() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let on_route = |path: &str| {
+        query.route.as_deref().is_none_or(|route| route == results.capture.route || route == path)
+    };
+    let in_range = |timestamp: &str| {
+        let Ok(at) = DateTime::parse_from_rfc3339(timestamp) else {
+            return false;
+        };
+        query.from.is_none_or(|from| at >= from) && query.to.is_none_or(|to| at < to)
+    };
+    let exchanges: Vec<_> = results
+        .exchanges
+        .iter()
+        .filter(|exchange| on_route(&exchange.path) && in_range(&exchange.timestamp))
+        .cloned()
+        .collect();
+    (
+        [(header::CONTENT_DISPOSITION, "attachment; filename=\"capture.har\"")],
+        Json(capture::to_har(&exchanges)),
+    )
+        .into_response()
+}
+
+/// Recent auth failures
+///
+/// Lists the last `middleware.auth.failure_log_size` requests denied by
+/// authentication or authorization, newest first, with the reason, client
+/// IP and pseudonymized token subject. Tokens are never kept.
+#[utoipa::path(
+    get,
+    path = "/admin/auth/failures",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Recent denials", body = [AuthFailure])
+    )
+)]
+pub async fn auth_failures(State(state): State<AppState>) -> Json<Vec<AuthFailure>> {
+    Json(state.auth_failures.recent())
+}
+
+/// Captured profiles
+///
+/// Lists the profiles kept in memory, newest first. Profiles are only
+/// captured by builds with the `profiling` feature and `profiling.enabled`.
+#[utoipa::path(
+    get,
+    path = "/admin/profiles",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Kept profiles", body = [ProfileSummary])
+    )
+)]
+pub async fn list_profiles(State(state): State<AppState>) -> Json<Vec<ProfileSummary>> {
+    Json(state.profiler.list())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileQuery {
+    pub format: Option<String>,
+}
+
+/// Download a profile
+///
+/// Returns one profile as a flamegraph SVG (the default), a gzipped pprof
+/// protobuf (`format=pprof`), or folded stacks (`format=folded`).
+#[utoipa::path(
+    get,
+    path = "/admin/profiles/{id}",
+    tag = "admin",
+    params(
+        ("id" = u64, Path, description = "Profile id from GET /admin/profiles"),
+        ("format" = Option<String>, Query, description = "svg, pprof, or folded")
+    ),
+    responses(
+        (status = 200, description = "The profile in the requested format"),
+        (status = 400, description = "Unknown format"),
+        (status = 404, description = "No such profile")
+    )
+)]
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+    Query(query): Query<ProfileQuery>,
+) -> Response {
+    let Some(profile) = state.profiler.get(id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match query.format.as_deref().unwrap_or("svg") {
+        "svg" => ([(header::CONTENT_TYPE, "image/svg+xml")], profile.svg()).into_response(),
+        "folded" => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], profile.folded()).into_response(),
+        "pprof" => match profile.pprof() {
+            Ok(bytes) => (
+                [
+                    (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"profile-{}.pb.gz\"", id)),
+                ],
+                bytes,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Error encoding profile {}: {:#}", id, e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        },
+        _ => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// Rollout state
+///
+/// Returns the rollout state this replica acts on and its part in
+/// coordination: backend, leadership, and whether it has fallen back to
+/// local-only mode.
+#[utoipa::path(
+    get,
+    path = "/admin/rollout",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Rollout state", body = CoordinationStatus)
+    )
+)]
+pub async fn rollout_state(State(state): State<AppState>) -> Json<CoordinationStatus> {
+    Json(state.coordinator.status().await)
+}
+
+/// Update the rollout
+///
+/// Sets the rollout percentage, pauses or resumes advancement, or switches
+/// between automatic and manual mode. With coordination configured the
+/// change reaches every replica.
+#[utoipa::path(
+    put,
+    path = "/admin/rollout",
+    tag = "admin",
+    request_body = RolloutUpdate,
+    responses(
+        (status = 200, description = "Updated rollout state", body = CoordinationStatus),
+        (status = 400, description = "Percentage outside 0-100")
+    )
+)]
+pub async fn update_rollout(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<RolloutUpdate>,
+) -> Result<Json<CoordinationStatus>, StatusCode> {
+    if payload
+        .rollout_percentage
+        .is_some_and(|percentage| !(0.0..=100.0).contains(&percentage))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let actor = audit_actor(&state, claims);
+    let updated = state.coordinator.update(payload, &actor).await;
+    tracing::info!(
+        actor = %actor,
+        rollout_percentage = updated.rollout_percentage,
+        paused = updated.paused,
+        mode = ?updated.mode,
+        "Rollout updated"
+    );
+    Ok(Json(state.coordinator.status().await))
+}
+
+/// Advance the rollout
+///
+/// Steps the rollout forward by `canary_rollout.step`, as the gatekeeper
+/// would, including the mirror readiness gate when leaving 0%.
+#[utoipa::path(
+    post,
+    path = "/admin/rollout/advance",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Rollout advanced", body = CoordinationStatus),
+        (status = 409, description = "Paused, manual, already at 100%, or readiness checks failing")
+    )
+)]
+pub async fn advance_rollout(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<Json<CoordinationStatus>, StatusCode> {
+    let actor = audit_actor(&state, claims);
+    if !Gatekeeper::new(state.clone()).advance_rollout().await {
+        return Err(StatusCode::CONFLICT);
+    }
+    let status = state.coordinator.status().await;
+    tracing::info!(actor = %actor, rollout_percentage = status.state.rollout_percentage, "Rollout advanced");
+    Ok(Json(status))
+}
+
+/// Roll back the rollout
+///
+/// Steps the rollout back as an automatic rollback would, cancelling any
+/// slow-start ramp and posting the rollback alert.
+#[utoipa::path(
+    post,
+    path = "/admin/rollout/rollback",
+    tag = "admin",
+    request_body = RollbackRequest,
+    responses(
+        (status = 200, description = "Rolled back", body = CoordinationStatus)
+    )
+)]
+pub async fn rollback_rollout(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<RollbackRequest>,
+) -> Json<CoordinationStatus> {
+    let actor = audit_actor(&state, claims);
+    let reason = format!(
+        "{} (requested by {})",
+        payload.reason.as_deref().unwrap_or("operator request"),
+        actor
+    );
+    Gatekeeper::new(state.clone()).force_rollback(&reason).await;
+    Json(state.coordinator.status().await)
+}
+
+/// Dry-run the gatekeeper
+///
+/// Judges the current monitor readings as the gatekeeper would, with any
+/// thresholds in the body in place of the configured ones, and returns
+/// every rule's inputs and outcome along with the rollback that would
+/// follow. Nothing is rolled back or notified, and the route counts are
+/// left for the next real check.
+#[utoipa::path(
+    post,
+    path = "/admin/gatekeeper/evaluate",
+    tag = "admin",
+    request_body(content = Option<ThresholdOverrides>, description = "Thresholds to try; all optional"),
+    responses(
+        (status = 200, description = "Decision trace", body = GatekeeperEvaluation),
+        (status = 400, description = "Body isn't a threshold overrides object")
+    )
+)]
+pub async fn evaluate_gatekeeper(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<GatekeeperEvaluation>, (StatusCode, String)> {
+    let overrides: ThresholdOverrides = if body.iter().all(u8::is_ascii_whitespace) {
+        ThresholdOverrides::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    };
+    Ok(Json(Gatekeeper::new(state).evaluate(&overrides).await))
+}
+
+/// Export runtime state
+///
+/// Returns what a replacement instance needs to pick up where this one
+/// leaves off at a blue/green swap: the rollout state and history, any
+/// slow-start ramp, the gatekeeper's cooldown, route reductions, smoke
+/// checks and drift streaks, the performance baseline, and the upstream
+/// probe results. Nothing identifying a client is included.
+#[utoipa::path(
+    get,
+    path = "/admin/state/export",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Snapshot of the runtime state", body = StateSnapshot)
+    )
+)]
+pub async fn export_state(State(state): State<AppState>, claims: Option<Extension<Claims>>) -> Json<StateSnapshot> {
+    let actor = audit_actor(&state, claims);
+    let snapshot = handoff::export(&state).await;
+    tracing::info!(actor = %actor, exported_at = %snapshot.exported_at, "Runtime state exported");
+    Json(snapshot)
+}
+
+/// Import runtime state
+///
+/// Takes over a snapshot exported by the instance being replaced. Only
+/// accepted before this instance reports ready. Snapshots in another
+/// format, older than `handoff.max_snapshot_age`, or exported further in
+/// the future than `handoff.max_clock_skew` are refused. With rollout
+/// coordination the shared rollout state is kept instead of the
+/// snapshot's.
+#[utoipa::path(
+    post,
+    path = "/admin/state/import",
+    tag = "admin",
+    request_body = StateSnapshot,
+    responses(
+        (status = 200, description = "What was taken over", body = StateImportReport),
+        (status = 409, description = "This instance already reports ready"),
+        (status = 422, description = "Snapshot refused; the reason is in the body")
+    )
+)]
+pub async fn import_state(
+    State(state): State<AppState>,
+    claims: Option<Extension<Claims>>,
+    Json(snapshot): Json<StateSnapshot>,
+) -> Result<Json<StateImportReport>, (StatusCode, String)> {
+    let actor = audit_actor(&state, claims);
+    let from_instance = snapshot.instance_id.clone();
+    handoff::import(&state, snapshot, &actor).await.map(Json).map_err(|refusal| {
+        tracing::warn!(
+            audit = true,
+            actor = %actor,
+            from_instance = %from_instance,
+            reason = %refusal,
+            "Runtime state import refused"
+        );
+        (refusal.status(), refusal.to_string())
+    })
+}
+
+/// API key quota usage
+///
+/// Returns how many requests the key with this id has made in the current
+/// day and month, how many it has left, and when each count starts over.
+/// Periods the key has no quota for are left out.
+#[utoipa::path(
+    get,
+    path = "/admin/quotas/{key_id}",
+    tag = "admin",
+    params(
+        ("key_id" = String, Path, description = "Id of an entry in middleware.auth.api_keys")
+    ),
+    responses(
+        (status = 200, description = "Used and remaining requests per period", body = QuotaReport),
+        (status = 404, description = "No API key with this id"),
+        (status = 503, description = "The quota store can't be reached")
+    )
+)]
+pub async fn quota_usage(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> Result<Json<QuotaReport>, (StatusCode, String)> {
+    let config = state.config_watcher.get_config().await;
+    let auth = &config.middleware.auth;
+    let key = auth
+        .api_keys
+        .iter()
+        .find(|key| key.id == key_id)
+        .ok_or((StatusCode::NOT_FOUND, format!("no API key with id {:?}", key_id)))?;
+    state
+        .quotas
+        .report(key, auth.quota_reset_hour, state.clock.now())
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e)))
+}
+
+/// Who an audit entry is attributed to, pseudonymized like every other user
+/// identifier that reaches the logs.
+fn audit_actor(state: &AppState, claims: Option<Extension<Claims>>) -> String {
+    claims
+        .map(|Extension(claims)| state.pseudonymizer.pseudonymize(&claims.sub))
+        .unwrap_or_else(|| "anonymous".to_string())
+}
@@ -0,0 +1,76 @@
+use axum::{
+    http::{Extensions, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{Map, Value};
+
+use crate::middleware::request_id::RequestId;
+
+pub use crate::models::error::{ErrorBody, ErrorResponse};
+
+/// The request's id, or a fresh UUIDv7 for a request the request id
+/// middleware hasn't seen.
+pub fn request_id(extensions: &Extensions) -> String {
+    extensions
+        .get::<RequestId>()
+        .cloned()
+        .unwrap_or_else(RequestId::generate)
+        .as_str()
+        .to_string()
+}
+
+/// A request the gateway turned away, answered as an [`ErrorResponse`]
+/// carrying the request's id, which the request id middleware also echoes
+/// in the response headers.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    request_id: Option<String>,
+    details: Map<String, Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            request_id: None,
+            details: Map::new(),
+        }
+    }
+
+    /// Takes the request id from the request's `extensions`; see
+    /// [`request_id`].
+    pub fn for_request(self, extensions: &Extensions) -> Self {
+        self.with_request_id(request_id(extensions))
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Adds a member to the error object next to `code` and `message`.
+    pub fn with_detail(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = self.request_id.unwrap_or_else(|| RequestId::generate().as_str().to_string());
+        let body = ErrorResponse {
+            error: ErrorBody {
+                code: self.code.to_string(),
+                message: self.message,
+                request_id,
+                details: self.details,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
@@ -1,41 +1,29 @@
-use axum::{extract::State, response::Json};
-use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use axum::{extract::State, http::StatusCode, response::Json};
 use tracing::info;
 
-use crate::AppState;
+use crate::{config::AppConfig, upstream::health::UpstreamProbe, AppState};
 
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct HealthResponse {
-    pub status: String,
-    pub service: String,
-    pub version: String,
-    pub timestamp: String,
-}
-
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct DetailedHealthResponse {
-    pub status: String,
-    pub service: String,
-    pub version: String,
-    pub timestamp: String,
-    pub config_loaded: bool,
-    pub hot_reload_enabled: bool,
-    pub server_config: ServerConfigInfo,
-    pub upstream_services: UpstreamStatus,
-}
-
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct ServerConfigInfo {
-    pub host: String,
-    pub port: u16,
-    pub timeout_seconds: u64,
-}
+pub use crate::models::health::{
+    DetailedHealthResponse, HealthResponse, ReadinessResponse, ServerConfigInfo, UpstreamStatus,
+};
 
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct UpstreamStatus {
-    pub status: String,
-    pub note: String,
+impl UpstreamStatus {
+    fn from_probes(config: &AppConfig, upstreams: Vec<UpstreamProbe>) -> Self {
+        let (status, note) = if !config.http_client.health_check.enabled {
+            ("disabled", "Upstream health checks are off (http_client.health_check)")
+        } else if upstreams.is_empty() {
+            ("unknown", "No upstream has been probed yet")
+        } else if upstreams.iter().all(|probe| probe.healthy) {
+            ("healthy", "Every upstream passed its latest probe")
+        } else {
+            ("unhealthy", "An upstream failed its latest probe")
+        };
+        Self {
+            status: status.to_string(),
+            note: note.to_string(),
+            upstreams,
+        }
+    }
 }
 
 /// Basic health check endpoint
@@ -61,6 +49,27 @@ pub async fn health(State(_state): State<AppState>) -> Json<HealthResponse> {
     })
 }
 
+/// Readiness check endpoint
+///
+/// Returns 503 until startup warm-up has run, so no traffic is routed to an
+/// instance whose first requests would still pay one-off initialization,
+/// and for good if warm-up found the metrics exporter broken.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready for traffic", body = ReadinessResponse),
+        (status = 503, description = "Still warming up, or a warm-up check failed", body = ReadinessResponse)
+    )
+)]
+pub async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let warmup = state.warmup.report();
+    let ready = state.warmup.is_ready();
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(ReadinessResponse { ready, warmup }))
+}
+
 /// Detailed health check endpoint
 ///
 /// Returns comprehensive health information including configuration status,
@@ -78,23 +87,33 @@ pub async fn health_detailed(State(state): State<AppState>) -> Json<DetailedHeal
     info!("Detailed health check requested");
     
     let config = state.config_watcher.get_config().await;
+    let clock_skew = state.clock.skew();
+    let upstream_services = UpstreamStatus::from_probes(&config, state.upstream_health.report(&config));
+    let status = if clock_skew.as_ref().is_some_and(|skew| !skew.within_tolerance) {
+        "degraded"
+    } else {
+        "healthy"
+    };
     
     Json(DetailedHealthResponse {
-        status: "healthy".to_string(),
+        status: status.to_string(),
         service: "project-gateway".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         config_loaded: true,
         hot_reload_enabled: true,
+        config_file_present: state.config_watcher.file_present(),
+        config_last_loaded_at: state.config_watcher.last_loaded_at().to_rfc3339(),
         server_config: ServerConfigInfo {
             host: config.server.host,
             port: config.server.port,
-            timeout_seconds: config.server.timeout_seconds,
-        },
-        upstream_services: UpstreamStatus {
-            status: "checking".to_string(),
-            note: "Upstream health checks not yet implemented".to_string(),
+            timeout_seconds: config.server.timeout.get().as_secs(),
         },
+        upstream_services,
+        memory: state.memory_budget.report(),
+        clock_skew,
+        listener: state.listener.report(),
+        config_last_reload: state.config_watcher.last_reload(),
     })
 }
 
@@ -0,0 +1,53 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use std::convert::Infallible;
+
+use crate::{config::normalize_base_path, AppState};
+
+pub const FORWARDED_PREFIX_HEADER: &str = "x-forwarded-prefix";
+
+/// Path prefix clients used to reach this request, for building links they
+/// can follow (redirects, `Location` headers, problem+json `instance`).
+///
+/// An ingress that strips the prefix announces it via `X-Forwarded-Prefix`;
+/// otherwise the configured `server.public_base_path` applies when the
+/// request arrived under it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicPrefix(pub String);
+
+impl PublicPrefix {
+    pub fn resolve(headers: &HeaderMap, path: &str, base_path: &str) -> Self {
+        let forwarded = headers
+            .get(FORWARDED_PREFIX_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(normalize_base_path);
+        if let Some(prefix) = forwarded {
+            return Self(prefix);
+        }
+
+        let under_base = !base_path.is_empty()
+            && path
+                .strip_prefix(base_path)
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(false);
+        Self(if under_base { base_path.to_string() } else { String::new() })
+    }
+
+    /// Prefixes a gateway-absolute path such as `/docs/`.
+    pub fn link(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for PublicPrefix {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let base_path = state.config_watcher.get_config().await.server.base_path();
+        Ok(Self::resolve(&parts.headers, parts.uri.path(), &base_path))
+    }
+}
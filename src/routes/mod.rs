@@ -1,2 +1,8 @@
+pub mod admin;
+pub mod error;
 pub mod health;
+pub mod links;
+pub mod monitoring;
+pub mod negotiation;
 pub mod users;
+pub mod versions;
@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    monitoring::{
+        drift::LatencyDriftStatus,
+        heatmap::{LatencyHeatmapReport, MAX_BUCKETS, MAX_WINDOW, SLOT},
+        slo::{SliStatus, SloStatus},
+        PerformanceMetrics, PerformanceValidation,
+    },
+    config::HumanDuration,
+    routes::negotiation::{Accept, Negotiated, Tabular},
+    AppState,
+};
+
+/// One objective as a CSV row; each SLI's fields get its name as a prefix.
+#[derive(Serialize)]
+struct SloRow<'a> {
+    name: &'a str,
+    method: Option<&'a str>,
+    route: &'a str,
+    window: &'a str,
+    requests: u64,
+    availability_target: Option<f64>,
+    availability_achieved: Option<f64>,
+    availability_error_budget_remaining: Option<f64>,
+    latency_target: Option<f64>,
+    latency_achieved: Option<f64>,
+    latency_error_budget_remaining: Option<f64>,
+    burn_rate_1h: f64,
+    burn_rate_6h: f64,
+    fast_burn: bool,
+}
+
+impl Tabular for Vec<SloStatus> {
+    fn rows(&self) -> impl Iterator<Item = impl Serialize + '_> {
+        self.iter().map(|status| {
+            let sli = |sli: &Option<SliStatus>| {
+                sli.as_ref()
+                    .map(|sli| (Some(sli.target), sli.achieved, Some(sli.error_budget_remaining)))
+                    .unwrap_or_default()
+            };
+            let availability = sli(&status.availability);
+            let latency = sli(&status.latency);
+            SloRow {
+                name: &status.name,
+                method: status.method.as_deref(),
+                route: &status.route,
+                window: &status.window,
+                requests: status.requests,
+                availability_target: availability.0,
+                availability_achieved: availability.1,
+                availability_error_budget_remaining: availability.2,
+                latency_target: latency.0,
+                latency_achieved: latency.1,
+                latency_error_budget_remaining: latency.2,
+                burn_rate_1h: status.burn_rate_1h,
+                burn_rate_6h: status.burn_rate_6h,
+                fast_burn: status.fast_burn,
+            }
+        })
+    }
+}
+
+/// SLO compliance
+///
+/// Returns each configured objective's compliance over its window and its
+/// error-budget burn rates over the last 1h and 6h.
+#[utoipa::path(
+    get,
+    path = "/monitoring/slo",
+    tag = "monitoring",
+    responses(
+        (status = 200, description = "SLO compliance and burn rates", body = [SloStatus],
+            content_type = ["application/json", "application/msgpack", "text/csv"]),
+        (status = 406, description = "None of the accepted media types can be produced",
+            body = crate::routes::error::ErrorResponse)
+    )
+)]
+pub async fn slo_status(State(state): State<AppState>, accept: Accept) -> Negotiated<Vec<SloStatus>> {
+    let config = state.config_watcher.get_config().await;
+    Negotiated::table(&accept, state.slo_tracker.status(&config.slo))
+}
+
+/// Latency and error rates per variant, the comparison against the
+/// baseline, and latency distribution drift.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PerformanceReport {
+    pub rust: Option<PerformanceMetrics>,
+    pub legacy: Option<PerformanceMetrics>,
+    pub validation: PerformanceValidation,
+    /// Every variant and route scored at the last drift evaluation; empty
+    /// while `canary_rollout.latency_drift` is off.
+    pub latency_drift: Vec<LatencyDriftStatus>,
+}
+
+/// Performance
+///
+/// Returns recent latency percentiles and error rates for the Rust and
+/// legacy paths, how they compare with the baseline, and how far each
+/// route's latency distribution has drifted from its recent history.
+#[utoipa::path(
+    get,
+    path = "/monitoring/performance",
+    tag = "monitoring",
+    responses(
+        (status = 200, description = "Performance of both paths and latency drift", body = PerformanceReport)
+    )
+)]
+pub async fn performance(State(state): State<AppState>) -> Json<PerformanceReport> {
+    let config = state.config_watcher.get_config().await;
+    let drift = &config.canary_rollout.latency_drift;
+    let monitor = &state.performance_monitor;
+    Json(PerformanceReport {
+        rust: monitor.get_current_metrics("rust"),
+        legacy: monitor.get_current_metrics("legacy"),
+        validation: monitor.validate_performance(),
+        latency_drift: if drift.enabled { state.latency_drift.status(drift) } else { Vec::new() },
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HeatmapQuery {
+    pub variant: Option<String>,
+    pub route: Option<String>,
+    pub window: Option<String>,
+    pub buckets: Option<usize>,
+}
+
+/// Latency heatmap
+///
+/// Returns one variant's latency distribution over a recent window as
+/// bucket edges and counts, ready to draw. Without `route`, every route's
+/// requests are merged. Cheap enough to poll every few seconds.
+#[utoipa::path(
+    get,
+    path = "/monitoring/heatmap",
+    tag = "monitoring",
+    params(
+        ("variant" = Option<String>, Query, description = "rust (the default) or legacy"),
+        ("route" = Option<String>, Query, description = "Matched route, such as /api/v1/users; all routes when omitted"),
+        ("window" = Option<String>, Query, description = "How far back to look, 15s to 30m (default 5m)"),
+        ("buckets" = Option<usize>, Query, description = "Most buckets to return, 1 to 160 (default 40)")
+    ),
+    responses(
+        (status = 200, description = "Bucket edges and counts", body = LatencyHeatmapReport),
+        (status = 400, description = "Unknown variant, or window or buckets out of range")
+    )
+)]
+pub async fn heatmap(
+    State(state): State<AppState>,
+    Query(query): Query<HeatmapQuery>,
+) -> Result<Json<LatencyHeatmapReport>, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+    let variant = match query.variant.as_deref().unwrap_or("rust") {
+        "rust" => "rust",
+        "legacy" => "legacy",
+        other => return Err(bad_request(format!("unknown variant {:?} (use rust or legacy)", other))),
+    };
+    let window = match query.window.as_deref() {
+        Some(window) => window.parse::<HumanDuration>().map_err(bad_request)?.get(),
+        None => std::time::Duration::from_secs(5 * 60),
+    };
+    if window < SLOT || window > MAX_WINDOW {
+        return Err(bad_request(format!(
+            "window must be between {} and {}",
+            HumanDuration::from_millis(SLOT.as_millis() as u64),
+            HumanDuration::from_millis(MAX_WINDOW.as_millis() as u64)
+        )));
+    }
+    let buckets = query.buckets.unwrap_or(40);
+    if !(1..=MAX_BUCKETS).contains(&buckets) {
+        return Err(bad_request(format!("buckets must be between 1 and {}", MAX_BUCKETS)));
+    }
+    Ok(Json(state.latency_heatmap.report(variant, query.route.as_deref(), window, buckets)))
+}
@@ -0,0 +1,229 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use tracing::error;
+
+use crate::routes::error::{self, ApiError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    MessagePack,
+    Csv,
+}
+
+impl ResponseFormat {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::MessagePack => "application/msgpack",
+            ResponseFormat::Csv => "text/csv",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ResponseFormat::Csv => "text/csv; charset=utf-8",
+            other => other.media_type(),
+        }
+    }
+
+    /// How specifically `range` names this format: 2 for its own media type,
+    /// 1 for `type/*`, 0 for `*/*`.
+    fn specificity(self, range: &str) -> Option<u8> {
+        let media_type = self.media_type();
+        let aliases: &[&str] = match self {
+            ResponseFormat::MessagePack => &["application/x-msgpack", "application/vnd.msgpack"],
+            _ => &[],
+        };
+        if range == media_type || aliases.contains(&range) {
+            return Some(2);
+        }
+        match range.strip_suffix("/*") {
+            Some("*") => Some(0),
+            Some(kind) if media_type.split('/').next() == Some(kind) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Formats every negotiated endpoint can send.
+pub const DOCUMENT_FORMATS: &[ResponseFormat] = &[ResponseFormat::Json, ResponseFormat::MessagePack];
+/// Formats for list-shaped endpoints, which can also be sent as CSV.
+pub const TABLE_FORMATS: &[ResponseFormat] = &[ResponseFormat::Json, ResponseFormat::MessagePack, ResponseFormat::Csv];
+
+/// Media ranges from the request's `Accept` header with their quality.
+/// Without the header any format is acceptable. Extracted, it also keeps
+/// the request's id for the 406 when nothing listed can be produced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Accept {
+    ranges: Vec<(String, f32)>,
+    request_id: Option<String>,
+}
+
+impl Accept {
+    pub fn parse(header: Option<&str>) -> Self {
+        let ranges = header
+            .into_iter()
+            .flat_map(|header| header.split(','))
+            .filter_map(|entry| {
+                let mut params = entry.split(';');
+                let range = params.next()?.trim().to_ascii_lowercase();
+                if range.is_empty() {
+                    return None;
+                }
+                let quality = params
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((range, quality))
+            })
+            .collect();
+        Self {
+            ranges,
+            request_id: None,
+        }
+    }
+
+    /// The client's preferred format among `supported`, ties going to the
+    /// earlier one. A format's quality comes from the most specific range
+    /// naming it, so `*/*, text/csv;q=0` rules CSV out.
+    pub fn negotiate(&self, supported: &[ResponseFormat]) -> Option<ResponseFormat> {
+        if self.ranges.is_empty() {
+            return supported.first().copied();
+        }
+        let mut best: Option<(ResponseFormat, f32)> = None;
+        for &format in supported {
+            let quality = self
+                .ranges
+                .iter()
+                .filter_map(|(range, quality)| format.specificity(range).map(|specificity| (specificity, *quality)))
+                .max_by(|a, b| a.0.cmp(&b.0))
+                .map(|(_, quality)| quality);
+            if let Some(quality) = quality.filter(|quality| *quality > 0.0) {
+                if best.is_none_or(|(_, best)| quality > best) {
+                    best = Some((format, quality));
+                }
+            }
+        }
+        best.map(|(format, _)| format)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Accept {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            request_id: Some(error::request_id(&parts.extensions)),
+            ..Self::parse(parts.headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()))
+        })
+    }
+}
+
+/// List-shaped responses, sent as CSV with one row per item.
+pub trait Tabular {
+    fn rows(&self) -> impl Iterator<Item = impl Serialize + '_>;
+}
+
+/// Maps keyed by field name and values in their JSON form (UUIDs as
+/// strings, not bytes), so MessagePack consumers see the documented schema.
+fn encode_msgpack<T: Serialize>(body: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    let mut bytes = Vec::new();
+    let mut serializer = rmp_serde::Serializer::new(&mut bytes).with_struct_map().with_human_readable();
+    body.serialize(&mut serializer)?;
+    Ok(bytes)
+}
+
+fn encode_csv<T: Tabular>(body: &T) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in body.rows() {
+        writer.serialize(row)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+type CsvEncoder<T> = fn(&T) -> Result<Vec<u8>, csv::Error>;
+
+/// A handler's response body, serialized in the format the client asked
+/// for, or a 406 listing the endpoint's formats.
+pub struct Negotiated<T> {
+    format: Option<ResponseFormat>,
+    supported: &'static [ResponseFormat],
+    body: T,
+    csv: Option<CsvEncoder<T>>,
+    request_id: Option<String>,
+}
+
+impl<T: Serialize> Negotiated<T> {
+    /// JSON or MessagePack.
+    pub fn new(accept: &Accept, body: T) -> Self {
+        Self {
+            format: accept.negotiate(DOCUMENT_FORMATS),
+            supported: DOCUMENT_FORMATS,
+            body,
+            csv: None,
+            request_id: accept.request_id.clone(),
+        }
+    }
+}
+
+impl<T: Serialize + Tabular> Negotiated<T> {
+    /// JSON, MessagePack or CSV.
+    pub fn table(accept: &Accept, body: T) -> Self {
+        Self {
+            format: accept.negotiate(TABLE_FORMATS),
+            supported: TABLE_FORMATS,
+            body,
+            csv: Some(encode_csv::<T>),
+            request_id: accept.request_id.clone(),
+        }
+    }
+}
+
+/// 406 listing the endpoint's formats.
+fn not_acceptable(supported: &[ResponseFormat], request_id: Option<String>) -> Response {
+    let supported: Vec<&str> = supported.iter().map(|format| format.media_type()).collect();
+    let mut error = ApiError::new(
+        StatusCode::NOT_ACCEPTABLE,
+        "not_acceptable",
+        "None of the media types in Accept can be produced",
+    )
+    .with_detail("supported_types", supported);
+    if let Some(request_id) = request_id {
+        error = error.with_request_id(request_id);
+    }
+    ([(header::VARY, "accept")], error).into_response()
+}
+
+impl<T: Serialize> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Some(format) = self.format else {
+            return not_acceptable(self.supported, self.request_id);
+        };
+        let encoded = match (format, self.csv) {
+            (ResponseFormat::Json, _) => serde_json::to_vec(&self.body).map_err(|e| e.to_string()),
+            (ResponseFormat::MessagePack, _) => encode_msgpack(&self.body).map_err(|e| e.to_string()),
+            (ResponseFormat::Csv, Some(csv)) => csv(&self.body).map_err(|e| e.to_string()),
+            (ResponseFormat::Csv, None) => Err("no CSV encoding for this response".to_string()),
+        };
+        match encoded {
+            Ok(bytes) => Response::builder()
+                .header(header::CONTENT_TYPE, format.content_type())
+                .header(header::VARY, "accept")
+                .body(Body::from(bytes))
+                .unwrap(),
+            Err(e) => {
+                error!(format = format.media_type(), "Failed to serialize response: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
@@ -1,53 +1,56 @@
-use axum::{extract::State, http::StatusCode, response::Json};
-use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::AppState;
+pub use crate::models::users::{CreateUserRequest, CreateUserResponse, ListUsersQuery, User, UserListResponse};
 
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct User {
-    pub id: Uuid,
-    pub username: String,
-    pub email: String,
-    pub created_at: String,
-    pub active: bool,
-}
-
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct CreateUserRequest {
-    pub username: String,
-    pub email: String,
-}
+// Only named in the OpenAPI annotations
+#[allow(unused_imports)]
+use crate::routes::error::ErrorResponse;
+use crate::{
+    middleware::request_id::RequestId,
+    routes::{
+        error::ApiError,
+        negotiation::{Accept, Negotiated, Tabular},
+    },
+    AppState,
+};
 
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct CreateUserResponse {
-    pub user: User,
-    pub message: String,
-}
-
-#[derive(Serialize, Deserialize, ToSchema)]
-pub struct UserListResponse {
-    pub users: Vec<User>,
-    pub total: usize,
-    pub page: usize,
-    pub per_page: usize,
+impl Tabular for UserListResponse {
+    fn rows(&self) -> impl Iterator<Item = impl Serialize + '_> {
+        self.users.iter()
+    }
 }
 
 /// List all users
 ///
-/// Returns a paginated list of all users in the system.
+/// Returns a paginated list of all users in the system. `text/csv` has one
+/// row per user and leaves out the paging fields.
 #[utoipa::path(
     get,
     path = "/api/v1/users",
     tag = "users",
+    params(ListUsersQuery),
     responses(
-        (status = 200, description = "List of users retrieved successfully", body = UserListResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden - insufficient permissions")
-    )
+        (status = 200, description = "List of users retrieved successfully", body = UserListResponse,
+            content_type = ["application/json", "application/msgpack", "text/csv"]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - needs the `reader` or `admin` role", body = ErrorResponse),
+        (status = 406, description = "None of the accepted media types can be produced", body = ErrorResponse),
+        (status = 429, description = "Client over its request rate or concurrency limit", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
 )]
-pub async fn list_users(State(_state): State<AppState>) -> Json<UserListResponse> {
+pub async fn list_users(
+    State(_state): State<AppState>,
+    Query(query): Query<ListUsersQuery>,
+    accept: Accept,
+) -> Negotiated<UserListResponse> {
     // Mock data for demonstration
     let mock_users = vec![
         User {
@@ -66,12 +69,17 @@ pub async fn list_users(State(_state): State<AppState>) -> Json<UserListResponse
         },
     ];
 
-    Json(UserListResponse {
-        total: mock_users.len(),
-        page: 1,
-        per_page: 10,
-        users: mock_users,
-    })
+    let page = query.page.max(1);
+    let per_page = 10;
+    Negotiated::table(
+        &accept,
+        UserListResponse {
+            total: mock_users.len(),
+            page,
+            per_page,
+            users: mock_users.into_iter().skip((page - 1).saturating_mul(per_page)).take(per_page).collect(),
+        },
+    )
 }
 
 /// Create a new user
@@ -83,20 +91,29 @@ pub async fn list_users(State(_state): State<AppState>) -> Json<UserListResponse
     tag = "users",
     request_body = CreateUserRequest,
     responses(
-        (status = 201, description = "User created successfully", body = CreateUserResponse),
-        (status = 400, description = "Invalid request data"),
-        (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden - insufficient permissions"),
-        (status = 409, description = "User already exists")
-    )
+        (status = 201, description = "User created successfully", body = CreateUserResponse,
+            content_type = ["application/json", "application/msgpack"]),
+        (status = 400, description = "Invalid request data", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - needs the `admin` role", body = ErrorResponse),
+        (status = 406, description = "None of the accepted media types can be produced", body = ErrorResponse),
+        (status = 409, description = "User already exists"),
+        (status = 429, description = "Client over its request rate or concurrency limit", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = []))
 )]
 pub async fn create_user(
     State(_state): State<AppState>,
+    accept: Accept,
+    Extension(request_id): Extension<RequestId>,
     Json(payload): Json<CreateUserRequest>,
-) -> Result<Json<CreateUserResponse>, StatusCode> {
+) -> Result<Negotiated<CreateUserResponse>, ApiError> {
     // Basic validation
     if payload.username.is_empty() || payload.email.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(
+            ApiError::new(StatusCode::BAD_REQUEST, "invalid_request", "username and email must not be empty")
+                .with_request_id(request_id.as_str()),
+        );
     }
 
     // Create mock user
@@ -108,9 +125,12 @@ pub async fn create_user(
         active: true,
     };
 
-    Ok(Json(CreateUserResponse {
-        user: new_user,
-        message: "User created successfully".to_string(),
-    }))
+    Ok(Negotiated::new(
+        &accept,
+        CreateUserResponse {
+            user: new_user,
+            message: "User created successfully".to_string(),
+        },
+    ))
 }
 
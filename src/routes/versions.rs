@@ -0,0 +1,63 @@
+use axum::{extract::State, response::Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::VersionSource,
+    middleware::versioning::lifecycle,
+    AppState,
+};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiVersionInfo {
+    pub version: String,
+    /// `stable` or `deprecated`.
+    pub status: String,
+    pub path_prefix: String,
+    pub deprecated: Option<String>,
+    pub sunset: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiVersionsResponse {
+    pub default_version: String,
+    /// Header clients can select a version with.
+    pub header: String,
+    /// Which wins when the path and the header name different versions.
+    pub precedence: VersionSource,
+    pub versions: Vec<ApiVersionInfo>,
+}
+
+/// Supported API versions
+///
+/// Lists the API versions the gateway serves, how to select one, and which
+/// are deprecated or have a sunset date.
+#[utoipa::path(
+    get,
+    path = "/api/versions",
+    tag = "versions",
+    responses(
+        (status = 200, description = "Supported API versions", body = ApiVersionsResponse)
+    )
+)]
+pub async fn list_versions(State(state): State<AppState>) -> Json<ApiVersionsResponse> {
+    let config = state.config_watcher.get_config().await;
+    let versioning = &config.versioning;
+    let base = versioning.base_path.trim_end_matches('/');
+    Json(ApiVersionsResponse {
+        default_version: versioning.default_version.clone(),
+        header: versioning.header.clone(),
+        precedence: versioning.precedence,
+        versions: versioning
+            .versions
+            .iter()
+            .map(|version| ApiVersionInfo {
+                version: version.name.clone(),
+                status: lifecycle(version).to_string(),
+                path_prefix: format!("{}/{}", base, version.name),
+                deprecated: version.deprecated.map(|date| date.to_string()),
+                sunset: version.sunset.map(|date| date.to_string()),
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,203 @@
+//! Fake upstreams for `project-gateway dev` and the tests.
+//!
+//! A `FakeLegacy` answers like the legacy gateway: `/api/v1/users` lists
+//! and creates users from an in-memory store, and any other path echoes
+//! what it was asked. Per-route profiles add latency and inject errors.
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::config::HumanDuration;
+
+/// Header naming the fake that answered.
+pub const SERVED_BY_HEADER: &str = "x-served-by";
+
+/// How a fake answers: after `latency` plus up to `jitter`, and with
+/// `error_status` for `error_rate` percent of requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FakeProfile {
+    pub latency: HumanDuration,
+    pub jitter: HumanDuration,
+    pub error_rate: f64,
+    pub error_status: u16,
+}
+
+impl Default for FakeProfile {
+    fn default() -> Self {
+        Self {
+            latency: HumanDuration::from_millis(0),
+            jitter: HumanDuration::from_millis(0),
+            error_rate: 0.0,
+            error_status: 503,
+        }
+    }
+}
+
+/// A profile for the routes a `[METHOD ]/pattern` selector covers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FakeRoute {
+    pub selector: String,
+    #[serde(flatten)]
+    pub profile: FakeProfile,
+}
+
+/// The default profile, overridden by the first matching route.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FakeUpstreamConfig {
+    #[serde(flatten)]
+    pub profile: FakeProfile,
+    pub routes: Vec<FakeRoute>,
+}
+
+impl FakeUpstreamConfig {
+    pub fn profile_for(&self, method: &str, path: &str) -> &FakeProfile {
+        self.routes
+            .iter()
+            .find(|route| crate::config::route_selector_matches(&route.selector, method, path))
+            .map(|route| &route.profile)
+            .unwrap_or(&self.profile)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedUser {
+    pub username: String,
+    pub email: String,
+}
+
+struct Fake {
+    name: String,
+    config: FakeUpstreamConfig,
+    users: Mutex<Vec<Value>>,
+    requests: AtomicU64,
+}
+
+fn user(username: &str, email: &str) -> Value {
+    json!({
+        "id": uuid::Uuid::new_v4(),
+        "username": username,
+        "email": email,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "active": true,
+    })
+}
+
+/// A fake legacy gateway (or mirror target) on an ephemeral port, stopped
+/// when dropped.
+pub struct FakeLegacy {
+    addr: SocketAddr,
+    fake: Arc<Fake>,
+    server: JoinHandle<()>,
+}
+
+impl FakeLegacy {
+    pub async fn start(name: &str, config: FakeUpstreamConfig, seed_users: &[SeedUser]) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let fake = Arc::new(Fake {
+            name: name.to_string(),
+            config,
+            users: Mutex::new(seed_users.iter().map(|seed| user(&seed.username, &seed.email)).collect()),
+            requests: AtomicU64::new(0),
+        });
+        let app = Router::new().fallback(answer).with_state(fake.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self { addr, fake, server })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.fake.name
+    }
+
+    /// Requests answered so far, injected errors included.
+    pub fn requests(&self) -> u64 {
+        self.fake.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FakeLegacy {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn answer(State(fake): State<Arc<Fake>>, method: Method, uri: Uri, body: Bytes) -> Response {
+    fake.requests.fetch_add(1, Ordering::Relaxed);
+    let profile = fake.config.profile_for(method.as_str(), uri.path());
+    let (delay, failed) = {
+        let mut rng = rand::thread_rng();
+        let jitter = profile.jitter.get().as_millis() as u64;
+        let delay = profile.latency.get() + Duration::from_millis(if jitter > 0 { rng.gen_range(0..=jitter) } else { 0 });
+        (delay, rng.gen_bool((profile.error_rate / 100.0).clamp(0.0, 1.0)))
+    };
+    tokio::time::sleep(delay).await;
+
+    let mut response = if failed {
+        let status = StatusCode::from_u16(profile.error_status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        (status, Json(json!({ "error": "injected failure", "served_by": fake.name }))).into_response()
+    } else {
+        respond(&fake, &method, &uri, &body)
+    };
+    if let Ok(name) = fake.name.parse() {
+        response.headers_mut().insert(SERVED_BY_HEADER, name);
+    }
+    response
+}
+
+fn respond(fake: &Fake, method: &Method, uri: &Uri, body: &[u8]) -> Response {
+    let Ok(mut users) = fake.users.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match (method, uri.path()) {
+        (&Method::GET, "/api/v1/users") => Json(json!({
+            "users": *users,
+            "total": users.len(),
+            "page": 1,
+            "per_page": 10,
+        }))
+        .into_response(),
+        (&Method::POST, "/api/v1/users") => {
+            let request: Value = serde_json::from_slice(body).unwrap_or_default();
+            match (request["username"].as_str(), request["email"].as_str()) {
+                (Some(username), Some(email)) if !username.is_empty() && !email.is_empty() => {
+                    let created = user(username, email);
+                    users.push(created.clone());
+                    (StatusCode::CREATED, Json(json!({ "user": created, "message": "User created successfully" }))).into_response()
+                }
+                _ => StatusCode::BAD_REQUEST.into_response(),
+            }
+        }
+        _ => Json(json!({
+            "served_by": fake.name,
+            "method": method.as_str(),
+            "path": uri.path(),
+        }))
+        .into_response(),
+    }
+}
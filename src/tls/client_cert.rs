@@ -0,0 +1,137 @@
+//! Verified client certificate identity, and its propagation to upstreams.
+
+use anyhow::{Context, Result};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use x509_parser::{extensions::GeneralName, prelude::FromDer};
+
+use crate::config::ClientCertForwarding;
+
+pub const SUBJECT_HEADER: &str = "x-client-cert-subject";
+pub const SAN_HEADER: &str = "x-client-cert-san";
+pub const SERIAL_HEADER: &str = "x-client-cert-serial";
+pub const FINGERPRINT_HEADER: &str = "x-client-cert-fingerprint";
+pub const XFCC_HEADER: &str = "x-forwarded-client-cert";
+
+/// Characters left unescaped in the `Cert` element, as Envoy does.
+const PEM_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// The leaf certificate a client presented and the TLS listener verified
+/// against `server.tls.client_auth`. Stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct ClientCertIdentity {
+    /// Subject DN, e.g. `CN=billing, O=Example`.
+    pub subject: String,
+    /// `DNS:`, `URI:`, `email:` and `IP:` entries in certificate order.
+    pub sans: Vec<String>,
+    /// Serial number in lowercase hex.
+    pub serial: String,
+    /// SHA-256 of the SubjectPublicKeyInfo, lowercase hex.
+    pub spki_sha256: String,
+    /// SHA-256 of the whole certificate, lowercase hex (Envoy's `Hash`).
+    pub cert_sha256: String,
+    pub pem: String,
+    /// Expiry as a Unix timestamp.
+    pub not_after: u64,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl ClientCertIdentity {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (_, cert) = x509_parser::certificate::X509Certificate::from_der(der).context("parsing client certificate")?;
+
+        let mut sans = Vec::new();
+        if let Ok(Some(extension)) = cert.subject_alternative_name() {
+            for name in &extension.value.general_names {
+                match name {
+                    GeneralName::DNSName(dns) => sans.push(format!("DNS:{}", dns)),
+                    GeneralName::URI(uri) => sans.push(format!("URI:{}", uri)),
+                    GeneralName::RFC822Name(email) => sans.push(format!("email:{}", email)),
+                    GeneralName::IPAddress(ip) => match ip.len() {
+                        4 => sans.push(format!("IP:{}", std::net::Ipv4Addr::from(<[u8; 4]>::try_from(*ip)?))),
+                        16 => sans.push(format!("IP:{}", std::net::Ipv6Addr::from(<[u8; 16]>::try_from(*ip)?))),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        let base64 = BASE64.encode(der);
+        let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+        for line in base64.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            sans,
+            serial: cert.raw_serial_as_string().replace(':', ""),
+            spki_sha256: hex(&Sha256::digest(cert.public_key().raw)),
+            cert_sha256: hex(&Sha256::digest(der)),
+            pem,
+            not_after: cert.validity().not_after.timestamp().max(0) as u64,
+        })
+    }
+
+    fn sans_with(&self, prefix: &str) -> impl Iterator<Item = &str> {
+        let prefix = format!("{}:", prefix);
+        self.sans.iter().filter_map(move |san| san.strip_prefix(prefix.as_str()))
+    }
+
+    /// `X-Forwarded-Client-Cert` element in Envoy's format.
+    pub fn xfcc(&self) -> String {
+        let mut element = format!(
+            "Hash={};Cert=\"{}\";Subject=\"{}\"",
+            self.cert_sha256,
+            utf8_percent_encode(&self.pem, PEM_ESCAPE),
+            self.subject.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        for uri in self.sans_with("URI") {
+            element.push_str(&format!(";URI={}", uri));
+        }
+        for dns in self.sans_with("DNS") {
+            element.push_str(&format!(";DNS={}", dns));
+        }
+        element
+    }
+}
+
+/// Whether `name` is one of the identity headers the gateway sets.
+pub fn is_identity_header(name: &HeaderName) -> bool {
+    name.as_str().starts_with("x-client-cert-") || name.as_str() == XFCC_HEADER
+}
+
+/// Replaces whatever identity headers the client sent with the verified
+/// identity in `mode`'s format. With no identity, or forwarding off, the
+/// headers are only stripped.
+pub fn apply(headers: &mut HeaderMap, identity: Option<&ClientCertIdentity>, mode: ClientCertForwarding) {
+    let spoofed: Vec<HeaderName> = headers.keys().filter(|name| is_identity_header(name)).cloned().collect();
+    for name in spoofed {
+        headers.remove(name);
+    }
+
+    let Some(identity) = identity else { return };
+    let values = match mode {
+        ClientCertForwarding::Off => return,
+        ClientCertForwarding::Headers => vec![
+            (SUBJECT_HEADER, identity.subject.clone()),
+            (SAN_HEADER, identity.sans.join(", ")),
+            (SERIAL_HEADER, identity.serial.clone()),
+            (FINGERPRINT_HEADER, format!("sha256:{}", identity.spki_sha256)),
+        ],
+        ClientCertForwarding::Xfcc => vec![(XFCC_HEADER, identity.xfcc())],
+    };
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_bytes(value.as_bytes()) {
+            headers.insert(name, value);
+        }
+    }
+}
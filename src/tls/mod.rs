@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Context, Result};
+use axum::Router;
+use metrics::gauge;
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{danger::ClientCertVerifier, ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{watcher::ConfigWatcher, ClientAuthConfig, TlsConfig},
+    metrics::labels::{label, Dimension},
+};
+
+pub mod client_cert;
+
+use client_cert::ClientCertIdentity;
+
+const DEFAULT_CERTIFICATE: &str = "default";
+
+/// Certificate table consulted on every handshake.
+///
+/// Swapping the table only affects new handshakes; established connections
+/// keep the certificate they negotiated.
+#[derive(Debug, Default)]
+pub struct SniResolver {
+    table: RwLock<ResolverTable>,
+}
+
+#[derive(Debug, Default)]
+struct ResolverTable {
+    by_host: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl SniResolver {
+    /// Exact host match first, then a `*.` wildcard for the parent domain,
+    /// then the default certificate.
+    pub fn lookup(&self, server_name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        let table = self.table.read().ok()?;
+        server_name
+            .map(|name| name.trim_end_matches('.').to_ascii_lowercase())
+            .and_then(|name| {
+                table.by_host.get(&name).or_else(|| {
+                    name.split_once('.')
+                        .and_then(|(_, parent)| table.by_host.get(&format!("*.{}", parent)))
+                })
+            })
+            .or(table.default.as_ref())
+            .cloned()
+    }
+
+    fn replace(&self, table: ResolverTable) {
+        if let Ok(mut current) = self.table.write() {
+            *current = table;
+        }
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.lookup(client_hello.server_name())
+    }
+}
+
+/// Certificate status as reported by `GET /admin/tls`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TlsCertificateInfo {
+    /// `default`, or the first SNI host of the entry.
+    pub name: String,
+    pub sni_hosts: Vec<String>,
+    pub cert_path: String,
+    /// RFC 3339 expiry of the certificate being served.
+    pub not_after: Option<String>,
+    pub days_until_expiry: Option<i64>,
+    pub expiring_soon: bool,
+    /// Last reload failure; the previously loaded certificate stays in use.
+    pub error: Option<String>,
+}
+
+struct LoadedCertificate {
+    name: String,
+    sni_hosts: Vec<String>,
+    cert_path: String,
+    key_path: String,
+    /// Raw PEM contents, compared on refresh to detect changes.
+    source: Option<(Vec<u8>, Vec<u8>)>,
+    key: Option<Arc<CertifiedKey>>,
+    not_after: Option<i64>,
+    error: Option<String>,
+}
+
+/// Loads the configured certificates and keeps the SNI resolver in sync with
+/// the files on disk.
+pub struct TlsManager {
+    provider: Arc<CryptoProvider>,
+    resolver: Arc<SniResolver>,
+    loaded: RwLock<Vec<LoadedCertificate>>,
+    expiry_warning_days: RwLock<u32>,
+    client_verifier: Option<Arc<dyn ClientCertVerifier>>,
+}
+
+impl TlsManager {
+    /// Loads every configured certificate, failing if any of them is unusable.
+    pub fn new(config: &TlsConfig) -> Result<Self> {
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let client_verifier = config
+            .client_auth
+            .as_ref()
+            .map(|client_auth| client_verifier(client_auth, &provider))
+            .transpose()?;
+        let manager = Self {
+            provider,
+            resolver: Arc::new(SniResolver::default()),
+            loaded: RwLock::new(Vec::new()),
+            expiry_warning_days: RwLock::new(config.expiry_warning_days),
+            client_verifier,
+        };
+        manager.refresh(config);
+
+        if let Some(failed) = manager.certificates().into_iter().find(|cert| cert.error.is_some()) {
+            return Err(anyhow!(
+                "TLS certificate {} ({}) failed to load: {}",
+                failed.name,
+                failed.cert_path,
+                failed.error.unwrap_or_default()
+            ));
+        }
+        Ok(manager)
+    }
+
+    /// Reloads entries whose files changed. An entry that fails to load keeps
+    /// serving its previous certificate (or is skipped if it never loaded);
+    /// the other entries are applied regardless.
+    pub fn refresh(&self, config: &TlsConfig) {
+        let Ok(mut loaded) = self.loaded.write() else {
+            return;
+        };
+        let mut previous: Vec<LoadedCertificate> = std::mem::take(&mut *loaded);
+
+        let wanted = std::iter::once((DEFAULT_CERTIFICATE.to_string(), Vec::new(), &config.cert_path, &config.key_path))
+            .chain(config.certificates.iter().map(|entry| {
+                let name = entry.sni_hosts.first().cloned().unwrap_or_else(|| entry.cert_path.clone());
+                (name, entry.sni_hosts.clone(), &entry.cert_path, &entry.key_path)
+            }));
+
+        for (name, sni_hosts, cert_path, key_path) in wanted {
+            let prior = previous
+                .iter()
+                .position(|cert| cert.name == name)
+                .map(|index| previous.swap_remove(index));
+            loaded.push(self.reload_entry(prior, name, sni_hosts, cert_path, key_path));
+        }
+
+        let mut table = ResolverTable::default();
+        for cert in loaded.iter() {
+            let Some(key) = &cert.key else { continue };
+            if cert.name == DEFAULT_CERTIFICATE {
+                table.default = Some(key.clone());
+            }
+            for host in &cert.sni_hosts {
+                table.by_host.insert(host.to_ascii_lowercase(), key.clone());
+            }
+        }
+        self.resolver.replace(table);
+
+        if let Ok(mut days) = self.expiry_warning_days.write() {
+            *days = config.expiry_warning_days;
+        }
+        drop(loaded);
+        self.record_expiry_metrics();
+    }
+
+    fn reload_entry(
+        &self,
+        prior: Option<LoadedCertificate>,
+        name: String,
+        sni_hosts: Vec<String>,
+        cert_path: &str,
+        key_path: &str,
+    ) -> LoadedCertificate {
+        let source = std::fs::read(cert_path)
+            .with_context(|| format!("reading {}", cert_path))
+            .and_then(|cert| Ok((cert, std::fs::read(key_path).with_context(|| format!("reading {}", key_path))?)));
+
+        let same_files = prior
+            .as_ref()
+            .is_some_and(|prior| prior.cert_path == cert_path && prior.key_path == key_path);
+        let unchanged = match (&prior, &source) {
+            (Some(prior), Ok(source)) => same_files && prior.error.is_none() && prior.source.as_ref() == Some(source),
+            _ => false,
+        };
+        let prior = match prior {
+            Some(prior) if unchanged => return LoadedCertificate { sni_hosts, ..prior },
+            prior => prior,
+        };
+
+        match source.and_then(|(cert, key)| self.certified_key(&cert, &key).map(|loaded| (cert, key, loaded))) {
+            Ok((cert, key, (certified, not_after))) => {
+                info!(certificate = %name, cert_path, "Loaded TLS certificate");
+                LoadedCertificate {
+                    name,
+                    sni_hosts,
+                    cert_path: cert_path.to_string(),
+                    key_path: key_path.to_string(),
+                    source: Some((cert, key)),
+                    key: Some(Arc::new(certified)),
+                    not_after: Some(not_after),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                let fallback = prior.filter(|_| same_files);
+                error!(
+                    certificate = %name,
+                    cert_path,
+                    error = %message,
+                    keeping_previous = fallback.as_ref().is_some_and(|prior| prior.key.is_some()),
+                    "Failed to load TLS certificate"
+                );
+                match fallback {
+                    Some(prior) => LoadedCertificate {
+                        sni_hosts,
+                        error: Some(message),
+                        ..prior
+                    },
+                    None => LoadedCertificate {
+                        name,
+                        sni_hosts,
+                        cert_path: cert_path.to_string(),
+                        key_path: key_path.to_string(),
+                        source: None,
+                        key: None,
+                        not_after: None,
+                        error: Some(message),
+                    },
+                }
+            }
+        }
+    }
+
+    fn certified_key(&self, cert_pem: &[u8], key_pem: &[u8]) -> Result<(CertifiedKey, i64)> {
+        let chain = rustls_pemfile::certs(&mut &cert_pem[..])
+            .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+            .context("parsing certificate PEM")?;
+        let leaf = chain.first().ok_or_else(|| anyhow!("no certificate found"))?;
+        let (_, parsed) = x509_parser::parse_x509_certificate(leaf).context("parsing certificate")?;
+        let not_after = parsed.validity().not_after.timestamp();
+
+        let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut &key_pem[..])
+            .context("parsing key PEM")?
+            .ok_or_else(|| anyhow!("no private key found"))?;
+        let certified = CertifiedKey::from_der(chain, key, &self.provider).context("certificate and key do not match")?;
+
+        Ok((certified, not_after))
+    }
+
+    pub fn resolver(&self) -> Arc<SniResolver> {
+        self.resolver.clone()
+    }
+
+    /// Builds an acceptor that resolves certificates through this manager, so
+    /// reloads apply to new handshakes without restarting the listener.
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let builder = rustls::ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_verifier {
+            Some(verifier) => builder.with_client_cert_verifier(verifier.clone()),
+            None => builder.with_no_client_auth(),
+        };
+        let mut server_config = builder.with_cert_resolver(self.resolver.clone());
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(server_config)))
+    }
+
+    pub fn certificates(&self) -> Vec<TlsCertificateInfo> {
+        let warning_days = self.expiry_warning_days.read().map(|days| *days).unwrap_or(30) as i64;
+        let now = chrono::Utc::now().timestamp();
+
+        self.loaded
+            .read()
+            .map(|loaded| {
+                loaded
+                    .iter()
+                    .map(|cert| {
+                        let days_until_expiry = cert.not_after.map(|not_after| (not_after - now).div_euclid(86_400));
+                        TlsCertificateInfo {
+                            name: cert.name.clone(),
+                            sni_hosts: cert.sni_hosts.clone(),
+                            cert_path: cert.cert_path.clone(),
+                            not_after: cert
+                                .not_after
+                                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                                .map(|at| at.to_rfc3339()),
+                            days_until_expiry,
+                            expiring_soon: days_until_expiry.is_some_and(|days| days < warning_days),
+                            error: cert.error.clone(),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn record_expiry_metrics(&self) {
+        for cert in self.certificates() {
+            if let Some(days) = cert.days_until_expiry {
+                gauge!("gateway_tls_certificate_expiry_days", "name" => label(Dimension::Certificate, &cert.name)).set(days as f64);
+            }
+            gauge!("gateway_tls_certificate_expiring_soon", "name" => label(Dimension::Certificate, &cert.name))
+                .set(if cert.expiring_soon { 1.0 } else { 0.0 });
+            if cert.expiring_soon {
+                warn!(
+                    certificate = %cert.name,
+                    days_until_expiry = cert.days_until_expiry,
+                    "TLS certificate expires soon"
+                );
+            }
+        }
+    }
+
+    /// Re-reads certificate files on the configured interval.
+    pub async fn start(self: Arc<Self>, config_watcher: Arc<ConfigWatcher>) {
+        loop {
+            let config = config_watcher.get_config().await;
+            let Some(tls) = config.server.tls.filter(|tls| tls.enabled) else {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                continue;
+            };
+            tokio::time::sleep(Duration::from_secs(tls.reload_interval_seconds.max(1))).await;
+            self.refresh(&tls);
+        }
+    }
+}
+
+/// Verifier for client certificates chaining to `ca_path`.
+fn client_verifier(config: &ClientAuthConfig, provider: &Arc<CryptoProvider>) -> Result<Arc<dyn ClientCertVerifier>> {
+    let pem = std::fs::read(&config.ca_path).with_context(|| format!("reading {}", config.ca_path))?;
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &pem[..]) {
+        roots
+            .add(cert.context("parsing client CA PEM")?)
+            .context("adding client CA certificate")?;
+    }
+    if roots.is_empty() {
+        return Err(anyhow!("no client CA certificate found in {}", config.ca_path));
+    }
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+    let builder = if config.required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    };
+    builder.build().context("building client certificate verifier")
+}
+
+/// Serves `app` over TLS, attaching the peer address as `ConnectInfo` the way
+/// `into_make_service_with_connect_info` does for plain HTTP, until the
+/// process exits.
+pub async fn serve(listener: TcpListener, app: Router, acceptor: TlsAcceptor) -> Result<()> {
+    crate::listener::serve(listener, app, Some(acceptor), std::future::pending()).await;
+    Ok(())
+}
+
+/// The identity in the client certificate the acceptor verified, parsed once
+/// for every request on the connection.
+pub fn peer_identity(
+    stream: &tokio_rustls::server::TlsStream<tokio::net::TcpStream>,
+    remote_addr: SocketAddr,
+) -> Option<Arc<ClientCertIdentity>> {
+    let leaf = stream.get_ref().1.peer_certificates()?.first()?;
+    match ClientCertIdentity::from_der(leaf) {
+        Ok(identity) => Some(Arc::new(identity)),
+        Err(e) => {
+            warn!(remote_addr = %remote_addr, "Ignoring unparseable client certificate: {:#}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Content-Encoding handling for features that look inside bodies.
+//!
+//! Bodies are always relayed as the bytes the upstream sent; decoding here
+//! only produces a private copy for inspection. Decoded output is capped so a
+//! small compressed body can't expand without bound.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use bytes::Bytes;
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
+use tracing::debug;
+
+/// A content coding the gateway can decode and re-encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("unsupported content encoding {0:?}")]
+    Unsupported(String),
+    #[error("decoded body exceeds {0} bytes")]
+    TooLarge(u64),
+    #[error("body is not valid {coding}: {error}")]
+    Corrupt { coding: &'static str, error: std::io::Error },
+}
+
+impl DecodeError {
+    /// Label for `gateway_body_inspection_skipped_total`.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            DecodeError::Unsupported(_) => "unsupported_encoding",
+            DecodeError::TooLarge(_) => "decompression_limit",
+            DecodeError::Corrupt { .. } => "corrupt",
+        }
+    }
+}
+
+impl ContentCoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentCoding::Identity => "identity",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+            ContentCoding::Brotli => "br",
+        }
+    }
+
+    /// The coding `headers` declare. Stacked codings (`gzip, br`) and ones
+    /// the gateway can't decode, such as `zstd`, are unsupported.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, DecodeError> {
+        let mut declared = Vec::new();
+        for value in headers.get_all(header::CONTENT_ENCODING) {
+            declared.extend(
+                String::from_utf8_lossy(value.as_bytes())
+                    .split(',')
+                    .map(|coding| coding.trim().to_ascii_lowercase())
+                    .filter(|coding| !coding.is_empty() && coding != "identity"),
+            );
+        }
+        match declared.as_slice() {
+            [] => Ok(ContentCoding::Identity),
+            [coding] => match coding.as_str() {
+                "gzip" | "x-gzip" => Ok(ContentCoding::Gzip),
+                "deflate" => Ok(ContentCoding::Deflate),
+                "br" => Ok(ContentCoding::Brotli),
+                _ => Err(DecodeError::Unsupported(coding.clone())),
+            },
+            _ => Err(DecodeError::Unsupported(declared.join(", "))),
+        }
+    }
+
+    /// Decodes `body`, failing with `TooLarge` as soon as the output passes
+    /// `limit` bytes. Identity bodies are borrowed as they are.
+    pub fn decode<'a>(&self, body: &'a [u8], limit: u64) -> Result<Cow<'a, [u8]>, DecodeError> {
+        let reader: Box<dyn Read + '_> = match self {
+            ContentCoding::Identity => return Ok(Cow::Borrowed(body)),
+            ContentCoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(body)),
+            ContentCoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(body)),
+            ContentCoding::Brotli => Box::new(brotli::Decompressor::new(body, 4096)),
+        };
+
+        let mut decoded = Vec::new();
+        reader
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut decoded)
+            .map_err(|error| DecodeError::Corrupt {
+                coding: self.as_str(),
+                error,
+            })?;
+        if decoded.len() as u64 > limit {
+            return Err(DecodeError::TooLarge(limit));
+        }
+        Ok(Cow::Owned(decoded))
+    }
+
+    pub fn encode(&self, body: &[u8]) -> Vec<u8> {
+        // Writes into a Vec can't fail
+        match self {
+            ContentCoding::Identity => body.to_vec(),
+            ContentCoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                let _ = encoder.write_all(body);
+                encoder.finish().unwrap_or_default()
+            }
+            ContentCoding::Deflate => {
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                let _ = encoder.write_all(body);
+                encoder.finish().unwrap_or_default()
+            }
+            ContentCoding::Brotli => {
+                let mut encoded = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                    let _ = encoder.write_all(body);
+                }
+                encoded
+            }
+        }
+    }
+}
+
+/// Decodes `body` for `feature` to look at. When it can't be decoded the
+/// feature skips inspection: the skip is counted and `None` returned.
+pub fn inspect<'a>(feature: &'static str, headers: &HeaderMap, body: &'a [u8], limit: u64) -> Option<Cow<'a, [u8]>> {
+    match ContentCoding::from_headers(headers).and_then(|coding| coding.decode(body, limit)) {
+        Ok(decoded) => Some(decoded),
+        Err(e) => {
+            crate::metrics::record_inspection_skipped(feature, e.reason());
+            debug!(feature = feature, error = %e, "Skipping body inspection");
+            None
+        }
+    }
+}
+
+/// Lets a feature rewrite a body in its decoded form. The result is
+/// re-encoded with the body's original coding, and `Content-Length` is
+/// updated to match; `Content-Encoding` is left as it was.
+pub fn rewrite(
+    headers: &mut HeaderMap,
+    body: &[u8],
+    limit: u64,
+    transform: impl FnOnce(Vec<u8>) -> Vec<u8>,
+) -> Result<Bytes, DecodeError> {
+    let coding = ContentCoding::from_headers(headers)?;
+    let decoded = coding.decode(body, limit)?.into_owned();
+    let encoded = Bytes::from(coding.encode(&transform(decoded)));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(encoded.len()));
+    Ok(encoded)
+}
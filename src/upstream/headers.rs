@@ -0,0 +1,109 @@
+//! The User-Agent and headers every outbound request carries.
+//!
+//! `http_client.user_agent` and `default_headers` are set on the shared
+//! client when it is built; `upstream_headers` are added per request by
+//! [`super::UpstreamPool::request`]. Inbound copies of any of these are
+//! stripped before proxying or mirroring, so clients can't override them.
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+
+use crate::config::HttpClientConfig;
+
+/// Kinds of upstream the gateway calls; each may have its own
+/// `http_client.upstream_headers` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Upstream {
+    Legacy,
+    Mirror,
+    Webhooks,
+    Jwks,
+    Introspection,
+    TimeSource,
+}
+
+impl Upstream {
+    pub const ALL: [Upstream; 6] = [
+        Upstream::Legacy,
+        Upstream::Mirror,
+        Upstream::Webhooks,
+        Upstream::Jwks,
+        Upstream::Introspection,
+        Upstream::TimeSource,
+    ];
+
+    /// Key in `http_client.upstream_headers`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Upstream::Legacy => "legacy",
+            Upstream::Mirror => "mirror",
+            Upstream::Webhooks => "webhooks",
+            Upstream::Jwks => "jwks",
+            Upstream::Introspection => "introspection",
+            Upstream::TimeSource => "time_source",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|upstream| upstream.as_str() == name)
+    }
+}
+
+/// `template` with `{version}` and `{instance_id}` filled in.
+pub fn render_user_agent(template: &str, instance_id: &str) -> String {
+    template
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace("{instance_id}", instance_id)
+}
+
+/// Parses a configured header map; `User-Agent` has its own setting.
+pub fn header_map(headers: &BTreeMap<String, String>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::try_from(name.as_str()).with_context(|| format!("invalid header name {:?}", name))?;
+        if name == reqwest::header::USER_AGENT {
+            anyhow::bail!("set the User-Agent with http_client.user_agent");
+        }
+        let value = HeaderValue::try_from(value.as_str()).with_context(|| format!("invalid value for {}", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// What the shared client sends on its own behalf.
+pub(super) struct OutboundHeaders {
+    pub user_agent: HeaderValue,
+    pub defaults: HeaderMap,
+    pub per_upstream: Vec<(Upstream, HeaderMap)>,
+    /// Every header name above, which inbound requests may not set.
+    pub reserved: Vec<HeaderName>,
+}
+
+impl OutboundHeaders {
+    pub fn from_config(config: &HttpClientConfig, instance_id: &str) -> Result<Self> {
+        let user_agent = HeaderValue::try_from(render_user_agent(&config.user_agent, instance_id))
+            .context("invalid http_client.user_agent")?;
+        let defaults = header_map(&config.default_headers).context("invalid http_client.default_headers")?;
+        let mut per_upstream = Vec::new();
+        for (name, headers) in &config.upstream_headers {
+            let upstream = Upstream::from_name(name)
+                .with_context(|| format!("unknown upstream {:?} in http_client.upstream_headers", name))?;
+            let headers = header_map(headers).with_context(|| format!("invalid http_client.upstream_headers.{}", name))?;
+            per_upstream.push((upstream, headers));
+        }
+
+        let mut reserved = vec![reqwest::header::USER_AGENT];
+        for name in defaults.keys().chain(per_upstream.iter().flat_map(|(_, headers)| headers.keys())) {
+            if !reserved.contains(name) {
+                reserved.push(name.clone());
+            }
+        }
+        Ok(Self {
+            user_agent,
+            defaults,
+            per_upstream,
+            reserved,
+        })
+    }
+}
@@ -0,0 +1,182 @@
+//! Health probes of the legacy gateway and mirror target.
+//!
+//! Each upstream is probed with `HEAD` on `http_client.health_check.path`.
+//! Some older legacy instances answer `HEAD` with 405; such an upstream is
+//! remembered as refusing it and probed from then on with a one-byte ranged
+//! `GET`, of which at most [`MAX_PROBE_BODY`] bytes are read. Probes carry
+//! the configured User-Agent plus [`PROBE_USER_AGENT_SUFFIX`] and the [`SYNTHETIC_HEADER`] marker so the upstream
+//! can leave them out of its analytics, and go straight to the upstream
+//! rather than through the canary forwarder, so the rollout monitor never
+//! sees them.
+
+use axum::http::{header, StatusCode};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info};
+
+use super::{Upstream, UpstreamPool};
+use crate::{config::AppConfig, AppState};
+
+pub use crate::models::health::{ProbeMethod, UpstreamProbe};
+
+/// Appended to `http_client.user_agent` on probes.
+pub const PROBE_USER_AGENT_SUFFIX: &str = "health-probe";
+/// Marks requests the gateway makes on its own behalf.
+pub const SYNTHETIC_HEADER: &str = "x-synthetic-traffic";
+/// Most of a `GET` probe's body that is read before the response is dropped.
+pub const MAX_PROBE_BODY: usize = 1024;
+
+impl ProbeMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProbeMethod::Head => "head",
+            ProbeMethod::Get => "get",
+        }
+    }
+}
+
+/// Upstreams to probe and their probe URLs.
+pub fn targets(config: &AppConfig) -> Vec<(Upstream, String)> {
+    let path = &config.http_client.health_check.path;
+    let mut targets = Vec::new();
+    let canary = &config.canary_rollout;
+    if canary.enabled && canary.legacy_gateway_url.starts_with("http") {
+        targets.push((Upstream::Legacy, format!("{}{}", canary.legacy_gateway_url.trim_end_matches('/'), path)));
+    }
+    if config.mirror.enabled && config.mirror.base_url.starts_with("http") {
+        targets.push((Upstream::Mirror, format!("{}{}", config.mirror.base_url.trim_end_matches('/'), path)));
+    }
+    targets
+}
+
+/// Whether a probe's status means the upstream is up. A ranged `GET` of an
+/// empty body may get 416.
+fn is_healthy(status: StatusCode) -> bool {
+    status.is_success() || status.is_redirection() || status == StatusCode::RANGE_NOT_SATISFIABLE
+}
+
+async fn send(
+    upstreams: &UpstreamPool,
+    kind: Upstream,
+    url: &str,
+    method: ProbeMethod,
+    timeout: Duration,
+) -> reqwest::Result<StatusCode> {
+    let request = match method {
+        ProbeMethod::Head => upstreams.request(kind, reqwest::Method::HEAD, url),
+        ProbeMethod::Get => upstreams.request(kind, reqwest::Method::GET, url).header(header::RANGE, "bytes=0-0"),
+    };
+    let mut response = request
+        .header(header::USER_AGENT, format!("{} {}", upstreams.user_agent(), PROBE_USER_AGENT_SUFFIX))
+        .header(SYNTHETIC_HEADER, "health-probe")
+        .timeout(timeout)
+        .send()
+        .await?;
+    let status = response.status();
+    // Upstreams ignoring the range still send the whole body; stop early
+    let mut read = 0;
+    while read < MAX_PROBE_BODY {
+        match response.chunk().await? {
+            Some(chunk) => read += chunk.len(),
+            None => break,
+        }
+    }
+    Ok(status)
+}
+
+/// Latest probe results by probe URL, and which upstreams refuse `HEAD`.
+#[derive(Default)]
+pub struct UpstreamHealth {
+    probes: RwLock<HashMap<String, UpstreamProbe>>,
+}
+
+impl UpstreamHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Probes every upstream once.
+    pub async fn check_once(&self, upstreams: &UpstreamPool, config: &AppConfig) -> Vec<UpstreamProbe> {
+        let timeout = config.http_client.health_check.timeout.get();
+        let mut results = Vec::new();
+        for (kind, url) in targets(config) {
+            let upstream = super::upstream_key(&url);
+            let mut head_supported = self
+                .probes
+                .read()
+                .unwrap()
+                .get(&url)
+                .is_none_or(|previous| previous.head_supported);
+
+            let mut method = if head_supported { ProbeMethod::Head } else { ProbeMethod::Get };
+            let mut started = Instant::now();
+            let mut outcome = send(upstreams, kind, &url, method, timeout).await;
+            if method == ProbeMethod::Head
+                && matches!(outcome, Ok(StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED))
+            {
+                info!(upstream = %upstream, "Upstream refuses HEAD health probes; probing with GET from now on");
+                head_supported = false;
+                method = ProbeMethod::Get;
+                started = Instant::now();
+                outcome = send(upstreams, kind, &url, method, timeout).await;
+            }
+            let latency = started.elapsed();
+
+            let healthy = outcome.as_ref().is_ok_and(|status| is_healthy(*status));
+            debug!(upstream = %upstream, method = method.as_str(), healthy, latency_ms = latency.as_millis(), "Probed upstream");
+            crate::metrics::record_upstream_probe(kind.as_str(), method.as_str(), healthy, latency);
+            let probe = UpstreamProbe {
+                name: kind.as_str().to_string(),
+                upstream,
+                url,
+                healthy,
+                method,
+                head_supported,
+                status: outcome.as_ref().ok().map(|status| status.as_u16()),
+                latency_ms: latency.as_secs_f64() * 1000.0,
+                error: outcome.err().map(|e| e.to_string()),
+                checked_at: chrono::Utc::now().to_rfc3339(),
+            };
+            self.probes.write().unwrap().insert(probe.url.clone(), probe.clone());
+            results.push(probe);
+        }
+        results
+    }
+
+    /// Latest results for the upstreams `config` names, in probe order.
+    pub fn report(&self, config: &AppConfig) -> Vec<UpstreamProbe> {
+        let probes = self.probes.read().unwrap();
+        targets(config)
+            .into_iter()
+            .filter_map(|(_, url)| probes.get(&url).cloned())
+            .collect()
+    }
+
+    /// Every probe result held, whatever the config names now.
+    pub fn probes(&self) -> Vec<UpstreamProbe> {
+        self.probes.read().unwrap().values().cloned().collect()
+    }
+
+    /// Takes over results exported by another instance for probe URLs this
+    /// one hasn't probed yet; its own results are fresher.
+    pub fn restore(&self, probes: &[UpstreamProbe]) {
+        let mut held = self.probes.write().unwrap();
+        for probe in probes {
+            held.entry(probe.url.clone()).or_insert_with(|| probe.clone());
+        }
+    }
+
+    /// Probes every `http_client.health_check.interval` while enabled.
+    pub async fn start(self: Arc<Self>, state: AppState) {
+        loop {
+            let config = state.config_watcher.get_config().await;
+            if config.http_client.health_check.enabled {
+                self.check_once(&state.upstreams, &config).await;
+            }
+            tokio::time::sleep(config.http_client.health_check.interval.get()).await;
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use metrics::{gauge, histogram};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use utoipa::ToSchema;
+
+use crate::{
+    config::HttpClientConfig,
+    metrics::labels::{label, Dimension},
+};
+use headers::OutboundHeaders;
+pub use headers::Upstream;
+
+pub mod encoding;
+pub mod headers;
+pub mod health;
+pub mod prewarm;
+pub mod proxy;
+pub mod validation;
+
+/// Shared outbound HTTP client plus a thin connection-tracking layer.
+///
+/// Every outbound call (canary proxy, mirror, contract checks, webhooks) goes
+/// through this client so proxy settings apply uniformly.
+///
+/// reqwest doesn't expose its pool internals, so each upstream host gets a
+/// semaphore sized to the configured connection limit. Acquiring a permit
+/// models connection acquisition: time spent waiting for one is recorded
+/// separately from the upstream's own response time.
+pub struct UpstreamPool {
+    client: reqwest::Client,
+    outbound_headers: OutboundHeaders,
+    max_connections_per_host: usize,
+    hosts: RwLock<HashMap<String, Arc<HostPool>>>,
+}
+
+struct HostPool {
+    upstream: String,
+    semaphore: Arc<Semaphore>,
+    in_use: AtomicUsize,
+    pending: AtomicUsize,
+    /// High-water mark of concurrent connections, i.e. connections the pool
+    /// has opened and may keep idle.
+    opened: AtomicUsize,
+}
+
+impl HostPool {
+    fn idle(&self) -> usize {
+        self.opened
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.in_use.load(Ordering::Relaxed))
+    }
+
+    fn publish(&self) {
+        let upstream = label(Dimension::Upstream, &self.upstream);
+        gauge!("gateway_upstream_pool_in_use", "upstream" => upstream.clone())
+            .set(self.in_use.load(Ordering::Relaxed) as f64);
+        gauge!("gateway_upstream_pool_pending", "upstream" => upstream.clone())
+            .set(self.pending.load(Ordering::Relaxed) as f64);
+        gauge!("gateway_upstream_pool_idle", "upstream" => upstream).set(self.idle() as f64);
+    }
+}
+
+struct PendingGuard<'a>(&'a HostPool);
+
+impl<'a> PendingGuard<'a> {
+    fn new(host: &'a HostPool) -> Self {
+        host.pending.fetch_add(1, Ordering::Relaxed);
+        host.publish();
+        Self(host)
+    }
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::Relaxed);
+        self.0.publish();
+    }
+}
+
+/// A held upstream connection slot, released on drop.
+pub struct PoolPermit {
+    host: Arc<HostPool>,
+    _permit: OwnedSemaphorePermit,
+    /// Time spent waiting for the slot.
+    pub wait: Duration,
+}
+
+impl Drop for PoolPermit {
+    fn drop(&mut self) {
+        self.host.in_use.fetch_sub(1, Ordering::Relaxed);
+        self.host.publish();
+    }
+}
+
+/// What [`UpstreamPool::prewarm`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prewarmed {
+    pub probes: usize,
+    /// Connections known to be open: those in use plus probes answered.
+    pub warm: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamPoolStats {
+    pub upstream: String,
+    pub max_connections: usize,
+    pub in_use: usize,
+    pub idle: usize,
+    pub pending: usize,
+}
+
+impl UpstreamPool {
+    pub fn new(config: &HttpClientConfig) -> Self {
+        let outbound_headers = OutboundHeaders::from_config(config, &crate::coordination::instance_id())
+            .expect("invalid http_client headers");
+        let mut builder = reqwest::Client::builder()
+            .pool_max_idle_per_host(config.max_idle_per_host.unwrap_or(config.max_connections_per_host))
+            .pool_idle_timeout(config.pool_idle_timeout.get())
+            .connect_timeout(config.connect_timeout.get())
+            .user_agent(outbound_headers.user_agent.clone())
+            .default_headers(outbound_headers.defaults.clone());
+        if let Some(proxy_config) = &config.proxy {
+            builder = proxy::configure(builder, proxy_config).expect("invalid http_client.proxy configuration");
+        }
+        let client = builder.build().expect("failed to build upstream HTTP client");
+
+        Self {
+            client,
+            outbound_headers,
+            max_connections_per_host: config.max_connections_per_host.max(1),
+            hosts: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// A request to a `kind` upstream, carrying its `upstream_headers` on top
+    /// of the client's defaults.
+    pub fn request(&self, kind: Upstream, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        match self.outbound_headers.per_upstream.iter().find(|(upstream, _)| *upstream == kind) {
+            Some((_, headers)) => request.headers(headers.clone()),
+            None => request,
+        }
+    }
+
+    /// The rendered `http_client.user_agent`.
+    pub fn user_agent(&self) -> &str {
+        self.outbound_headers.user_agent.to_str().unwrap_or_default()
+    }
+
+    /// Drops inbound copies of the headers the gateway sets itself, so a
+    /// client can't override them on proxied or mirrored requests.
+    pub fn strip_outbound_headers(&self, headers: &mut reqwest::header::HeaderMap) {
+        for name in &self.outbound_headers.reserved {
+            headers.remove(name);
+        }
+    }
+
+    /// Waits up to `queue_timeout` for a connection slot, returning `None`
+    /// when the upstream stays saturated for longer than that.
+    pub async fn acquire_timeout(&self, url: &str, queue_timeout: Duration) -> Option<PoolPermit> {
+        tokio::time::timeout(queue_timeout, self.acquire(url)).await.ok()
+    }
+
+    /// Waits for a connection slot to the upstream serving `url`.
+    pub async fn acquire(&self, url: &str) -> PoolPermit {
+        let host = self.host_pool(&upstream_key(url));
+        let start = Instant::now();
+
+        // Dropped on admission or when the caller gives up waiting
+        let pending = PendingGuard::new(&host);
+        let permit = host
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("upstream pool semaphore is never closed");
+        drop(pending);
+
+        let in_use = host.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        host.opened.fetch_max(in_use, Ordering::Relaxed);
+        host.publish();
+
+        let wait = start.elapsed();
+        histogram!("gateway_upstream_pool_wait_seconds", "upstream" => label(Dimension::Upstream, &host.upstream))
+            .record(wait.as_secs_f64());
+
+        PoolPermit {
+            host,
+            _permit: permit,
+            wait,
+        }
+    }
+
+    /// Opens a connection to the upstream serving `url` and leaves it idle in
+    /// the pool, so the first proxied request doesn't pay for TCP and TLS
+    /// setup. Any response status will do.
+    pub async fn warm(&self, url: &str, timeout: Duration) -> reqwest::Result<()> {
+        let _permit = self.acquire(url).await;
+        self.client.head(upstream_key(url)).timeout(timeout).send().await?;
+        Ok(())
+    }
+
+    /// Tops the upstream serving `url` up to `target` open connections by
+    /// sending concurrent `HEAD` probes through the pool, at most `budget`
+    /// of them. Connections in use already count as warm, and probes only
+    /// take free slots, so live requests never wait behind them. Probes skip
+    /// the pool wait histogram and the performance monitor.
+    pub async fn prewarm(&self, url: &str, target: usize, budget: usize, timeout: Duration) -> Prewarmed {
+        let host = self.host_pool(&upstream_key(url));
+        let in_use = host.in_use.load(Ordering::Relaxed);
+        let probes = target.saturating_sub(in_use).min(budget);
+
+        let mut permits = Vec::with_capacity(probes);
+        while permits.len() < probes {
+            let Ok(permit) = host.semaphore.clone().try_acquire_owned() else {
+                break;
+            };
+            let in_use = host.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+            host.opened.fetch_max(in_use, Ordering::Relaxed);
+            permits.push(PoolPermit {
+                host: host.clone(),
+                _permit: permit,
+                wait: Duration::ZERO,
+            });
+        }
+        host.publish();
+
+        let probe = |_| self.client.head(&host.upstream).timeout(timeout).send();
+        let results = futures::future::join_all(permits.iter().map(probe)).await;
+        drop(permits);
+        let warm = in_use + results.iter().filter(|result| result.is_ok()).count();
+        gauge!("gateway_upstream_warm_connections", "upstream" => label(Dimension::Upstream, &host.upstream)).set(warm as f64);
+        Prewarmed {
+            probes: results.len(),
+            warm,
+        }
+    }
+
+    pub fn stats(&self) -> Vec<UpstreamPoolStats> {
+        let hosts = match self.hosts.read() {
+            Ok(hosts) => hosts,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut stats: Vec<UpstreamPoolStats> = hosts
+            .values()
+            .map(|host| UpstreamPoolStats {
+                upstream: host.upstream.clone(),
+                max_connections: self.max_connections_per_host,
+                in_use: host.in_use.load(Ordering::Relaxed),
+                idle: host.idle(),
+                pending: host.pending.load(Ordering::Relaxed),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.upstream.cmp(&b.upstream));
+        stats
+    }
+
+    fn host_pool(&self, upstream: &str) -> Arc<HostPool> {
+        if let Some(host) = self.hosts.read().ok().and_then(|hosts| hosts.get(upstream).cloned()) {
+            return host;
+        }
+
+        let mut hosts = self.hosts.write().expect("upstream pool lock poisoned");
+        hosts
+            .entry(upstream.to_string())
+            .or_insert_with(|| {
+                Arc::new(HostPool {
+                    upstream: upstream.to_string(),
+                    semaphore: Arc::new(Semaphore::new(self.max_connections_per_host)),
+                    in_use: AtomicUsize::new(0),
+                    pending: AtomicUsize::new(0),
+                    opened: AtomicUsize::new(0),
+                })
+            })
+            .clone()
+    }
+}
+
+/// `scheme://host:port` of a URL, used as the pool key and metric label.
+pub fn upstream_key(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => format!(
+            "{}://{}:{}",
+            parsed.scheme(),
+            parsed.host_str().unwrap_or_default(),
+            parsed.port_or_known_default().unwrap_or_default()
+        ),
+        Err(_) => "unknown".to_string(),
+    }
+}
@@ -0,0 +1,83 @@
+//! Keeps connections to the legacy gateway and mirror target warm.
+//!
+//! As the rollout advances, legacy carries less traffic and its pooled
+//! connections idle out, so a rollback would open a burst of new ones at the
+//! worst moment. Each round tops every upstream up to its target with `HEAD`
+//! probes. The legacy target grows with the rollout percentage, leaving
+//! headroom for the traffic a rollback would send back.
+
+use std::time::Duration;
+use tracing::debug;
+
+use crate::{config::AppConfig, AppState};
+
+/// How long a probe may take before its connection is given up on.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One upstream's outcome in a pre-warm round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrewarmResult {
+    pub upstream: String,
+    pub target: usize,
+    pub probes: usize,
+    pub warm: usize,
+}
+
+/// Upstreams to keep warm and how many connections each should have, legacy
+/// first so it gets the probe budget ahead of the mirror target.
+pub fn targets(config: &AppConfig) -> Vec<(String, usize)> {
+    let prewarm = &config.http_client.prewarm;
+    let cap = config.http_client.max_connections_per_host.max(1);
+    let mut targets = Vec::new();
+    let canary = &config.canary_rollout;
+    if canary.enabled && canary.legacy_gateway_url.starts_with("http") {
+        let target = prewarm.legacy_target(canary.rollout_percentage).min(cap);
+        targets.push((super::upstream_key(&canary.legacy_gateway_url), target));
+    }
+    if config.mirror.enabled && config.mirror.base_url.starts_with("http") {
+        let upstream = super::upstream_key(&config.mirror.base_url);
+        if !targets.iter().any(|(legacy, _)| *legacy == upstream) {
+            targets.push((upstream, prewarm.min_connections.min(cap)));
+        }
+    }
+    targets
+}
+
+/// Runs one pre-warm round, spending at most the configured probe budget.
+pub async fn prewarm_once(state: &AppState, config: &AppConfig) -> Vec<PrewarmResult> {
+    let mut budget = config.http_client.prewarm.probes_per_round();
+    let mut results = Vec::new();
+    for (upstream, target) in targets(config) {
+        let prewarmed = state.upstreams.prewarm(&upstream, target, budget, PROBE_TIMEOUT).await;
+        budget -= prewarmed.probes;
+        debug!(upstream = %upstream, target, probes = prewarmed.probes, warm = prewarmed.warm, "Pre-warmed upstream connections");
+        results.push(PrewarmResult {
+            upstream,
+            target,
+            probes: prewarmed.probes,
+            warm: prewarmed.warm,
+        });
+    }
+    results
+}
+
+/// Pre-warms every `http_client.prewarm.interval` while enabled, and at once
+/// after a config reload so a rollout advance is covered straight away.
+pub async fn start(state: AppState) {
+    let mut reloads = state.config_watcher.subscribe_to_reloads();
+    loop {
+        let config = state.config_watcher.get_config().await;
+        if config.http_client.prewarm.enabled {
+            prewarm_once(&state, &config).await;
+        }
+
+        tokio::select! {
+            reload = reloads.recv() => {
+                if let Err(tokio::sync::broadcast::error::RecvError::Closed) = reload {
+                    break;
+                }
+            }
+            _ = tokio::time::sleep(config.http_client.prewarm.interval.get()) => {}
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Proxy, Url};
+use std::net::IpAddr;
+
+use crate::config::ProxyConfig;
+
+#[derive(Debug, Clone, PartialEq)]
+enum NoProxyRule {
+    All,
+    Network { addr: IpAddr, prefix: u8 },
+    /// Lowercased domain without a leading dot; matches itself and subdomains.
+    Domain(String),
+}
+
+/// Hosts that bypass the proxy.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoProxy {
+    rules: Vec<NoProxyRule>,
+}
+
+impl NoProxy {
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let rules = entries
+            .iter()
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+            .map(parse_rule)
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let bare = host.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).unwrap_or(host);
+        let host = match bare.parse::<IpAddr>() {
+            Ok(addr) => Host::Ip(addr),
+            Err(_) => Host::Domain(bare.trim_end_matches('.').to_ascii_lowercase()),
+        };
+
+        self.rules.iter().any(|rule| match (rule, &host) {
+            (NoProxyRule::All, _) => true,
+            (NoProxyRule::Network { addr, prefix }, Host::Ip(ip)) => in_network(*ip, *addr, *prefix),
+            (NoProxyRule::Domain(domain), Host::Domain(name)) => {
+                name == domain || name.strip_suffix(domain.as_str()).is_some_and(|rest| rest.ends_with('.'))
+            }
+            _ => false,
+        })
+    }
+}
+
+enum Host {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+fn parse_rule(entry: &str) -> Result<NoProxyRule> {
+    if entry == "*" {
+        return Ok(NoProxyRule::All);
+    }
+
+    if let Some((addr, prefix)) = entry.split_once('/') {
+        let addr: IpAddr = addr.parse().with_context(|| format!("invalid no_proxy network {:?}", entry))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix: u8 = prefix
+            .parse()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(|| anyhow!("invalid prefix length in no_proxy entry {:?}", entry))?;
+        return Ok(NoProxyRule::Network { addr, prefix });
+    }
+
+    let bare = entry.trim_start_matches("*.").trim_start_matches('.');
+    let bare = bare.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).unwrap_or(bare);
+    if let Ok(addr) = bare.parse::<IpAddr>() {
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        return Ok(NoProxyRule::Network { addr, prefix });
+    }
+    if bare.is_empty() || bare.contains(['/', ':', ' ']) {
+        return Err(anyhow!("invalid no_proxy entry {:?}", entry));
+    }
+    Ok(NoProxyRule::Domain(bare.trim_end_matches('.').to_ascii_lowercase()))
+}
+
+pub(crate) fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// The proxy password, read from `password_file` when one is configured.
+pub fn password(config: &ProxyConfig) -> Result<Option<String>> {
+    match &config.password_file {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|contents| Some(contents.trim_end_matches(['\r', '\n']).to_string()))
+            .with_context(|| format!("reading proxy password file {}", path)),
+        None => Ok(config.password.clone()),
+    }
+}
+
+/// Routes every request through the configured proxy except those matching
+/// `no_proxy`. Replaces reqwest's environment-variable proxy detection so all
+/// outbound traffic follows the same rules.
+pub fn configure(builder: reqwest::ClientBuilder, config: &ProxyConfig) -> Result<reqwest::ClientBuilder> {
+    let proxy_url = Url::parse(&config.url).with_context(|| format!("invalid proxy url {:?}", config.url))?;
+    if !matches!(proxy_url.scheme(), "http" | "https") {
+        return Err(anyhow!("proxy url must be http or https, got {:?}", config.url));
+    }
+    let no_proxy = NoProxy::parse(&config.no_proxy)?;
+
+    let target = proxy_url.clone();
+    let mut proxy = Proxy::custom(move |url| (!no_proxy.matches(url)).then(|| target.clone()));
+    if let Some(username) = &config.username {
+        proxy = proxy.basic_auth(username, password(config)?.as_deref().unwrap_or_default());
+    }
+
+    Ok(builder.no_proxy().proxy(proxy))
+}
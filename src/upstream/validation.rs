@@ -0,0 +1,132 @@
+use bytes::{Bytes, BytesMut};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use serde::de::IgnoredAny;
+
+use super::encoding;
+use crate::config::RouteConfig;
+
+/// Strict JSON parsing only applies to bodies that decode to at most this
+/// size; larger ones are relayed without being parsed.
+pub const STRICT_JSON_MAX_BYTES: usize = 1024 * 1024;
+/// How much of an offending body is kept for the log.
+const EXCERPT_BYTES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    ContentType,
+    TooLarge,
+    MalformedJson,
+}
+
+impl ViolationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ViolationKind::ContentType => "content_type",
+            ViolationKind::TooLarge => "too_large",
+            ViolationKind::MalformedJson => "malformed_json",
+        }
+    }
+}
+
+/// An upstream response that broke its route's expectations.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub detail: String,
+    /// Start of the offending body, for the log.
+    pub excerpt: Option<String>,
+}
+
+fn excerpt(body: &[u8]) -> String {
+    String::from_utf8_lossy(&body[..body.len().min(EXCERPT_BYTES)]).into_owned()
+}
+
+/// Whether `content_type` matches one of `expected`; entries may end in `/*`.
+/// Parameters such as `charset` are ignored.
+pub fn content_type_matches(expected: &[String], content_type: Option<&str>) -> bool {
+    let Some(media_type) = content_type.and_then(|value| value.split(';').next()).map(str::trim) else {
+        return false;
+    };
+    expected.iter().any(|expected| match expected.strip_suffix("/*") {
+        Some(prefix) => media_type
+            .split_once('/')
+            .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(prefix)),
+        None => media_type.eq_ignore_ascii_case(expected),
+    })
+}
+
+/// Describes a content-type mismatch, or `None` when the type is acceptable.
+fn content_type_mismatch(route: &RouteConfig, headers: &HeaderMap) -> Option<String> {
+    if route.expected_content_types.is_empty() {
+        return None;
+    }
+    let content_type = headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    (!content_type_matches(&route.expected_content_types, content_type)).then(|| {
+        format!(
+            "content type {:?} is not one of {:?}",
+            content_type.unwrap_or("(none)"),
+            route.expected_content_types
+        )
+    })
+}
+
+fn too_large(limit: u64) -> Violation {
+    Violation {
+        kind: ViolationKind::TooLarge,
+        detail: format!("response body exceeds {} bytes", limit),
+        excerpt: None,
+    }
+}
+
+/// Reads `response` while enforcing `route`'s expectations. The outer error
+/// is a transport failure; the inner one a contract violation. A body that
+/// outgrows `max_response_bytes` stops being read as soon as it does, and
+/// dropping the response aborts the upstream stream.
+///
+/// `max_response_bytes` applies to the bytes on the wire. Compressed bodies
+/// are decoded, up to `max_decoded` bytes, only to be parsed; the bytes
+/// returned are always the ones the upstream sent.
+pub async fn read_validated(
+    route: &RouteConfig,
+    mut response: reqwest::Response,
+    max_decoded: u64,
+) -> Result<Result<Bytes, Violation>, reqwest::Error> {
+    if let Some(detail) = content_type_mismatch(route, response.headers()) {
+        let first_chunk = response.chunk().await.ok().flatten();
+        return Ok(Err(Violation {
+            kind: ViolationKind::ContentType,
+            detail,
+            excerpt: first_chunk.as_deref().map(excerpt),
+        }));
+    }
+
+    let limit = route.max_response_bytes.map(|size| size.bytes());
+    if let (Some(limit), Some(length)) = (limit, response.content_length()) {
+        if length > limit {
+            return Ok(Err(too_large(limit)));
+        }
+    }
+
+    let mut body = BytesMut::new();
+    while let Some(chunk) = response.chunk().await? {
+        if let Some(limit) = limit.filter(|limit| (body.len() + chunk.len()) as u64 > *limit) {
+            return Ok(Err(too_large(limit)));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    if route.strict_json {
+        let decoded = encoding::inspect("strict_json", response.headers(), &body, max_decoded)
+            .filter(|decoded| decoded.len() <= STRICT_JSON_MAX_BYTES);
+        if let Some(decoded) = decoded {
+            if let Err(e) = serde_json::from_slice::<IgnoredAny>(&decoded) {
+                return Ok(Err(Violation {
+                    kind: ViolationKind::MalformedJson,
+                    detail: format!("body is not valid JSON: {}", e),
+                    excerpt: Some(excerpt(&decoded)),
+                }));
+            }
+        }
+    }
+    Ok(Ok(body.freeze()))
+}
@@ -0,0 +1,192 @@
+//! Exponential backoff with full jitter, shared by everything that retries.
+//!
+//! The wait after the nth failed attempt is drawn uniformly between zero and
+//! `initial_delay * multiplier^(n-1)`, capped at `max_delay`, so callers
+//! that fail together don't retry together.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{future::Future, time::Duration};
+use tokio::time::Instant;
+
+use crate::config::HumanDuration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Jitter {
+    /// Anywhere from zero up to the exponential delay.
+    #[default]
+    Full,
+    /// Exactly the exponential delay.
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first included; 0 keeps trying.
+    pub max_attempts: u32,
+    pub initial_delay: HumanDuration,
+    pub max_delay: HumanDuration,
+    pub multiplier: f64,
+    /// No retry is started that would begin later than this after the
+    /// first attempt; zero for no limit.
+    pub max_elapsed: HumanDuration,
+    pub jitter: Jitter,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: HumanDuration::from_millis(100),
+            max_delay: HumanDuration::from_secs(10),
+            multiplier: 2.0,
+            max_elapsed: HumanDuration::from_secs(0),
+            jitter: Jitter::Full,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt.
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The longest wait after the `failures`th failed attempt.
+    pub fn ceiling(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let seconds = self.initial_delay.get().as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        let max = self.max_delay.get();
+        if seconds.is_finite() && seconds < max.as_secs_f64() {
+            Duration::from_secs_f64(seconds)
+        } else {
+            max
+        }
+    }
+
+    /// The wait after the `failures`th failed attempt, jittered with `rng`.
+    pub fn delay_with<R: Rng + ?Sized>(&self, failures: u32, rng: &mut R) -> Duration {
+        let ceiling = self.ceiling(failures);
+        match self.jitter {
+            Jitter::None => ceiling,
+            Jitter::Full => Duration::from_nanos(rng.gen_range(0..=ceiling.as_nanos().min(u64::MAX as u128) as u64)),
+        }
+    }
+
+    pub fn delay(&self, failures: u32) -> Duration {
+        self.delay_with(failures, &mut rand::thread_rng())
+    }
+}
+
+/// The waits of one run of attempts.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    failures: u32,
+    started: Instant,
+    waited: Duration,
+}
+
+impl Backoff {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            failures: 0,
+            started: Instant::now(),
+            waited: Duration::ZERO,
+        }
+    }
+
+    /// How long to wait after another failure, or `None` once the policy
+    /// gives up.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.failures = self.failures.saturating_add(1);
+        if self.policy.max_attempts != 0 && self.failures >= self.policy.max_attempts {
+            return None;
+        }
+        let delay = self.policy.delay(self.failures);
+        if !self.policy.max_elapsed.is_zero() && self.started.elapsed() + delay > self.policy.max_elapsed.get() {
+            return None;
+        }
+        self.waited += delay;
+        Some(delay)
+    }
+
+    /// Starts over after a success.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.started = Instant::now();
+        self.waited = Duration::ZERO;
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Total of the delays handed out.
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+/// How a [`retry`] run ended.
+#[derive(Debug)]
+pub struct Retried<T, E> {
+    pub result: Result<T, E>,
+    pub attempts: u32,
+    pub total_delay: Duration,
+}
+
+impl<T, E> Retried<T, E> {
+    pub fn retries(&self) -> u32 {
+        self.attempts.saturating_sub(1)
+    }
+}
+
+/// Runs `op` (passed the attempt number, from 1) until it succeeds or
+/// `policy` gives up, sleeping between attempts.
+pub async fn retry<T, E, F, Fut>(policy: &RetryPolicy, op: F) -> Retried<T, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_if(policy, op, |_| true).await
+}
+
+/// [`retry`], giving up at once on errors `retryable` turns down.
+pub async fn retry_if<T, E, F, Fut>(policy: &RetryPolicy, mut op: F, retryable: impl Fn(&E) -> bool) -> Retried<T, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut backoff = Backoff::new(policy.clone());
+    loop {
+        let attempt = backoff.failures() + 1;
+        let error = match op(attempt).await {
+            Ok(value) => {
+                return Retried {
+                    result: Ok(value),
+                    attempts: attempt,
+                    total_delay: backoff.waited(),
+                }
+            }
+            Err(error) => error,
+        };
+        let delay = if retryable(&error) { backoff.next_delay() } else { None };
+        match delay {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => {
+                return Retried {
+                    result: Err(error),
+                    attempts: attempt,
+                    total_delay: backoff.waited(),
+                }
+            }
+        }
+    }
+}
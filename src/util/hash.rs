@@ -0,0 +1,20 @@
+//! Stable placement of keys in the unit interval.
+
+use sha2::{Digest, Sha256};
+
+/// Where `parts` fall in `[0, 1)`, the same on every replica and across
+/// restarts. Parts are hashed with a zero byte between them, so `("ab",
+/// "c")` and `("a", "bc")` land apart.
+pub fn unit_interval(parts: &[&str]) -> f64 {
+    let mut hasher = Sha256::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            hasher.update([0]);
+        }
+        hasher.update(part.as_bytes());
+    }
+    let digest = hasher.finalize();
+    let value = u64::from_be_bytes(digest[..8].try_into().expect("8 bytes"));
+    // The top 53 bits fit an f64 exactly, so the result never rounds up to 1
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
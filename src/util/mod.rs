@@ -0,0 +1,4 @@
+//! Small helpers shared across modules.
+
+pub mod backoff;
+pub mod hash;
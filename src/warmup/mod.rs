@@ -0,0 +1,163 @@
+//! Startup warm-up.
+//!
+//! Several one-off costs otherwise land on the first request after a deploy:
+//! the `Lazy` metric handles, the first connection to each upstream, and the
+//! first pass through the middleware stack (tracing callsite registration,
+//! per-route metric handles). [`Warmup::run`] pays them up front, and
+//! `GET /readyz` reports ready only once it has.
+//!
+//! Warm-up also runs the metrics self-check. An instance whose exporter
+//! doesn't show what it records never reports ready, so a deploy that broke
+//! metrics fails instead of exporting nothing.
+
+use axum::{body::Body, http::Request, Router};
+use std::{
+    collections::BTreeSet,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tower::ServiceExt;
+use tracing::{error, info, warn};
+
+use crate::AppState;
+
+pub use crate::models::health::{WarmupReport, WarmupStep};
+
+/// How long warm-up waits for each upstream to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Warm-up state; the gateway is ready once a report is recorded.
+#[derive(Default)]
+pub struct Warmup {
+    report: RwLock<Option<WarmupReport>>,
+}
+
+fn millis(took: Duration) -> f64 {
+    took.as_secs_f64() * 1000.0
+}
+
+impl Warmup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether warm-up has run without a failed step.
+    pub fn is_ready(&self) -> bool {
+        self.report
+            .read()
+            .map(|report| report.as_ref().is_some_and(|report| report.steps.iter().all(|step| !step.failed)))
+            .unwrap_or(false)
+    }
+
+    pub fn report(&self) -> Option<WarmupReport> {
+        self.report.read().ok().and_then(|report| report.clone())
+    }
+
+    /// Runs every warm-up step against `router` and marks the gateway ready.
+    /// Failures are logged and, but for the metrics self-check, never block
+    /// readiness: an unreachable upstream costs the first request what it
+    /// would have anyway.
+    pub async fn run(&self, state: &AppState, router: &Router) -> WarmupReport {
+        let started = Instant::now();
+        let mut steps = Vec::new();
+
+        let step = Instant::now();
+        crate::metrics::install_recorder();
+        crate::metrics::warm_up();
+        steps.push(finish("metrics", step, "recorder and static metric handles".to_string()));
+
+        let step = Instant::now();
+        steps.push(check_metrics(state, step).await);
+
+        let step = Instant::now();
+        let detail = warm_upstreams(state).await;
+        steps.push(finish("upstream_clients", step, detail));
+
+        let step = Instant::now();
+        let detail = warm_router(state, router).await;
+        steps.push(finish("router", step, detail));
+
+        let report = WarmupReport {
+            steps,
+            took_ms: millis(started.elapsed()),
+            completed_at: chrono::Utc::now().to_rfc3339(),
+        };
+        info!(took_ms = report.took_ms, "Warm-up complete; ready for traffic");
+        if let Ok(mut current) = self.report.write() {
+            *current = Some(report.clone());
+        }
+        report
+    }
+}
+
+fn finish(name: &str, started: Instant, detail: String) -> WarmupStep {
+    let step = WarmupStep {
+        name: name.to_string(),
+        took_ms: millis(started.elapsed()),
+        detail,
+        failed: false,
+    };
+    info!(step = %step.name, took_ms = step.took_ms, detail = %step.detail, "Warmed up");
+    step
+}
+
+/// Runs the metrics self-check unless metrics are disabled.
+async fn check_metrics(state: &AppState, started: Instant) -> WarmupStep {
+    if !state.config_watcher.get_config().await.metrics.enabled {
+        return finish("metrics_selfcheck", started, "skipped, metrics disabled".to_string());
+    }
+    let check = crate::metrics::selfcheck::run();
+    if check.passed {
+        return finish("metrics_selfcheck", started, format!("{} metric families exported", check.families.len()));
+    }
+    let problems: Vec<String> = check.families.into_iter().filter_map(|family| family.detail).collect();
+    let step = WarmupStep {
+        name: "metrics_selfcheck".to_string(),
+        took_ms: millis(started.elapsed()),
+        detail: problems.join("; "),
+        failed: true,
+    };
+    error!(detail = %step.detail, "Metrics self-check failed; not reporting ready");
+    step
+}
+
+/// Connects the shared client to the legacy gateway and mirror target.
+async fn warm_upstreams(state: &AppState) -> String {
+    let config = state.config_watcher.get_config().await;
+    let mut upstreams = BTreeSet::new();
+    if config.canary_rollout.enabled && config.canary_rollout.legacy_gateway_url.starts_with("http") {
+        upstreams.insert(crate::upstream::upstream_key(&config.canary_rollout.legacy_gateway_url));
+    }
+    if config.mirror.enabled && config.mirror.base_url.starts_with("http") {
+        upstreams.insert(crate::upstream::upstream_key(&config.mirror.base_url));
+    }
+
+    let results = futures::future::join_all(upstreams.iter().map(|upstream| async move {
+        let result = state.upstreams.warm(upstream, CONNECT_TIMEOUT).await;
+        if let Err(e) = &result {
+            warn!(upstream = %upstream, "Could not connect during warm-up: {}", e);
+        }
+        result.is_ok()
+    }))
+    .await;
+    let connected = results.iter().filter(|connected| **connected).count();
+    format!("{} of {} upstreams connected", connected, upstreams.len())
+}
+
+/// Sends a health check through the full middleware stack, pinned to the
+/// Rust handler so nothing is proxied or counted as rollout traffic.
+async fn warm_router(state: &AppState, router: &Router) -> String {
+    let config = state.config_watcher.get_config().await;
+    let request = Request::builder()
+        .uri("/health")
+        .header(config.canary_rollout.trigger_header.as_str(), "rust")
+        .body(Body::empty());
+    let response = match request {
+        Ok(request) => router.clone().oneshot(request).await,
+        Err(e) => return format!("could not build request: {}", e),
+    };
+    match response {
+        Ok(response) => format!("GET /health answered {}", response.status().as_u16()),
+        Err(e) => format!("GET /health failed: {}", e),
+    }
+}
@@ -0,0 +1,315 @@
+mod common;
+
+use common::{base_config, CapturedLogs, logs, metric_value, spawn_app};
+use project_gateway::{
+    config::{AppConfig, ByteSize, LogFormat, RouteLogLevel},
+    features::Feature,
+    middleware::logging::combined_layers,
+};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// The access log line mentioning `marker`.
+fn access_line(marker: &str) -> Option<String> {
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .find(|line| line.contains("Request completed") && line.contains(marker))
+        .map(str::to_string)
+}
+
+fn body_logging_config(max_body_size: u64) -> AppConfig {
+    logs();
+    let mut config = base_config();
+    config.middleware.logging.enabled = true;
+    config.middleware.logging.include_request_body = true;
+    config.middleware.logging.include_response_body = true;
+    config.middleware.logging.max_body_size = ByteSize::from_bytes(max_body_size);
+    config
+}
+
+#[tokio::test]
+async fn one_event_per_request_with_sizes_client_and_truncated_bodies() {
+    let app = spawn_app(body_logging_config(24)).await;
+
+    let request = json!({ "username": "marker-long-request", "email": "marker@example.com" }).to_string();
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header("User-Agent", "access-log-test/1.0")
+        .header("Content-Type", "application/json")
+        .body(request.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let sent = response.bytes().await.unwrap();
+
+    let line = access_line("access-log-test/1.0").expect("access log line");
+    for field in [
+        "method=POST".to_string(),
+        "path=/api/v1/users".to_string(),
+        "status=200".to_string(),
+        "client_ip=\"127.0.0.1\"".to_string(),
+        format!("bytes_in={}", request.len()),
+        format!("bytes_out={}", sent.len()),
+        format!("request_body={:?}", &request[..24]),
+        "request_body_truncated=true".to_string(),
+        "response_body_truncated=true".to_string(),
+    ] {
+        assert!(line.contains(&field), "{} missing from {}", field, line);
+    }
+    assert!(!line.contains("marker-long-request"), "{}", line);
+}
+
+#[tokio::test]
+async fn binary_bodies_are_counted_but_not_logged() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("marker-binary-body", "image/png"))
+        .mount(&legacy)
+        .await;
+    let mut config = body_logging_config(1024);
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("User-Agent", "binary-body-test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.bytes().await.unwrap(), "marker-binary-body");
+
+    let line = access_line("binary-body-test").expect("access log line");
+    assert!(!line.contains("marker-binary-body"), "{}", line);
+    assert!(!line.contains("response_body="), "{}", line);
+    assert!(line.contains("bytes_out=18"), "{}", line);
+}
+
+#[tokio::test]
+async fn long_streamed_responses_are_logged_from_their_start() {
+    let legacy = MockServer::start().await;
+    let body = format!("marker-response-start{}", "x".repeat(100_000));
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body.clone(), "text/plain"))
+        .mount(&legacy)
+        .await;
+    let mut config = body_logging_config(32);
+    config.middleware.logging.include_request_body = false;
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("User-Agent", "streamed-response-test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), body);
+
+    let line = access_line("streamed-response-test").expect("access log line");
+    assert!(line.contains(&format!("response_body={:?}", &body[..32])), "{}", line);
+    assert!(line.contains("response_body_truncated=true"), "{}", line);
+    assert!(line.contains(&format!("bytes_out={}", body.len())), "{}", line);
+    assert!(line.contains("bytes_in=0"), "{}", line);
+}
+
+#[tokio::test]
+async fn combined_format_writes_apache_lines_with_request_id_and_backend() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("marker-combined", "text/plain"))
+        .mount(&legacy)
+        .await;
+    // Formatted as the gateway does in production, for this thread, which
+    // runs the server too
+    let combined = CapturedLogs::default();
+    let writer = combined.clone();
+    let _subscriber = tracing::subscriber::set_default(
+        tracing_subscriber::registry().with(combined_layers(move || writer.clone())),
+    );
+    let mut config = body_logging_config(1024);
+    config.middleware.logging.include_request_body = false;
+    config.middleware.logging.include_response_body = false;
+    config.middleware.logging.include_headers = true;
+    config.middleware.logging.format = LogFormat::Combined;
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users?page=2"))
+        .header("User-Agent", "combined-test/1.0 \"quoted\"")
+        .header("Referer", "https://example.com/form")
+        .header("X-Request-Id", "req-combined")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "marker-combined");
+
+    let logged = String::from_utf8_lossy(&combined.0.lock().unwrap()).into_owned();
+    let line = logged.lines().find(|line| line.starts_with("127.0.0.1 ")).expect("access log line");
+    let (received, rest) = line.strip_prefix("127.0.0.1 - - [").unwrap().split_once("] ").unwrap();
+    assert!(chrono::DateTime::parse_from_str(received, "%d/%b/%Y:%H:%M:%S %z").is_ok(), "{}", line);
+    assert_eq!(
+        rest,
+        "\"GET /api/v1/users HTTP/1.1\" 200 15 \"https://example.com/form\" \
+         \"combined-test/1.0 \\\"quoted\\\"\" \"req-combined\" legacy"
+    );
+    // Headers follow on a line of their own
+    let detail = logged.lines().find(|line| line.contains("Request detail")).expect("detail line");
+    assert!(detail.contains("request_headers") && detail.contains("req-combined"), "{}", detail);
+}
+
+#[test]
+fn the_format_can_be_overridden_from_the_environment() {
+    let env = [("GATEWAY_LOGGING__FORMAT".to_string(), "combined".to_string())].into();
+    let config = AppConfig::load_str_with_env(include_str!("../config/default.yaml"), env);
+    assert_eq!(config.unwrap().middleware.logging.format, LogFormat::Combined);
+    assert_eq!(base_config().middleware.logging.format, LogFormat::Json);
+}
+
+/// Ids of the access log lines from `user_agent`.
+fn logged_ids(user_agent: &str) -> Vec<String> {
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .filter(|line| line.contains("Request completed") && line.contains(user_agent))
+        .filter_map(|line| line.split("request_id=\"").nth(1)?.split('"').next().map(str::to_string))
+        .collect()
+}
+
+#[tokio::test]
+async fn sampling_drops_successes_by_request_id_but_keeps_errors_and_route_overrides() {
+    let mut config = body_logging_config(1024);
+    config.middleware.logging.sample_rate = 0.0;
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap();
+    route.log_sample_rate = Some(1.0);
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+    let get = |path: &str, user_agent: &str, request_id: String| {
+        client
+            .get(app.url(path))
+            .header("X-Gateway-Version", "rust")
+            .header("User-Agent", user_agent)
+            .header("X-Request-Id", request_id)
+            .send()
+    };
+
+    for n in 0..10 {
+        assert_eq!(get("/health", "sampled-out", format!("health-{}", n)).await.unwrap().status(), 200);
+    }
+    assert_eq!(get("/api/v1/users", "route-override", "users-1".into()).await.unwrap().status(), 200);
+    let response = client
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header("User-Agent", "failed-request")
+        .json(&json!({ "username": "no-email" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_client_error());
+    response.bytes().await.unwrap();
+
+    assert!(logged_ids("sampled-out").is_empty());
+    assert_eq!(logged_ids("route-override"), ["users-1"]);
+    assert_eq!(logged_ids("failed-request").len(), 1);
+    let metrics = app.scrape_metrics().await;
+    assert_eq!(metric_value(&metrics, "gateway_access_log_suppressed_total", &[("route", "/health")]), 10.0);
+
+    // Two replicas sampling at half keep the same requests
+    config.middleware.logging.sample_rate = 0.5;
+    let replicas = [spawn_app(config.clone()).await, spawn_app(config).await];
+    for (replica, user_agent) in replicas.iter().zip(["replica-a", "replica-b"]) {
+        for n in 0..40 {
+            let response = client
+                .get(replica.url("/health"))
+                .header("X-Gateway-Version", "rust")
+                .header("User-Agent", user_agent)
+                .header("X-Request-Id", format!("replicated-{}", n))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+        }
+    }
+    let kept = logged_ids("replica-a");
+    assert_eq!(kept, logged_ids("replica-b"));
+    assert!(!kept.is_empty() && kept.len() < 40, "{} of 40 kept", kept.len());
+}
+
+#[tokio::test]
+async fn route_levels_quiet_or_detail_a_path_and_apply_on_reload() {
+    let mut config = body_logging_config(1024);
+    config.middleware.logging.include_request_body = false;
+    config.middleware.logging.include_response_body = false;
+    config.middleware.logging.route_levels.insert("/health".to_string(), RouteLogLevel::Off);
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+    let request = |path: &str, user_agent: &str| {
+        client
+            .post(app.url(path))
+            .header("X-Gateway-Version", "rust")
+            .header("User-Agent", user_agent)
+            .json(&json!({ "username": "levels", "email": "levels@example.com" }))
+            .send()
+    };
+
+    let probe = client
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust")
+        .header("User-Agent", "level-off")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(probe.status(), 200);
+    assert!(access_line("level-off").is_none());
+    let metrics = app.scrape_metrics().await;
+    assert!(metric_value(&metrics, "gateway_response_bytes_total", &[("route", "/health"), ("variant", "rust")]) > 0.0);
+
+    request("/api/v1/users", "level-default").await.unwrap().bytes().await.unwrap();
+    let line = access_line("level-default").expect("access log line");
+    assert!(!line.contains("request_body="), "{}", line);
+
+    // Turned up to debug during an incident, without a restart
+    config.middleware.logging.route_levels.insert("/api/v1/users".to_string(), RouteLogLevel::Debug);
+    app.state.config_watcher.apply(config.clone()).await;
+    request("/api/v1/users", "level-debug").await.unwrap().bytes().await.unwrap();
+    let line = access_line("level-debug").expect("access log line");
+    assert!(line.contains("request_body=") && line.contains("request_headers="), "{}", line);
+
+    // The runtime switches still beat a debug level
+    app.state.feature_overrides.set(Feature::BodyLogging, false, None, "test");
+    request("/api/v1/users", "level-debug-no-bodies").await.unwrap().bytes().await.unwrap();
+    let line = access_line("level-debug-no-bodies").expect("access log line");
+    assert!(!line.contains("request_body=") && !line.contains("request_headers="), "{}", line);
+    app.state.feature_overrides.set(Feature::RequestLogging, false, None, "test");
+    request("/api/v1/users", "level-debug-off").await.unwrap().bytes().await.unwrap();
+    assert!(access_line("level-debug-off").is_none());
+    app.state.feature_overrides.clear(Feature::BodyLogging, "test");
+    app.state.feature_overrides.clear(Feature::RequestLogging, "test");
+
+    config.middleware.logging.route_levels.insert("/api/v1/users".to_string(), RouteLogLevel::Error);
+    app.state.config_watcher.apply(config).await;
+    request("/api/v1/users", "level-error").await.unwrap().bytes().await.unwrap();
+    assert!(access_line("level-error").is_none());
+}
+
+#[test]
+fn the_longest_matching_prefix_sets_the_level() {
+    let mut logging = base_config().middleware.logging;
+    logging.route_levels.insert("/".to_string(), RouteLogLevel::Error);
+    logging.route_levels.insert("/health".to_string(), RouteLogLevel::Off);
+    logging.route_levels.insert("/health/deep/".to_string(), RouteLogLevel::Debug);
+    assert_eq!(logging.route_level("/health"), Some(RouteLogLevel::Off));
+    assert_eq!(logging.route_level("/health/deep/db"), Some(RouteLogLevel::Debug));
+    assert_eq!(logging.route_level("/healthz"), Some(RouteLogLevel::Error));
+    logging.route_levels.remove("/");
+    assert_eq!(logging.route_level("/healthz"), None);
+}
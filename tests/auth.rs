@@ -0,0 +1,253 @@
+mod common;
+
+use common::{base_config, jwt, jwt_accepted, jwt_auth, metric_value, spawn_app, JWT_NOW};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use project_gateway::{
+    config::AuthConfig,
+    middleware::{
+        auth::{issue_token, validate_token_at, AuthCache, Claims},
+        auth_audit::AuthFailureReason,
+    },
+};
+use serde_json::{json, Value};
+
+const PRIMARY: &str = "new-secret";
+const SECONDARY: &str = "old-secret";
+
+fn auth_config(secrets: &[&str]) -> AuthConfig {
+    AuthConfig {
+        jwt_secrets: secrets.iter().map(|s| s.to_string()).collect(),
+        exempt_paths: Vec::new(),
+        ..jwt_auth()
+    }
+}
+
+fn claims(sub: &str) -> Claims {
+    Claims {
+        sub: sub.to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: None,
+        roles: vec!["reader".to_string()],
+    }
+}
+
+#[tokio::test]
+async fn token_signed_with_secondary_secret_validates() {
+    let mut config = base_config();
+    config.middleware.auth = auth_config(&[PRIMARY, SECONDARY]);
+    let app = spawn_app(config).await;
+
+    let old_token = issue_token(&auth_config(&[SECONDARY]), &claims("legacy-client")).unwrap();
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(old_token)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn removing_secondary_secret_rejects_cached_tokens() {
+    let mut config = base_config();
+    config.middleware.auth = auth_config(&[PRIMARY, SECONDARY]);
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+
+    let old_token = issue_token(&auth_config(&[SECONDARY]), &claims("legacy-client")).unwrap();
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(&old_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(app.state.auth_cache.len(), 1);
+
+    config.middleware.auth = auth_config(&[PRIMARY]);
+    app.state.config_watcher.apply(config).await;
+
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(&old_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn rotation_reloads_secrets_and_counts_uses_per_index() {
+    let scrapeable = |secrets: &[&str]| AuthConfig {
+        exempt_paths: vec!["/metrics".to_string()],
+        ..auth_config(secrets)
+    };
+    let mut config = base_config();
+    config.middleware.auth = scrapeable(&[SECONDARY]);
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+    let status = |token: String| {
+        let request = client.get(app.url("/api/v1/users")).bearer_auth(token);
+        async move { request.send().await.unwrap().status() }
+    };
+    let uses = |scraped: &str, index: &str| metric_value(scraped, "gateway_jwt_secret_validations_total", &[("secret_index", index)]);
+
+    let old_token = issue_token(&auth_config(&[SECONDARY]), &claims("before-rotation")).unwrap();
+    assert_eq!(status(old_token.clone()).await, 200);
+
+    // Rotate: the new secret goes first, the old one stays for a while
+    config.middleware.auth = scrapeable(&[PRIMARY, SECONDARY]);
+    app.state.config_watcher.apply(config).await;
+    let before = app.scrape_metrics().await;
+    let new_token = issue_token(&app.state.config_watcher.get_config().await.middleware.auth, &claims("after-rotation")).unwrap();
+    assert_eq!(status(new_token).await, 200);
+    assert_eq!(status(old_token.clone()).await, 200);
+    assert_eq!(status(old_token).await, 200);
+
+    let after = app.scrape_metrics().await;
+    assert!(uses(&after, "0") - uses(&before, "0") >= 1.0);
+    assert!(uses(&after, "1") - uses(&before, "1") >= 2.0);
+}
+
+#[tokio::test]
+async fn tokens_are_issued_with_primary_secret_only() {
+    let config = auth_config(&[PRIMARY, SECONDARY]);
+    let token = issue_token(&config, &claims("issuer-test")).unwrap();
+    let validation = Validation::new(Algorithm::HS256);
+
+    assert!(decode::<Claims>(&token, &DecodingKey::from_secret(PRIMARY.as_bytes()), &validation).is_ok());
+    assert!(decode::<Claims>(&token, &DecodingKey::from_secret(SECONDARY.as_bytes()), &validation).is_err());
+}
+
+#[tokio::test]
+async fn missing_token_is_rejected() {
+    let mut config = base_config();
+    config.middleware.auth = auth_config(&[PRIMARY]);
+    let app = spawn_app(config).await;
+
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+fn exempting(paths: &[&str]) -> AuthConfig {
+    AuthConfig {
+        exempt_paths: paths.iter().map(|p| p.to_string()).collect(),
+        ..auth_config(&[PRIMARY])
+    }
+}
+
+#[test]
+fn exempt_paths_match_exactly_or_below_a_wildcard() {
+    let auth = exempting(&["/health", "/api-docs/*", "/docs/*"]);
+
+    for path in ["/health", "/health/", "/api-docs", "/api-docs/", "/api-docs/openapi.json", "/docs/index.html"] {
+        assert!(auth.is_exempt(path), "{} should be exempt", path);
+    }
+    for path in [
+        // Prefixes only match whole segments
+        "/api/v1/users",
+        "/api-docsx",
+        "/api-docs-internal/openapi.json",
+        "/healthz",
+        "/health/details",
+        // Paths are case sensitive
+        "/HEALTH",
+        "/API-DOCS/openapi.json",
+        // An upstream could resolve these outside the exempt prefix
+        "/api-docs/../api/v1/users",
+        "/api-docs/%2e%2e/api/v1/users",
+        "/api-docs%2F..%2Fapi/v1/users",
+    ] {
+        assert!(!auth.is_exempt(path), "{} should not be exempt", path);
+    }
+}
+
+#[tokio::test]
+async fn exempt_paths_skip_auth_and_reload_with_the_config() {
+    let mut config = base_config();
+    config.middleware.auth = exempting(&["/health", "/api-docs/*"]);
+    let app = spawn_app(config.clone()).await;
+    let status = |path: &str| {
+        let url = app.url(path);
+        async move { reqwest::get(url).await.unwrap().status() }
+    };
+
+    assert_eq!(status("/health").await, 200);
+    // Exempt too, so the router rather than auth turns it away
+    assert_eq!(status("/health/").await, 404);
+    assert_eq!(status("/api-docs/openapi.json").await, 200);
+    assert_eq!(status("/api/v1/users").await, 401);
+
+    config.middleware.auth = exempting(&["/api-docs/*"]);
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(status("/health").await, 401);
+    assert_eq!(status("/api-docs/openapi.json").await, 200);
+}
+
+fn expecting(issuer: Option<&str>, audience: &[&str]) -> AuthConfig {
+    AuthConfig {
+        issuer: issuer.map(str::to_string),
+        audience: audience.iter().map(|aud| aud.to_string()).collect(),
+        ..jwt_auth()
+    }
+}
+
+#[test]
+fn issuer_and_audience_are_checked_only_when_configured() {
+    let open = expecting(None, &[]);
+    assert!(jwt_accepted(&open, json!({ "sub": "a", "exp": JWT_NOW + 60 })));
+    assert!(jwt_accepted(&open, json!({ "sub": "a", "exp": JWT_NOW + 60, "iss": "anyone", "aud": ["anything"] })));
+
+    let issuer = expecting(Some("https://idp.example.com/"), &[]);
+    assert!(jwt_accepted(&issuer, json!({ "sub": "a", "exp": JWT_NOW + 60, "iss": "https://idp.example.com/" })));
+    assert!(!jwt_accepted(&issuer, json!({ "sub": "a", "exp": JWT_NOW + 60, "iss": "https://evil.example.com/" })));
+    assert!(!jwt_accepted(&issuer, json!({ "sub": "a", "exp": JWT_NOW + 60 })));
+}
+
+#[test]
+fn any_configured_audience_is_accepted() {
+    let auth = expecting(None, &["gateway", "legacy-api"]);
+    assert!(jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 60, "aud": "legacy-api" })));
+    assert!(jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 60, "aud": ["reports", "gateway"] })));
+    assert!(!jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 60, "aud": ["reports"] })));
+    assert!(!jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 60 })));
+}
+
+#[test]
+fn cached_tokens_are_rechecked_when_the_audience_changes() {
+    let cache = AuthCache::new();
+    let token = jwt(json!({ "sub": "a", "exp": JWT_NOW + 60, "aud": "gateway" }));
+    assert!(validate_token_at(&cache, &expecting(None, &["gateway"]), &token, JWT_NOW).is_some());
+    assert!(validate_token_at(&cache, &expecting(None, &["reports"]), &token, JWT_NOW).is_none());
+}
+
+#[tokio::test]
+async fn mismatched_claims_get_401_naming_the_claim() {
+    let mut config = base_config();
+    config.middleware.auth = expecting(Some("https://idp.example.com/"), &["gateway"]);
+    let app = spawn_app(config).await;
+    let exp = chrono::Utc::now().timestamp() + 600;
+
+    let cases = [
+        (json!({ "sub": "a", "exp": exp, "iss": "https://idp.example.com/", "aud": "secret-audience" }), "aud"),
+        (json!({ "sub": "a", "exp": exp, "iss": "https://secret-issuer.example.com/", "aud": "gateway" }), "iss"),
+    ];
+    for (claims, claim) in cases {
+        let response = reqwest::Client::new()
+            .get(app.url("/api/v1/users"))
+            .bearer_auth(jwt(claims))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        let body = response.text().await.unwrap();
+        assert!(!body.contains("secret-"), "{}", body);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"]["code"], "invalid_token");
+        assert_eq!(body["error"]["claim"], claim);
+    }
+
+    let reasons: Vec<_> = app.state.auth_failures.recent().iter().map(|failure| failure.reason).collect();
+    assert_eq!(reasons, [AuthFailureReason::WrongIssuer, AuthFailureReason::WrongAudience]);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{base_config, jwt_auth, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::AuthConfig,
+    middleware::{
+        auth::{issue_token, Claims},
+        auth_audit::{AuthFailure, AuthFailureReason},
+    },
+};
+
+const SECRET: &str = "audit-secret";
+
+fn auth_config(secret: &str) -> AuthConfig {
+    AuthConfig {
+        jwt_secrets: vec![secret.to_string()],
+        exempt_paths: vec!["/metrics".to_string(), "/admin/*".to_string()],
+        failure_log_size: 3,
+        ..jwt_auth()
+    }
+}
+
+fn token(secret: &str, sub: &str, exp_offset: i64) -> String {
+    let claims = Claims {
+        sub: sub.to_string(),
+        exp: (chrono::Utc::now().timestamp() + exp_offset) as u64,
+        scope: None,
+        roles: vec!["reader".to_string()],
+    };
+    issue_token(&auth_config(secret), &claims).unwrap()
+}
+
+async fn get(app: &TestApp, token: Option<&str>) -> u16 {
+    let mut request = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Forwarded-For", "203.0.113.7, 10.0.0.1");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap().status().as_u16()
+}
+
+fn failures(scrape: &str, reason: &str) -> f64 {
+    metric_value(scrape, "gateway_auth_failures_total", &[("reason", reason)])
+}
+
+#[tokio::test]
+async fn denials_are_classified_counted_and_kept_without_the_token() {
+    let mut config = base_config();
+    config.middleware.auth = auth_config(SECRET);
+    // 10.0.0.1 is our own load balancer, so the hop before it is the client
+    config.middleware.rate_limiting.trusted_proxies.push("10.0.0.0/8".to_string());
+    let app = spawn_app(config).await;
+    let before = app.scrape_metrics().await;
+
+    let expired = token(SECRET, "expired-user", -3600);
+    let forged = token("someone-elses-secret", "forging-user", 600);
+    for token in [None, Some("not-a-jwt"), Some(expired.as_str()), Some(forged.as_str())] {
+        assert_eq!(get(&app, token).await, 401);
+    }
+    assert_eq!(get(&app, Some(&token(SECRET, "good-user", 600))).await, 200);
+
+    let after = app.scrape_metrics().await;
+    for reason in ["missing_credentials", "malformed_token", "expired", "bad_signature"] {
+        assert_eq!(failures(&after, reason) - failures(&before, reason), 1.0, "{}", reason);
+    }
+
+    let response = reqwest::Client::new()
+        .get(app.url("/admin/auth/failures"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.unwrap();
+    assert!(!body.contains(&expired) && !body.contains(&forged) && !body.contains("not-a-jwt"));
+    assert!(!body.contains("expired-user") && !body.contains("forging-user"));
+
+    // Only the last failure_log_size, newest first
+    let listed: Vec<AuthFailure> = serde_json::from_str(&body).unwrap();
+    let reasons: Vec<_> = listed.iter().map(|failure| failure.reason).collect();
+    assert_eq!(
+        reasons,
+        [AuthFailureReason::BadSignature, AuthFailureReason::Expired, AuthFailureReason::MalformedToken]
+    );
+    assert_eq!(listed[0].subject, Some(app.state.pseudonymizer.pseudonymize("forging-user")));
+    assert_eq!(listed[1].subject, Some(app.state.pseudonymizer.pseudonymize("expired-user")));
+    assert_eq!(listed[2].subject, None);
+    assert!(listed.iter().all(|failure| failure.client_ip.as_deref() == Some("203.0.113.7")));
+    assert!(listed.iter().all(|failure| failure.method == "GET" && failure.path == "/api/v1/users"));
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::{base_config, jwt_auth, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, AuthConfig, RouteAuthorization},
+    docs::ApiDoc,
+    middleware::{
+        auth::{issue_token, Claims},
+        auth_audit::AuthFailureReason,
+        authorization::is_authorized,
+    },
+};
+use serde_json::{json, Value};
+use utoipa::OpenApi;
+
+const SECRET: &str = "authorization-secret";
+
+fn auth_config() -> AuthConfig {
+    AuthConfig {
+        jwt_secrets: vec![SECRET.to_string()],
+        exempt_paths: vec!["/metrics".to_string()],
+        ..jwt_auth()
+    }
+}
+
+fn claims(roles: &[&str], scope: Option<&str>) -> Claims {
+    Claims {
+        sub: "caller".to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: scope.map(str::to_string),
+        roles: roles.iter().map(|role| role.to_string()).collect(),
+    }
+}
+
+fn token(roles: &[&str], scope: Option<&str>) -> String {
+    issue_token(&auth_config(), &claims(roles, scope)).unwrap()
+}
+
+fn requiring(roles: &[&str], scopes: &[&str]) -> RouteAuthorization {
+    RouteAuthorization {
+        roles: roles.iter().map(|role| role.to_string()).collect(),
+        scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+    }
+}
+
+fn protected_config() -> AppConfig {
+    let mut config = base_config();
+    config.middleware.auth = auth_config();
+    config
+}
+
+async fn call(app: &TestApp, method: reqwest::Method, token: Option<&str>) -> (u16, Value) {
+    let mut request = reqwest::Client::new()
+        .request(method, app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .json(&json!({ "username": "new", "email": "new@example.com" }));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+#[test]
+fn any_listed_role_or_scope_authorizes() {
+    let required = requiring(&["reader", "admin"], &["users:read"]);
+    assert!(is_authorized(&required, &claims(&["reader"], None)));
+    assert!(is_authorized(&required, &claims(&["auditor", "admin"], None)));
+    assert!(is_authorized(&required, &claims(&[], Some("profile users:read"))));
+    assert!(!is_authorized(&required, &claims(&["auditor"], Some("users:write"))));
+    // Roles compare exactly
+    assert!(!is_authorized(&required, &claims(&["Admin"], None)));
+    // Nothing required, nothing needed
+    assert!(is_authorized(&RouteAuthorization::default(), &claims(&[], None)));
+}
+
+#[test]
+fn wildcard_scopes_cover_their_prefix() {
+    let required = requiring(&[], &["users:write"]);
+    assert!(is_authorized(&required, &claims(&[], Some("users:*"))));
+    assert!(is_authorized(&required, &claims(&[], Some("*"))));
+    assert!(!is_authorized(&required, &claims(&[], Some("docs:*"))));
+    assert!(!is_authorized(&required, &claims(&[], Some("users:"))));
+}
+
+#[tokio::test]
+async fn writes_need_admin_while_reads_accept_reader() {
+    let app = spawn_app(protected_config()).await;
+    let reader = token(&["reader"], None);
+    let admin = token(&["admin"], None);
+
+    assert_eq!(call(&app, reqwest::Method::GET, Some(&reader)).await.0, 200);
+    assert_eq!(call(&app, reqwest::Method::GET, Some(&admin)).await.0, 200);
+    assert_eq!(call(&app, reqwest::Method::POST, Some(&admin)).await.0, 200);
+
+    let (status, body) = call(&app, reqwest::Method::POST, Some(&reader)).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["error"]["code"], "insufficient_scope");
+    assert_eq!(body["error"]["required_roles"], json!(["admin"]));
+
+    let scrape = app.scrape_metrics().await;
+    let denials = metric_value(
+        &scrape,
+        "gateway_authorization_denials_total",
+        &[("route", "/api/v1/users"), ("reason", "insufficient_scope")],
+    );
+    assert!(denials >= 1.0, "{}", scrape);
+
+    let failures = app.state.auth_failures.recent();
+    assert_eq!(failures[0].reason, AuthFailureReason::InsufficientScope);
+    assert_eq!(failures[0].method, "POST");
+    assert_eq!(failures[0].subject, Some(app.state.pseudonymizer.pseudonymize("caller")));
+}
+
+#[tokio::test]
+async fn route_scopes_accept_wildcard_grants_and_reload() {
+    let mut config = protected_config();
+    let app = spawn_app(config.clone()).await;
+    let wildcard = token(&[], Some("users:*"));
+    assert_eq!(call(&app, reqwest::Method::GET, Some(&wildcard)).await.0, 403);
+
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap();
+    route.authorization = Some(requiring(&["reader"], &["users:read"]));
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(call(&app, reqwest::Method::GET, Some(&wildcard)).await.0, 200);
+}
+
+#[tokio::test]
+async fn protected_routes_refuse_unauthenticated_callers() {
+    let mut config = protected_config();
+    let app = spawn_app(config.clone()).await;
+    assert_eq!(call(&app, reqwest::Method::GET, None).await.0, 401);
+
+    // Exempting the path from auth doesn't lift the route's requirement
+    config.middleware.auth.exempt_paths = vec!["/api/v1/users".to_string()];
+    app.state.config_watcher.apply(config).await;
+    let (status, body) = call(&app, reqwest::Method::GET, None).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "authentication_required");
+    assert_eq!(body["error"]["required_roles"], json!(["reader", "admin"]));
+}
+
+#[test]
+fn spec_documents_the_bearer_requirement_on_user_routes() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    assert_eq!(spec["components"]["securitySchemes"]["bearer_auth"]["scheme"], "bearer");
+    for method in ["get", "post"] {
+        assert_eq!(
+            spec["paths"]["/api/v1/users"][method]["security"],
+            json!([{ "bearer_auth": [] }]),
+            "{}",
+            method
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use project_gateway::{
+    config::HumanDuration,
+    util::backoff::{retry, retry_if, Backoff, Jitter, RetryPolicy},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+fn policy(max_attempts: u32, initial_ms: u64, max_ms: u64) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_delay: HumanDuration::from_millis(initial_ms),
+        max_delay: HumanDuration::from_millis(max_ms),
+        multiplier: 2.0,
+        max_elapsed: HumanDuration::from_secs(0),
+        jitter: Jitter::Full,
+    }
+}
+
+#[test]
+fn delays_stay_within_the_exponential_ceiling() {
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..2_000 {
+        let mut random = policy(0, rng.gen_range(0..2_000), rng.gen_range(0..60_000));
+        random.multiplier = rng.gen_range(1.0..4.0);
+        let mut previous = Duration::ZERO;
+        for failures in 1..=64 {
+            let ceiling = random.ceiling(failures);
+            assert!(ceiling <= random.max_delay.get(), "{:?} after {} failures", random, failures);
+            assert!(ceiling >= previous, "{:?} shrank after {} failures", random, failures);
+            let expected = random.initial_delay.get().as_secs_f64() * random.multiplier.powi(failures as i32 - 1);
+            assert!(ceiling.as_secs_f64() <= expected + 1e-6, "{:?} after {} failures", random, failures);
+            previous = ceiling;
+
+            assert!(random.delay_with(failures, &mut rng) <= ceiling);
+            let exact = RetryPolicy {
+                jitter: Jitter::None,
+                ..random.clone()
+            };
+            assert_eq!(exact.delay_with(failures, &mut rng), ceiling);
+        }
+    }
+}
+
+#[test]
+fn full_jitter_spreads_delays_evenly_below_the_ceiling() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let policy = policy(0, 1_000, 1_000);
+    let samples = 20_000;
+    let mut buckets = [0u32; 10];
+    let mut total = 0.0;
+    for _ in 0..samples {
+        let fraction = policy.delay_with(1, &mut rng).as_secs_f64();
+        total += fraction;
+        buckets[((fraction * 10.0) as usize).min(9)] += 1;
+    }
+    let mean = total / samples as f64;
+    assert!((mean - 0.5).abs() < 0.02, "mean {}", mean);
+    for (bucket, count) in buckets.iter().enumerate() {
+        let share = f64::from(*count) / samples as f64;
+        assert!((0.08..0.12).contains(&share), "bucket {} got {}", bucket, share);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn backoff_gives_up_at_max_attempts_or_max_elapsed() {
+    let mut backoff = Backoff::new(policy(3, 100, 1_000));
+    assert!(backoff.next_delay().is_some());
+    assert!(backoff.next_delay().is_some());
+    assert_eq!(backoff.next_delay(), None);
+    backoff.reset();
+    assert!(backoff.next_delay().is_some());
+
+    let mut capped = policy(0, 1_000, 1_000);
+    capped.jitter = Jitter::None;
+    capped.max_elapsed = HumanDuration::from_millis(2_500);
+    let mut backoff = Backoff::new(capped);
+    for _ in 0..2 {
+        let delay = backoff.next_delay().unwrap();
+        tokio::time::sleep(delay).await;
+    }
+    assert_eq!(backoff.next_delay(), None);
+    assert_eq!(backoff.waited(), Duration::from_secs(2));
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_reports_attempts_and_total_delay() {
+    let mut exact = policy(5, 100, 10_000);
+    exact.jitter = Jitter::None;
+
+    let started = Instant::now();
+    let retried = retry(&exact, |attempt| async move { if attempt < 3 { Err(attempt) } else { Ok("done") } }).await;
+    assert_eq!(retried.result, Ok("done"));
+    assert_eq!(retried.attempts, 3);
+    assert_eq!(retried.retries(), 2);
+    assert_eq!(retried.total_delay, Duration::from_millis(300));
+    assert_eq!(started.elapsed(), Duration::from_millis(300));
+
+    let exhausted = retry(&exact, |attempt| async move { Err::<(), _>(attempt) }).await;
+    assert_eq!(exhausted.result, Err(5));
+    assert_eq!(exhausted.total_delay, Duration::from_millis(1_500));
+
+    let permanent = retry_if(&exact, |attempt| async move { Err::<(), _>(attempt) }, |_| false).await;
+    assert_eq!(permanent.attempts, 1);
+    assert_eq!(permanent.total_delay, Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn concurrent_failures_do_not_retry_in_lockstep() {
+    let policy = policy(6, 1_000, 30_000);
+    let run = || {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = attempts.clone();
+        let started = Instant::now();
+        let policy = policy.clone();
+        let task = tokio::spawn(async move {
+            retry(&policy, |_| {
+                recorded.lock().unwrap().push(started.elapsed());
+                async { Err::<(), _>("unavailable") }
+            })
+            .await
+        });
+        (task, attempts)
+    };
+    let (first, first_attempts) = run();
+    let (second, second_attempts) = run();
+    assert_eq!(first.await.unwrap().attempts, 6);
+    assert_eq!(second.await.unwrap().attempts, 6);
+
+    let first_attempts = first_attempts.lock().unwrap().clone();
+    let second_attempts = second_attempts.lock().unwrap().clone();
+    assert_eq!(first_attempts[0], second_attempts[0]);
+    let together = first_attempts[1..]
+        .iter()
+        .zip(&second_attempts[1..])
+        .filter(|(a, b)| a.abs_diff(**b) < Duration::from_millis(10))
+        .count();
+    assert!(together < 5, "retried together: {:?} and {:?}", first_attempts, second_attempts);
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use argon2::{
+    password_hash::{PasswordHasher, SaltString},
+    Argon2,
+};
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::AdminBasicAuthConfig,
+    middleware::{
+        auth::{issue_token, Claims},
+        basic_auth::{is_supported_hash, verify_password},
+    },
+};
+
+async fn protected_app(password_hash: String) -> TestApp {
+    let mut config = base_config();
+    config.admin_basic_auth = Some(AdminBasicAuthConfig {
+        username: "ops".to_string(),
+        password_hash,
+        realm: "gateway admin".to_string(),
+    });
+    spawn_app(config).await
+}
+
+async fn get(app: &TestApp, path: &str, credentials: Option<(&str, &str)>) -> reqwest::Response {
+    let mut request = reqwest::Client::new().get(app.url(path));
+    if let Some((username, password)) = credentials {
+        request = request.basic_auth(username, Some(password));
+    }
+    request.send().await.unwrap()
+}
+
+#[tokio::test]
+async fn docs_and_metrics_need_the_configured_credentials() {
+    let app = protected_app(bcrypt::hash("s3cret", 4).unwrap()).await;
+
+    for path in ["/metrics", "/docs/", "/api-docs/openapi.json"] {
+        assert_eq!(get(&app, path, Some(("ops", "s3cret"))).await.status(), 200, "{}", path);
+
+        for credentials in [None, Some(("ops", "wrong")), Some(("root", "s3cret"))] {
+            let response = get(&app, path, credentials).await;
+            assert_eq!(response.status(), 401, "{} {:?}", path, credentials);
+            assert_eq!(
+                response.headers()["www-authenticate"],
+                "Basic realm=\"gateway admin\", charset=\"UTF-8\""
+            );
+        }
+    }
+
+    // Other routes don't ask
+    let health = reqwest::Client::new()
+        .get(app.url("/api/v1/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(health.status(), 200);
+}
+
+#[tokio::test]
+async fn basic_auth_is_independent_of_jwt_auth() {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.admin_basic_auth = Some(AdminBasicAuthConfig {
+        username: "ops".to_string(),
+        password_hash: bcrypt::hash("s3cret", 4).unwrap(),
+        realm: "project-gateway".to_string(),
+    });
+    let app = spawn_app(config).await;
+
+    // A valid bearer token is no substitute for the credentials
+    let token = issue_token(
+        &app.state.config_watcher.get_config().await.middleware.auth,
+        &Claims {
+            sub: "reader".to_string(),
+            exp: chrono::Utc::now().timestamp() as u64 + 600,
+            scope: Some("docs:read".to_string()),
+            roles: vec!["admin".to_string()],
+        },
+    )
+    .unwrap();
+    let response = reqwest::Client::new()
+        .get(app.url("/metrics"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(get(&app, "/metrics", Some(("ops", "s3cret"))).await.status(), 200);
+}
+
+#[tokio::test]
+async fn endpoints_are_open_without_the_section() {
+    let app = spawn_app(base_config()).await;
+    assert_eq!(get(&app, "/metrics", None).await.status(), 200);
+    assert_eq!(get(&app, "/docs/", None).await.status(), 200);
+}
+
+#[test]
+fn argon2_and_bcrypt_hashes_are_verified() {
+    let salt = SaltString::from_b64("c29tZXNhbHRzb21lc2FsdA").unwrap();
+    let hash = Argon2::default().hash_password(b"s3cret", &salt).unwrap().to_string();
+    assert!(is_supported_hash(&hash));
+    assert!(verify_password("s3cret", &hash));
+    assert!(!verify_password("wrong", &hash));
+
+    let hash = bcrypt::hash("s3cret", 4).unwrap();
+    assert!(is_supported_hash(&hash));
+    assert!(verify_password("s3cret", &hash));
+    assert!(!is_supported_hash("s3cret"));
+    assert!(!verify_password("s3cret", "$2b$04$not-a-real-hash"));
+}
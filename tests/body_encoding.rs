@@ -0,0 +1,175 @@
+mod common;
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use common::{base_config, logs, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, ByteSize},
+    upstream::encoding::{self, ContentCoding, DecodeError},
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const USERS: &str = r#"{"users":[{"id":1,"name":"Ada"},{"id":2,"name":"Grace"},{"id":3,"name":"Hedy"}]}"#;
+
+fn captured() -> String {
+    String::from_utf8_lossy(&logs().0.lock().unwrap()).into_owned()
+}
+
+fn encoded(coding: ContentCoding, body: &[u8]) -> ResponseTemplate {
+    ResponseTemplate::new(200)
+        .insert_header("content-type", "application/json")
+        .insert_header("content-encoding", coding.as_str())
+        .set_body_bytes(coding.encode(body))
+}
+
+async fn upstream(template: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(template).mount(&server).await;
+    server
+}
+
+/// Config that proxies `GET /api/v1/users` to `legacy`, parsing it as JSON.
+fn strict_legacy_config(legacy: &MockServer) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap();
+    route.strict_json = true;
+    config
+}
+
+/// The response as the client received it: reqwest here doesn't decompress.
+async fn get_users(app: &TestApp) -> (u16, Option<String>, Vec<u8>) {
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap().to_string());
+    (response.status().as_u16(), encoding, response.bytes().await.unwrap().to_vec())
+}
+
+fn skipped(scrape: &str, feature: &str, reason: &str) -> f64 {
+    metric_value(
+        scrape,
+        "gateway_body_inspection_skipped_total",
+        &[("feature", feature), ("reason", reason)],
+    )
+}
+
+#[test]
+fn rewrite_round_trips_through_brotli() {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("br"));
+    let original = ContentCoding::Brotli.encode(USERS.as_bytes());
+
+    let rewritten = encoding::rewrite(&mut headers, &original, 1024 * 1024, |body| {
+        let mut users: Value = serde_json::from_slice(&body).unwrap();
+        users["count"] = json!(3);
+        serde_json::to_vec(&users).unwrap()
+    })
+    .unwrap();
+
+    assert_eq!(headers[header::CONTENT_ENCODING], "br");
+    assert_eq!(headers[header::CONTENT_LENGTH], rewritten.len().to_string().as_str());
+    let decoded = ContentCoding::Brotli.decode(&rewritten, 1024 * 1024).unwrap();
+    let users: Value = serde_json::from_slice(&decoded).unwrap();
+    assert_eq!(users["count"], 3);
+    assert_eq!(users["users"][2]["name"], "Hedy");
+}
+
+#[test]
+fn decompression_bombs_stop_at_the_limit() {
+    let bomb = ContentCoding::Gzip.encode(&vec![0; 16 * 1024 * 1024]);
+    assert!(bomb.len() < 64 * 1024, "{} bytes compressed", bomb.len());
+
+    let error = ContentCoding::Gzip.decode(&bomb, 1024 * 1024).unwrap_err();
+    assert!(matches!(error, DecodeError::TooLarge(limit) if limit == 1024 * 1024), "{}", error);
+    assert_eq!(error.reason(), "decompression_limit");
+    assert_eq!(ContentCoding::Gzip.decode(&bomb, 16 * 1024 * 1024).unwrap().len(), 16 * 1024 * 1024);
+}
+
+#[test]
+fn codings_are_read_from_content_encoding() {
+    let coding = |value: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(value));
+        ContentCoding::from_headers(&headers)
+    };
+    assert_eq!(ContentCoding::from_headers(&HeaderMap::new()).unwrap(), ContentCoding::Identity);
+    assert_eq!(coding("GZIP").unwrap(), ContentCoding::Gzip);
+    assert_eq!(coding("identity, br").unwrap(), ContentCoding::Brotli);
+    assert!(matches!(coding("zstd"), Err(DecodeError::Unsupported(_))));
+    assert!(matches!(coding("gzip, br"), Err(DecodeError::Unsupported(_))));
+}
+
+#[tokio::test]
+async fn strict_json_reads_through_gzip_and_relays_the_original_bytes() {
+    let legacy = upstream(encoded(ContentCoding::Gzip, USERS.as_bytes())).await;
+    let app = spawn_app(strict_legacy_config(&legacy)).await;
+
+    let (status, encoding, body) = get_users(&app).await;
+    assert_eq!(status, 200);
+    assert_eq!(encoding.as_deref(), Some("gzip"));
+    assert_eq!(body, ContentCoding::Gzip.encode(USERS.as_bytes()));
+
+    // What gets parsed is the decoded document
+    let legacy = upstream(encoded(ContentCoding::Gzip, br#"{"users": ["#)).await;
+    let app = spawn_app(strict_legacy_config(&legacy)).await;
+    let (status, _, _) = get_users(&app).await;
+    assert_eq!(status, 502);
+}
+
+#[tokio::test]
+async fn undecodable_bodies_skip_inspection_and_are_counted() {
+    let zstd = upstream(
+        ResponseTemplate::new(200)
+            .insert_header("content-type", "application/json")
+            .insert_header("content-encoding", "zstd")
+            .set_body_bytes(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+    )
+    .await;
+    let app = spawn_app(strict_legacy_config(&zstd)).await;
+    let (status, encoding, body) = get_users(&app).await;
+    assert_eq!(status, 200);
+    assert_eq!(encoding.as_deref(), Some("zstd"));
+    assert_eq!(body, [0x28, 0xb5, 0x2f, 0xfd, 0x00]);
+    assert!(skipped(&app.scrape_metrics().await, "strict_json", "unsupported_encoding") >= 1.0);
+
+    let bomb = upstream(encoded(ContentCoding::Gzip, &vec![b' '; 4 * 1024 * 1024])).await;
+    let mut config = strict_legacy_config(&bomb);
+    config.middleware.decompression.max_decoded_size = ByteSize::from_bytes(64 * 1024);
+    let app = spawn_app(config).await;
+    let (status, _, _) = get_users(&app).await;
+    assert_eq!(status, 200);
+    assert!(skipped(&app.scrape_metrics().await, "strict_json", "decompression_limit") >= 1.0);
+}
+
+#[tokio::test]
+async fn mirror_compares_sizes_after_decoding() {
+    logs();
+    // The same document, gzipped by the legacy gateway and brotli'd by the mirror
+    let legacy = upstream(encoded(ContentCoding::Gzip, USERS.as_bytes())).await;
+    let mirror = upstream(encoded(ContentCoding::Brotli, USERS.as_bytes())).await;
+    let mut config = strict_legacy_config(&legacy);
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let app = spawn_app(config).await;
+
+    let (status, _, body) = get_users(&app).await;
+    assert_eq!(status, 200);
+    assert_ne!(body.len(), ContentCoding::Brotli.encode(USERS.as_bytes()).len());
+
+    let expected = format!("mirror_bytes={} main_bytes={} size_delta_bytes=0", USERS.len(), USERS.len());
+    for _ in 0..100 {
+        if captured().contains(&expected) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no size comparison in the logs:\n{}", captured());
+}
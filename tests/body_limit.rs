@@ -0,0 +1,159 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::config::{validation::check, AppConfig, ByteSize, Severity};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn limited_config() -> AppConfig {
+    let mut config = base_config();
+    config.server.max_body_bytes = ByteSize::from_bytes(4096);
+    config
+}
+
+/// A user to create, padded with whitespace to `size` bytes.
+fn user_body(size: usize) -> Vec<u8> {
+    let mut body = br#"{"username":"limits","email":"limits@example.com"}"#.to_vec();
+    body.resize(size.max(body.len()), b' ');
+    body
+}
+
+fn route_limit(config: &mut AppConfig, method: &str, path: &str, limit: u64) {
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.method == method && route.path == path)
+        .unwrap();
+    route.max_body_bytes = Some(ByteSize::from_bytes(limit));
+}
+
+async fn post(app: &TestApp, path: &str, body: Vec<u8>) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url(path))
+        .header("Content-Type", "application/json")
+        .header("X-Gateway-Version", "rust")
+        .header("X-Request-Id", "body-limit")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Streams a user body of `chunks` chunks of `chunk_size` bytes with chunked
+/// transfer encoding, so the gateway only learns its size as it arrives, and
+/// returns the raw response.
+async fn post_chunked(app: &TestApp, path: &str, chunks: usize, chunk_size: usize) -> String {
+    let mut stream = TcpStream::connect(app.addr).await.unwrap();
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\
+         X-Gateway-Version: rust\r\nX-Request-Id: chunked\r\nConnection: close\r\n\r\n",
+        path, app.addr
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    for body in user_body(chunks * chunk_size).chunks(chunk_size) {
+        let chunk = [format!("{:x}\r\n", body.len()).as_bytes(), body, b"\r\n"].concat();
+        if stream.write_all(&chunk).await.is_err() {
+            break;
+        }
+    }
+    let _ = stream.write_all(b"0\r\n\r\n").await;
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn declared_length_over_the_limit_is_rejected_before_proxying() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(201)).mount(&legacy).await;
+    let mut config = limited_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+
+    // Unpinned, so it would be proxied to legacy at 0% rollout
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("X-Request-Id", "body-limit")
+        .body(user_body(8192))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+    assert_eq!(response.headers()["x-request-id"], "body-limit");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "payload_too_large");
+    assert_eq!(body["error"]["limit_bytes"], 4096);
+    assert_eq!(body["error"]["request_id"], "body-limit");
+    assert!(legacy.received_requests().await.unwrap().is_empty());
+
+    assert_eq!(post(&app, "/api/v1/users", user_body(4096)).await.status(), 200);
+    let metrics = app.scrape_metrics().await;
+    assert!(
+        metric_value(
+            &metrics,
+            "gateway_body_limit_rejections_total",
+            &[("route", "/api/v1/users"), ("reason", "content_length")]
+        ) >= 1.0
+    );
+}
+
+#[tokio::test]
+async fn chunked_bodies_are_cut_off_mid_stream() {
+    let app = spawn_app(limited_config()).await;
+
+    let response = post_chunked(&app, "/api/v1/users", 16, 1024).await;
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+    assert!(response.contains("\"code\":\"payload_too_large\""), "{}", response);
+    assert!(response.to_ascii_lowercase().contains("x-request-id: chunked"), "{}", response);
+
+    // Under the limit, a chunked body goes through
+    let response = post_chunked(&app, "/api/v1/users", 3, 1024).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    let metrics = app.scrape_metrics().await;
+    assert!(
+        metric_value(
+            &metrics,
+            "gateway_body_limit_rejections_total",
+            &[("route", "/api/v1/users"), ("reason", "stream")]
+        ) >= 1.0
+    );
+}
+
+#[tokio::test]
+async fn routes_override_the_server_limit_either_way() {
+    let mut config = limited_config();
+    route_limit(&mut config, "POST", "/api/v1/users", 64 * 1024);
+    let app = spawn_app(config.clone()).await;
+
+    // Over the server's 4KiB, under the route's 64KiB
+    assert_eq!(post(&app, "/api/v1/users", user_body(32 * 1024)).await.status(), 200);
+    let response = post_chunked(&app, "/api/v1/users", 32, 1024).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    // Routes without an override keep the server's limit
+    assert_eq!(post(&app, "/admin/config/validate", user_body(8192)).await.status(), 413);
+
+    // A tighter override applies from the next request after a reload
+    route_limit(&mut config, "POST", "/api/v1/users", 1024);
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(post(&app, "/api/v1/users", user_body(2048)).await.status(), 413);
+}
+
+#[test]
+fn zero_limits_are_rejected_by_validation() {
+    let mut config = base_config();
+    config.server.max_body_bytes = ByteSize::from_bytes(0);
+    route_limit(&mut config, "POST", "/api/v1/users", 0);
+    let fields: Vec<(&str, &str)> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(|issue| (issue.section, issue.field))
+        .collect();
+    assert!(fields.contains(&("server", "max_body_bytes")), "{:?}", fields);
+    assert!(fields.contains(&("routes", "max_body_bytes")), "{:?}", fields);
+}
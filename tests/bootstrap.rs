@@ -0,0 +1,98 @@
+mod common;
+
+use common::{base_config, metric_value};
+use project_gateway::{
+    app::create_app,
+    bootstrap::{Bootstrap, STARTING_UP_CODE},
+    config::watcher::ConfigWatcher,
+    listener::ListenerSupervisor,
+    monitoring::PerformanceMonitor,
+    AppState,
+};
+use serde_json::Value;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tempfile::NamedTempFile;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn requests_racing_startup_get_starting_up_until_the_router_takes_over() {
+    let mut config = base_config();
+    config.server.host = "127.0.0.1".to_string();
+    config.server.port = 0;
+    let config_file = NamedTempFile::new().unwrap();
+    let config_watcher = Arc::new(ConfigWatcher::new(config_file.path().to_str().unwrap(), config).unwrap());
+    let state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+
+    // Bound before the router exists, as `main` does
+    let bootstrap = Arc::new(Bootstrap::new());
+    let supervisor = ListenerSupervisor::bind(state.clone(), bootstrap.service()).await.unwrap();
+    let url = format!("http://{}/health", supervisor.local_addr());
+    tokio::spawn(supervisor.run());
+
+    let client = reqwest::Client::new();
+    let started = Arc::new(AtomicBool::new(false));
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            let (client, url, started) = (client.clone(), url.clone(), started.clone());
+            tokio::spawn(async move {
+                let mut answers = Vec::new();
+                // Keep going a little past the hand-over
+                let mut after = 0;
+                while after < 5 {
+                    if started.load(Ordering::SeqCst) {
+                        after += 1;
+                    }
+                    let response = client
+                        .get(&url)
+                        .header("X-Gateway-Version", "rust")
+                        .send()
+                        .await
+                        .expect("an answer, not a dropped connection");
+                    let status = response.status().as_u16();
+                    let retry_after = response.headers().get("retry-after").cloned();
+                    let body = response.text().await.expect("a complete body");
+                    answers.push((status, retry_after, body));
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                answers
+            })
+        })
+        .collect();
+
+    // A deliberately slow initialization
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!bootstrap.is_ready());
+    let app = create_app(state.clone()).await.unwrap();
+    let report = bootstrap.ready(app);
+    started.store(true, Ordering::SeqCst);
+
+    let mut rejected = 0;
+    let mut served = 0;
+    for client in clients {
+        for (status, retry_after, body) in client.await.unwrap() {
+            match status {
+                503 => {
+                    let problem: Value = serde_json::from_str(&body).unwrap();
+                    assert_eq!(problem["code"], STARTING_UP_CODE);
+                    assert_eq!(retry_after.unwrap(), "1");
+                    rejected += 1;
+                }
+                200 => served += 1,
+                other => panic!("unexpected {}: {}", other, body),
+            }
+        }
+    }
+    assert!(rejected > 0 && served >= 20, "{} rejected, {} served", rejected, served);
+    // A rejection racing the hand-over may miss the report, not the metric
+    assert!(report.rejected <= rejected, "{} of {}", report.rejected, rejected);
+    assert!(report.duration >= Duration::from_millis(200));
+
+    let metrics = project_gateway::metrics::render().unwrap();
+    assert_eq!(metric_value(&metrics, "gateway_bootstrap_rejected_requests_total", &[]), rejected as f64);
+    assert!(metric_value(&metrics, "gateway_bootstrap_duration_seconds", &[]) >= 0.2);
+}
@@ -0,0 +1,483 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{CanaryRolloutConfig, HumanDuration, RolloutOverride, StickyCookieConfig, StickyKey},
+    middleware::canary::{
+        decision::{decide, sticky_sample, RequestAttributes, RoutingDecision},
+        forwarder::end_to_end_headers,
+        Backend,
+    },
+};
+use axum::{extract::ConnectInfo, http::Request};
+use serde_json::Value;
+use std::{collections::HashSet, net::SocketAddr};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn canary(enabled: bool, rollout_percentage: f64) -> CanaryRolloutConfig {
+    let mut config = base_config().canary_rollout;
+    config.enabled = enabled;
+    config.rollout_percentage = rollout_percentage;
+    config
+}
+
+fn header(value: &str) -> RequestAttributes<'_> {
+    RequestAttributes {
+        trigger_header: Some(value),
+        sticky_sample: None,
+        sticky_cookie: None,
+    }
+}
+
+// Decision engine
+
+#[test]
+fn disabled_short_circuits_everything() {
+    let config = canary(false, 0.0);
+    assert_eq!(decide(&header("legacy"), &config, 0.99), RoutingDecision::Disabled);
+    assert_eq!(decide(&RequestAttributes::default(), &config, 0.99), RoutingDecision::Disabled);
+    assert_eq!(RoutingDecision::Disabled.backend(), Backend::Rust);
+    assert_eq!(RoutingDecision::Disabled.reason(), "canary_disabled");
+}
+
+#[test]
+fn header_override_beats_rollout_case_insensitively() {
+    let all_legacy = canary(true, 0.0);
+    let all_rust = canary(true, 100.0);
+
+    for value in ["rust", "RUST", "Rust"] {
+        assert_eq!(
+            decide(&header(value), &all_legacy, 0.5),
+            RoutingDecision::HeaderOverride(Backend::Rust)
+        );
+    }
+    for value in ["legacy", "LEGACY"] {
+        assert_eq!(
+            decide(&header(value), &all_rust, 0.5),
+            RoutingDecision::HeaderOverride(Backend::Legacy)
+        );
+    }
+    assert_eq!(RoutingDecision::HeaderOverride(Backend::Legacy).reason(), "header_override");
+}
+
+#[test]
+fn unrecognized_header_falls_through_to_rollout() {
+    let config = canary(true, 0.0);
+    let decision = decide(&header("python"), &config, 0.5);
+    assert_eq!(decision.backend(), Backend::Legacy);
+    assert_eq!(decision.reason(), "rollout");
+}
+
+#[test]
+fn rollout_compares_sample_against_percentage() {
+    let config = canary(true, 25.0);
+    assert_eq!(
+        decide(&RequestAttributes::default(), &config, 0.249),
+        RoutingDecision::Rollout {
+            backend: Backend::Rust,
+            sample: 24.9
+        }
+    );
+    // The boundary itself goes to legacy
+    assert_eq!(decide(&RequestAttributes::default(), &config, 0.25).backend(), Backend::Legacy);
+
+    assert_eq!(decide(&RequestAttributes::default(), &canary(true, 0.0), 0.0).backend(), Backend::Legacy);
+    assert_eq!(decide(&RequestAttributes::default(), &canary(true, 100.0), 0.999_999).backend(), Backend::Rust);
+}
+
+#[test]
+fn rollout_share_tracks_percentage() {
+    for percentage in [0.0, 10.0, 50.0, 90.0, 100.0] {
+        let config = canary(true, percentage);
+        let rust = (0..1000)
+            .map(|i| decide(&RequestAttributes::default(), &config, i as f64 / 1000.0))
+            .filter(|decision| decision.backend() == Backend::Rust)
+            .count();
+        assert_eq!(rust as f64, percentage * 10.0);
+    }
+}
+
+fn sticky(key: &str) -> CanaryRolloutConfig {
+    let mut config = canary(true, 30.0);
+    config.sticky_key = Some(key.parse().unwrap());
+    config.sticky_seed = "seed".to_string();
+    config
+}
+
+/// Keys sent to Rust out of `session-0` to `session-999`.
+fn rust_keys(config: &CanaryRolloutConfig) -> HashSet<usize> {
+    (0..1000)
+        .filter(|i| {
+            let request = Request::get("/").header("x-session-id", format!("session-{}", i)).body(()).unwrap();
+            let attributes = RequestAttributes::from_request(&request, config, &[]);
+            // The random draw never matters once the key is present
+            let backend = decide(&attributes, config, 0.0).backend();
+            assert_eq!(decide(&attributes, config, 0.999).backend(), backend);
+            backend == Backend::Rust
+        })
+        .collect()
+}
+
+#[test]
+fn sticky_keys_keep_their_backend_and_only_move_to_rust_as_rollout_grows() {
+    let mut config = sticky("header:X-Session-Id");
+    let at_30 = rust_keys(&config);
+    assert!((250..350).contains(&at_30.len()), "{} keys on rust", at_30.len());
+    // Another replica with the same seed agrees
+    assert_eq!(rust_keys(&config.clone()), at_30);
+
+    config.rollout_percentage = 60.0;
+    let at_60 = rust_keys(&config);
+    assert!(at_30.is_subset(&at_60));
+    assert!((550..650).contains(&at_60.len()), "{} keys on rust", at_60.len());
+
+    config.sticky_seed = "reshuffled".to_string();
+    assert_ne!(rust_keys(&config), at_60);
+}
+
+#[test]
+fn sticky_key_sources_fall_back_to_random_when_absent() {
+    let expected = Some(sticky_sample("seed", "abc"));
+
+    let config = sticky("cookie:gw_session");
+    let request = Request::get("/").header("cookie", "theme=dark; gw_session=abc").body(()).unwrap();
+    assert_eq!(RequestAttributes::from_request(&request, &config, &[]).sticky_sample, expected);
+
+    let config = sticky("client_ip");
+    let mut request = Request::get("/").header("x-forwarded-for", "abc").body(()).unwrap();
+    request.extensions_mut().insert(ConnectInfo("127.0.0.1:4000".parse::<SocketAddr>().unwrap()));
+    assert_eq!(
+        RequestAttributes::from_request(&request, &config, &["127.0.0.1/32".to_string()]).sticky_sample,
+        expected
+    );
+
+    // Without the key the request is sampled, and the trigger header still wins
+    let request = Request::get("/").header("X-Gateway-Version", "legacy").body(()).unwrap();
+    let attributes = RequestAttributes::from_request(&request, &config, &[]);
+    assert_eq!(attributes.sticky_sample, None);
+    assert_eq!(decide(&attributes, &canary(true, 100.0), 0.5), RoutingDecision::HeaderOverride(Backend::Legacy));
+    assert_eq!(decide(&RequestAttributes::default(), &config, 0.1).backend(), Backend::Rust);
+    assert_eq!(decide(&RequestAttributes::default(), &config, 0.9).backend(), Backend::Legacy);
+}
+
+#[test]
+fn sticky_key_syntax_is_checked() {
+    assert_eq!("client_ip".parse::<StickyKey>(), Ok(StickyKey::ClientIp));
+    assert_eq!("header:X-Session-Id".parse::<StickyKey>(), Ok(StickyKey::Header("x-session-id".to_string())));
+    assert_eq!("cookie:gw_session".parse::<StickyKey>(), Ok(StickyKey::Cookie("gw_session".to_string())));
+    for invalid in ["ip", "header:bad name", "cookie:", "cookie:a=b"] {
+        assert!(invalid.parse::<StickyKey>().is_err(), "{}", invalid);
+    }
+
+    let mut value = serde_json::to_value(canary(true, 10.0)).unwrap();
+    value["sticky_key"] = "cookie:gw_session".into();
+    let config: CanaryRolloutConfig = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(config.sticky_key, Some(StickyKey::Cookie("gw_session".to_string())));
+    value["sticky_key"] = "session".into();
+    assert!(serde_json::from_value::<CanaryRolloutConfig>(value).is_err());
+}
+
+fn cookie(backend: Backend, trigger_header: Option<&str>) -> RequestAttributes<'_> {
+    RequestAttributes {
+        trigger_header,
+        sticky_sample: None,
+        sticky_cookie: Some(backend),
+    }
+}
+
+#[test]
+fn sticky_cookie_beats_the_roll_but_not_the_trigger_header() {
+    let config = canary(true, 50.0);
+    assert_eq!(decide(&cookie(Backend::Rust, None), &config, 0.99), RoutingDecision::StickyCookie(Backend::Rust));
+    assert_eq!(decide(&cookie(Backend::Legacy, None), &config, 0.0), RoutingDecision::StickyCookie(Backend::Legacy));
+    assert_eq!(RoutingDecision::StickyCookie(Backend::Rust).reason(), "sticky_cookie");
+    assert_eq!(
+        decide(&cookie(Backend::Legacy, Some("rust")), &config, 0.99),
+        RoutingDecision::HeaderOverride(Backend::Rust)
+    );
+
+    // A backend left with no traffic no longer holds its sessions
+    assert_eq!(
+        decide(&cookie(Backend::Rust, None), &canary(true, 0.0), 0.5),
+        RoutingDecision::Rollout { backend: Backend::Legacy, sample: 50.0 }
+    );
+    assert_eq!(decide(&cookie(Backend::Legacy, None), &canary(true, 100.0), 0.5).backend(), Backend::Rust);
+
+    let request = Request::get("/").header("cookie", "gw_backend=rust").body(()).unwrap();
+    assert_eq!(RequestAttributes::from_request(&request, &config, &[]).sticky_cookie, None);
+    let mut config = config;
+    config.sticky_cookie = Some(StickyCookieConfig::default());
+    assert_eq!(RequestAttributes::from_request(&request, &config, &[]).sticky_cookie, Some(Backend::Rust));
+    let request = Request::get("/").header("cookie", "gw_backend=python").body(()).unwrap();
+    assert_eq!(RequestAttributes::from_request(&request, &config, &[]).sticky_cookie, None);
+}
+
+fn rollout_override(path_prefix: &str, method: Option<&str>, percentage: f64) -> RolloutOverride {
+    RolloutOverride {
+        path_prefix: path_prefix.to_string(),
+        method: method.map(str::to_string),
+        percentage,
+    }
+}
+
+#[test]
+fn route_overrides_match_the_longest_prefix_by_segment() {
+    let mut config = canary(true, 20.0);
+    config.route_overrides = vec![
+        rollout_override("/api/v1/users", None, 50.0),
+        rollout_override("/api/v1/payments", None, 5.0),
+        rollout_override("/api/v1/payments", Some("post"), 1.0),
+        rollout_override("/api/v1/payments/refunds/", None, 0.0),
+    ];
+
+    for (method, path, percentage, group) in [
+        ("GET", "/api/v1/users", 50.0, "/api/v1/users"),
+        ("DELETE", "/api/v1/users/42", 50.0, "/api/v1/users"),
+        ("GET", "/api/v1/usersettings", 20.0, "default"),
+        ("GET", "/api/v1/payments/7", 5.0, "/api/v1/payments"),
+        ("POST", "/api/v1/payments/7", 1.0, "POST /api/v1/payments"),
+        ("POST", "/api/v1/payments/refunds/7", 0.0, "/api/v1/payments/refunds/"),
+        ("GET", "/health", 20.0, "default"),
+    ] {
+        assert_eq!(config.percentage_for(method, path, 20.0), percentage, "{} {}", method, path);
+        assert_eq!(config.route_group(method, path), group, "{} {}", method, path);
+    }
+}
+
+#[test]
+fn hop_by_hop_headers_are_not_forwarded() {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("connection", "keep-alive, x-session-hint".parse().unwrap());
+    headers.insert("x-session-hint", "1".parse().unwrap());
+    headers.insert("transfer-encoding", "chunked".parse().unwrap());
+    headers.insert("host", "gateway.internal".parse().unwrap());
+    headers.insert("authorization", "Bearer token".parse().unwrap());
+
+    let forwarded = end_to_end_headers(&headers);
+    assert_eq!(forwarded.len(), 1);
+    assert!(forwarded.contains_key("authorization"));
+}
+
+// Characterization of the middleware as a whole
+
+async fn app_with_legacy(enabled: bool, rollout_percentage: f64) -> (TestApp, MockServer) {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "served_by": "legacy" })))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.enabled = enabled;
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    (spawn_app(config).await, legacy)
+}
+
+async fn served_by(app: &TestApp, trigger: Option<&str>) -> &'static str {
+    let mut request = reqwest::Client::new().get(app.url("/api/v1/users"));
+    if let Some(trigger) = trigger {
+        request = request.header("X-Gateway-Version", trigger);
+    }
+    let body: Value = request.send().await.unwrap().json().await.unwrap();
+    if body["served_by"] == "legacy" {
+        "legacy"
+    } else {
+        "rust"
+    }
+}
+
+#[tokio::test]
+async fn disabled_canary_never_proxies() {
+    let (app, legacy) = app_with_legacy(false, 0.0).await;
+    assert_eq!(served_by(&app, None).await, "rust");
+    assert_eq!(served_by(&app, Some("legacy")).await, "rust");
+    assert!(legacy.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn trigger_header_overrides_rollout() {
+    let (app, _legacy) = app_with_legacy(true, 0.0).await;
+    assert_eq!(served_by(&app, None).await, "legacy");
+    assert_eq!(served_by(&app, Some("Rust")).await, "rust");
+
+    let (app, _legacy) = app_with_legacy(true, 100.0).await;
+    assert_eq!(served_by(&app, None).await, "rust");
+    assert_eq!(served_by(&app, Some("LEGACY")).await, "legacy");
+    assert_eq!(served_by(&app, Some("other")).await, "rust");
+}
+
+#[tokio::test]
+async fn sticky_sessions_stay_on_one_backend() {
+    let (app, legacy) = app_with_legacy(true, 50.0).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 50.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.sticky_key = Some("header:X-Session-Id".parse().unwrap());
+    app.state.config_watcher.apply(config).await;
+
+    let client = reqwest::Client::new();
+    let mut backends = HashSet::new();
+    for session in 0..10 {
+        let session = format!("session-{}", session);
+        let expected = if sticky_sample("", &session) < 0.5 { "rust" } else { "legacy" };
+        for _ in 0..3 {
+            let body: Value = client
+                .get(app.url("/api/v1/users"))
+                .header("X-Session-Id", &session)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let served_by = if body["served_by"] == "legacy" { "legacy" } else { "rust" };
+            assert_eq!(served_by, expected, "{}", session);
+        }
+        backends.insert(expected);
+    }
+    assert_eq!(backends.len(), 2);
+}
+
+/// Who served a request carrying `cookie`, and the assignment cookie set
+/// on the response, if any.
+async fn with_cookie(app: &TestApp, cookie: Option<&str>, trigger: Option<&str>) -> (&'static str, Option<String>) {
+    let mut request = reqwest::Client::new().get(app.url("/api/v1/users"));
+    if let Some(cookie) = cookie {
+        request = request.header("Cookie", cookie);
+    }
+    if let Some(trigger) = trigger {
+        request = request.header("X-Gateway-Version", trigger);
+    }
+    let response = request.send().await.unwrap();
+    let set_cookie = response
+        .headers()
+        .get("set-cookie")
+        .map(|value| value.to_str().unwrap().to_string());
+    let body: Value = response.json().await.unwrap();
+    (if body["served_by"] == "legacy" { "legacy" } else { "rust" }, set_cookie)
+}
+
+#[tokio::test]
+async fn sticky_cookie_is_issued_honored_and_rewritten_after_a_rollback() {
+    let (app, legacy) = app_with_legacy(true, 50.0).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 50.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.sticky_cookie = Some(StickyCookieConfig {
+        ttl: HumanDuration::from_secs(2 * 3600),
+        secure: false,
+        ..Default::default()
+    });
+    app.state.config_watcher.apply(config.clone()).await;
+
+    // First contact rolls and records the assignment
+    let (backend, set_cookie) = with_cookie(&app, None, None).await;
+    assert_eq!(
+        set_cookie.as_deref(),
+        Some(format!("gw_backend={}; Path=/; Max-Age=7200; HttpOnly; SameSite=Lax", backend).as_str())
+    );
+
+    // The cookie wins over the roll every time and isn't reissued
+    for backend in ["rust", "legacy"] {
+        let cookie = format!("theme=dark; gw_backend={}", backend);
+        for _ in 0..5 {
+            assert_eq!(with_cookie(&app, Some(&cookie), None).await, (backend, None));
+        }
+    }
+    // The trigger header still beats it
+    assert_eq!(with_cookie(&app, Some("gw_backend=legacy"), Some("rust")).await, ("rust", None));
+
+    // After a rollback to 0% a Rust cookie is ignored and rewritten
+    config.canary_rollout.rollout_percentage = 0.0;
+    app.state.config_watcher.apply(config).await;
+    let (backend, set_cookie) = with_cookie(&app, Some("gw_backend=rust"), None).await;
+    assert_eq!(backend, "legacy");
+    assert!(set_cookie.unwrap().starts_with("gw_backend=legacy;"));
+}
+
+#[tokio::test]
+async fn route_overrides_neither_follow_nor_set_the_sticky_cookie() {
+    let (app, legacy) = app_with_legacy(true, 50.0).await;
+    let mut config = base_config();
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.sticky_key = Some("header:X-Session-Id".parse().unwrap());
+    config.canary_rollout.sticky_cookie = Some(StickyCookieConfig {
+        secure: false,
+        ..Default::default()
+    });
+    config.canary_rollout.route_overrides = vec![
+        rollout_override("/api/v1/users", None, 99.0),
+        rollout_override("/api/v1/health", None, 1.0),
+    ];
+    app.state.config_watcher.apply(config).await;
+    // Lands on Rust at 99% and on legacy at 1%
+    let session = (0..)
+        .map(|session| format!("session-{}", session))
+        .find(|session| (0.01..0.99).contains(&sticky_sample("", session)))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    for (path, cookie, expected) in [
+        ("/api/v1/users", "gw_backend=legacy", "rust"),
+        ("/api/v1/health", "gw_backend=rust", "legacy"),
+    ] {
+        let response = client
+            .get(app.url(path))
+            .header("X-Session-Id", &session)
+            .header("Cookie", cookie)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers().get("set-cookie").is_none(), "{}", path);
+        let body: Value = response.json().await.unwrap();
+        let served_by = if body["served_by"] == "legacy" { "legacy" } else { "rust" };
+        assert_eq!(served_by, expected, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn proxied_requests_are_tagged_and_stripped_of_hop_headers() {
+    let (app, legacy) = app_with_legacy(true, 0.0).await;
+    reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("proxy-authorization", "Basic secret")
+        .send()
+        .await
+        .unwrap();
+
+    let received = legacy.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].headers["x-routed-by"], "Rust-Gateway-Canary");
+    assert!(!received[0].headers.contains_key("proxy-authorization"));
+}
+
+#[tokio::test]
+async fn request_bodies_reach_legacy_byte_for_byte() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(201)).mount(&legacy).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    let json = br#"{"username":"forwarded","email":"forwarded@example.com"}"#.to_vec();
+    let binary: Vec<u8> = (0..=255u8).cycle().take(64 * 1024).collect();
+    for (content_type, body) in [("application/json", json), ("application/octet-stream", binary)] {
+        let response = client
+            .post(app.url("/api/v1/users"))
+            .header("Content-Type", content_type)
+            .body(body.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+
+        let received = legacy.received_requests().await.unwrap();
+        let request = received.last().unwrap();
+        assert_eq!(request.body, body);
+        assert_eq!(request.headers["content-type"], content_type);
+        assert_eq!(request.headers["content-length"], body.len().to_string().as_str());
+    }
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use common::base_config;
+use project_gateway::middleware::canary::simulate::{read_access_log, sweep, ReplayRecord};
+use serde_json::json;
+use std::{collections::HashMap, io::Write};
+
+const REQUESTS: usize = 20_000;
+
+/// A synthetic log: 60/40 across two routes, 500 sticky keys, 5% of requests
+/// pinned to Rust and 2% to legacy through the trigger header.
+fn synthetic_log() -> Vec<ReplayRecord> {
+    (0..REQUESTS)
+        .map(|i| {
+            let path = if i % 5 < 3 { "/api/v1/users" } else { "/api/v1/orders" };
+            let mut headers = HashMap::new();
+            match i % 100 {
+                0..=4 => {
+                    headers.insert("X-Gateway-Version".to_string(), "rust".to_string());
+                }
+                5..=6 => {
+                    headers.insert("x-gateway-version".to_string(), "Legacy".to_string());
+                }
+                _ => {}
+            }
+            ReplayRecord {
+                method: Some("GET".to_string()),
+                path: path.to_string(),
+                route: None,
+                sticky_key: Some(format!("client-{}", i % 500)),
+                headers,
+            }
+        })
+        .collect()
+}
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64, what: &str) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{}: {:.2}% is not within {} points of {}%",
+        what,
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn replay_reports_the_intended_split() {
+    let records = synthetic_log();
+    let runs = sweep(&records, &base_config().canary_rollout, &[25.0], 1.5, 7);
+    let run = &runs[0];
+
+    assert_eq!(run.overall.total(), REQUESTS as u64);
+    assert_eq!(run.rollout.total(), (REQUESTS * 93 / 100) as u64);
+    assert_close(run.rollout.rust_percentage(), 25.0, 1.0, "overall");
+
+    assert_eq!(run.routes.len(), 2);
+    assert_eq!(run.routes["/api/v1/users"].total() + run.routes["/api/v1/orders"].total(), run.rollout.total());
+    for (route, counts) in &run.routes {
+        assert_close(counts.rust_percentage(), 25.0, 1.5, route);
+    }
+
+    // Header overrides are reported apart and never count against the target
+    assert_eq!(run.overrides["rust"].rust, (REQUESTS * 5 / 100) as u64);
+    assert_eq!(run.overrides["rust"].legacy, 0);
+    assert_eq!(run.overrides["legacy"].legacy, (REQUESTS * 2 / 100) as u64);
+    assert_eq!(run.overrides["legacy"].rust, 0);
+
+    assert!(run.deviations.is_empty(), "{:?}", run.deviations);
+    // Per-request sampling isn't sticky: nearly every key sees both variants
+    assert_eq!(run.sticky_keys, 500);
+    assert!(run.split_keys > 450, "{} split keys", run.split_keys);
+}
+
+#[test]
+fn sweep_tracks_each_percentage() {
+    let records = synthetic_log();
+    let percentages = [0.0, 1.0, 5.0, 25.0, 50.0, 100.0];
+    let runs = sweep(&records, &base_config().canary_rollout, &percentages, 1.5, 7);
+
+    assert_eq!(runs.len(), percentages.len());
+    for (run, percentage) in runs.iter().zip(percentages) {
+        assert_eq!(run.percentage, percentage);
+        assert_close(run.rollout.rust_percentage(), percentage, 1.0, &format!("{}%", percentage));
+    }
+    assert_eq!(runs[0].rollout.rust, 0);
+    assert_eq!(runs[5].rollout.legacy, 0);
+    assert_eq!(runs[5].split_keys, 0);
+
+    // Same seed, same answer
+    let again = sweep(&records, &base_config().canary_rollout, &percentages, 1.5, 7);
+    assert_eq!(again[3].rollout, runs[3].rollout);
+}
+
+#[test]
+fn sticky_replay_keeps_every_key_on_one_variant() {
+    let records = synthetic_log();
+    let mut config = base_config().canary_rollout;
+    config.sticky_key = Some("client_ip".parse().unwrap());
+    let run = &sweep(&records, &config, &[25.0], 5.0, 7)[0];
+
+    assert_eq!(run.sticky_keys, 500);
+    assert_eq!(run.split_keys, 0);
+    // The split is by key now, so it's only as even as 500 keys allow
+    assert_close(run.rollout.rust_percentage(), 25.0, 5.0, "overall");
+}
+
+#[test]
+fn deviations_beyond_the_tolerance_are_flagged() {
+    let records = synthetic_log();
+    let run = &sweep(&records, &base_config().canary_rollout, &[25.0], 0.0, 7)[0];
+    assert!(run.deviations.iter().any(|deviation| deviation.starts_with("overall:")), "{:?}", run.deviations);
+
+    // Routes with too few requests to judge are never flagged
+    let few = &records[..100];
+    let run = &sweep(few, &base_config().canary_rollout, &[25.0], 0.0, 7)[0];
+    assert!(run.deviations.iter().all(|deviation| deviation.starts_with("overall:")));
+}
+
+#[test]
+fn gateway_json_logs_and_flat_records_both_parse() {
+    let lines = [
+        json!({"timestamp": "2025-07-03T01:00:00Z", "level": "INFO", "fields": {"message": "Request completed", "method": "GET", "path": "/api/v1/users", "status": 200}}),
+        json!({"timestamp": "2025-07-03T01:00:01Z", "level": "INFO", "fields": {"message": "Configuration reloaded"}}),
+        json!({"path": "/api/v1/users/42", "route": "/api/v1/users/:id", "sticky_key": "tenant-1", "headers": {"X-Gateway-Version": "rust"}}),
+    ];
+    let log = lines.iter().map(|line| line.to_string()).collect::<Vec<_>>().join("\n\n");
+    let (records, skipped) = read_access_log(log.as_bytes()).unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(skipped, 1);
+    assert_eq!(records[0].path, "/api/v1/users");
+    assert_eq!(records[1].route.as_deref(), Some("/api/v1/users/:id"));
+
+    let runs = sweep(&records, &base_config().canary_rollout, &[0.0], 1.0, 1);
+    assert_eq!(runs[0].routes["/api/v1/users"].legacy, 1);
+    assert_eq!(runs[0].overrides["rust"].rust, 1);
+
+    assert!(read_access_log("not json".as_bytes()).is_err());
+}
+
+#[test]
+fn cli_prints_a_table_and_fails_on_deviation() {
+    let mut log = tempfile::NamedTempFile::new().unwrap();
+    for record in synthetic_log().iter().take(5_000) {
+        writeln!(log, "{}", json!({"path": record.path, "headers": record.headers})).unwrap();
+    }
+
+    let run = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_project-gateway"))
+            .args(["simulate-canary", "--access-log", log.path().to_str().unwrap()])
+            .args(["--config", "config/default.yaml"])
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--sweep", "1,5,25,50", "--tolerance", "3"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}\n{}", stdout, String::from_utf8_lossy(&output.stderr));
+    for target in ["1%", "5%", "25%", "50%"] {
+        assert!(stdout.lines().any(|line| line.trim_start().starts_with(target)), "{}", stdout);
+    }
+    assert!(stdout.contains("/api/v1/orders"));
+
+    let output = run(&["--percentage", "25", "--tolerance", "0"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("DEVIATION"));
+
+    assert!(!run(&["--percentage", "250"]).status.success());
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A legacy upstream that accepts one connection and reads the request
+/// without answering. wiremock can't tell when a caller gives up, so the
+/// test holds the raw connection to see whether the gateway closes it.
+async fn silent_upstream() -> (String, tokio::task::JoinHandle<TcpStream>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let accepted = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert!(read > 0, "gateway closed the upstream before sending a request");
+            request.extend_from_slice(&buf[..read]);
+        }
+        stream
+    });
+    (url, accepted)
+}
+
+async fn app_with_legacy(url: String) -> TestApp {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = url;
+    spawn_app(config).await
+}
+
+/// Sends `method` to `/api/v1/users` and hangs up once the upstream has it.
+async fn send_and_hang_up(app: &TestApp, method: &str, upstream: tokio::task::JoinHandle<TcpStream>) -> TcpStream {
+    let mut client = TcpStream::connect(app.addr).await.unwrap();
+    let request = format!(
+        "{} /api/v1/users HTTP/1.1\r\nHost: gateway\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{{}}",
+        method
+    );
+    client.write_all(request.as_bytes()).await.unwrap();
+    let upstream = tokio::time::timeout(Duration::from_secs(5), upstream).await.unwrap().unwrap();
+    drop(client);
+    upstream
+}
+
+async fn disconnects(app: &TestApp, method: &str) -> f64 {
+    for _ in 0..100 {
+        let value = metric_value(
+            &app.scrape_metrics().await,
+            "gateway_client_disconnects_total",
+            &[("method", method), ("route", "/api/v1/users"), ("stage", "upstream")],
+        );
+        if value > 0.0 {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    0.0
+}
+
+#[tokio::test]
+async fn abandoned_reads_cancel_the_upstream_call() {
+    let (url, upstream) = silent_upstream().await;
+    let app = app_with_legacy(url).await;
+    let mut upstream = send_and_hang_up(&app, "GET", upstream).await;
+
+    // The gateway gives up its upstream connection rather than wait out the timeout
+    let mut buf = [0u8; 1];
+    let read = tokio::time::timeout(Duration::from_secs(5), upstream.read(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))), "upstream connection left open: {:?}", read);
+    assert_eq!(disconnects(&app, "GET").await, 1.0);
+}
+
+#[tokio::test]
+async fn abandoned_writes_run_to_completion() {
+    let (url, upstream) = silent_upstream().await;
+    let app = app_with_legacy(url).await;
+    let mut upstream = send_and_hang_up(&app, "POST", upstream).await;
+    assert_eq!(disconnects(&app, "POST").await, 1.0);
+
+    // The write is still in flight once the client has gone
+    let mut buf = [0u8; 1];
+    assert!(tokio::time::timeout(Duration::from_millis(300), upstream.read(&mut buf)).await.is_err());
+
+    upstream
+        .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\n{}")
+        .await
+        .unwrap();
+    for _ in 0..100 {
+        let completed = app
+            .state
+            .performance_monitor
+            .get_current_metrics("legacy")
+            .map(|metrics| metrics.request_count)
+            .unwrap_or(0);
+        if completed == 1 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the abandoned write never completed");
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::{
+    client::{ClientError, GatewayClient},
+    config::SloObjective,
+    models::{
+        admin::RollbackRequest,
+        rollout::{RolloutMode, RolloutUpdate},
+        users::CreateUserRequest,
+    },
+};
+
+#[tokio::test]
+async fn api_and_health_endpoints_round_trip() {
+    let mut config = base_config();
+    config.slo.objectives = vec![SloObjective {
+        name: "users".to_string(),
+        method: None,
+        route: "/api/v1/users".to_string(),
+        availability: Some(99.9),
+        latency: None,
+        window: "30d".parse().unwrap(),
+    }];
+    let app = spawn_app(config).await;
+    let client = GatewayClient::new(&app.url("/"), None);
+
+    let health = client.health().await.unwrap();
+    assert_eq!(health.status, "healthy");
+    let detailed = client.detailed_health().await.unwrap();
+    assert!(detailed.config_loaded);
+    assert_eq!(detailed.server_config.port, app.state.config_watcher.get_config().await.server.port);
+    // The test server never runs warm-up, so this is the 503 body
+    let readiness = client.readiness().await.unwrap();
+    assert!(!readiness.ready);
+    assert!(readiness.warmup.is_none());
+
+    let first = client.list_users(1).await.unwrap();
+    assert_eq!((first.users.len(), first.total, first.page, first.per_page), (2, 2, 1, 10));
+    assert_eq!(first.users[0].username, "admin");
+    let second = client.list_users(2).await.unwrap();
+    assert!(second.users.is_empty());
+    assert_eq!((second.total, second.page), (2, 2));
+
+    let created = client
+        .create_user(&CreateUserRequest {
+            username: "ada".to_string(),
+            email: "ada@example.com".to_string(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(created.user.username, "ada");
+    assert!(created.user.active);
+
+    let slo = client.slo_status().await.unwrap();
+    assert_eq!(slo.len(), 1);
+    assert_eq!(slo[0].name, "users");
+    assert_eq!(slo[0].requests, 3);
+    assert!(slo[0].availability.is_some());
+    assert!(slo[0].latency.is_none());
+}
+
+#[tokio::test]
+async fn operational_endpoints_round_trip() {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 10.0;
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    config.canary_rollout.webhook_url = String::new();
+    let app = spawn_app(config).await;
+    let client = GatewayClient::new(&app.url(""), None);
+
+    let status = client.gatekeeper_status().await.unwrap();
+    assert_eq!(status.current_rollout_percentage, 10.0);
+    assert!(!status.rollback_triggered);
+
+    let rollout = client.rollout_status().await.unwrap();
+    assert_eq!(rollout.backend, "local");
+    assert_eq!(rollout.state.rollout_percentage, 10.0);
+    assert_eq!(rollout.state.mode, RolloutMode::Automatic);
+
+    let advanced = client.advance_rollout().await.unwrap();
+    assert_eq!(advanced.state.rollout_percentage, 15.0);
+    assert!(advanced.state.version > rollout.state.version);
+
+    let updated = client
+        .update_rollout(&RolloutUpdate {
+            rollout_percentage: Some(40.0),
+            paused: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(updated.state.rollout_percentage, 40.0);
+    assert!(updated.state.paused);
+    assert_eq!(client.rollout_status().await.unwrap().state, updated.state);
+
+    // A paused rollout doesn't advance; the 409 comes back as an API error
+    match client.advance_rollout().await {
+        Err(ClientError::Api { status, .. }) => assert_eq!(status, 409),
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+
+    let rolled_back = client
+        .rollback_rollout(&RollbackRequest {
+            reason: Some("client test".to_string()),
+        })
+        .await
+        .unwrap();
+    assert!(rolled_back.state.rollout_percentage < 40.0);
+    assert_eq!(
+        client.gatekeeper_status().await.unwrap().current_rollout_percentage,
+        rolled_back.state.rollout_percentage
+    );
+
+    let read_only = client.set_read_only(true).await.unwrap();
+    assert!(read_only.enabled);
+    assert!(!read_only.persisted);
+    assert!(client.read_only_status().await.unwrap().enabled);
+    assert!(!client.set_read_only(false).await.unwrap().enabled);
+}
+
+#[tokio::test]
+async fn error_envelopes_become_api_errors() {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    let app = spawn_app(config).await;
+    let client = GatewayClient::new(&app.url(""), None);
+
+    match client.list_users(1).await {
+        Err(ClientError::Api { status, error, .. }) => {
+            assert_eq!(status, 401);
+            let error = error.expect("the gateway answers with its error envelope");
+            assert_eq!(error.code, "authentication_required");
+            assert!(!error.request_id.is_empty());
+        }
+        other => panic!("expected a 401, got {other:?}"),
+    }
+}
@@ -0,0 +1,285 @@
+mod common;
+
+use common::base_config;
+use project_gateway::{
+    app::create_app,
+    config::{watcher::ConfigWatcher, AppConfig, ClientAuthConfig, ClientCertForwarding, TlsConfig},
+    monitoring::PerformanceMonitor,
+    tls::{
+        self,
+        client_cert::{self, ClientCertIdentity},
+        TlsManager,
+    },
+    AppState,
+};
+use rcgen::{BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair, SanType};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use sha2::{Digest, Sha256};
+use std::{net::SocketAddr, sync::Arc};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsConnector;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+struct TestPki {
+    ca: rcgen::Certificate,
+    ca_key: KeyPair,
+    dir: TempDir,
+}
+
+struct Issued {
+    der: Vec<u8>,
+    pem: String,
+    key: KeyPair,
+}
+
+impl TestPki {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, "Test CA");
+        let ca_key = KeyPair::generate().unwrap();
+        let ca = params.self_signed(&ca_key).unwrap();
+        Self {
+            ca,
+            ca_key,
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    fn issue(&self, params: CertificateParams) -> Issued {
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        Issued {
+            der: cert.der().to_vec(),
+            pem: cert.pem(),
+            key,
+        }
+    }
+
+    /// Workload certificate with a SPIFFE URI and a DNS name.
+    fn client(&self) -> Issued {
+        let mut params = CertificateParams::new(vec!["billing.internal".to_string()]).unwrap();
+        params
+            .subject_alt_names
+            .push(SanType::URI("spiffe://example.org/billing".try_into().unwrap()));
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, "billing");
+        name.push(DnType::OrganizationName, "Example");
+        params.distinguished_name = name;
+        self.issue(params)
+    }
+
+    fn tls_config(&self, required: bool) -> TlsConfig {
+        let server = self.issue(CertificateParams::new(vec!["localhost".to_string()]).unwrap());
+        let write = |name: &str, contents: &str| {
+            let path = self.dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.display().to_string()
+        };
+        TlsConfig {
+            enabled: true,
+            cert_path: write("server.crt", &server.pem),
+            key_path: write("server.key", &server.key.serialize_pem()),
+            certificates: Vec::new(),
+            expiry_warning_days: 30,
+            reload_interval_seconds: 30,
+            client_auth: Some(ClientAuthConfig {
+                ca_path: write("ca.crt", &self.ca.pem()),
+                required,
+            }),
+        }
+    }
+
+    fn connector(&self, client: Option<&Issued>) -> TlsConnector {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(CertificateDer::from(self.ca.der().to_vec())).unwrap();
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let builder = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match client {
+            Some(client) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from(client.der.clone())],
+                    PrivateKeyDer::try_from(client.key.serialize_der()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+}
+
+async fn spawn_tls_app(config: AppConfig, tls: &TlsConfig) -> SocketAddr {
+    let manager = Arc::new(TlsManager::new(tls).unwrap());
+    let config_file = tempfile::NamedTempFile::new().unwrap();
+    let config_watcher = Arc::new(ConfigWatcher::new(config_file.path().to_str().unwrap(), config).unwrap());
+    let mut state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+    state.tls = Some(manager.clone());
+
+    let app = create_app(state).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let acceptor = manager.acceptor().unwrap();
+    tokio::spawn(async move {
+        let _config_file = config_file;
+        tls::serve(listener, app, acceptor).await.unwrap();
+    });
+    addr
+}
+
+/// Sends `GET /api/v1/users` with `headers` over TLS and returns the status
+/// line, or the I/O error if the handshake or exchange failed.
+async fn get_users(addr: SocketAddr, connector: TlsConnector, headers: &str) -> std::io::Result<String> {
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+    stream
+        .write_all(format!("GET /api/v1/users HTTP/1.0\r\nHost: localhost\r\n{}\r\n", headers).as_bytes())
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response.lines().next().unwrap_or_default().to_string())
+}
+
+async fn legacy_gateway() -> MockServer {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&legacy)
+        .await;
+    legacy
+}
+
+fn proxying_config(legacy: &MockServer, forwarding: ClientCertForwarding) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.client_cert_forwarding = forwarding;
+    config
+}
+
+async fn last_received(legacy: &MockServer) -> wiremock::Request {
+    legacy.received_requests().await.unwrap().pop().expect("request reached the legacy gateway")
+}
+
+fn header<'a>(request: &'a wiremock::Request, name: &str) -> Option<&'a str> {
+    request.headers.get(name).map(|value| value.to_str().unwrap())
+}
+
+#[test]
+fn identity_is_read_from_the_certificate() {
+    let pki = TestPki::new();
+    let client = pki.client();
+    let identity = ClientCertIdentity::from_der(&client.der).unwrap();
+
+    assert!(identity.subject.contains("CN=billing"), "{}", identity.subject);
+    assert!(identity.subject.contains("O=Example"), "{}", identity.subject);
+    assert_eq!(identity.sans, ["DNS:billing.internal", "URI:spiffe://example.org/billing"]);
+    assert!(!identity.serial.is_empty() && identity.serial.chars().all(|c| c.is_ascii_hexdigit()));
+    let spki: String = Sha256::digest(client.key.public_key_der()).iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(identity.spki_sha256, spki);
+    assert_eq!(identity.pem.trim(), client.pem.trim());
+}
+
+#[test]
+fn spoofed_headers_are_stripped_even_when_forwarding_is_off() {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("x-client-cert-subject", "CN=admin".parse().unwrap());
+    headers.insert("x-forwarded-client-cert", "Hash=00".parse().unwrap());
+    headers.insert("x-request-id", "abc".parse().unwrap());
+
+    let identity = ClientCertIdentity::from_der(&TestPki::new().client().der).unwrap();
+    client_cert::apply(&mut headers, Some(&identity), ClientCertForwarding::Off);
+    assert_eq!(headers.len(), 1);
+    assert!(headers.contains_key("x-request-id"));
+}
+
+#[tokio::test]
+async fn verified_identity_reaches_the_legacy_gateway_as_headers() {
+    let pki = TestPki::new();
+    let client = pki.client();
+    let legacy = legacy_gateway().await;
+    let addr = spawn_tls_app(proxying_config(&legacy, ClientCertForwarding::Headers), &pki.tls_config(false)).await;
+    let spoofed = "X-Client-Cert-Subject: CN=admin, O=Example\r\nX-Forwarded-Client-Cert: Subject=\"CN=admin\"\r\n";
+
+    let status = get_users(addr, pki.connector(Some(&client)), spoofed).await.unwrap();
+    assert!(status.contains("200"), "{}", status);
+    let received = last_received(&legacy).await;
+    let identity = ClientCertIdentity::from_der(&client.der).unwrap();
+    assert_eq!(header(&received, "x-client-cert-subject"), Some(identity.subject.as_str()));
+    assert_eq!(
+        header(&received, "x-client-cert-san"),
+        Some("DNS:billing.internal, URI:spiffe://example.org/billing")
+    );
+    assert_eq!(header(&received, "x-client-cert-serial"), Some(identity.serial.as_str()));
+    assert_eq!(
+        header(&received, "x-client-cert-fingerprint"),
+        Some(format!("sha256:{}", identity.spki_sha256).as_str())
+    );
+    assert!(header(&received, "x-forwarded-client-cert").is_none());
+
+    // Without a certificate the client's own values are dropped, not passed on
+    let status = get_users(addr, pki.connector(None), spoofed).await.unwrap();
+    assert!(status.contains("200"), "{}", status);
+    let received = last_received(&legacy).await;
+    assert!(received.headers.keys().all(|name| !client_cert::is_identity_header(name)));
+}
+
+#[tokio::test]
+async fn xfcc_carries_the_url_encoded_certificate() {
+    let pki = TestPki::new();
+    let client = pki.client();
+    let legacy = legacy_gateway().await;
+    let addr = spawn_tls_app(proxying_config(&legacy, ClientCertForwarding::Xfcc), &pki.tls_config(false)).await;
+
+    get_users(addr, pki.connector(Some(&client)), "X-Forwarded-Client-Cert: Hash=00\r\n")
+        .await
+        .unwrap();
+    let received = last_received(&legacy).await;
+    let xfcc = header(&received, "x-forwarded-client-cert").unwrap();
+    let hash: String = Sha256::digest(&client.der).iter().map(|b| format!("{:02x}", b)).collect();
+    assert!(xfcc.starts_with(&format!("Hash={};Cert=\"-----BEGIN%20CERTIFICATE-----%0A", hash)), "{}", xfcc);
+    assert!(xfcc.contains(";Subject=\"CN=billing"), "{}", xfcc);
+    assert!(xfcc.ends_with(";URI=spiffe://example.org/billing;DNS=billing.internal"), "{}", xfcc);
+    assert!(header(&received, "x-client-cert-subject").is_none());
+}
+
+#[tokio::test]
+async fn client_certificate_can_stand_in_for_a_token() {
+    let pki = TestPki::new();
+    let client = pki.client();
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.jwt_secrets = vec!["client-cert-test-secret".to_string()];
+    config.middleware.auth.client_certificates = true;
+    // Certificates carry no roles; only authentication is under test here
+    for route in &mut config.routes {
+        route.authorization = None;
+    }
+    let addr = spawn_tls_app(config, &pki.tls_config(false)).await;
+    let rust = "X-Gateway-Version: rust\r\n";
+
+    let status = get_users(addr, pki.connector(Some(&client)), rust).await.unwrap();
+    assert!(status.contains("200"), "{}", status);
+    let status = get_users(addr, pki.connector(None), rust).await.unwrap();
+    assert!(status.contains("401"), "{}", status);
+}
+
+#[tokio::test]
+async fn required_client_auth_refuses_anonymous_and_foreign_clients() {
+    let pki = TestPki::new();
+    let addr = spawn_tls_app(base_config(), &pki.tls_config(true)).await;
+    let rust = "X-Gateway-Version: rust\r\n";
+
+    assert!(get_users(addr, pki.connector(None), rust).await.is_err());
+    let foreign = TestPki::new();
+    let stranger = foreign.client();
+    assert!(get_users(addr, pki.connector(Some(&stranger)), rust).await.is_err());
+    let status = get_users(addr, pki.connector(Some(&pki.client())), rust).await.unwrap();
+    assert!(status.contains("200"), "{}", status);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{base_config, jwt, jwt_accepted, jwt_auth, metric_value, spawn_app, spawn_app_with, JWT_NOW};
+use project_gateway::{
+    clock::{check_skew, Clock},
+    config::{validation::check, AuthConfig, MirrorWindow, Severity},
+    middleware::auth::{validate_token_at, AuthCache},
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn auth(tolerance: &str) -> AuthConfig {
+    AuthConfig {
+        clock_skew_tolerance: tolerance.parse().unwrap(),
+        ..jwt_auth()
+    }
+}
+
+#[test]
+fn time_claims_are_judged_with_the_skew_tolerance() {
+    let auth = auth("60s");
+    assert!(jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW - 60 })));
+    assert!(!jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW - 61 })));
+    assert!(jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 600, "nbf": JWT_NOW + 60 })));
+    assert!(!jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 600, "nbf": JWT_NOW + 61 })));
+    assert!(jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 600, "iat": JWT_NOW + 60 })));
+    assert!(!jwt_accepted(&auth, json!({ "sub": "a", "exp": JWT_NOW + 600, "iat": JWT_NOW + 61 })));
+
+    // A cached token stops being accepted at the same boundary
+    let cache = AuthCache::new();
+    let expiring = jwt(json!({ "sub": "a", "exp": JWT_NOW }));
+    assert!(validate_token_at(&cache, &auth, &expiring, JWT_NOW).is_some());
+    assert!(validate_token_at(&cache, &auth, &expiring, JWT_NOW + 60).is_some());
+    assert!(validate_token_at(&cache, &auth, &expiring, JWT_NOW + 61).is_none());
+
+    let strict = self::auth("5s");
+    assert!(jwt_accepted(&strict, json!({ "sub": "a", "exp": JWT_NOW - 5 })));
+    assert!(!jwt_accepted(&strict, json!({ "sub": "a", "exp": JWT_NOW - 6 })));
+
+    let mut config = base_config();
+    config.middleware.auth = self::auth("10m");
+    assert!(check(&config).iter().any(|issue| issue.severity == Severity::Error
+        && issue.section == "middleware.auth"
+        && issue.field == "clock_skew_tolerance"));
+}
+
+/// A time source whose `Date` header is `offset` away from real time.
+async fn time_source(offset: chrono::Duration) -> MockServer {
+    let server = MockServer::start().await;
+    let date = (Utc::now() + offset).format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(200).insert_header("Date", date.as_str()))
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn pinned_json(url: String) -> Value {
+    reqwest::Client::new()
+        .get(url)
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn skewed_time_source_degrades_detailed_health() {
+    let source = time_source(chrono::Duration::minutes(-5)).await;
+    let mut config = base_config();
+    config.clock.time_source_url = Some(source.uri());
+    let app = spawn_app(config).await;
+
+    let body = pinned_json(app.url("/api/v1/health")).await;
+    assert_eq!(body["status"], "healthy");
+    assert!(body.get("clock_skew").is_none());
+
+    // Our clock is five minutes ahead of the source
+    let skew = check_skew(&app.state).await.unwrap();
+    assert!((299.0..=301.0).contains(&skew.skew_seconds), "{}", skew.skew_seconds);
+    assert!(!skew.within_tolerance);
+
+    let body = pinned_json(app.url("/api/v1/health")).await;
+    assert_eq!(body["status"], "degraded");
+    assert_eq!(body["clock_skew"]["source"], source.uri());
+    assert_eq!(body["clock_skew"]["within_tolerance"], false);
+    let metrics = app.scrape_metrics().await;
+    assert!((299.0..=301.0).contains(&metric_value(&metrics, "gateway_clock_skew_seconds", &[("source", &source.uri())])));
+}
+
+#[tokio::test]
+async fn legacy_gateway_is_the_fallback_time_source() {
+    let legacy = time_source(chrono::Duration::zero()).await;
+    let mut config = base_config();
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+
+    let app = spawn_app(config.clone()).await;
+    let skew = check_skew(&app.state).await.unwrap();
+    assert_eq!(skew.source, legacy.uri());
+    assert!(skew.within_tolerance, "{}", skew.skew_seconds);
+    assert_eq!(pinned_json(app.url("/api/v1/health")).await["status"], "healthy");
+
+    // A gateway clock that drifted behind is caught against the same source
+    let drifted = spawn_app_with(config, |state| {
+        state.clock = Arc::new(Clock::offset_by(chrono::Duration::seconds(-90)));
+    })
+    .await;
+    let skew = check_skew(&drifted.state).await.unwrap();
+    assert!((-91.0..=-89.0).contains(&skew.skew_seconds), "{}", skew.skew_seconds);
+    assert_eq!(pinned_json(drifted.url("/api/v1/health")).await["status"], "degraded");
+}
+
+#[tokio::test]
+async fn schedules_follow_the_gateway_clock() {
+    // 2025-07-09 is a Wednesday
+    let wednesday_noon: DateTime<Utc> = "2025-07-09T12:00:00Z".parse().unwrap();
+    let mut config = base_config();
+    config.mirror.sample_percentage = 10.0;
+    config.mirror.schedule = vec![MirrorWindow {
+        window: "Wed 11:00-13:00".parse().unwrap(),
+        sample_percentage: 25.0,
+    }];
+    let app = spawn_app_with(config, |state| {
+        state.clock = Arc::new(Clock::offset_by(wednesday_noon - Utc::now()));
+    })
+    .await;
+
+    let status = pinned_json(app.url("/admin/mirror/status")).await;
+    assert_eq!(status["sample_percentage"], 25.0);
+    assert_eq!(status["active_window"], "Wed 11:00-13:00");
+}
@@ -0,0 +1,169 @@
+#![allow(dead_code)]
+
+use jsonwebtoken::{encode, EncodingKey, Header};
+use project_gateway::{
+    app::create_app,
+    config::{watcher::ConfigWatcher, AppConfig, AuthConfig},
+    middleware::auth::{validate_token_at, AuthCache},
+    monitoring::PerformanceMonitor,
+    AppState,
+};
+use serde_json::Value;
+use std::{
+    io::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tempfile::NamedTempFile;
+use tokio::net::TcpListener;
+
+/// The shipped default config, used as the starting point for test configs.
+pub fn base_config() -> AppConfig {
+    serde_yaml::from_str(include_str!("../../config/default.yaml"))
+        .expect("default config should parse")
+}
+
+/// Secret of the tokens [`jwt`] signs.
+pub const JWT_SECRET: &str = "test-jwt-secret";
+/// When [`jwt_accepted`] validates tokens.
+pub const JWT_NOW: u64 = 1_750_000_000;
+
+/// The default auth config, switched on and verifying [`jwt`]'s tokens.
+pub fn jwt_auth() -> AuthConfig {
+    let mut auth = base_config().middleware.auth;
+    auth.enabled = true;
+    auth.jwt_secrets = vec![JWT_SECRET.to_string()];
+    auth
+}
+
+/// A token carrying `claims`, signed with [`JWT_SECRET`].
+pub fn jwt(claims: Value) -> String {
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap()
+}
+
+/// Whether `auth` accepts a token carrying `claims` at [`JWT_NOW`].
+pub fn jwt_accepted(auth: &AuthConfig, claims: Value) -> bool {
+    validate_token_at(&AuthCache::new(), auth, &jwt(claims), JWT_NOW).is_some()
+}
+
+pub struct TestApp {
+    pub addr: SocketAddr,
+    pub state: AppState,
+    pub config_file: NamedTempFile,
+}
+
+impl TestApp {
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Scrapes `/metrics`, pinned to the Rust handler so canary routing can't
+    /// proxy the scrape to a fake legacy upstream.
+    pub async fn scrape_metrics(&self) -> String {
+        reqwest::Client::new()
+            .get(self.url("/metrics"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .expect("metrics request")
+            .text()
+            .await
+            .expect("metrics body")
+    }
+}
+
+/// Starts the full application on an ephemeral port.
+pub async fn spawn_app(config: AppConfig) -> TestApp {
+    spawn_app_with(config, |_| {}).await
+}
+
+/// Like [`spawn_app`], letting the test swap parts of the state before the
+/// router is built.
+pub async fn spawn_app_with(config: AppConfig, customize: impl FnOnce(&mut AppState)) -> TestApp {
+    let config_file = NamedTempFile::new().expect("temp config file");
+    std::fs::write(config_file.path(), serde_yaml::to_string(&config).unwrap())
+        .expect("write temp config");
+
+    let config_watcher = Arc::new(
+        ConfigWatcher::new(config_file.path().to_str().unwrap(), config)
+            .expect("config watcher"),
+    );
+    let mut state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+    customize(&mut state);
+
+    let app = create_app(state.clone()).await.expect("app");
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+    });
+
+    TestApp {
+        addr,
+        state,
+        config_file,
+    }
+}
+
+/// Reads a single sample from Prometheus text output, or 0 when the series
+/// hasn't been emitted yet.
+pub fn metric_value(scrape: &str, name: &str, labels: &[(&str, &str)]) -> f64 {
+    scrape
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter(|line| {
+            line.strip_prefix(name)
+                .map(|rest| rest.starts_with('{') || rest.starts_with(' '))
+                .unwrap_or(false)
+        })
+        .find(|line| {
+            labels
+                .iter()
+                .all(|(key, value)| line.contains(&format!("{}=\"{}\"", key, value)))
+        })
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// Log output captured from every test in a test binary.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(pub Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+static LOGS: OnceLock<CapturedLogs> = OnceLock::new();
+
+/// Installs the binary's subscriber on first use, capturing up to `level`.
+/// Each binary sticks to one level, as the first call decides it.
+fn captured(level: tracing::Level) -> &'static CapturedLogs {
+    LOGS.get_or_init(|| {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(level)
+            .with_writer(move || writer.clone())
+            .init();
+        logs
+    })
+}
+
+/// The binary's log output at `INFO` and above.
+pub fn logs() -> &'static CapturedLogs {
+    captured(tracing::Level::INFO)
+}
+
+/// The binary's log output, debug events included.
+pub fn debug_logs() -> &'static CapturedLogs {
+    captured(tracing::Level::DEBUG)
+}
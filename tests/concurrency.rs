@@ -0,0 +1,137 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::config::RateLimitTier;
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn capped_app(legacy: &MockServer) -> TestApp {
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .mount(legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let rate_limiting = &mut config.middleware.rate_limiting;
+    rate_limiting.enabled = true;
+    rate_limiting.max_concurrent_per_client = Some(2);
+    rate_limiting
+        .client_tiers
+        .insert("premium-key".to_string(), "premium".to_string());
+    rate_limiting.tiers.insert(
+        "premium".to_string(),
+        RateLimitTier {
+            max_concurrent_per_client: Some(4),
+        },
+    );
+    spawn_app(config).await
+}
+
+/// Sends `count` concurrent slow requests as `api_key` and returns their statuses.
+fn burst(app: &TestApp, api_key: &str, count: usize) -> tokio::task::JoinHandle<Vec<u16>> {
+    let url = app.url("/api/v1/users");
+    let api_key = api_key.to_string();
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let requests = (0..count).map(|_| client.get(&url).header("X-API-Key", &api_key).send());
+        futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .map(|response| response.unwrap().status().as_u16())
+            .collect()
+    })
+}
+
+async fn eventually(condition: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("condition not reached within 2s");
+}
+
+fn count(statuses: &[u16], status: u16) -> usize {
+    statuses.iter().filter(|s| **s == status).count()
+}
+
+#[tokio::test]
+async fn busy_client_is_capped_without_affecting_others() {
+    let legacy = MockServer::start().await;
+    let app = capped_app(&legacy).await;
+
+    let batch = burst(&app, "batch-key", 6);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let interactive = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-API-Key", "interactive-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(interactive.status(), 200);
+
+    let statuses = batch.await.unwrap();
+    assert_eq!(count(&statuses, 200), 2, "{:?}", statuses);
+    assert_eq!(count(&statuses, 429), 4, "{:?}", statuses);
+}
+
+#[tokio::test]
+async fn rejection_carries_distinct_error_code() {
+    let legacy = MockServer::start().await;
+    let app = capped_app(&legacy).await;
+
+    let batch = burst(&app, "batch-key", 2);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let rejected = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-API-Key", "batch-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rejected.status(), 429);
+    let body: Value = rejected.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "concurrency_limit_exceeded");
+
+    batch.await.unwrap();
+}
+
+#[tokio::test]
+async fn tier_override_raises_cap() {
+    let legacy = MockServer::start().await;
+    let app = capped_app(&legacy).await;
+
+    let statuses = burst(&app, "premium-key", 6).await.unwrap();
+    assert_eq!(count(&statuses, 200), 4, "{:?}", statuses);
+    assert_eq!(count(&statuses, 429), 2, "{:?}", statuses);
+}
+
+#[tokio::test]
+async fn idle_client_semaphores_are_collected() {
+    let legacy = MockServer::start().await;
+    let app = capped_app(&legacy).await;
+
+    let limiter = &app.state.concurrency_limiter;
+    burst(&app, "done-key", 1).await.unwrap();
+    // Permits are released when the response body finishes, just after the client sees the status
+    eventually(|| limiter.in_flight("done-key") == 0).await;
+    let busy = burst(&app, "busy-key", 1);
+    eventually(|| limiter.in_flight("busy-key") == 1).await;
+
+    assert_eq!(limiter.tracked_clients(), 2);
+    assert_eq!(limiter.in_flight("busy-key"), 1);
+
+    // Only the client with nothing in flight is dropped
+    assert_eq!(limiter.collect_idle(Duration::ZERO), 1);
+    assert_eq!(limiter.tracked_clients(), 1);
+
+    busy.await.unwrap();
+    eventually(|| limiter.in_flight("busy-key") == 0).await;
+    assert_eq!(limiter.collect_idle(Duration::ZERO), 1);
+    assert_eq!(limiter.tracked_clients(), 0);
+}
@@ -0,0 +1,340 @@
+mod common;
+
+use common::{base_config, metric_value, TestApp};
+use project_gateway::{
+    app::create_app,
+    config::{
+        overlay::{content_hash, OverlayFile},
+        staged::{bucket, ConfigVariant},
+        watcher::ConfigWatcher,
+        AppConfig,
+    },
+    monitoring::PerformanceMonitor,
+    AppState,
+};
+use serde_json::Value;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+struct OverlayApp {
+    app: TestApp,
+    overrides: PathBuf,
+    dir: TempDir,
+}
+
+impl OverlayApp {
+    fn config_path(&self) -> &str {
+        self.app.config_file.path().to_str().unwrap()
+    }
+
+    /// Edits the config file in place the way an operator's editor would.
+    fn edit(&self, path: &[&str], value: serde_yaml::Value) {
+        let mut tree: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(self.config_path()).unwrap()).unwrap();
+        let (last, parents) = path.split_last().unwrap();
+        let node = parents.iter().fold(&mut tree, |node, key| node.get_mut(*key).unwrap());
+        node[*last] = value;
+        std::fs::write(self.config_path(), serde_yaml::to_string(&tree).unwrap()).unwrap();
+    }
+
+    fn on_disk(&self) -> AppConfig {
+        serde_yaml::from_str(&std::fs::read_to_string(self.config_path()).unwrap()).unwrap()
+    }
+
+    fn overlay(&self) -> OverlayFile {
+        OverlayFile::read(&self.overrides).unwrap()
+    }
+
+    async fn config(&self) -> AppConfig {
+        self.app.state.config_watcher.get_config().await
+    }
+
+    async fn admin_config(&self) -> Value {
+        reqwest::Client::new()
+            .get(self.app.url("/admin/config"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+}
+
+async fn spawn_with_overlay(config: AppConfig) -> OverlayApp {
+    let dir = TempDir::new().unwrap();
+    let config_file = tempfile::Builder::new().suffix(".yaml").tempfile_in(dir.path()).unwrap();
+    std::fs::write(config_file.path(), serde_yaml::to_string(&config).unwrap()).unwrap();
+    let overrides = dir.path().join("overrides.yaml");
+
+    let config_watcher =
+        Arc::new(ConfigWatcher::with_overlay(config_file.path().to_str().unwrap(), &overrides).unwrap());
+    let state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+    let router = create_app(state.clone()).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+    });
+
+    OverlayApp {
+        app: TestApp { addr, state, config_file },
+        overrides,
+        dir,
+    }
+}
+
+fn rollout_config() -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 10.0;
+    config.canary_rollout.step = 10.0;
+    config
+}
+
+#[tokio::test]
+async fn manual_edit_and_rollout_write_both_survive() {
+    let overlay_app = spawn_with_overlay(rollout_config()).await;
+
+    // The operator saves an edit and the gatekeeper rolls back before the
+    // watcher has reloaded it
+    overlay_app.edit(&["canary_rollout", "step"], 25.0.into());
+    overlay_app.app.state.coordinator.set_percentage(4.0, "gatekeeper").await;
+
+    let config = overlay_app.config().await;
+    assert_eq!((config.canary_rollout.step, config.canary_rollout.rollout_percentage), (25.0, 4.0));
+
+    // The config file keeps the operator's edit and nothing else; the write
+    // went to the overlay, recorded against the edited file
+    let on_disk = overlay_app.on_disk();
+    assert_eq!((on_disk.canary_rollout.step, on_disk.canary_rollout.rollout_percentage), (25.0, 10.0));
+    let overlay = overlay_app.overlay();
+    let base = std::fs::read(overlay_app.config_path()).unwrap();
+    assert_eq!(overlay.base_sha256, Some(content_hash(&base)));
+    assert_eq!(overlay.entries.len(), 1);
+    assert_eq!(overlay.entries[0].path, "canary_rollout.rollout_percentage");
+    assert_eq!(overlay.entries[0].value, serde_yaml::Value::from(4.0));
+    assert_eq!(overlay.entries[0].base_value, Some(serde_yaml::Value::from(10.0)));
+
+    // The watcher's reload of the edit and a restart both see the two changes
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let config = overlay_app.config().await;
+    assert_eq!((config.canary_rollout.step, config.canary_rollout.rollout_percentage), (25.0, 4.0));
+    let restarted = ConfigWatcher::with_overlay(overlay_app.config_path(), &overlay_app.overrides).unwrap();
+    let config = restarted.get_config().await;
+    assert_eq!((config.canary_rollout.step, config.canary_rollout.rollout_percentage), (25.0, 4.0));
+
+    let body = overlay_app.admin_config().await;
+    assert_eq!(body["canary_rollout"]["rollout_percentage"], 4.0);
+    assert_eq!(body["overlay"][0]["path"], "canary_rollout.rollout_percentage");
+    assert_eq!(body["overlay"][0]["value"], 4.0);
+    assert_eq!(body["overlay"][0]["base_value"], 10.0);
+    assert_eq!(body["overlay"][0]["updated_by"], "gatekeeper");
+}
+
+#[tokio::test]
+async fn rollback_override_holds_survive_a_restart() {
+    let overlay_app = spawn_with_overlay(rollout_config()).await;
+    overlay_app.app.state.coordinator.roll_back(4.0, "gatekeeper").await;
+    let restarted = ConfigWatcher::with_overlay(overlay_app.config_path(), &overlay_app.overrides).unwrap();
+    let canary = restarted.get_config().await.canary_rollout;
+    assert_eq!((canary.rollout_percentage, canary.override_hold), (4.0, Some(4.0)));
+
+    // Raising the rollout lifts it for good
+    overlay_app.app.state.coordinator.set_percentage(20.0, "gatekeeper").await;
+    let restarted = ConfigWatcher::with_overlay(overlay_app.config_path(), &overlay_app.overrides).unwrap();
+    let canary = restarted.get_config().await.canary_rollout;
+    assert_eq!((canary.rollout_percentage, canary.override_hold), (20.0, None));
+}
+
+#[tokio::test]
+async fn operator_edit_of_a_persisted_value_wins() {
+    let overlay_app = spawn_with_overlay(rollout_config()).await;
+    overlay_app.app.state.coordinator.set_percentage(40.0, "admin").await;
+    assert_eq!(overlay_app.config().await.canary_rollout.rollout_percentage, 40.0);
+
+    overlay_app.edit(&["canary_rollout", "rollout_percentage"], 5.0.into());
+    overlay_app.app.state.config_watcher.reload().await.unwrap();
+    assert_eq!(overlay_app.config().await.canary_rollout.rollout_percentage, 5.0);
+    let body = overlay_app.admin_config().await;
+    assert_eq!(body["overlay"], serde_json::json!([]));
+
+    // The next write drops the superseded entry from the file
+    let watcher = &overlay_app.app.state.config_watcher;
+    watcher.persist("canary_rollout.step", 20.0, "admin").await.unwrap();
+    let overlay = overlay_app.overlay();
+    assert_eq!(overlay.entries.iter().map(|entry| entry.path.as_str()).collect::<Vec<_>>(), ["canary_rollout.step"]);
+    let config = overlay_app.config().await;
+    assert_eq!((config.canary_rollout.step, config.canary_rollout.rollout_percentage), (20.0, 5.0));
+}
+
+#[tokio::test]
+async fn writes_are_serialized_and_validated() {
+    let overlay_app = spawn_with_overlay(rollout_config()).await;
+    let watcher = overlay_app.app.state.config_watcher.clone();
+
+    let writes = (1..=20).map(|n| {
+        let watcher = watcher.clone();
+        let path = if n % 2 == 0 { "canary_rollout.step" } else { "canary_rollout.rollout_percentage" };
+        tokio::spawn(async move { watcher.persist(path, n as f64, "test").await })
+    });
+    for write in writes.collect::<Vec<_>>() {
+        write.await.unwrap().unwrap();
+    }
+
+    // Every write landed whole, and the last of each path is what's active
+    let overlay = overlay_app.overlay();
+    assert_eq!(overlay.entries.len(), 2);
+    let config = overlay_app.config().await;
+    for entry in &overlay.entries {
+        let active = match entry.path.as_str() {
+            "canary_rollout.step" => config.canary_rollout.step,
+            _ => config.canary_rollout.rollout_percentage,
+        };
+        assert_eq!(entry.value, serde_yaml::Value::from(active));
+    }
+    assert_eq!(std::fs::read_dir(overlay_app.dir.path()).unwrap().count(), 2);
+
+    // A value the config rejects is neither written nor applied
+    assert!(watcher.persist("canary_rollout.rollout_percentage", 250.0, "test").await.is_err());
+    assert_eq!(overlay_app.overlay(), overlay);
+    assert_eq!(overlay_app.config().await.canary_rollout.rollout_percentage, config.canary_rollout.rollout_percentage);
+}
+
+/// Two overlapping requests from `api_key`; the second starts while the
+/// first is still being served.
+async fn overlapping(app: &TestApp, api_key: &str) -> (u16, u16) {
+    let send = |delay: u64| async move {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        reqwest::Client::new()
+            .get(app.url("/api/v1/users"))
+            .header("X-API-Key", api_key)
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    };
+    tokio::join!(send(0), send(150))
+}
+
+#[tokio::test]
+async fn staged_rate_limits_apply_to_their_share_of_clients_until_promoted() {
+    let legacy = MockServer::start().await;
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .mount(&legacy)
+        .await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.middleware.rate_limiting.enabled = true;
+    config.middleware.rate_limiting.max_concurrent_per_client = None;
+    let overlay_app = spawn_with_overlay(config).await;
+    let app = &overlay_app.app;
+    let watcher = &app.state.config_watcher;
+
+    let stage = reqwest::Client::new()
+        .post(app.url("/admin/config/stage"))
+        .header("X-Gateway-Version", "rust")
+        .json(&serde_json::json!({
+            "path": "middleware.rate_limiting.max_concurrent_per_client",
+            "value": 1,
+            "percentage": 50.0,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stage.status(), 200);
+    assert_eq!(overlay_app.config().await.middleware.rate_limiting.max_concurrent_per_client, None);
+    let staged = watcher.staged().unwrap();
+    assert_eq!(staged.config.middleware.rate_limiting.max_concurrent_per_client, Some(1));
+
+    // Half the clients get the stricter limit, and always the same half
+    let keys: Vec<String> = (0..100).map(|n| format!("client-{}", n)).collect();
+    let in_staged = keys.iter().filter(|key| staged.variant_for(key) == ConfigVariant::Staged).count();
+    assert!((30..=70).contains(&in_staged), "{}", in_staged);
+    let staged_key = keys.iter().find(|key| bucket(key) < 50.0).unwrap();
+    let current_key = keys.iter().find(|key| bucket(key) >= 50.0).unwrap();
+    for _ in 0..3 {
+        assert_eq!(watcher.config_for(staged_key).await.0, Some(ConfigVariant::Staged));
+        assert_eq!(watcher.config_for(current_key).await.0, Some(ConfigVariant::Current));
+    }
+    let before = app.scrape_metrics().await;
+    assert_eq!(overlapping(app, staged_key).await, (200, 429));
+    assert_eq!(overlapping(app, current_key).await, (200, 200));
+    let after = app.scrape_metrics().await;
+    let responses = |scrape: &str, variant: &str, status_class: &str| {
+        metric_value(
+            scrape,
+            "gateway_config_variant_responses_total",
+            &[("variant", variant), ("status_class", status_class)],
+        )
+    };
+    assert_eq!(responses(&after, "staged", "4xx") - responses(&before, "staged", "4xx"), 1.0);
+    // The scrapes are counted too, under whichever variant their IP lands in
+    assert!(responses(&after, "current", "2xx") - responses(&before, "current", "2xx") >= 2.0);
+    assert_eq!(overlay_app.admin_config().await["staged"]["percentage"], 50.0);
+
+    // Promoted, the limit applies to everyone and survives a restart
+    let promote = reqwest::Client::new()
+        .post(app.url("/admin/config/promote-staged"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(promote.status(), 204);
+    assert!(watcher.staged().is_none());
+    assert_eq!(overlapping(app, current_key).await, (200, 429));
+    let overlay = overlay_app.overlay();
+    assert!(overlay.staged.is_none());
+    assert_eq!(overlay.entries[0].path, "middleware.rate_limiting.max_concurrent_per_client");
+    let restarted = ConfigWatcher::with_overlay(overlay_app.config_path(), &overlay_app.overrides).unwrap();
+    assert_eq!(restarted.get_config().await.middleware.rate_limiting.max_concurrent_per_client, Some(1));
+}
+
+#[tokio::test]
+async fn only_whitelisted_sections_can_be_staged() {
+    let overlay_app = spawn_with_overlay(rollout_config()).await;
+    let watcher = &overlay_app.app.state.config_watcher;
+
+    for path in ["middleware.auth.enabled", "canary_rollout.rollout_percentage", "middleware.rate_limitingx"] {
+        assert!(watcher.stage(path, true, 50.0, "test").await.is_err(), "{}", path);
+    }
+    assert!(watcher.stage("middleware.rate_limiting.enabled", true, 150.0, "test").await.is_err());
+    assert_eq!(overlay_app.overlay(), OverlayFile::default());
+
+    // Discarding puts everyone back on the current config
+    watcher.stage("middleware.rate_limiting.enabled", true, 100.0, "test").await.unwrap();
+    assert_eq!(watcher.config_for("anyone").await.0, Some(ConfigVariant::Staged));
+    watcher.discard_staged("test").await.unwrap();
+    assert_eq!(watcher.config_for("anyone").await.0, None);
+    assert!(overlay_app.overlay().staged.is_none());
+    assert!(watcher.discard_staged("test").await.is_err());
+}
+
+#[tokio::test]
+async fn read_only_switch_is_persisted_across_restarts() {
+    let overlay_app = spawn_with_overlay(base_config()).await;
+
+    let status: Value = reqwest::Client::new()
+        .post(overlay_app.app.url("/admin/read-only"))
+        .json(&serde_json::json!({ "enabled": true }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["persisted"], true);
+
+    assert!(!overlay_app.on_disk().read_only.enabled);
+    let overlay = overlay_app.overlay();
+    assert_eq!(overlay.entries[0].path, "read_only.enabled");
+    assert_eq!(overlay.entries[0].value, serde_yaml::Value::from(true));
+    let restarted = ConfigWatcher::with_overlay(overlay_app.config_path(), &overlay_app.overrides).unwrap();
+    assert!(restarted.get_config().await.read_only.enabled);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::config::{
+    partial::salvage,
+    watcher::{ConfigReloadOutcome, ConfigWatcher},
+    AppConfig, ByteSize, HumanDuration, ReloadMode,
+};
+use serde_json::Value;
+
+fn partial_config() -> AppConfig {
+    let mut config = base_config();
+    config.config_reload.mode = ReloadMode::Partial;
+    config.read_only.retry_after = HumanDuration::from_secs(45);
+    config
+}
+
+/// `config` with 42% rolled out, `read_only` given a value of the wrong
+/// type and `routes` left as YAML that doesn't parse.
+fn broken_file(config: &AppConfig) -> String {
+    let mut config = config.clone();
+    config.canary_rollout.rollout_percentage = 42.0;
+    let mut document: serde_yaml::Mapping = serde_yaml::from_value(serde_yaml::to_value(&config).unwrap()).unwrap();
+    document.remove("routes");
+    document.insert("read_only".into(), serde_yaml::from_str("{ enabled: sometimes, retry_after: 10s }").unwrap());
+    let mut document = serde_yaml::to_string(&document).unwrap();
+    document.push_str("routes:\n  - path: \"/api/v1/users\n    method: [GET\n");
+    document
+}
+
+async fn reload(app: &TestApp) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url("/admin/config/reload"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn broken_sections_keep_their_previous_values() {
+    let config = partial_config();
+    let app = spawn_app(config.clone()).await;
+    std::fs::write(app.config_file.path(), broken_file(&config)).unwrap();
+
+    let response = reload(&app).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["retained_sections"], serde_json::json!(["read_only", "routes"]));
+    assert!(body["applied_sections"].as_array().unwrap().iter().any(|section| section == "canary_rollout"));
+
+    let active = app.state.config_watcher.get_config().await;
+    assert_eq!(active.canary_rollout.rollout_percentage, 42.0);
+    assert_eq!(active.routes.len(), config.routes.len());
+    assert_eq!(active.routes[0].path, config.routes[0].path);
+    assert_eq!(active.read_only.retry_after, HumanDuration::from_secs(45));
+
+    let event = app.state.config_watcher.last_reload().unwrap();
+    assert_eq!(event.outcome, ConfigReloadOutcome::Partial);
+    assert_eq!(event.retained_sections, ["read_only", "routes"]);
+    assert!(event.error.is_some());
+    let health: Value = reqwest::Client::new()
+        .get(app.url("/api/v1/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(health["config_last_reload"]["outcome"], "partial");
+
+    let metrics = app.scrape_metrics().await;
+    assert!(metric_value(&metrics, "gateway_config_partial_reload_total", &[]) >= 1.0);
+
+    // Fixing the file brings the retained sections in on the next reload
+    let mut fixed = config.clone();
+    fixed.read_only.retry_after = HumanDuration::from_secs(10);
+    std::fs::write(app.config_file.path(), serde_yaml::to_string(&fixed).unwrap()).unwrap();
+    assert_eq!(reload(&app).await.status(), 200);
+    assert_eq!(
+        app.state.config_watcher.get_config().await.read_only.retry_after,
+        HumanDuration::from_secs(10)
+    );
+    assert_eq!(app.state.config_watcher.last_reload().unwrap().outcome, ConfigReloadOutcome::Applied);
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_the_whole_file() {
+    let config = base_config();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gateway.yaml");
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    let watcher = ConfigWatcher::new(path.to_str().unwrap(), config.clone()).unwrap();
+
+    std::fs::write(&path, broken_file(&config)).unwrap();
+    assert!(watcher.reload().await.is_err());
+    let active = watcher.get_config().await;
+    assert_eq!(active.canary_rollout.rollout_percentage, config.canary_rollout.rollout_percentage);
+    let event = watcher.last_reload().unwrap();
+    assert_eq!(event.outcome, ConfigReloadOutcome::Rejected);
+    assert!(event.applied_sections.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_and_oversized_files_are_rejected_in_partial_mode() {
+    let mut config = partial_config();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gateway.yaml");
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    let watcher = ConfigWatcher::new(path.to_str().unwrap(), config.clone()).unwrap();
+
+    // Every section loads but the whole doesn't validate
+    let mut invalid = config.clone();
+    invalid.canary_rollout.rollout_percentage = 42.0;
+    invalid.read_only.retry_after = HumanDuration::from_secs(0);
+    std::fs::write(&path, serde_yaml::to_string(&invalid).unwrap()).unwrap();
+    assert!(watcher.reload().await.is_err());
+    assert_eq!(watcher.last_reload().unwrap().outcome, ConfigReloadOutcome::Rejected);
+
+    config.config_reload.max_file_size = ByteSize::from_bytes(1024);
+    watcher.apply(config.clone()).await;
+    std::fs::write(&path, broken_file(&config)).unwrap();
+    let error = watcher.reload().await.unwrap_err().to_string();
+    assert!(error.contains("config_reload.max_file_size"), "{}", error);
+    assert_eq!(watcher.get_config().await.canary_rollout.rollout_percentage, config.canary_rollout.rollout_percentage);
+}
+
+#[test]
+fn sections_are_tried_one_by_one_and_one_must_load() {
+    let previous = serde_yaml::to_string(&base_config()).unwrap();
+    assert!(salvage("# nothing but a comment\nserver: [\n", &previous).is_err());
+
+    let salvaged = salvage("# edited\nreset_overrides: true\nmetrics: {\n", &previous).unwrap();
+    assert_eq!((salvaged.applied, salvaged.retained), (vec!["reset_overrides".to_string()], vec!["metrics".to_string()]));
+}
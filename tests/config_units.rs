@@ -0,0 +1,174 @@
+mod common;
+
+use common::base_config;
+use project_gateway::config::{validation::check, AppConfig, ByteSize, HumanDuration, Severity};
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+struct Fields {
+    #[serde(default, deserialize_with = "project_gateway::config::units::legacy_seconds")]
+    seconds: HumanDuration,
+    #[serde(default, deserialize_with = "project_gateway::config::units::legacy_millis")]
+    millis: HumanDuration,
+}
+
+fn fields(yaml: &str) -> Fields {
+    serde_yaml::from_str(yaml).unwrap_or_else(|error| panic!("{}: {}", yaml, error))
+}
+
+#[test]
+fn durations_parse_every_unit() {
+    let cases = [
+        ("0s", Duration::ZERO),
+        ("250ms", Duration::from_millis(250)),
+        ("30s", Duration::from_secs(30)),
+        ("1.5s", Duration::from_millis(1500)),
+        ("2m", Duration::from_secs(120)),
+        ("0.5m", Duration::from_secs(30)),
+        ("1h", Duration::from_secs(3600)),
+        ("1d", Duration::from_secs(86_400)),
+        ("10 s", Duration::from_secs(10)),
+        (" 5MS ", Duration::from_millis(5)),
+        ("3H", Duration::from_secs(3 * 3600)),
+    ];
+    for (input, expected) in cases {
+        let parsed: HumanDuration = input.parse().unwrap_or_else(|error| panic!("{}: {}", input, error));
+        assert_eq!(parsed.get(), expected, "{}", input);
+        assert!(!parsed.is_legacy_numeric(), "{}", input);
+    }
+}
+
+#[test]
+fn malformed_durations_are_rejected() {
+    for input in ["", "s", "30", "30x", "30 sec", "-1s", "1.5ms", "0.0001s", "ms30", "1e30d", "1..5s"] {
+        assert!(input.parse::<HumanDuration>().is_err(), "{:?} should not parse", input);
+    }
+}
+
+#[test]
+fn sizes_parse_every_unit() {
+    let cases = [
+        ("0B", 0),
+        ("512B", 512),
+        ("1KB", 1_000),
+        ("1KiB", 1_024),
+        ("64kib", 64 * 1_024),
+        ("1.5MB", 1_500_000),
+        ("5MiB", 5 * 1_048_576),
+        ("2GB", 2_000_000_000),
+        ("1GiB", 1_073_741_824),
+        ("0.5 KiB", 512),
+    ];
+    for (input, expected) in cases {
+        let parsed: ByteSize = input.parse().unwrap_or_else(|error| panic!("{}: {}", input, error));
+        assert_eq!(parsed.bytes(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn malformed_sizes_are_rejected() {
+    for input in ["", "KiB", "5", "5TB", "5 megabytes", "-1KiB", "1.5B", "0.1KiB", "99999999999GiB"] {
+        assert!(input.parse::<ByteSize>().is_err(), "{:?} should not parse", input);
+    }
+}
+
+#[test]
+fn bare_numbers_use_the_legacy_unit() {
+    let parsed = fields("seconds: 30\nmillis: 250");
+    assert_eq!(parsed.seconds.get(), Duration::from_secs(30));
+    assert_eq!(parsed.millis.get(), Duration::from_millis(250));
+    assert!(parsed.seconds.is_legacy_numeric());
+    assert!(parsed.millis.is_legacy_numeric());
+
+    let parsed = fields("seconds: \"1.5\"\nmillis: \"2s\"");
+    assert_eq!(parsed.seconds.get(), Duration::from_millis(1500));
+    assert_eq!(parsed.millis.get(), Duration::from_secs(2));
+    assert!(!parsed.millis.is_legacy_numeric());
+
+    let size: ByteSize = serde_yaml::from_str("4096").unwrap();
+    assert_eq!(size.bytes(), 4096);
+    assert!(size.is_legacy_numeric());
+
+    for yaml in ["seconds: -1", "millis: 0.5", "seconds: true"] {
+        assert!(serde_yaml::from_str::<Fields>(yaml).is_err(), "{}", yaml);
+    }
+    // Fields without a legacy unit insist on one
+    assert!(serde_yaml::from_str::<HumanDuration>("30").is_err());
+}
+
+#[test]
+fn serialization_emits_the_largest_exact_unit() {
+    let durations = [
+        (HumanDuration::from_millis(0), "0s"),
+        (HumanDuration::from_millis(250), "250ms"),
+        (HumanDuration::from_millis(1500), "1500ms"),
+        (HumanDuration::from_secs(30), "30s"),
+        (HumanDuration::from_secs(300), "5m"),
+        (HumanDuration::from_secs(7200), "2h"),
+        (HumanDuration::from_secs(86_400), "1d"),
+    ];
+    for (duration, expected) in durations {
+        assert_eq!(duration.to_string(), expected);
+        let round_trip: HumanDuration = serde_yaml::from_str(&serde_yaml::to_string(&duration).unwrap()).unwrap();
+        assert_eq!(round_trip, duration);
+    }
+
+    let sizes = [
+        (ByteSize::from_bytes(0), "0B"),
+        (ByteSize::from_bytes(1_000), "1KB"),
+        (ByteSize::from_bytes(65_536), "64KiB"),
+        (ByteSize::from_bytes(1_000_001), "1000001B"),
+        (ByteSize::from_bytes(5 << 20), "5MiB"),
+        (ByteSize::from_bytes(1 << 30), "1GiB"),
+    ];
+    for (size, expected) in sizes {
+        assert_eq!(size.to_string(), expected);
+        let round_trip: ByteSize = serde_yaml::from_str(&serde_yaml::to_string(&size).unwrap()).unwrap();
+        assert_eq!(round_trip, size);
+    }
+}
+
+#[test]
+fn legacy_and_unit_forms_load_identically() {
+    let mixed: AppConfig = serde_yaml::from_str(include_str!("fixtures/mixed_units.yaml")).unwrap();
+    let default = base_config();
+    assert_eq!(serde_yaml::to_string(&mixed).unwrap(), serde_yaml::to_string(&default).unwrap());
+
+    let serialized = serde_yaml::to_string(&mixed).unwrap();
+    assert!(serialized.contains("timeout: 30s"), "{}", serialized);
+    assert!(serialized.contains("success_window: 5m"), "{}", serialized);
+    assert!(serialized.contains("max_body_size: 64KiB"), "{}", serialized);
+}
+
+#[test]
+fn legacy_forms_warn_with_the_replacement() {
+    let mixed: AppConfig = serde_yaml::from_str(include_str!("fixtures/mixed_units.yaml")).unwrap();
+    let warnings: Vec<_> = check(&mixed)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Warning && issue.message.contains("deprecated"))
+        .map(|issue| (issue.section, issue.field, issue.message))
+        .collect();
+
+    let expected = [
+        ("server", "timeout", "\"30s\""),
+        ("mirror", "timeout", "\"5s\""),
+        ("canary_rollout", "success_window", "\"5m\""),
+        ("middleware.logging", "max_body_size", "\"64KiB\""),
+    ];
+    assert_eq!(warnings.len(), expected.len(), "{:?}", warnings);
+    for (section, field, replacement) in expected {
+        assert!(
+            warnings
+                .iter()
+                .any(|(s, f, message)| *s == section && *f == field && message.contains(replacement)),
+            "missing {}.{} in {:?}",
+            section,
+            field,
+            warnings
+        );
+    }
+
+    assert!(!check(&base_config()).iter().any(|issue| issue.message.contains("deprecated")));
+    assert!(mixed.validate().is_ok());
+}
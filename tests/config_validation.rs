@@ -0,0 +1,533 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::config::{
+    validation::check, watcher::ConfigWatcher, AdminBasicAuthConfig, AppConfig, ByteSize, ClientCertForwarding, ConfigValidationError,
+    CookieSameSite, CoordinationConfig, CoordinationKind, HumanDuration, MirrorQueueKind, MirrorWindow, ProxyConfig, RateLimitTier, Severity,
+    IntrospectionConfig, RolloutOverride, SigningKey, StickyCookieConfig,
+};
+use project_gateway::routes::admin::ConfigValidation;
+use std::time::Duration;
+
+type Mutation = fn(&mut AppConfig);
+
+/// Each conflicting config and the (section, field) error it must produce.
+const CONFLICTS: &[(&str, Mutation, &str, &str)] = &[
+    (
+        "canary without legacy URL",
+        |c| c.canary_rollout.legacy_gateway_url = String::new(),
+        "canary_rollout",
+        "legacy_gateway_url",
+    ),
+    (
+        "rollout above 100%",
+        |c| c.canary_rollout.rollout_percentage = 150.0,
+        "canary_rollout",
+        "rollout_percentage",
+    ),
+    (
+        "mirror without URL",
+        |c| {
+            c.mirror.enabled = true;
+            c.mirror.base_url = "not a url".to_string();
+        },
+        "mirror",
+        "base_url",
+    ),
+    (
+        "disk mirror queue without a directory",
+        |c| {
+            c.mirror.queue.kind = MirrorQueueKind::Disk;
+            c.mirror.queue.path = String::new();
+        },
+        "mirror.queue",
+        "path",
+    ),
+    (
+        "mirror report rate above 100%",
+        |c| c.mirror.report.max_mismatch_rate = 150.0,
+        "mirror.report",
+        "max_mismatch_rate",
+    ),
+    (
+        "mirror with zero timeout",
+        |c| {
+            c.mirror.enabled = true;
+            c.mirror.timeout = HumanDuration::from_millis(0);
+        },
+        "mirror",
+        "timeout",
+    ),
+    (
+        "mirror sampling above 100%",
+        |c| c.mirror.sample_percentage = 120.0,
+        "mirror",
+        "sample_percentage",
+    ),
+    (
+        "mirror timezone by name",
+        |c| c.mirror.timezone = "Europe/Berlin".to_string(),
+        "mirror",
+        "timezone",
+    ),
+    (
+        "overlapping mirror windows",
+        |c| {
+            c.mirror.schedule = vec![
+                MirrorWindow { window: "Mon-Fri 22:00-06:00".parse().unwrap(), sample_percentage: 50.0 },
+                MirrorWindow { window: "Sat 05:00-07:00".parse().unwrap(), sample_percentage: 10.0 },
+            ];
+        },
+        "mirror",
+        "schedule",
+    ),
+    (
+        "zero request timeout",
+        |c| c.server.timeout = HumanDuration::from_secs(0),
+        "server",
+        "timeout",
+    ),
+    (
+        "zero legacy timeout",
+        |c| c.canary_rollout.legacy_timeout = Some(HumanDuration::from_millis(0)),
+        "canary_rollout",
+        "legacy_timeout",
+    ),
+    (
+        "zero queue timeout",
+        |c| c.server.queue_timeout = HumanDuration::from_millis(0),
+        "server",
+        "queue_timeout",
+    ),
+    (
+        "zero success window",
+        |c| c.canary_rollout.success_window = HumanDuration::from_secs(0),
+        "canary_rollout",
+        "success_window",
+    ),
+    (
+        "pseudonymization without a salt",
+        |c| c.privacy.salt = None,
+        "privacy",
+        "salt",
+    ),
+    (
+        "route with a malformed content type",
+        |c| c.routes[0].expected_content_types = vec!["json".to_string()],
+        "routes",
+        "expected_content_types",
+    ),
+    (
+        "route with a zero response cap",
+        |c| c.routes[0].max_response_bytes = Some(ByteSize::from_bytes(0)),
+        "routes",
+        "max_response_bytes",
+    ),
+    (
+        "auth without secrets",
+        |c| {
+            c.middleware.auth.enabled = true;
+            c.middleware.auth.jwt_secret = String::new();
+            c.middleware.auth.jwt_secrets.clear();
+        },
+        "middleware.auth",
+        "jwt_secrets",
+    ),
+    (
+        "zero concurrency cap",
+        |c| c.middleware.rate_limiting.max_concurrent_per_client = Some(0),
+        "middleware.rate_limiting",
+        "max_concurrent_per_client",
+    ),
+    (
+        "client mapped to undefined tier",
+        |c| {
+            c.middleware
+                .rate_limiting
+                .client_tiers
+                .insert("batch-key".to_string(), "missing".to_string());
+        },
+        "middleware.rate_limiting",
+        "client_tiers",
+    ),
+    (
+        "tier with zero cap",
+        |c| {
+            c.middleware.rate_limiting.tiers.insert(
+                "frozen".to_string(),
+                RateLimitTier {
+                    max_concurrent_per_client: Some(0),
+                },
+            );
+        },
+        "middleware.rate_limiting",
+        "tiers",
+    ),
+    (
+        "unsafe base path",
+        |c| c.server.public_base_path = "//evil.example.com".to_string(),
+        "server",
+        "public_base_path",
+    ),
+    (
+        "relative metrics path",
+        |c| c.metrics.path = "metrics".to_string(),
+        "metrics",
+        "path",
+    ),
+    (
+        "zero connections per host",
+        |c| c.http_client.max_connections_per_host = 0,
+        "http_client",
+        "max_connections_per_host",
+    ),
+    (
+        "sticky cookie with SameSite=None but not Secure",
+        |c| {
+            c.canary_rollout.sticky_cookie = Some(StickyCookieConfig {
+                secure: false,
+                same_site: CookieSameSite::None,
+                ..Default::default()
+            })
+        },
+        "canary_rollout.sticky_cookie",
+        "same_site",
+    ),
+    (
+        "invalid sticky cookie name",
+        |c| {
+            c.canary_rollout.sticky_cookie = Some(StickyCookieConfig {
+                name: "gw backend".to_string(),
+                ..Default::default()
+            })
+        },
+        "canary_rollout.sticky_cookie",
+        "name",
+    ),
+    (
+        "route override above 100%",
+        |c| {
+            c.canary_rollout.route_overrides = vec![RolloutOverride {
+                path_prefix: "/api/v1/users".to_string(),
+                method: None,
+                percentage: 150.0,
+            }]
+        },
+        "canary_rollout",
+        "route_overrides",
+    ),
+    (
+        "zero connect timeout",
+        |c| c.http_client.connect_timeout = HumanDuration::from_secs(0),
+        "http_client",
+        "connect_timeout",
+    ),
+    (
+        "proxy with invalid no_proxy entry",
+        |c| {
+            c.http_client.proxy = Some(ProxyConfig {
+                url: "http://proxy.internal:3128".to_string(),
+                no_proxy: vec!["10.0.0.0/33".to_string()],
+                username: None,
+                password: None,
+                password_file: None,
+            })
+        },
+        "http_client.proxy",
+        "no_proxy",
+    ),
+    (
+        "proxy with missing password file",
+        |c| {
+            c.http_client.proxy = Some(ProxyConfig {
+                url: "http://proxy.internal:3128".to_string(),
+                no_proxy: Vec::new(),
+                username: Some("gateway".to_string()),
+                password: None,
+                password_file: Some("/nonexistent/proxy_password".to_string()),
+            })
+        },
+        "http_client.proxy",
+        "password_file",
+    ),
+    (
+        "zero header count",
+        |c| c.server.max_header_count = 0,
+        "server",
+        "max_header_count",
+    ),
+    (
+        "per-header limit on an invalid name",
+        |c| {
+            c.canary_rollout
+                .legacy_header_limits
+                .header_size_limits
+                .insert("bad header".to_string(), ByteSize::from_bytes(1024));
+        },
+        "canary_rollout.legacy_header_limits",
+        "header_size_limits",
+    ),
+    (
+        "coordination with a non-redis URL",
+        |c| c.canary_rollout.coordination = Some(coordination("http://redis.internal:6379")),
+        "canary_rollout.coordination",
+        "url",
+    ),
+    (
+        "coordination with a zero lease",
+        |c| {
+            let mut coordination = coordination("redis://redis.internal:6379");
+            coordination.leader_lease = HumanDuration::from_secs(0);
+            c.canary_rollout.coordination = Some(coordination);
+        },
+        "canary_rollout.coordination",
+        "leader_lease",
+    ),
+    (
+        "auth exemption with a wildcard mid-path",
+        |c| c.middleware.auth.exempt_paths = vec!["/api/*/health".to_string()],
+        "middleware.auth",
+        "exempt_paths",
+    ),
+    (
+        "signing key without a secret",
+        |c| {
+            c.middleware.auth.request_signing.keys = vec![SigningKey {
+                name: "billing-legacy".to_string(),
+                secret: String::new(),
+                roles: Vec::new(),
+            }]
+        },
+        "middleware.auth.request_signing",
+        "keys",
+    ),
+    (
+        "introspection without an http URL",
+        |c| {
+            c.middleware.auth.introspection = Some(IntrospectionConfig {
+                url: "idp.internal/introspect".to_string(),
+                client_id: String::new(),
+                client_secret: String::new(),
+                cache_ttl: HumanDuration::from_secs(60),
+                negative_cache_ttl: HumanDuration::from_secs(10),
+                timeout: HumanDuration::from_secs(5),
+                on_failure: Default::default(),
+            })
+        },
+        "middleware.auth.introspection",
+        "url",
+    ),
+    (
+        "identity header with a space in its name",
+        |c| c.middleware.auth.identity_headers.subject = "X Auth Subject".to_string(),
+        "middleware.auth.identity_headers",
+        "names",
+    ),
+    (
+        "notifications with an empty window",
+        |c| c.notifications.max_per_window = 0,
+        "notifications",
+        "max_per_window",
+    ),
+    (
+        "negative latency degradation allowance",
+        |c| c.canary_rollout.max_latency_degradation = -1.0,
+        "canary_rollout",
+        "max_latency_degradation",
+    ),
+    (
+        "webhook retries that shrink",
+        |c| c.notifications.retry.multiplier = 0.5,
+        "notifications",
+        "retry",
+    ),
+    (
+        "docs basic auth with a plaintext password",
+        |c| {
+            c.admin_basic_auth = Some(AdminBasicAuthConfig {
+                username: "ops".to_string(),
+                password_hash: "hunter2".to_string(),
+                realm: "project-gateway".to_string(),
+            })
+        },
+        "admin_basic_auth",
+        "password_hash",
+    ),
+    (
+        "access log sample rate above 1",
+        |c| c.middleware.logging.sample_rate = 1.5,
+        "middleware.logging",
+        "sample_rate",
+    ),
+    (
+        "negative route log sample rate",
+        |c| c.routes[0].log_sample_rate = Some(-0.1),
+        "routes",
+        "log_sample_rate",
+    ),
+];
+
+fn coordination(url: &str) -> CoordinationConfig {
+    CoordinationConfig {
+        kind: CoordinationKind::Redis,
+        url: url.to_string(),
+        key_prefix: "gateway:".to_string(),
+        refresh_interval: HumanDuration::from_secs(5),
+        leader_lease: HumanDuration::from_secs(15),
+    }
+}
+
+#[test]
+fn default_config_has_no_errors() {
+    let errors: Vec<_> = check(&base_config())
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .collect();
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn each_conflict_produces_its_structured_error() {
+    for (name, mutate, section, field) in CONFLICTS {
+        let mut config = base_config();
+        mutate(&mut config);
+
+        let error = config.validate().expect_err(name);
+        let error = error
+            .downcast_ref::<ConfigValidationError>()
+            .unwrap_or_else(|| panic!("{}: not a ConfigValidationError", name));
+        assert!(
+            error
+                .issues
+                .iter()
+                .any(|issue| issue.section == *section && issue.field == *field && issue.severity == Severity::Error),
+            "{}: expected {}.{} in {:?}",
+            name,
+            section,
+            field,
+            error.issues
+        );
+    }
+}
+
+#[test]
+fn warnings_do_not_block_startup() {
+    let mut config = base_config();
+    config.middleware.logging.enabled = false;
+    config.middleware.logging.include_request_body = true;
+    config.server.queue_timeout = HumanDuration::from_secs(60);
+    config.canary_rollout.client_cert_forwarding = ClientCertForwarding::Headers;
+
+    let issues = check(&config);
+    assert!(issues
+        .iter()
+        .any(|issue| issue.field == "client_cert_forwarding" && issue.severity == Severity::Warning));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.field == "queue_timeout" && issue.severity == Severity::Warning));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.section == "middleware.logging" && issue.severity == Severity::Warning));
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn startup_report_lists_features() {
+    let report = base_config().startup_report();
+    for feature in ["canary_rollout", "mirror", "auth", "rate_limiting", "logging"] {
+        assert!(report.contains(feature), "missing {} in\n{}", feature, report);
+    }
+}
+
+#[tokio::test]
+async fn conflicting_reload_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gateway.yaml");
+    let mut config = base_config();
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    let watcher = ConfigWatcher::new(path.to_str().unwrap(), config.clone()).unwrap();
+
+    let mut invalid = config.clone();
+    invalid.server.port = 4321;
+    invalid.canary_rollout.legacy_gateway_url = String::new();
+    std::fs::write(&path, serde_yaml::to_string(&invalid).unwrap()).unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(watcher.get_config().await.server.port, config.server.port);
+
+    // A valid edit afterwards still applies
+    config.server.port = 4322;
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    for _ in 0..100 {
+        if watcher.get_config().await.server.port == 4322 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("valid config after a rejected reload was not applied");
+}
+
+async fn dry_run(app: &TestApp, query: &str, document: String) -> ConfigValidation {
+    reqwest::Client::new()
+        .post(app.url(&format!("/admin/config/validate{}", query)))
+        .header("X-Gateway-Version", "rust")
+        .body(document)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn dry_run_reports_errors_without_applying() {
+    let config = base_config();
+    let app = spawn_app(config.clone()).await;
+
+    let mut invalid = config.clone();
+    invalid.server.port = 4321;
+    invalid.canary_rollout.legacy_gateway_url = String::new();
+    let result = dry_run(&app, "", serde_yaml::to_string(&invalid).unwrap()).await;
+    assert!(!result.valid);
+    assert!(result.changes.is_empty());
+    assert!(result
+        .errors
+        .iter()
+        .any(|issue| issue.section == "canary_rollout" && issue.field == "legacy_gateway_url"));
+
+    let unparseable = dry_run(&app, "", "server: [not, a, map".to_string()).await;
+    assert!(!unparseable.valid);
+    assert!(unparseable.load_error.is_some());
+
+    assert_eq!(app.state.config_watcher.get_config().await.server.port, config.server.port);
+}
+
+#[tokio::test]
+async fn dry_run_diffs_a_valid_config_against_the_active_one() {
+    let config = base_config();
+    let app = spawn_app(config.clone()).await;
+
+    // The file on disk is what is running
+    assert!(dry_run(&app, "", String::new()).await.changes.is_empty());
+
+    let mut candidate = config.clone();
+    candidate.middleware.rate_limiting.requests_per_minute = 5000;
+    candidate.middleware.auth.jwt_secrets = vec!["rotated-secret".to_string()];
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("next.yaml");
+    std::fs::write(&path, serde_yaml::to_string(&candidate).unwrap()).unwrap();
+
+    for result in [
+        dry_run(&app, "", serde_yaml::to_string(&candidate).unwrap()).await,
+        dry_run(&app, &format!("?path={}", path.display()), String::new()).await,
+    ] {
+        assert!(result.valid, "{:?}", result);
+        let paths: Vec<&str> = result.changes.iter().map(|change| change.path.as_str()).collect();
+        assert_eq!(paths, ["middleware.auth.jwt_secrets", "middleware.rate_limiting.requests_per_minute"]);
+        assert_eq!(result.changes[0].candidate, serde_json::json!(["[redacted]"]));
+        assert_eq!(result.changes[1].current, 1000);
+        assert_eq!(result.changes[1].candidate, 5000);
+    }
+
+    assert_eq!(app.state.config_watcher.get_config().await.middleware.rate_limiting.requests_per_minute, 1000);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::base_config;
+use project_gateway::config::watcher::ConfigWatcher;
+use std::time::Duration;
+
+async fn wait_for_port(watcher: &ConfigWatcher, port: u16) -> bool {
+    for _ in 0..100 {
+        if watcher.get_config().await.server.port == port {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn survives_config_file_deletion_and_recreation() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gateway.yaml");
+    let mut config = base_config();
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+
+    let watcher = ConfigWatcher::new(path.to_str().unwrap(), config.clone()).unwrap();
+    let loaded_at = watcher.last_loaded_at();
+
+    std::fs::remove_file(&path).unwrap();
+    for _ in 0..100 {
+        if !watcher.file_present() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(!watcher.file_present(), "deletion not observed");
+    assert_eq!(watcher.get_config().await.server.port, config.server.port);
+
+    config.server.port = 4321;
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    assert!(wait_for_port(&watcher, 4321).await, "recreated config was not reloaded");
+    assert!(watcher.file_present());
+    assert!(watcher.last_loaded_at() > loaded_at);
+
+    // The re-established watch keeps picking up edits
+    config.server.port = 4322;
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+    assert!(wait_for_port(&watcher, 4322).await, "edit after recreation was not reloaded");
+}
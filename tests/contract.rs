@@ -0,0 +1,74 @@
+mod common;
+
+use axum::{routing::get, Json, Router};
+use common::base_config;
+use project_gateway::{
+    contract::{validate_response, ContractChecker},
+    upstream::UpstreamPool,
+};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn spec() -> Value {
+    serde_json::from_str(include_str!("fixtures/contract_spec.json")).unwrap()
+}
+
+fn conforming() -> Value {
+    json!({ "widgets": [{ "id": 1, "name": "gear", "note": null }] })
+}
+
+fn non_conforming() -> Value {
+    json!({ "widgets": [{ "id": "1" }] })
+}
+
+#[test]
+fn conforming_response_has_no_violations() {
+    let body = serde_json::to_vec(&conforming()).unwrap();
+    assert!(validate_response(&spec(), "GET", "/widgets", 200, &body).is_empty());
+}
+
+#[test]
+fn non_conforming_response_reports_each_violation() {
+    let body = serde_json::to_vec(&non_conforming()).unwrap();
+    let errors = validate_response(&spec(), "GET", "/widgets", 200, &body);
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors.iter().any(|e| e.contains("$.widgets[0].id") && e.contains("expected integer")));
+    assert!(errors.iter().any(|e| e.contains("missing required property 'name'")));
+}
+
+#[test]
+fn undocumented_status_and_html_bodies_fail() {
+    assert!(!validate_response(&spec(), "GET", "/widgets", 500, b"{}").is_empty());
+    assert!(!validate_response(&spec(), "GET", "/widgets", 200, b"<html></html>").is_empty());
+}
+
+#[tokio::test]
+async fn run_once_reports_per_target_verdicts() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/widgets"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(non_conforming()))
+        .mount(&legacy)
+        .await;
+
+    let router = Router::new().route("/widgets", get(|| async { Json(conforming()) }));
+    let mut config = base_config();
+    config.contract_check.enabled = true;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+
+    let checker = ContractChecker::new();
+    let report = checker.run_once(&UpstreamPool::new(&Default::default()), &spec(), router, &config).await;
+
+    assert_eq!(report.routes.len(), 1);
+    let result = &report.routes[0];
+    assert_eq!(result.route, "/widgets");
+    assert!(result.rust.passed, "{:?}", result.rust.errors);
+    assert!(!result.legacy.passed);
+    assert!(!result.passed);
+    assert_eq!(checker.route_passing("GET", "/widgets"), Some(false));
+    assert_eq!(checker.route_passing("GET", "/unknown"), None);
+}
@@ -0,0 +1,325 @@
+mod common;
+
+use common::{base_config, spawn_app_with, TestApp};
+use project_gateway::{
+    config::{CoordinationConfig, CoordinationKind, HumanDuration, RolloutOverride},
+    coordination::{
+        CoordinationStore, MemoryStore, RedisStore, RolloutCoordinator, RolloutMode, RolloutUpdate,
+    },
+    gatekeeper::Gatekeeper,
+};
+use serde_json::{json, Value};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+const REFRESH: Duration = Duration::from_millis(50);
+const LEASE: Duration = Duration::from_millis(300);
+
+struct Replica {
+    app: TestApp,
+    sync: JoinHandle<()>,
+}
+
+/// Starts a gateway whose rollout state lives in `store`, as one of several
+/// replicas behind a load balancer.
+async fn replica(name: &str, rollout_percentage: f64, store: Arc<dyn CoordinationStore>) -> Replica {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.slow_start = HumanDuration::from_secs(0);
+    let app = spawn_app_with(config.clone(), |state| {
+        state.coordinator = Arc::new(RolloutCoordinator::new(
+            state.config_watcher.clone(),
+            &config,
+            name.to_string(),
+            Some(store),
+            REFRESH,
+            LEASE,
+        ));
+    })
+    .await;
+    let sync = tokio::spawn(app.state.coordinator.clone().run());
+    Replica { app, sync }
+}
+
+impl Replica {
+    /// GETs `path` from the Rust handlers, whatever the rollout percentage.
+    async fn get_json(&self, path: &str) -> Value {
+        reqwest::Client::new()
+            .get(self.app.url(path))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn rollout_percentage(&self) -> f64 {
+        self.app.state.config_watcher.get_config().await.canary_rollout.rollout_percentage
+    }
+
+    async fn is_leader(&self) -> bool {
+        self.app.state.coordinator.is_leader().await
+    }
+}
+
+/// Polls `condition` until it holds, failing the test after a few seconds.
+async fn eventually<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if condition().await {
+            return;
+        }
+        tokio::time::sleep(REFRESH).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+async fn leaders(replicas: &[&Replica]) -> usize {
+    let mut leaders = 0;
+    for replica in replicas {
+        leaders += replica.is_leader().await as usize;
+    }
+    leaders
+}
+
+#[tokio::test]
+async fn replicas_converge_on_the_shared_state() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    eventually("the first replica to seed the store", || async {
+        store.load().await.unwrap().is_some()
+    })
+    .await;
+
+    // A replica started with a different local percentage follows the store
+    let b = replica("replica-b", 20.0, store.clone()).await;
+    eventually("replica-b to adopt 5%", || async { b.rollout_percentage().await == 5.0 }).await;
+
+    let response = reqwest::Client::new()
+        .put(a.app.url("/admin/rollout"))
+        .header("X-Gateway-Version", "rust")
+        .json(&json!({ "rollout_percentage": 40.0, "paused": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    eventually("replica-b to follow the update", || async {
+        b.rollout_percentage().await == 40.0 && b.app.state.coordinator.state().await.paused
+    })
+    .await;
+    let status = b.get_json("/gatekeeper/status").await;
+    assert_eq!(status["current_rollout_percentage"], 40.0);
+    assert_eq!(status["coordination"]["backend"], "memory");
+    assert_eq!(status["coordination"]["state"]["paused"], true);
+    assert_eq!(status["coordination"]["degraded"], false);
+}
+
+#[tokio::test]
+async fn out_of_range_percentages_are_rejected() {
+    let a = replica("replica-a", 5.0, Arc::new(MemoryStore::new())).await;
+    let response = reqwest::Client::new()
+        .put(a.app.url("/admin/rollout"))
+        .header("X-Gateway-Version", "rust")
+        .json(&json!({ "rollout_percentage": 120.0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(a.rollout_percentage().await, 5.0);
+}
+
+#[tokio::test]
+async fn generations_and_their_history_are_shared_through_the_store() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    let b = replica("replica-b", 5.0, store.clone()).await;
+    a.app.state.coordinator.set_percentage(30.0, "operator").await;
+
+    eventually("replica-b to route under generation 1", || async {
+        b.app.state.coordinator.generation() == 1
+    })
+    .await;
+    let shared = store.load().await.unwrap().unwrap();
+    assert_eq!(shared.version, 1);
+    let latest = shared.generation(1).unwrap();
+    assert_eq!((latest.rollout_percentage, latest.updated_by.as_str()), (30.0, "operator"));
+    assert_eq!(b.app.state.coordinator.state().await.history, shared.history);
+}
+
+#[tokio::test]
+async fn global_rollbacks_hold_overrides_on_every_replica() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 50.0, store.clone()).await;
+    let b = replica("replica-b", 50.0, store.clone()).await;
+    let mut config = b.app.state.config_watcher.get_config().await;
+    config.canary_rollout.route_overrides = vec![RolloutOverride {
+        path_prefix: "/api/v1/users".to_string(),
+        method: None,
+        percentage: 60.0,
+    }];
+    b.app.state.config_watcher.apply(config).await;
+    let users = || async {
+        let config = b.app.state.config_watcher.get_config().await;
+        b.app.state.slow_start.route_percentage(&config.canary_rollout, "GET", "/api/v1/users")
+    };
+
+    Gatekeeper::new(a.app.state.clone()).force_rollback("manual").await;
+    let rolled_back = a.rollout_percentage().await;
+    eventually("replica-b to hold its override", || async {
+        b.app.state.coordinator.state().await.override_hold == Some(rolled_back) && users().await == rolled_back
+    })
+    .await;
+
+    a.app.state.coordinator.set_percentage(55.0, "operator").await;
+    eventually("replica-b to lift the hold", || async {
+        b.app.state.coordinator.state().await.override_hold.is_none() && users().await == 60.0
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn leadership_fails_over_when_the_leader_disappears() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    let b = replica("replica-b", 5.0, store.clone()).await;
+    eventually("a single leader", || async { leaders(&[&a, &b]).await == 1 }).await;
+
+    // The leader stops renewing without releasing, as a crashed process would
+    let (leader, follower) = if a.is_leader().await { (a, b) } else { (b, a) };
+    leader.sync.abort();
+    eventually("the follower to take over", || async { follower.is_leader().await }).await;
+
+    // Leadership stays put while the new leader keeps renewing
+    tokio::time::sleep(LEASE * 2).await;
+    assert!(follower.is_leader().await);
+}
+
+#[tokio::test]
+async fn resigning_hands_leadership_over_early() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    eventually("replica-a to lead", || async { a.is_leader().await }).await;
+    let b = replica("replica-b", 5.0, store.clone()).await;
+
+    a.sync.abort();
+    a.app.state.coordinator.resign().await;
+    assert!(!a.is_leader().await);
+    // Well before the old lease would have expired
+    tokio::time::timeout(LEASE / 2, async {
+        while !b.is_leader().await {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("replica-b should take over once the lease is released");
+}
+
+#[tokio::test]
+async fn unreachable_backend_falls_back_to_local_state() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    let b = replica("replica-b", 5.0, store.clone()).await;
+    eventually("a single leader", || async { leaders(&[&a, &b]).await == 1 }).await;
+
+    store.set_available(false);
+    eventually("both replicas to degrade", || async {
+        a.app.state.coordinator.status().await.degraded && b.app.state.coordinator.status().await.degraded
+    })
+    .await;
+    // Each replica now guards its own traffic
+    assert_eq!(leaders(&[&a, &b]).await, 2);
+    let status = a.get_json("/admin/rollout").await;
+    assert_eq!(status["degraded"], true);
+    assert!(status["last_error"].as_str().unwrap().contains("unavailable"));
+
+    // Local changes still apply, but only here
+    a.app.state.coordinator.set_percentage(30.0, "operator").await;
+    assert_eq!(a.rollout_percentage().await, 30.0);
+    assert_eq!(b.rollout_percentage().await, 5.0);
+
+    // Once the store is back the change made during the outage is shared
+    store.set_available(true);
+    eventually("replica-b to pick up the outage change", || async {
+        b.rollout_percentage().await == 30.0
+    })
+    .await;
+    eventually("recovery and a single leader", || async {
+        !a.app.state.coordinator.status().await.degraded && leaders(&[&a, &b]).await == 1
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn paused_or_manual_rollouts_do_not_advance() {
+    let store = Arc::new(MemoryStore::new());
+    let a = replica("replica-a", 5.0, store.clone()).await;
+    let gatekeeper = Gatekeeper::new(a.app.state.clone());
+    let coordinator = &a.app.state.coordinator;
+
+    coordinator
+        .update(
+            RolloutUpdate {
+                paused: Some(true),
+                ..Default::default()
+            },
+            "operator",
+        )
+        .await;
+    assert!(!gatekeeper.advance_rollout().await);
+
+    coordinator
+        .update(
+            RolloutUpdate {
+                paused: Some(false),
+                mode: Some(RolloutMode::Manual),
+                ..Default::default()
+            },
+            "operator",
+        )
+        .await;
+    assert!(!gatekeeper.advance_rollout().await);
+    assert_eq!(a.rollout_percentage().await, 5.0);
+
+    coordinator
+        .update(
+            RolloutUpdate {
+                mode: Some(RolloutMode::Automatic),
+                ..Default::default()
+            },
+            "operator",
+        )
+        .await;
+    assert!(gatekeeper.advance_rollout().await);
+    assert_eq!(store.load().await.unwrap().unwrap().rollout_percentage, a.rollout_percentage().await);
+}
+
+/// Runs against a real server: `REDIS_URL=redis://127.0.0.1:6379 cargo test
+/// -- --ignored redis`.
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn redis_replicas_converge_and_share_one_leader() {
+    let url = std::env::var("REDIS_URL").expect("REDIS_URL");
+    let config = CoordinationConfig {
+        kind: CoordinationKind::Redis,
+        url,
+        key_prefix: format!("gateway-test-{}:", uuid::Uuid::new_v4().simple()),
+        refresh_interval: HumanDuration::from_millis(50),
+        leader_lease: HumanDuration::from_millis(300),
+    };
+    let a = replica("replica-a", 5.0, Arc::new(RedisStore::new(&config).unwrap())).await;
+    let b = replica("replica-b", 5.0, Arc::new(RedisStore::new(&config).unwrap())).await;
+    eventually("a single leader", || async { leaders(&[&a, &b]).await == 1 }).await;
+
+    a.app.state.coordinator.set_percentage(25.0, "operator").await;
+    eventually("replica-b to follow", || async { b.rollout_percentage().await == 25.0 }).await;
+
+    let (leader, follower) = if a.is_leader().await { (a, b) } else { (b, a) };
+    leader.sync.abort();
+    eventually("failover", || async { follower.is_leader().await }).await;
+}
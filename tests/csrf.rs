@@ -0,0 +1,156 @@
+mod common;
+
+use common::{base_config, jwt_auth, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, AuthConfig, CsrfMode, CsrfRoute},
+    middleware::auth::{issue_token, Claims},
+};
+use reqwest::{header, RequestBuilder, StatusCode};
+use serde_json::Value;
+
+fn csrf_config(mode: CsrfMode) -> AppConfig {
+    let mut config = base_config();
+    let csrf = &mut config.middleware.csrf;
+    csrf.enabled = true;
+    csrf.secure_cookie = false;
+    csrf.allowed_origins = vec!["https://console.example.com".to_string()];
+    csrf.routes = vec![CsrfRoute {
+        route: "/admin/*".to_string(),
+        mode,
+    }];
+    config
+}
+
+/// Pinned to the Rust handlers so canary routing can't proxy the request.
+fn pinned(request: RequestBuilder) -> RequestBuilder {
+    request.header("X-Gateway-Version", "rust")
+}
+
+fn reload(app: &TestApp) -> RequestBuilder {
+    pinned(reqwest::Client::new().post(app.url("/admin/config/reload")))
+}
+
+/// Fetches a page and returns the token from its `XSRF-TOKEN` cookie.
+async fn fetch_token(app: &TestApp) -> String {
+    let response = pinned(reqwest::Client::new().get(app.url("/admin/config"))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+    assert!(cookie.contains("SameSite=Strict"), "{}", cookie);
+    assert!(!cookie.contains("HttpOnly"), "scripts must be able to read the token: {}", cookie);
+    let token = cookie.split(';').next().unwrap().strip_prefix("XSRF-TOKEN=").unwrap();
+    token.to_string()
+}
+
+async fn problem_code(response: reqwest::Response) -> String {
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+    let problem: Value = response.json().await.unwrap();
+    problem["code"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn unsafe_requests_without_a_token_are_rejected() {
+    let app = spawn_app(csrf_config(CsrfMode::DoubleSubmit)).await;
+
+    let response = reload(&app).send().await.unwrap();
+    assert_eq!(problem_code(response).await, "csrf_token_missing");
+
+    let token = fetch_token(&app).await;
+    let header_only = reload(&app).header("X-XSRF-Token", &token).send().await.unwrap();
+    assert_eq!(problem_code(header_only).await, "csrf_token_missing");
+
+    let scrape = app.scrape_metrics().await;
+    assert_eq!(metric_value(&scrape, "gateway_csrf_rejections_total", &[("reason", "csrf_token_missing")]), 2.0);
+}
+
+#[tokio::test]
+async fn mismatched_tokens_are_rejected() {
+    let app = spawn_app(csrf_config(CsrfMode::DoubleSubmit)).await;
+    let token = fetch_token(&app).await;
+    let other = fetch_token(&app).await;
+    assert_ne!(token, other, "tokens are random");
+
+    let response = reload(&app)
+        .header(header::COOKIE, format!("XSRF-TOKEN={}", token))
+        .header("X-XSRF-Token", other)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(problem_code(response).await, "csrf_token_mismatch");
+
+    // A forged cookie and header that match still need a token the gateway issued recently
+    let forged = format!("0.{}", "ab".repeat(32));
+    let response = reload(&app)
+        .header(header::COOKIE, format!("XSRF-TOKEN={}", forged))
+        .header("X-XSRF-Token", &forged)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(problem_code(response).await, "csrf_token_expired");
+}
+
+#[tokio::test]
+async fn issued_token_round_trips() {
+    let app = spawn_app(csrf_config(CsrfMode::DoubleSubmit)).await;
+    let token = fetch_token(&app).await;
+
+    let response = reload(&app)
+        .header(header::COOKIE, format!("session=abc; XSRF-TOKEN={}", token))
+        .header("X-XSRF-Token", &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A current token isn't replaced
+    let response = pinned(reqwest::Client::new().get(app.url("/admin/config")))
+        .header(header::COOKIE, format!("XSRF-TOKEN={}", token))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get(header::SET_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn bearer_authenticated_requests_skip_the_check() {
+    let auth = AuthConfig {
+        jwt_secrets: vec!["csrf-secret".to_string()],
+        exempt_paths: Vec::new(),
+        ..jwt_auth()
+    };
+    let mut config = csrf_config(CsrfMode::DoubleSubmit);
+    config.middleware.auth = auth.clone();
+    let app = spawn_app(config).await;
+    let claims = Claims {
+        sub: "operator".to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: None,
+        roles: Vec::new(),
+    };
+
+    let response = reload(&app).bearer_auth(issue_token(&auth, &claims).unwrap()).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn origin_mode_checks_origin_then_referer() {
+    let app = spawn_app(csrf_config(CsrfMode::Origin)).await;
+
+    let allowed = reload(&app).header(header::ORIGIN, "https://console.example.com").send().await.unwrap();
+    assert_eq!(allowed.status(), StatusCode::OK);
+    let referred = reload(&app)
+        .header(header::REFERER, "https://console.example.com/rollouts?tab=1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(referred.status(), StatusCode::OK);
+
+    let foreign = reload(&app).header(header::ORIGIN, "https://evil.example").send().await.unwrap();
+    assert_eq!(problem_code(foreign).await, "csrf_origin_rejected");
+    let missing = reload(&app).send().await.unwrap();
+    assert_eq!(problem_code(missing).await, "csrf_origin_rejected");
+
+    // Origin mode never hands out tokens
+    let page = pinned(reqwest::Client::new().get(app.url("/admin/config"))).send().await.unwrap();
+    assert!(page.headers().get(header::SET_COOKIE).is_none());
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, AuthConfig},
+    ctl::{self, CtlError},
+    middleware::auth::{issue_token, Claims},
+};
+use serde_json::Value;
+use std::io::Write;
+
+fn ctl_config() -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 10.0;
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    config.canary_rollout.webhook_url = String::new();
+    config
+}
+
+/// Runs `ctl` against `app`, returning its output or error.
+async fn ctl(app: &TestApp, args: &[&str]) -> Result<String, CtlError> {
+    let mut argv = vec!["--server".to_string(), app.url("")];
+    argv.extend(args.iter().map(|arg| arg.to_string()));
+    let mut out = Vec::new();
+    ctl::run(&argv, &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[tokio::test]
+async fn rollout_commands_drive_the_rollout() {
+    let app = spawn_app(ctl_config()).await;
+
+    let status = ctl(&app, &["rollout", "status"]).await.unwrap();
+    let lines: Vec<&str> = status.lines().collect();
+    assert_eq!(lines[0], "FIELD               VALUE");
+    assert_eq!(lines[1], "rollout_percentage  10");
+    assert!(lines.contains(&"mode                automatic"));
+
+    let advanced: Value = serde_json::from_str(&ctl(&app, &["-o", "json", "rollout", "advance"]).await.unwrap()).unwrap();
+    assert_eq!(advanced["state"]["rollout_percentage"], 15.0);
+
+    let paused = ctl(&app, &["rollout", "pause"]).await.unwrap();
+    assert!(paused.contains("paused              true"));
+    // A paused rollout doesn't advance
+    let error = ctl(&app, &["rollout", "advance"]).await.unwrap_err();
+    assert!(matches!(error, CtlError::Api { status: 409, .. }));
+    assert_eq!(error.exit_code(), 1);
+
+    ctl(&app, &["rollout", "resume"]).await.unwrap();
+    let rolled_back: Value =
+        serde_json::from_str(&ctl(&app, &["--output", "json", "rollout", "rollback", "bad", "deploy"]).await.unwrap())
+            .unwrap();
+    assert_eq!(rolled_back["state"]["rollout_percentage"], 10.0);
+    assert_eq!(rolled_back["state"]["paused"], false);
+}
+
+#[tokio::test]
+async fn routes_are_listed_and_matched() {
+    let mut config = ctl_config();
+    let mut by_id = config.routes[1].clone();
+    by_id.path = "/api/v1/users/:id".to_string();
+    config.routes.push(by_id);
+    let app = spawn_app(config).await;
+
+    let routes = ctl(&app, &["routes", "list"]).await.unwrap();
+    let lines: Vec<&str> = routes.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("METHOD  PATH"));
+    assert!(lines[4].starts_with("GET     /api/v1/users/:id  true  -"));
+
+    let matched: Value =
+        serde_json::from_str(&ctl(&app, &["-o", "json", "routes", "match", "get", "/api/v1/users/42"]).await.unwrap())
+            .unwrap();
+    assert_eq!(matched["path"], "/api/v1/users/:id");
+    assert_eq!(matched["method"], "GET");
+
+    let error = ctl(&app, &["routes", "match", "DELETE", "/api/v1/users/42"]).await.unwrap_err();
+    assert!(matches!(error, CtlError::Api { status: 404, .. }));
+    assert_eq!(error.to_string(), "API error 404: not found");
+}
+
+#[tokio::test]
+async fn mirror_can_be_switched_at_runtime() {
+    let app = spawn_app(ctl_config()).await;
+
+    let disabled = ctl(&app, &["mirror", "disable", "--ttl", "60"]).await.unwrap();
+    assert!(disabled.contains("effective     false"));
+    assert!(disabled.contains("override      false"));
+
+    let status: Value = serde_json::from_str(&ctl(&app, &["-o", "json", "mirror", "status"]).await.unwrap()).unwrap();
+    assert_eq!(status["enabled"], false);
+
+    ctl(&app, &["mirror", "enable"]).await.unwrap();
+    assert!(ctl(&app, &["mirror", "status"]).await.unwrap().contains("enabled                    true"));
+}
+
+#[tokio::test]
+async fn config_reload_applies_the_file_or_reports_why_not() {
+    let app = spawn_app(ctl_config()).await;
+
+    let mut edited = ctl_config();
+    edited.canary_rollout.step = 7.0;
+    std::fs::write(app.config_file.path(), serde_yaml::to_string(&edited).unwrap()).unwrap();
+    let reloaded = ctl(&app, &["config", "reload"]).await.unwrap();
+    assert!(reloaded.starts_with("Configuration reloaded at "));
+    assert_eq!(app.state.config_watcher.get_config().await.canary_rollout.step, 7.0);
+    assert!(ctl(&app, &["config", "show"]).await.unwrap().contains("step: 7"));
+
+    std::fs::write(app.config_file.path(), "server: [not, a, map]").unwrap();
+    let error = ctl(&app, &["config", "reload"]).await.unwrap_err();
+    assert!(matches!(error, CtlError::Api { status: 422, .. }));
+    assert_eq!(app.state.config_watcher.get_config().await.canary_rollout.step, 7.0);
+}
+
+#[tokio::test]
+async fn token_file_authenticates_admin_calls() {
+    let auth = AuthConfig {
+        enabled: true,
+        jwt_secret: String::new(),
+        jwt_secrets: vec!["ctl-secret".to_string()],
+        client_certificates: false,
+        clock_skew_tolerance: "60s".parse().unwrap(),
+        issuer: None,
+        audience: Vec::new(),
+        jwks_url: None,
+        jwks_refresh_interval: "5m".parse().unwrap(),
+        jwks_retry: Default::default(),
+        exempt_paths: Vec::new(),
+        request_signing: Default::default(),
+        introspection: None,
+        identity_headers: Default::default(),
+        failure_log_size: 100,
+        api_keys: Vec::new(),
+        quota_reset_hour: 0,
+    };
+    let mut config = ctl_config();
+    config.middleware.auth = auth.clone();
+    let app = spawn_app(config).await;
+
+    let error = ctl(&app, &["rollout", "status"]).await.unwrap_err();
+    assert!(matches!(error, CtlError::Api { status: 401, .. }));
+
+    let claims = Claims {
+        sub: "operator".to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: None,
+        roles: Vec::new(),
+    };
+    let mut token_file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(token_file, "{}", issue_token(&auth, &claims).unwrap()).unwrap();
+    let status = ctl(&app, &["--token-file", token_file.path().to_str().unwrap(), "rollout", "status"]).await;
+    assert!(status.unwrap().contains("rollout_percentage  10"));
+}
+
+#[tokio::test]
+async fn usage_errors_exit_with_2() {
+    let app = spawn_app(ctl_config()).await;
+    for args in [&["rollout"][..], &["mirror", "enable", "--ttl", "soon"], &["-o", "yaml", "routes", "list"], &[]] {
+        let error = ctl(&app, args).await.unwrap_err();
+        assert_eq!(error.exit_code(), 2, "{:?}: {}", args, error);
+        assert!(error.to_string().contains("usage: project-gateway ctl"));
+    }
+}
@@ -0,0 +1,283 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    contract::schema,
+    middleware::capture::{to_har, CapturedExchange, CapturedMessage, CapturedTimings},
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, time::Duration};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn start_capture(app: &TestApp, body: Value) -> (StatusCode, Value) {
+    let response = reqwest::Client::new()
+        .post(app.url("/admin/debug/capture"))
+        .header("X-Gateway-Version", "rust")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+async fn results(app: &TestApp) -> Value {
+    reqwest::get(app.url("/admin/debug/capture/results"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn create_user(app: &TestApp, username: &str) {
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("Authorization", "Bearer secret-token")
+        .header("X-Trace-Note", "visible")
+        .json(&json!({ "username": username, "email": "capture@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn captures_only_the_matching_route() {
+    let app = spawn_app(base_config()).await;
+    let (status, capture) = start_capture(
+        &app,
+        json!({ "route": "/api/v1/users", "duration_seconds": 60, "max_requests": 10, "include_bodies": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(capture["active"], true);
+
+    create_user(&app, "captured").await;
+    reqwest::get(app.url("/health")).await.unwrap();
+    reqwest::get(app.url("/api/v1/health")).await.unwrap();
+
+    let results = results(&app).await;
+    let exchanges = results["exchanges"].as_array().unwrap();
+    assert_eq!(exchanges.len(), 1, "{:#}", results);
+
+    let exchange = &exchanges[0];
+    assert_eq!(exchange["method"], "POST");
+    assert_eq!(exchange["path"], "/api/v1/users");
+    assert_eq!(exchange["status"], 200);
+    assert_eq!(exchange["request"]["headers"]["authorization"], "[redacted]");
+    assert_eq!(exchange["request"]["headers"]["x-trace-note"], "visible");
+    assert!(exchange["request"]["body"].as_str().unwrap().contains("captured"));
+    assert!(exchange["response"]["body"].as_str().unwrap().contains("captured"));
+    assert_eq!(results["capture"]["captured"], 1);
+}
+
+#[tokio::test]
+async fn bodies_are_omitted_unless_requested() {
+    let app = spawn_app(base_config()).await;
+    start_capture(&app, json!({ "route": "/api/v1/users" })).await;
+
+    create_user(&app, "no-bodies").await;
+
+    let results = results(&app).await;
+    let exchange = &results["exchanges"][0];
+    assert!(exchange["request"]["body"].is_null());
+    assert!(exchange["response"]["body"].is_null());
+    assert_eq!(exchange["request"]["headers"]["x-trace-note"], "visible");
+}
+
+#[tokio::test]
+async fn request_cap_ends_the_capture() {
+    let app = spawn_app(base_config()).await;
+    start_capture(&app, json!({ "route": "/api/v1/users", "max_requests": 2 })).await;
+
+    for username in ["one", "two", "three"] {
+        create_user(&app, username).await;
+    }
+
+    let results = results(&app).await;
+    assert_eq!(results["exchanges"].as_array().unwrap().len(), 2);
+    assert_eq!(results["capture"]["active"], false);
+    assert_eq!(results["capture"]["ended_reason"], "max_requests");
+    assert!(!app.state.debug_capture.is_active());
+}
+
+#[tokio::test]
+async fn only_one_capture_runs_and_it_expires() {
+    let app = spawn_app(base_config()).await;
+    let (status, _) = start_capture(&app, json!({ "route": "/api/v1/users", "duration_seconds": 1 })).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, running) = start_capture(&app, json!({ "route": "/health" })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(running["route"], "/api/v1/users");
+
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    // The deadline clears the flag without any traffic
+    assert!(!app.state.debug_capture.is_active());
+
+    create_user(&app, "after-deadline").await;
+    let results = results(&app).await;
+    assert!(results["exchanges"].as_array().unwrap().is_empty());
+    assert_eq!(results["capture"]["ended_reason"], "expired");
+
+    // A new capture may start once the old one has ended
+    let (status, _) = start_capture(&app, json!({ "route": "/health" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn invalid_captures_are_rejected() {
+    let app = spawn_app(base_config()).await;
+    for body in [
+        json!({ "route": "/health", "duration_seconds": 0 }),
+        json!({ "route": "/health", "max_requests": 0 }),
+        json!({ "route": "/health", "duration_seconds": 86400 }),
+    ] {
+        assert_eq!(start_capture(&app, body).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    let response = reqwest::get(app.url("/admin/debug/capture/results")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+fn har_schema_errors(har: &Value) -> Vec<String> {
+    let har_schema: Value = serde_json::from_str(include_str!("fixtures/har_1_2_schema.json")).unwrap();
+    let mut errors = Vec::new();
+    schema::validate(&har_schema, &har_schema, har, "har", &mut errors);
+    errors
+}
+
+fn message(headers: &[(&str, &str)], body: Option<&str>, body_encoding: Option<&str>) -> CapturedMessage {
+    CapturedMessage {
+        headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<BTreeMap<_, _>>(),
+        body: body.map(str::to_string),
+        body_encoding: body_encoding.map(str::to_string),
+        body_truncated: false,
+    }
+}
+
+/// One proxied JSON exchange and one local upload with a binary body.
+fn canned_exchanges() -> Vec<CapturedExchange> {
+    let mut created = message(&[("content-type", "text/plain")], Some("stored"), None);
+    created.body_truncated = true;
+    vec![
+        CapturedExchange {
+            timestamp: "2026-03-02T10:15:00.000+00:00".to_string(),
+            method: "GET".to_string(),
+            path: "/api/v1/users/42".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            status: 200,
+            latency_ms: 50.0,
+            timings: CapturedTimings {
+                queue_ms: 2.0,
+                upstream_wait_ms: Some(40.0),
+                upstream_receive_ms: Some(5.5),
+            },
+            request: message(&[("authorization", "[redacted]"), ("host", "gateway.example.com")], None, None),
+            response: message(&[("content-type", "application/json")], Some(r#"{"id":42}"#), None),
+        },
+        CapturedExchange {
+            timestamp: "2026-03-02T10:15:01.000+00:00".to_string(),
+            method: "POST".to_string(),
+            path: "/api/v1/uploads".to_string(),
+            http_version: "HTTP/2.0".to_string(),
+            status: 201,
+            latency_ms: 3.25,
+            timings: CapturedTimings::default(),
+            request: message(
+                &[("content-type", "application/octet-stream"), ("host", "gateway.example.com")],
+                Some("AAEC/w=="),
+                Some("base64"),
+            ),
+            response: created,
+        },
+    ]
+}
+
+#[test]
+fn canned_capture_matches_the_golden_har() {
+    let mut har = to_har(&canned_exchanges());
+    assert_eq!(har["log"]["creator"]["version"], env!("CARGO_PKG_VERSION"));
+    har["log"]["creator"]["version"] = json!("0.0.0");
+    let golden: Value = serde_json::from_str(include_str!("fixtures/capture.har")).unwrap();
+    assert_eq!(har, golden, "{:#}", har);
+    assert_eq!(har_schema_errors(&har), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn export_is_valid_har_with_upstream_phases() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(vec![0x89, b'P', b'N', b'G', 0xff, 0x00], "image/png")
+                .set_delay(Duration::from_millis(30)),
+        )
+        .mount(&legacy)
+        .await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+    let (status, _) = start_capture(&app, json!({ "route": "/api/v1/users", "include_bodies": true })).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .header("Authorization", "Bearer secret-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Pinned to the Rust handler so the export isn't proxied too
+    let export = client
+        .get(app.url("/admin/debug/capture/export?format=har"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(export.status(), StatusCode::OK);
+    let har: Value = export.json().await.unwrap();
+    assert_eq!(har_schema_errors(&har), Vec::<String>::new());
+
+    let entry = &har["log"]["entries"][0];
+    assert_eq!(har["log"]["entries"].as_array().unwrap().len(), 1);
+    let headers = entry["request"]["headers"].as_array().unwrap();
+    assert!(headers.contains(&json!({ "name": "authorization", "value": "[redacted]" })));
+    assert_eq!(entry["response"]["content"]["encoding"], "base64");
+    assert_eq!(entry["response"]["content"]["text"], "iVBOR/8A");
+    assert_eq!(entry["response"]["content"]["size"], 6);
+
+    let timings = &entry["timings"];
+    assert!(timings["wait"].as_f64().unwrap() >= 30.0, "{}", timings);
+    let phases: f64 = ["blocked", "send", "wait", "receive", "_gateway"]
+        .iter()
+        .map(|phase| timings[phase].as_f64().unwrap())
+        .sum();
+    assert!((phases - entry["time"].as_f64().unwrap()).abs() < 0.001, "{}", entry);
+}
+
+#[tokio::test]
+async fn export_narrows_by_route_and_time() {
+    let app = spawn_app(base_config()).await;
+    let export = |query: &str| {
+        let url = app.url(&format!("/admin/debug/capture/export{}", query));
+        async move { reqwest::get(url).await.unwrap() }
+    };
+    assert_eq!(export("").await.status(), StatusCode::NOT_FOUND);
+
+    start_capture(&app, json!({ "route": "/api/v1/users" })).await;
+    create_user(&app, "exported").await;
+
+    let entries = |har: Value| har["log"]["entries"].as_array().unwrap().len();
+    assert_eq!(entries(export("").await.json().await.unwrap()), 1);
+    assert_eq!(entries(export("?route=/api/v1/users").await.json().await.unwrap()), 1);
+    assert_eq!(entries(export("?route=/health").await.json().await.unwrap()), 0);
+    assert_eq!(entries(export("?from=2000-01-01T00:00:00Z&to=2100-01-01T00:00:00Z").await.json().await.unwrap()), 1);
+    assert_eq!(entries(export("?from=2100-01-01T00:00:00Z").await.json().await.unwrap()), 0);
+    assert_eq!(export("?format=xml").await.status(), StatusCode::BAD_REQUEST);
+}
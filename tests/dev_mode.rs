@@ -0,0 +1,156 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::{
+    dev::DevConfig,
+    test_util::{FakeLegacy, FakeProfile, FakeRoute, FakeUpstreamConfig, SeedUser, SERVED_BY_HEADER},
+};
+use serde_json::Value;
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+fn seed(username: &str) -> SeedUser {
+    SeedUser {
+        username: username.to_string(),
+        email: format!("{}@example.com", username),
+    }
+}
+
+/// Kills the dev gateway when the test ends, pass or fail.
+struct DevProcess(Child);
+
+impl Drop for DevProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn checked_in_dev_config_parses() {
+    let dev = DevConfig::load("config/dev.yaml").unwrap();
+    assert_eq!(dev.seed_users.len(), 3);
+    assert_eq!(dev.legacy.profile_for("POST", "/api/v1/users").error_status, 500);
+    assert_eq!(dev.legacy.profile_for("GET", "/api/v1/health").error_rate, 0.0);
+}
+
+#[tokio::test]
+async fn dev_mode_routes_canary_traffic_to_the_fake_legacy() {
+    let dir = tempfile::tempdir().unwrap();
+    let dev = DevConfig {
+        port: 0,
+        base_config: "config/default.yaml".to_string(),
+        generated_config: dir.path().join("gateway.yaml").to_string_lossy().into_owned(),
+        rollout_percentage: 0.0,
+        seed_users: vec![seed("ada"), seed("grace")],
+        ..DevConfig::default()
+    };
+    let dev_yaml = dir.path().join("dev.yaml");
+    std::fs::write(&dev_yaml, serde_yaml::to_string(&dev).unwrap()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_project-gateway"))
+        .args(["dev", "--config", dev_yaml.to_str().unwrap()])
+        .env("RUST_LOG", "warn")
+        .env_remove("CONFIG_PATH")
+        .env("CONFIG_OVERRIDES_PATH", dir.path().join("overrides.yaml"))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let _process = DevProcess(child);
+
+    // Read up to the summary, then keep draining so logging never blocks
+    let (found, gateway) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut lines = BufReader::new(stdout).lines();
+        for line in lines.by_ref().map_while(Result::ok) {
+            if let Some(url) = line.trim().strip_prefix("Gateway:") {
+                let _ = found.send(url.trim().to_string());
+                break;
+            }
+        }
+        lines.for_each(drop);
+    });
+    let gateway = gateway.recv_timeout(Duration::from_secs(30)).expect("dev mode never printed its summary");
+
+    let client = reqwest::Client::new();
+    let response = client.get(format!("{}/api/v1/users", gateway)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()[SERVED_BY_HEADER], "fake-legacy");
+    let listed: Value = response.json().await.unwrap();
+    assert_eq!(listed["total"], 2);
+    assert_eq!(listed["users"][0]["username"], "ada");
+
+    // The Rust handlers are still a header away
+    let response = client
+        .get(format!("{}/api/v1/users", gateway))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key(SERVED_BY_HEADER));
+
+    // The legacy-served request was mirrored to the fake mirror
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let status: Value = client
+            .get(format!("{}/admin/mirror/status", gateway))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if status["enabled"] == true && status["summary"]["samples"].as_u64().unwrap_or(0) > 0 {
+            break;
+        }
+        assert!(Instant::now() < deadline, "nothing was mirrored: {}", status);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let generated = std::fs::read_to_string(dir.path().join("gateway.yaml")).unwrap();
+    assert!(generated.starts_with("# Generated by `project-gateway dev`"));
+}
+
+#[tokio::test]
+async fn fake_legacy_applies_route_profiles_behind_the_gateway() {
+    let legacy = FakeLegacy::start(
+        "fake-legacy",
+        FakeUpstreamConfig {
+            profile: FakeProfile::default(),
+            routes: vec![FakeRoute {
+                selector: "GET /api/v1/orders*".to_string(),
+                profile: FakeProfile {
+                    latency: "50ms".parse().unwrap(),
+                    error_rate: 100.0,
+                    error_status: 500,
+                    ..FakeProfile::default()
+                },
+            }],
+        },
+        &[seed("ada")],
+    )
+    .await
+    .unwrap();
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.url();
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    let listed: Value = client.get(app.url("/api/v1/users")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed["users"][0]["username"], "ada");
+
+    let started = Instant::now();
+    let response = client.get(app.url("/api/v1/orders/42")).send().await.unwrap();
+    assert_eq!(response.status(), 500);
+    assert_eq!(response.headers()[SERVED_BY_HEADER], "fake-legacy");
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(legacy.requests(), 2);
+}
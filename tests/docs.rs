@@ -0,0 +1,219 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{validation::check, AppConfig, Severity},
+    middleware::auth::{issue_token, Claims},
+};
+use serde_json::Value;
+
+async fn prefixed_app() -> TestApp {
+    let mut config = base_config();
+    config.server.public_base_path = "/gateway/".to_string();
+    config.server.public_url = Some("https://api.example.com".to_string());
+    spawn_app(config).await
+}
+
+fn no_redirects() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn spec_servers_come_from_config() {
+    let app = prefixed_app().await;
+
+    let spec: Value = reqwest::get(app.url("/gateway/api-docs/openapi.json"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let servers = spec["servers"].as_array().unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0]["url"], "https://api.example.com/gateway");
+}
+
+#[tokio::test]
+async fn ui_assets_are_served_under_base_path() {
+    let app = prefixed_app().await;
+    let client = no_redirects();
+
+    let redirect = client.get(app.url("/gateway/docs")).send().await.unwrap();
+    assert!(redirect.status().is_redirection());
+    assert_eq!(redirect.headers()["location"], "/gateway/docs/");
+
+    let index = client.get(app.url("/gateway/docs/")).send().await.unwrap();
+    assert_eq!(index.status(), 200);
+    assert!(index.text().await.unwrap().contains("swagger-ui"));
+
+    let initializer = client
+        .get(app.url("/gateway/docs/swagger-initializer.js"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(initializer.contains("/gateway/api-docs/openapi.json"));
+
+    let css = client.get(app.url("/gateway/docs/swagger-ui.css")).send().await.unwrap();
+    assert_eq!(css.status(), 200);
+}
+
+#[tokio::test]
+async fn stripped_prefix_is_detected_from_forwarded_header() {
+    let app = prefixed_app().await;
+    let client = no_redirects();
+
+    let redirect = client
+        .get(app.url("/docs"))
+        .header("X-Forwarded-Prefix", "/gateway")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(redirect.headers()["location"], "/gateway/docs/");
+
+    let initializer = client
+        .get(app.url("/docs/swagger-initializer.js"))
+        .header("X-Forwarded-Prefix", "/gateway")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(initializer.contains("/gateway/api-docs/openapi.json"));
+
+    let spec: Value = client
+        .get(app.url("/api-docs/openapi.json"))
+        .header("X-Forwarded-Prefix", "/gateway")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(spec["servers"][0]["url"], "https://api.example.com/gateway");
+}
+
+#[tokio::test]
+async fn unsafe_forwarded_prefix_is_ignored() {
+    let app = prefixed_app().await;
+
+    let redirect = no_redirects()
+        .get(app.url("/docs"))
+        .header("X-Forwarded-Prefix", "//evil.example.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(redirect.headers()["location"], "/docs/");
+}
+
+#[tokio::test]
+async fn root_mount_works_without_base_path() {
+    let app = spawn_app(base_config()).await;
+
+    let spec: Value = reqwest::get(app.url("/api-docs/openapi.json"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(spec["servers"][0]["url"], "/");
+
+    let index = reqwest::get(app.url("/docs/")).await.unwrap();
+    assert_eq!(index.status(), 200);
+}
+
+fn token(config: &AppConfig, scope: Option<&str>) -> String {
+    let claims = Claims {
+        sub: "reader".to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: scope.map(str::to_string),
+        roles: Vec::new(),
+    };
+    issue_token(&config.middleware.auth, &claims).unwrap()
+}
+
+async fn docs_status(app: &TestApp, path: &str, token: Option<&str>) -> u16 {
+    let mut request = reqwest::Client::new().get(app.url(path));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap().status().as_u16()
+}
+
+#[tokio::test]
+async fn disabled_docs_are_not_mounted() {
+    let mut config = base_config();
+    config.docs.enabled = false;
+    let app = spawn_app(config).await;
+
+    assert_eq!(docs_status(&app, "/docs/", None).await, 404);
+    assert_eq!(docs_status(&app, "/api-docs/openapi.json", None).await, 404);
+}
+
+#[tokio::test]
+async fn required_auth_needs_the_docs_scope() {
+    let mut config = base_config();
+    config.docs.require_auth = true;
+    let app = spawn_app(config.clone()).await;
+
+    for path in ["/docs/", "/api-docs/openapi.json"] {
+        assert_eq!(docs_status(&app, path, None).await, 401);
+        assert_eq!(docs_status(&app, path, Some("not-a-token")).await, 401);
+        assert_eq!(docs_status(&app, path, Some(&token(&config, Some("users:read")))).await, 403);
+        assert_eq!(docs_status(&app, path, Some(&token(&config, Some("users:read docs:read")))).await, 200);
+    }
+    // The rest of the API is unaffected
+    assert_eq!(docs_status(&app, "/health", None).await, 200);
+
+    config.middleware.auth.jwt_secrets.clear();
+    assert!(check(&config).iter().any(|issue| issue.severity == Severity::Error
+        && issue.section == "docs"
+        && issue.field == "require_auth"));
+}
+
+#[tokio::test]
+async fn docs_are_served_only_to_allowed_networks() {
+    let mut config = base_config();
+    config.docs.allowed_cidrs = vec!["127.0.0.0/8".to_string()];
+    let app = spawn_app(config.clone()).await;
+    assert_eq!(docs_status(&app, "/docs/", None).await, 200);
+
+    config.docs.allowed_cidrs = vec!["10.0.0.0/8".to_string(), "::1".to_string()];
+    let app = spawn_app(config.clone()).await;
+    assert_eq!(docs_status(&app, "/docs/", None).await, 403);
+    assert_eq!(docs_status(&app, "/api-docs/openapi.json", None).await, 403);
+
+    config.docs.allowed_cidrs = vec!["10.0.0.0/33".to_string()];
+    assert!(check(&config).iter().any(|issue| issue.section == "docs" && issue.field == "allowed_cidrs"));
+}
+
+#[tokio::test]
+async fn admin_paths_are_redacted_for_readers_without_the_scope() {
+    let mut config = base_config();
+    config.docs.redact_admin_paths = true;
+    let app = spawn_app(config.clone()).await;
+    let spec = |token: Option<String>| {
+        let mut request = reqwest::Client::new().get(app.url("/api-docs/openapi.json"));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        async move { request.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+
+    let public = spec(None).await;
+    let paths = public["paths"].as_object().unwrap();
+    assert!(paths.contains_key("/api/v1/users"));
+    assert!(!paths.keys().any(|path| path.starts_with("/admin")));
+    assert!(!public["tags"].as_array().unwrap().iter().any(|tag| tag["name"] == "admin"));
+    let unscoped = spec(Some(token(&config, None))).await;
+    assert_eq!(unscoped["paths"], public["paths"]);
+
+    let full = spec(Some(token(&config, Some("docs:read")))).await;
+    assert!(full["paths"].as_object().unwrap().contains_key("/admin/config"));
+}
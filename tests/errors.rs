@@ -0,0 +1,99 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::docs::ApiDoc;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use utoipa::OpenApi;
+
+#[tokio::test]
+async fn rejections_share_the_envelope_and_echo_the_request_id() {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.jwt_secrets = vec!["errors-secret".to_string()];
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("x-request-id", "req-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["x-request-id"], "req-123");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({ "error": {
+            "code": "authentication_required",
+            "message": "This route needs a bearer token, request signature or client certificate",
+            "request_id": "req-123",
+        }})
+    );
+
+    // Without an incoming id one is generated and returned both ways
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .bearer_auth("not-a-jwt")
+        .send()
+        .await
+        .unwrap();
+    let echoed = response.headers()["x-request-id"].to_str().unwrap().to_string();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_token");
+    assert_eq!(body["error"]["request_id"], echoed.as_str());
+    assert!(uuid::Uuid::parse_str(&echoed).is_ok(), "{}", echoed);
+}
+
+#[tokio::test]
+async fn invalid_user_is_a_400_envelope() {
+    let mut config = base_config();
+    config.canary_rollout.enabled = false;
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("x-request-id", "create-1")
+        .json(&json!({ "username": "", "email": "" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_request");
+    assert_eq!(body["error"]["request_id"], "create-1");
+}
+
+#[tokio::test]
+async fn unreachable_legacy_gateway_is_a_502_envelope() {
+    // Bind and drop a listener so nothing answers on the port
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = format!("http://{}", closed);
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("x-request-id", "proxy-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 502);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "upstream_unavailable");
+    assert_eq!(body["error"]["request_id"], "proxy-1");
+}
+
+#[test]
+fn spec_documents_the_error_envelope() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let schemas = &spec["components"]["schemas"];
+    assert_eq!(schemas["ErrorResponse"]["properties"]["error"]["$ref"], "#/components/schemas/ErrorBody");
+    let fields = schemas["ErrorBody"]["allOf"].as_array().unwrap().iter().find(|part| part["required"].is_array());
+    assert_eq!(fields.unwrap()["required"], json!(["code", "message", "request_id"]));
+
+    let bad_request = &spec["paths"]["/api/v1/users"]["post"]["responses"]["400"];
+    let schema = &bad_request["content"]["application/json"]["schema"]["$ref"];
+    assert_eq!(schema, "#/components/schemas/ErrorResponse");
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::metric_value;
+use project_gateway::memory::expiring::{ExpiringMap, SWEEP_BUDGET};
+use std::time::{Duration, Instant};
+
+const TTL: Duration = Duration::from_secs(1);
+
+#[test]
+fn sweeps_work_off_expired_entries_a_bounded_batch_per_tick() {
+    let map = ExpiringMap::new("sweep_test", 1_000_000);
+    let start = Instant::now();
+    for client in 0..50_000u32 {
+        map.insert_at(client, client, TTL, start);
+    }
+    // Long-lived clients are left alone
+    for client in 50_000..50_100u32 {
+        map.insert_at(client, client, Duration::from_secs(3600), start);
+    }
+    assert_eq!(map.len(), 50_100);
+    let full = map.memory_usage();
+
+    // Nothing is due yet, so a tick does no work
+    assert_eq!(map.sweep_at(start, SWEEP_BUDGET).examined, 0);
+
+    let later = start + TTL * 2;
+    let mut ticks = 0;
+    while map.len() > 100 {
+        let stats = map.sweep_at(later, SWEEP_BUDGET);
+        assert!(stats.examined <= SWEEP_BUDGET, "{:?}", stats);
+        assert!(stats.expired > 0, "tick {} made no progress: {:?}", ticks, stats);
+        ticks += 1;
+    }
+    assert_eq!(ticks, 50_000usize.div_ceil(SWEEP_BUDGET));
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.memory_usage(), full / 50_100 * 100);
+    assert_eq!(map.sweep_at(later, SWEEP_BUDGET).examined, 0);
+}
+
+#[test]
+fn capacity_bounds_the_map_however_many_clients_show_up() {
+    let map = ExpiringMap::new("capacity_test", 1_000);
+    let start = Instant::now();
+    for client in 0..100_000u32 {
+        map.insert_at(client, (), Duration::from_secs(3600), start);
+    }
+    assert!(map.len() <= 1_008, "{} entries", map.len());
+    assert!(map.len() >= 900, "{} entries", map.len());
+}
+
+#[test]
+fn touched_and_pinned_entries_outlive_their_ttl() {
+    let map = ExpiringMap::new("pin_test", 100).with_pin(|in_flight: &u32| *in_flight > 0);
+    let start = Instant::now();
+    map.upsert_at(&"busy", TTL, start, || 0, |in_flight| *in_flight += 1);
+    map.upsert_at(&"idle", TTL, start, || 0, |_| ());
+    map.upsert_at(&"returning", TTL, start, || 0, |_| ());
+
+    // Used again just before it would have expired
+    map.upsert_at(&"returning", TTL, start + TTL / 2, || 0, |_| ());
+
+    let later = start + TTL + TTL / 4;
+    assert_eq!(map.sweep_at(later, SWEEP_BUDGET).expired, 1);
+    assert_eq!(map.read_at(&"idle", later, |_| ()), None);
+    assert_eq!(map.read_at(&"busy", later, |in_flight| *in_flight), Some(1));
+    assert!(map.read_at(&"returning", later, |_| ()).is_some());
+
+    // Once the pinned entry's work is done it expires like any other
+    map.upsert_at(&"busy", TTL, later, || 0, |in_flight| *in_flight -= 1);
+    let much_later = later + TTL * 2;
+    assert_eq!(map.sweep_at(much_later, SWEEP_BUDGET).expired, 2);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn sweeps_and_evictions_are_exported_per_store() {
+    let handle = project_gateway::metrics::install_recorder();
+    let map = ExpiringMap::new("metrics_test", 160);
+    let start = Instant::now();
+    for client in 0..1_000u32 {
+        map.insert_at(client, (), TTL, start);
+    }
+    map.sweep_at(start + TTL * 2, SWEEP_BUDGET);
+
+    let scrape = handle.render();
+    let store = [("store", "metrics_test")];
+    assert_eq!(metric_value(&scrape, "gateway_state_entries", &store), 0.0);
+    let evicted = |reason| metric_value(&scrape, "gateway_state_evictions_total", &[("store", "metrics_test"), ("reason", reason)]);
+    assert!(evicted("capacity") >= 840.0);
+    assert_eq!(evicted("capacity") + evicted("expired"), 1_000.0);
+    assert_eq!(metric_value(&scrape, "gateway_state_sweep_seconds_count", &store), 1.0);
+}
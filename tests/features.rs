@@ -0,0 +1,117 @@
+mod common;
+
+use common::{base_config, logs, spawn_app, TestApp};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+fn logged(marker: &str) -> bool {
+    String::from_utf8_lossy(&logs().0.lock().unwrap()).contains(marker)
+}
+
+async fn body_logging_app() -> TestApp {
+    logs();
+    let mut config = base_config();
+    config.middleware.logging.enabled = true;
+    config.middleware.logging.include_request_body = true;
+    spawn_app(config).await
+}
+
+async fn create_user(app: &TestApp, username: &str) {
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .json(&json!({ "username": username, "email": "marker@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+async fn set_feature(app: &TestApp, name: &str, body: Value) -> Value {
+    let response = reqwest::Client::new()
+        .put(app.url(&format!("/admin/features/{}", name)))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    response.json().await.unwrap()
+}
+
+async fn feature_state(app: &TestApp, name: &str) -> Value {
+    let features: Vec<Value> = reqwest::get(app.url("/admin/features"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    features
+        .into_iter()
+        .find(|feature| feature["name"] == name)
+        .expect("feature listed")
+}
+
+#[tokio::test]
+async fn disabling_body_logging_takes_effect_immediately() {
+    let app = body_logging_app().await;
+
+    create_user(&app, "marker-before-toggle").await;
+    assert!(logged("marker-before-toggle"));
+
+    let state = set_feature(&app, "body_logging", json!({ "enabled": false })).await;
+    assert_eq!(state["config_value"], true);
+    assert_eq!(state["effective"], false);
+
+    create_user(&app, "marker-after-toggle").await;
+    assert!(!logged("marker-after-toggle"));
+
+    let listed = feature_state(&app, "body_logging").await;
+    assert_eq!(listed["override_value"], false);
+    assert_eq!(listed["effective"], false);
+}
+
+#[tokio::test]
+async fn override_expires_after_ttl() {
+    let app = body_logging_app().await;
+
+    let state = set_feature(&app, "body_logging", json!({ "enabled": false, "ttl_seconds": 1 })).await;
+    assert!(state["override_expires_at"].is_string());
+
+    create_user(&app, "marker-during-ttl").await;
+    assert!(!logged("marker-during-ttl"));
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    create_user(&app, "marker-after-ttl").await;
+    assert!(logged("marker-after-ttl"));
+    assert_eq!(feature_state(&app, "body_logging").await["override_value"], Value::Null);
+}
+
+#[tokio::test]
+async fn overrides_survive_reload_unless_reset_requested() {
+    let app = body_logging_app().await;
+    set_feature(&app, "mirror", json!({ "enabled": true })).await;
+
+    let mut config = app.state.config_watcher.get_config().await;
+    app.state.config_watcher.apply(config.clone()).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(feature_state(&app, "mirror").await["override_value"], true);
+
+    config.reset_overrides = true;
+    app.state.config_watcher.apply(config).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let state = feature_state(&app, "mirror").await;
+    assert_eq!(state["override_value"], Value::Null);
+    assert_eq!(state["effective"], false);
+}
+
+#[tokio::test]
+async fn unknown_feature_is_not_found() {
+    let app = body_logging_app().await;
+    let response = reqwest::Client::new()
+        .put(app.url("/admin/features/teleport"))
+        .json(&json!({ "enabled": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
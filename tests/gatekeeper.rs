@@ -0,0 +1,258 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::RolloutReadinessConfig,
+    gatekeeper::{
+        decide, evaluate_readiness, Gatekeeper, GatekeeperEvaluation, HealthSnapshot, HealthThresholds, RollbackScope,
+        RuleOutcome, ThresholdOverrides,
+    },
+    monitoring::{MirrorOutcome, MirrorSummary},
+};
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn summary(samples: usize, success_rate: f64, mismatch_rate: f64, mirror_p99_ms: f64) -> Option<MirrorSummary> {
+    Some(MirrorSummary {
+        samples,
+        success_rate,
+        mismatch_rate,
+        contract_violation_rate: 0.0,
+        mirror_p99_ms,
+        main_p99_ms: 100.0,
+    })
+}
+
+#[test]
+fn readiness_requires_every_threshold() {
+    let thresholds = RolloutReadinessConfig::default();
+
+    assert!(evaluate_readiness(summary(500, 99.9, 0.2, 120.0), &thresholds).ready);
+
+    let cases = [
+        (None, "No mirror traffic"),
+        (summary(10, 100.0, 0.0, 100.0), "mirror samples"),
+        (summary(500, 95.0, 0.0, 100.0), "success rate"),
+        (summary(500, 100.0, 5.0, 100.0), "mismatch rate"),
+        (summary(500, 100.0, 0.0, 300.0), "p99 latency"),
+        (
+            summary(500, 100.0, 0.0, 100.0).map(|summary| MirrorSummary {
+                contract_violation_rate: 2.0,
+                ..summary
+            }),
+            "contract violation",
+        ),
+    ];
+    for (mirror, expected) in cases {
+        let readiness = evaluate_readiness(mirror, &thresholds);
+        assert!(!readiness.ready, "expected blocked on {}", expected);
+        assert_eq!(readiness.blocking_reasons.len(), 1, "{:?}", readiness.blocking_reasons);
+        assert!(
+            readiness.blocking_reasons[0].contains(expected),
+            "{:?} should mention {}",
+            readiness.blocking_reasons,
+            expected
+        );
+    }
+}
+
+async fn mirror_only_app() -> TestApp {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.mirror.enabled = true;
+    config.canary_rollout.readiness.min_mirror_samples = 20;
+    spawn_app(config).await
+}
+
+fn record_mirrors(app: &TestApp, count: usize, mismatches: usize) {
+    for i in 0..count {
+        app.state.performance_monitor.record_mirror(MirrorOutcome {
+            success: true,
+            mismatch: i < mismatches,
+            contract_violation: false,
+            mirror_latency_ms: 20.0,
+            main_latency_ms: 25.0,
+        });
+    }
+}
+
+#[tokio::test]
+async fn advancement_from_zero_is_gated_on_readiness() {
+    let app = mirror_only_app().await;
+    let gatekeeper = Gatekeeper::new(app.state.clone());
+
+    // Too many mismatches: 5 of 20 is 25%
+    record_mirrors(&app, 20, 5);
+    let status = gatekeeper.get_status().await;
+    let readiness = status.rollout_readiness.expect("pre-rollout mode");
+    assert!(!readiness.ready);
+    assert!(readiness.blocking_reasons[0].contains("mismatch"));
+    assert!(!gatekeeper.advance_rollout().await);
+
+    // Clean mirror traffic dilutes the mismatches below 1%
+    record_mirrors(&app, 980, 0);
+    let readiness = gatekeeper.get_status().await.rollout_readiness.unwrap();
+    assert!(readiness.ready, "{:?}", readiness.blocking_reasons);
+    assert!(gatekeeper.advance_rollout().await);
+}
+
+#[tokio::test]
+async fn readiness_only_applies_before_rollout() {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 10.0;
+    config.mirror.enabled = true;
+    let app = spawn_app(config).await;
+
+    let status = Gatekeeper::new(app.state.clone()).get_status().await;
+    assert!(status.rollout_readiness.is_none());
+}
+
+#[tokio::test]
+async fn status_endpoint_reports_readiness() {
+    let app = mirror_only_app().await;
+
+    let status: Value = reqwest::Client::new()
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["rollout_readiness"]["ready"], false);
+    assert!(status["rollout_readiness"]["blocking_reasons"][0].is_string());
+}
+
+fn thresholds() -> HealthThresholds {
+    HealthThresholds {
+        max_errors: 0.5,
+        max_latency_degradation: 10.0,
+        monitor_latency_p99: true,
+        rollback_on_fast_burn: true,
+        hold_on_latency_drift: false,
+        step: 5.0,
+        scoped_rollback: true,
+        min_route_requests: 20,
+    }
+}
+
+#[test]
+fn overrides_replace_only_the_thresholds_they_set() {
+    let snapshot = HealthSnapshot {
+        rollout_percentage: 50.0,
+        effective_percentage: 50.0,
+        error_rate: 0.8,
+        latency_degradation_percent: 7.0,
+        ..HealthSnapshot::default()
+    };
+
+    let configured = decide(&snapshot, &thresholds());
+    assert!(!configured.healthy);
+    assert_eq!(configured.rollback_reason.as_deref(), Some("Error rate 0.8% exceeds threshold 0.5%"));
+    let action = configured.action.unwrap();
+    assert_eq!((action.scope, action.from, action.to), (RollbackScope::Global, 50.0, 45.0));
+
+    let overrides = ThresholdOverrides {
+        max_errors: Some(1.0),
+        max_latency_degradation: Some(5.0),
+        ..ThresholdOverrides::default()
+    };
+    let tried = overrides.apply(thresholds());
+    assert_eq!((tried.max_errors, tried.max_latency_degradation, tried.step), (1.0, 5.0, 5.0));
+    assert_eq!(overrides.overridden(), ["max_errors", "max_latency_degradation"]);
+
+    let decision = decide(&snapshot, &tried);
+    let outcomes: Vec<(&str, RuleOutcome)> =
+        decision.rules.iter().map(|rule| (rule.rule.as_str(), rule.outcome)).collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("error_rate", RuleOutcome::Pass),
+            ("latency_degradation", RuleOutcome::Fail),
+            ("slo_fast_burn", RuleOutcome::Pass),
+            ("latency_drift", RuleOutcome::Skipped),
+            ("route_error_rate", RuleOutcome::Skipped),
+            ("rollback_cooldown", RuleOutcome::Pass),
+            ("manual_mode", RuleOutcome::Pass),
+        ]
+    );
+    assert_eq!(decision.rules[1].observed, 7.0);
+    assert_eq!(decision.rules[1].threshold, Some(5.0));
+    assert_eq!(decision.rollback_reason.as_deref(), Some("Latency degraded by 7% (threshold: 5%)"));
+
+    // Neither a cooldown nor manual mode changes what the rules found, only
+    // whether anything happens
+    let cooling = decide(&HealthSnapshot { in_cooldown: true, ..snapshot.clone() }, &tried);
+    assert!(cooling.healthy && cooling.action.is_none());
+    let manual = decide(&HealthSnapshot { manual_mode: true, ..snapshot }, &tried);
+    assert!(!manual.healthy && manual.action.is_none());
+}
+
+#[tokio::test]
+async fn dry_runs_explain_without_side_effects() {
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).mount(&webhook).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 40.0;
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    config.canary_rollout.max_errors = 5.0;
+    config.canary_rollout.webhook_url = webhook.uri();
+    let app = spawn_app(config).await;
+    for n in 0..40 {
+        app.state.scoped_rollbacks.record("GET", "/api/v1/users", n < 4);
+    }
+
+    let evaluate = |body: Value| {
+        let url = app.url("/admin/gatekeeper/evaluate");
+        async move {
+            let response = reqwest::Client::new()
+                .post(url)
+                .header("X-Gateway-Version", "rust")
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            response.json::<GatekeeperEvaluation>().await.unwrap()
+        }
+    };
+
+    // 10% on one route breaches the configured 5%, for that route alone
+    let configured = evaluate(json!({})).await;
+    assert!(configured.overridden.is_empty());
+    assert_eq!(configured.snapshot.routes[0].requests, 40);
+    let route_rule = configured.decision.rules.iter().find(|rule| rule.rule == "route_error_rate").unwrap();
+    assert_eq!((route_rule.outcome, route_rule.observed, route_rule.threshold), (RuleOutcome::Fail, 10.0, Some(5.0)));
+    let action = configured.decision.action.clone().unwrap();
+    assert!(matches!(&action.scope, RollbackScope::Route { route, .. } if route == "/api/v1/users"));
+    assert_eq!((action.from, action.to), (40.0, 35.0));
+
+    let relaxed = evaluate(json!({ "max_errors": 20.0 })).await;
+    assert_eq!(relaxed.overridden, ["max_errors"]);
+    assert_eq!(relaxed.thresholds.max_errors, 20.0);
+    assert!(relaxed.decision.action.is_none());
+    let global = evaluate(json!({ "scoped_rollback": false })).await;
+    assert_eq!(global.decision.action.unwrap().scope, RollbackScope::Global);
+
+    // Asked again, the answer is the same and nothing has moved
+    let again = evaluate(json!({})).await;
+    assert_eq!(again.decision.action, Some(action));
+    assert_eq!(app.state.coordinator.state().await.rollout_percentage, 40.0);
+    assert!(app.state.scoped_rollbacks.active().is_empty());
+    assert!(webhook.received_requests().await.unwrap().is_empty());
+
+    // Unknown thresholds are refused rather than ignored
+    let refused = reqwest::Client::new()
+        .post(app.url("/admin/gatekeeper/evaluate"))
+        .header("X-Gateway-Version", "rust")
+        .json(&json!({ "max_error": 1.0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), 400);
+
+    // The real check still sees the route counts the dry runs looked at
+    let applied = Gatekeeper::new(app.state.clone()).check_routes().await.unwrap();
+    assert_eq!((applied.from, applied.to), (40.0, 35.0));
+}
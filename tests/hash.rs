@@ -0,0 +1,21 @@
+use project_gateway::{config::staged::bucket, middleware::canary::decision::sticky_sample, util::hash::unit_interval};
+
+#[test]
+fn keys_spread_evenly_below_one() {
+    let mut buckets = [0u32; 10];
+    for n in 0..10_000 {
+        let sample = unit_interval(&[&n.to_string()]);
+        assert!((0.0..1.0).contains(&sample), "{} landed at {}", n, sample);
+        buckets[(sample * 10.0) as usize] += 1;
+    }
+    for (bucket, count) in buckets.iter().enumerate() {
+        assert!((900..1100).contains(count), "bucket {} got {}", bucket, count);
+    }
+}
+
+#[test]
+fn parts_are_kept_apart_and_shared_by_every_caller() {
+    assert_ne!(unit_interval(&["ab", "c"]), unit_interval(&["a", "bc"]));
+    assert_eq!(sticky_sample("seed", "abc"), unit_interval(&["seed", "abc"]));
+    assert_eq!(bucket("client-1"), unit_interval(&["client-1"]) * 100.0);
+}
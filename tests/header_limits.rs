@@ -0,0 +1,138 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::config::{AppConfig, ByteSize};
+use serde_json::Value;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// Pinned to the Rust handlers so only the gateway's own limits apply.
+async fn get(app: &TestApp, headers: &[(String, String)]) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust");
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    request.send().await.unwrap()
+}
+
+async fn problem(response: reqwest::Response) -> (Value, String) {
+    assert_eq!(response.status(), 431);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let body = response.text().await.unwrap();
+    (serde_json::from_str(&body).unwrap(), body)
+}
+
+fn filler(name: &str, bytes: usize) -> (String, String) {
+    (name.to_string(), "x".repeat(bytes))
+}
+
+#[tokio::test]
+async fn oversized_single_header_is_rejected_without_echoing_it() {
+    let app = spawn_app(base_config()).await;
+    let cookie = format!("session={}", "s".repeat(20 * 1024));
+
+    let (problem, body) = problem(get(&app, &[("cookie".to_string(), cookie)]).await).await;
+    assert_eq!(problem["status"], 431);
+    assert_eq!(problem["limit"], "header_bytes");
+    assert_eq!(problem["header"], "cookie");
+    assert_eq!(problem["scope"], "server");
+    assert!(problem["detail"].as_str().unwrap().contains("16384"));
+    assert!(!body.contains("sssssssss"));
+
+    let scrape = app.scrape_metrics().await;
+    let rejections = metric_value(
+        &scrape,
+        "gateway_header_limit_rejections_total",
+        &[("limit", "header_bytes"), ("scope", "server")],
+    );
+    assert!(rejections >= 1.0, "{}", scrape);
+}
+
+#[tokio::test]
+async fn too_many_headers_are_rejected() {
+    let mut config = base_config();
+    config.server.max_header_count = 20;
+    let app = spawn_app(config).await;
+
+    let headers: Vec<_> = (0..30).map(|i| (format!("x-extra-{}", i), "1".to_string())).collect();
+    let (problem, _) = problem(get(&app, &headers).await).await;
+    assert_eq!(problem["limit"], "count");
+    assert!(problem.get("header").is_none());
+}
+
+#[tokio::test]
+async fn total_header_size_is_limited() {
+    let mut config = base_config();
+    config.server.max_header_bytes = ByteSize::from_bytes(4 * 1024);
+    let app = spawn_app(config).await;
+
+    let headers: Vec<_> = (0..5).map(|i| filler(&format!("x-part-{}", i), 1024)).collect();
+    let (problem, _) = problem(get(&app, &headers).await).await;
+    assert_eq!(problem["limit"], "total_bytes");
+}
+
+#[tokio::test]
+async fn compliant_large_request_passes() {
+    let app = spawn_app(base_config()).await;
+    let mut headers: Vec<_> = (0..40).map(|i| filler(&format!("x-extra-{}", i), 256)).collect();
+    headers.push(filler("cookie", 12 * 1024));
+
+    let response = get(&app, &headers).await;
+    assert_eq!(response.status(), 200);
+}
+
+fn legacy_config(legacy: &MockServer) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config
+        .canary_rollout
+        .legacy_header_limits
+        .header_size_limits
+        .insert("X-Tenant-Context".to_string(), ByteSize::from_bytes(1024));
+    config
+}
+
+#[tokio::test]
+async fn legacy_limits_fail_fast_before_proxying() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&legacy)
+        .await;
+    let app = spawn_app(legacy_config(&legacy)).await;
+    let client = reqwest::Client::new();
+    let context = "t".repeat(2 * 1024);
+
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .header("x-tenant-context", &context)
+        .send()
+        .await
+        .unwrap();
+    let (problem, _) = problem(response).await;
+    assert_eq!(problem["scope"], "legacy");
+    assert_eq!(problem["header"], "x-tenant-context");
+    assert!(legacy.received_requests().await.unwrap().is_empty());
+
+    // The Rust handlers aren't bound by the legacy gateway's limits
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .header("x-tenant-context", &context)
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Within the legacy limit the request is proxied as usual
+    let response = client
+        .get(app.url("/api/v1/users"))
+        .header("x-tenant-context", "t".repeat(512))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(legacy.received_requests().await.unwrap().len(), 1);
+}
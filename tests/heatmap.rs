@@ -0,0 +1,82 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::monitoring::heatmap::{LatencyHeatmap, LatencyHeatmapReport};
+use std::time::Duration;
+
+const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+fn assert_well_formed(report: &LatencyHeatmapReport) {
+    assert_eq!(report.counts.iter().sum::<u64>(), report.samples);
+    assert_eq!(report.boundaries_ms.len(), report.counts.len() + 1);
+    assert!(report.boundaries_ms.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", report.boundaries_ms);
+}
+
+#[test]
+fn bucket_counts_add_up_to_the_samples_recorded() {
+    let heatmap = LatencyHeatmap::new();
+    for (latency_ms, count) in [(1.0, 100), (10.0, 50), (100.0, 25), (1000.0, 5)] {
+        for _ in 0..count {
+            heatmap.record("rust", "/api/v1/users", latency_ms);
+        }
+    }
+    for _ in 0..20 {
+        heatmap.record("rust", "/health", 0.5);
+        heatmap.record("legacy", "/api/v1/users", 50.0);
+    }
+
+    let report = heatmap.report("rust", Some("/api/v1/users"), WINDOW, 10);
+    assert_well_formed(&report);
+    assert_eq!((report.samples, report.routes), (180, 1));
+    assert!(report.counts.len() <= 10);
+    assert!(report.boundaries_ms[0] <= 1.0 && *report.boundaries_ms.last().unwrap() > 1000.0);
+    assert_eq!(report.counts[0], 100);
+    assert_eq!(*report.counts.last().unwrap(), 5);
+
+    // At full resolution each distinct latency is its own non-empty bucket
+    let report = heatmap.report("rust", Some("/api/v1/users"), WINDOW, 160);
+    assert_well_formed(&report);
+    let non_empty: Vec<u64> = report.counts.iter().copied().filter(|&count| count > 0).collect();
+    assert_eq!(non_empty, [100, 50, 25, 5]);
+
+    // Without a route, every route of the variant is merged
+    let report = heatmap.report("rust", None, WINDOW, 40);
+    assert_well_formed(&report);
+    assert_eq!((report.samples, report.routes), (200, 2));
+
+    let report = heatmap.report("legacy", Some("/health"), WINDOW, 40);
+    assert_eq!((report.samples, report.routes), (0, 0));
+    assert!(report.counts.is_empty() && report.boundaries_ms.is_empty());
+}
+
+#[tokio::test]
+async fn served_requests_show_up_and_absurd_queries_are_rejected() {
+    let app = spawn_app(base_config()).await;
+    let client = reqwest::Client::new();
+    for _ in 0..12 {
+        let response = client
+            .get(app.url("/api/v1/users"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let heatmap = |query: &str| {
+        client
+            .get(app.url(&format!("/monitoring/heatmap?{}", query)))
+            .header("X-Gateway-Version", "rust")
+            .send()
+    };
+    let response = heatmap("variant=rust&route=/api/v1/users&window=5m&buckets=40").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let report: LatencyHeatmapReport = response.json().await.unwrap();
+    assert_well_formed(&report);
+    assert_eq!(report.samples, 12);
+    assert_eq!(report.window, "5m");
+
+    for query in ["buckets=0", "buckets=100000", "window=2h", "window=1s", "window=soon", "variant=go"] {
+        assert_eq!(heatmap(query).await.unwrap().status(), 400, "{}", query);
+    }
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::{
+    config::AppConfig,
+    middleware::auth::{issue_token, Claims},
+};
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+async fn upstream() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn legacy_config(legacy: &MockServer) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.middleware.auth.enabled = true;
+    config
+}
+
+fn token(config: &AppConfig) -> String {
+    issue_token(
+        &config.middleware.auth,
+        &Claims {
+            sub: "svc-reporting".to_string(),
+            exp: chrono::Utc::now().timestamp() as u64 + 600,
+            scope: Some("users:read".to_string()),
+            roles: vec!["reader".to_string(), "auditor".to_string()],
+        },
+    )
+    .unwrap()
+}
+
+async fn received(server: &MockServer) -> Request {
+    for _ in 0..100 {
+        if let Some(request) = server.received_requests().await.unwrap_or_default().pop() {
+            return request;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("nothing reached {}", server.uri());
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[tokio::test]
+async fn spoofed_identity_is_replaced_with_the_verified_caller() {
+    let legacy = upstream().await;
+    let mirror = upstream().await;
+    let mut config = legacy_config(&legacy);
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let token = token(&config);
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(&token)
+        .header("X-Auth-Subject", "admin")
+        .header("X-Auth-Tenant", "other")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    for request in [received(&legacy).await, received(&mirror).await] {
+        assert_eq!(header(&request, "x-auth-subject"), Some("svc-reporting"));
+        assert_eq!(header(&request, "x-auth-roles"), Some("reader,auditor"));
+        assert_eq!(header(&request, "x-auth-scopes"), Some("users:read"));
+        assert_eq!(header(&request, "x-auth-method"), Some("jwt"));
+        assert_eq!(header(&request, "x-auth-tenant"), None);
+        assert_eq!(request.headers.get_all("x-auth-subject").iter().count(), 1);
+    }
+}
+
+#[tokio::test]
+async fn header_names_are_configurable() {
+    let legacy = upstream().await;
+    let mut config = legacy_config(&legacy);
+    config.middleware.auth.identity_headers.subject = "X-Remote-User".to_string();
+    let token = token(&config);
+    let app = spawn_app(config).await;
+
+    reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(&token)
+        .header("X-Remote-User", "admin")
+        .send()
+        .await
+        .unwrap();
+
+    let request = received(&legacy).await;
+    assert_eq!(header(&request, "x-remote-user"), Some("svc-reporting"));
+    assert_eq!(header(&request, "x-auth-subject"), None);
+    assert_eq!(header(&request, "x-auth-method"), Some("jwt"));
+}
+
+#[tokio::test]
+async fn disabled_forwarding_passes_headers_through() {
+    let legacy = upstream().await;
+    let mut config = legacy_config(&legacy);
+    config.middleware.auth.identity_headers.enabled = false;
+    let token = token(&config);
+    let app = spawn_app(config).await;
+
+    reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .bearer_auth(&token)
+        .header("X-Auth-Subject", "admin")
+        .send()
+        .await
+        .unwrap();
+
+    let request = received(&legacy).await;
+    assert_eq!(header(&request, "x-auth-subject"), Some("admin"));
+    assert_eq!(header(&request, "x-auth-method"), None);
+}
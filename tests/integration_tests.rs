@@ -1,7 +1,4 @@
-use reqwest;
 use serde_json::Value;
-use std::time::Duration;
-use tokio::time::sleep;
 
 const BASE_URL: &str = "http://127.0.0.1:3000";
 
@@ -10,7 +7,7 @@ async fn test_health_endpoint() {
     let client = reqwest::Client::new();
 
     // Test main health endpoint
-    let response = client.get(&format!("{}/health", BASE_URL)).send().await;
+    let response = client.get(format!("{}/health", BASE_URL)).send().await;
 
     match response {
         Ok(resp) => {
@@ -32,7 +29,7 @@ async fn test_api_health_endpoint() {
     let client = reqwest::Client::new();
 
     let response = client
-        .get(&format!("{}/api/v1/health", BASE_URL))
+        .get(format!("{}/api/v1/health", BASE_URL))
         .send()
         .await;
 
@@ -55,7 +52,7 @@ async fn test_users_list_endpoint() {
     let client = reqwest::Client::new();
 
     let response = client
-        .get(&format!("{}/api/v1/users", BASE_URL))
+        .get(format!("{}/api/v1/users", BASE_URL))
         .send()
         .await;
 
@@ -77,7 +74,7 @@ async fn test_users_list_endpoint() {
 async fn test_metrics_endpoint() {
     let client = reqwest::Client::new();
 
-    let response = client.get(&format!("{}/metrics", BASE_URL)).send().await;
+    let response = client.get(format!("{}/metrics", BASE_URL)).send().await;
 
     match response {
         Ok(resp) => {
@@ -0,0 +1,131 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{validation::check, IpFilterConfig, Severity},
+    middleware::ip_filter::{Block, IpFilter},
+};
+use serde_json::Value;
+
+async fn get(app: &TestApp, path: &str, forwarded_for: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(app.url(path))
+        .header("X-Gateway-Version", "rust")
+        .header("X-Forwarded-For", forwarded_for)
+        .send()
+        .await
+        .unwrap()
+}
+
+fn strings(entries: &[&str]) -> Vec<String> {
+    entries.iter().map(|entry| entry.to_string()).collect()
+}
+
+#[tokio::test]
+async fn denied_clients_get_403_behind_a_trusted_proxy() {
+    let mut config = base_config();
+    config.middleware.ip_filter.deny = strings(&["203.0.113.0/24"]);
+    let app = spawn_app(config).await;
+
+    // The test client connects from 127.0.0.1, a trusted proxy by default
+    let blocked = get(&app, "/health", "203.0.113.9").await;
+    assert_eq!(blocked.status(), 403);
+    let body: Value = blocked.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "ip_blocked");
+    assert_eq!(get(&app, "/health", "198.51.100.1").await.status(), 200);
+    // A hop that isn't an address can't be cleared against the list
+    assert_eq!(get(&app, "/health", "unknown").await.status(), 403);
+
+    let metrics = app.scrape_metrics().await;
+    assert!(metric_value(&metrics, "gateway_ip_blocked_total", &[("reason", "denied")]) >= 1.0);
+}
+
+#[tokio::test]
+async fn forwarded_for_is_ignored_from_untrusted_peers() {
+    let mut config = base_config();
+    config.middleware.ip_filter.deny = strings(&["203.0.113.0/24", "127.0.0.1"]);
+    config.middleware.ip_filter.trusted_proxies = strings(&["10.0.0.0/8"]);
+    let app = spawn_app(config).await;
+
+    // The peer's own address decides, whatever it claims to forward for
+    assert_eq!(get(&app, "/health", "198.51.100.1").await.status(), 403);
+
+    let mut config = base_config();
+    config.middleware.ip_filter.deny = strings(&["203.0.113.0/24"]);
+    config.middleware.ip_filter.trusted_proxies = strings(&["10.0.0.0/8"]);
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(get(&app, "/health", "203.0.113.9").await.status(), 200);
+}
+
+#[tokio::test]
+async fn the_allowlist_guards_only_admin_paths_and_deny_comes_first() {
+    let mut config = base_config();
+    config.middleware.ip_filter.allow = strings(&["198.51.100.0/24"]);
+    let app = spawn_app(config.clone()).await;
+
+    assert_eq!(get(&app, "/admin/mirror/status", "198.51.100.7").await.status(), 200);
+    assert_eq!(get(&app, "/admin/mirror/status", "192.0.2.1").await.status(), 403);
+    assert_eq!(get(&app, "/health", "192.0.2.1").await.status(), 200);
+    let metrics = app.scrape_metrics().await;
+    assert!(metric_value(&metrics, "gateway_ip_blocked_total", &[("reason", "not_allowed")]) >= 1.0);
+
+    // Reloaded lists apply to the next request
+    config.middleware.ip_filter.deny = strings(&["198.51.100.7/32"]);
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(get(&app, "/admin/mirror/status", "198.51.100.7").await.status(), 403);
+    assert_eq!(get(&app, "/admin/mirror/status", "198.51.100.8").await.status(), 200);
+}
+
+#[test]
+fn allow_paths_match_whole_segments() {
+    let config = IpFilterConfig {
+        allow: strings(&["10.0.0.0/8", "2001:db8::/32"]),
+        ..IpFilterConfig::default()
+    };
+    let filter = IpFilter::new();
+    let outside = Some("192.0.2.1".parse().unwrap());
+    assert_eq!(filter.check(&config, outside, "/admin"), Some(Block::NotAllowed));
+    assert_eq!(filter.check(&config, outside, "/admin/rollout"), Some(Block::NotAllowed));
+    assert_eq!(filter.check(&config, outside, "/administrator"), None);
+    assert_eq!(filter.check(&config, Some("2001:db8::1".parse().unwrap()), "/admin"), None);
+    // An unknown client isn't on the allowlist, and is denied once there's
+    // a deny list
+    assert_eq!(filter.check(&config, None, "/admin"), Some(Block::NotAllowed));
+    let denying = IpFilterConfig {
+        deny: strings(&["203.0.113.0/24"]),
+        ..config.clone()
+    };
+    assert_eq!(filter.check(&denying, None, "/api/v1/users"), Some(Block::Denied));
+
+    // A trailing slash still covers the prefix itself
+    let slashed = IpFilterConfig {
+        allow_paths: strings(&["/admin/"]),
+        ..config.clone()
+    };
+    assert_eq!(filter.check(&slashed, outside, "/admin"), Some(Block::NotAllowed));
+    assert_eq!(filter.check(&slashed, outside, "/admin/rollout"), Some(Block::NotAllowed));
+    assert_eq!(filter.check(&slashed, outside, "/administrator"), None);
+
+    let everywhere = IpFilterConfig {
+        allow_paths: Vec::new(),
+        ..config
+    };
+    assert_eq!(filter.check(&everywhere, outside, "/api/v1/users"), Some(Block::NotAllowed));
+}
+
+#[test]
+fn malformed_networks_are_rejected_by_validation() {
+    let mut config = base_config();
+    config.middleware.ip_filter = IpFilterConfig {
+        deny: strings(&["10.0.0.0/33"]),
+        allow: strings(&["office"]),
+        allow_paths: strings(&["admin"]),
+        trusted_proxies: strings(&["192.168.0.1/24/8"]),
+    };
+    let fields: Vec<&str> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "middleware.ip_filter")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, ["deny", "allow", "trusted_proxies", "allow_paths"]);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use project_gateway::{
+    config::{validation::check, Severity},
+    middleware::auth::{issue_token, Claims},
+};
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const PRIVATE_KEY: &str = include_str!("fixtures/jwks_rsa.pem");
+const PUBLIC_KEY: &str = include_str!("fixtures/jwks_rsa_public.json");
+
+/// The fixture's public key published under each of `kids`.
+fn key_set(kids: &[&str]) -> Value {
+    let public: Value = serde_json::from_str(PUBLIC_KEY).unwrap();
+    let keys: Vec<Value> = kids
+        .iter()
+        .map(|kid| {
+            let mut key = public.clone();
+            key["kid"] = json!(kid);
+            key
+        })
+        .collect();
+    json!({ "keys": keys })
+}
+
+fn rs256_token(kid: &str) -> String {
+    signed(kid, "idp-user")
+}
+
+fn signed(kid: &str, sub: &str) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(kid.to_string());
+    let claims = json!({ "sub": sub, "exp": chrono::Utc::now().timestamp() + 600 });
+    encode(&header, &claims, &EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap()).unwrap()
+}
+
+/// Replaces whatever the identity provider served before.
+async fn publish(idp: &MockServer, response: ResponseTemplate) {
+    idp.reset().await;
+    Mock::given(method("GET")).respond_with(response).mount(idp).await;
+}
+
+async fn identity_provider(kids: &[&str]) -> MockServer {
+    let idp = MockServer::start().await;
+    publish(&idp, ResponseTemplate::new(200).set_body_json(key_set(kids))).await;
+    idp
+}
+
+fn jwks_url(idp: &MockServer) -> String {
+    format!("{}/.well-known/jwks.json", idp.uri())
+}
+
+async fn jwks_app(idp: &MockServer) -> TestApp {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.jwks_url = Some(jwks_url(idp));
+    // Only authentication is under test here
+    for route in &mut config.routes {
+        route.authorization = None;
+    }
+    spawn_app(config).await
+}
+
+async fn status(app: &TestApp, token: &str) -> u16 {
+    reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+async fn fetches(idp: &MockServer) -> usize {
+    idp.received_requests().await.unwrap().len()
+}
+
+#[tokio::test]
+async fn tokens_are_verified_with_the_key_their_kid_names() {
+    let idp = identity_provider(&["key-1"]).await;
+    let app = jwks_app(&idp).await;
+
+    let token = rs256_token("key-1");
+    assert_eq!(status(&app, &token).await, 200);
+    assert_eq!(status(&app, &token).await, 200);
+    assert_eq!(fetches(&idp).await, 1);
+
+    // Another token's claims under this token's signature don't verify
+    let parts: Vec<&str> = token.split('.').collect();
+    let other = signed("key-1", "admin");
+    let forged = format!("{}.{}.{}", parts[0], other.split('.').nth(1).unwrap(), parts[2]);
+    assert_eq!(status(&app, &forged).await, 401);
+
+    // Tokens signed with the shared secret keep working alongside
+    let config = app.state.config_watcher.get_config().await;
+    let claims = Claims {
+        sub: "service".to_string(),
+        exp: chrono::Utc::now().timestamp() as u64 + 600,
+        scope: None,
+        roles: Vec::new(),
+    };
+    assert_eq!(status(&app, &issue_token(&config.middleware.auth, &claims).unwrap()).await, 200);
+}
+
+#[tokio::test]
+async fn unknown_kid_forces_one_refresh_before_rejection() {
+    let idp = identity_provider(&["key-1"]).await;
+    let app = jwks_app(&idp).await;
+    assert_eq!(status(&app, &rs256_token("key-1")).await, 200);
+
+    // A rotated-in key is fetched on first sight
+    publish(&idp, ResponseTemplate::new(200).set_body_json(key_set(&["key-1", "key-2"]))).await;
+    assert_eq!(status(&app, &rs256_token("key-2")).await, 200);
+    assert_eq!(fetches(&idp).await, 1);
+
+    // A key the provider doesn't publish is rejected after one more fetch,
+    // and doesn't cause another straight away
+    assert_eq!(status(&app, &rs256_token("ghost")).await, 401);
+    assert_eq!(fetches(&idp).await, 2);
+    assert_eq!(status(&app, &rs256_token("ghost")).await, 401);
+    assert_eq!(fetches(&idp).await, 2);
+}
+
+#[tokio::test]
+async fn failed_refresh_keeps_the_last_good_key_set() {
+    let idp = identity_provider(&["key-1"]).await;
+    let app = jwks_app(&idp).await;
+    assert_eq!(status(&app, &rs256_token("key-1")).await, 200);
+
+    publish(&idp, ResponseTemplate::new(503)).await;
+    let refreshed = app.state.jwks.refresh(&app.state.upstreams, &jwks_url(&idp)).await;
+    assert!(refreshed.is_err());
+    assert_eq!(app.state.jwks.keys(&jwks_url(&idp)).keys.len(), 1);
+    // A token the auth cache hasn't seen still verifies against the old set
+    assert_eq!(status(&app, &signed("key-1", "after-outage")).await, 200);
+}
+
+#[test]
+fn jwks_url_can_stand_in_for_shared_secrets() {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.jwt_secrets.clear();
+    assert!(check(&config).iter().any(|issue| issue.severity == Severity::Error && issue.field == "jwt_secrets"));
+
+    config.middleware.auth.jwks_url = Some("https://idp.example.com/.well-known/jwks.json".to_string());
+    assert!(check(&config).iter().all(|issue| issue.section != "middleware.auth"));
+
+    config.middleware.auth.jwks_url = Some("not a url".to_string());
+    assert!(check(&config).iter().any(|issue| issue.severity == Severity::Error && issue.field == "jwks_url"));
+}
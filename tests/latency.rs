@@ -0,0 +1,89 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::monitoring::{PerformanceMonitor, UpstreamTiming};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const UPSTREAM_DELAY_MS: f64 = 150.0;
+
+#[tokio::test]
+async fn legacy_latency_decomposes_into_upstream_and_overhead() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("{\"users\":[]}")
+                .set_delay(Duration::from_millis(UPSTREAM_DELAY_MS as u64)),
+        )
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+
+    let client = reqwest::Client::new();
+    for _ in 0..5 {
+        let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let legacy_metrics = app
+        .state
+        .performance_monitor
+        .get_current_metrics("legacy")
+        .expect("legacy metrics");
+    let upstream = legacy_metrics.upstream_latency_ms.expect("upstream latency");
+    let first_byte = legacy_metrics.upstream_first_byte_ms.expect("first byte latency");
+    let overhead = legacy_metrics.gateway_overhead_ms.expect("gateway overhead");
+
+    assert_eq!(legacy_metrics.request_count, 5);
+    assert!(upstream.p50_ms >= UPSTREAM_DELAY_MS, "upstream {:?}", upstream);
+    assert!(first_byte.p50_ms <= upstream.p50_ms);
+    assert!(overhead.avg_ms >= 0.0 && overhead.avg_ms < UPSTREAM_DELAY_MS);
+    let sum = upstream.avg_ms + overhead.avg_ms;
+    assert!(
+        (sum - legacy_metrics.avg_latency_ms).abs() < 1.0,
+        "upstream {} + overhead {} != total {}",
+        upstream.avg_ms,
+        overhead.avg_ms,
+        legacy_metrics.avg_latency_ms
+    );
+
+    let status: Value = client
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(status["latency"]["legacy_upstream_ms"]["p50_ms"].as_f64().unwrap() >= UPSTREAM_DELAY_MS);
+    assert!(status["latency"]["gateway_overhead_ms"]["avg_ms"].is_number());
+}
+
+#[test]
+fn validation_compares_rust_against_legacy_upstream_time() {
+    let monitor = PerformanceMonitor::new();
+    monitor.record_request("rust", 50.0, false);
+    // 100ms in the legacy upstream plus 20ms of gateway proxy overhead
+    monitor.record_request("legacy", 120.0, false);
+    monitor.record_legacy_upstream(UpstreamTiming {
+        first_byte_ms: 80.0,
+        full_body_ms: 100.0,
+        overhead_ms: 20.0,
+    });
+
+    let rust = monitor.get_current_metrics("rust").unwrap();
+    let legacy = monitor.get_current_metrics("legacy").unwrap();
+    monitor.set_baseline(rust, legacy);
+    assert_eq!(monitor.get_baseline().unwrap().improvement_factor, 2.0);
+
+    let validation = monitor.validate_performance();
+    assert_eq!(validation.latency_improvement_percent, 50.0);
+    assert_eq!(validation.gateway_overhead_ms.unwrap().p99_ms, 20.0);
+}
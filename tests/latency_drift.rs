@@ -0,0 +1,238 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::{
+    config::{validation::check, DriftMeasure, LatencyDriftConfig, Severity},
+    gatekeeper::{decide, Gatekeeper, HealthSnapshot, HealthThresholds, RuleOutcome},
+    monitoring::{
+        drift::LatencyDrift,
+        stats::{ks_statistic, population_stability_index, LatencyHistogram},
+    },
+};
+use serde_json::Value;
+
+/// `count` latencies spread evenly over a log-logistic body with median
+/// `median_ms`, and its slowest 1.5% at about `tail_ms`.
+fn latencies(count: usize, median_ms: f64, tail_ms: f64) -> Vec<f64> {
+    let body = count * 985 / 1000;
+    let tail = count - body;
+    let body = (0..body).map(|i| {
+        let q = (i as f64 + 0.5) / body as f64;
+        median_ms * (q / (1.0 - q)).powf(0.25)
+    });
+    let tail = (0..tail).map(move |i| tail_ms * (1.0 + 0.5 * i as f64 / tail as f64));
+    body.chain(tail).collect()
+}
+
+fn p99(samples: &[f64]) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted[(sorted.len() as f64 * 0.99) as usize]
+}
+
+#[test]
+fn same_shape_scores_near_zero() {
+    let reference = LatencyHistogram::from_samples(latencies(5000, 3.0, 40.0));
+    // Fewer samples of the same distribution
+    let current = LatencyHistogram::from_samples(latencies(700, 3.0, 40.0));
+
+    assert!(ks_statistic(&reference, &current).unwrap() < 0.02);
+    let psi = population_stability_index(&reference, &current).unwrap();
+    assert!(psi < 0.05, "PSI {psi}");
+    assert_eq!(ks_statistic(&reference, &reference), Some(0.0));
+    assert_eq!(ks_statistic(&reference, &LatencyHistogram::new()), None);
+}
+
+#[test]
+fn a_shifted_body_under_the_same_p99_scores_high() {
+    let before = latencies(5000, 3.0, 40.0);
+    let after = latencies(5000, 6.0, 40.0);
+    // The p99 gate sees nothing: the tail hasn't moved
+    assert!((p99(&after) / p99(&before) - 1.0).abs() < 0.01);
+
+    let reference = LatencyHistogram::from_samples(before);
+    let current = LatencyHistogram::from_samples(after);
+    let ks = ks_statistic(&reference, &current).unwrap();
+    let psi = population_stability_index(&reference, &current).unwrap();
+    assert!(ks > 0.5, "KS {ks}");
+    assert!(psi > 1.0, "PSI {psi}");
+
+    // A small shift scores less than a large one
+    let nudged = LatencyHistogram::from_samples(latencies(5000, 3.3, 40.0));
+    assert!(ks_statistic(&reference, &nudged).unwrap() < ks);
+    assert!(population_stability_index(&reference, &nudged).unwrap() < psi);
+}
+
+#[test]
+fn extreme_latencies_land_in_the_edge_buckets() {
+    let low = LatencyHistogram::from_samples([0.0, 0.001, f64::NAN]);
+    let high = LatencyHistogram::from_samples([1e9, f64::INFINITY]);
+    assert_eq!((low.count(), high.count()), (3, 2));
+    assert_eq!(ks_statistic(&low, &high), Some(1.0));
+}
+
+fn drift_config() -> LatencyDriftConfig {
+    LatencyDriftConfig {
+        enabled: true,
+        measure: DriftMeasure::Ks,
+        max_score: 0.2,
+        reference_window: "10m".parse().unwrap(),
+        evaluation_interval: "60s".parse().unwrap(),
+        sustained_intervals: 2,
+        min_samples: 100,
+    }
+}
+
+fn record(drift: &LatencyDrift, variant: &'static str, samples: &[f64]) {
+    for &latency_ms in samples {
+        drift.record(variant, "/api/v1/users", latency_ms);
+    }
+}
+
+#[test]
+fn only_sustained_drift_is_reported() {
+    let config = drift_config();
+    let drift = LatencyDrift::new();
+    // Nothing is recorded before the first evaluation turns detection on
+    record(&drift, "rust", &latencies(500, 3.0, 40.0));
+    assert!(drift.evaluate(&config).is_empty());
+
+    // The first interval only builds the reference
+    record(&drift, "rust", &latencies(500, 3.0, 40.0));
+    let first = drift.evaluate(&config);
+    assert_eq!(first[0].score, None);
+    assert_eq!((first[0].samples, first[0].reference_samples), (500, 0));
+
+    record(&drift, "rust", &latencies(400, 3.0, 40.0));
+    let same = drift.evaluate(&config);
+    assert!(same[0].score.unwrap() < 0.05);
+    assert_eq!(same[0].drifting_intervals, 0);
+
+    // One drifting interval is not yet sustained
+    record(&drift, "rust", &latencies(400, 6.0, 40.0));
+    let once = drift.evaluate(&config);
+    assert!(once[0].score.unwrap() > config.max_score);
+    assert!(!once[0].sustained);
+    assert!(drift.sustained(&config).is_empty());
+
+    // Too little traffic leaves the streak alone
+    record(&drift, "rust", &latencies(10, 6.0, 40.0));
+    assert_eq!(drift.evaluate(&config)[0].drifting_intervals, 1);
+
+    record(&drift, "rust", &latencies(400, 6.0, 40.0));
+    let twice = drift.evaluate(&config);
+    assert_eq!(twice[0].variant, "rust");
+    assert!(twice[0].sustained);
+    assert_eq!(drift.sustained(&config), ["rust /api/v1/users"]);
+
+    // Switching detection off forgets everything
+    let off = LatencyDriftConfig { enabled: false, ..config };
+    assert!(drift.evaluate(&off).is_empty());
+    assert!(drift.status(&drift_config()).is_empty());
+}
+
+#[test]
+fn drift_holds_the_rollout_without_rolling_back() {
+    let thresholds = HealthThresholds {
+        max_errors: 0.5,
+        max_latency_degradation: 10.0,
+        monitor_latency_p99: true,
+        rollback_on_fast_burn: false,
+        hold_on_latency_drift: true,
+        step: 5.0,
+        scoped_rollback: true,
+        min_route_requests: 20,
+    };
+    let snapshot = HealthSnapshot {
+        rollout_percentage: 20.0,
+        effective_percentage: 20.0,
+        drifting_latency: vec!["rust /api/v1/users".to_string()],
+        ..HealthSnapshot::default()
+    };
+
+    let decision = decide(&snapshot, &thresholds);
+    assert!(decision.healthy);
+    assert!(decision.action.is_none());
+    assert_eq!(
+        decision.hold_reason.as_deref(),
+        Some("Latency distribution drifting: rust /api/v1/users")
+    );
+    let rule = decision.rules.iter().find(|rule| rule.rule == "latency_drift").unwrap();
+    assert_eq!((rule.outcome, rule.observed), (RuleOutcome::Fail, 1.0));
+
+    let off = decide(&snapshot, &HealthThresholds { hold_on_latency_drift: false, ..thresholds });
+    assert!(off.hold_reason.is_none());
+}
+
+#[tokio::test]
+async fn sustained_drift_blocks_advancement_and_is_reported() {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 20.0;
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    config.canary_rollout.latency_drift = drift_config();
+    let app = spawn_app(config.clone()).await;
+    let drift = &app.state.latency_drift;
+    let gatekeeper = Gatekeeper::new(app.state.clone());
+
+    drift.evaluate(&config.canary_rollout.latency_drift);
+    for median_ms in [3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 6.0, 6.0] {
+        record(drift, "rust", &latencies(400, median_ms, 40.0));
+        record(drift, "legacy", &latencies(400, 3.0, 40.0));
+        drift.evaluate(&config.canary_rollout.latency_drift);
+    }
+
+    let status = gatekeeper.get_status().await;
+    assert!(status.is_healthy);
+    assert!(!status.rollback_triggered);
+    assert_eq!(status.latency_drift.len(), 1);
+    assert_eq!(status.latency_drift[0].variant, "rust");
+    assert!(!gatekeeper.advance_rollout().await);
+    assert_eq!(app.state.coordinator.state().await.rollout_percentage, 20.0);
+
+    let report: Value = reqwest::Client::new()
+        .get(app.url("/monitoring/performance"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let series = report["latency_drift"].as_array().unwrap();
+    assert_eq!(series.len(), 2);
+    let legacy = series.iter().find(|series| series["variant"] == "legacy").unwrap();
+    assert_eq!(legacy["sustained"], false);
+    assert!(legacy["score"].as_f64().unwrap() < 0.05);
+
+    let metrics = app.scrape_metrics().await;
+    let score = metric_value(
+        &metrics,
+        "gateway_latency_drift_score",
+        &[("variant", "rust"), ("route", "/api/v1/users")],
+    );
+    assert!(score > 0.2, "{score}");
+
+    // Back to the shape that dominates the reference, the streak ends and
+    // the rollout moves
+    record(drift, "rust", &latencies(400, 3.0, 40.0));
+    drift.evaluate(&config.canary_rollout.latency_drift);
+    assert!(gatekeeper.get_status().await.latency_drift.is_empty());
+    assert!(gatekeeper.advance_rollout().await);
+}
+
+#[test]
+fn drift_settings_are_validated() {
+    let mut config = base_config();
+    config.canary_rollout.latency_drift = LatencyDriftConfig {
+        max_score: 0.0,
+        reference_window: "30s".parse().unwrap(),
+        sustained_intervals: 0,
+        ..drift_config()
+    };
+    let fields: Vec<&str> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "canary_rollout.latency_drift")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, ["max_score", "reference_window", "sustained_intervals"]);
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::base_config;
+use project_gateway::{
+    app::create_app,
+    config::{watcher::ConfigWatcher, AppConfig, TlsConfig},
+    listener::{ListenerEventKind, ListenerSupervisor},
+    monitoring::PerformanceMonitor,
+    AppState,
+};
+use serde_json::Value;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tempfile::NamedTempFile;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// A gateway served by a listener supervisor, the way `main` runs it.
+struct Instance {
+    state: AppState,
+    addr: SocketAddr,
+    config: AppConfig,
+    _config_file: NamedTempFile,
+}
+
+impl Instance {
+    async fn start(mut config: AppConfig) -> Self {
+        config.server.host = "127.0.0.1".to_string();
+        config.server.port = 0;
+        let config_file = NamedTempFile::new().unwrap();
+        let config_watcher = Arc::new(ConfigWatcher::new(config_file.path().to_str().unwrap(), config.clone()).unwrap());
+        let state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+        let app = create_app(state.clone()).await.unwrap();
+        let supervisor = ListenerSupervisor::bind(state.clone(), app).await.unwrap();
+        let addr = supervisor.local_addr();
+        tokio::spawn(supervisor.run());
+        Self {
+            state,
+            addr,
+            config,
+            _config_file: config_file,
+        }
+    }
+
+    async fn reload(&self, change: impl FnOnce(&mut AppConfig)) {
+        let mut config = self.config.clone();
+        change(&mut config);
+        self.state.config_watcher.apply(config).await;
+    }
+
+    fn events(&self) -> Vec<ListenerEventKind> {
+        let report = self.state.listener.report().unwrap();
+        report.events.iter().map(|event| event.kind).collect()
+    }
+
+    async fn wait_for_event(&self, kind: ListenerEventKind) {
+        for _ in 0..100 {
+            if self.events().contains(&kind) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no {kind:?} event within 2s: {:?}", self.events());
+    }
+}
+
+/// A port nothing is listening on, as far as anyone can tell.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+async fn slow_legacy(delay: Duration) -> (MockServer, AppConfig) {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(delay))
+        .mount(&legacy)
+        .await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    (legacy, config)
+}
+
+fn get(addr: SocketAddr, path: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new().get(format!("http://{}{}", addr, path))
+}
+
+#[tokio::test]
+async fn a_new_port_serves_while_the_old_one_drains() {
+    let (_legacy, config) = slow_legacy(Duration::from_millis(600)).await;
+    let instance = Instance::start(config).await;
+    let old = instance.addr;
+    assert_eq!(instance.events(), [ListenerEventKind::Bound]);
+
+    let in_flight = tokio::spawn(get(old, "/api/v1/users").send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let port = free_port();
+    instance.reload(|config| config.server.port = port).await;
+    instance.wait_for_event(ListenerEventKind::Rebound).await;
+    let new: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    let health: Value = get(new, "/api/v1/health")
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(health["listener"]["address"], format!("http://{}", new));
+    assert_eq!(health["listener"]["events"][1]["previous_address"], format!("http://{}", old));
+
+    // The request started before the rebind finishes on the old listener
+    assert_eq!(in_flight.await.unwrap().unwrap().status(), 200);
+    instance.wait_for_event(ListenerEventKind::Drained).await;
+    assert_eq!(
+        instance.events(),
+        [ListenerEventKind::Bound, ListenerEventKind::Rebound, ListenerEventKind::Drained]
+    );
+    let drained = instance.state.listener.report().unwrap().events[2].clone();
+    assert_eq!(drained.address, format!("http://{}", old));
+    assert!(drained.detail.is_none());
+
+    // Only the new port accepts connections now
+    assert!(tokio::net::TcpStream::connect(old).await.is_err());
+    assert_eq!(get(new, "/api/v1/users").send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn a_failed_rebind_keeps_the_old_listener() {
+    let instance = Instance::start(base_config()).await;
+
+    // Port taken by someone else
+    let squatter = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let taken = squatter.local_addr().unwrap().port();
+    instance.reload(|config| config.server.port = taken).await;
+    instance.wait_for_event(ListenerEventKind::RebindFailed).await;
+
+    // A certificate that doesn't exist
+    instance
+        .reload(|config| {
+            config.server.tls = Some(TlsConfig {
+                enabled: true,
+                cert_path: "/nonexistent/gateway.crt".to_string(),
+                key_path: "/nonexistent/gateway.key".to_string(),
+                certificates: Vec::new(),
+                expiry_warning_days: 30,
+                reload_interval_seconds: 60,
+                client_auth: None,
+            })
+        })
+        .await;
+    for _ in 0..100 {
+        if instance.events().len() == 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let report = instance.state.listener.report().unwrap();
+    let kinds: Vec<_> = report.events.iter().map(|event| event.kind).collect();
+    assert_eq!(
+        kinds,
+        [ListenerEventKind::Bound, ListenerEventKind::RebindFailed, ListenerEventKind::RebindFailed]
+    );
+    assert!(report.events[1].detail.as_ref().unwrap().contains(&taken.to_string()));
+    assert!(report.events[2].detail.as_ref().unwrap().contains("gateway.crt"));
+    assert_eq!(report.address, format!("http://{}", instance.addr));
+    assert_eq!(get(instance.addr, "/health").send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn connections_still_open_after_the_grace_period_are_closed() {
+    let (_legacy, mut config) = slow_legacy(Duration::from_secs(5)).await;
+    config.server.drain_grace_period = "200ms".parse().unwrap();
+    let instance = Instance::start(config).await;
+
+    let in_flight = tokio::spawn(get(instance.addr, "/api/v1/users").send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let port = free_port();
+    instance.reload(|config| config.server.port = port).await;
+    instance.wait_for_event(ListenerEventKind::Drained).await;
+
+    assert!(in_flight.await.unwrap().is_err());
+    let drained = instance.state.listener.report().unwrap().events[2].clone();
+    assert_eq!(
+        drained.detail.as_deref(),
+        Some("1 connection(s) closed at the end of the grace period")
+    );
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{validation::check, Severity},
+    middleware::load_shedding::LoadShedder,
+};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn shedding_app(legacy: &MockServer, max_in_flight: usize, exempt_health_checks: bool) -> TestApp {
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .mount(legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.middleware.load_shedding.max_in_flight = Some(max_in_flight);
+    config.middleware.load_shedding.exempt_health_checks = exempt_health_checks;
+    spawn_app(config).await
+}
+
+/// Sends `count` concurrent slow requests and returns their statuses.
+fn burst(app: &TestApp, count: usize) -> tokio::task::JoinHandle<Vec<u16>> {
+    let url = app.url("/api/v1/users");
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let requests = (0..count).map(|_| client.get(&url).send());
+        futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .map(|response| response.unwrap().status().as_u16())
+            .collect()
+    })
+}
+
+async fn eventually(condition: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("condition not reached within 2s");
+}
+
+fn count(statuses: &[u16], status: u16) -> usize {
+    statuses.iter().filter(|s| **s == status).count()
+}
+
+#[tokio::test]
+async fn requests_over_the_limit_are_shed_while_probes_answer() {
+    let legacy = MockServer::start().await;
+    let app = shedding_app(&legacy, 2, true).await;
+
+    let slow = burst(&app, 2);
+    let shedder = app.state.load_shedder.clone();
+    eventually(|| shedder.in_flight() == 2).await;
+
+    let client = reqwest::Client::new();
+    let shed = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(shed.status(), 503);
+    let body: Value = shed.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "server_busy");
+    assert!(!body["error"]["request_id"].as_str().unwrap().is_empty());
+
+    for probe in ["/health", "/readyz", "/api/v1/health"] {
+        let response = client.get(app.url(probe)).send().await.unwrap();
+        assert_ne!(response.status(), 503, "{probe} was shed");
+    }
+    // Other tests' apps share the gauge, so only check that it's published
+    assert!(app.scrape_metrics().await.contains("gateway_in_flight_requests"));
+
+    let statuses = slow.await.unwrap();
+    assert_eq!(count(&statuses, 200), 2, "{:?}", statuses);
+    eventually(|| shedder.in_flight() == 0).await;
+
+    let metrics = app.scrape_metrics().await;
+    assert!(metric_value(&metrics, "gateway_load_shed_total", &[("route", "/api/v1/users")]) >= 1.0);
+    let served = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(served.status(), 200);
+}
+
+#[tokio::test]
+async fn probes_are_shed_too_unless_exempt() {
+    let legacy = MockServer::start().await;
+    let app = shedding_app(&legacy, 1, false).await;
+
+    let slow = burst(&app, 1);
+    let shedder = app.state.load_shedder.clone();
+    eventually(|| shedder.in_flight() == 1).await;
+
+    let probe = reqwest::Client::new().get(app.url("/health")).send().await.unwrap();
+    assert_eq!(probe.status(), 503);
+    assert_eq!(slow.await.unwrap(), [200]);
+}
+
+#[test]
+fn a_changed_limit_takes_effect_for_new_requests() {
+    let shedder = LoadShedder::new();
+    let first = shedder.try_admit(Some(1)).unwrap();
+    assert!(shedder.try_admit(Some(1)).is_none());
+
+    // Raising the limit starts a fresh count; the old request still shows
+    let second = shedder.try_admit(Some(2)).unwrap();
+    let third = shedder.try_admit(Some(2)).unwrap();
+    assert!(shedder.try_admit(Some(2)).is_none());
+    assert_eq!(shedder.in_flight(), 3);
+
+    // Without a limit nothing is shed, but requests are still counted
+    let unlimited: Vec<_> = (0..50).map(|_| shedder.try_admit(None).unwrap()).collect();
+    assert_eq!(shedder.in_flight(), 53);
+    drop((first, second, third, unlimited));
+    assert_eq!(shedder.in_flight(), 0);
+}
+
+#[test]
+fn a_zero_limit_is_rejected() {
+    let mut config = base_config();
+    config.middleware.load_shedding.max_in_flight = Some(0);
+    let fields: Vec<&str> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "middleware.load_shedding")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, ["max_in_flight"]);
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::{
+    config::{validation::check, CronSchedule, MaintenanceWindow, Severity},
+    maintenance::Maintenance,
+};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+fn window(route_selector: &str, start_cron: &str, duration: &str) -> MaintenanceWindow {
+    MaintenanceWindow {
+        route_selector: route_selector.to_string(),
+        start_cron: start_cron.parse().unwrap(),
+        duration: duration.parse().unwrap(),
+        message: "Down for the weekly migration".to_string(),
+        timezone: "UTC".to_string(),
+    }
+}
+
+#[test]
+fn cron_schedules_match_their_minutes() {
+    // 2025-07-06 is a Sunday
+    let weekly: CronSchedule = "30 2 * * 7".parse().unwrap();
+    assert!(weekly.matches(&at("2025-07-06T02:30:59Z")));
+    assert!(!weekly.matches(&at("2025-07-06T02:31:00Z")));
+    assert!(!weekly.matches(&at("2025-07-07T02:30:00Z")));
+
+    let stepped: CronSchedule = "*/15 9-17 * * 1-5".parse().unwrap();
+    assert!(stepped.matches(&at("2025-07-07T09:45:00Z")));
+    assert!(!stepped.matches(&at("2025-07-07T09:50:00Z")));
+    assert!(!stepped.matches(&at("2025-07-06T09:45:00Z")));
+
+    // With both day fields restricted, either one matching is enough
+    let either: CronSchedule = "0 0 1 * 0".parse().unwrap();
+    assert!(either.matches(&at("2025-07-01T00:00:00Z")));
+    assert!(either.matches(&at("2025-07-06T00:00:00Z")));
+    assert!(!either.matches(&at("2025-07-02T00:00:00Z")));
+
+    for invalid in ["* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "MON * * * *"] {
+        assert!(invalid.parse::<CronSchedule>().is_err(), "{} parsed", invalid);
+    }
+    assert_eq!("0  2 * *   SUN".parse::<CronSchedule>().unwrap_err(), "\"SUN\" is not a cron field for 0-7");
+    assert_eq!("0  2 * * 0".parse::<CronSchedule>().unwrap().to_string(), "0 2 * * 0");
+}
+
+#[test]
+fn windows_open_and_close_on_schedule() {
+    let mut config = base_config();
+    config.maintenance_windows = vec![window("POST /api/v1/users*", "0 2 * * 0", "30m")];
+    let maintenance = Maintenance::new();
+
+    let transitions = maintenance.evaluate_at(&config, at("2025-07-06T01:59:59Z"));
+    assert!(transitions.started.is_empty());
+    assert!(maintenance.blocking("POST", "/api/v1/users").is_none());
+
+    let transitions = maintenance.evaluate_at(&config, at("2025-07-06T02:00:00Z"));
+    assert_eq!(transitions.started.len(), 1);
+    let active = maintenance.blocking("POST", "/api/v1/users").unwrap();
+    assert_eq!(active.started_at, at("2025-07-06T02:00:00Z"));
+    assert_eq!(active.ends_at, at("2025-07-06T02:30:00Z"));
+    // Only the selected method is blocked
+    assert!(maintenance.blocking("GET", "/api/v1/users").is_none());
+
+    // Still open later on, without announcing it again
+    let transitions = maintenance.evaluate_at(&config, at("2025-07-06T02:29:59Z"));
+    assert!(transitions.started.is_empty() && transitions.ended.is_empty());
+    assert_eq!(maintenance.active().len(), 1);
+
+    let transitions = maintenance.evaluate_at(&config, at("2025-07-06T02:30:00Z"));
+    assert_eq!(transitions.ended.len(), 1);
+    assert!(maintenance.active().is_empty());
+
+    // The schedule is read in the window's offset
+    config.maintenance_windows[0].timezone = "+02:00".to_string();
+    assert_eq!(maintenance.evaluate_at(&config, at("2025-07-06T00:10:00Z")).started.len(), 1);
+    assert_eq!(maintenance.evaluate_at(&config, at("2025-07-06T02:10:00Z")).ended.len(), 1);
+}
+
+#[tokio::test]
+async fn blocked_routes_answer_503_until_the_window_ends() {
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&mirror).await;
+    let mut config = base_config();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    config.maintenance_windows = vec![window("/api/v1/users", "0 2 * * 0", "30m")];
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+    let get = |path: &'static str| {
+        let request = client.get(app.url(path)).header("X-Gateway-Version", "rust");
+        async move { request.send().await.unwrap() }
+    };
+
+    app.state.maintenance.evaluate_at(&config, at("2025-07-06T02:05:00Z"));
+    let response = get("/api/v1/users").await;
+    assert_eq!(response.status(), 503);
+    assert_eq!(response.headers()["retry-after"], "Sun, 06 Jul 2025 02:30:00 GMT");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "maintenance");
+    assert_eq!(body["error"]["message"], "Down for the weekly migration");
+    assert_eq!(body["error"]["ends_at"], "2025-07-06T02:30:00Z");
+    assert_eq!(get("/api/v1/health").await.status(), 200);
+
+    let routes: Value = get("/admin/routes").await.json().await.unwrap();
+    let users = routes.as_array().unwrap().iter().find(|route| route["path"] == "/api/v1/users").unwrap();
+    assert_eq!(users["maintenance"]["route_selector"], "/api/v1/users");
+    assert_eq!(users["maintenance"]["ends_at"], "2025-07-06T02:30:00Z");
+    let status: Value = get("/gatekeeper/status").await.json().await.unwrap();
+    assert_eq!(status["maintenance"].as_array().unwrap().len(), 1);
+
+    // Blocked requests aren't mirrored
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mirrored = mirror.received_requests().await.unwrap();
+    assert!(mirrored.iter().all(|request| request.url.path() != "/api/v1/users"));
+    assert_eq!(
+        metric_value(&app.scrape_metrics().await, "gateway_maintenance_rejections_total", &[("route", "/api/v1/users")]),
+        1.0
+    );
+
+    app.state.maintenance.evaluate_at(&config, at("2025-07-06T02:30:00Z"));
+    assert_eq!(get("/api/v1/users").await.status(), 200);
+    let status: Value = get("/gatekeeper/status").await.json().await.unwrap();
+    assert!(status["maintenance"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn malformed_windows_are_rejected() {
+    let mut config = base_config();
+    config.maintenance_windows = vec![window("users", "0 2 * * 0", "0s"), window("GET,POST /api/*", "0 2 * * 0", "8d")];
+    config.maintenance_windows[1].timezone = "Europe/Berlin".to_string();
+    let fields: Vec<&str> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "maintenance_windows")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, ["route_selector", "duration", "route_selector", "duration", "timezone"]);
+
+    config.maintenance_windows = vec![window("DELETE /api/v1/users*", "*/30 * * * *", "7d")];
+    assert!(check(&config).iter().all(|issue| issue.section != "maintenance_windows"));
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, ByteSize},
+    memory::MemoryReport,
+    middleware::auth::{issue_token, validate_token, Claims},
+    monitoring::MirrorOutcome,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const TOKENS: usize = 2_000;
+
+fn store(report: &MemoryReport, name: &str) -> u64 {
+    report.stores.iter().find(|store| store.name == name).unwrap().bytes
+}
+
+/// Captures a few exchanges, fills the mirror ring, and caches `TOKENS`
+/// tokens, all well within the default budget.
+async fn fill_stores(app: &TestApp, config: &AppConfig) {
+    let client = reqwest::Client::new();
+    let response = client
+        .post(app.url("/admin/debug/capture"))
+        .header("X-Gateway-Version", "rust")
+        .json(&json!({ "route": "/api/v1/users", "max_requests": 5, "include_bodies": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    for _ in 0..5 {
+        let response = client
+            .get(app.url("/api/v1/users"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap();
+        response.bytes().await.unwrap();
+    }
+
+    for _ in 0..1_000 {
+        app.state.performance_monitor.record_mirror(MirrorOutcome {
+            success: true,
+            mismatch: false,
+            contract_violation: false,
+            mirror_latency_ms: 10.0,
+            main_latency_ms: 10.0,
+        });
+    }
+
+    let exp = chrono::Utc::now().timestamp() as u64 + 600;
+    for i in 0..TOKENS {
+        let claims = Claims {
+            sub: format!("user-{}", i),
+            exp: exp + i as u64,
+            scope: None,
+            roles: Vec::new(),
+        };
+        let token = issue_token(&config.middleware.auth, &claims).unwrap();
+        assert!(validate_token(&app.state.auth_cache, &config.middleware.auth, &token).is_some());
+    }
+}
+
+/// Applies a new budget and waits for the background enforcement to bring
+/// usage under it.
+async fn shrink_budget(app: &TestApp, config: &mut AppConfig, budget: u64) -> MemoryReport {
+    config.memory.budget = ByteSize::from_bytes(budget);
+    config.memory.evict_to_percentage = 90.0;
+    app.state.config_watcher.apply(config.clone()).await;
+    for _ in 0..100 {
+        let report = app.state.memory_budget.report();
+        if report.budget_bytes == budget && report.used_bytes <= budget {
+            return report;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("usage never came under {}: {:?}", budget, app.state.memory_budget.report());
+}
+
+#[tokio::test]
+async fn pressure_evicts_stores_in_priority_order() {
+    let mut config = base_config();
+    config.middleware.auth.jwt_secrets = vec!["memory-budget-secret".to_string()];
+    let app = spawn_app(config.clone()).await;
+    fill_stores(&app, &config).await;
+
+    let before = app.state.memory_budget.report();
+    let (capture, cache, mirror) = (
+        store(&before, "debug_capture"),
+        store(&before, "auth_cache"),
+        store(&before, "mirror_outcomes"),
+    );
+    assert!(capture > 0 && cache > 0 && mirror > 0, "{:?}", before);
+    assert_eq!(app.state.auth_cache.len(), TOKENS);
+
+    // Room for the mirror ring and half the cache: the capture goes, the
+    // cache shrinks, and the mirror ring is left alone
+    let low_water = mirror + cache / 2 + store(&before, "concurrency_limiter")
+        + store(&before, "request_rate_limiter");
+    let after = shrink_budget(&app, &mut config, low_water * 10 / 9).await;
+    assert!(after.used_bytes <= after.low_water_bytes, "{:?}", after);
+    assert_eq!(store(&after, "debug_capture"), 0);
+    assert!(store(&after, "auth_cache") < cache);
+    assert!(store(&after, "auth_cache") > 0);
+    assert_eq!(store(&after, "mirror_outcomes"), mirror);
+
+    assert!(app.state.auth_cache.len() < TOKENS);
+
+    let capture_results: Value = reqwest::Client::new()
+        .get(app.url("/admin/debug/capture/results"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(capture_results["capture"]["evicted"], 5);
+    assert!(capture_results["exchanges"].as_array().unwrap().is_empty());
+
+    // Below the mirror ring alone, everything evictable gives way
+    let after = shrink_budget(&app, &mut config, mirror / 2).await;
+    assert!(app.state.auth_cache.is_empty());
+    assert!(store(&after, "mirror_outcomes") < mirror);
+
+    let scrape = app.scrape_metrics().await;
+    for evicted in ["debug_capture", "auth_cache", "mirror_outcomes"] {
+        assert!(
+            metric_value(&scrape, "gateway_memory_evicted_bytes_total", &[("store", evicted)]) > 0.0,
+            "{}",
+            scrape
+        );
+    }
+}
+
+#[tokio::test]
+async fn detailed_health_reports_store_usage() {
+    let app = spawn_app(base_config()).await;
+    let health: Value = reqwest::Client::new()
+        .get(app.url("/api/v1/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let memory = &health["memory"];
+    assert_eq!(memory["budget_bytes"], 256 * 1024 * 1024);
+    let stores: Vec<(&str, bool)> = memory["stores"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|store| (store["name"].as_str().unwrap(), store["evictable"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(
+        stores,
+        [
+            ("debug_capture", true),
+            ("auth_cache", true),
+            ("mirror_outcomes", true),
+            ("mirror_queue", true),
+            ("concurrency_limiter", false),
+            ("request_rate_limiter", false)
+        ]
+    );
+}
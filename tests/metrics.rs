@@ -0,0 +1,127 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::metrics::{
+    install_recorder,
+    labels::{label, Dimension, LabelGuard, OTHER},
+};
+use std::time::Duration;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn byte_counters_advance_by_exact_body_sizes() {
+    let legacy = MockServer::start().await;
+    let legacy_body = "x".repeat(4096);
+    Mock::given(method("GET"))
+        .and(path("/api/v1/users"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(legacy_body.clone()))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.mirror.enabled = false;
+    config.canary_rollout.enabled = true;
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    let before = app.scrape_metrics().await;
+    let response_labels = [("route", "/api/v1/users"), ("variant", "legacy")];
+    let request_labels = [("route", "/api/v1/users")];
+
+    // Response direction: proxied through the legacy variant
+    let body = client
+        .get(app.url("/api/v1/users"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body.len(), legacy_body.len());
+
+    // Request direction: forced onto the Rust handler, which reads the whole body
+    let request_body = r#"{"username":"bytes","email":"bytes@gateway.internal"}"#;
+    let response = client
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    response.bytes().await.unwrap();
+
+    // Counters are reported once the bodies finish streaming
+    let mut after = String::new();
+    for _ in 0..50 {
+        after = app.scrape_metrics().await;
+        let response_delta = metric_value(&after, "gateway_response_bytes_total", &response_labels)
+            - metric_value(&before, "gateway_response_bytes_total", &response_labels);
+        let request_delta = metric_value(&after, "gateway_request_bytes_total", &request_labels)
+            - metric_value(&before, "gateway_request_bytes_total", &request_labels);
+        if response_delta >= legacy_body.len() as f64 && request_delta >= request_body.len() as f64 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(
+        metric_value(&after, "gateway_response_bytes_total", &response_labels)
+            - metric_value(&before, "gateway_response_bytes_total", &response_labels),
+        legacy_body.len() as f64
+    );
+    assert_eq!(
+        metric_value(&after, "gateway_request_bytes_total", &request_labels)
+            - metric_value(&before, "gateway_request_bytes_total", &request_labels),
+        request_body.len() as f64
+    );
+}
+
+fn overflows(dimension: &str) -> f64 {
+    metric_value(&install_recorder().render(), "gateway_metric_label_overflow_total", &[("dimension", dimension)])
+}
+
+#[test]
+fn flooded_dimension_is_capped_and_overflow_is_counted() {
+    let before = overflows("client");
+    let guard = LabelGuard::new();
+    guard.register(Dimension::Client, None, 10);
+
+    let labels: Vec<String> = (0..500).map(|i| guard.label(Dimension::Client, &format!("client-{}", i))).collect();
+    assert_eq!(guard.distinct(Dimension::Client), 10);
+    assert_eq!(labels.iter().filter(|label| *label == OTHER).count(), 490);
+    assert_eq!(labels[..10], (0..10).map(|i| format!("client-{}", i)).collect::<Vec<_>>());
+    // Values admitted before the cap keep their own label
+    assert_eq!(guard.label(Dimension::Client, "client-3"), "client-3");
+    assert_eq!(overflows("client") - before, 490.0);
+}
+
+#[test]
+fn values_off_the_allowlist_become_other() {
+    let guard = LabelGuard::new();
+    guard.register(Dimension::Slo, Some(vec!["users".to_string()]), 5);
+    assert_eq!(guard.label(Dimension::Slo, "users"), "users");
+    assert_eq!(guard.label(Dimension::Slo, "made-up"), OTHER);
+    assert_eq!(guard.distinct(Dimension::Slo), 1);
+
+    assert_eq!(guard.label(Dimension::Method, "GET"), "GET");
+    assert_eq!(guard.label(Dimension::Method, "BREW"), OTHER);
+}
+
+#[tokio::test]
+async fn route_labels_are_allowlisted_from_the_route_table() {
+    let _app = spawn_app(base_config()).await;
+
+    assert_eq!(label(Dimension::Route, "/api/v1/users"), "/api/v1/users");
+    assert_eq!(label(Dimension::Route, "/admin/profiles/:id"), "/admin/profiles/:id");
+    assert_eq!(label(Dimension::Route, "unmatched"), "unmatched");
+    for id in 0..100 {
+        assert_eq!(label(Dimension::Route, &format!("/api/v1/users/{}", id)), OTHER);
+    }
+}
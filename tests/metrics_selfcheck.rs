@@ -0,0 +1,47 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use serde_json::Value;
+
+async fn selfcheck(app: &TestApp) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(app.url("/admin/metrics/selfcheck"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn probe_series_show_up_in_the_scrape_with_their_deltas() {
+    let app = spawn_app(base_config()).await;
+
+    let (status, body) = selfcheck(&app).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["passed"], true);
+    let families = body["families"].as_array().unwrap();
+    let names: Vec<(&str, &str)> = families
+        .iter()
+        .map(|family| (family["family"].as_str().unwrap(), family["kind"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        names,
+        [("gateway_selfcheck_probe_total", "counter"), ("gateway_selfcheck_probe_seconds", "histogram")]
+    );
+    assert!(families.iter().all(|family| family["passed"] == true && family["observed_delta"] == 1.0));
+    assert!(families.iter().all(|family| family.get("detail").is_none()));
+
+    // Each run moves the probes again, and /metrics serves the same output
+    let before = metric_value(&app.scrape_metrics().await, "gateway_selfcheck_probe_total", &[]);
+    assert_eq!(selfcheck(&app).await.0, 200);
+    let after = metric_value(&app.scrape_metrics().await, "gateway_selfcheck_probe_total", &[]);
+    assert_eq!(after - before, 1.0);
+}
+
+#[tokio::test]
+async fn concurrent_checks_do_not_see_each_others_probes() {
+    let app = spawn_app(base_config()).await;
+    let checks = futures::future::join_all((0..8).map(|_| selfcheck(&app))).await;
+    assert!(checks.iter().all(|(status, body)| *status == 200 && body["passed"] == true));
+}
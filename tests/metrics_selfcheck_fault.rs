@@ -0,0 +1,56 @@
+//! Runs with `--features fault-injection`. The exporter hook is process-wide,
+//! so this binary holds a single test.
+
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{app::create_app, metrics::fault};
+use serde_json::Value;
+
+async fn get(app: &TestApp, path: &str) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(app.url(path))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn a_detached_exporter_fails_the_selfcheck_and_readiness() {
+    let app = spawn_app(base_config()).await;
+    let router = create_app(app.state.clone()).await.unwrap();
+
+    fault::detach_exporter(true);
+    let (status, body) = get(&app, "/admin/metrics/selfcheck").await;
+    assert_eq!(status, 503);
+    assert_eq!(body["passed"], false);
+    let details: Vec<&str> = body["families"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .inspect(|family| assert_eq!(family["passed"], false))
+        .map(|family| family["detail"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        details,
+        [
+            "gateway_selfcheck_probe_total missing from the scrape",
+            "gateway_selfcheck_probe_seconds_count missing from the scrape"
+        ]
+    );
+
+    // Warm-up finishes, but the instance never reports ready
+    let report = app.state.warmup.run(&app.state, &router).await;
+    let step = report.steps.iter().find(|step| step.name == "metrics_selfcheck").unwrap();
+    assert!(step.failed);
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, 503);
+    assert_eq!(body["ready"], false);
+
+    fault::detach_exporter(false);
+    assert_eq!(get(&app, "/admin/metrics/selfcheck").await.0, 200);
+    app.state.warmup.run(&app.state, &router).await;
+    assert_eq!(get(&app, "/readyz").await.0, 200);
+}
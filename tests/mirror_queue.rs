@@ -0,0 +1,172 @@
+mod common;
+
+use common::{base_config, metric_value};
+use project_gateway::{
+    config::{watcher::ConfigWatcher, AppConfig, ByteSize, MirrorQueueConfig, MirrorQueueKind},
+    mirror::{parity::ParityStore, DiskLog, MirrorJob, MirrorQueue, MirrorWorker},
+    monitoring::PerformanceMonitor,
+    upstream::UpstreamPool,
+};
+use std::{io::Write, path::Path, sync::Arc, time::Duration};
+use tempfile::{NamedTempFile, TempDir};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn disk_queue(dir: &Path) -> MirrorQueueConfig {
+    MirrorQueueConfig {
+        kind: MirrorQueueKind::Disk,
+        path: dir.to_str().unwrap().to_string(),
+        max_bytes: ByteSize::from_bytes(1024 * 1024),
+    }
+}
+
+fn job(n: usize) -> MirrorJob {
+    MirrorJob {
+        method: "GET".to_string(),
+        path_and_query: format!("/api/v1/users?n={}", n),
+        headers: vec![("x-request-id".to_string(), format!("req-{}", n).into_bytes())],
+        route: "/api/v1/users".to_string(),
+        main_status: 200,
+        main_latency_ms: 12.5,
+        main_bytes: Some(42),
+        main_body: None,
+        rollout_generation: None,
+        request_id: Some(format!("req-{}", n)),
+    }
+}
+
+/// A worker as the gateway starts one, sending to `mirror_url`.
+fn start_worker(queue: Arc<MirrorQueue>, mirror_url: &str) -> (tokio::task::JoinHandle<()>, NamedTempFile) {
+    let mut config: AppConfig = base_config();
+    config.mirror.base_url = mirror_url.to_string();
+    let config_file = NamedTempFile::new().unwrap();
+    std::fs::write(config_file.path(), serde_yaml::to_string(&config).unwrap()).unwrap();
+    let config_watcher = Arc::new(ConfigWatcher::new(config_file.path().to_str().unwrap(), config.clone()).unwrap());
+    let upstreams = Arc::new(UpstreamPool::new(&config.http_client));
+    let handle = MirrorWorker::new(
+        queue,
+        config_watcher,
+        upstreams,
+        Arc::new(PerformanceMonitor::new()),
+        Arc::new(ParityStore::memory()),
+    )
+    .spawn();
+    (handle, config_file)
+}
+
+async fn wait_for_requests(mirror: &MockServer, count: usize) -> Vec<String> {
+    for _ in 0..100 {
+        let received = mirror.received_requests().await.unwrap();
+        if received.len() >= count {
+            return received.iter().map(|request| request.url.query().unwrap_or_default().to_string()).collect();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("mirror received fewer than {} requests", count);
+}
+
+#[tokio::test]
+async fn persisted_entries_are_delivered_once_after_restart() {
+    let handle = project_gateway::metrics::install_recorder();
+    let dir = TempDir::new().unwrap();
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&mirror).await;
+
+    // Enqueued, then the gateway stops before its worker sends anything
+    let queue = MirrorQueue::open(&disk_queue(dir.path())).unwrap();
+    for n in 0..3 {
+        assert!(queue.push(job(n)));
+    }
+    assert_eq!(queue.depth(), 3);
+    drop(queue);
+
+    // After the restart the worker picks them up
+    let queue = Arc::new(MirrorQueue::open(&disk_queue(dir.path())).unwrap());
+    assert_eq!(queue.depth(), 3);
+    assert!(metric_value(&handle.render(), "gateway_mirror_queue_replayed_total", &[]) >= 3.0);
+    let (worker, _config) = start_worker(queue.clone(), &mirror.uri());
+    let mut delivered = wait_for_requests(&mirror, 3).await;
+    delivered.sort();
+    assert_eq!(delivered, ["n=0", "n=1", "n=2"]);
+    let received = mirror.received_requests().await.unwrap();
+    assert_eq!(received[0].headers["x-request-id"], "req-0");
+    assert_eq!(received[0].headers["x-mirrored-by"], "Rust-Gateway");
+    for _ in 0..100 {
+        if queue.depth() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(queue.depth(), 0);
+    worker.abort();
+    let _ = worker.await;
+    drop(queue);
+
+    // Another restart replays nothing
+    let queue = Arc::new(MirrorQueue::open(&disk_queue(dir.path())).unwrap());
+    assert_eq!(queue.depth(), 0);
+    let (_worker, _config) = start_worker(queue.clone(), &mirror.uri());
+    assert!(queue.push(job(3)));
+    let delivered = wait_for_requests(&mirror, 4).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(mirror.received_requests().await.unwrap().len(), 4);
+    assert_eq!(delivered[3], "n=3");
+}
+
+#[test]
+fn corrupt_tail_is_truncated_on_open() {
+    let dir = TempDir::new().unwrap();
+    let (mut log, _) = DiskLog::open(dir.path(), 1024 * 1024).unwrap();
+    for payload in [&b"first"[..], b"second", b"third"] {
+        assert!(log.append(payload).unwrap());
+    }
+    drop(log);
+
+    // The last record was half written when the process died
+    let segment = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "log") && std::fs::metadata(path).unwrap().len() > 0)
+        .unwrap();
+    let full = std::fs::metadata(&segment).unwrap().len();
+    std::fs::OpenOptions::new().write(true).open(&segment).unwrap().set_len(full - 2).unwrap();
+
+    let (mut log, recovery) = DiskLog::open(dir.path(), 1024 * 1024).unwrap();
+    assert_eq!(recovery.pending, 2);
+    assert_eq!(recovery.truncated_bytes, 8 + "third".len() as u64 - 2);
+    let (seq, payload) = log.read_next().unwrap().unwrap();
+    assert_eq!((seq, payload.as_slice()), (0, &b"first"[..]));
+    log.ack(seq).unwrap();
+    drop(log);
+
+    // Garbage after intact records is cut off too
+    let (log, _) = DiskLog::open(dir.path(), 1024 * 1024).unwrap();
+    drop(log);
+    let mut file = std::fs::OpenOptions::new().append(true).open(&segment).unwrap();
+    file.write_all(&[0xff; 5]).unwrap();
+    drop(file);
+    let (mut log, recovery) = DiskLog::open(dir.path(), 1024 * 1024).unwrap();
+    assert_eq!(recovery.pending, 1);
+    assert_eq!(recovery.truncated_bytes, 5);
+    assert_eq!(log.read_next().unwrap().unwrap().1, b"second");
+    assert!(log.read_next().unwrap().is_none());
+}
+
+#[test]
+fn full_log_refuses_records_until_consumed() {
+    let dir = TempDir::new().unwrap();
+    let (mut log, _) = DiskLog::open(dir.path(), 100).unwrap();
+    let payload = [7u8; 40];
+    assert!(log.append(&payload).unwrap());
+    assert!(log.append(&payload).unwrap());
+    assert!(!log.append(&payload).unwrap());
+    assert_eq!(log.disk_bytes(), 96);
+
+    let (first, _) = log.read_next().unwrap().unwrap();
+    let (second, _) = log.read_next().unwrap().unwrap();
+    // Only acknowledged, contiguous records are checkpointed
+    log.ack(second).unwrap();
+    assert_eq!(log.depth(), 2);
+    log.ack(first).unwrap();
+    assert_eq!(log.depth(), 0);
+    assert!(log.append(&payload).unwrap());
+}
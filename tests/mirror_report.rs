@@ -0,0 +1,270 @@
+mod common;
+
+use chrono::{DateTime, Duration, Utc};
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, MirrorReportConfig},
+    mirror::{
+        parity::{ParityRecord, ParityStore},
+        report::{MirrorReport, Verdict},
+    },
+};
+use serde_json::{json, Value};
+use std::{io::Write, path::Path};
+use tempfile::TempDir;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn record(at: DateTime<Utc>, method: &str, route: &str, main_status: u16, mirror_status: Option<u16>) -> ParityRecord {
+    ParityRecord {
+        at,
+        method: method.to_string(),
+        route: route.to_string(),
+        request_id: None,
+        main_status,
+        mirror_status,
+        main_latency_ms: 10.0,
+        mirror_latency_ms: if method == "GET" { 15.0 } else { 20.0 },
+        contract_violation: false,
+        body_match: None,
+    }
+}
+
+fn thresholds() -> MirrorReportConfig {
+    MirrorReportConfig {
+        min_samples: 10,
+        max_mismatch_rate: 5.0,
+        max_failure_rate: 5.0,
+        max_contract_violation_rate: 5.0,
+        max_p99_latency_delta_ms: 50.0,
+        examples: 2,
+    }
+}
+
+fn parity_config(dir: &Path) -> AppConfig {
+    let mut config = base_config();
+    config.mirror.parity.path = Some(dir.to_str().unwrap().to_string());
+    config.mirror.report = thresholds();
+    config
+}
+
+/// Ten in the morning three days ago, so a campaign's records span two
+/// daily files.
+fn campaign_start() -> DateTime<Utc> {
+    (Utc::now() - Duration::days(3)).date_naive().and_hms_opt(10, 0, 0).unwrap().and_utc()
+}
+
+async fn report(app: &TestApp, body: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url("/admin/mirror/report"))
+        .header("X-Gateway-Version", "rust")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Records a campaign into `dir`, crashing mid-write halfway through, and
+/// starts a gateway on it that records the rest.
+async fn campaign_across_a_restart(dir: &Path, config: AppConfig) -> TestApp {
+    let start = campaign_start();
+    let store = ParityStore::open(&config.mirror.parity, Utc::now()).unwrap();
+    for i in 0..20 {
+        store.record(record(start + Duration::seconds(i), "GET", "/api/v1/users", 200, Some(200)));
+    }
+    // Before the campaign, so outside any report on it
+    store.record(record(start - Duration::hours(1), "GET", "/api/v1/users", 200, Some(503)));
+    drop(store);
+    let first_day = dir.join(format!("{}.jsonl", start.format("%Y-%m-%d")));
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&first_day)
+        .unwrap()
+        .write_all(br#"{"at":"2026-"#)
+        .unwrap();
+    std::fs::write(dir.join("2000-01-01.jsonl"), "").unwrap();
+
+    let app = spawn_app(config).await;
+    assert!(!dir.join("2000-01-01.jsonl").exists(), "expired records weren't deleted");
+    let store = &app.state.mirror_parity;
+    let later = start + Duration::hours(1);
+    for (main, mirror) in [(201, Some(201)), (201, Some(201)), (201, Some(201)), (201, None), (201, Some(400))] {
+        store.record(record(later, "POST", "/api/v1/users", main, mirror));
+    }
+    let next_day = start + Duration::days(1);
+    for i in 0..20 {
+        let mirror_status = if i == 7 { 500 } else { 200 };
+        store.record(ParityRecord {
+            request_id: Some(format!("req-{}", i)),
+            ..record(next_day + Duration::seconds(i), "GET", "/api/v1/users", 200, Some(mirror_status))
+        });
+    }
+    app
+}
+
+#[tokio::test]
+async fn report_covers_records_from_before_a_restart() {
+    let dir = TempDir::new().unwrap();
+    let app = campaign_across_a_restart(dir.path(), parity_config(dir.path())).await;
+    let start = campaign_start();
+
+    let response = report(&app, json!({ "from": start, "to": start + Duration::days(2) })).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["persisted"], true);
+    assert_eq!(report["verdict"], "pass");
+    assert!(report.get("markdown").is_none());
+
+    // 40 GETs and 5 POSTs; the cut-short line and the earlier record are left out
+    let totals = &report["totals"];
+    assert_eq!(totals["requests"], 45);
+    assert_eq!(totals["failures"], 1);
+    assert_eq!(totals["status_mismatches"], 2);
+    assert!((totals["mismatch_rate"].as_f64().unwrap() - 2.0 / 44.0 * 100.0).abs() < 1e-9);
+    assert!((totals["failure_rate"].as_f64().unwrap() - 1.0 / 45.0 * 100.0).abs() < 1e-9);
+    assert_eq!(totals["latency_delta_ms"]["p50_ms"], 5.0);
+    assert_eq!(totals["latency_delta_ms"]["p99_ms"], 10.0);
+
+    let routes = report["routes"].as_array().unwrap();
+    assert_eq!(routes.len(), 2);
+    assert_eq!((&routes[0]["method"], &routes[0]["totals"]["requests"]), (&json!("GET"), &json!(40)));
+    assert_eq!(routes[0]["totals"]["status_matrix"], json!({ "200": { "200": 39, "500": 1 } }));
+    assert_eq!(routes[0]["verdict"], "pass");
+    assert_eq!(
+        routes[1]["totals"]["status_matrix"],
+        json!({ "201": { "201": 3, "400": 1, "failed": 1 } })
+    );
+    // Five requests are too few to judge the route on its own
+    assert_eq!(routes[1]["verdict"], Value::Null);
+
+    let examples = report["top_mismatches"].as_array().unwrap();
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[0]["route"], "/api/v1/users");
+    assert_eq!((&examples[0]["main_status"], &examples[0]["mirror_status"]), (&json!(200), &json!(500)));
+    assert_eq!(examples[0]["request_id"], "req-7");
+    assert_eq!(examples[1]["mirror_status"], 400);
+
+    // Only the day after the restart
+    let second_day: Value = report_json(&app, start + Duration::days(1), start + Duration::days(2)).await;
+    assert_eq!(second_day["totals"]["requests"], 20);
+}
+
+async fn report_json(app: &TestApp, from: DateTime<Utc>, to: DateTime<Utc>) -> Value {
+    report(app, json!({ "from": from, "to": to })).await.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn tighter_thresholds_fail_the_campaign_and_the_report_is_posted() {
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks/rollout"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&webhook)
+        .await;
+    let dir = TempDir::new().unwrap();
+    let mut config = parity_config(dir.path());
+    config.canary_rollout.webhook_url = format!("{}/hooks/rollout", webhook.uri());
+    let app = campaign_across_a_restart(dir.path(), config.clone()).await;
+
+    config.mirror.report.max_mismatch_rate = 2.0;
+    app.state.config_watcher.apply(config).await;
+    let start = campaign_start();
+    let report: Value = report(
+        &app,
+        json!({ "from": start, "to": start + Duration::days(2), "markdown": true, "notify": true }),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    assert_eq!(report["verdict"], "fail");
+    let failed: Vec<&str> = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|check| check["passed"] == false)
+        .map(|check| check["check"].as_str().unwrap())
+        .collect();
+    assert_eq!(failed, ["mismatch_rate", "failing_routes"]);
+    // 1 of 40 GETs is 2.5%
+    assert_eq!(report["routes"][0]["verdict"], "fail");
+    assert_eq!(report["routes"][0]["failed_checks"], json!(["mismatch_rate"]));
+
+    let markdown = report["markdown"].as_str().unwrap();
+    assert!(markdown.starts_with("# Mirror parity report: FAIL"));
+    assert!(markdown.contains("| GET /api/v1/users | 40 |"));
+    assert!(markdown.contains("| GET /api/v1/users | 200 | 500 | 1 | req-7 |"));
+
+    let posted = webhook.received_requests().await.unwrap();
+    assert_eq!(posted.len(), 1);
+    let payload: Value = serde_json::from_slice(&posted[0].body).unwrap();
+    assert_eq!(payload["verdict"], "fail");
+    assert_eq!(payload["attachment"]["content_type"], "text/markdown");
+    assert_eq!(payload["attachment"]["content"], markdown);
+}
+
+#[test]
+fn too_few_requests_fail_the_verdict() {
+    let start = campaign_start();
+    let records: Vec<ParityRecord> = (0..9)
+        .map(|i| record(start + Duration::seconds(i), "GET", "/api/v1/users", 200, Some(200)))
+        .collect();
+    let report = MirrorReport::compile(&records, start, start + Duration::days(1), true, &thresholds(), Utc::now());
+
+    assert_eq!(report.verdict, Verdict::Fail);
+    let min_samples = &report.checks[0];
+    assert_eq!((min_samples.check.as_str(), min_samples.passed), ("min_samples", false));
+    assert!(report.checks[1..].iter().all(|check| check.passed));
+    assert!(report.top_mismatches.is_empty());
+
+    let empty = MirrorReport::compile(&[], start, start + Duration::days(1), true, &thresholds(), Utc::now());
+    assert_eq!(empty.verdict, Verdict::Fail);
+    assert!(empty.totals.latency_delta_ms.is_none());
+}
+
+#[tokio::test]
+async fn mirrored_requests_are_recorded() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&legacy).await;
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(502)).mount(&mirror).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users?email=someone@example.com"))
+        .header("X-Request-Id", "req-mirrored")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let from = Utc::now() - Duration::minutes(1);
+    for _ in 0..100 {
+        let records = app.state.mirror_parity.read(from, Utc::now()).unwrap();
+        if let Some(record) = records.first() {
+            assert_eq!(record.route, "/api/v1/users");
+            assert_eq!(record.request_id.as_deref(), Some("req-mirrored"));
+            assert_eq!((record.main_status, record.mirror_status), (200, Some(502)));
+            assert!(!app.state.mirror_parity.is_persistent());
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("no parity record for the mirrored request");
+}
+
+#[tokio::test]
+async fn from_must_be_before_to() {
+    let app = spawn_app(base_config()).await;
+    let now = Utc::now();
+    let response = report(&app, json!({ "from": now, "to": now - Duration::hours(1) })).await;
+    assert_eq!(response.status(), 400);
+}
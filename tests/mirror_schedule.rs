@@ -0,0 +1,159 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::config::{validation::check, MirrorConfig, MirrorWindow, Severity, TimeWindow};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+fn window(window: &str, sample_percentage: f64) -> MirrorWindow {
+    MirrorWindow {
+        window: window.parse().unwrap(),
+        sample_percentage,
+    }
+}
+
+fn scheduled(timezone: &str, schedule: Vec<MirrorWindow>) -> MirrorConfig {
+    let mut mirror = base_config().mirror;
+    mirror.sample_percentage = 10.0;
+    mirror.timezone = timezone.to_string();
+    mirror.schedule = schedule;
+    mirror
+}
+
+#[test]
+fn percentage_switches_at_window_boundaries() {
+    // 2025-07-07 is a Monday
+    let mirror = scheduled("UTC", vec![window("Mon-Fri 09:00-17:30", 1.0)]);
+
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T08:59:59Z")), 10.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T09:00:00Z")), 1.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T17:29:59Z")), 1.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T17:30:00Z")), 10.0);
+    // Weekend days aren't in the window
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-12T12:00:00Z")), 10.0);
+    assert_eq!(mirror.active_window(at("2025-07-11T12:00:00Z")).unwrap().window.to_string(), "Mon-Fri 09:00-17:30");
+}
+
+#[test]
+fn overnight_windows_belong_to_their_start_day() {
+    let mirror = scheduled("UTC", vec![window("Fri 22:00-06:00", 50.0)]);
+
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-11T21:59:00Z")), 10.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-11T22:00:00Z")), 50.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-12T05:59:00Z")), 50.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-12T06:00:00Z")), 10.0);
+    // Thursday night isn't covered
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-10T23:00:00Z")), 10.0);
+
+    // Sunday night runs into Monday morning
+    let mirror = scheduled("UTC", vec![window("Sun 23:00-01:00", 0.0)]);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T00:30:00Z")), 0.0);
+}
+
+#[test]
+fn windows_are_read_in_the_configured_offset() {
+    let mirror = scheduled("+02:00", vec![window("09:00-10:00", 1.0)]);
+
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T07:00:00Z")), 1.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T08:00:00Z")), 10.0);
+    assert_eq!(mirror.sample_percentage_at(at("2025-07-07T09:00:00Z")), 10.0);
+}
+
+#[test]
+fn malformed_windows_are_rejected() {
+    for invalid in ["9-17", "Mon-Fry 09:00-17:00", "25:00-26:00", "09:00-09:00", "Mon 09:00"] {
+        assert!(invalid.parse::<TimeWindow>().is_err(), "{} parsed", invalid);
+    }
+    for valid in ["00:00-24:00", "Sat,Sun 10:00-14:00", "Fri-Mon 18:00-08:00"] {
+        assert!(valid.parse::<TimeWindow>().is_ok(), "{} rejected", valid);
+    }
+
+    let mut config = base_config();
+    config.mirror.schedule = vec![window("Mon-Fri 09:00-17:00", 1.0), window("Fri-Mon 16:00-18:00", 2.0)];
+    let issues = check(&config);
+    assert!(issues
+        .iter()
+        .any(|issue| issue.severity == Severity::Error && issue.section == "mirror" && issue.field == "schedule"));
+
+    config.mirror.schedule = vec![window("Mon-Fri 09:00-17:00", 1.0), window("Mon-Fri 17:00-09:00", 2.0)];
+    assert!(check(&config).iter().all(|issue| issue.field != "schedule"));
+}
+
+#[tokio::test]
+async fn status_and_sampling_follow_config_reloads() {
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&mirror)
+        .await;
+    let mut config = base_config();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    config.mirror.schedule = vec![window("00:00-24:00", 0.0)];
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+    let status = || async {
+        client
+            .get(app.url("/admin/mirror/status"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap()
+    };
+    let get_users = || async {
+        let response = client
+            .get(app.url("/api/v1/users"))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    };
+
+    let body = status().await;
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["sample_percentage"], 0.0);
+    assert_eq!(body["default_sample_percentage"], 100.0);
+    assert_eq!(body["active_window"], "00:00-24:00");
+    assert_eq!(body["timezone"], "UTC");
+    for _ in 0..5 {
+        get_users().await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(mirror.received_requests().await.unwrap().is_empty());
+    assert_eq!(metric_value(&app.scrape_metrics().await, "gateway_mirror_sample_percentage", &[]), 0.0);
+
+    // Dropping the window on reload mirrors everything again
+    config.mirror.schedule.clear();
+    app.state.config_watcher.apply(config).await;
+    let body = status().await;
+    assert_eq!(body["sample_percentage"], 100.0);
+    assert!(body["active_window"].is_null());
+    get_users().await;
+    let mirrored_users = || async {
+        mirror
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == "/api/v1/users")
+            .count()
+    };
+    for _ in 0..100 {
+        if mirrored_users().await > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(mirrored_users().await, 1);
+    assert_eq!(metric_value(&app.scrape_metrics().await, "gateway_mirror_sample_percentage", &[]), 100.0);
+}
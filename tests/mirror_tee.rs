@@ -0,0 +1,182 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use bytes::Bytes;
+use common::{base_config, logs, spawn_app};
+use futures::{stream, StreamExt};
+use project_gateway::mirror::tee::{tee, BodyComparison, BodyDigest, TeeAbort, QUEUE_CHUNKS};
+use std::{
+    convert::Infallible,
+    time::Duration,
+};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn chunked(chunks: Vec<Vec<u8>>) -> Body {
+    Body::from_stream(stream::iter(chunks.into_iter().map(|chunk| Ok::<_, Infallible>(Bytes::from(chunk)))))
+}
+
+fn numbered_chunks(count: usize, len: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|n| vec![n as u8; len]).collect()
+}
+
+#[tokio::test]
+async fn client_and_comparison_see_the_same_chunks_in_order() {
+    let chunks = numbered_chunks(10, 1000);
+    let (body, reader) = tee(chunked(chunks.clone()), 64 * 1024, None);
+    let comparison = tokio::spawn(reader.finish());
+
+    let mut received = Vec::new();
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        received.push(chunk.unwrap().to_vec());
+    }
+    assert_eq!(received, chunks);
+
+    let digest = comparison.await.unwrap().unwrap();
+    assert_eq!(digest, BodyDigest::of(&chunks.concat(), 64 * 1024, None));
+    assert!(digest.is_complete());
+}
+
+#[tokio::test]
+async fn capture_stops_at_the_limit_but_the_hash_covers_everything() {
+    let chunks = numbered_chunks(10, 1000);
+    let (body, reader) = tee(chunked(chunks.clone()), 2500, Some("gzip".to_string()));
+    let comparison = tokio::spawn(reader.finish());
+    assert_eq!(to_bytes(body, usize::MAX).await.unwrap().len(), 10_000);
+
+    let digest = comparison.await.unwrap().unwrap();
+    let whole = chunks.concat();
+    assert_eq!(digest.bytes, 10_000);
+    assert_eq!(digest.prefix, whole[..2500]);
+    assert!(!digest.is_complete());
+    assert_eq!(digest.sha256, BodyDigest::of(&whole, 0, None).sha256);
+    assert_eq!(digest.content_encoding.as_deref(), Some("gzip"));
+}
+
+#[tokio::test]
+async fn early_client_disconnect_abandons_only_the_capture() {
+    let (body, reader) = tee(chunked(numbered_chunks(10, 1000)), 64 * 1024, None);
+    let mut stream = body.into_data_stream();
+    assert_eq!(stream.next().await.unwrap().unwrap().len(), 1000);
+    drop(stream);
+
+    assert_eq!(reader.finish().await, Err(TeeAbort::Interrupted));
+}
+
+#[tokio::test]
+async fn comparison_falling_behind_never_holds_up_the_client() {
+    // Nothing reads the comparison side while the client drains the body
+    let chunks = numbered_chunks(QUEUE_CHUNKS * 2, 100);
+    let (body, reader) = tee(chunked(chunks.clone()), 64 * 1024, None);
+    let received = tokio::time::timeout(Duration::from_secs(5), to_bytes(body, usize::MAX)).await.unwrap().unwrap();
+    assert_eq!(received, chunks.concat());
+
+    assert_eq!(reader.finish().await, Err(TeeAbort::FellBehind));
+}
+
+#[test]
+fn large_bodies_are_compared_by_hash_and_prefix() {
+    let main = vec![b'a'; 200_000];
+    let digest = |body: &[u8]| BodyDigest::of(body, 1000, None);
+    assert_eq!(digest(&main).compare(&digest(&main)), Some(BodyComparison::Match));
+
+    let mut early = main.clone();
+    early[500] = b'b';
+    assert_eq!(
+        digest(&main).compare(&digest(&early)),
+        Some(BodyComparison::Differs { first_difference: Some(500) })
+    );
+
+    // Beyond the prefix only the hashes differ
+    let mut late = main.clone();
+    late[150_000] = b'b';
+    assert_eq!(
+        digest(&main).compare(&digest(&late)),
+        Some(BodyComparison::Differs { first_difference: None })
+    );
+
+    // A short body differs where it ends
+    assert_eq!(
+        digest(&main).compare(&digest(&main[..300])),
+        Some(BodyComparison::Differs { first_difference: Some(300) })
+    );
+
+    // Bodies streamed with different encodings aren't comparable
+    let gzipped = BodyDigest::of(&main, 1000, Some("gzip".to_string()));
+    assert_eq!(digest(&main).compare(&gzipped), None);
+}
+
+/// The mirror comparisons logged for `path` so far.
+fn comparisons(path: &str) -> Vec<String> {
+    let path = format!("path={:?} ", path);
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .filter(|line| line.contains("Mirror request completed") && line.contains(&path))
+        .map(str::to_string)
+        .collect()
+}
+
+async fn wait_for_comparisons(path: &str, count: usize) -> Vec<String> {
+    for _ in 0..100 {
+        let comparisons = comparisons(path);
+        if comparisons.len() >= count {
+            return comparisons;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("fewer than {} comparisons logged for {}", count, path);
+}
+
+async fn upstream(body: Vec<u8>) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn mirror_bodies_are_compared_with_the_streamed_main_body() {
+    logs();
+    let body: Vec<u8> = (0..200_000u32).map(|n| (n % 251) as u8).collect();
+    let legacy = upstream(body.clone()).await;
+    let mirror = upstream(body.clone()).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let app = spawn_app(config).await;
+
+    let received = reqwest::get(app.url("/api/v1/users")).await.unwrap().bytes().await.unwrap();
+    assert_eq!(received, body);
+    let logged = wait_for_comparisons("/api/v1/users", 1).await;
+    assert!(logged[0].contains("body_match=true"), "{}", logged[0]);
+
+    // The main body is larger than the compared prefix, so a difference
+    // past it is caught by the hash alone
+    let mut different = body.clone();
+    different[150_000] ^= 0xff;
+    mirror.reset().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(different))
+        .mount(&mirror)
+        .await;
+    reqwest::get(app.url("/api/v1/users")).await.unwrap().bytes().await.unwrap();
+    let logged = wait_for_comparisons("/api/v1/users", 2).await;
+    assert!(logged[1].contains("body_match=false"), "{}", logged[1]);
+    assert!(!logged[1].contains("body_first_difference"), "{}", logged[1]);
+
+    // Within the prefix the first difference is located
+    let mut different = body.clone();
+    different[1234] ^= 0xff;
+    mirror.reset().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(different))
+        .mount(&mirror)
+        .await;
+    reqwest::get(app.url("/api/v1/users")).await.unwrap().bytes().await.unwrap();
+    let logged = wait_for_comparisons("/api/v1/users", 3).await;
+    assert!(logged[2].contains("body_first_difference=1234"), "{}", logged[2]);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::routes::{
+    negotiation::{Accept, ResponseFormat, TABLE_FORMATS},
+    users::UserListResponse,
+};
+use reqwest::{header, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+
+async fn list_users(app: &TestApp, accept: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust");
+    if let Some(accept) = accept {
+        request = request.header(header::ACCEPT, accept);
+    }
+    request.send().await.unwrap()
+}
+
+fn content_type(response: &reqwest::Response) -> &str {
+    response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn users_list_defaults_to_json() {
+    let app = spawn_app(base_config()).await;
+    for accept in [None, Some("*/*"), Some("application/json")] {
+        let response = list_users(&app, accept).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(content_type(&response), "application/json");
+        assert_eq!(response.headers()[header::VARY], "accept");
+        let users: UserListResponse = response.json().await.unwrap();
+        assert_eq!(users.total, 2);
+    }
+}
+
+#[tokio::test]
+async fn users_list_as_msgpack() {
+    let app = spawn_app(base_config()).await;
+    let response = list_users(&app, Some("application/msgpack")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "application/msgpack");
+
+    let bytes = response.bytes().await.unwrap();
+    let mut decoder = rmp_serde::Deserializer::new(&bytes[..]).with_human_readable();
+    let users = UserListResponse::deserialize(&mut decoder).unwrap();
+    assert_eq!(users.users.len(), 2);
+    assert_eq!(users.users[0].username, "admin");
+    // Fields are encoded by name and UUIDs as strings, so consumers see the JSON shape
+    let generic: Value = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(generic["per_page"], 10);
+    assert_eq!(generic["users"][0]["id"].as_str().unwrap(), users.users[0].id.to_string());
+}
+
+#[tokio::test]
+async fn users_list_as_csv() {
+    let app = spawn_app(base_config()).await;
+    let response = list_users(&app, Some("text/csv, application/json;q=0.5")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "text/csv; charset=utf-8");
+
+    let body = response.text().await.unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "id,username,email,created_at,active");
+    let admin: Vec<&str> = lines[1].split(',').collect();
+    assert_eq!(admin[1..], ["admin", "admin@gateway.internal", admin[3], "true"]);
+}
+
+#[tokio::test]
+async fn unsupported_types_get_406_with_the_supported_ones() {
+    let app = spawn_app(base_config()).await;
+    for accept in ["application/xml", "text/html, */*;q=0"] {
+        let response = list_users(&app, Some(accept)).await;
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE, "{}", accept);
+        assert_eq!(content_type(&response), "application/json");
+        let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "not_acceptable");
+        assert_eq!(body["error"]["request_id"], request_id);
+        assert_eq!(
+            body["error"]["supported_types"],
+            serde_json::json!(["application/json", "application/msgpack", "text/csv"])
+        );
+    }
+
+    // Endpoints that aren't lists don't offer CSV
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header(header::ACCEPT, "text/csv")
+        .json(&serde_json::json!({"username": "ops", "email": "ops@gateway.internal"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["supported_types"], serde_json::json!(["application/json", "application/msgpack"]));
+}
+
+#[test]
+fn most_specific_range_sets_the_quality() {
+    let negotiate = |header: &str| Accept::parse(Some(header)).negotiate(TABLE_FORMATS);
+    assert_eq!(negotiate("text/*"), Some(ResponseFormat::Csv));
+    assert_eq!(negotiate("*/*, application/json;q=0"), Some(ResponseFormat::MessagePack));
+    assert_eq!(negotiate("application/x-msgpack;q=0.9, text/csv;q=0.8"), Some(ResponseFormat::MessagePack));
+    assert_eq!(negotiate("Application/JSON;q=0.1, text/csv;q=0.1"), Some(ResponseFormat::Json));
+    assert_eq!(negotiate("image/png"), None);
+}
+
+#[test]
+fn spec_lists_the_formats_per_endpoint() {
+    use utoipa::OpenApi;
+    let spec = serde_json::to_value(project_gateway::docs::ApiDoc::openapi()).unwrap();
+    let content_types = |path: &str, method: &str| -> Vec<String> {
+        let content = spec["paths"][path][method]["responses"]["200"]["content"].as_object().unwrap();
+        content.keys().cloned().collect()
+    };
+    assert_eq!(content_types("/api/v1/users", "get"), ["application/json", "application/msgpack", "text/csv"]);
+    assert_eq!(content_types("/monitoring/slo", "get"), ["application/json", "application/msgpack", "text/csv"]);
+    assert!(spec["paths"]["/api/v1/users"]["get"]["responses"]["406"].is_object());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{AppConfig, HumanDuration},
+    notifications::{Admission, Notification, NotificationStatus, Severity},
+};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use wiremock::{
+    matchers::{body_string_contains, method},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn event(kind: &'static str, subject: &str) -> Notification {
+    Notification {
+        kind,
+        subject: subject.to_string(),
+        severity: Severity::Warning,
+        state_change: false,
+        payload: serde_json::json!({ "text": format!("{} {}", kind, subject) }),
+    }
+}
+
+async fn app_with_webhook() -> (TestApp, MockServer, AppConfig) {
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).mount(&webhook).await;
+
+    let mut config = base_config();
+    config.canary_rollout.webhook_url = format!("{}/hooks/T000/secret", webhook.uri());
+    config.notifications.max_per_window = 3;
+    config.notifications.window = HumanDuration::from_secs(600);
+    config.notifications.digest_interval = HumanDuration::from_secs(900);
+    config.notifications.min_interval = HumanDuration::from_secs(300);
+    let app = spawn_app(config.clone()).await;
+    (app, webhook, config)
+}
+
+async fn posted(webhook: &MockServer) -> Vec<Value> {
+    webhook
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+async fn notification_status(app: &TestApp) -> NotificationStatus {
+    reqwest::get(app.url("/admin/notifications/status")).await.unwrap().json().await.unwrap()
+}
+
+#[tokio::test]
+async fn a_flood_is_held_to_the_window_and_summed_up_in_a_digest() {
+    let (app, webhook, config) = app_with_webhook().await;
+    for n in 0..40 {
+        let kind = if n % 4 == 0 { "slo_fast_burn" } else { "smoke_check_failed" };
+        app.state.notifier.notify(&config, event(kind, &format!("route-{}", n))).await;
+    }
+    assert_eq!(posted(&webhook).await.len(), 3);
+
+    let status = notification_status(&app).await;
+    assert_eq!(status.suppressed_total, 37);
+    let destination = &status.destinations[0];
+    assert_eq!(destination.destination, format!("{}/[redacted]", webhook.uri()));
+    assert_eq!(destination.sent_in_window, 3);
+    assert_eq!(destination.suppressed["smoke_check_failed"], 28);
+    assert_eq!(destination.suppressed["slo_fast_burn"], 9);
+    assert!(destination.next_digest_in_seconds.is_some_and(|seconds| seconds > 890 && seconds <= 900));
+    let scraped = app.scrape_metrics().await;
+    assert_eq!(
+        metric_value(&scraped, "gateway_notifications_total", &[("kind", "smoke_check_failed"), ("outcome", "rate_limited")]),
+        28.0
+    );
+
+    // Nothing is due before the digest interval has passed
+    app.state.notifier.send_due_digests_at(&config, Instant::now()).await;
+    assert_eq!(posted(&webhook).await.len(), 3);
+
+    app.state.notifier.send_due_digests_at(&config, Instant::now() + Duration::from_secs(900)).await;
+    let posted = posted(&webhook).await;
+    assert_eq!(posted.len(), 4);
+    let digest = &posted[3];
+    assert_eq!(digest["suppressed_total"], 37);
+    assert_eq!(digest["suppressed"]["smoke_check_failed"], 28);
+    assert_eq!(digest["suppressed"]["slo_fast_burn"], 9);
+    assert!(digest["text"].as_str().unwrap().starts_with("37 notifications held back in the last 15m"));
+
+    let status = notification_status(&app).await;
+    assert_eq!(status.digests_sent, 1);
+    assert!(status.destinations[0].suppressed.is_empty());
+    assert_eq!(status.destinations[0].next_digest_in_seconds, None);
+}
+
+#[tokio::test]
+async fn critical_events_bypass_a_full_window() {
+    let (app, webhook, config) = app_with_webhook().await;
+    for n in 0..10 {
+        app.state.notifier.notify(&config, event("smoke_check_failed", &format!("route-{}", n))).await;
+    }
+
+    let mut rollback = event("rollback", "route GET /api/v1/users");
+    rollback.severity = Severity::Critical;
+    rollback.payload = serde_json::json!({ "text": "rolled back to 0%" });
+    app.state.notifier.notify(&config, rollback.clone()).await;
+    app.state.notifier.notify(&config, rollback).await;
+
+    let posted = posted(&webhook).await;
+    assert_eq!(posted.len(), 5);
+    assert_eq!(posted[4]["text"], "rolled back to 0%");
+    assert_eq!(notification_status(&app).await.suppressed_total, 7);
+}
+
+#[tokio::test]
+async fn repeats_are_held_to_the_minimum_interval_unless_the_state_changed() {
+    let app = spawn_app(base_config()).await;
+    let mut config = base_config().notifications;
+    config.min_intervals.insert("slo_fast_burn".to_string(), HumanDuration::from_secs(900));
+    let notifier = &app.state.notifier;
+    let now = Instant::now();
+    let admit = |notification: &Notification, at: Duration| notifier.admit_at(&config, "http://hooks", notification, now + at);
+
+    let flapping = event("smoke_check_failed", "GET /api/v1/users");
+    assert_eq!(admit(&flapping, Duration::ZERO), Admission::Send);
+    assert_eq!(admit(&flapping, Duration::from_secs(60)), Admission::TooSoon);
+    // Other subjects and other event types are counted apart
+    assert_eq!(admit(&event("smoke_check_failed", "POST /api/v1/users"), Duration::from_secs(60)), Admission::Send);
+    assert_eq!(admit(&event("slo_fast_burn", "GET /api/v1/users"), Duration::from_secs(60)), Admission::Send);
+    assert_eq!(admit(&flapping, Duration::from_secs(300)), Admission::Send);
+
+    // Per-type intervals override the default
+    let burn = event("slo_fast_burn", "GET /api/v1/users");
+    assert_eq!(admit(&burn, Duration::from_secs(600)), Admission::TooSoon);
+    assert_eq!(admit(&burn, Duration::from_secs(960)), Admission::Send);
+
+    let mut rollback = event("rollback", "global");
+    rollback.state_change = true;
+    assert_eq!(admit(&rollback, Duration::from_secs(1000)), Admission::Send);
+    assert_eq!(admit(&rollback, Duration::from_secs(1001)), Admission::Send);
+}
+
+#[tokio::test]
+async fn failed_posts_are_retried_unless_the_webhook_refuses_them() {
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("flaky"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .mount(&webhook)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("refused"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&webhook)
+        .await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).mount(&webhook).await;
+
+    let mut config = base_config();
+    config.canary_rollout.webhook_url = format!("{}/hooks/T000/secret", webhook.uri());
+    config.notifications.retry.initial_delay = HumanDuration::from_millis(10);
+    let app = spawn_app(config.clone()).await;
+
+    app.state.notifier.notify(&config, event("smoke_check_failed", "flaky")).await;
+    assert_eq!(posted(&webhook).await.len(), 3);
+    app.state.notifier.notify(&config, event("smoke_check_failed", "refused")).await;
+    assert_eq!(posted(&webhook).await.len(), 4);
+
+    let scraped = app.scrape_metrics().await;
+    assert_eq!(metric_value(&scraped, "gateway_retries_total", &[("operation", "webhook")]), 2.0);
+}
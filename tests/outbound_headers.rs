@@ -0,0 +1,142 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::{
+    config::{validation::check, IntrospectionConfig, IntrospectionFailure, Severity},
+    coordination::instance_id,
+    middleware::introspection,
+};
+use std::{collections::BTreeMap, time::Duration};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn headers(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+#[tokio::test]
+async fn every_outbound_call_carries_the_configured_headers() {
+    let upstream = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/jwks"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "keys": [] })))
+        .mount(&upstream)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/introspect"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "active": false })))
+        .mount(&upstream)
+        .await;
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&upstream)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = format!("{}/legacy", upstream.uri());
+    config.canary_rollout.webhook_url = format!("{}/hooks/T000/secret", upstream.uri());
+    config.mirror.enabled = true;
+    config.mirror.base_url = format!("{}/mirror", upstream.uri());
+    config.http_client.user_agent = "project-gateway/{version} ({instance_id})".to_string();
+    config.http_client.default_headers = headers(&[("X-Service-Name", "project-gateway"), ("X-Environment", "staging")]);
+    config.http_client.upstream_headers =
+        BTreeMap::from([("legacy".to_string(), headers(&[("X-Environment", "staging-legacy")]))]);
+    let app = spawn_app(config.clone()).await;
+    let state = &app.state;
+
+    // Legacy proxy and mirror, with the client trying to pass itself off
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("User-Agent", "curl/8.0")
+        .header("X-Service-Name", "someone-else")
+        .header("X-Environment", "production")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    // Health probes, webhooks, JWKS, introspection and the time source
+    state.upstream_health.check_once(&state.upstreams, &config).await;
+    state
+        .notifier
+        .notify(
+            &config,
+            project_gateway::notifications::Notification {
+                kind: "smoke_check_failed",
+                subject: "route".to_string(),
+                severity: project_gateway::notifications::Severity::Warning,
+                state_change: false,
+                payload: serde_json::json!({ "text": "failed" }),
+            },
+        )
+        .await;
+    state.jwks.refresh(&state.upstreams, &format!("{}/jwks", upstream.uri())).await.unwrap();
+    let introspection_config = IntrospectionConfig {
+        url: format!("{}/introspect", upstream.uri()),
+        client_id: String::new(),
+        client_secret: String::new(),
+        cache_ttl: "60s".parse().unwrap(),
+        negative_cache_ttl: "0s".parse().unwrap(),
+        timeout: "1s".parse().unwrap(),
+        on_failure: IntrospectionFailure::Closed,
+    };
+    let auth = &config.middleware.auth;
+    introspection::validate_at(&state.auth_cache, &state.upstreams, auth, &introspection_config, "opaque", 0).await;
+    let _ = state.clock.check(&state.upstreams, &format!("{}/time", upstream.uri()), Duration::from_secs(10)).await;
+
+    // The mirror is sent to in the background
+    let mut received = Vec::new();
+    for _ in 0..100 {
+        received = upstream.received_requests().await.unwrap();
+        if received.iter().any(|request| request.url.path().starts_with("/mirror/api")) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let user_agent = format!("project-gateway/{} ({})", env!("CARGO_PKG_VERSION"), instance_id());
+    let mut seen = Vec::new();
+    for request in &received {
+        let path = request.url.path();
+        let single = |name: &str| {
+            let values: Vec<_> = request.headers.get_all(name).iter().map(|value| value.to_str().unwrap()).collect();
+            assert_eq!(values.len(), 1, "{} {}: {:?}", path, name, values);
+            values[0].to_string()
+        };
+        assert!(single("user-agent").starts_with(&user_agent), "{}", path);
+        assert_eq!(single("x-service-name"), "project-gateway", "{}", path);
+        let environment = if path.starts_with("/legacy") { "staging-legacy" } else { "staging" };
+        assert_eq!(single("x-environment"), environment, "{}", path);
+        seen.push(path.to_string());
+    }
+    for expected in [
+        "/legacy/api/v1/users",
+        "/mirror/api/v1/users",
+        "/legacy/status",
+        "/mirror/status",
+        "/hooks/T000/secret",
+        "/jwks",
+        "/introspect",
+        "/time",
+    ] {
+        assert!(seen.iter().any(|path| path == expected), "no request to {}: {:?}", expected, seen);
+    }
+
+    let scraped = app.scrape_metrics().await;
+    assert!(scraped.contains(&format!("gateway_instance_info{{instance_id=\"{}\"", instance_id())));
+}
+
+#[test]
+fn malformed_headers_and_unknown_upstreams_are_rejected() {
+    let mut config = base_config();
+    config.http_client.default_headers = headers(&[("User-Agent", "mine")]);
+    config.http_client.upstream_headers = BTreeMap::from([("legacy-v2".to_string(), BTreeMap::new())]);
+    let errors: Vec<_> = check(&config)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "http_client")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(errors, ["default_headers", "upstream_headers"]);
+}
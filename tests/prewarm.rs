@@ -0,0 +1,97 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::{
+    config::{validation::check, AppConfig, Severity},
+    upstream::{prewarm, upstream_key},
+};
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn upstream() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD")).respond_with(ResponseTemplate::new(200)).mount(&server).await;
+    server
+}
+
+fn prewarm_config(legacy: &MockServer) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.enabled = true;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.http_client.prewarm.enabled = true;
+    config.http_client.prewarm.min_connections = 2;
+    config.http_client.prewarm.rollback_headroom = 8;
+    config.http_client.prewarm.interval = "60s".parse().unwrap();
+    config.http_client.prewarm.max_probes_per_second = 1.0;
+    config
+}
+
+async fn probes(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap().iter().filter(|request| request.method.as_str() == "HEAD").count()
+}
+
+async fn wait_for_probes(server: &MockServer, count: usize) {
+    for _ in 0..100 {
+        if probes(server).await >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("upstream received {} probes, wanted {}", probes(server).await, count);
+}
+
+#[tokio::test]
+async fn rollout_advance_warms_headroom_for_a_rollback() {
+    let legacy = upstream().await;
+    let mut config = prewarm_config(&legacy);
+    let app = spawn_app(config.clone()).await;
+    let upstream = upstream_key(&legacy.uri());
+    tokio::spawn(prewarm::start(app.state.clone()));
+
+    // At 0% legacy takes all traffic and only the minimum is kept warm
+    wait_for_probes(&legacy, 2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(probes(&legacy).await, 2);
+
+    // Advancing to 50% warms half the headroom straight away
+    config.canary_rollout.rollout_percentage = 50.0;
+    app.state.config_watcher.apply(config).await;
+    wait_for_probes(&legacy, 2 + 6).await;
+    let stats = app.state.upstreams.stats();
+    let pool = stats.iter().find(|stats| stats.upstream == upstream).unwrap();
+    assert_eq!((pool.in_use, pool.idle), (0, 6));
+    let metrics = app.scrape_metrics().await;
+    assert_eq!(metric_value(&metrics, "gateway_upstream_warm_connections", &[("upstream", &upstream)]), 6.0);
+
+    // Probes aren't upstream traffic as far as the gatekeeper is concerned
+    assert!(app.state.performance_monitor.get_current_metrics("legacy").is_none());
+    assert_eq!(metric_value(&metrics, "gateway_upstream_pool_wait_seconds_count", &[("upstream", &upstream)]), 0.0);
+}
+
+#[tokio::test]
+async fn probes_stay_within_the_rate_limit() {
+    let legacy = upstream().await;
+    let mirror = upstream().await;
+    let mut config = prewarm_config(&legacy);
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    config.http_client.prewarm.interval = "4s".parse().unwrap();
+    let app = spawn_app(config.clone()).await;
+    assert!(check(&config).iter().any(|issue| issue.severity == Severity::Warning
+        && issue.section == "http_client.prewarm"
+        && issue.field == "max_probes_per_second"));
+
+    // Legacy wants 10 but the round may only send 4; the mirror gets none
+    let results = prewarm::prewarm_once(&app.state, &config).await;
+    assert_eq!(results.iter().map(|result| (result.target, result.probes)).collect::<Vec<_>>(), [(10, 4), (2, 0)]);
+    assert_eq!(probes(&legacy).await, 4);
+    assert_eq!(probes(&mirror).await, 0);
+
+    // With room for every probe both upstreams are topped up
+    config.http_client.prewarm.max_probes_per_second = 10.0;
+    let results = prewarm::prewarm_once(&app.state, &config).await;
+    assert_eq!(results.iter().map(|result| result.warm).collect::<Vec<_>>(), [10, 2]);
+    assert!(check(&config).iter().all(|issue| issue.section != "http_client.prewarm"));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use axum::{body::Body, http::Request};
+use common::{base_config, jwt_auth, logs, spawn_app};
+use project_gateway::{
+    config::{AuthConfig, PrivacyConfig},
+    middleware::{
+        auth::{issue_token, Claims},
+        rate_limit::ClientIdentity,
+    },
+    privacy::{pseudonym, Pseudonymizer},
+};
+use serde_json::{json, Value};
+
+const USER: &str = "jane.doe@example.com";
+
+fn captured() -> String {
+    String::from_utf8_lossy(&logs().0.lock().unwrap()).into_owned()
+}
+
+fn privacy(salt: &str) -> PrivacyConfig {
+    PrivacyConfig {
+        pseudonymize_user_ids: true,
+        salt: Some(salt.to_string()),
+        salt_file: None,
+    }
+}
+
+#[test]
+fn pseudonyms_are_deterministic_and_salt_sensitive() {
+    let first = pseudonym(b"deployment-a", USER);
+    assert_eq!(first, pseudonym(b"deployment-a", USER));
+    assert_eq!(first.len(), 12);
+    assert!(first.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+
+    assert_ne!(first, pseudonym(b"deployment-b", USER));
+    assert_ne!(first, pseudonym(b"deployment-a", "john.doe@example.com"));
+
+    let pseudonymizer = Pseudonymizer::new(&privacy("deployment-a")).unwrap();
+    assert_eq!(pseudonymizer.pseudonymize(USER), first);
+}
+
+#[test]
+fn pseudonymization_can_be_disabled_but_not_left_unsalted() {
+    let disabled = PrivacyConfig {
+        pseudonymize_user_ids: false,
+        ..privacy("unused")
+    };
+    assert_eq!(Pseudonymizer::new(&disabled).unwrap().pseudonymize(USER), USER);
+
+    let unsalted = PrivacyConfig {
+        salt: None,
+        ..privacy("unused")
+    };
+    assert!(Pseudonymizer::new(&unsalted).is_err());
+    assert_ne!(Pseudonymizer::ephemeral().pseudonymize(USER), USER);
+}
+
+#[test]
+fn salt_file_takes_precedence() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), "from-file\n").unwrap();
+    let config = PrivacyConfig {
+        salt_file: Some(file.path().display().to_string()),
+        ..privacy("inline")
+    };
+    assert_eq!(
+        Pseudonymizer::new(&config).unwrap().pseudonymize(USER),
+        pseudonym(b"from-file", USER)
+    );
+}
+
+#[test]
+fn client_identity_labels_carry_pseudonyms() {
+    let mut request = Request::new(Body::empty());
+    request.extensions_mut().insert(Claims {
+        sub: USER.to_string(),
+        exp: 0,
+        scope: None,
+        roles: Vec::new(),
+    });
+    let client = ClientIdentity::of(&request, &Pseudonymizer::new(&privacy("labels")).unwrap(), &[]);
+    assert_eq!(client.key, USER);
+    assert_eq!(client.label, format!("sub:{}", pseudonym(b"labels", USER)));
+}
+
+#[tokio::test]
+async fn raw_user_ids_never_reach_the_logs() {
+    logs();
+    let auth = AuthConfig {
+        jwt_secrets: vec!["privacy-test-secret".to_string()],
+        exempt_paths: Vec::new(),
+        ..jwt_auth()
+    };
+    let mut config = base_config();
+    config.middleware.auth = auth.clone();
+    config.privacy = privacy("log-salt");
+    let app = spawn_app(config).await;
+
+    let token = issue_token(
+        &auth,
+        &Claims {
+            sub: USER.to_string(),
+            exp: chrono::Utc::now().timestamp() as u64 + 600,
+            scope: None,
+            roles: vec!["reader".to_string()],
+        },
+    )
+    .unwrap();
+    let client = reqwest::Client::new();
+
+    let response = client.get(app.url("/api/v1/users")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .put(app.url("/admin/features/body_logging"))
+        .bearer_auth(&token)
+        .json(&json!({ "enabled": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let logs = captured();
+    let expected = pseudonym(b"log-salt", USER);
+    assert!(!logs.contains(USER), "raw user ID logged:\n{}", logs);
+    assert!(logs.contains(&format!("user=\"{}\"", expected)), "{}", logs);
+    assert!(logs.contains(&format!("actor=\"{}\"", expected)), "{}", logs);
+}
+
+#[tokio::test]
+async fn config_endpoint_hides_the_salt() {
+    let mut config = base_config();
+    config.privacy = privacy("do-not-show-me");
+    let app = spawn_app(config).await;
+
+    let config: Value = reqwest::get(app.url("/admin/config")).await.unwrap().json().await.unwrap();
+    assert_eq!(config["privacy"]["salt"], "[redacted]");
+    assert!(!config.to_string().contains("do-not-show-me"));
+}
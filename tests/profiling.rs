@@ -0,0 +1,142 @@
+#![cfg(feature = "profiling")]
+
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use flate2::read::GzDecoder;
+use project_gateway::config::{validation::check, AppConfig, Severity};
+use serde_json::Value;
+use std::{io::Read, time::Duration};
+
+fn profiling_config(sample_one_in: u64) -> AppConfig {
+    let mut config = base_config();
+    config.profiling.enabled = true;
+    config.profiling.sample_one_in = sample_one_in;
+    config
+}
+
+async fn list_users(app: &TestApp, force_profile: bool) -> u16 {
+    let mut request = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust");
+    if force_profile {
+        request = request.header("X-Profile", "1");
+    }
+    request.send().await.unwrap().status().as_u16()
+}
+
+async fn profiles(app: &TestApp) -> Vec<Value> {
+    let profiles: Value = reqwest::Client::new()
+        .get(app.url("/admin/profiles"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    profiles.as_array().unwrap().clone()
+}
+
+async fn download(app: &TestApp, id: &Value, format: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(app.url(&format!("/admin/profiles/{}?format={}", id, format)))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn forced_profile_is_retrievable_as_flamegraph_and_pprof() {
+    let app = spawn_app(profiling_config(0)).await;
+    assert_eq!(list_users(&app, true).await, 200);
+
+    let profiles = profiles(&app).await;
+    assert_eq!(profiles.len(), 1, "{:?}", profiles);
+    let profile = &profiles[0];
+    assert_eq!(profile["method"], "GET");
+    assert_eq!(profile["route"], "/api/v1/users");
+    assert_eq!(profile["status"], 200);
+    assert_eq!(profile["trigger"], "requested");
+
+    let svg = download(&app, &profile["id"], "svg").await;
+    assert_eq!(svg.status(), 200);
+    assert_eq!(svg.headers()["content-type"], "image/svg+xml");
+    let svg = svg.text().await.unwrap();
+    assert!(svg.starts_with("<?xml") && svg.trim_end().ends_with("</svg>"), "{}", svg);
+    assert!(svg.contains("GET /api/v1/users"));
+
+    let folded = download(&app, &profile["id"], "folded").await.text().await.unwrap();
+    assert!(folded.lines().any(|line| line.starts_with("GET /api/v1/users;gateway ")), "{}", folded);
+
+    let pprof = download(&app, &profile["id"], "pprof").await.bytes().await.unwrap();
+    let mut decoded = Vec::new();
+    GzDecoder::new(&pprof[..]).read_to_end(&mut decoded).unwrap();
+    let decoded = String::from_utf8_lossy(&decoded);
+    assert!(decoded.contains("microseconds") && decoded.contains("GET /api/v1/users"));
+
+    assert_eq!(download(&app, &profile["id"], "jpeg").await.status(), 400);
+    assert_eq!(download(&app, &Value::from(999), "svg").await.status(), 404);
+}
+
+#[tokio::test]
+async fn sampling_is_deterministic_and_the_header_needs_a_trusted_peer() {
+    let mut config = profiling_config(3);
+    config.profiling.trusted_networks = vec!["10.0.0.0/8".to_string()];
+    let app = spawn_app(config).await;
+
+    for _ in 0..7 {
+        list_users(&app, true).await;
+    }
+
+    // Requests 3 and 6, newest first; the header alone counts for nothing
+    let profiles = profiles(&app).await;
+    assert_eq!(profiles.len(), 2, "{:?}", profiles);
+    assert!(profiles.iter().all(|profile| profile["trigger"] == "sampled"));
+    assert!(profiles[0]["id"].as_u64() > profiles[1]["id"].as_u64());
+}
+
+#[tokio::test]
+async fn capture_is_skipped_when_the_profiler_is_busy_or_disabled() {
+    let app = spawn_app(profiling_config(0)).await;
+
+    let permit = app.state.profiler.start(Duration::from_millis(1)).await.unwrap();
+    assert_eq!(list_users(&app, true).await, 200);
+    drop(permit);
+    assert!(profiles(&app).await.is_empty());
+    let scrape = app.scrape_metrics().await;
+    assert!(metric_value(&scrape, "gateway_profiles_total", &[("outcome", "start_budget")]) >= 1.0);
+
+    let mut config = profiling_config(1);
+    config.profiling.enabled = false;
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(list_users(&app, true).await, 200);
+    assert!(profiles(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn oldest_profiles_are_dropped_past_the_limit() {
+    let mut config = profiling_config(0);
+    config.profiling.max_profiles = 2;
+    let app = spawn_app(config).await;
+
+    for _ in 0..3 {
+        list_users(&app, true).await;
+    }
+    let ids: Vec<u64> = profiles(&app).await.iter().map(|profile| profile["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![3, 2]);
+}
+
+#[test]
+fn invalid_profiling_settings_are_rejected() {
+    let mut config = profiling_config(0);
+    config.profiling.max_profiles = 0;
+    config.profiling.trusted_networks = vec!["10.0.0.0/33".to_string()];
+    let fields: Vec<&str> = check(&config)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "profiling")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, vec!["max_profiles", "trusted_networks"]);
+}
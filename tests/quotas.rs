@@ -0,0 +1,133 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{ApiKey, ApiKeyQuota},
+    quota::{MemoryQuotaStore, QuotaPeriod, QuotaTracker},
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+fn api_key(id: &str, daily: Option<u64>, monthly: Option<u64>) -> ApiKey {
+    ApiKey {
+        id: id.to_string(),
+        key: format!("{}-secret", id),
+        quota: ApiKeyQuota { daily, monthly },
+    }
+}
+
+async fn send(app: &TestApp, api_key: &str, count: usize) -> Vec<reqwest::Response> {
+    let client = reqwest::Client::new();
+    let mut responses = Vec::new();
+    for _ in 0..count {
+        let response = client
+            .get(app.url("/health"))
+            .header("X-Gateway-Version", "rust")
+            .header("X-API-Key", api_key)
+            .send()
+            .await
+            .unwrap();
+        responses.push(response);
+    }
+    responses
+}
+
+async fn usage(app: &TestApp, key_id: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(app.url(&format!("/admin/quotas/{}", key_id)))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn a_used_up_quota_gets_429_until_it_resets() {
+    let mut config = base_config();
+    config.middleware.auth.api_keys = vec![api_key("partner", Some(3), Some(100)), api_key("internal", None, None)];
+    let app = spawn_app(config.clone()).await;
+
+    let responses = send(&app, "partner-secret", 4).await;
+    let statuses: Vec<u16> = responses.iter().map(|response| response.status().as_u16()).collect();
+    assert_eq!(statuses, [200, 200, 200, 429]);
+    let rejected = responses.into_iter().last().unwrap();
+    let retry_after: u64 = rejected.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=86_400).contains(&retry_after), "{}", retry_after);
+    let body: Value = rejected.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "quota_exhausted");
+    assert_eq!(body["error"]["period"], "daily");
+    assert_eq!(body["error"]["limit"], 3);
+
+    // Keys without a quota, and keys that aren't listed, aren't held to one
+    for key in ["internal-secret", "unlisted"] {
+        assert!(send(&app, key, 5).await.iter().all(|response| response.status() == 200));
+    }
+
+    let report: Value = usage(&app, "partner").await.json().await.unwrap();
+    assert_eq!(report["backend"], "memory");
+    let quotas = report["quotas"].as_array().unwrap();
+    assert_eq!((quotas[0]["period"].as_str(), quotas[0]["used"].as_u64(), quotas[0]["remaining"].as_u64()), (Some("daily"), Some(3), Some(0)));
+    assert_eq!((quotas[1]["period"].as_str(), quotas[1]["used"].as_u64(), quotas[1]["remaining"].as_u64()), (Some("monthly"), Some(3), Some(97)));
+    let resets_at = DateTime::parse_from_rfc3339(quotas[0]["resets_at"].as_str().unwrap()).unwrap();
+    assert!(resets_at > Utc::now() && resets_at <= Utc::now() + chrono::Duration::days(1));
+    assert_eq!(body["error"]["resets_at"], quotas[0]["resets_at"]);
+    assert_eq!(usage(&app, "nobody").await.status(), 404);
+
+    let metrics = app.scrape_metrics().await;
+    assert_eq!(metric_value(&metrics, "gateway_quota_exhausted_total", &[("key_id", "partner"), ("period", "daily")]), 1.0);
+    assert_eq!(config.redacted()["middleware"]["auth"]["api_keys"][0]["key"], "[redacted]");
+}
+
+#[test]
+fn periods_start_at_the_reset_hour() {
+    let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+
+    // Before the day's reset hour, still in yesterday's period and, on the
+    // 1st, in last month's
+    let now = at("2026-03-01T05:59:00Z");
+    assert_eq!(QuotaPeriod::Daily.bounds(now, 6), (at("2026-02-28T06:00:00Z"), at("2026-03-01T06:00:00Z")));
+    assert_eq!(QuotaPeriod::Monthly.bounds(now, 6), (at("2026-02-01T06:00:00Z"), at("2026-03-01T06:00:00Z")));
+
+    let now = at("2026-12-31T06:00:00Z");
+    assert_eq!(QuotaPeriod::Daily.bounds(now, 6), (at("2026-12-31T06:00:00Z"), at("2027-01-01T06:00:00Z")));
+    assert_eq!(QuotaPeriod::Monthly.bounds(now, 6), (at("2026-12-01T06:00:00Z"), at("2027-01-01T06:00:00Z")));
+}
+
+#[tokio::test]
+async fn counts_are_kept_by_the_store_and_start_over_each_period() {
+    let store = Arc::new(MemoryQuotaStore::new());
+    let key = api_key("partner", Some(2), Some(3));
+    let now = DateTime::parse_from_rfc3339("2026-03-10T12:00:00Z").unwrap().with_timezone(&Utc);
+
+    let tracker = QuotaTracker::new(store.clone());
+    assert!(tracker.take(&key, 0, now).await.unwrap().is_ok());
+    assert!(tracker.take(&key, 0, now).await.unwrap().is_ok());
+
+    // A tracker started afresh over the same store, as after a restart
+    let tracker = QuotaTracker::new(store);
+    let exhausted = tracker.take(&key, 0, now).await.unwrap().unwrap_err();
+    assert_eq!((exhausted.usage.period, exhausted.usage.used), (QuotaPeriod::Daily, 2));
+    assert_eq!(exhausted.retry_after.as_secs(), 12 * 3600);
+
+    // The next day the daily count starts over but the monthly one doesn't;
+    // a request that would go over either isn't counted against the other
+    let tomorrow = now + chrono::Duration::days(1);
+    assert!(tracker.take(&key, 0, tomorrow).await.unwrap().is_ok());
+    let exhausted = tracker.take(&key, 0, tomorrow).await.unwrap().unwrap_err();
+    assert_eq!(exhausted.usage.period, QuotaPeriod::Monthly);
+    let report = tracker.report(&key, 0, tomorrow).await.unwrap();
+    let used: Vec<(u64, u64)> = report.quotas.iter().map(|quota| (quota.used, quota.remaining)).collect();
+    assert_eq!(used, [(1, 1), (3, 0)]);
+}
+
+#[test]
+fn api_keys_must_be_distinct_and_quotas_nonzero() {
+    let mut config = base_config();
+    config.middleware.auth.api_keys = vec![api_key("partner", Some(0), None), api_key("partner", None, None)];
+    config.middleware.auth.quota_reset_hour = 24;
+    let error = config.validate().unwrap_err().to_string();
+    for expected in ["is listed twice", "same key", "zero quota", "quota_reset_hour"] {
+        assert!(error.contains(expected), "{}", error);
+    }
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{MissingKeyFallback, RateLimitKey, RateLimitOverride, RateLimitingConfig},
+    middleware::{
+        auth::Claims,
+        rate_limit::{client_ip, usage_label, ClientIdentity, RequestRateLimiter},
+    },
+    privacy::Pseudonymizer,
+};
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+async fn limited_app() -> TestApp {
+    limited_app_behind(&["127.0.0.1/32"]).await
+}
+
+async fn limited_app_behind(trusted_proxies: &[&str]) -> TestApp {
+    let mut config = base_config();
+    let rate_limiting = &mut config.middleware.rate_limiting;
+    rate_limiting.enabled = true;
+    rate_limiting.requests_per_minute = 3;
+    rate_limiting.overrides.push(RateLimitOverride {
+        subject: "batch-service".to_string(),
+        requests_per_minute: 6,
+    });
+    rate_limiting.trusted_proxies = trusted_proxies.iter().map(|cidr| cidr.to_string()).collect();
+    spawn_app(config).await
+}
+
+/// Sends `count` requests as `api_key` and returns the responses.
+async fn send(app: &TestApp, api_key: &str, count: usize) -> Vec<reqwest::Response> {
+    let client = reqwest::Client::new();
+    let mut responses = Vec::new();
+    for _ in 0..count {
+        let response = client
+            .get(app.url("/health"))
+            .header("X-Gateway-Version", "rust")
+            .header("X-API-Key", api_key)
+            .send()
+            .await
+            .unwrap();
+        responses.push(response);
+    }
+    responses
+}
+
+/// Fires `count` requests at once claiming to be forwarded for `ip`.
+async fn burst_from(app: &TestApp, ip: &str, count: usize) -> Vec<u16> {
+    let client = reqwest::Client::new();
+    let requests = (0..count).map(|_| {
+        client
+            .get(app.url("/health"))
+            .header("X-Gateway-Version", "rust")
+            .header("X-Forwarded-For", ip)
+            .send()
+    });
+    let mut statuses: Vec<u16> = futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .map(|response| response.unwrap().status().as_u16())
+        .collect();
+    statuses.sort();
+    statuses
+}
+
+fn statuses(responses: &[reqwest::Response]) -> Vec<u16> {
+    responses.iter().map(|response| response.status().as_u16()).collect()
+}
+
+#[tokio::test]
+async fn clients_get_separate_quotas_and_429_with_retry_after() {
+    let app = limited_app().await;
+
+    let alice = send(&app, "alice-key", 4).await;
+    assert_eq!(statuses(&alice), [200, 200, 200, 429]);
+    let retry_after: u64 = alice[3].headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=20).contains(&retry_after), "{}", retry_after);
+
+    // Another client is unaffected, and an override raises the quota
+    assert_eq!(statuses(&send(&app, "bob-key", 3).await), [200, 200, 200]);
+    assert_eq!(statuses(&send(&app, "batch-service", 7).await), [200, 200, 200, 200, 200, 200, 429]);
+
+    let body: serde_json::Value = send(&app, "alice-key", 1).await.remove(0).json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+}
+
+fn header(response: &reqwest::Response, name: &str) -> Option<u64> {
+    response.headers().get(name).map(|value| value.to_str().unwrap().parse().unwrap())
+}
+
+#[tokio::test]
+async fn quota_headers_follow_the_rule_that_applied() {
+    let app = limited_app().await;
+
+    let alice = send(&app, "alice-key", 4).await;
+    let remaining: Vec<Option<u64>> = alice.iter().map(|response| header(response, "x-ratelimit-remaining")).collect();
+    assert_eq!(remaining, [Some(2), Some(1), Some(0), Some(0)]);
+    assert!(alice.iter().all(|response| header(response, "x-ratelimit-limit") == Some(3)));
+    // A bucket of 3 per minute refills a token every 20s
+    let reset = header(&alice[2], "x-ratelimit-reset").unwrap();
+    assert!((59..=60).contains(&reset), "{}", reset);
+    assert_eq!(alice[3].status(), 429);
+    let retry_after = header(&alice[3], "retry-after").unwrap();
+    assert!((1..=20).contains(&retry_after), "{}", retry_after);
+
+    // The override's rate, not the default
+    let batch = send(&app, "batch-service", 1).await;
+    assert_eq!(header(&batch[0], "x-ratelimit-limit"), Some(6));
+    assert_eq!(header(&batch[0], "x-ratelimit-remaining"), Some(5));
+}
+
+#[tokio::test]
+async fn quota_headers_can_be_turned_off_per_route() {
+    let mut config = base_config();
+    config.middleware.rate_limiting.requests_per_minute = 3;
+    let users = config.routes.iter_mut().find(|route| route.path == "/api/v1/users").unwrap();
+    users.rate_limit_headers = Some(false);
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+    let get = |path: &str| client.get(app.url(path)).header("X-Gateway-Version", "rust").header("X-API-Key", "dave-key").send();
+
+    let health = get("/health").await.unwrap();
+    assert_eq!(header(&health, "x-ratelimit-limit"), Some(3));
+    let hidden = get("/api/v1/users").await.unwrap();
+    assert_eq!(hidden.status(), 200);
+    assert!(hidden.headers().get("x-ratelimit-remaining").is_none());
+    // Still limited, and 429s still say when to retry
+    assert_eq!(get("/api/v1/users").await.unwrap().status(), 200);
+    let rejected = get("/api/v1/users").await.unwrap();
+    assert_eq!(rejected.status(), 429);
+    assert!(rejected.headers().get("retry-after").is_some());
+    assert!(rejected.headers().get("x-ratelimit-limit").is_none());
+}
+
+#[tokio::test]
+async fn usage_is_counted_under_a_hashed_identity() {
+    let app = limited_app().await;
+    send(&app, "carol-key", 4).await;
+
+    let client = ClientIdentity {
+        key: "carol-key".to_string(),
+        label: String::new(),
+    };
+    let label = usage_label(&client, &app.state.pseudonymizer);
+    assert_eq!(label.len(), 4);
+
+    let scrape = app.scrape_metrics().await;
+    assert!(!scrape.contains("carol-key"));
+    let client = [("client", label.as_str())];
+    assert_eq!(metric_value(&scrape, "gateway_client_requests_total", &client), 4.0);
+    assert_eq!(metric_value(&scrape, "gateway_client_rate_limited_total", &client), 1.0);
+}
+
+#[test]
+fn buckets_refill_over_time() {
+    let limiter = RequestRateLimiter::new();
+    let start = Instant::now();
+    for _ in 0..60 {
+        assert!(limiter.try_take_at("client", 60, start).is_ok());
+    }
+    let wait = limiter.try_take_at("client", 60, start).unwrap_err().retry_after;
+    assert!(wait <= Duration::from_secs(1), "{:?}", wait);
+
+    let later = start + Duration::from_secs(2);
+    let quota = limiter.try_take_at("client", 60, later).unwrap();
+    assert_eq!((quota.limit, quota.remaining), (60, 1));
+    assert_eq!(quota.reset, Duration::from_secs(59));
+    assert!(limiter.try_take_at("client", 60, later).is_ok());
+    assert!(limiter.try_take_at("client", 60, later).is_err());
+}
+
+#[tokio::test]
+async fn concurrent_bursts_are_held_to_the_quota_per_ip() {
+    let app = limited_app().await;
+
+    let (first, second) = tokio::join!(burst_from(&app, "198.51.100.1", 20), burst_from(&app, "198.51.100.2", 20));
+    for statuses in [first, second] {
+        assert_eq!(statuses.iter().filter(|&&status| status == 200).count(), 3, "{:?}", statuses);
+        assert_eq!(statuses.iter().filter(|&&status| status == 429).count(), 17, "{:?}", statuses);
+    }
+}
+
+#[tokio::test]
+async fn forwarded_for_from_an_untrusted_peer_is_ignored() {
+    let app = limited_app_behind(&[]).await;
+
+    // Rotating the claimed address doesn't buy a fresh bucket
+    let mut statuses = Vec::new();
+    for ip in ["198.51.100.1", "198.51.100.2", "198.51.100.3", "198.51.100.4"] {
+        statuses.extend(burst_from(&app, ip, 1).await);
+    }
+    assert_eq!(statuses, [200, 200, 200, 429]);
+}
+
+#[test]
+fn threads_racing_one_bucket_take_exactly_its_capacity() {
+    let limiter = RequestRateLimiter::new();
+    let now = Instant::now();
+    let granted: usize = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| (0..50).filter(|_| limiter.try_take_at("client", 100, now).is_ok()).count()))
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    assert_eq!(granted, 100);
+}
+
+#[test]
+fn client_ip_believes_only_hops_added_by_trusted_proxies() {
+    let trusted = ["10.0.0.0/8".to_string()];
+    let resolve = |peer: &str, forwarded: Option<&str>| {
+        let mut request = axum::http::Request::new(());
+        let peer: SocketAddr = format!("{}:4000", peer).parse().unwrap();
+        request.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+        if let Some(forwarded) = forwarded {
+            request.headers_mut().insert("x-forwarded-for", forwarded.parse().unwrap());
+        }
+        client_ip(request.headers(), request.extensions(), &trusted).unwrap()
+    };
+
+    assert_eq!(resolve("203.0.113.9", Some("198.51.100.1")), "203.0.113.9");
+    assert_eq!(resolve("10.0.0.2", None), "10.0.0.2");
+    assert_eq!(resolve("10.0.0.2", Some("198.51.100.1, 203.0.113.5, 10.0.0.1")), "203.0.113.5");
+    assert_eq!(resolve("10.0.0.2", Some("10.0.0.3, 10.0.0.1")), "10.0.0.3");
+}
+
+fn keyed_request(tenant: Option<&str>, subject: Option<&str>) -> axum::http::Request<()> {
+    let mut request = axum::http::Request::new(());
+    let peer: SocketAddr = "203.0.113.7:4000".parse().unwrap();
+    request.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+    request.headers_mut().insert("x-api-key", "erin-key".parse().unwrap());
+    if let Some(tenant) = tenant {
+        request.headers_mut().insert("x-tenant-id", tenant.parse().unwrap());
+    }
+    if let Some(subject) = subject {
+        request.extensions_mut().insert(Claims {
+            sub: subject.to_string(),
+            exp: 0,
+            scope: None,
+            roles: Vec::new(),
+        });
+    }
+    request
+}
+
+#[test]
+fn keys_follow_the_configured_strategy() {
+    let pseudonymizer = Pseudonymizer::ephemeral();
+    let mut rate_limiting: RateLimitingConfig = base_config().middleware.rate_limiting;
+    let key = |rate_limiting: &RateLimitingConfig, tenant, subject| {
+        ClientIdentity::keyed(&keyed_request(tenant, subject), rate_limiting, &pseudonymizer)
+    };
+
+    // Unset, the API key wins over the address
+    assert_eq!(key(&rate_limiting, Some("acme"), None).key, "erin-key");
+
+    rate_limiting.key_by = vec!["header:X-Tenant-Id".parse().unwrap()];
+    let tenant = key(&rate_limiting, Some("acme"), Some("user-1"));
+    assert_eq!(tenant.key, "acme");
+    assert!(tenant.label.starts_with("x-tenant-id:"), "{}", tenant.label);
+    assert!(!tenant.label.contains("acme"));
+
+    rate_limiting.key_by = vec![RateLimitKey::JwtSubject, RateLimitKey::Header("x-tenant-id".to_string())];
+    assert_eq!(key(&rate_limiting, Some("acme"), Some("user-1")).key, "user-1|acme");
+
+    // Any part missing sends the request to the fallback
+    assert_eq!(key(&rate_limiting, None, Some("user-1")).key, "203.0.113.7");
+    assert_eq!(key(&rate_limiting, Some("acme"), None).label, "ip:203.0.113.7");
+    rate_limiting.missing_key = MissingKeyFallback::Anonymous;
+    assert_eq!(key(&rate_limiting, Some("acme"), None).key, "anonymous");
+    assert_eq!(key(&rate_limiting, Some("acme"), Some("user-1")).key, "user-1|acme");
+}
+
+#[test]
+fn key_strategies_parse_from_config() {
+    let keys: Vec<RateLimitKey> = serde_yaml::from_str("[ip, jwt_sub, \"header:X-Tenant-Id\"]").unwrap();
+    assert_eq!(
+        keys,
+        [RateLimitKey::Ip, RateLimitKey::JwtSubject, RateLimitKey::Header("x-tenant-id".to_string())]
+    );
+    assert_eq!(serde_yaml::to_string(&keys).unwrap(), "- ip\n- jwt_sub\n- header:x-tenant-id\n");
+    assert!("header:".parse::<RateLimitKey>().is_err());
+    assert!("header:bad header".parse::<RateLimitKey>().is_err());
+    assert!("cookie".parse::<RateLimitKey>().is_err());
+}
+
+/// Sends a request as `tenant`, or without a tenant header, and returns its status.
+async fn send_as_tenant(app: &TestApp, tenant: Option<&str>) -> u16 {
+    let mut request = reqwest::Client::new()
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust");
+    if let Some(tenant) = tenant {
+        request = request.header("X-Tenant-Id", tenant);
+    }
+    request.send().await.unwrap().status().as_u16()
+}
+
+#[tokio::test]
+async fn switching_the_key_on_reload_rebuckets_clients() {
+    let mut config = base_config();
+    let rate_limiting = &mut config.middleware.rate_limiting;
+    rate_limiting.requests_per_minute = 2;
+    rate_limiting.key_by = vec![RateLimitKey::Header("x-tenant-id".to_string())];
+    rate_limiting.missing_key = MissingKeyFallback::Anonymous;
+    let app = spawn_app(config.clone()).await;
+
+    for (tenant, expected) in [(Some("acme"), [200, 200, 429]), (Some("globex"), [200, 200, 429])] {
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            statuses.push(send_as_tenant(&app, tenant).await);
+        }
+        assert_eq!(statuses, expected, "{:?}", tenant);
+    }
+    // Requests without the header share one bucket
+    assert_eq!(send_as_tenant(&app, None).await, 200);
+    assert_eq!(send_as_tenant(&app, None).await, 200);
+    assert_eq!(send_as_tenant(&app, None).await, 429);
+
+    // Keyed by address instead, everyone starts over in the one loopback bucket
+    config.middleware.rate_limiting.key_by = vec![RateLimitKey::Ip];
+    app.state.config_watcher.apply(config.clone()).await;
+    assert_eq!(send_as_tenant(&app, Some("acme")).await, 200);
+    assert_eq!(send_as_tenant(&app, Some("globex")).await, 200);
+    assert_eq!(send_as_tenant(&app, None).await, 429);
+
+    // And back: the tenant buckets kept their state
+    config.middleware.rate_limiting.key_by = vec![RateLimitKey::Header("x-tenant-id".to_string())];
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(send_as_tenant(&app, Some("acme")).await, 429);
+    assert_eq!(send_as_tenant(&app, Some("initech")).await, 200);
+}
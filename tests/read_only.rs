@@ -0,0 +1,121 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::config::{validation::check, LatencyObjective, Severity, SloObjective};
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn new_user() -> Value {
+    json!({ "username": "ada", "email": "ada@example.com" })
+}
+
+#[tokio::test]
+async fn writes_are_rejected_while_reads_continue() {
+    let mirror = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(201)).mount(&mirror).await;
+    let mut config = base_config();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    config.read_only.retry_after = "45s".parse().unwrap();
+    config.slo.objectives = vec![SloObjective {
+        name: "users".to_string(),
+        method: None,
+        route: "/api/v1/users".to_string(),
+        availability: Some(99.9),
+        latency: Some(LatencyObjective {
+            threshold: "1s".parse().unwrap(),
+            percentile: 99.0,
+        }),
+        window: "30d".parse().unwrap(),
+    }];
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+    let post_user = || {
+        client
+            .post(app.url("/api/v1/users"))
+            .header("X-Gateway-Version", "rust")
+            .json(&new_user())
+            .send()
+    };
+
+    let status: Value = client
+        .post(app.url("/admin/read-only"))
+        .json(&json!({ "enabled": true }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["persisted"], false);
+
+    let read = client.get(app.url("/api/v1/users")).header("X-Gateway-Version", "rust").send().await.unwrap();
+    assert_eq!(read.status(), 200);
+
+    let write = post_user().await.unwrap();
+    assert_eq!(write.status(), 503);
+    assert_eq!(write.headers()["retry-after"], "45");
+    assert_eq!(write.headers()["content-type"], "application/problem+json");
+    let body: Value = write.json().await.unwrap();
+    assert_eq!(body["code"], "read_only");
+    assert_eq!(body["status"], 503);
+
+    // Rejected writes are neither mirrored nor counted against the SLO
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mirrored = mirror.received_requests().await.unwrap();
+    assert!(mirrored.iter().all(|request| request.method.as_str() != "POST"));
+    let slo = app.state.config_watcher.get_config().await.slo;
+    assert_eq!(app.state.slo_tracker.status(&slo)[0].requests, 1);
+    assert_eq!(
+        metric_value(&app.scrape_metrics().await, "gateway_read_only_rejections_total", &[("method", "POST")]),
+        1.0
+    );
+
+    let status: Value = client
+        .post(app.url("/admin/read-only"))
+        .json(&json!({ "enabled": false }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["enabled"], false);
+    assert_eq!(post_user().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn exempt_routes_stay_writable() {
+    let mut config = base_config();
+    config.read_only.enabled = true;
+    config.read_only.exempt_routes = vec!["POST /api/v1/users".to_string()];
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .json(&new_user())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let status: Value = reqwest::get(app.url("/admin/read-only")).await.unwrap().json().await.unwrap();
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["exempt_routes"], json!(["POST /api/v1/users"]));
+}
+
+#[test]
+fn exemptions_must_be_route_selectors() {
+    let mut config = base_config();
+    config.read_only.exempt_routes = vec!["users".to_string(), "GET,POST /api/*".to_string()];
+    config.read_only.retry_after = "0s".parse().unwrap();
+    let fields: Vec<&str> = check(&config)
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "read_only")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, ["exempt_routes", "exempt_routes", "retry_after"]);
+}
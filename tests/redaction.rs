@@ -0,0 +1,145 @@
+mod common;
+
+use common::{base_config, logs, spawn_app};
+use project_gateway::{
+    config::AppConfig,
+    privacy::redaction::{Redactor, REDACTED},
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// The first log line containing every one of `markers`.
+fn log_line(markers: &[&str]) -> Option<String> {
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .find(|line| markers.iter().all(|marker| line.contains(marker)))
+        .map(str::to_string)
+}
+
+fn redacting_config() -> AppConfig {
+    logs();
+    let mut config = base_config();
+    let logging = &mut config.middleware.logging;
+    logging.enabled = true;
+    logging.include_request_body = true;
+    logging.include_headers = true;
+    logging.redact_json_fields = vec!["user.password".to_string(), "*.token".to_string()];
+    config
+}
+
+#[test]
+fn json_fields_are_redacted_by_path_and_other_bodies_are_left_alone() {
+    let redactor = Redactor::new(&redacting_config().middleware.logging);
+
+    let body = json!({
+        "user": { "name": "ada", "password": "hunter2" },
+        "sessions": [{ "token": "t-1" }, { "token": "t-2", "device": "phone" }],
+        "password": "top-level is not user.password",
+    })
+    .to_string();
+    let redacted: Value = serde_json::from_str(&redactor.body(&body)).unwrap();
+    assert_eq!(redacted["user"], json!({ "name": "ada", "password": REDACTED }));
+    assert_eq!(redacted["sessions"], json!([{ "token": REDACTED }, { "token": REDACTED, "device": "phone" }]));
+    assert_eq!(redacted["password"], "top-level is not user.password");
+
+    // Nothing to redact keeps the body byte for byte
+    let untouched = "{ \"user\": { \"name\": \"ada\" } }";
+    assert_eq!(redactor.body(untouched), untouched);
+    let text = "password=hunter2&token=t-1";
+    assert_eq!(redactor.body(text), text);
+
+    // A body cut short is redacted by the last key of each path
+    let cut = r#"{"user": {"password": "hunter2", "name": "ada"}, "sessions": [{"token": "t-1"#;
+    assert_eq!(
+        redactor.body(cut),
+        r#"{"user": {"password": "[REDACTED]", "name": "ada"}, "sessions": [{"token": "[REDACTED]""#
+    );
+}
+
+#[tokio::test]
+async fn access_log_scrubs_json_secrets_and_redacted_headers() {
+    let app = spawn_app(redacting_config()).await;
+    let client = reqwest::Client::new();
+
+    let body = json!({ "username": "json-body-test", "email": "redaction@example.com", "user": { "password": "nested-secret" } }).to_string();
+    let response = client
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header("User-Agent", "json-body-test")
+        .header("Content-Type", "application/json")
+        .header("Authorization", "Bearer header-secret")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let line = log_line(&["Request completed", "json-body-test"]).expect("access log line");
+    assert!(!line.contains("nested-secret") && !line.contains("header-secret"), "{}", line);
+    assert!(line.contains(r#"\"password\":\"[REDACTED]\""#), "{}", line);
+    assert!(line.contains(r#"\"authorization\":\"[REDACTED]\""#), "{}", line);
+
+    // Read whole by the config validator, whatever it makes of it
+    let body = "plain text with \"password\": \"kept-as-is\"";
+    let response = client
+        .post(app.url("/admin/config/validate"))
+        .header("X-Gateway-Version", "rust")
+        .header("User-Agent", "text-body-test")
+        .header("Content-Type", "text/plain")
+        .header("Cookie", "session=cookie-secret")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    response.bytes().await.unwrap();
+    let line = log_line(&["Request completed", "text-body-test"]).expect("access log line");
+    assert!(line.contains(&format!("request_body={:?}", body)), "{}", line);
+    assert!(!line.contains("cookie-secret"), "{}", line);
+    assert!(line.contains(r#"\"cookie\":\"[REDACTED]\""#), "{}", line);
+    assert!(line.contains(r#"\"user-agent\":\"text-body-test\""#), "{}", line);
+}
+
+#[tokio::test]
+async fn mirror_contract_violation_excerpts_are_redacted() {
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(r#"{"user": {"password": "mirror-secret"}, "marker": "mirror-excerpt"}"#, "text/html"),
+        )
+        .mount(&mirror)
+        .await;
+    let mut config = redacting_config();
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap();
+    route.expected_content_types = vec!["application/json".to_string()];
+    let app = spawn_app(config).await;
+
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    for _ in 0..100 {
+        if let Some(line) = log_line(&["violated the route contract", "mirror-excerpt"]) {
+            assert!(!line.contains("mirror-secret"), "{}", line);
+            assert!(line.contains("[REDACTED]"), "{}", line);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the mirror violation was never logged");
+}
+
+#[test]
+fn redaction_patterns_are_validated() {
+    let mut config = base_config();
+    config.middleware.logging.redact_headers = vec!["bad header".to_string()];
+    config.middleware.logging.redact_json_fields = vec!["user..password".to_string()];
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("redact_headers") && error.contains("redact_json_fields"), "{}", error);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::{base_config, debug_logs, spawn_app};
+use serde_json::Value;
+use tokio::net::TcpListener;
+use wiremock::{
+    matchers::{header, method},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn generated_ids_are_uuid_v7_and_reach_canary_error_bodies_and_spans() {
+    debug_logs();
+    // Bind and drop a listener so nothing answers on the port
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = format!("http://{}", closed);
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new().get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(response.status(), 502);
+    let echoed = response.headers()["x-request-id"].to_str().unwrap().to_string();
+    let generated = uuid::Uuid::parse_str(&echoed).unwrap();
+    assert_eq!(generated.get_version_num(), 7);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "upstream_unavailable");
+    assert_eq!(body["error"]["request_id"], echoed.as_str());
+
+    let logged = String::from_utf8_lossy(&debug_logs().0.lock().unwrap()).into_owned();
+    let span = format!("request_id=\"{}\"", echoed);
+    assert!(logged.lines().any(|line| line.contains(&span) && line.contains("uri=/api/v1/users")), "{}", logged);
+
+    // A usable client id is kept; an oversized one is replaced
+    let response = reqwest::Client::new()
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust")
+        .header("X-Request-Id", "client-chosen")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "client-chosen");
+    let response = reqwest::Client::new()
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust")
+        .header("X-Request-Id", "x".repeat(200))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"].len(), 36);
+}
+
+#[tokio::test]
+async fn the_configured_header_is_forwarded_to_legacy_and_the_mirror() {
+    debug_logs();
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("x-correlation-id", "corr-1"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&legacy)
+        .await;
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&mirror).await;
+    let mut config = base_config();
+    config.middleware.request_id.header = "X-Correlation-Id".to_string();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let app = spawn_app(config).await;
+
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Correlation-Id", "corr-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-correlation-id"], "corr-1");
+    assert!(response.headers().get("x-request-id").is_none());
+
+    for _ in 0..100 {
+        let received = mirror.received_requests().await.unwrap_or_default();
+        if let Some(request) = received.first() {
+            assert_eq!(request.headers["x-correlation-id"], "corr-1");
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("the request was never mirrored");
+}
+
+#[test]
+fn the_header_must_be_a_valid_name() {
+    let mut config = base_config();
+    config.middleware.request_id.header = "bad header".to_string();
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("middleware.request_id"), "{}", error);
+}
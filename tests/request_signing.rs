@@ -0,0 +1,117 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{ByteSize, SigningKey},
+    middleware::request_signing::{sign, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+};
+use serde_json::Value;
+
+const BODY: &str = r#"{"username":"signed","email":"signed@example.com"}"#;
+
+fn key(secret: &str) -> SigningKey {
+    SigningKey {
+        name: "billing-legacy".to_string(),
+        secret: secret.to_string(),
+        roles: vec!["admin".to_string()],
+    }
+}
+
+async fn signing_app() -> TestApp {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.request_signing.keys = vec![key("other-caller"), key("billing-secret")];
+    config.middleware.auth.request_signing.max_body_bytes = ByteSize::from_bytes(1024);
+    spawn_app(config).await
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+async fn post(app: &TestApp, signature: &str, timestamp: u64, body: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .header("content-type", "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn signed_requests_reach_the_handler_with_their_body() {
+    let app = signing_app().await;
+    let timestamp = now();
+    let signature = sign(&key("billing-secret"), "POST", "/api/v1/users", timestamp, BODY.as_bytes());
+
+    let response = post(&app, &signature, timestamp, BODY).await;
+    assert_eq!(response.status(), 200);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["user"]["username"], "signed");
+
+    // The same signature isn't accepted twice
+    assert_eq!(post(&app, &signature, timestamp, BODY).await.status(), 401);
+}
+
+#[tokio::test]
+async fn tampered_stale_and_unknown_signatures_are_rejected() {
+    let app = signing_app().await;
+    let timestamp = now();
+    let signature = sign(&key("billing-secret"), "POST", "/api/v1/users", timestamp, BODY.as_bytes());
+
+    let tampered = BODY.replace("signed@", "attacker@");
+    assert_eq!(post(&app, &signature, timestamp, &tampered).await.status(), 401);
+    assert_eq!(post(&app, &signature, timestamp + 1, BODY).await.status(), 401);
+
+    let stale = timestamp - 301;
+    let stale_signature = sign(&key("billing-secret"), "POST", "/api/v1/users", stale, BODY.as_bytes());
+    assert_eq!(post(&app, &stale_signature, stale, BODY).await.status(), 401);
+
+    let unknown = sign(&key("not-configured"), "POST", "/api/v1/users", timestamp, BODY.as_bytes());
+    assert_eq!(post(&app, &unknown, timestamp, BODY).await.status(), 401);
+    assert_eq!(post(&app, "not-hex", timestamp, BODY).await.status(), 401);
+
+    // Within the skew either way is fine
+    let late = timestamp - 240;
+    let late_signature = sign(&key("billing-secret"), "POST", "/api/v1/users", late, BODY.as_bytes());
+    assert_eq!(post(&app, &late_signature, late, BODY).await.status(), 200);
+}
+
+#[tokio::test]
+async fn bodies_over_the_limit_are_refused() {
+    let app = signing_app().await;
+    let timestamp = now();
+    let body = format!(r#"{{"username":"{}","email":"big@example.com"}}"#, "x".repeat(2048));
+    let signature = sign(&key("billing-secret"), "POST", "/api/v1/users", timestamp, body.as_bytes());
+    assert_eq!(post(&app, &signature, timestamp, &body).await.status(), 413);
+}
+
+#[tokio::test]
+async fn signed_callers_get_only_their_keys_roles() {
+    let mut config = base_config();
+    config.middleware.auth.enabled = true;
+    config.middleware.auth.request_signing.keys = vec![SigningKey {
+        roles: vec!["reader".to_string()],
+        ..key("billing-secret")
+    }];
+    let app = spawn_app(config).await;
+
+    let timestamp = now();
+    let signature = sign(&key("billing-secret"), "POST", "/api/v1/users", timestamp, BODY.as_bytes());
+    assert_eq!(post(&app, &signature, timestamp, BODY).await.status(), 403);
+
+    let signature = sign(&key("billing-secret"), "GET", "/api/v1/users?page=2", timestamp, b"");
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users?page=2"))
+        .header("X-Gateway-Version", "rust")
+        .header(SIGNATURE_HEADER, signature)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
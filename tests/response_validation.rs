@@ -0,0 +1,190 @@
+mod common;
+
+use axum::{body::Body, response::Response, Router};
+use common::{base_config, metric_value, spawn_app, TestApp};
+use futures::StreamExt;
+use project_gateway::config::{AppConfig, ByteSize, RouteConfig};
+use serde_json::Value;
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::net::TcpListener;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// Config that proxies everything to `legacy_url`, with `checks` applied to
+/// `GET /api/v1/users`.
+fn legacy_config(legacy_url: String, checks: impl FnOnce(&mut RouteConfig)) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy_url;
+    let route = config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap();
+    checks(route);
+    config
+}
+
+async fn upstream(template: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(template).mount(&server).await;
+    server
+}
+
+async fn get_users(app: &TestApp) -> (u16, Value) {
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+fn violations(scrape: &str, source: &str, kind: &str) -> f64 {
+    metric_value(
+        scrape,
+        "gateway_upstream_contract_violations_total",
+        &[("route", "/api/v1/users"), ("source", source), ("kind", kind)],
+    )
+}
+
+#[tokio::test]
+async fn unexpected_content_type_becomes_a_502() {
+    let legacy = upstream(
+        ResponseTemplate::new(200).set_body_raw("<html><body>Internal error</body></html>", "text/html; charset=utf-8"),
+    )
+    .await;
+    let app = spawn_app(legacy_config(legacy.uri(), |route| {
+        route.expected_content_types = vec!["application/json".to_string()];
+    }))
+    .await;
+
+    let (status, body) = get_users(&app).await;
+    assert_eq!(status, 502);
+    assert_eq!(body["error"]["code"], "upstream_contract_violation");
+    assert!(body["error"]["message"].as_str().unwrap().contains("text/html"));
+    assert!(violations(&app.scrape_metrics().await, "legacy", "content_type") >= 1.0);
+
+    // Routes without expectations relay the body untouched
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/health"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("Internal error"));
+}
+
+#[tokio::test]
+async fn matching_content_types_pass_through() {
+    let legacy = upstream(
+        ResponseTemplate::new(200).set_body_raw(r#"{"users":[]}"#, "application/problem+json"),
+    )
+    .await;
+    let app = spawn_app(legacy_config(legacy.uri(), |route| {
+        route.expected_content_types = vec!["application/json".to_string(), "application/*".to_string()];
+        route.strict_json = true;
+        route.max_response_bytes = Some(ByteSize::from_bytes(1024));
+    }))
+    .await;
+
+    let (status, body) = get_users(&app).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["users"], Value::Array(vec![]));
+}
+
+/// Legacy upstream that streams 1 KiB chunks without a content length,
+/// counting how many it managed to send.
+async fn endless_upstream() -> (String, Arc<AtomicUsize>) {
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = sent.clone();
+    let app = Router::new().fallback(move || {
+        let counter = counter.clone();
+        async move {
+            let chunks = futures::stream::iter(0..100_000).map(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Ok::<_, Infallible>(vec![b' '; 1024])
+            });
+            Response::builder()
+                .header("content-type", "application/json")
+                .body(Body::from_stream(chunks))
+                .unwrap()
+        }
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), sent)
+}
+
+#[tokio::test]
+async fn oversized_streams_are_aborted_mid_flight() {
+    let (legacy_url, sent) = endless_upstream().await;
+    let app = spawn_app(legacy_config(legacy_url, |route| {
+        route.max_response_bytes = Some("16KiB".parse().unwrap());
+    }))
+    .await;
+
+    let (status, body) = tokio::time::timeout(Duration::from_secs(10), get_users(&app))
+        .await
+        .expect("the gateway gives up instead of reading the whole stream");
+    assert_eq!(status, 502);
+    assert_eq!(body["error"]["code"], "upstream_contract_violation");
+    assert!(violations(&app.scrape_metrics().await, "legacy", "too_large") >= 1.0);
+
+    // The upstream stops once the gateway hangs up
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let after_abort = sent.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(sent.load(Ordering::Relaxed), after_abort);
+    assert!(after_abort < 100_000, "sent {} chunks", after_abort);
+}
+
+#[tokio::test]
+async fn malformed_json_fails_strict_routes() {
+    let legacy = upstream(
+        ResponseTemplate::new(200).set_body_raw(r#"{"users": [{"id": 1},"#, "application/json"),
+    )
+    .await;
+    let app = spawn_app(legacy_config(legacy.uri(), |route| route.strict_json = true)).await;
+
+    let (status, body) = get_users(&app).await;
+    assert_eq!(status, 502);
+    assert!(body["error"]["message"].as_str().unwrap().contains("not valid JSON"));
+    assert!(violations(&app.scrape_metrics().await, "legacy", "malformed_json") >= 1.0);
+}
+
+#[tokio::test]
+async fn mirror_violations_count_against_parity() {
+    let mirror = upstream(
+        ResponseTemplate::new(200).set_body_raw("<html></html>", "text/html"),
+    )
+    .await;
+    let mut config = legacy_config("http://127.0.0.1:9".to_string(), |route| {
+        route.expected_content_types = vec!["application/json".to_string()];
+    });
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.mirror.enabled = true;
+    config.mirror.base_url = mirror.uri();
+    let app = spawn_app(config).await;
+
+    let (status, _) = get_users(&app).await;
+    assert_eq!(status, 200);
+
+    let mut summary = None;
+    for _ in 0..100 {
+        summary = app.state.performance_monitor.mirror_summary();
+        if summary.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let summary = summary.expect("mirror outcome recorded");
+    assert_eq!(summary.contract_violation_rate, 100.0);
+    assert_eq!(summary.mismatch_rate, 0.0);
+    assert!(violations(&app.scrape_metrics().await, "mirror", "content_type") >= 1.0);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{base_config, logs, metric_value, spawn_app, TestApp};
+use project_gateway::config::HumanDuration;
+use serde_json::json;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// Captured lines containing `message` for requests to `path`.
+fn logged(message: &str, path: &str) -> Vec<String> {
+    let path = format!("path={}", path);
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .filter(|line| line.contains(message) && line.contains(&path))
+        .map(str::to_string)
+        .collect()
+}
+
+async fn app_at(rollout_percentage: f64, customize: impl FnOnce(&mut project_gateway::config::AppConfig)) -> TestApp {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.slow_start = HumanDuration::from_secs(0);
+    config.canary_rollout.generation_header = true;
+    customize(&mut config);
+    spawn_app(config).await
+}
+
+/// The generation stamped on a response from the Rust handler for `path`.
+async fn generation_of(app: &TestApp, path: &str) -> u64 {
+    let response = reqwest::Client::new()
+        .get(app.url(path))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    response.headers()["x-rollout-generation"].to_str().unwrap().parse().unwrap()
+}
+
+async fn admin(request: reqwest::RequestBuilder) {
+    let response = request.header("X-Gateway-Version", "rust").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn responses_before_and_after_a_change_carry_their_generation() {
+    logs();
+    let app = app_at(5.0, |_| {}).await;
+    let client = reqwest::Client::new();
+    assert_eq!(generation_of(&app, "/api/v1/users").await, 0);
+
+    admin(client.post(app.url("/admin/rollout/advance"))).await;
+    assert_eq!(generation_of(&app, "/api/v1/users").await, 1);
+
+    // Pausing is a change of state too
+    admin(client.put(app.url("/admin/rollout")).json(&json!({ "paused": true }))).await;
+    assert_eq!(generation_of(&app, "/api/v1/users").await, 2);
+
+    // The history maps each generation to the state it stood for
+    let state = app.state.coordinator.state().await;
+    let history: Vec<_> = state
+        .history
+        .iter()
+        .map(|entry| (entry.generation, entry.rollout_percentage, entry.paused))
+        .collect();
+    assert_eq!(history, [(0, 5.0, false), (1, 10.0, false), (2, 10.0, true)]);
+    assert!(state.history.iter().all(|entry| entry.updated_at > 0));
+    assert_eq!(state.generation(1).unwrap().updated_by, "gatekeeper");
+
+    let access = logged("Request completed", "/api/v1/users");
+    for generation in 0..3 {
+        let stamp = format!("rollout_generation={}", generation);
+        assert!(access.iter().any(|line| line.contains(&stamp)), "{} missing from {:?}", stamp, access);
+    }
+    let scrape = app.scrape_metrics().await;
+    assert!(metric_value(&scrape, "gateway_requests_total", &[("generation", "2")]) >= 1.0, "{}", scrape);
+
+    // The history is served with the rest of the rollout state
+    let status: serde_json::Value = client
+        .get(app.url("/admin/rollout"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["state"]["history"][1]["rollout_percentage"], 10.0);
+}
+
+#[tokio::test]
+async fn mirror_records_carry_the_generation() {
+    logs();
+    let mirror = MockServer::start().await;
+    Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&mirror).await;
+    let app = app_at(5.0, |config| {
+        config.mirror.enabled = true;
+        config.mirror.base_url = mirror.uri();
+    })
+    .await;
+
+    admin(reqwest::Client::new().post(app.url("/admin/rollout/advance"))).await;
+    assert_eq!(generation_of(&app, "/api/v1/health").await, 1);
+    for _ in 0..100 {
+        let records = logged("Mirror request completed", "\"/api/v1/health\"");
+        if let Some(record) = records.first() {
+            assert!(record.contains("rollout_generation=1"), "{}", record);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no mirror record for /api/v1/health");
+}
+
+#[tokio::test]
+async fn generation_header_is_opt_in() {
+    let app = app_at(5.0, |config| config.canary_rollout.generation_header = false).await;
+    let response = reqwest::Client::new()
+        .get(app.url("/api/v1/users"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get("x-rollout-generation").is_none());
+}
@@ -0,0 +1,242 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::RolloutOverride,
+    gatekeeper::{choose_scope, Gatekeeper, RollbackScope, SliceStats},
+};
+use serde_json::Value;
+use wiremock::{
+    matchers::{body_partial_json, method},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn slice(method: &str, route: &str, requests: u64, errors: u64) -> SliceStats {
+    SliceStats {
+        method: method.to_string(),
+        route: route.to_string(),
+        requests,
+        errors,
+    }
+}
+
+fn users() -> RollbackScope {
+    RollbackScope::Route {
+        method: "GET".to_string(),
+        route: "/api/v1/users".to_string(),
+    }
+}
+
+#[test]
+fn scope_narrows_to_the_one_route_that_regressed() {
+    let healthy = slice("GET", "/api/v1/health", 500, 1);
+    let regressed = slice("GET", "/api/v1/users", 40, 10);
+
+    assert_eq!(choose_scope(std::slice::from_ref(&healthy), 5.0, 20), None);
+    assert_eq!(choose_scope(&[healthy.clone(), regressed.clone()], 5.0, 20), Some(users()));
+    // Too few requests to blame the route on
+    assert_eq!(choose_scope(&[healthy.clone(), slice("GET", "/api/v1/users", 4, 4)], 5.0, 20), None);
+
+    // Several regressions, or failures elsewhere too, can't be pinned on one route
+    let also_regressed = slice("POST", "/api/v1/users", 30, 6);
+    assert_eq!(
+        choose_scope(&[healthy, regressed.clone(), also_regressed], 5.0, 20),
+        Some(RollbackScope::Global)
+    );
+    let failing_below_minimum = slice("GET", "/api/v1/health", 10, 4);
+    assert_eq!(choose_scope(&[regressed, failing_below_minimum], 5.0, 20), Some(RollbackScope::Global));
+}
+
+async fn rollout_app(rollout_percentage: f64, step: f64) -> (TestApp, MockServer) {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "served_by": "legacy" })))
+        .mount(&legacy)
+        .await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).mount(&legacy).await;
+
+    let mut config = base_config();
+    config.canary_rollout.enabled = true;
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.step = step;
+    config.canary_rollout.max_errors = 5.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.webhook_url = format!("{}/hooks/rollback", legacy.uri());
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    (spawn_app(config).await, legacy)
+}
+
+fn record(app: &TestApp, method: &str, route: &str, requests: u64, errors: u64) {
+    for n in 0..requests {
+        app.state.scoped_rollbacks.record(method, route, n < errors);
+    }
+}
+
+async fn served_by(app: &TestApp, path: &str) -> String {
+    let body: Value = reqwest::get(app.url(path)).await.unwrap().json().await.unwrap();
+    body["served_by"].as_str().unwrap_or("rust").to_string()
+}
+
+#[tokio::test]
+async fn isolated_regression_only_reduces_its_route() {
+    let (app, legacy) = rollout_app(100.0, 100.0).await;
+    record(&app, "GET", "/api/v1/health", 200, 0);
+    record(&app, "GET", "/api/v1/users", 40, 10);
+
+    let action = Gatekeeper::new(app.state.clone()).check_routes().await.unwrap();
+    assert_eq!(action.scope, users());
+    assert_eq!((action.from, action.to), (100.0, 0.0));
+    assert_eq!(app.state.config_watcher.get_config().await.canary_rollout.rollout_percentage, 100.0);
+
+    // Only the regressed route falls back to legacy
+    assert_eq!(served_by(&app, "/api/v1/users").await, "legacy");
+    assert_eq!(served_by(&app, "/api/v1/health").await, "rust");
+
+    let status: Value = reqwest::get(app.url("/gatekeeper/status")).await.unwrap().json().await.unwrap();
+    assert_eq!(status["current_rollout_percentage"], 100.0);
+    let reductions = status["scoped_rollbacks"].as_array().unwrap();
+    assert_eq!(reductions.len(), 1);
+    assert_eq!(reductions[0]["route"], "/api/v1/users");
+    assert_eq!(reductions[0]["percentage"], 0.0);
+
+    let alerts: Vec<Value> = legacy
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/hooks/rollback")
+        .map(|request| request.body_json().unwrap())
+        .collect();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0]["scope"], serde_json::json!({ "kind": "route", "method": "GET", "route": "/api/v1/users" }));
+    assert!(alerts[0]["text"].as_str().unwrap().contains("Scope: route GET /api/v1/users"));
+}
+
+#[tokio::test]
+async fn ambiguous_regressions_roll_back_globally() {
+    let (app, legacy) = rollout_app(50.0, 10.0).await;
+    Mock::given(body_partial_json(serde_json::json!({ "scope": { "kind": "global" } })))
+        .respond_with(ResponseTemplate::new(200))
+        .with_priority(1)
+        .expect(1)
+        .mount(&legacy)
+        .await;
+    record(&app, "GET", "/api/v1/users", 40, 10);
+    record(&app, "POST", "/api/v1/users", 40, 10);
+
+    let action = Gatekeeper::new(app.state.clone()).check_routes().await.unwrap();
+    assert_eq!(action.scope, RollbackScope::Global);
+    assert_eq!((action.from, action.to), (50.0, 40.0));
+    assert_eq!(app.state.config_watcher.get_config().await.canary_rollout.rollout_percentage, 40.0);
+    assert!(app.state.scoped_rollbacks.active().is_empty());
+
+    // Each check judges only what happened since the last one
+    assert!(Gatekeeper::new(app.state.clone()).check_routes().await.is_none());
+}
+
+#[tokio::test]
+async fn route_rollbacks_reduce_an_override_and_leave_the_global_knob() {
+    let (app, _legacy) = rollout_app(0.0, 100.0).await;
+    let mut config = app.state.config_watcher.get_config().await;
+    config.canary_rollout.route_overrides = vec![RolloutOverride {
+        path_prefix: "/api/v1/users".to_string(),
+        method: Some("GET".to_string()),
+        percentage: 100.0,
+    }];
+    app.state.config_watcher.apply(config.clone()).await;
+
+    assert_eq!(served_by(&app, "/api/v1/users").await, "rust");
+    assert_eq!(served_by(&app, "/api/v1/health").await, "legacy");
+    let metrics = app.scrape_metrics().await;
+    let requests = |group, variant| {
+        metric_value(&metrics, "gateway_route_group_requests_total", &[("route_group", group), ("variant", variant)])
+    };
+    assert_eq!(requests("GET /api/v1/users", "rust"), 1.0);
+    // Startup warmup and the other tests also count under default
+    assert!(requests("default", "legacy") >= 1.0);
+
+    let status: Value = reqwest::Client::new()
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["route_percentages"], serde_json::json!({ "GET /api/v1/users": 100.0 }));
+
+    record(&app, "GET", "/api/v1/health", 200, 0);
+    record(&app, "GET", "/api/v1/users", 40, 10);
+    let action = Gatekeeper::new(app.state.clone()).check_routes().await.unwrap();
+    assert_eq!(action.scope, users());
+    assert_eq!((action.from, action.to), (100.0, 0.0));
+    assert_eq!(app.state.config_watcher.get_config().await.canary_rollout.rollout_percentage, 0.0);
+    assert_eq!(served_by(&app, "/api/v1/users").await, "legacy");
+
+    // Lowering the override to the reduction releases it, cooldown or not
+    config.canary_rollout.route_overrides[0].percentage = 0.0;
+    app.state.config_watcher.apply(config).await;
+    assert!(Gatekeeper::new(app.state.clone()).check_routes().await.is_none());
+    assert!(app.state.scoped_rollbacks.active().is_empty());
+}
+
+#[tokio::test]
+async fn global_rollbacks_hold_overrides_to_the_global_percentage() {
+    let (app, _legacy) = rollout_app(100.0, 100.0).await;
+    let mut config = app.state.config_watcher.get_config().await;
+    config.canary_rollout.route_overrides = vec![
+        RolloutOverride {
+            path_prefix: "/api/v1/users".to_string(),
+            method: None,
+            percentage: 50.0,
+        },
+        RolloutOverride {
+            path_prefix: "/api/v1/payments".to_string(),
+            method: None,
+            percentage: 0.5,
+        },
+    ];
+    app.state.config_watcher.apply(config).await;
+    let percentage = |app: &TestApp, path: &'static str| {
+        let app = app.state.clone();
+        async move {
+            let config = app.config_watcher.get_config().await;
+            app.slow_start.route_percentage(&config.canary_rollout, "GET", path)
+        }
+    };
+    assert_eq!(percentage(&app, "/api/v1/users").await, 50.0);
+
+    // An operator lowering the rollout leaves the overrides alone
+    let mut config = app.state.config_watcher.get_config().await;
+    config.canary_rollout.rollout_percentage = 80.0;
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(percentage(&app, "/api/v1/users").await, 50.0);
+
+    Gatekeeper::new(app.state.clone()).force_rollback("manual").await;
+    let mut config = app.state.config_watcher.get_config().await;
+    assert_eq!(config.canary_rollout.rollout_percentage, 1.0);
+    assert_eq!(config.canary_rollout.override_hold, Some(1.0));
+    assert_eq!(app.state.coordinator.state().await.override_hold, Some(1.0));
+    assert_eq!(percentage(&app, "/api/v1/users").await, 1.0);
+    assert_eq!(percentage(&app, "/api/v1/payments").await, 0.5);
+    let status: Value = reqwest::Client::new()
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["route_percentages"]["/api/v1/users"], 1.0);
+
+    // The hold is part of the config written, so a restart keeps it
+    let restarted = spawn_app(config.clone()).await;
+    assert_eq!(percentage(&restarted, "/api/v1/users").await, 1.0);
+
+    // Raising the rollout again lifts the hold
+    config.canary_rollout.rollout_percentage = 10.0;
+    app.state.config_watcher.apply(config).await;
+    assert_eq!(percentage(&app, "/api/v1/users").await, 50.0);
+}
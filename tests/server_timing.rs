@@ -0,0 +1,145 @@
+mod common;
+
+use common::{base_config, logs, spawn_app};
+use project_gateway::{config::AppConfig, middleware::timing::TimingBreakdown};
+use std::{
+    collections::HashMap,
+    time::Duration,
+};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const UPSTREAM_DELAY: Duration = Duration::from_millis(200);
+
+/// Config that proxies everything to a legacy gateway answering after
+/// [`UPSTREAM_DELAY`], with `Server-Timing` switched on.
+async fn delayed_legacy() -> (MockServer, AppConfig) {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]").set_delay(UPSTREAM_DELAY))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.middleware.server_timing.enabled = true;
+    (legacy, config)
+}
+
+/// `Server-Timing` entries by name, in milliseconds.
+fn server_timing(response: &reqwest::Response) -> Option<HashMap<String, u64>> {
+    let header = response.headers().get("server-timing")?.to_str().unwrap();
+    Some(
+        header
+            .split(", ")
+            .map(|entry| {
+                let (name, duration) = entry.split_once(";dur=").unwrap();
+                (name.to_string(), duration.parse().unwrap())
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn header_lists_every_entry_and_coarsens_auth() {
+    let breakdown = TimingBreakdown {
+        gateway: Duration::from_micros(3_400),
+        upstream: Some(Duration::from_millis(281)),
+        auth: Duration::from_micros(14_900),
+        queue: Duration::ZERO,
+    };
+    assert_eq!(breakdown.header_value(), "gateway;dur=3, upstream;dur=281, auth;dur=10, queue;dur=0");
+
+    let local = TimingBreakdown {
+        upstream: None,
+        auth: Duration::from_micros(15_000),
+        ..breakdown
+    };
+    assert_eq!(local.header_value(), "gateway;dur=3, auth;dur=20, queue;dur=0");
+    assert_eq!(TimingBreakdown { auth: Duration::from_micros(4_000), ..local }.auth_ms(), 0);
+}
+
+#[tokio::test]
+async fn upstream_entry_tracks_the_proxied_call() {
+    let (_legacy, config) = delayed_legacy().await;
+    let app = spawn_app(config).await;
+
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let timing = server_timing(&response).expect("Server-Timing header");
+
+    let upstream = timing["upstream"];
+    let delay = UPSTREAM_DELAY.as_millis() as u64;
+    assert!((delay..delay + 150).contains(&upstream), "upstream took {}ms", upstream);
+    assert!(timing["gateway"] < delay, "{:?}", timing);
+    assert_eq!(timing["auth"], 0);
+    assert!(timing.contains_key("queue"));
+}
+
+#[tokio::test]
+async fn rust_handlers_report_no_upstream_entry() {
+    let mut config = base_config();
+    config.middleware.server_timing.enabled = true;
+    let app = spawn_app(config).await;
+
+    let response = reqwest::get(app.url("/health")).await.unwrap();
+    let timing = server_timing(&response).expect("Server-Timing header");
+    assert!(!timing.contains_key("upstream"), "{:?}", timing);
+    assert!(timing.contains_key("gateway"));
+}
+
+#[tokio::test]
+async fn header_is_absent_when_disabled_or_suppressed() {
+    let (_legacy, mut config) = delayed_legacy().await;
+    config.middleware.server_timing.enabled = false;
+    let app = spawn_app(config.clone()).await;
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    assert!(server_timing(&response).is_none());
+
+    // An external-facing route opts out while the rest keep the header
+    config.middleware.server_timing.enabled = true;
+    for route in config.routes.iter_mut().filter(|route| route.path == "/api/v1/users") {
+        route.server_timing = Some(false);
+    }
+    config.middleware.server_timing.exclude_paths = vec!["/gatekeeper".to_string()];
+    let app = spawn_app(config).await;
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    assert!(server_timing(&response).is_none());
+    let client = reqwest::Client::new();
+    let response = client
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert!(server_timing(&response).is_none());
+    let response = client
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert!(server_timing(&response).is_some());
+}
+
+#[tokio::test]
+async fn access_log_agrees_with_the_header() {
+    logs();
+    let (_legacy, config) = delayed_legacy().await;
+    let app = spawn_app(config).await;
+
+    let response = reqwest::get(app.url("/api/v1/users")).await.unwrap();
+    let timing = server_timing(&response).expect("Server-Timing header");
+
+    let logs = String::from_utf8_lossy(&logs().0.lock().unwrap()).into_owned();
+    let expected = format!(
+        "gateway_ms={} upstream_ms={} auth_ms={} queue_ms={}",
+        timing["gateway"], timing["upstream"], timing["auth"], timing["queue"]
+    );
+    assert!(
+        logs.lines().any(|line| line.contains("Request completed") && line.contains(&expected)),
+        "no access log line with {} in\n{}",
+        expected,
+        logs
+    );
+}
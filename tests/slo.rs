@@ -0,0 +1,164 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::{
+    config::{validation::check, LatencyObjective, Severity, SloConfig, SloObjective},
+    monitoring::slo::SloTracker,
+};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{
+    matchers::{body_partial_json, method},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn objective(name: &str, route: &str) -> SloObjective {
+    SloObjective {
+        name: name.to_string(),
+        method: None,
+        route: route.to_string(),
+        availability: Some(99.9),
+        latency: Some(LatencyObjective {
+            threshold: "100ms".parse().unwrap(),
+            percentile: 99.0,
+        }),
+        window: "30d".parse().unwrap(),
+    }
+}
+
+fn slo_config() -> SloConfig {
+    SloConfig {
+        objectives: vec![objective("users", "/api/v1/users*")],
+        ..SloConfig::default()
+    }
+}
+
+#[test]
+fn burn_rates_compare_bad_requests_with_the_budget() {
+    let config = slo_config();
+    let tracker = SloTracker::new();
+    let now = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+    let fast = Duration::from_millis(20);
+
+    // Two hours ago: 100 clean requests, outside the 1h window but inside 6h
+    let earlier = now - chrono::Duration::hours(2);
+    for _ in 0..100 {
+        tracker.record_at(&config, "GET", "/api/v1/users", 200, fast, earlier);
+    }
+    // Last minute: 10 of 100 failed, 5 more too slow
+    for i in 0..100 {
+        let (status, latency) = match i {
+            0..10 => (503, fast),
+            10..15 => (200, Duration::from_millis(250)),
+            _ => (200, fast),
+        };
+        tracker.record_at(&config, "GET", "/api/v1/users/42", status, latency, now);
+    }
+    // Not covered by the objective
+    tracker.record_at(&config, "GET", "/health", 500, fast, now);
+
+    let status = &tracker.status_at(&config, now)[0];
+    assert_eq!(status.requests, 200);
+    let availability = status.availability.as_ref().unwrap();
+    assert!((availability.burn_rate_1h - 100.0).abs() < 1e-6);
+    assert!((availability.burn_rate_6h - 50.0).abs() < 1e-6);
+    assert!((availability.achieved.unwrap() - 95.0).abs() < 1e-6);
+    assert!(!availability.compliant);
+    let latency = status.latency.as_ref().unwrap();
+    assert!((latency.burn_rate_1h - 5.0).abs() < 1e-6);
+    assert!((status.burn_rate_1h - 100.0).abs() < 1e-6);
+    assert!(status.fast_burn);
+
+    // An hour later the failures only count towards the 6h window
+    let later = now + chrono::Duration::minutes(61);
+    let status = &tracker.status_at(&config, later)[0];
+    assert_eq!(status.burn_rate_1h, 0.0);
+    assert!(status.burn_rate_6h > 0.0);
+    assert!(!status.fast_burn);
+}
+
+#[test]
+fn fast_burn_is_raised_once_until_it_clears() {
+    let config = slo_config();
+    let tracker = SloTracker::new();
+    let now = Utc::now();
+    for status in [200, 500] {
+        tracker.record_at(&config, "GET", "/api/v1/users", status, Duration::ZERO, now);
+    }
+
+    let raised = tracker.evaluate_at(&config, now);
+    assert_eq!(raised.len(), 1);
+    assert_eq!(tracker.fast_burning(), vec!["users".to_string()]);
+    assert!(tracker.evaluate_at(&config, now).is_empty());
+
+    tracker.evaluate_at(&config, now + chrono::Duration::hours(2));
+    assert!(tracker.fast_burning().is_empty());
+}
+
+#[tokio::test]
+async fn fast_burn_alerts_the_webhook_and_exports_burn_rates() {
+    let webhook = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "slo": "users" })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&webhook)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.canary_rollout.webhook_url = webhook.uri();
+    config.slo = slo_config();
+    config.slo.evaluation_interval = "50ms".parse().unwrap();
+    let app = spawn_app(config).await;
+
+    let client = reqwest::Client::new();
+    for path in ["/api/v1/users", "/api/v1/users/nobody"] {
+        client
+            .get(app.url(path))
+            .header("X-Gateway-Version", "rust")
+            .send()
+            .await
+            .unwrap();
+    }
+    // The Rust handlers don't fail on demand, so the failure is injected
+    let slo = app.state.config_watcher.get_config().await.slo;
+    app.state.slo_tracker.record(&slo, "GET", "/api/v1/users", 503, Duration::ZERO);
+    tokio::spawn(app.state.slo_tracker.clone().start(app.state.clone()));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let statuses: Value = client
+        .get(app.url("/monitoring/slo"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(statuses[0]["name"], "users");
+    assert_eq!(statuses[0]["requests"], 3);
+    assert_eq!(statuses[0]["fast_burn"], true);
+
+    let scrape = app.scrape_metrics().await;
+    assert!(metric_value(&scrape, "gateway_slo_burn_rate", &[("slo", "users"), ("window", "1h")]) >= 14.4);
+    webhook.verify().await;
+}
+
+#[test]
+fn objectives_need_a_name_and_a_valid_sli() {
+    let mut config = base_config();
+    let mut unnamed = objective("", "/api/*");
+    unnamed.availability = Some(100.0);
+    let mut empty = objective("empty", "/api/*");
+    empty.availability = None;
+    empty.latency = None;
+    config.slo.objectives = vec![objective("users", "/api/*"), objective("users", "/api/*"), unnamed, empty];
+    let messages: Vec<String> = check(&config)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "slo")
+        .map(|issue| issue.message)
+        .collect();
+    assert_eq!(messages.len(), 4, "{:?}", messages);
+}
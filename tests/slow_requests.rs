@@ -0,0 +1,59 @@
+mod common;
+
+use common::{base_config, logs, metric_value, spawn_app};
+use project_gateway::config::HumanDuration;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn slow_lines() -> Vec<String> {
+    String::from_utf8_lossy(&logs().0.lock().unwrap())
+        .lines()
+        .filter(|line| line.contains("WARN slow_request:"))
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn slow_requests_warn_with_route_backend_and_breakdown_until_turned_off() {
+    logs();
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(150)))
+        .mount(&legacy)
+        .await;
+    let mut config = base_config();
+    // The access log stays off; slow requests are warned about regardless
+    config.middleware.logging.enabled = false;
+    config.middleware.logging.slow_request_threshold = HumanDuration::from_millis(100);
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+
+    let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .get(app.url("/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let lines = slow_lines();
+    assert_eq!(lines.len(), 1, "{:?}", lines);
+    for field in ["route=\"/api/v1/users\"", "backend=\"legacy\"", "status=200", "threshold_ms=100", "upstream_ms="] {
+        assert!(lines[0].contains(field), "{} missing from {}", field, lines[0]);
+    }
+    let metrics = app.scrape_metrics().await;
+    assert_eq!(metric_value(&metrics, "gateway_slow_requests_total", &[("route", "/api/v1/users")]), 1.0);
+
+    // Reloading with 0 turns the warnings off
+    config.middleware.logging.slow_request_threshold = HumanDuration::from_millis(0);
+    app.state.config_watcher.apply(config).await;
+    let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(slow_lines().len(), 1);
+    let metrics = app.scrape_metrics().await;
+    assert_eq!(metric_value(&metrics, "gateway_slow_requests_total", &[("route", "/api/v1/users")]), 1.0);
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::{CanaryRolloutConfig, HumanDuration},
+    gatekeeper::{Gatekeeper, SlowStart},
+};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn canary(rollout_percentage: f64) -> CanaryRolloutConfig {
+    let mut config = base_config().canary_rollout;
+    config.rollout_percentage = rollout_percentage;
+    config
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramp_is_linear_from_old_to_new_stage() {
+    let slow_start = SlowStart::new();
+    let config = canary(25.0);
+    slow_start.begin(5.0, 25.0, Duration::from_secs(100));
+
+    // (seconds to advance, effective percentage afterwards)
+    for (step, expected) in [(0, 5.0), (25, 10.0), (25, 15.0), (40, 23.0)] {
+        tokio::time::advance(Duration::from_secs(step)).await;
+        let effective = slow_start.effective_percentage(&config);
+        assert!((effective - expected).abs() < 1e-9, "{} != {}", effective, expected);
+    }
+    assert_eq!(slow_start.status(&config).unwrap().elapsed_seconds, 90.0);
+
+    tokio::time::advance(Duration::from_secs(10)).await;
+    assert_eq!(slow_start.effective_percentage(&config), 25.0);
+    assert!(slow_start.status(&config).is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramp_only_applies_while_heading_to_the_configured_target() {
+    let slow_start = SlowStart::new();
+    slow_start.begin(5.0, 25.0, Duration::from_secs(100));
+    tokio::time::advance(Duration::from_secs(50)).await;
+
+    // A reload to some other percentage supersedes the ramp
+    assert_eq!(slow_start.effective_percentage(&canary(40.0)), 40.0);
+
+    // Beginning again towards the same target keeps the progress made
+    slow_start.begin(5.0, 25.0, Duration::from_secs(100));
+    assert_eq!(slow_start.effective_percentage(&canary(25.0)), 15.0);
+
+    // Zero duration switches at once
+    slow_start.begin(25.0, 50.0, Duration::ZERO);
+    assert_eq!(slow_start.effective_percentage(&canary(50.0)), 50.0);
+}
+
+async fn ramping_app(rollout_percentage: f64, step: f64) -> TestApp {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.step = step;
+    config.canary_rollout.slow_start = HumanDuration::from_secs(60);
+    spawn_app(config).await
+}
+
+/// Rust p99 twice the legacy baseline, optionally with a 10% error rate.
+fn record_degradation(app: &TestApp, with_errors: bool) {
+    let monitor = &app.state.performance_monitor;
+    for i in 0..100 {
+        monitor.record_request("legacy", 100.0, false);
+        monitor.record_request("rust", 200.0, with_errors && i % 10 == 0);
+    }
+    monitor.set_baseline(
+        monitor.get_current_metrics("rust").unwrap(),
+        monitor.get_current_metrics("legacy").unwrap(),
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn latency_judgments_are_suppressed_during_the_ramp() {
+    let app = ramping_app(5.0, 20.0).await;
+    let gatekeeper = Gatekeeper::new(app.state.clone());
+    record_degradation(&app, false);
+
+    assert!(gatekeeper.advance_rollout().await);
+    let status = gatekeeper.get_status().await;
+    assert_eq!(status.current_rollout_percentage, 25.0);
+    assert_eq!(status.effective_rollout_percentage, 5.0);
+    assert!(status.latency_degradation_percent > 10.0);
+    assert!(status.is_healthy, "latency must not count during slow-start");
+
+    tokio::time::advance(Duration::from_secs(30)).await;
+    let status = gatekeeper.get_status().await;
+    assert_eq!(status.effective_rollout_percentage, 15.0);
+    assert_eq!(status.slow_start.as_ref().unwrap().to_percentage, 25.0);
+    assert!(status.is_healthy);
+
+    tokio::time::advance(Duration::from_secs(30)).await;
+    let status = gatekeeper.get_status().await;
+    assert!(status.slow_start.is_none());
+    assert_eq!(status.effective_rollout_percentage, 25.0);
+    assert!(!status.is_healthy);
+    assert!(status.rollback_reason.unwrap().contains("Latency"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn error_rates_are_judged_during_the_ramp() {
+    let app = ramping_app(5.0, 20.0).await;
+    let gatekeeper = Gatekeeper::new(app.state.clone());
+    record_degradation(&app, true);
+
+    assert!(gatekeeper.advance_rollout().await);
+    let status = gatekeeper.get_status().await;
+    assert!(status.slow_start.is_some());
+    assert!(!status.is_healthy);
+    assert!(status.rollback_reason.unwrap().contains("Error rate"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rollback_cancels_the_ramp() {
+    let app = ramping_app(5.0, 20.0).await;
+    let gatekeeper = Gatekeeper::new(app.state.clone());
+
+    assert!(gatekeeper.advance_rollout().await);
+    tokio::time::advance(Duration::from_secs(45)).await;
+    assert_eq!(gatekeeper.get_status().await.effective_rollout_percentage, 20.0);
+
+    gatekeeper.force_rollback("test").await;
+    let status = gatekeeper.get_status().await;
+    assert!(status.slow_start.is_none());
+    // 20% effective minus a 20-point step, floored at 1%
+    assert_eq!(status.current_rollout_percentage, 1.0);
+    assert_eq!(status.effective_rollout_percentage, 1.0);
+}
+
+#[tokio::test]
+async fn canary_routing_uses_the_effective_percentage() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "served_by": "legacy" })))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+    // Freshly advanced from 0%: nothing reaches the Rust path yet
+    app.state.slow_start.begin(0.0, 100.0, Duration::from_secs(3600));
+
+    for _ in 0..5 {
+        let body: Value = reqwest::get(app.url("/api/v1/users")).await.unwrap().json().await.unwrap();
+        assert_eq!(body["served_by"], "legacy");
+    }
+
+    let status: Value = reqwest::Client::new()
+        .get(app.url("/gatekeeper/status"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["slow_start"]["to_percentage"], 100.0);
+    assert!(status["effective_rollout_percentage"].as_f64().unwrap() < 1.0);
+}
+
+#[tokio::test]
+async fn reloads_that_raise_the_percentage_start_a_ramp() {
+    let app = ramping_app(10.0, 10.0).await;
+    let mut config = app.state.config_watcher.get_config().await;
+
+    config.canary_rollout.rollout_percentage = 50.0;
+    app.state.config_watcher.apply(config.clone()).await;
+    let mut ramp = None;
+    for _ in 0..50 {
+        ramp = app.state.slow_start.status(&config.canary_rollout);
+        if ramp.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(ramp.expect("reload starts a ramp").from_percentage, 10.0);
+
+    config.canary_rollout.rollout_percentage = 30.0;
+    app.state.config_watcher.apply(config.clone()).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(app.state.slow_start.status(&config.canary_rollout).is_none());
+    assert_eq!(app.state.slow_start.effective_percentage(&config.canary_rollout), 30.0);
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    app::create_app,
+    config::{validation::check, AppConfig, Severity, SmokeCheck},
+};
+use serde_json::{json, Value};
+use std::{collections::HashMap, time::Duration};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+fn smoke(expected_status: u16) -> SmokeCheck {
+    SmokeCheck {
+        method: None,
+        path_params: HashMap::new(),
+        body: None,
+        expected_status,
+    }
+}
+
+/// Everything rolled out to Rust, with `smoke` guarding `GET /api/v1/users`.
+fn smoke_config(legacy: &MockServer, smoke: SmokeCheck) -> AppConfig {
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 100.0;
+    config.canary_rollout.slow_start = "0s".parse().unwrap();
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.webhook_url = String::new();
+    config.canary_rollout.smoke_retry_interval = "100ms".parse().unwrap();
+    config
+        .routes
+        .iter_mut()
+        .find(|route| route.path == "/api/v1/users" && route.method == "GET")
+        .unwrap()
+        .smoke = Some(smoke);
+    config
+}
+
+async fn start_smoke_checks(app: &TestApp) {
+    let router = create_app(app.state.clone()).await.unwrap();
+    tokio::spawn(app.state.smoke_gate.clone().start(app.state.clone(), router));
+}
+
+async fn list_users_route(app: &TestApp) -> Value {
+    let routes: Value = reqwest::Client::new()
+        .get(app.url("/admin/routes"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    routes
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|route| route["method"] == "GET" && route["path"] == "/api/v1/users")
+        .cloned()
+        .unwrap()
+}
+
+async fn wait_for_state(app: &TestApp, state: &str) -> Value {
+    for _ in 0..100 {
+        let route = list_users_route(app).await;
+        if route["smoke"]["state"] == state {
+            return route;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("smoke check never reached {}: {}", state, list_users_route(app).await);
+}
+
+/// Lists users without pinning a backend and returns the response body.
+async fn list_users(app: &TestApp) -> String {
+    reqwest::get(app.url("/api/v1/users")).await.unwrap().text().await.unwrap()
+}
+
+#[test]
+fn smoke_paths_fill_in_route_parameters() {
+    let mut check = smoke(200);
+    check.path_params = HashMap::from([("id".to_string(), "42".to_string())]);
+    assert_eq!(check.path("/api/v1/users/:id").unwrap(), "/api/v1/users/42");
+    assert_eq!(check.path("/api/v1/users/{id}/roles").unwrap(), "/api/v1/users/42/roles");
+    assert_eq!(check.path("/api/v1/orgs/:org/users/:id").unwrap_err(), "org");
+
+    let mut config = base_config();
+    config.routes[0].path = "/api/v1/orgs/:org".to_string();
+    config.routes[0].smoke = Some(check);
+    assert!(check_fails(&config, "smoke.path_params"));
+}
+
+fn check_fails(config: &AppConfig, field: &str) -> bool {
+    check(config)
+        .iter()
+        .any(|issue| issue.severity == Severity::Error && issue.section == "routes" && issue.field == field)
+}
+
+#[tokio::test]
+async fn failing_smoke_check_keeps_the_route_on_legacy_until_fixed() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("legacy"))
+        .mount(&legacy)
+        .await;
+    // The Rust handler answers 200, not the 201 the check expects
+    let mut config = smoke_config(&legacy, smoke(201));
+    config.canary_rollout.rollout_percentage = 0.0;
+    let app = spawn_app(config.clone()).await;
+    start_smoke_checks(&app).await;
+
+    // Nothing is checked while the route isn't eligible
+    let route = list_users_route(&app).await;
+    assert_eq!(route["smoke"]["state"], "pending");
+    assert_eq!(route["smoke"]["attempts"], 0);
+    assert_eq!(route["live"], false);
+
+    config.canary_rollout.rollout_percentage = 100.0;
+    app.state.config_watcher.apply(config.clone()).await;
+    let route = wait_for_state(&app, "failing").await;
+    assert_eq!(route["live"], false);
+    assert_eq!(route["smoke"]["expected_status"], 201);
+    assert_eq!(route["smoke"]["last_status"], 200);
+
+    for _ in 0..5 {
+        assert_eq!(list_users(&app).await, "legacy");
+    }
+    assert_eq!(legacy.received_requests().await.unwrap().len(), 5);
+
+    // Retries keep failing on their own interval
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let route = list_users_route(&app).await;
+    assert!(route["smoke"]["attempts"].as_u64().unwrap() >= 2, "{}", route);
+    let scrape = app.scrape_metrics().await;
+    let labels = [("method", "GET"), ("route", "/api/v1/users")];
+    assert!(metric_value(&scrape, "gateway_smoke_checks_total", &[labels[0], labels[1], ("result", "failed")]) >= 2.0);
+    assert_eq!(metric_value(&scrape, "gateway_route_live", &labels), 0.0);
+
+    // Once the check passes the route takes its rollout share
+    config.routes.iter_mut().find(|route| route.smoke.is_some()).unwrap().smoke = Some(smoke(200));
+    app.state.config_watcher.apply(config).await;
+    let route = wait_for_state(&app, "live").await;
+    assert_eq!(route["live"], true);
+    assert!(route["smoke"]["error"].is_null());
+
+    assert!(list_users(&app).await.contains("\"users\""));
+    assert_eq!(legacy.received_requests().await.unwrap().len(), 5);
+    assert_eq!(metric_value(&app.scrape_metrics().await, "gateway_route_live", &labels), 1.0);
+}
+
+#[tokio::test]
+async fn routes_without_smoke_checks_are_always_live() {
+    let legacy = MockServer::start().await;
+    let app = spawn_app(smoke_config(&legacy, smoke(200))).await;
+
+    let response = reqwest::Client::new()
+        .post(app.url("/api/v1/users"))
+        .json(&json!({ "username": "ada", "email": "ada@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(legacy.received_requests().await.unwrap().is_empty());
+
+    let routes: Value = reqwest::get(app.url("/admin/routes")).await.unwrap().json().await.unwrap();
+    let create_user = routes
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|route| route["method"] == "POST" && route["path"] == "/api/v1/users")
+        .unwrap();
+    assert_eq!(create_user["live"], true);
+    assert!(create_user["smoke"].is_null());
+}
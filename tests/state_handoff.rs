@@ -0,0 +1,145 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    app::create_app,
+    config::AppConfig,
+    gatekeeper::{Gatekeeper, ThresholdOverrides},
+    handoff::StateSnapshot,
+};
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn rollout_config(webhook: &MockServer, rollout_percentage: f64) -> AppConfig {
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).mount(webhook).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = rollout_percentage;
+    config.canary_rollout.step = 10.0;
+    config.canary_rollout.max_errors = 5.0;
+    config.canary_rollout.webhook_url = webhook.uri();
+    config
+}
+
+async fn export(app: &TestApp) -> StateSnapshot {
+    reqwest::Client::new()
+        .get(app.url("/admin/state/export"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn import(app: &TestApp, snapshot: &StateSnapshot) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url("/admin/state/import"))
+        .header("X-Gateway-Version", "rust")
+        .json(snapshot)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn in_cooldown(app: &TestApp) -> bool {
+    Gatekeeper::new(app.state.clone())
+        .evaluate(&ThresholdOverrides::default())
+        .await
+        .snapshot
+        .in_cooldown
+}
+
+#[tokio::test]
+async fn gatekeeper_and_rollout_state_carry_over() {
+    let webhook = MockServer::start().await;
+    let old = spawn_app(rollout_config(&webhook, 40.0).await).await;
+
+    // One route regresses and is rolled back on its own, starting the
+    // cooldown; an operator then lowers the rollout
+    for n in 0..200 {
+        old.state.scoped_rollbacks.record("GET", "/api/v1/health", false);
+        if n < 40 {
+            old.state.scoped_rollbacks.record("GET", "/api/v1/users", n < 10);
+        }
+    }
+    let action = Gatekeeper::new(old.state.clone()).check_routes().await.unwrap();
+    assert_eq!(action.to, 30.0);
+    old.state.coordinator.set_percentage(35.0, "operator").await;
+    for latency_ms in [10.0, 12.0, 14.0] {
+        old.state.performance_monitor.record_request("rust", latency_ms, false);
+        old.state.performance_monitor.record_request("legacy", latency_ms * 3.0, false);
+    }
+    let monitor = &old.state.performance_monitor;
+    monitor.set_baseline(monitor.get_current_metrics("rust").unwrap(), monitor.get_current_metrics("legacy").unwrap());
+    assert!(in_cooldown(&old).await);
+
+    let new = spawn_app(rollout_config(&webhook, 0.0).await).await;
+    assert!(!in_cooldown(&new).await);
+    let snapshot = export(&old).await;
+    let response = import(&new, &snapshot).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["restored"], serde_json::json!(["rollout", "gatekeeper", "baseline", "upstreams"]));
+
+    // The rollout, its generations and history, at the full percentage
+    // rather than ramping up to it
+    let rollout = new.state.coordinator.state().await;
+    assert_eq!(rollout, old.state.coordinator.state().await);
+    assert_eq!(rollout.history.len(), 2);
+    assert_eq!(new.state.coordinator.generation(), old.state.coordinator.generation());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let config = new.state.config_watcher.get_config().await;
+    assert_eq!(config.canary_rollout.rollout_percentage, 35.0);
+    assert!(new.state.slow_start.status(&config.canary_rollout).is_none());
+    assert_eq!(new.state.slow_start.effective_percentage(&config.canary_rollout), 35.0);
+
+    // The gatekeeper's cooldown and the route it held back
+    assert!(in_cooldown(&new).await);
+    assert_eq!(new.state.rollback_cooldown.last_rollback(), old.state.rollback_cooldown.last_rollback());
+    assert_eq!(
+        serde_json::to_value(new.state.scoped_rollbacks.active()).unwrap(),
+        serde_json::to_value(old.state.scoped_rollbacks.active()).unwrap()
+    );
+    assert_eq!(new.state.scoped_rollbacks.percentage_for("GET", "/api/v1/users", 35.0), 30.0);
+    let baseline = new.state.performance_monitor.get_baseline().unwrap();
+    assert_eq!(baseline.improvement_factor, monitor.get_baseline().unwrap().improvement_factor);
+
+    // Once ready, the instance may be taking traffic and no longer imports
+    let router = create_app(new.state.clone()).await.unwrap();
+    new.state.warmup.run(&new.state, &router).await;
+    assert_eq!(import(&new, &snapshot).await.status(), 409);
+}
+
+#[tokio::test]
+async fn incompatible_or_implausibly_timed_snapshots_are_refused() {
+    let webhook = MockServer::start().await;
+    let old = spawn_app(rollout_config(&webhook, 40.0).await).await;
+    old.state.coordinator.set_percentage(20.0, "operator").await;
+    let new = spawn_app(rollout_config(&webhook, 0.0).await).await;
+    let snapshot = export(&old).await;
+
+    let at = |offset: chrono::Duration| StateSnapshot {
+        exported_at: (chrono::Utc::now() + offset).to_rfc3339(),
+        ..snapshot.clone()
+    };
+    for (refused, expected) in [
+        (at(chrono::Duration::minutes(-10)), "handoff.max_snapshot_age"),
+        (at(chrono::Duration::minutes(5)), "handoff.max_clock_skew"),
+        (StateSnapshot { format: 2, ..snapshot.clone() }, "format 2"),
+    ] {
+        let response = import(&new, &refused).await;
+        assert_eq!(response.status(), 422);
+        let reason = response.text().await.unwrap();
+        assert!(reason.contains(expected), "{}", reason);
+    }
+    assert_eq!(new.state.coordinator.state().await.version, 0);
+
+    // Within the allowed skew, a little ahead is taken as now
+    let response = import(&new, &at(chrono::Duration::seconds(5))).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["age_seconds"], 0.0);
+    assert_eq!(new.state.coordinator.state().await.rollout_percentage, 20.0);
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use chrono::Datelike;
+use common::{base_config, metric_value};
+use project_gateway::{
+    app::create_app,
+    config::{watcher::ConfigWatcher, SniCertificateConfig, TlsConfig},
+    monitoring::PerformanceMonitor,
+    tls::{self, TlsManager},
+    AppState,
+};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    DigitallySignedStruct, SignatureScheme,
+};
+use serde_json::Value;
+use std::{net::SocketAddr, path::Path, sync::Arc};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsConnector;
+
+struct TestCert {
+    pem: String,
+    key_pem: String,
+    der: Vec<u8>,
+}
+
+fn self_signed(host: &str, expires_in_days: Option<i64>) -> TestCert {
+    let mut params = rcgen::CertificateParams::new(vec![host.to_string()]).unwrap();
+    if let Some(days) = expires_in_days {
+        let expiry = chrono::Utc::now() + chrono::Duration::days(days);
+        params.not_after = rcgen::date_time_ymd(expiry.year(), expiry.month() as u8, expiry.day() as u8);
+    }
+    let key = rcgen::KeyPair::generate().unwrap();
+    let cert = params.self_signed(&key).unwrap();
+    TestCert {
+        pem: cert.pem(),
+        key_pem: key.serialize_pem(),
+        der: cert.der().to_vec(),
+    }
+}
+
+fn write_cert(dir: &Path, name: &str, cert: &TestCert) -> (String, String) {
+    let cert_path = dir.join(format!("{}.crt", name));
+    let key_path = dir.join(format!("{}.key", name));
+    std::fs::write(&cert_path, &cert.pem).unwrap();
+    std::fs::write(&key_path, &cert.key_pem).unwrap();
+    (cert_path.display().to_string(), key_path.display().to_string())
+}
+
+struct Fixture {
+    dir: TempDir,
+    config: TlsConfig,
+}
+
+/// Default certificate plus one SNI entry each for a.example.com and
+/// b.example.com.
+fn fixture(certs: [&TestCert; 3]) -> Fixture {
+    let dir = TempDir::new().unwrap();
+    let (cert_path, key_path) = write_cert(dir.path(), "default", certs[0]);
+    let certificates = [("a", certs[1]), ("b", certs[2])]
+        .into_iter()
+        .map(|(name, cert)| {
+            let (cert_path, key_path) = write_cert(dir.path(), name, cert);
+            SniCertificateConfig {
+                sni_hosts: vec![format!("{}.example.com", name)],
+                cert_path,
+                key_path,
+            }
+        })
+        .collect();
+
+    Fixture {
+        dir,
+        config: TlsConfig {
+            enabled: true,
+            cert_path,
+            key_path,
+            certificates,
+            expiry_warning_days: 30,
+            reload_interval_seconds: 1,
+            client_auth: None,
+        },
+    }
+}
+
+/// Accepts any server certificate so the test can inspect which one was sent.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn connector() -> TlsConnector {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+async fn spawn_tls_app(manager: Arc<TlsManager>) -> SocketAddr {
+    let mut config = base_config();
+    config.canary_rollout.enabled = false;
+    let config_file = tempfile::NamedTempFile::new().unwrap();
+    let config_watcher = Arc::new(ConfigWatcher::new(config_file.path().to_str().unwrap(), config).unwrap());
+    let mut state = AppState::new(config_watcher, Arc::new(PerformanceMonitor::new())).await;
+    state.tls = Some(manager.clone());
+
+    let app = create_app(state).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let acceptor = manager.acceptor().unwrap();
+    tokio::spawn(async move {
+        let _config_file = config_file;
+        tls::serve(listener, app, acceptor).await.unwrap();
+    });
+    addr
+}
+
+/// Performs a handshake for `server_name` and returns the leaf certificate the
+/// server presented.
+async fn presented_cert(addr: SocketAddr, server_name: &str) -> Vec<u8> {
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let name = ServerName::try_from(server_name.to_string()).unwrap();
+    let stream = connector().connect(name, tcp).await.unwrap();
+    stream.get_ref().1.peer_certificates().unwrap()[0].to_vec()
+}
+
+#[tokio::test]
+async fn certificate_is_selected_by_sni() {
+    let (default, a, b) = (
+        self_signed("gateway.internal", None),
+        self_signed("a.example.com", None),
+        self_signed("b.example.com", None),
+    );
+    let fixture = fixture([&default, &a, &b]);
+    let manager = Arc::new(TlsManager::new(&fixture.config).unwrap());
+    let addr = spawn_tls_app(manager).await;
+
+    assert_eq!(presented_cert(addr, "a.example.com").await, a.der);
+    assert_eq!(presented_cert(addr, "B.Example.com").await, b.der);
+    assert_eq!(presented_cert(addr, "unknown.example.org").await, default.der);
+}
+
+#[tokio::test]
+async fn requests_are_served_over_tls() {
+    let certs = [
+        self_signed("gateway.internal", None),
+        self_signed("a.example.com", None),
+        self_signed("b.example.com", None),
+    ];
+    let fixture = fixture([&certs[0], &certs[1], &certs[2]]);
+    let addr = spawn_tls_app(Arc::new(TlsManager::new(&fixture.config).unwrap())).await;
+
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector()
+        .connect(ServerName::try_from("a.example.com").unwrap(), tcp)
+        .await
+        .unwrap();
+    stream
+        // HTTP/1.0 so the body isn't chunked
+        .write_all(b"GET /admin/tls HTTP/1.0\r\nHost: a.example.com\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+    let body: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    let names: Vec<&str> = body.as_array().unwrap().iter().map(|cert| cert["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["default", "a.example.com", "b.example.com"]);
+    assert!(body[1]["days_until_expiry"].as_i64().unwrap() > 30);
+}
+
+#[tokio::test]
+async fn reload_applies_good_entries_and_keeps_bad_ones_serving() {
+    let (default, a, b) = (
+        self_signed("gateway.internal", None),
+        self_signed("a.example.com", None),
+        self_signed("b.example.com", None),
+    );
+    let fixture = fixture([&default, &a, &b]);
+    let manager = Arc::new(TlsManager::new(&fixture.config).unwrap());
+    let addr = spawn_tls_app(manager.clone()).await;
+
+    // Rotate A and corrupt B
+    let rotated = self_signed("a.example.com", None);
+    write_cert(fixture.dir.path(), "a", &rotated);
+    std::fs::write(fixture.dir.path().join("b.crt"), "not a certificate").unwrap();
+    manager.refresh(&fixture.config);
+
+    assert_eq!(presented_cert(addr, "a.example.com").await, rotated.der);
+    assert_eq!(presented_cert(addr, "b.example.com").await, b.der);
+
+    let status = manager.certificates();
+    assert!(status[1].error.is_none());
+    assert!(status[2].error.as_deref().unwrap().contains("no certificate found"));
+
+    // Fixing B clears the error
+    write_cert(fixture.dir.path(), "b", &b);
+    manager.refresh(&fixture.config);
+    assert!(manager.certificates()[2].error.is_none());
+}
+
+#[tokio::test]
+async fn startup_fails_on_unusable_certificate() {
+    let certs = [
+        self_signed("gateway.internal", None),
+        self_signed("a.example.com", None),
+        self_signed("b.example.com", None),
+    ];
+    let mut fixture = fixture([&certs[0], &certs[1], &certs[2]]);
+    // A's key paired with B's certificate
+    fixture.config.certificates[0].key_path = fixture.config.certificates[1].key_path.clone();
+
+    let error = TlsManager::new(&fixture.config).err().expect("mismatched key should fail");
+    assert!(error.to_string().contains("a.example.com"), "{}", error);
+}
+
+#[tokio::test]
+async fn expiring_certificates_are_flagged() {
+    let (default, a, b) = (
+        self_signed("gateway.internal", None),
+        self_signed("expiring.example.com", Some(5)),
+        self_signed("b.example.com", None),
+    );
+    let mut fixture = fixture([&default, &a, &b]);
+    // Distinct names so the gauges don't collide with the other tests
+    fixture.config.certificates[0].sni_hosts = vec!["expiring.example.com".to_string()];
+    fixture.config.certificates[1].sni_hosts = vec!["fresh.example.com".to_string()];
+    // The recorder must be installed before the gauges are first set
+    let handle = project_gateway::metrics::install_recorder();
+    let manager = TlsManager::new(&fixture.config).unwrap();
+
+    let status = manager.certificates();
+    assert!(status[1].expiring_soon);
+    assert!(status[1].days_until_expiry.unwrap() <= 5);
+    assert!(!status[2].expiring_soon);
+
+    let scrape = handle.render();
+    assert_eq!(
+        metric_value(&scrape, "gateway_tls_certificate_expiring_soon", &[("name", "expiring.example.com")]),
+        1.0
+    );
+    assert_eq!(
+        metric_value(&scrape, "gateway_tls_certificate_expiring_soon", &[("name", "fresh.example.com")]),
+        0.0
+    );
+    assert!(metric_value(&scrape, "gateway_tls_certificate_expiry_days", &[("name", "expiring.example.com")]) <= 5.0);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app};
+use project_gateway::{
+    config::TraceSamplingConfig,
+    monitoring::trace_sampling::{decide, CompletedRequest, SampleReason, TraceSampler},
+};
+use std::time::{Duration, Instant};
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+fn sampling(ratio: f64) -> TraceSamplingConfig {
+    TraceSamplingConfig {
+        enabled: true,
+        ratio,
+        ..Default::default()
+    }
+}
+
+fn fast_ok() -> CompletedRequest {
+    CompletedRequest {
+        status: 200,
+        latency: Duration::from_millis(20),
+        legacy_routed: false,
+        since_rollout_change: None,
+    }
+}
+
+#[test]
+fn errors_are_always_kept_while_fast_successes_follow_the_ratio() {
+    let config = sampling(0.1);
+    let error = CompletedRequest { status: 503, ..fast_ok() };
+    for n in 0..1000 {
+        assert_eq!(decide(&error, &config, n as f64 / 1000.0), SampleReason::Error);
+    }
+
+    let kept = (0..1000)
+        .filter(|n| decide(&fast_ok(), &config, *n as f64 / 1000.0).is_sampled())
+        .count();
+    assert_eq!(kept, 100);
+    assert_eq!(decide(&fast_ok(), &sampling(0.0), 0.0), SampleReason::Dropped);
+}
+
+#[test]
+fn slow_and_recently_rerouted_legacy_requests_are_kept() {
+    let config = sampling(0.0);
+    let slow = CompletedRequest {
+        latency: Duration::from_secs(2),
+        ..fast_ok()
+    };
+    assert_eq!(decide(&slow, &config, 0.5), SampleReason::Slow);
+
+    let legacy = |since: Option<Duration>| CompletedRequest {
+        legacy_routed: true,
+        since_rollout_change: since,
+        ..fast_ok()
+    };
+    assert_eq!(decide(&legacy(Some(Duration::from_secs(60))), &config, 0.5), SampleReason::RolloutChange);
+    assert_eq!(decide(&legacy(Some(Duration::from_secs(3600))), &config, 0.5), SampleReason::Dropped);
+    assert_eq!(decide(&legacy(None), &config, 0.5), SampleReason::Dropped);
+
+    // A generation change starts the window; the first one seen doesn't
+    let sampler = TraceSampler::new();
+    let now = Instant::now();
+    assert_eq!(sampler.since_rollout_change(3, now), None);
+    assert_eq!(sampler.since_rollout_change(3, now + Duration::from_secs(5)), None);
+    assert_eq!(sampler.since_rollout_change(4, now + Duration::from_secs(10)), Some(Duration::ZERO));
+    assert_eq!(
+        sampler.since_rollout_change(4, now + Duration::from_secs(70)),
+        Some(Duration::from_secs(60))
+    );
+}
+
+#[tokio::test]
+async fn decisions_are_counted_by_reason() {
+    let legacy = MockServer::start().await;
+    Mock::given(any()).respond_with(ResponseTemplate::new(502)).mount(&legacy).await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.tracing.sampling = sampling(0.0);
+    let app = spawn_app(config).await;
+    let before = app.scrape_metrics().await;
+
+    let client = reqwest::Client::new();
+    for _ in 0..3 {
+        let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+        assert_eq!(response.status(), 502);
+    }
+    let health = client
+        .get(app.url("/api/v1/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(health.status(), 200);
+
+    let after = app.scrape_metrics().await;
+    let decisions = |scrape: &str, reason: &str| {
+        metric_value(scrape, "gateway_trace_sampling_decisions_total", &[("reason", reason)])
+    };
+    assert_eq!(decisions(&after, "error") - decisions(&before, "error"), 3.0);
+    // The health check, and the first scrape
+    assert_eq!(decisions(&after, "dropped") - decisions(&before, "dropped"), 2.0);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{
+    config::AppConfig,
+    upstream::health::{ProbeMethod, PROBE_USER_AGENT_SUFFIX, SYNTHETIC_HEADER},
+};
+use serde_json::Value;
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn probed_app(legacy: &MockServer) -> (TestApp, AppConfig) {
+    let mut config = base_config();
+    config.canary_rollout.enabled = true;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.mirror.enabled = false;
+    config.http_client.health_check.enabled = true;
+    let app = spawn_app(config.clone()).await;
+    (app, config)
+}
+
+async fn methods(server: &MockServer) -> Vec<String> {
+    let requests = server.received_requests().await.unwrap_or_default();
+    for request in &requests {
+        let user_agent = request.headers["user-agent"].to_str().unwrap();
+        assert!(user_agent.starts_with("project-gateway/") && user_agent.ends_with(PROBE_USER_AGENT_SUFFIX));
+        assert_eq!(request.headers[SYNTHETIC_HEADER], "health-probe");
+    }
+    requests.iter().map(|request| request.method.to_string()).collect()
+}
+
+#[tokio::test]
+async fn upstreams_refusing_head_are_probed_with_a_ranged_get_from_then_on() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/status"))
+        .respond_with(ResponseTemplate::new(405))
+        .mount(&legacy)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/status"))
+        .and(header("range", "bytes=0-0"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![b'x'; 4 * 1024 * 1024]))
+        .mount(&legacy)
+        .await;
+    let (app, config) = probed_app(&legacy).await;
+
+    for _ in 0..2 {
+        let probes = app.state.upstream_health.check_once(&app.state.upstreams, &config).await;
+        assert_eq!(probes.len(), 1);
+        assert!(probes[0].healthy);
+        assert_eq!(probes[0].method, ProbeMethod::Get);
+        assert!(!probes[0].head_supported);
+    }
+    // HEAD was tried once and not again
+    assert_eq!(methods(&legacy).await, ["HEAD", "GET", "GET"]);
+
+    let health: Value = reqwest::Client::new()
+        .get(app.url("/api/v1/health"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let upstreams = &health["upstream_services"];
+    assert_eq!(upstreams["status"], "healthy");
+    assert_eq!(upstreams["upstreams"][0]["name"], "legacy");
+    assert_eq!(upstreams["upstreams"][0]["method"], "get");
+    assert_eq!(upstreams["upstreams"][0]["head_supported"], false);
+    assert!(upstreams["upstreams"][0]["latency_ms"].as_f64().is_some());
+
+    // Probes never reach the rollout monitor or the legacy request metrics
+    assert!(app.state.performance_monitor.get_current_metrics("legacy").is_none());
+    let scraped = app.scrape_metrics().await;
+    assert!(!scraped.contains("gateway_upstream_seconds"));
+    assert!(scraped.contains("gateway_upstream_health_probes_total"));
+}
+
+#[tokio::test]
+async fn head_probes_report_failing_upstreams() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/status"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&legacy)
+        .await;
+    let (app, config) = probed_app(&legacy).await;
+
+    let probes = app.state.upstream_health.check_once(&app.state.upstreams, &config).await;
+    assert!(!probes[0].healthy);
+    assert_eq!(probes[0].method, ProbeMethod::Head);
+    assert_eq!(probes[0].status, Some(503));
+    assert!(probes[0].head_supported);
+    assert_eq!(methods(&legacy).await, ["HEAD"]);
+
+    let report = app.state.upstream_health.report(&config);
+    assert_eq!(report.len(), 1);
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use common::{base_config, spawn_app};
+use project_gateway::{
+    config::{HttpClientConfig, HumanDuration},
+    upstream::UpstreamPool,
+};
+use axum::{extract::ConnectInfo, routing::get, Router};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+async fn slow_upstream() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn saturated_pool_records_wait_and_pending() {
+    let upstream = slow_upstream().await;
+    let pool = Arc::new(UpstreamPool::new(&HttpClientConfig {
+        max_connections_per_host: 1,
+        ..Default::default()
+    }));
+
+    let mut requests = Vec::new();
+    for _ in 0..3 {
+        let pool = pool.clone();
+        let url = format!("{}/slow", upstream.uri());
+        requests.push(tokio::spawn(async move {
+            let permit = pool.acquire(&url).await;
+            pool.client().get(&url).send().await.unwrap();
+            permit.wait
+        }));
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let stats = pool.stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].in_use, 1);
+    assert_eq!(stats[0].pending, 2);
+
+    let mut waits = Vec::new();
+    for request in requests {
+        waits.push(request.await.unwrap());
+    }
+    waits.sort();
+    assert!(waits[2] >= Duration::from_millis(300), "{:?}", waits);
+
+    let stats = pool.stats();
+    assert_eq!(stats[0].in_use, 0);
+    assert_eq!(stats[0].pending, 0);
+    assert_eq!(stats[0].idle, 1);
+}
+
+#[tokio::test]
+async fn admin_endpoint_lists_legacy_pool() {
+    let legacy = slow_upstream().await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    client.get(app.url("/api/v1/users")).send().await.unwrap();
+
+    let pools: Value = client
+        .get(app.url("/admin/upstreams"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let legacy_key = project_gateway::upstream::upstream_key(&legacy.uri());
+    assert!(pools
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|pool| pool["upstream"] == legacy_key.as_str() && pool["idle"] == 1));
+}
+
+#[tokio::test]
+async fn queued_excess_gets_fast_503_instead_of_slow_timeout() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(1000)))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.http_client.max_connections_per_host = 1;
+    config.server.queue_timeout = HumanDuration::from_millis(100);
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    let first = tokio::spawn(client.get(app.url("/api/v1/users")).send());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let started = std::time::Instant::now();
+    let queued = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    let queued_for = started.elapsed();
+
+    assert_eq!(queued.status(), 503);
+    assert!(queued_for < Duration::from_millis(600), "took {:?}", queued_for);
+    let body: Value = queued.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "queued_too_long");
+
+    assert_eq!(first.await.unwrap().unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn legacy_calls_time_out_after_the_configured_deadline() {
+    let legacy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(1000)))
+        .mount(&legacy)
+        .await;
+
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.canary_rollout.legacy_timeout = Some(HumanDuration::from_millis(200));
+    let app = spawn_app(config.clone()).await;
+    let client = reqwest::Client::new();
+
+    let started = std::time::Instant::now();
+    let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    let took = started.elapsed();
+    assert_eq!(response.status(), 504);
+    assert!(took < Duration::from_millis(800), "took {:?}", took);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+    assert_eq!(body["error"]["message"], "Legacy gateway request timed out after 200ms");
+
+    // Without its own deadline the proxy follows server.timeout, reloads
+    // included
+    config.canary_rollout.legacy_timeout = None;
+    config.server.timeout = HumanDuration::from_millis(300);
+    app.state.config_watcher.apply(config.clone()).await;
+    let body: Value = client.get(app.url("/api/v1/users")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Legacy gateway request timed out after 300ms");
+
+    config.server.timeout = HumanDuration::from_secs(5);
+    app.state.config_watcher.apply(config.clone()).await;
+    assert_eq!(client.get(app.url("/api/v1/users")).send().await.unwrap().status(), 200);
+
+    // The deadline covers a body that trickles in after prompt headers
+    config.canary_rollout.legacy_gateway_url = trickling_upstream().await;
+    config.canary_rollout.legacy_timeout = Some(HumanDuration::from_millis(200));
+    app.state.config_watcher.apply(config).await;
+    let started = std::time::Instant::now();
+    let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    let took = started.elapsed();
+    assert_eq!(response.status(), 504);
+    assert!(took < Duration::from_millis(800), "took {:?}", took);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Legacy gateway request timed out after 200ms");
+}
+
+/// A legacy upstream that sends its headers and the start of its body at
+/// once, and the rest a second later.
+async fn trickling_upstream() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await;
+                let head = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 13\r\n\r\n";
+                socket.write_all(format!("{}{{\"users\":", head).as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let _ = socket.write_all(b" []}").await;
+            });
+        }
+    });
+    url
+}
+
+/// A legacy upstream that records the client port of every request, one
+/// per connection it accepted.
+async fn connection_counting_upstream() -> (String, Arc<Mutex<HashSet<u16>>>) {
+    let ports = Arc::new(Mutex::new(HashSet::new()));
+    let seen = ports.clone();
+    let router = Router::new().fallback(get(move |ConnectInfo(peer): ConnectInfo<SocketAddr>| async move {
+        seen.lock().unwrap().insert(peer.port());
+        "ok"
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
+    });
+    (url, ports)
+}
+
+#[tokio::test]
+async fn proxied_requests_reuse_pooled_connections_until_idle_timeout() {
+    let (legacy, connections) = connection_counting_upstream().await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy;
+    config.http_client.pool_idle_timeout = HumanDuration::from_millis(300);
+    let app = spawn_app(config).await;
+    let client = reqwest::Client::new();
+
+    for _ in 0..5 {
+        let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    assert_eq!(connections.lock().unwrap().len(), 1);
+
+    // A connection left unused past the idle timeout is replaced
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let response = client.get(app.url("/api/v1/users")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(connections.lock().unwrap().len(), 2);
+}
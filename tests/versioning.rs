@@ -0,0 +1,138 @@
+mod common;
+
+use common::{base_config, metric_value, spawn_app, TestApp};
+use project_gateway::{
+    config::{validation::check, ApiVersionConfig, AppConfig, Severity, VersionSource},
+    middleware::versioning::{negotiate, Negotiation},
+};
+use serde_json::{json, Value};
+
+fn version(name: &str) -> ApiVersionConfig {
+    ApiVersionConfig {
+        name: name.to_string(),
+        deprecated: None,
+        sunset: None,
+    }
+}
+
+fn versioned_config(precedence: VersionSource) -> AppConfig {
+    let mut config = base_config();
+    config.versioning.precedence = precedence;
+    config.versioning.versions = vec![version("v1"), version("v2")];
+    config
+}
+
+async fn get(app: &TestApp, path: &str, accept_version: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .get(app.url(path))
+        .header("X-Gateway-Version", "rust");
+    if let Some(version) = accept_version {
+        request = request.header("Accept-Version", version);
+    }
+    request.send().await.unwrap()
+}
+
+#[test]
+fn versions_come_from_the_path_header_or_default() {
+    let config = versioned_config(VersionSource::Path).versioning;
+    let version = |version: &str, path: &str| Negotiation::Version {
+        version: version.to_string(),
+        path: path.to_string(),
+    };
+    assert_eq!(negotiate(&config, "/api/v2/users", None), version("v2", "/api/v2/users"));
+    assert_eq!(negotiate(&config, "/api/users", Some("v2")), version("v2", "/api/v2/users"));
+    assert_eq!(negotiate(&config, "/api/users", None), version("v1", "/api/v1/users"));
+    assert_eq!(negotiate(&config, "/api/v1/users", Some("v2")), version("v1", "/api/v1/users"));
+    assert_eq!(negotiate(&config, "/api/v3/users", None), Negotiation::Unknown("v3".to_string()));
+    assert_eq!(negotiate(&config, "/api/versions", Some("v9")), Negotiation::Unversioned);
+    assert_eq!(negotiate(&config, "/apiary/v1", None), Negotiation::Unversioned);
+    assert_eq!(negotiate(&config, "/health", Some("v2")), Negotiation::Unversioned);
+
+    let mut config = versioned_config(VersionSource::Header).versioning;
+    assert_eq!(negotiate(&config, "/api/v1/users", Some("v2")), version("v2", "/api/v2/users"));
+    config.enabled = false;
+    assert_eq!(negotiate(&config, "/api/v9/users", None), Negotiation::Unversioned);
+}
+
+#[tokio::test]
+async fn path_selects_the_version() {
+    let app = spawn_app(versioned_config(VersionSource::Path)).await;
+
+    let response = get(&app, "/api/v1/users", Some("v2")).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("deprecation").is_none());
+    assert!(response.text().await.unwrap().contains("\"users\""));
+
+    // v2 has no routes of its own yet
+    assert_eq!(get(&app, "/api/v2/users", None).await.status(), 404);
+
+    let scrape = app.scrape_metrics().await;
+    assert!(metric_value(&scrape, "gateway_api_requests_total", &[("version", "v1"), ("lifecycle", "stable")]) >= 1.0);
+}
+
+#[tokio::test]
+async fn header_selects_the_version_for_unversioned_paths_or_when_it_takes_precedence() {
+    let app = spawn_app(versioned_config(VersionSource::Path)).await;
+    let response = get(&app, "/api/users?limit=1", Some("v1")).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("\"users\""));
+    assert_eq!(get(&app, "/api/users", Some("v2")).await.status(), 404);
+    assert_eq!(get(&app, "/api/users", None).await.status(), 200);
+
+    let app = spawn_app(versioned_config(VersionSource::Header)).await;
+    assert_eq!(get(&app, "/api/v1/users", Some("v2")).await.status(), 404);
+    assert_eq!(get(&app, "/api/v1/users", Some("v1")).await.status(), 200);
+}
+
+#[tokio::test]
+async fn deprecated_versions_announce_deprecation_and_sunset() {
+    let mut config = versioned_config(VersionSource::Path);
+    config.versioning.versions[0].deprecated = Some("2026-01-01".parse().unwrap());
+    config.versioning.versions[0].sunset = Some("2026-12-31".parse().unwrap());
+    let app = spawn_app(config).await;
+
+    let response = get(&app, "/api/v1/users", None).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["deprecation"], "@1767225600");
+    assert_eq!(response.headers()["sunset"], "Thu, 31 Dec 2026 00:00:00 GMT");
+
+    let versions: Value = get(&app, "/api/versions", None).await.json().await.unwrap();
+    assert_eq!(versions["default_version"], "v1");
+    assert_eq!(versions["precedence"], "path");
+    assert_eq!(
+        versions["versions"],
+        json!([
+            { "version": "v1", "status": "deprecated", "path_prefix": "/api/v1", "deprecated": "2026-01-01", "sunset": "2026-12-31" },
+            { "version": "v2", "status": "stable", "path_prefix": "/api/v2", "deprecated": null, "sunset": null },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn unknown_versions_are_not_acceptable() {
+    let app = spawn_app(versioned_config(VersionSource::Path)).await;
+
+    for (path, header) in [("/api/v3/users", None), ("/api/users", Some("v9"))] {
+        let response = get(&app, path, header).await;
+        assert_eq!(response.status(), 406);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unsupported_version");
+        assert_eq!(body["error"]["supported_versions"], json!(["v1", "v2"]));
+    }
+
+    let scrape = app.scrape_metrics().await;
+    assert!(metric_value(&scrape, "gateway_api_requests_total", &[("version", "unknown")]) >= 2.0);
+}
+
+#[test]
+fn default_version_must_be_configured() {
+    let mut config = versioned_config(VersionSource::Path);
+    config.versioning.default_version = "v3".to_string();
+    config.versioning.versions.push(version("latest"));
+    let fields: Vec<&str> = check(&config)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error && issue.section == "versioning")
+        .map(|issue| issue.field)
+        .collect();
+    assert_eq!(fields, vec!["default_version", "versions"]);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use axum::{body::Body, http::Request, Router};
+use common::{base_config, spawn_app, TestApp};
+use project_gateway::{app::create_app, config::AppConfig};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// Rollout at 0%, so unpinned requests are proxied to `legacy`.
+async fn legacy_config(legacy: &MockServer) -> AppConfig {
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("legacy"))
+        .mount(legacy)
+        .await;
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(legacy)
+        .await;
+    let mut config = base_config();
+    config.canary_rollout.rollout_percentage = 0.0;
+    config.canary_rollout.legacy_gateway_url = legacy.uri();
+    config.mirror.enabled = false;
+    config
+}
+
+async fn readiness(app: &TestApp) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(app.url("/readyz"))
+        .header("X-Gateway-Version", "rust")
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+/// Time to the response head of a request proxied to legacy, through the
+/// in-process router.
+async fn timed_request(router: &Router) -> Duration {
+    let request = Request::builder().uri("/api/v1/users").body(Body::empty()).unwrap();
+    let started = Instant::now();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let took = started.elapsed();
+    assert_eq!(response.status(), 200);
+    took
+}
+
+#[tokio::test]
+async fn warm_up_connects_upstreams_and_then_reports_ready() {
+    let legacy = MockServer::start().await;
+    let app = spawn_app(legacy_config(&legacy).await).await;
+
+    let (status, body) = readiness(&app).await;
+    assert_eq!(status, 503);
+    assert_eq!(body["ready"], false);
+
+    let router = create_app(app.state.clone()).await.unwrap();
+    let report = app.state.warmup.run(&app.state, &router).await;
+    let steps: Vec<&str> = report.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(steps, vec!["metrics", "metrics_selfcheck", "upstream_clients", "router"]);
+    assert_eq!(report.steps[1].detail, "2 metric families exported");
+    assert_eq!(report.steps[2].detail, "1 of 1 upstreams connected");
+    assert_eq!(report.steps[3].detail, "GET /health answered 200");
+
+    let (status, body) = readiness(&app).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["warmup"]["steps"].as_array().unwrap().len(), 4);
+
+    // The connection is opened ahead of time and left idle for the first request
+    let requests = legacy.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method.as_str(), "HEAD");
+    let pools = app.state.upstreams.stats();
+    let legacy_pool = pools.iter().find(|pool| pool.upstream == legacy.uri()).unwrap();
+    assert_eq!(legacy_pool.idle, 1);
+}
+
+#[tokio::test]
+async fn first_request_after_warm_up_costs_about_as_much_as_later_ones() {
+    let legacy = MockServer::start().await;
+    let config = legacy_config(&legacy).await;
+
+    // Without warm-up, for comparison; not asserted since other tests in
+    // this binary may already have paid the process-wide costs
+    let cold = spawn_app(config.clone()).await;
+    let cold_router = create_app(cold.state.clone()).await.unwrap();
+    let cold_first = timed_request(&cold_router).await;
+
+    let warm = spawn_app(config).await;
+    let router = create_app(warm.state.clone()).await.unwrap();
+    warm.state.warmup.run(&warm.state, &router).await;
+    let first = timed_request(&router).await;
+
+    let mut later = Vec::new();
+    for _ in 0..20 {
+        later.push(timed_request(&router).await);
+    }
+    later.sort();
+    let median = later[later.len() / 2];
+    eprintln!("cold first: {:?}, warm first: {:?}, median after: {:?}", cold_first, first, median);
+    assert!(
+        first <= median * 3 + Duration::from_millis(5),
+        "first request took {:?} against a median of {:?}",
+        first,
+        median
+    );
+}